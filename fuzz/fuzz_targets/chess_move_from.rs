@@ -0,0 +1,17 @@
+/*
+chess_move_from.rs
+Reads any text as a move. Reading must never panic, and a move that was read must read back the
+same once written.
+*/
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_chess::prelude::*;
+
+fuzz_target!(|text: &str| {
+    if let Ok(chess_move) = ChessMove::from(text) {
+        let written = chess_move.to_string();
+        assert_eq!(ChessMove::from(&written).as_ref(), Ok(&chess_move), "{text} was written as {written}");
+    }
+});
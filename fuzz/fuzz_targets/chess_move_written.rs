@@ -0,0 +1,14 @@
+/*
+chess_move_written.rs
+Writes arbitrary moves in SAN, which must read back as the same move.
+*/
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_chess::prelude::*;
+
+fuzz_target!(|chess_move: ChessMove| {
+    let written = chess_move.to_string();
+    assert_eq!(ChessMove::from(&written).as_ref(), Ok(&chess_move), "{written}");
+});
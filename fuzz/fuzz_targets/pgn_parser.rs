@@ -0,0 +1,24 @@
+/*
+pgn_parser.rs
+Reads any text as PGN. Reading must never panic, and games that were read must read back with the
+same tags and moves once written.
+*/
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_chess::prelude::*;
+
+fuzz_target!(|text: &str| {
+    let games = match parse_pgn_games(text) {
+        Ok(games) => games,
+        Err(_) => return,
+    };
+    for game in games {
+        let written = game.to_string();
+        let read = PgnGame::from(&written).unwrap_or_else(|e| panic!("the written game can't be read: {e}\n{written}"));
+        assert_eq!(read.to_string(), written);
+        // Replaying must not panic either, whatever the moves are.
+        game.positions().for_each(drop);
+    }
+});
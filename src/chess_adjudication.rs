@@ -0,0 +1,176 @@
+/*
+chess_adjudication.rs
+Adjudication of games between engines, so hopeless and dead drawn games don't have to be played
+out. It is set up in the config file and applies to watch, match and tournament games:
+
+resign_threshold = 600      the evaluation, in centipawns, a side has to be behind by...
+resign_moves = 4            ...in this many moves of each side in a row to lose, 0 for never
+draw_threshold = 10         the evaluation both sides have to stay within...
+draw_moves = 8              ...in this many moves of each side in a row to draw, 0 for never
+tablebase_adjudication = true
+max_game_moves = 200        moves after which the game is a draw, 0 for no limit
+
+There are no endgame tablebase files to probe, so tablebase adjudication settles the endings
+whose result is known without them: king and queen or rook against a bare king win, and two
+knights or a minor piece each against a king draw. Only engine evaluations count towards the
+thresholds, moves typed at the terminal leave the counts as they are.
+*/
+
+use crate::{
+    chess_common::ChessPiece,
+    chess_config::ChessConfig,
+    chess_core::{Board, BoardSquare, Team},
+    chess_pgn::PgnResult,
+    chess_uci::EngineScore,
+};
+
+// The adjudication settings of the config, a number of moves of 0 turning a rule off.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AdjudicationRules {
+    resign_threshold: u32,
+    resign_moves: u32,
+    draw_threshold: u32,
+    draw_moves: u32,
+    tablebase: bool,
+    max_game_moves: u32,
+}
+
+impl AdjudicationRules {
+    pub fn from_config(config: &ChessConfig) -> AdjudicationRules {
+        AdjudicationRules {
+            resign_threshold: config.get_resign_threshold(),
+            resign_moves: config.get_resign_moves(),
+            draw_threshold: config.get_draw_threshold(),
+            draw_moves: config.get_draw_moves(),
+            tablebase: config.get_tablebase_adjudication(),
+            max_game_moves: config.get_max_game_moves(),
+        }
+    }
+}
+
+// Follows a game's evaluations move by move for the rules.
+#[derive(Clone, Debug)]
+pub struct Adjudicator {
+    rules: AdjudicationRules,
+    // Evaluations in a row that were decisive, and for which side.
+    decisive: u32,
+    leader: Option<Team>,
+    // Evaluations in a row within the draw threshold.
+    level: u32,
+}
+
+impl Adjudicator {
+    pub fn new(rules: AdjudicationRules) -> Adjudicator {
+        Adjudicator { rules, decisive: 0, leader: None, level: 0 }
+    }
+
+    // The result of the game after a move, if the rules decide it. The score is the evaluation
+    // of the engine that played the move, from White's side.
+    pub fn adjudicate(&mut self, board: &Board, score: Option<EngineScore>) -> Option<(PgnResult, &'static str)> {
+        if let Some(score) = score {
+            let cp = score.to_centipawns();
+            let leader = match cp {
+                cp if cp >= self.rules.resign_threshold as i32 => Some(Team::Light),
+                cp if cp <= -(self.rules.resign_threshold as i32) => Some(Team::Dark),
+                _ => None,
+            };
+            self.decisive = if leader.is_some() && leader == self.leader { self.decisive + 1 } else { leader.is_some() as u32 };
+            self.leader = leader;
+            self.level = if cp.unsigned_abs() <= self.rules.draw_threshold { self.level + 1 } else { 0 };
+
+            // Both engines get to judge the position, so a move of each side counts as a move.
+            if self.rules.resign_moves > 0 && self.decisive >= self.rules.resign_moves * 2 {
+                let result = match self.leader {
+                    Some(Team::Light) => PgnResult::WhiteWin,
+                    _ => PgnResult::BlackWin,
+                };
+                return Some((result, "adjudication, the evaluation stayed decisive"));
+            }
+            if self.rules.draw_moves > 0 && self.level >= self.rules.draw_moves * 2 {
+                return Some((PgnResult::Draw, "adjudication, the evaluation stayed level"));
+            }
+        }
+        if self.rules.tablebase {
+            if let Some(result) = known_ending(board) {
+                return Some((result, "tablebase adjudication"));
+            }
+        }
+        let max = self.rules.max_game_moves;
+        if max > 0 && board.get_fullmove_number() > max && board.get_turn() == Team::Light {
+            return Some((PgnResult::Draw, "adjudication at the move limit"));
+        }
+        None
+    }
+}
+
+// The result of an elementary ending, when nothing can be taken right away to change it.
+fn known_ending(board: &Board) -> Option<PgnResult> {
+    let mut pieces = [Vec::new(), Vec::new()];
+    for square in BoardSquare::all() {
+        if let Some(piece) = board.get_piece_at(square) {
+            if piece.get_piece_type() != ChessPiece::King {
+                pieces[(*piece.get_team() == Team::Dark) as usize].push(piece.get_piece_type());
+            }
+        }
+    }
+    let capture = board.legal_moves().iter().any(|m| board.get_piece_at(m.get_to()).is_some());
+    if capture {
+        return None;
+    }
+    let minor = |p: &ChessPiece| matches!(p, ChessPiece::Knight | ChessPiece::Bishop);
+    match (pieces[0].as_slice(), pieces[1].as_slice()) {
+        ([ChessPiece::Queen | ChessPiece::Rook], []) => Some(PgnResult::WhiteWin),
+        ([], [ChessPiece::Queen | ChessPiece::Rook]) => Some(PgnResult::BlackWin),
+        ([ChessPiece::Knight, ChessPiece::Knight], []) | ([], [ChessPiece::Knight, ChessPiece::Knight]) => Some(PgnResult::Draw),
+        ([a], [b]) if minor(a) && minor(b) => Some(PgnResult::Draw),
+        _ => None,
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_adjudication {
+    use super::*;
+
+    fn rules(settings: &[(&str, &str)]) -> AdjudicationRules {
+        let mut config = ChessConfig::new();
+        for (setting, value) in settings {
+            config.set(setting, value).unwrap();
+        }
+        AdjudicationRules::from_config(&config)
+    }
+
+    #[test]
+    pub fn evaluations_decide_games_after_enough_moves() {
+        let board = Board::new();
+        let mut adjudicator = Adjudicator::new(rules(&[("resign_threshold", "500"), ("resign_moves", "2")]));
+        let scores = [-600, -700, 100, -650, -900, -800, EngineScore::Mate(-3).to_centipawns()];
+        let results: Vec<_> = scores.iter().map(|cp| adjudicator.adjudicate(&board, Some(EngineScore::Centipawns(*cp)))).collect();
+        assert!(results[..6].iter().all(|r| r.is_none()));
+        assert_eq!(results[6], Some((PgnResult::BlackWin, "adjudication, the evaluation stayed decisive")));
+
+        let mut adjudicator = Adjudicator::new(rules(&[("draw_threshold", "10"), ("draw_moves", "1")]));
+        assert_eq!(adjudicator.adjudicate(&board, Some(EngineScore::Centipawns(5))), None);
+        // Moves without an evaluation neither count nor break the run.
+        assert_eq!(adjudicator.adjudicate(&board, None), None);
+        assert_eq!(adjudicator.adjudicate(&board, Some(EngineScore::Centipawns(-10))),
+            Some((PgnResult::Draw, "adjudication, the evaluation stayed level")));
+
+        let mut adjudicator = Adjudicator::new(rules(&[("max_game_moves", "40")]));
+        let late = Board::from_fen("r3k3/8/8/8/8/8/8/4K2R w - - 0 41").unwrap();
+        assert_eq!(adjudicator.adjudicate(&late, None), Some((PgnResult::Draw, "adjudication at the move limit")));
+    }
+
+    #[test]
+    pub fn known_endings_are_settled() {
+        let mut adjudicator = Adjudicator::new(rules(&[("tablebase_adjudication", "on")]));
+        let mut ending = |fen: &str| adjudicator.adjudicate(&Board::from_fen(fen).unwrap(), None).map(|(r, _)| r);
+        assert_eq!(ending("8/8/4k3/8/8/3K4/8/7R b - - 0 60"), Some(PgnResult::WhiteWin));
+        assert_eq!(ending("8/8/4k3/8/8/3K4/8/4q3 w - - 0 60"), Some(PgnResult::BlackWin));
+        assert_eq!(ending("8/8/4k3/2n5/8/3K4/8/7N w - - 0 60"), Some(PgnResult::Draw));
+        // The king can take the rook.
+        assert_eq!(ending("8/8/4k3/4R3/8/3K4/8/8 b - - 0 60"), None);
+        assert_eq!(ending("8/8/4k3/8/8/3K4/8/6RR w - - 0 60"), None);
+    }
+}
@@ -0,0 +1,373 @@
+/*
+chess_annotate.rs
+Annotation of whole games by an engine. Every position of a game is searched and each move gets
+the evaluation after it as a %eval comment. Moves that cost the player winning chances against
+the engine's best are marked as inaccuracies, mistakes or blunders together with the better line,
+and each player's accuracy sums up how much of their chances their moves kept, on the scale
+lichess uses.
+*/
+
+use std::fmt::Display;
+use crate::{
+    chess_core::{Board, BoardMove, Team},
+    chess_game::GameState,
+    chess_pgn::{PgnAnnotation, PgnGame, suffix_annotation_nag},
+    chess_uci::{EngineScore, SearchResult, UciError},
+};
+
+// Evaluations beyond this decide the game, more doesn't change the winning chances.
+const DECISIVE_CENTIPAWNS: i32 = 1000;
+// Winning chances, in percent, a move has to lose to be an inaccuracy, a mistake or a blunder.
+const INACCURACY_LOSS: f64 = 5.0;
+const MISTAKE_LOSS: f64 = 10.0;
+const BLUNDER_LOSS: f64 = 15.0;
+// Moves of the better line given after an inaccuracy, mistake or blunder.
+const BETTER_LINE_MOVES: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveJudgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+// How one side played over a game.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerSummary {
+    moves: u32,
+    accuracy_sum: f64,
+    inaccuracies: u32,
+    mistakes: u32,
+    blunders: u32,
+}
+
+pub struct AnnotatedGame {
+    game: PgnGame,
+    white: PlayerSummary,
+    black: PlayerSummary,
+}
+
+impl Display for MoveJudgement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            MoveJudgement::Inaccuracy => "Inaccuracy",
+            MoveJudgement::Mistake => "Mistake",
+            MoveJudgement::Blunder => "Blunder",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl MoveJudgement {
+    // Judges a move by the winning chances, in percent, it lost against the best move.
+    pub fn from(lost: f64) -> Option<MoveJudgement> {
+        if lost >= BLUNDER_LOSS {
+            Some(MoveJudgement::Blunder)
+        }
+        else if lost >= MISTAKE_LOSS {
+            Some(MoveJudgement::Mistake)
+        }
+        else if lost >= INACCURACY_LOSS {
+            Some(MoveJudgement::Inaccuracy)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn get_suffix(&self) -> &'static str {
+        match self {
+            MoveJudgement::Inaccuracy => "?!",
+            MoveJudgement::Mistake => "?",
+            MoveJudgement::Blunder => "??",
+        }
+    }
+}
+
+impl Display for PlayerSummary {
+    // e.g. "accuracy 87%, 2 inaccuracies, 1 mistake, no blunders"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = |n: u32, one: &str, many: &str| match n {
+            0 => format!("no {many}"),
+            1 => format!("1 {one}"),
+            n => format!("{n} {many}"),
+        };
+        match self.get_accuracy() {
+            Some(accuracy) => write!(f, "accuracy {:.0}%, ", accuracy)?,
+            None => write!(f, "no moves, ")?,
+        }
+        write!(f, "{}, {}, {}", count(self.inaccuracies, "inaccuracy", "inaccuracies"),
+            count(self.mistakes, "mistake", "mistakes"), count(self.blunders, "blunder", "blunders"))
+    }
+}
+
+impl PlayerSummary {
+    fn add_move(&mut self, accuracy: f64, judgement: Option<MoveJudgement>) {
+        self.moves += 1;
+        self.accuracy_sum += accuracy;
+        match judgement {
+            Some(MoveJudgement::Inaccuracy) => self.inaccuracies += 1,
+            Some(MoveJudgement::Mistake) => self.mistakes += 1,
+            Some(MoveJudgement::Blunder) => self.blunders += 1,
+            None => (),
+        }
+    }
+
+    // Adds the moves of another summary, e.g. to sum up a player over several games.
+    pub fn merge(&mut self, other: &PlayerSummary) {
+        self.moves += other.moves;
+        self.accuracy_sum += other.accuracy_sum;
+        self.inaccuracies += other.inaccuracies;
+        self.mistakes += other.mistakes;
+        self.blunders += other.blunders;
+    }
+
+    // The average accuracy of the player's moves in percent, None before their first move.
+    pub fn get_accuracy(&self) -> Option<f64> {
+        match self.moves {
+            0 => None,
+            moves => Some(self.accuracy_sum / moves as f64),
+        }
+    }
+
+    pub fn get_moves(&self) -> u32 {
+        self.moves
+    }
+
+    pub fn get_inaccuracies(&self) -> u32 {
+        self.inaccuracies
+    }
+
+    pub fn get_mistakes(&self) -> u32 {
+        self.mistakes
+    }
+
+    pub fn get_blunders(&self) -> u32 {
+        self.blunders
+    }
+}
+
+impl AnnotatedGame {
+    pub fn get_game(&self) -> &PgnGame {
+        &self.game
+    }
+
+    pub fn get_summary(&self, team: Team) -> &PlayerSummary {
+        match team {
+            Team::Light => &self.white,
+            Team::Dark => &self.black,
+        }
+    }
+}
+
+// Annotates a game with an engine. `search` is given each position of the game that has moves,
+// along with the moves leading to it, and returns the engine's result. The annotations of the
+// game are kept, and the annotator is named in the Annotator tag.
+pub fn annotate_game(
+    state: &GameState,
+    annotator: &str,
+    mut search: impl FnMut(&Board, &[BoardMove]) -> Result<Option<SearchResult>, UciError>,
+) -> Result<AnnotatedGame, UciError> {
+    let moves = state.get_board_moves();
+    let mut boards = vec![Board::new()];
+    for board_move in &moves {
+        let mut board = boards[boards.len() - 1].clone();
+        board.make_move(*board_move);
+        boards.push(board);
+    }
+    let mut results = Vec::new();
+    for (ply, board) in boards.iter().enumerate() {
+        let result = match board.legal_moves().is_empty() {
+            true => None,
+            false => search(board, &moves[..ply])?,
+        };
+        results.push(result);
+    }
+
+    let mut game = state.get_pgn_game().clone();
+    game.clear_moves();
+    let mut white = PlayerSummary::default();
+    let mut black = PlayerSummary::default();
+    let plies = state.get_pgn_game().get_plies();
+    for (ply, (chess_move, annotation)) in plies.into_iter().enumerate() {
+        let (before, after) = (&boards[ply], &boards[ply + 1]);
+        let score_before = position_score(before, &results[ply]);
+        let score_after = position_score(after, &results[ply + 1]);
+        // Both scores are from the side to move, the one after the move is the opponent's.
+        let lost = (win_percent(score_before) - (100.0 - win_percent(score_after))).max(0.0);
+        let accuracy = move_accuracy(lost);
+        let best = results[ply].as_ref().filter(|r| r.get_best_move() != moves[ply]);
+        let judgement = best.and_then(|_| MoveJudgement::from(lost));
+
+        let mut annotation = annotation.clone();
+        if results[ply + 1].is_some() {
+            let white_score = if after.get_turn() == Team::Dark { score_after.negate() } else { score_after };
+            annotation.add_comment(format!("[%eval {}]", eval_text(white_score)));
+        }
+        if let (Some(judgement), Some(best)) = (judgement, best) {
+            add_judgement(&mut annotation, judgement, before, best);
+        }
+        match before.get_turn() {
+            Team::Light => white.add_move(accuracy, judgement),
+            Team::Dark => black.add_move(accuracy, judgement),
+        }
+        game.push_annotated_move(chess_move.clone(), annotation);
+    }
+
+    game.set_tag("Annotator", String::from(annotator));
+    game.add_initial_comment(format!("White: {white}. Black: {black}."));
+    Ok(AnnotatedGame { game, white, black })
+}
+
+// How one side played by the %eval comments already in a game, as annotate or a site left them,
+// each move judged by the winning chances lost from the evaluation before it. Moves without an
+// evaluation before and after them are left out.
+pub fn summarize_evals(game: &PgnGame, team: Team) -> PlayerSummary {
+    let mut summary = PlayerSummary::default();
+    let mut before = None;
+    for (ply, (_, annotation)) in game.get_plies().into_iter().enumerate() {
+        let after = annotation.get_comments().iter().find_map(|c| read_eval(c));
+        let mover = if ply % 2 == 0 { Team::Light } else { Team::Dark };
+        if let (true, Some(before), Some(after)) = (mover == team, before, after) {
+            let from_mover = |score: EngineScore| if mover == Team::Dark { score.negate() } else { score };
+            // #0 is written after checkmate, which the mover gave.
+            let lost = match after {
+                EngineScore::Mate(0) => 0.0,
+                after => (win_percent(from_mover(before)) - win_percent(from_mover(after))).max(0.0),
+            };
+            summary.add_move(move_accuracy(lost), MoveJudgement::from(lost));
+        }
+        before = after;
+    }
+    summary
+}
+
+// How much of their chances a move kept, in percent, on the scale lichess uses.
+fn move_accuracy(lost: f64) -> f64 {
+    (103.1668 * (-0.04354 * lost).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+// The evaluation of a %eval comment, from White's side, e.g. [%eval 0.35] or [%eval #-2].
+pub fn read_eval(comment: &str) -> Option<EngineScore> {
+    let start = comment.find("[%eval ")? + "[%eval ".len();
+    let text = comment[start..].split([']', ',', ' ']).next()?;
+    match text.strip_prefix('#') {
+        Some(moves) => moves.parse().ok().map(EngineScore::Mate),
+        None => text.parse::<f64>().ok().map(|pawns| EngineScore::Centipawns((pawns * 100.0).round() as i32)),
+    }
+}
+
+// The score of a position from the side to move. A position without moves is checkmate or
+// stalemate, which the search doesn't score.
+fn position_score(board: &Board, result: &Option<SearchResult>) -> EngineScore {
+    match result.as_ref().and_then(|r| r.get_score()) {
+        Some(score) => score,
+        None if board.is_checkmate() => EngineScore::Mate(0),
+        None => EngineScore::Centipawns(0),
+    }
+}
+
+// The winning chances, in percent, of a score.
+fn win_percent(score: EngineScore) -> f64 {
+    let centipawns = score.to_centipawns().clamp(-DECISIVE_CENTIPAWNS, DECISIVE_CENTIPAWNS) as f64;
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * centipawns).exp()) - 1.0)
+}
+
+// An evaluation as written in a %eval comment, in pawns or moves to mate, e.g. 0.35 and #-2.
+fn eval_text(score: EngineScore) -> String {
+    match score {
+        EngineScore::Centipawns(cp) => format!("{:.2}", cp as f64 / 100.0),
+        EngineScore::Mate(moves) => format!("#{}", moves),
+    }
+}
+
+// Marks a move with its judgement, unless it already has a move assessment, names the best
+// move and gives the line the engine expected after it.
+fn add_judgement(annotation: &mut PgnAnnotation, judgement: MoveJudgement, before: &Board, best: &SearchResult) {
+    let assessed = annotation.get_nags().iter().any(|nag| (1..=6).contains(nag));
+    if let (false, Some(nag)) = (assessed, suffix_annotation_nag(judgement.get_suffix())) {
+        annotation.add_nag(nag);
+    }
+    annotation.add_comment(format!("{judgement}. {} was best.", before.to_chess_move(best.get_best_move())));
+    let pv = best.get_info().get_pv();
+    let line = before.to_san_line(&pv[..pv.len().min(BETTER_LINE_MOVES)]);
+    if !line.is_empty() {
+        annotation.add_variation(line);
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_annotate {
+    use crate::{
+        chess_engine,
+        chess_pgn::{ChessMove, parse_pgn_games},
+        chess_uci::SearchLimit,
+    };
+    use super::*;
+
+    fn annotate(pgn: &str) -> AnnotatedGame {
+        let pgn_game = parse_pgn_games(pgn).unwrap().into_iter().next().unwrap();
+        let state = GameState::from_pgn_game(pgn_game).unwrap();
+        annotate_game(&state, "Test", |board, _| Ok(chess_engine::search(board, SearchLimit::Depth(3)))).unwrap()
+    }
+
+    #[test]
+    pub fn blunders_are_marked_with_the_better_line() {
+        let annotated = annotate("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0");
+        let plies = annotated.get_game().get_plies();
+        let (nf6, annotation) = plies[5];
+        assert_eq!(*nf6, ChessMove::from("Nf6").unwrap());
+        assert_eq!(annotation.get_nags(), &vec![4]);
+        assert_eq!(annotation.get_comments()[0], "[%eval #1]");
+        assert!(annotation.get_comments()[1].starts_with("Blunder. "), "{:?}", annotation.get_comments());
+        assert!(annotation.get_variations()[0].starts_with("3... "));
+        // The mate itself ends the game without an evaluation after it.
+        assert!(plies[6].1.get_comments().is_empty());
+
+        assert_eq!(annotated.get_summary(Team::Dark).get_blunders(), 1);
+        assert_eq!(annotated.get_summary(Team::Light).get_blunders(), 0);
+        let text = annotated.get_game().to_string();
+        assert!(text.contains("[Annotator \"Test\"]"));
+        assert!(text.contains("{White: accuracy "));
+    }
+
+    #[test]
+    pub fn accuracy_follows_the_chances_kept() {
+        assert!((win_percent(EngineScore::Centipawns(0)) - 50.0).abs() < 1e-9);
+        assert!(win_percent(EngineScore::Mate(3)) > 97.0);
+        assert!(win_percent(EngineScore::Mate(-3)) < 3.0);
+        assert_eq!(MoveJudgement::from(4.0), None);
+        assert_eq!(MoveJudgement::from(12.0), Some(MoveJudgement::Mistake));
+        assert_eq!(eval_text(EngineScore::Centipawns(-150)), "-1.50");
+        assert_eq!(read_eval("[%eval -1.50]"), Some(EngineScore::Centipawns(-150)));
+        assert_eq!(read_eval("Mate ahead. [%eval #-2] [%clk 0:01:00]"), Some(EngineScore::Mate(-2)));
+        assert_eq!(read_eval("[%eval 0.3,18]"), Some(EngineScore::Centipawns(30)));
+        assert_eq!(read_eval("[%clk 0:01:00]"), None);
+
+        let mut summary = PlayerSummary::default();
+        assert_eq!(summary.to_string(), "no moves, no inaccuracies, no mistakes, no blunders");
+        summary.add_move(100.0, None);
+        summary.add_move(50.0, Some(MoveJudgement::Mistake));
+        assert_eq!(summary.to_string(), "accuracy 75%, no inaccuracies, 1 mistake, no blunders");
+        let mut total = PlayerSummary::default();
+        total.merge(&summary);
+        total.add_move(90.0, Some(MoveJudgement::Inaccuracy));
+        assert_eq!(total.get_moves(), 3);
+        assert_eq!(total.to_string(), "accuracy 80%, 1 inaccuracy, 1 mistake, no blunders");
+    }
+
+    #[test]
+    pub fn stored_evaluations_are_summed_up() {
+        let text = "1. e4 { [%eval 0.3] } 1... e5 { [%eval 0.25] } 2. Qh5 { [%eval -0.4] } 2... Nc6 { [%eval -0.3] } \
+            3. Bc4 { [%eval -0.2] } 3... Nf6 { [%eval 10.0] } 4. Qxf7# { [%eval #0] } 1-0";
+        let game = crate::chess_pgn::parse_pgn_games(text).unwrap().remove(0);
+        let white = summarize_evals(&game, Team::Light);
+        let black = summarize_evals(&game, Team::Dark);
+        // 1. e4 has no evaluation before it.
+        assert_eq!((white.get_moves(), black.get_moves()), (3, 3));
+        assert_eq!((white.get_inaccuracies(), white.get_mistakes(), white.get_blunders()), (1, 0, 0));
+        assert_eq!((black.get_inaccuracies(), black.get_mistakes(), black.get_blunders()), (0, 0, 1));
+    }
+}
@@ -0,0 +1,122 @@
+/*
+chess_arbitrary.rs
+Arbitrary for the types the fuzz targets build from raw bytes, built with the arbitrary feature.
+Simple types derive it where they are declared. The ones here only make values that could have
+been read from a PGN file, so a target can check that writing a value and reading it back gives
+the same value:
+
+  ChessMove  built through ChessMoveBuilder, e.g. Nbxd7+, exd8=Q#, O-O
+  PgnDate    years 0 to 9999, months 1 to 12 and days 1 to 31, each part possibly unknown
+  PgnRound   one or more round numbers, ?, or -
+*/
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    chess_common::{ChessCoordinate, ChessPiece},
+    chess_pgn::{ChessMove, ChessMoveBuilder, PgnDate, PgnRound},
+};
+
+const PROMOTION_PIECES: [ChessPiece; 4] = [ChessPiece::Knight, ChessPiece::Bishop, ChessPiece::Rook, ChessPiece::Queen];
+
+impl<'a> Arbitrary<'a> for ChessMove {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<ChessMove> {
+        let mut builder = ChessMoveBuilder::new();
+        if u.ratio(1, 10)? {
+            // Castling is read as a king move.
+            builder = builder.set_castle(u.arbitrary()?).set_moving_piece(ChessPiece::King);
+        }
+        else {
+            let piece: ChessPiece = u.arbitrary()?;
+            let is_capture: bool = u.arbitrary()?;
+            builder = builder
+                .set_moving_piece(piece)
+                .set_destination(ChessCoordinate::new(u.arbitrary()?, u.arbitrary()?))
+                .set_is_capture(is_capture);
+            // Pawns say the file they came from when capturing and nothing otherwise, other pieces
+            // say as much of their square as needed to tell them apart.
+            let origin = match piece {
+                ChessPiece::Pawn if is_capture => ChessCoordinate::from_file(u.arbitrary()?),
+                ChessPiece::Pawn => ChessCoordinate::empty(),
+                _ => u.arbitrary()?,
+            };
+            if !origin.is_empty() {
+                builder = builder.set_origin(origin);
+            }
+            if piece == ChessPiece::Pawn && u.ratio(1, 8)? {
+                builder = builder.set_promotion(*u.choose(&PROMOTION_PIECES)?);
+            }
+        }
+        match u.int_in_range(0..=4)? {
+            0 => builder = builder.set_is_check(true),
+            1 => builder = builder.set_is_checkmate(true),
+            _ => (),
+        }
+        builder.build().map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+impl<'a> Arbitrary<'a> for PgnDate {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<PgnDate> {
+        let year = if u.arbitrary()? { Some(u.int_in_range(0..=9999)?) } else { None };
+        let month = if u.arbitrary()? { Some(u.int_in_range(1..=12)?) } else { None };
+        let day = if u.arbitrary()? { Some(u.int_in_range(1..=31)?) } else { None };
+        Ok(PgnDate::new(year, month, day))
+    }
+}
+
+impl<'a> Arbitrary<'a> for PgnRound {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<PgnRound> {
+        Ok(match u.int_in_range(0..=5)? {
+            0 => PgnRound::Unknown,
+            1 => PgnRound::Inappropriate,
+            _ => {
+                let mut rounds = vec![u.arbitrary()?];
+                while rounds.len() < 4 && u.ratio(1, 4)? {
+                    rounds.push(u.arbitrary()?);
+                }
+                PgnRound::Known(rounds)
+            }
+        })
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_arbitrary {
+    use crate::chess_pgn::PgnResult;
+    use super::*;
+
+    // Bytes that aren't random but vary enough to reach every kind of value.
+    fn bytes(seed: u64) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e3779b97f4a7c15) | 1;
+        (0..256).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect()
+    }
+
+    #[test]
+    pub fn arbitrary_values_read_back_the_same() {
+        let mut moves = 0;
+        for seed in 0..500 {
+            let data = bytes(seed);
+            let mut u = Unstructured::new(&data);
+            if let Ok(chess_move) = ChessMove::arbitrary(&mut u) {
+                moves += 1;
+                let written = chess_move.to_string();
+                assert_eq!(ChessMove::from(&written).as_ref(), Ok(&chess_move), "{written}");
+            }
+            let date = PgnDate::arbitrary(&mut u).unwrap();
+            assert_eq!(PgnDate::from(&date.to_string()).map(|d| d.to_string()), Some(date.to_string()));
+            let round = PgnRound::arbitrary(&mut u).unwrap();
+            assert_eq!(PgnRound::from(&round.to_string()).map(|r| r.to_string()), Ok(round.to_string()));
+            let result = PgnResult::arbitrary(&mut u).unwrap();
+            assert_eq!(PgnResult::from(&result.to_string()), Some(result));
+        }
+        assert!(moves > 250, "only {moves} moves were made");
+    }
+}
@@ -0,0 +1,309 @@
+/*
+chess_batch.rs
+The analyze-batch mode of the program, which annotates every game in a directory of PGN files,
+e.g. a club's season or a player's archive. The annotated games of each file are written to a
+file of the same name in the output directory, next to report.txt summing up every game and
+every player:
+
+rust_chess analyze-batch games --out annotated --player Rojas --since 2024
+
+Files that can't be read and games that can't be replayed are left out and listed in the report,
+so one bad game doesn't stop the batch.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    chess_annotate::{annotate_game, AnnotatedGame, PlayerSummary},
+    chess_cmd::{AnalyzeBatchArgs, ChessArgs},
+    chess_config::ChessConfig,
+    chess_core::Team,
+    chess_engine::{self, ENGINE_NAME},
+    chess_game::GameState,
+    chess_pgn::{parse_pgn_games, PgnDate, PgnGame},
+    chess_uci::{SearchLimit, UciEngine, UciError},
+};
+
+// How long the built-in engine looks at each position unless told otherwise.
+const BUILT_IN_POSITION_TIME: Duration = Duration::from_millis(200);
+const REPORT_FILE_NAME: &str = "report.txt";
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(PathBuf, io::Error),
+    FileExists(PathBuf),
+    NoGames(PathBuf),
+    Engine(String, UciError),
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            BatchError::FileExists(path) => write!(f, "{} already exists, use --force to overwrite it", path.display()),
+            BatchError::NoGames(path) => write!(f, "{} has no PGN files", path.display()),
+            BatchError::Engine(name, e) => write!(f, "{} failed: {}", name, e),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+// Which games of the directory are annotated. Games without a known date are left out when a
+// date is asked for, as are games without an ECO tag when codes are.
+#[derive(Clone, Debug, Default)]
+pub struct GameFilter {
+    pub player: Option<String>,
+    pub eco: Vec<String>,
+    pub since: Option<PgnDate>,
+    pub until: Option<PgnDate>,
+}
+
+impl GameFilter {
+    pub fn matches(&self, game: &PgnGame) -> bool {
+        if let Some(player) = &self.player {
+            let player = player.to_lowercase();
+            if !game.get_white().to_lowercase().contains(&player) && !game.get_black().to_lowercase().contains(&player) {
+                return false;
+            }
+        }
+        if !self.eco.is_empty() {
+            let eco = game.get_tag("ECO").unwrap_or_default().to_uppercase();
+            if !self.eco.iter().any(|e| eco.starts_with(&e.to_uppercase())) {
+                return false;
+            }
+        }
+        let date = game.get_date();
+        if let Some(since) = &self.since {
+            // A date in the first day of the bound counts, so missing parts of the bound are its
+            // earliest and of the game its latest.
+            match date_key(date, u8::MAX) {
+                Some(key) if key >= date_key(since, 0).unwrap_or_default() => (),
+                _ => return false,
+            }
+        }
+        if let Some(until) = &self.until {
+            match date_key(date, 0) {
+                Some(key) if key <= date_key(until, u8::MAX).unwrap_or_default() => (),
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+// A date as something to compare, with the unknown month and day filled in. None without a year.
+fn date_key(date: &PgnDate, unknown: u8) -> Option<(i32, u8, u8)> {
+    Some((date.get_year()?, date.get_month().unwrap_or(unknown), date.get_day().unwrap_or(unknown)))
+}
+
+// What was annotated, for the report.
+#[derive(Default)]
+pub struct BatchReport {
+    engine: String,
+    files: usize,
+    // A line for each game: where it is and how each side played.
+    games: Vec<String>,
+    // Every player over all their games, by name.
+    players: BTreeMap<String, (u32, PlayerSummary)>,
+    // Files and games left out, with why.
+    skipped: Vec<String>,
+}
+
+impl Display for BatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Annotated {} game(s) from {} file(s) with {}.", self.games.len(), self.files, self.engine)?;
+        if !self.games.is_empty() {
+            writeln!(f, "\nGames:")?;
+            for line in &self.games {
+                writeln!(f, "  {line}")?;
+            }
+            writeln!(f, "\nPlayers:")?;
+            let width = self.players.keys().map(|p| p.chars().count()).max().unwrap_or(0).max("Player".len());
+            writeln!(f, "  {:width$}  Games  Accuracy  Inaccuracies  Mistakes  Blunders", "Player")?;
+            for (name, (games, summary)) in &self.players {
+                let accuracy = summary.get_accuracy().map_or(String::from("-"), |a| format!("{a:.0}%"));
+                writeln!(f, "  {name:width$}  {games:>5}  {accuracy:>8}  {:>12}  {:>8}  {:>8}",
+                    summary.get_inaccuracies(), summary.get_mistakes(), summary.get_blunders())?;
+            }
+        }
+        if !self.skipped.is_empty() {
+            writeln!(f, "\nLeft out:")?;
+            for line in &self.skipped {
+                writeln!(f, "  {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BatchReport {
+    fn add_game(&mut self, source: &str, annotated: &AnnotatedGame) {
+        let game = annotated.get_game();
+        let (white, black) = (annotated.get_summary(Team::Light), annotated.get_summary(Team::Dark));
+        let accuracy = |s: &PlayerSummary| s.get_accuracy().map_or(String::from("-"), |a| format!("{a:.0}%"));
+        self.games.push(format!("{source}  {} - {}  {}  accuracy {} / {}",
+            game.get_white(), game.get_black(), game.get_result(), accuracy(white), accuracy(black)));
+        for (name, summary) in [(game.get_white(), white), (game.get_black(), black)] {
+            let (games, total) = self.players.entry(name.clone()).or_default();
+            *games += 1;
+            total.merge(summary);
+        }
+    }
+}
+
+pub fn batch_main(args: &ChessArgs, batch_args: &AnalyzeBatchArgs) {
+    match run(args, batch_args) {
+        Ok(report) => print!("{report}"),
+        Err(e) => {
+            eprintln!("error: {e}.");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run(args: &ChessArgs, batch_args: &AnalyzeBatchArgs) -> Result<BatchReport, BatchError> {
+    let config = args.config.clone()
+        .or_else(ChessConfig::default_path)
+        .and_then(|path| ChessConfig::load(&path).ok())
+        .unwrap_or_default();
+    let filter = GameFilter {
+        player: batch_args.player.clone(),
+        eco: batch_args.eco.clone(),
+        since: batch_args.since.clone(),
+        until: batch_args.until.clone(),
+    };
+    let movetime = batch_args.movetime.map(|ms| SearchLimit::MoveTime(Duration::from_millis(ms)));
+    match config.get_engine_path() {
+        Some(path) => {
+            let mut engine = UciEngine::start(path).map_err(|e| BatchError::Engine(path.display().to_string(), e))?;
+            let name = engine.get_name().clone();
+            let limit = movetime.unwrap_or(SearchLimit::Depth(config.get_engine_depth()));
+            analyze_directory(&batch_args.input_dir, &batch_args.out, &filter, batch_args.force, &name, |state| {
+                engine.new_game()?;
+                annotate_game(state, &name, |_, moves| engine.search(moves, limit).map(Some))
+            })
+        }
+        None => {
+            let limit = movetime.unwrap_or(SearchLimit::MoveTime(BUILT_IN_POSITION_TIME));
+            analyze_directory(&batch_args.input_dir, &batch_args.out, &filter, batch_args.force, ENGINE_NAME, |state| {
+                annotate_game(state, ENGINE_NAME, |board, _| Ok(chess_engine::search(board, limit)))
+            })
+        }
+    }
+}
+
+// Annotates the games of the .pgn files in `input_dir` that pass the filter with `annotate`,
+// writing them and the report to `output_dir`. Nothing is written over unless `overwrite` is set,
+// and an engine failure stops the batch.
+pub fn analyze_directory(
+    input_dir: &Path,
+    output_dir: &Path,
+    filter: &GameFilter,
+    overwrite: bool,
+    engine: &str,
+    mut annotate: impl FnMut(&GameState) -> Result<AnnotatedGame, UciError>,
+) -> Result<BatchReport, BatchError> {
+    let io_error = |path: &Path| { let path = path.to_path_buf(); move |e| BatchError::Io(path, e) };
+    let mut files: Vec<PathBuf> = fs::read_dir(input_dir).map_err(io_error(input_dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("pgn")))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(BatchError::NoGames(input_dir.to_path_buf()));
+    }
+    let report_path = output_dir.join(REPORT_FILE_NAME);
+    if !overwrite {
+        let outputs = files.iter().filter_map(|f| f.file_name()).map(|n| output_dir.join(n));
+        if let Some(existing) = outputs.chain([report_path.clone()]).find(|p| p.exists()) {
+            return Err(BatchError::FileExists(existing));
+        }
+    }
+    fs::create_dir_all(output_dir).map_err(io_error(output_dir))?;
+
+    let mut report = BatchReport { engine: String::from(engine), ..BatchReport::default() };
+    for file in &files {
+        let file_name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let games = match fs::read_to_string(file).map_err(|e| e.to_string()).and_then(|t| parse_pgn_games(&t).map_err(|e| e.to_string())) {
+            Ok(games) => games,
+            Err(e) => {
+                report.skipped.push(format!("{file_name}: {e}"));
+                continue;
+            }
+        };
+        report.files += 1;
+        let mut annotated_games = Vec::new();
+        for (index, game) in games.into_iter().enumerate() {
+            let source = format!("{file_name} #{}", index + 1);
+            if !filter.matches(&game) {
+                continue;
+            }
+            let state = match GameState::from_pgn_game(game) {
+                Ok(state) if state.get_ply_count() > 0 => state,
+                Ok(_) => continue,
+                Err(e) => {
+                    report.skipped.push(format!("{source}: {e}"));
+                    continue;
+                }
+            };
+            let annotated = annotate(&state).map_err(|e| BatchError::Engine(String::from(engine), e))?;
+            report.add_game(&source, &annotated);
+            annotated_games.push(annotated.get_game().to_string());
+        }
+        if !annotated_games.is_empty() {
+            let path = output_dir.join(&file_name);
+            fs::write(&path, format!("{}\n", annotated_games.join("\n\n"))).map_err(io_error(&path))?;
+        }
+    }
+    fs::write(&report_path, report.to_string()).map_err(io_error(&report_path))?;
+    Ok(report)
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_batch {
+    use super::*;
+
+    #[test]
+    pub fn games_are_filtered_annotated_and_reported() {
+        let dir = std::env::temp_dir().join(format!("rust_chess_batch_{}", std::process::id()));
+        let (input, output) = (dir.join("games"), dir.join("annotated"));
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("club.pgn"), "[White \"Rojas\"]\n[Black \"Fool\"]\n[Date \"2024.03.02\"]\n[ECO \"A02\"]\n\n\
+            1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n\n\
+            [White \"Fool\"]\n[Black \"Rojas\"]\n[Date \"2023.11.20\"]\n[ECO \"A02\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n\
+            [White \"Smith\"]\n[Black \"Jones\"]\n[Date \"2024.01.05\"]\n\n1. d4 d5 *\n").unwrap();
+        fs::write(input.join("broken.pgn"), "[White \"Rojas\"]\n[Date \"2024.02.01\"]\n\n1. e4 e5 2. Ke3 *\n").unwrap();
+        fs::write(input.join("notes.txt"), "not a game").unwrap();
+
+        let filter = GameFilter { player: Some(String::from("rojas")), since: PgnDate::from("2024.??.??"), ..GameFilter::default() };
+        let annotate = |state: &GameState| {
+            annotate_game(state, "Test", |board, _| Ok(chess_engine::search(board, SearchLimit::Depth(2))))
+        };
+        let report = analyze_directory(&input, &output, &filter, false, "Test", annotate).unwrap();
+        let text = report.to_string();
+        assert!(text.starts_with("Annotated 1 game(s) from 2 file(s) with Test.\n"), "{text}");
+        assert!(text.contains("\n  club.pgn #1  Rojas - Fool  1-0  accuracy "), "{text}");
+        assert!(text.contains("\nLeft out:\n  broken.pgn #1: move "), "{text}");
+        assert_eq!(fs::read_to_string(output.join(REPORT_FILE_NAME)).unwrap(), text);
+        let annotated = fs::read_to_string(output.join("club.pgn")).unwrap();
+        assert!(annotated.contains("[Annotator \"Test\"]") && !annotated.contains("Smith"), "{annotated}");
+        assert!(!output.join("broken.pgn").exists());
+
+        assert!(matches!(analyze_directory(&input, &output, &filter, false, "Test", annotate), Err(BatchError::FileExists(_))));
+        let eco = GameFilter { eco: vec![String::from("a0")], until: PgnDate::from("2023.??.??"), ..GameFilter::default() };
+        let report = analyze_directory(&input, &output, &eco, true, "Test", annotate).unwrap();
+        assert_eq!(report.games.len(), 1);
+        assert_eq!(report.players.get("Rojas").map(|(games, _)| *games), Some(1));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
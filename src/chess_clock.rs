@@ -0,0 +1,474 @@
+/*
+chess_clock.rs
+Chess clocks. A time control gives each side a base time, an increment added after every move
+and an optional delay, a grace period at the start of every move before the clock counts down.
+Classical time controls come in stages: after a number of moves the next stage's time is added,
+e.g. 90 minutes for 40 moves, then 30 more for the rest of the game. A last stage with a number
+of moves repeats. The clock only measures time, the instant it is read at is passed in so
+callers decide what "now" is.
+
+Time controls are written as minutes, plus seconds of increment, plus "d" and seconds of delay,
+with stages joined by colons and led by their number of moves: 5+3, 90+30, 15d5, 3+2d1,
+40/90+30:30+30. The presets bullet, blitz, rapid and classical stand for common ones.
+
+A base is given in seconds with an s after it, e.g. 90s+2 or 40/5400s+30:1800s. The PGN
+TimeControl tag counts in seconds throughout, 300+3 or 40/5400+30:1800+30, and is read on its own.
+*/
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::chess_core::Team;
+
+// Names for common time controls, and what they stand for.
+pub const TIME_CONTROL_PRESETS: [(&str, &str); 4] = [
+    ("bullet", "1+0"),
+    ("blitz", "3+2"),
+    ("rapid", "15+10"),
+    ("classical", "40/90+30:30+30"),
+];
+
+// The longest base, increment or delay a time control takes. A week is far beyond any game at the
+// board, and keeps the clocks' sums from overflowing.
+const MAX_TIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct TimeControl {
+    // Never empty. Every stage but the last has a number of moves.
+    stages: Vec<TimeStage>,
+}
+
+// A part of a time control, lasting for a number of moves or for the rest of the game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeStage {
+    moves: Option<u32>,
+    base: Duration,
+    increment: Duration,
+    delay: Duration,
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.base.as_secs_f64() / 60.0)?;
+            if !stage.increment.is_zero() {
+                write!(f, "+{}", stage.increment.as_secs())?;
+            }
+            if !stage.delay.is_zero() {
+                write!(f, "d{}", stage.delay.as_secs())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for TimeControl {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<TimeControl, String> {
+        TimeControl::from(&value).ok_or_else(|| format!("{} is not a time control, e.g. 5+3 or blitz", value))
+    }
+}
+
+impl From<TimeControl> for String {
+    fn from(time_control: TimeControl) -> String {
+        time_control.to_string()
+    }
+}
+
+impl TimeStage {
+    pub fn new(moves: Option<u32>, base: Duration, increment: Duration, delay: Duration) -> TimeStage {
+        TimeStage { moves, base, increment, delay }
+    }
+
+    // The number of moves the stage lasts, None for the rest of the game.
+    pub fn get_moves(&self) -> Option<u32> {
+        self.moves
+    }
+
+    pub fn get_base(&self) -> Duration {
+        self.base
+    }
+
+    pub fn get_increment(&self) -> Duration {
+        self.increment
+    }
+
+    pub fn get_delay(&self) -> Duration {
+        self.delay
+    }
+}
+
+impl TimeControl {
+    // A time control of a single stage.
+    pub fn new(base: Duration, increment: Duration, delay: Duration) -> TimeControl {
+        TimeControl { stages: vec![TimeStage::new(None, base, increment, delay)] }
+    }
+
+    // A time control of stages in the order they are played, None unless every stage but the
+    // last has a number of moves.
+    pub fn with_stages(stages: Vec<TimeStage>) -> Option<TimeControl> {
+        let (_, played_out) = stages.split_last()?;
+        if played_out.iter().any(|s| s.moves.is_none()) {
+            return None;
+        }
+        Some(TimeControl { stages })
+    }
+
+    // Reads a time control in minutes, e.g. 5+3 or 40/90+30:30+30, or a preset's name. Bases
+    // followed by an s are in seconds, e.g. 40/5400s+30:1800s.
+    pub fn from(time_control_str: &str) -> Option<TimeControl> {
+        let text = time_control_str.trim().to_lowercase();
+        let text = match TIME_CONTROL_PRESETS.iter().find(|(name, _)| *name == text) {
+            Some((_, preset)) => preset.to_string(),
+            None => text,
+        };
+        let stages = text.split(':').map(|stage| {
+            let (moves, stage) = split_moves(stage)?;
+            let (stage, delay) = match stage.split_once('d') {
+                Some((t, d)) => (t, d.parse::<u64>().ok()?),
+                None => (stage, 0),
+            };
+            let (base, increment) = match stage.split_once('+') {
+                Some((b, i)) => (b, i.parse::<u64>().ok()?),
+                None => (stage, 0),
+            };
+            let (base, unit) = match base.strip_suffix('s') {
+                Some(seconds) => (seconds, 1.0),
+                None => (base, 60.0),
+            };
+            let length = base.parse::<f64>().ok().filter(|l| l.is_finite() && *l > 0.0)?;
+            let base = Duration::try_from_secs_f64(length * unit).ok()?;
+            checked_stage(TimeStage::new(moves, base, Duration::from_secs(increment), Duration::from_secs(delay)))
+        });
+        TimeControl::with_stages(stages.collect::<Option<Vec<TimeStage>>>()?)
+    }
+
+    // Reads the value of a PGN TimeControl tag, e.g. 300+3 or 40/5400+30:1800+30. Unknown (?) and
+    // untimed (-) games have none, and neither do hourglass time controls (*60), which these
+    // clocks can't keep.
+    pub fn from_pgn_tag(tag: &str) -> Option<TimeControl> {
+        let stages = tag.trim().split(':').map(|stage| {
+            let (moves, stage) = split_moves(stage)?;
+            let (base, increment) = match stage.split_once('+') {
+                Some((b, i)) => (b, i.parse::<u64>().ok()?),
+                None => (stage, 0),
+            };
+            let seconds = base.parse::<u64>().ok().filter(|s| *s > 0)?;
+            checked_stage(TimeStage::new(moves, Duration::from_secs(seconds), Duration::from_secs(increment), Duration::ZERO))
+        });
+        TimeControl::with_stages(stages.collect::<Option<Vec<TimeStage>>>()?)
+    }
+
+    pub fn get_stages(&self) -> &Vec<TimeStage> {
+        &self.stages
+    }
+
+    // The value of the PGN TimeControl tag, seconds plus seconds of increment, e.g. "300+3" or
+    // "40/5400+30:1800+30". The tag has no way of writing a delay.
+    pub fn get_pgn_tag(&self) -> String {
+        let stages: Vec<String> = self.stages.iter().map(|stage| {
+            let mut text = match stage.moves {
+                Some(moves) => format!("{}/{}", moves, stage.base.as_secs()),
+                None => format!("{}", stage.base.as_secs()),
+            };
+            if !stage.increment.is_zero() {
+                text += format!("+{}", stage.increment.as_secs()).as_str();
+            }
+            text
+        }).collect();
+        stages.join(":")
+    }
+}
+
+// The stage, or None when one of its times is longer than a time control takes.
+fn checked_stage(stage: TimeStage) -> Option<TimeStage> {
+    [stage.base, stage.increment, stage.delay].iter().all(|t| *t <= MAX_TIME).then_some(stage)
+}
+
+// Splits the number of moves off a stage, e.g. 40 off 40/90+30.
+fn split_moves(stage: &str) -> Option<(Option<u32>, &str)> {
+    match stage.split_once('/') {
+        Some((moves, rest)) => Some((Some(moves.parse::<u32>().ok().filter(|m| *m > 0)?), rest)),
+        None => Some((None, stage)),
+    }
+}
+
+pub struct ChessClock {
+    time_control: TimeControl,
+    // Time left for White and Black, as of when the running clock was started.
+    remaining: [Duration; 2],
+    // The stage each side plays in, and the moves it made in that stage.
+    stage: [usize; 2],
+    stage_moves: [u32; 2],
+    // The side whose clock runs and since when, None while the clocks are stopped.
+    running: Option<(Team, Instant)>,
+}
+
+impl ChessClock {
+    pub fn new(time_control: TimeControl) -> ChessClock {
+        let base = time_control.stages[0].base;
+        ChessClock {
+            time_control,
+            remaining: [base; 2],
+            stage: [0; 2],
+            stage_moves: [0; 2],
+            running: None,
+        }
+    }
+
+    // A clock as it was left, stopped: the time each side has, White's first, the stage each
+    // plays in and the moves it made in that stage.
+    pub fn resume(time_control: TimeControl, remaining: [Duration; 2], stage: [usize; 2], stage_moves: [u32; 2]) -> ChessClock {
+        let last = time_control.stages.len() - 1;
+        ChessClock {
+            time_control,
+            remaining,
+            stage: stage.map(|s| s.min(last)),
+            stage_moves,
+            running: None,
+        }
+    }
+
+    pub fn get_time_control(&self) -> &TimeControl {
+        &self.time_control
+    }
+
+    // The index of the stage a side plays in and the moves it made in that stage.
+    pub fn get_stage_progress(&self, team: Team) -> (usize, u32) {
+        let index = team_index(team);
+        (self.stage[index], self.stage_moves[index])
+    }
+
+    // The stage of the time control a side plays in.
+    pub fn get_stage(&self, team: Team) -> &TimeStage {
+        &self.time_control.stages[self.stage[team_index(team)]]
+    }
+
+    // The side whose clock is running, if any.
+    pub fn get_running(&self) -> Option<Team> {
+        self.running.map(|(team, _)| team)
+    }
+
+    // The time a side has left at `now`. The delay of the running side's move is used up
+    // before its time is.
+    pub fn get_remaining(&self, team: Team, now: Instant) -> Duration {
+        let remaining = self.remaining[team_index(team)];
+        match self.running {
+            Some((running, since)) if running == team => {
+                let used = now.saturating_duration_since(since).saturating_sub(self.get_stage(team).delay);
+                remaining.saturating_sub(used)
+            }
+            _ => remaining,
+        }
+    }
+
+    // The side whose time ran out, if any.
+    pub fn get_flagged(&self, now: Instant) -> Option<Team> {
+        let team = self.get_running()?;
+        Some(team).filter(|t| self.get_remaining(*t, now).is_zero())
+    }
+
+    // Starts a side's clock, stopping the other one.
+    pub fn start(&mut self, team: Team, now: Instant) {
+        self.stop(now);
+        self.running = Some((team, now));
+    }
+
+    // Ends the turn of the side whose clock runs: its time is charged, the increment added
+    // unless it ran out of time, and the other side's clock is started. The move that ends a
+    // stage brings the next stage's time.
+    pub fn press(&mut self, now: Instant) {
+        if let Some((team, _)) = self.running {
+            self.stop(now);
+            let index = team_index(team);
+            let stage = *self.get_stage(team);
+            let flagged = self.remaining[index].is_zero();
+            if !flagged {
+                self.remaining[index] += stage.increment;
+            }
+            self.stage_moves[index] += 1;
+            if stage.moves == Some(self.stage_moves[index]) {
+                // The last stage starts over when it has a number of moves.
+                self.stage[index] = (self.stage[index] + 1).min(self.time_control.stages.len() - 1);
+                self.stage_moves[index] = 0;
+                if !flagged {
+                    self.remaining[index] += self.get_stage(team).base;
+                }
+            }
+            self.running = Some((team.opposite(), now));
+        }
+    }
+
+    // Stops the clocks, charging the running side for its time so far.
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((team, _)) = self.running {
+            self.remaining[team_index(team)] = self.get_remaining(team, now);
+            self.running = None;
+        }
+    }
+}
+
+fn team_index(team: Team) -> usize {
+    match team {
+        Team::Light => 0,
+        Team::Dark => 1,
+    }
+}
+
+// Clock time as shown to the players, h:mm:ss from an hour up and m:ss below. Seconds are
+// rounded up, so a clock only shows 0:00 once its time is out.
+pub fn format_clock_time(time: Duration) -> String {
+    let seconds = time.as_millis().div_ceil(1000);
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+    else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_chess_clock {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    pub fn time_controls_are_read_and_written() {
+        let blitz = TimeControl::from("5+3").unwrap();
+        assert_eq!(blitz, TimeControl::new(secs(300), secs(3), secs(0)));
+        assert_eq!(blitz.to_string(), "5+3");
+        assert_eq!(blitz.get_pgn_tag(), "300+3");
+
+        let delayed = TimeControl::from("0.5d2").unwrap();
+        assert_eq!(delayed, TimeControl::new(secs(30), secs(0), secs(2)));
+        assert_eq!(delayed.to_string(), "0.5d2");
+        assert_eq!(delayed.get_pgn_tag(), "30");
+
+        assert_eq!(TimeControl::from("90+30d5").unwrap().to_string(), "90+30d5");
+        for invalid in ["", "0", "-5", "5+", "five", "5+3d", "inf", "90:30", "0/90:30", "40/90:", "speedy"] {
+            assert_eq!(TimeControl::from(invalid), None, "{invalid}");
+        }
+        // Times too long for any game are refused rather than overflowing the clocks.
+        for huge in ["1e30", "40/5400e30", "5+99999999999999", "5d99999999999999"] {
+            assert_eq!(TimeControl::from(huge), None, "{huge}");
+        }
+        assert_eq!(TimeControl::from_pgn_tag("18446744073709551615"), None);
+        assert_eq!(TimeControl::try_from(String::from("1e30")), Err(String::from("1e30 is not a time control, e.g. 5+3 or blitz")));
+    }
+
+    #[test]
+    pub fn stages_and_presets_are_read_and_written() {
+        let classical = TimeControl::from("Classical").unwrap();
+        assert_eq!(classical.get_stages(), &vec![
+            TimeStage::new(Some(40), secs(5400), secs(30), secs(0)),
+            TimeStage::new(None, secs(1800), secs(30), secs(0)),
+        ]);
+        assert_eq!(classical.to_string(), "40/90+30:30+30");
+        assert_eq!(classical.get_pgn_tag(), "40/5400+30:1800+30");
+        assert_eq!(TimeControl::from("blitz").unwrap().to_string(), "3+2");
+        // Bases are in minutes unless marked as seconds, however long they are.
+        let seconds = TimeControl::from("40/5400s+30:1800s").unwrap();
+        assert_eq!(seconds.get_stages(), &vec![
+            TimeStage::new(Some(40), secs(5400), secs(30), secs(0)),
+            TimeStage::new(None, secs(1800), secs(0), secs(0)),
+        ]);
+        assert_eq!(seconds.to_string(), "40/90+30:30");
+        assert_eq!(TimeControl::from("40/5400s+30:1800s+30").as_ref(), Some(&classical));
+        assert_eq!(TimeControl::from("600").unwrap().get_stages()[0].get_base(), secs(36000));
+        assert_eq!(TimeControl::from("601").unwrap().get_stages()[0].get_base(), secs(36060));
+        assert_eq!(TimeControl::from("40/5400+30").unwrap().get_stages()[0].get_base(), secs(324000));
+        assert_eq!(TimeControl::from("90s+2").unwrap().to_string(), "1.5+2");
+        assert_eq!(TimeControl::from("s+2"), None);
+
+        assert_eq!(TimeControl::from_pgn_tag("40/5400+30:1800+30"), Some(classical));
+        assert_eq!(TimeControl::from_pgn_tag("300+3"), TimeControl::from("5+3"));
+        assert_eq!(TimeControl::from_pgn_tag("40/9000").unwrap().to_string(), "40/150");
+        for unknown in ["?", "-", "*60", "", "300:40/60"] {
+            assert_eq!(TimeControl::from_pgn_tag(unknown), None, "{unknown}");
+        }
+    }
+
+    #[test]
+    pub fn moves_charge_time_and_add_increment() {
+        let start = Instant::now();
+        let mut clock = ChessClock::new(TimeControl::from("1+2").unwrap());
+        clock.start(Team::Light, start);
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(10)), secs(50));
+        assert_eq!(clock.get_remaining(Team::Dark, start + secs(10)), secs(60));
+
+        clock.press(start + secs(10));
+        assert_eq!(clock.get_running(), Some(Team::Dark));
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(30)), secs(52));
+        assert_eq!(clock.get_remaining(Team::Dark, start + secs(30)), secs(40));
+
+        clock.stop(start + secs(30));
+        assert_eq!(clock.get_running(), None);
+        assert_eq!(clock.get_remaining(Team::Dark, start + secs(100)), secs(40));
+    }
+
+    #[test]
+    pub fn delay_is_used_before_the_time() {
+        let start = Instant::now();
+        let mut clock = ChessClock::new(TimeControl::from("1d5").unwrap());
+        clock.start(Team::Light, start);
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(4)), secs(60));
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(8)), secs(57));
+    }
+
+    #[test]
+    pub fn running_out_of_time_flags() {
+        let start = Instant::now();
+        let mut clock = ChessClock::new(TimeControl::from("1+5").unwrap());
+        clock.start(Team::Light, start);
+        assert_eq!(clock.get_flagged(start + secs(59)), None);
+        assert_eq!(clock.get_flagged(start + secs(61)), Some(Team::Light));
+
+        // No increment saves a side that already ran out.
+        clock.press(start + secs(61));
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(61)), Duration::ZERO);
+    }
+
+    #[test]
+    pub fn stages_bring_more_time() {
+        let start = Instant::now();
+        let mut clock = ChessClock::new(TimeControl::from("2/1+1:1/0.5").unwrap());
+        clock.start(Team::Light, start);
+        clock.press(start + secs(10));
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(10)), secs(51));
+        clock.press(start + secs(10));
+        clock.press(start + secs(20));
+        // The second move ends the first stage: the increment and 30 seconds are added.
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(20)), secs(72));
+        assert_eq!(clock.get_stage(Team::Light).get_base(), secs(30));
+        assert_eq!(clock.get_stage(Team::Dark).get_base(), secs(60));
+
+        // The last stage lasts a move and starts over.
+        clock.press(start + secs(20));
+        clock.press(start + secs(30));
+        assert_eq!(clock.get_remaining(Team::Light, start + secs(30)), secs(92));
+    }
+
+    #[test]
+    pub fn clock_times_are_formatted() {
+        assert_eq!(format_clock_time(secs(300)), "5:00");
+        assert_eq!(format_clock_time(secs(5400)), "1:30:00");
+        assert_eq!(format_clock_time(Duration::from_millis(9_450)), "0:10");
+        assert_eq!(format_clock_time(Duration::ZERO), "0:00");
+    }
+}
@@ -1,31 +1,837 @@
 
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use clap::{builder::TypedValueParser, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use crate::chess_pgn::{PgnDate, PgnResult};
+
+// Arguments given to the program on the command line.
+#[derive(Parser, Debug, Default)]
+#[command(name = "rust_chess")]
+#[command(author = "Raul Rojas")]
+#[command(version = "1.0")]
+#[command(about = "The game of chess written in Rust!")]
+pub struct ChessArgs {
+    /// Draw the board without colors. Setting the NO_COLOR environment variable does the same.
+    #[arg(long)]
+    pub no_color: bool,
+    /// Read settings from this file instead of ~/.config/rust-chess/config.toml.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Write the game as a line of JSON whenever it changes, appended to this file or sent to programs connecting to this port (e.g. 9000 or 0.0.0.0:9000).
+    #[arg(long, value_name = "PATH_OR_PORT")]
+    pub broadcast: Option<String>,
+    /// Play in a window, dragging the pieces on the board, instead of in the terminal.
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    pub gui: bool,
+    #[command(subcommand)]
+    pub mode: Option<ChessMode>,
+}
+
+// Ways to run the program other than playing in the terminal.
+#[derive(Subcommand, Debug)]
+pub enum ChessMode {
+    /// Speak the UCI protocol on stdin and stdout, for playing the built-in engine from a chess interface such as Arena or Cute Chess.
+    Uci,
+    /// Play a correspondence game kept in a file both players can reach, one move at a time.
+    Correspondence {
+        #[command(subcommand)]
+        action: CorrespondenceAction,
+    },
+    /// Work on PGN files.
+    Pgn {
+        #[command(subcommand)]
+        action: PgnAction,
+    },
+    /// Work on collections of games kept in PGN files.
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Annotate every game in a directory of PGN files with an engine.
+    #[command(long_about = "Annotates the games of every .pgn file in a directory as the annotate command does, with the configured engine_path or the built-in engine. Each file's annotated games are written to a file of the same name in the output directory, and report.txt there sums up the accuracy, inaccuracies, mistakes and blunders of every game and every player. Games can be picked by player, ECO code and date.\nExamples:\n  analyze-batch games --out annotated\n  analyze-batch club --out review --player Rojas --since 2024.01 --movetime 100\n  analyze-batch archive --out sicilians --eco B2 --eco B9")]
+    AnalyzeBatch(AnalyzeBatchArgs),
+    /// Sum up a player's games: results, openings, opponents, time trouble and errors.
+    #[command(long_about = "Compiles a report on a player from the games of the configured database_path, or of the PGN files given with --from: their results overall and with each color, the openings they play most and how they score in them, the average rating of their opponents, how many of their losses came in time trouble by the [%clk] times of the moves, and their accuracy and inaccuracies, mistakes and blunders per game by the [%eval] evaluations, as annotate and analyze-batch leave them. Players are matched by any part of their name, games without a result are left out.\nExamples:\n  report Rojas\n  report \"Carlsen, Magnus\" --from carlsen.pgn --json")]
+    Report(ReportArgs),
+    /// Run a round robin or Swiss tournament among players, built-in engine levels and UCI engines.
+    #[command(long_about = "Runs the tournament a TOML file describes: its name, its format, round-robin or swiss, the rounds of a Swiss event, whether a round robin is double, the milliseconds UCI engines think per move, and its players, each a name with a level of the built-in engine from 1 to 6, the path of a UCI engine, or neither to play at the terminal. Players are paired round by round, a Swiss event pairing players of the same score without rematches, and the standings are shown after each round with their tiebreaks, Sonneborn-Berger in a round robin and Buchholz in a Swiss event. Every game is written to the output PGN file as it finishes.\nExamples:\n  tournament club.toml --out club.pgn\n  tournament engines.toml --out engines.pgn --force")]
+    Tournament(TournamentArgs),
+    /// Play a series of games against an engine, or have two engines play one.
+    #[command(long_about = "Plays a match of games between two players, changing colors every game: the player at the terminal, or the engine given with --against, and the opponent. An opponent is a level of the built-in engine from 1 to 6, engine for the configured engine_path, or the path of a UCI engine. The score is shown after every game and the result of the match at the end, and every game is added to the game archive, and to a PGN file with --out.\nExamples:\n  match 3 --games 4\n  match engine --games 10 --out match.pgn\n  match /usr/bin/stockfish --against 5 --movetime 200")]
+    Match(MatchArgs),
+    /// Test whether an engine is stronger than another with a sequential probability ratio test.
+    #[command(long_about = "Plays pairs of games between two engines, each pair from the same opening with the colors swapped, and after every pair works out the log likelihood ratio (LLR) of the hypothesis that the first engine is elo1 Elo stronger (H1) against it being elo0 stronger (H0). The test stops when the LLR leaves the bounds set by alpha and beta, the chances of accepting H1 wrongly and H0 wrongly, or after --max-games. Engines are given like match opponents: a level of the built-in engine from 1 to 6, engine for the configured engine_path, or the path of a UCI engine. Openings are the first moves of the games of --openings, or a few main lines otherwise, and the configured adjudication rules apply.\nExamples:\n  sprt ./engine-new ./engine-old\n  sprt ./engine-new ./engine-old --elo0 0 --elo1 10 --alpha 0.05 --beta 0.1 --movetime 50\n  sprt ./engine-new 5 --openings book.pgn --opening-plies 10 --max-games 2000 --out sprt.pgn")]
+    Sprt(SprtArgs),
+    /// Check the move generator against the known perft counts of a set of positions.
+    #[command(long_about = "Counts the positions the move generator reaches from the start position, Kiwipete and other standard perft positions, and from positions testing en passant, castling, promotion and stalemate edge cases, and compares them with the published counts. Each position and depth is reported as ok or FAIL, and the program exits with an error when a count differs, so a build can be checked before it is trusted. Counting every position takes about ten seconds for a release build, --depth leaves out the deeper counts.\nExamples:\n  verify\n  verify --depth 3")]
+    Verify(VerifyArgs),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbAction {
+    /// Find the games that are in the PGN files more than once.
+    #[command(long_about = "Fingerprints every game of the PGN files by its final position, its number of half moves and its players, and lists the games found more than once, in the same file or across files. Exact duplicates have all three in common. Near duplicates are the same moves under other player names, and copies cut short, whose moves begin a longer game of the same players. With --remove the exact duplicates are taken out of the files, keeping the first copy of each game, and the files are written again as pgn fmt writes them.\nExamples:\n  db dedup games.pgn\n  db dedup 2023.pgn 2024.pgn --remove")]
+    Dedup {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Take the exact duplicates out of the files.
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Find the games reaching a material balance or pawn structure.
+    #[command(long_about = "Replays every game of the PGN files and lists the games reaching a position the query describes, with the move it was first reached at. A query is material, each side's pieces besides the king with either side having either, or a pawn structure: iqp for an isolated queen pawn, doubled, isolated or passed with an optional file. Terms joined by and must all hold at once. With --out the games found are written to a PGN file.\nExamples:\n  db search \"R+P vs R\" games.pgn\n  db search \"Q vs 2R\" 2023.pgn 2024.pgn\n  db search iqp games.pgn --out iqp.pgn\n  db search \"R+2P vs R+P and passed\" games.pgn")]
+    Search {
+        /// The position to look for, e.g. "R+P vs R", iqp or "doubled f".
+        query: String,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Write the games found to this PGN file.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// The player, by any part of their name.
+    pub player: String,
+    /// The PGN files to read instead of the game database. Can be given more than once.
+    #[arg(long)]
+    pub from: Vec<PathBuf>,
+    /// Write the report as JSON.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Only check the counts up to this depth.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub depth: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+pub struct SprtArgs {
+    /// The engine tested, e.g. a new build.
+    pub engine: String,
+    /// The engine it is tested against, e.g. the last build.
+    pub baseline: String,
+    /// The Elo difference of the null hypothesis.
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub elo0: f64,
+    /// The Elo difference of the alternative hypothesis.
+    #[arg(long, default_value_t = 5.0, allow_negative_numbers = true)]
+    pub elo1: f64,
+    /// The chance of accepting H1 when H0 holds.
+    #[arg(long, default_value_t = 0.05)]
+    pub alpha: f64,
+    /// The chance of accepting H0 when H1 holds.
+    #[arg(long, default_value_t = 0.05)]
+    pub beta: f64,
+    /// Stop without a decision after this many games, 0 for never.
+    #[arg(long, default_value_t = 0)]
+    pub max_games: u32,
+    /// Milliseconds UCI engines think per move.
+    #[arg(long, default_value_t = 100)]
+    pub movetime: u64,
+    /// A PGN file whose games' first moves are the openings.
+    #[arg(long)]
+    pub openings: Option<PathBuf>,
+    /// How many half moves of the games of --openings are played.
+    #[arg(long, default_value_t = 8)]
+    pub opening_plies: usize,
+    /// Write the games to this PGN file.
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct MatchArgs {
+    /// A level of the built-in engine from 1 to 6, engine, or the path of a UCI engine.
+    pub opponent: String,
+    /// The number of games.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    pub games: u32,
+    /// An engine to play the opponent instead of the player, given like the opponent.
+    #[arg(long)]
+    pub against: Option<String>,
+    /// Milliseconds UCI engines think per move.
+    #[arg(long, default_value_t = 1000)]
+    pub movetime: u64,
+    /// Also write the games to this PGN file.
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct TournamentArgs {
+    /// The TOML file describing the tournament.
+    pub file_path: PathBuf,
+    /// The PGN file the games are written to.
+    #[arg(long)]
+    pub out: PathBuf,
+    /// Overwrite the PGN file if it exists.
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeBatchArgs {
+    pub input_dir: PathBuf,
+    /// The directory the annotated games and the report are written to.
+    #[arg(long)]
+    pub out: PathBuf,
+    /// Only games this player played, by any part of their name.
+    #[arg(long)]
+    pub player: Option<String>,
+    /// Only games whose ECO code starts with this, e.g. B or B90. Can be given more than once.
+    #[arg(long)]
+    pub eco: Vec<String>,
+    /// Only games played on or after this date, as a year, year.month or year.month.day.
+    #[arg(long, value_parser = parse_pgn_date)]
+    pub since: Option<PgnDate>,
+    /// Only games played on or before this date, as a year, year.month or year.month.day.
+    #[arg(long, value_parser = parse_pgn_date)]
+    pub until: Option<PgnDate>,
+    /// Milliseconds the engine thinks per position, instead of searching to the configured depth.
+    #[arg(long)]
+    pub movetime: Option<u64>,
+    /// Overwrite files already in the output directory.
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PgnAction {
+    /// Rewrite a PGN file in a clean, consistent layout.
+    #[command(long_about = "Reads every game of a PGN file and writes them again with the Seven Tag Roster first, the other tags in order, normalized spacing and wrapped movetext, to the output file or the terminal. Comments, variations and clock annotations can be left out, e.g. to clean up exports from sites and engines.\nExamples:\n  pgn fmt games.pgn\n  pgn fmt export.pgn clean.pgn --strip-clocks\n  pgn fmt analysis.pgn moves.pgn --strip-comments --strip-variations --width 0")]
+    Fmt {
+        input: PathBuf,
+        output: Option<PathBuf>,
+        /// Wrap the movetext at this many characters, 0 keeps each game's moves on one line.
+        #[arg(long, default_value_t = 79)]
+        width: usize,
+        /// Keep the text of comments and variations as written instead of normalizing its spacing.
+        #[arg(long)]
+        preserve_text: bool,
+        /// Leave out the comments.
+        #[arg(long)]
+        strip_comments: bool,
+        /// Leave out the variations.
+        #[arg(long)]
+        strip_variations: bool,
+        /// Leave out clock annotations such as [%clk 0:02:59] from the comments.
+        #[arg(long)]
+        strip_clocks: bool,
+        /// Overwrite the output file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CorrespondenceAction {
+    /// Start a game file between two players.
+    New {
+        file_path: PathBuf,
+        #[arg(long)]
+        white: String,
+        #[arg(long)]
+        black: String,
+    },
+    /// Play your move, when it is your turn.
+    #[command(long_about = "Plays your move and appends it to the game file. Your side is the one given with --as, or the one your configured player_name plays.\nExamples:\n  correspondence move game.txt e4\n  correspondence move game.txt Nf6 --as black")]
+    Move {
+        file_path: PathBuf,
+        pgn_move: String,
+        /// The side you play.
+        #[arg(long = "as", value_parser = ["white", "black"])]
+        side: Option<String>,
+    },
+    /// Print the game and whose move it is.
+    Show { file_path: PathBuf },
+    /// Wait for the opponent's move, ringing the terminal bell once it is your turn.
+    Notify {
+        file_path: PathBuf,
+        /// The side you play.
+        #[arg(long = "as", value_parser = ["white", "black"])]
+        side: Option<String>,
+        /// How often to read the file, in seconds.
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "Rust Chess")]
 #[command(author = "Raul Rojas")]
 #[command(version = "1.0")]
 #[command(about = "The game of chess written in Rust!")]
+#[command(after_help = "Type help rules for the rules of castling, en passant, promotion, threefold repetition and the fifty move rule.")]
 pub struct ChessTuiCmd {
     #[command(subcommand)]
     pub command: ChessCommands,
 }
 
+impl ChessTuiCmd {
+    // Parses a command line. With prefixes, a command can be typed as any prefix naming only it,
+    // e.g. mo for move, as the command_prefixes setting turns on.
+    pub fn try_parse_line(args: Vec<String>, prefixes: bool) -> Result<ChessTuiCmd, clap::Error> {
+        let mut matches = ChessTuiCmd::command().infer_subcommands(prefixes).try_get_matches_from(args)?;
+        ChessTuiCmd::from_arg_matches_mut(&mut matches)
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum ChessCommands {
     /// Make a chess move.
-    #[command(long_about = "Examples:\n  e4\n  exd5\n  Nc3\n  e8=Q\n  O-O-O")]
+    #[command(long_about = "Pieces can be typed with the letters of the input_language setting, e.g. Sf3 for Nf3 in German. The game is kept in English notation.\nExamples:\n  e4\n  exd5\n  Nc3\n  e8=Q\n  O-O-O")]
     Move { pgn_move: String },
     /// Undo the last move or moves.
-    Undo { undo_count: Option<u8> },
+    Undo {
+        #[arg(default_value_t = 1)]
+        undo_count: u8,
+    },
     /// Redo the previously undon move or moves.
-    Redo { redo_count: Option<u8> },
+    Redo {
+        #[arg(default_value_t = 1)]
+        redo_count: u8,
+    },
+    /// Step forward through the game's moves without changing them.
+    Next {
+        #[arg(default_value_t = 1)]
+        count: u16,
+    },
+    /// Step back through the game's moves without changing them.
+    Prev {
+        #[arg(default_value_t = 1)]
+        count: u16,
+    },
+    /// Show the position after the given number of half moves, or after a move like 12w.
+    #[command(long_about = "Shows the position after the given number of half moves, counted from the start of the game, or after White's or Black's move of a move number. Going past the last move replays moves taken back with undo.\nExamples:\n  goto 0\n  goto 12\n  goto 12w\n  goto 12b")]
+    Goto {
+        #[arg(value_parser = parse_goto_ply)]
+        ply: usize,
+    },
+    /// List, enter or promote the variations kept when moves are taken back and others played.
+    #[command(long_about = "Taking back moves and playing other ones keeps the moves taken back as a variation. Entering a variation plays it in place of the line it branches off from, which is kept as a variation in turn. Promoting does the same and shows the position where the lines split.\nExamples:\n  variation list\n  variation enter 2\n  variation promote 1")]
+    Variation {
+        #[command(subcommand)]
+        action: VariationAction,
+    },
+    /// Mark a move with !, ?, !!, ??, !? or ?!, or with a numeric annotation glyph.
+    #[command(long_about = "Marks the last move, or the one shown when stepping through the game, with a move assessment (!, ?, !!, ??, !?, ?!) or a numeric annotation glyph, e.g. $14 for White being slightly better. A new assessment replaces the old one, clear takes them all off. The marks are shown in the move list and saved with the game.\nExamples:\n  annotate-move !?\n  annotate-move '$14'\n  annotate-move ?? --ply 12b\n  annotate-move clear")]
+    AnnotateMove {
+        glyph: String,
+        /// The move to mark, as half moves or a move number followed by w or b.
+        #[arg(long, value_parser = parse_goto_ply)]
+        ply: Option<usize>,
+    },
+    /// Show the starting position of the game.
+    Start,
+    /// Return to the current position of the game.
+    End,
     /// Reset the board.
     Reset,
+    /// Start a new game, on your own or against the computer.
+    #[command(long_about = "Starts a new game in place of the current one. Against the computer, the configured engine_path is played, or the built-in engine when there is none.\nExamples:\n  new\n  new --opponent Magnus\n  new --vs-engine\n  new --vs-engine --black --movetime 3000")]
+    New {
+        /// Play against the computer.
+        #[arg(long)]
+        vs_engine: bool,
+        /// Play Black against the computer.
+        #[arg(long, requires = "vs_engine")]
+        black: bool,
+        /// Milliseconds the computer thinks per move, instead of searching to the configured depth.
+        #[arg(long, requires = "vs_engine")]
+        movetime: Option<u64>,
+        /// The name of the player playing Black against you, so the game is rated.
+        #[arg(long, conflicts_with = "vs_engine")]
+        opponent: Option<String>,
+    },
+    /// Show the board.
+    Show {
+        /// Show the board from Black's side this once.
+        #[arg(long)]
+        flip: bool,
+    },
+    /// Turn the board around, so the side at the top is shown at the bottom from now on.
+    Flip,
+    /// Highlight the squares the piece on a square can move to.
+    #[command(long_about = "Highlights the squares the piece on the given square can legally move to.\nExamples:\n  select e2\n  select g1")]
+    Select { square: String },
+    /// List how the shown position differs from a FEN string.
+    #[command(long_about = "Lists the squares where the shown position and the given FEN differ, then the side to move, castling rights, en passant square and move clocks when they differ. Each line reads as the board's, then the FEN's. The FEN needs no quotes.\nExamples:\n  diff rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\n  diff 8/8/8/8/8/8/8/K6k w - -")]
+    Diff {
+        #[arg(required = true, allow_hyphen_values = true)]
+        fen: Vec<String>,
+    },
+    /// Read out a square, rank or file, or the whole position.
+    #[command(long_about = "Tells what stands on a square, the pieces on a rank or file, or without an argument every piece on the board and the side to move, in words for screen readers.\nExamples:\n  read\n  read e4\n  read 1\n  read g")]
+    Read { target: Option<String> },
     /// Save the current game into a PGN file.
-    Save { file_path: String },
+    #[command(long_about = "Saves the current game into a PGN file. With --session the whole session is saved instead, as JSON: the game with the moves taken back, the clocks, the computer opponent and the board's orientation, which PGN can't keep.\nExamples:\n  save game.pgn\n  save --force game.pgn\n  save --session game.json")]
+    Save {
+        file_path: PathBuf,
+        /// Overwrite the file if it already exists.
+        #[arg(long)]
+        force: bool,
+        /// Save the whole session rather than the game as PGN.
+        #[arg(long)]
+        session: bool,
+    },
     /// Load a game from a PGN file.
-    Load { file_path: String },
+    #[command(long_about = "Loads a game from a PGN file, or with --session a session saved by save --session, picking it up as it was left.\nExamples:\n  load game.pgn\n  load --session game.json")]
+    Load {
+        file_path: PathBuf,
+        /// Load a session saved by save --session.
+        #[arg(long)]
+        session: bool,
+    },
+    /// Export the shown position for pasting elsewhere.
+    Export {
+        #[command(subcommand)]
+        action: ExportAction,
+    },
+    /// Import a session written by export session, replacing the open games, settings and ratings.
+    #[command(long_about = "Reads a session written by export session: its open games take the place of the open games, with their clocks and computer opponents, its settings are used until the program ends, or kept with config save, and its ratings replace the players' ratings.\nExamples:\n  import session.json")]
+    Import { file_path: PathBuf },
+    /// End the current game with the given result.
+    #[command(long_about = "Ends the current game with the given result and an optional reason.\nExamples:\n  adjudicate 1-0\n  adjudicate 1/2-1/2 Agreed by arbiter\n  adjudicate 0-1 White abandoned the game")]
+    Adjudicate {
+        /// 1-0, 0-1 or 1/2-1/2.
+        #[arg(value_parser = parse_game_result)]
+        result: PgnResult,
+        reason: Vec<String>,
+    },
+    /// Resign the game, for the side to move or, against the computer, for you.
+    Resign,
+    /// End the game in a draw agreed by both players.
+    Draw,
+    /// Claim a draw by threefold repetition or the fifty move rule.
+    #[command(long_about = "Claims a draw for the side to move, by threefold repetition when the position is on the board for the third time with the same side to move, or by the fifty move rule when fifty moves of each side have been played without a capture or a pawn move. As over the board, the claim can be made with the move that brings it about: the move is played, and the game is drawn if the position after it makes the claim right. A wrong claim leaves the game going. The game is drawn without a claim at the fifth repetition and after seventy-five moves.\nExamples:\n  claim draw\n  claim draw Nf3")]
+    Claim {
+        #[command(subcommand)]
+        action: ClaimAction,
+    },
+    /// List the keys of the full screen interface.
+    #[command(long_about = "Lists what each key of the full screen interface does. The keys can be changed in the [keys] section of the config file, each action set to a list of keys such as ctrl-f, alt-left, pagedown, f5 or h; actions left out keep their default keys. Ctrl-C and Ctrl-D always quit.\nExamples:\n  keys")]
+    Keys,
+    /// Show, change or save the settings.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Watch two engines play each other.
+    #[command(long_about = "Starts a new game between two UCI engines and shows their moves as they are played.\nThe engines default to the configured engine_path.\nExamples:\n  watch\n  watch --white /usr/bin/stockfish --black /usr/games/fruit --delay 500\n  watch --movetime 2000")]
+    Watch {
+        /// The engine playing White.
+        #[arg(long)]
+        white: Option<PathBuf>,
+        /// The engine playing Black.
+        #[arg(long)]
+        black: Option<PathBuf>,
+        /// Milliseconds to wait after each move.
+        #[arg(long, default_value_t = 1000)]
+        delay: u64,
+        /// Milliseconds the engines think per move, instead of searching to the configured depth.
+        #[arg(long)]
+        movetime: Option<u64>,
+    },
+    /// Analyze the shown position with an engine until stopped.
+    #[command(long_about = "Analyzes the shown position with the configured engine_path, or the built-in engine when there is none, showing the depth, the evaluation from White's side and the best line. The analysis follows the position as moves are made or the game is stepped through.\nExamples:\n  analyze\n  analyze --multipv 3")]
+    Analyze {
+        /// How many of the best moves to show, each with its evaluation and line.
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=10))]
+        multipv: u32,
+    },
+    /// Serve the current game over WebSocket or HTTP, for a browser, a bot or a dashboard.
+    #[command(long_about = "Serves the current game on the given ports.\nWebSocket clients exchange JSON messages: they send {\"type\": \"move\", \"move\": \"Nf3\"} to move and {\"type\": \"state\"} to ask for the game, and get the board, moves and clocks in a state message whenever the game changes. The first client to connect plays Black, or White with --white, and against the computer plays in your place. Others watch.\nThe HTTP API answers GET /state, /fen, /moves (the legal moves), /pgn and /analysis?movetime=<ms>, and POST /move with {\"move\": \"Nf3\"}.\nExamples:\n  serve --ws 8080\n  serve --ws 8080 --white --host 0.0.0.0\n  serve --http 8081")]
+    #[command(group(clap::ArgGroup::new("ports").required(true).multiple(true).args(["ws", "http"])))]
+    Serve {
+        /// The port to listen on for WebSocket clients.
+        #[arg(long)]
+        ws: Option<u16>,
+        /// The port to listen on for HTTP requests.
+        #[arg(long)]
+        http: Option<u16>,
+        /// The address to listen on, 0.0.0.0 lets other computers connect.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// The WebSocket player plays White.
+        #[arg(long, requires = "ws")]
+        white: bool,
+    },
+    /// Play the analyzing engine's best move.
+    Best,
+    /// Stop watching the engines, analyzing or serving the game, the game so far is kept.
+    Stop,
+    /// Measure how much move ordering saves the built-in engine's search.
+    #[command(long_about = "Searches a set of positions to the given depth with the built-in engine, without and with move ordering, and compares the positions searched and the time taken.\nExamples:\n  bench\n  bench 4")]
+    Bench {
+        #[arg(default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..=6))]
+        depth: u32,
+    },
+    /// Annotate a game with an engine and save it to a PGN file.
+    #[command(long_about = "Searches every position of the current game, or of the first game in the PGN file given with --from, with the configured engine_path or the built-in engine when there is none. Each move gets the evaluation after it, inaccuracies, mistakes and blunders are marked along with the better line, and the accuracy of each player is summed up at the start of the game.\nExamples:\n  annotate annotated.pgn\n  annotate annotated.pgn --from games.pgn --movetime 500")]
+    Annotate {
+        file_path: PathBuf,
+        /// Annotate the first game of this PGN file instead of the current game.
+        #[arg(long)]
+        from: Option<PathBuf>,
+        /// Milliseconds the engine thinks per position, instead of searching to the configured depth.
+        #[arg(long)]
+        movetime: Option<u64>,
+        /// Overwrite the file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Download a chess.com player's games into the game database.
+    #[command(long_about = "Downloads the monthly game archives of a chess.com player through the public API and adds the games to the PGN file given with --to, or the configured database_path. Games already in the file are left out, so importing again only adds new games.\nExamples:\n  import-archive hikaru\n  import-archive hikaru --since 2024/01 --to hikaru.pgn")]
+    ImportArchive {
+        username: String,
+        /// The PGN file to add the games to.
+        #[arg(long)]
+        to: Option<PathBuf>,
+        /// Only download the months from this one on, as YYYY/MM.
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Show the moves played from the shown position in the game database, and play one of them.
+    #[command(long_about = "Lists the moves played from the shown position in the games of the configured database_path, or of the PGN file given with --from, with how many games each was played in and how often White won, the game was drawn and Black won, the most played first. Given a move, by its number in the list or in SAN, explore plays it and lists the moves played after it; prev and undo go back up. Games without a result are left out.\nExamples:\n  explore\n  explore 1\n  explore Nf3\n  explore --from masters.pgn")]
+    Explore {
+        /// The move to play, by its number in the list or in SAN.
+        choice: Option<String>,
+        /// The PGN file to explore instead of the game database.
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// Learn how the pieces move and how moves are written, with exercises on the board.
+    #[command(long_about = "A guided tutorial for beginners: how each piece moves, check and checkmate, castling, en passant, promotion and notation. Each step sets up a position and asks for a move, made with the move command as in a game.\nExamples:\n  tutorial start\n  tutorial start 3\n  tutorial hint\n  tutorial list")]
+    Tutorial {
+        #[command(subcommand)]
+        action: TutorialAction,
+    },
+    /// Drill the lines of an opening repertoire, the computer playing the other side.
+    #[command(long_about = "Drills the lines of the repertoire in a PGN file, its main lines and variations. The computer plays the other side's moves from the repertoire, the lines you know least first, and you answer with the move command. A move the repertoire doesn't have is taken back and counts as a mistake. How often each line was played without a mistake is kept between sessions, stop ends the training.\nExamples:\n  train-opening white.pgn\n  train-opening sicilian.pgn --black")]
+    TrainOpening {
+        file_path: PathBuf,
+        /// Play the repertoire as Black.
+        #[arg(long)]
+        black: bool,
+    },
+    /// Solve as many puzzles as you can against the clock.
+    #[command(long_about = "Plays puzzles getting harder from the puzzles.csv file in the data directory, in the format of the Lichess puzzle database, or from a few built-in ones without it. Find the best move with the move command, a wrong move is a strike and three strikes or the end of the time end the rush. The score and the solutions of the puzzles missed are shown at the end.\nExamples:\n  puzzle-rush\n  puzzle-rush --minutes 5")]
+    PuzzleRush {
+        /// The minutes the rush lasts.
+        #[arg(long, default_value_t = 3, value_parser = clap::builder::PossibleValuesParser::new(["3", "5"]).map(|m| m.parse::<u64>().unwrap()))]
+        minutes: u64,
+    },
+    /// Show the players' ratings, or one player's.
+    #[command(long_about = "Shows the Elo ratings of the players of rated games, highest first, or the rating of the player given. Games are rated when they end if both players are known: your configured player_name, the player named with new --opponent, or the computer at the depth or move time it plays with.\nExamples:\n  rating\n  rating Raul Rojas")]
+    Rating { player: Vec<String> },
+    /// Open another game or switch between the open games.
+    Game {
+        #[command(subcommand)]
+        action: GameAction,
+    },
+    /// Play the computer on several boards at once.
+    #[command(long_about = "Opens a game against the computer for every board, with you as White on each, keeping the current game open. The computer answers on a board as soon as you move there. Once every board is finished your score is told.\nExamples:\n  simul 4\n  simul 6 --movetime 200")]
+    Simul {
+        #[arg(value_parser = clap::value_parser!(u64).range(2..=10).map(|n| n as usize))]
+        boards: usize,
+        /// Milliseconds the computer thinks per move, instead of searching to the configured depth.
+        #[arg(long)]
+        movetime: Option<u64>,
+    },
+    /// Go to another board of the simul, by its number, or to the next one waiting for your move.
+    #[command(long_about = "Examples:\n  board\n  board 3")]
+    Board { number: Option<usize> },
     /// Quit the game. Warning: Unsaved progress will be lost.
     Quit,
-}
\ No newline at end of file
+}
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Show the current settings.
+    Show,
+    /// Change a setting for this session. Leave the value out to unset it.
+    #[command(long_about = "Changes a setting for this session, use config save to keep it.\nExamples:\n  config set theme blue\n  config set pieces ascii\n  config set player_name Raul Rojas\n  config set engine_path")]
+    Set { setting: String, value: Vec<String> },
+    /// Write the current settings to the config file.
+    Save,
+}
+#[derive(Subcommand, Debug)]
+pub enum VariationAction {
+    /// List the variations of the game, numbered.
+    List,
+    /// Play a variation, continuing from its last move.
+    Enter { number: usize },
+    /// Make a variation the line played, showing where it branches off.
+    Promote { number: usize },
+}
+#[derive(Subcommand, Debug)]
+pub enum ClaimAction {
+    /// Claim a draw, in the current position or with the move that leads to it.
+    Draw { pgn_move: Option<String> },
+}
+#[derive(Subcommand, Debug)]
+pub enum ExportAction {
+    /// A text diagram of the shown position, with coordinates and no colors.
+    #[command(long_about = "Draws the shown position as a plain text diagram with coordinates and the side to move, for pasting into forums, emails and issue reports. Pieces are letters unless --unicode is given. The diagram is shown, or written to the given file.\nExamples:\n  export diagram\n  export diagram --unicode --flip\n  export diagram position.txt")]
+    Diagram {
+        file_path: Option<PathBuf>,
+        /// Draw with chess symbols and box drawing characters.
+        #[arg(long)]
+        unicode: bool,
+        /// Draw the board from Black's side.
+        #[arg(long)]
+        flip: bool,
+        /// Overwrite the file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+    /// The game as LaTeX for the xskak package, with diagrams of chosen positions.
+    #[command(long_about = "Writes the game as a LaTeX snippet for the xskak package, for printed study material and club bulletins. Comments are set as text between the moves and a diagram is drawn after each half move given with --diagram, or of the starting position for 0. Variations are left out. The snippet is shown, or written to the given file.\nExamples:\n  export latex game.tex\n  export latex game.tex --diagram 12w --diagram 20b\n  export latex --diagram 0")]
+    Latex {
+        file_path: Option<PathBuf>,
+        /// Draw a diagram after this move, as half moves or a move number followed by w or b.
+        #[arg(long = "diagram", value_parser = parse_goto_ply)]
+        diagrams: Vec<usize>,
+        /// Overwrite the file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Every open game, the settings and the ratings, to back them up or move them elsewhere.
+    #[command(long_about = "Writes the whole session to a JSON file: every open game with its clocks, computer opponent and moves taken back, the settings and the players' ratings. import reads it back, on this machine or another.\nExamples:\n  export session session.json\n  export session backup/session.json --force")]
+    Session {
+        file_path: PathBuf,
+        /// Overwrite the file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+}
+#[derive(Subcommand, Debug)]
+pub enum TutorialAction {
+    /// Start the tutorial, from the first lesson or the one given by its number.
+    Start { lesson: Option<usize> },
+    /// List the lessons.
+    List,
+    /// Get a hint for the current exercise.
+    Hint,
+    /// Skip the current exercise.
+    Skip,
+    /// Leave the tutorial and return to the game.
+    Stop,
+}
+#[derive(Subcommand, Debug)]
+pub enum GameAction {
+    /// Start another game, keeping the current one open.
+    New,
+    /// List the open games.
+    List,
+    /// Continue with another open game, by its number in the list.
+    Switch { number: usize },
+}
+
+pub enum CommandOutcome {
+    Continue,
+    Quit,
+}
+
+// Implemented by anything that can carry out the commands declared in ChessCommands. Commands
+// are declared once in ChessCommands and each frontend only provides the handling.
+pub trait CommandDispatcher {
+    fn dispatch(&mut self, command: ChessCommands) -> CommandOutcome;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CommandLineError {
+    UnterminatedQuote,
+}
+
+impl std::fmt::Display for CommandLineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandLineError::UnterminatedQuote => write!(f, "missing closing quote"),
+        }
+    }
+}
+
+impl std::error::Error for CommandLineError {}
+
+// Splits a line of user input into arguments the way a shell would. Whitespace separates
+// arguments unless it is inside single or double quotes. Inside double quotes \" is a literal
+// quote; every other backslash is kept as typed so Windows paths don't need escaping.
+pub fn split_command_line(line: &str) -> Result<Vec<String>, CommandLineError> {
+    let mut args: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+                else if q == '"' && c == '\\' && chars.peek() == Some(&'"') {
+                    current.push('"');
+                    chars.next();
+                }
+                else {
+                    current.push(c);
+                }
+            }
+            None => {
+                if c.is_whitespace() {
+                    if in_arg {
+                        args.push(std::mem::take(&mut current));
+                        in_arg = false;
+                    }
+                    continue;
+                }
+                in_arg = true;
+                match c {
+                    '"' | '\'' => quote = Some(c),
+                    _ => current.push(c),
+                }
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(CommandLineError::UnterminatedQuote);
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+// The half moves played up to a goto target, a number of half moves or a move number followed
+// by w or b for White's or Black's move, e.g. 12w is the 23rd half move.
+fn parse_goto_ply(target: &str) -> Result<usize, String> {
+    let invalid = || format!("expected a number of half moves or a move number followed by w or b, e.g. 12w, not {target}");
+    let target = target.to_lowercase();
+    let (number, side) = match target.strip_suffix(['w', 'b']) {
+        Some(number) => (number, target.chars().last()),
+        None => (target.as_str(), None),
+    };
+    let number: usize = number.parse().map_err(|_| invalid())?;
+    match side {
+        None => Ok(number),
+        Some('w') => number.checked_mul(2).and_then(|n| n.checked_sub(1)).ok_or_else(invalid),
+        Some(_) if number == 0 => Err(invalid()),
+        Some(_) => number.checked_mul(2).ok_or_else(invalid),
+    }
+}
+
+// A date given as a year, year and month or a full date, separated by dots, slashes or dashes.
+// The parts left out are unknown.
+fn parse_pgn_date(date: &str) -> Result<PgnDate, String> {
+    let mut parts: Vec<&str> = date.split(['.', '/', '-']).collect();
+    if parts.len() > 3 || parts.iter().any(|p| p.is_empty() || p.contains('?')) {
+        return Err(format!("expected a date like 2024, 2024.05 or 2024.05.01, not {date}"));
+    }
+    parts.resize(3, "??");
+    PgnDate::from(&parts.join(".")).ok_or_else(|| format!("expected a date like 2024, 2024.05 or 2024.05.01, not {date}"))
+}
+
+// Reads the result a game is adjudicated with, a game can't be ended as unfinished.
+fn parse_game_result(result: &str) -> Result<PgnResult, String> {
+    match PgnResult::from(result) {
+        Some(PgnResult::Unknown) | None => Err(format!("expected 1-0, 0-1 or 1/2-1/2, not {result}")),
+        Some(r) => Ok(r),
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_command_line_splitting {
+    use super::*;
+
+    #[test]
+    pub fn splits_on_whitespace() {
+        assert_eq!(split_command_line("  move   e4 \n").unwrap(), vec!["move", "e4"]);
+        assert!(split_command_line("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn quoted_argument_is_kept_whole() {
+        assert_eq!(
+            split_command_line("tag Event \"Club Championship 2024\"").unwrap(),
+            vec!["tag", "Event", "Club Championship 2024"]
+        );
+        assert_eq!(
+            split_command_line("save 'my games/game 1.pgn'").unwrap(),
+            vec!["save", "my games/game 1.pgn"]
+        );
+    }
+
+    #[test]
+    pub fn empty_quotes_are_an_argument() {
+        assert_eq!(split_command_line("tag Site \"\"").unwrap(), vec!["tag", "Site", ""]);
+    }
+
+    #[test]
+    pub fn escaped_quotes_and_backslashes() {
+        assert_eq!(split_command_line(r#"say "a \"quoted\" word""#).unwrap(), vec!["say", "a \"quoted\" word"]);
+        assert_eq!(split_command_line(r"load C:\games\x.pgn").unwrap(), vec!["load", r"C:\games\x.pgn"]);
+        assert_eq!(split_command_line(r#"load "C:\My Games\x.pgn""#).unwrap(), vec!["load", r"C:\My Games\x.pgn"]);
+    }
+
+    #[test]
+    pub fn unterminated_quote_fails() {
+        assert_eq!(split_command_line("tag Event \"Club"), Err(CommandLineError::UnterminatedQuote));
+    }
+}
+
+#[cfg(test)]
+mod test_adjudicate_result {
+    use super::*;
+
+    fn parse(line: &str) -> Result<ChessTuiCmd, clap::Error> {
+        ChessTuiCmd::try_parse_line(split_command_line(&format!("chess {line}")).unwrap(), false)
+    }
+
+    #[test]
+    pub fn result_is_parsed() {
+        let cmd = parse("adjudicate 1/2-1/2 Agreed by arbiter").unwrap();
+        match cmd.command {
+            ChessCommands::Adjudicate { result, reason } => {
+                assert_eq!(result, PgnResult::Draw);
+                assert_eq!(reason, ["Agreed", "by", "arbiter"]);
+            }
+            _ => panic!("expected the adjudicate command"),
+        }
+    }
+
+    #[test]
+    pub fn other_results_are_rejected() {
+        for result in ["*", "1-1", "draw", "½-½"] {
+            assert!(parse(&format!("adjudicate {result}")).is_err(), "{result} was accepted");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_goto_ply {
+    use super::*;
+
+    #[test]
+    pub fn move_numbers_give_half_moves() {
+        assert_eq!(parse_goto_ply("12"), Ok(12));
+        assert_eq!(parse_goto_ply("12w"), Ok(23));
+        assert_eq!(parse_goto_ply("12B"), Ok(24));
+        assert!(parse_goto_ply("0w").is_err());
+        assert!(parse_goto_ply("0b").is_err());
+    }
+
+    #[test]
+    pub fn move_numbers_too_large_are_refused() {
+        assert!(parse_goto_ply("9999999999999999999w").is_err());
+        assert!(parse_goto_ply("9999999999999999999b").is_err());
+    }
+}
@@ -11,6 +11,7 @@ pub enum ChessTurn {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ChessPiece {
     Pawn,
     Knight,
@@ -46,15 +47,40 @@ impl ChessPiece {
             _ => None,
         }
     }
+
+    // The conventional material value of the piece in pawns. The king is not counted.
+    pub fn get_value(&self) -> u32 {
+        match self {
+            ChessPiece::Pawn => 1,
+            ChessPiece::Knight => 3,
+            ChessPiece::Bishop => 3,
+            ChessPiece::Rook => 5,
+            ChessPiece::Queen => 9,
+            ChessPiece::King => 0,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ChessPiece::Pawn => "pawn",
+            ChessPiece::Knight => "knight",
+            ChessPiece::Bishop => "bishop",
+            ChessPiece::Rook => "rook",
+            ChessPiece::Queen => "queen",
+            ChessPiece::King => "king",
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ChessCastle {
     KingsideCastle,
     QueensideCastle,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ChessCoordinate {
     file: Option<ChessFile>,
     rank: Option<ChessRank>,
@@ -137,6 +163,7 @@ impl ChessCoordinate {
 
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ChessFile {
     A,
     B,
@@ -182,9 +209,24 @@ impl ChessFile {
     pub fn as_usize(self) -> usize {
         self as usize
     }
+
+    pub fn from_usize(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(ChessFile::A),
+            1 => Some(ChessFile::B),
+            2 => Some(ChessFile::C),
+            3 => Some(ChessFile::D),
+            4 => Some(ChessFile::E),
+            5 => Some(ChessFile::F),
+            6 => Some(ChessFile::G),
+            7 => Some(ChessFile::H),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ChessRank {
     R1,
     R2,
@@ -230,4 +272,18 @@ impl ChessRank {
     pub fn as_usize(self) -> usize {
         self as usize
     }
+
+    pub fn from_usize(index: usize) -> Option<ChessRank> {
+        match index {
+            0 => Some(ChessRank::R1),
+            1 => Some(ChessRank::R2),
+            2 => Some(ChessRank::R3),
+            3 => Some(ChessRank::R4),
+            4 => Some(ChessRank::R5),
+            5 => Some(ChessRank::R6),
+            6 => Some(ChessRank::R7),
+            7 => Some(ChessRank::R8),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,1169 @@
+/*
+chess_config.rs
+User settings read from a TOML file, by default ~/.config/rust-chess/config.toml. Every setting
+is optional in the file, missing ones keep their defaults.
+
+Example config.toml:
+theme = "blue"
+pieces = "ascii"
+glyph_width = "wide"
+board_size = "large"
+piece_shapes = true
+input_language = "german"
+command_prefixes = false
+language = "auto"
+screen_reader = true
+player_name = "Raul Rojas"
+autosave_path = "/home/raul/.local/share/rust-chess/autosave.pgn"
+autosave_on_move = true
+autosave_interval = 30
+database_path = "/home/raul/.local/share/rust-chess/games.pgn"
+ratings_path = "/home/raul/.local/share/rust-chess/ratings.toml"
+archive_dir = "/home/raul/.local/share/rust-chess/archive"
+time_control = "5+3"
+engine_path = "/usr/bin/stockfish"
+engine_depth = 12
+ponder = true
+blunder_alerts = true
+blunder_threshold = 200
+alert_bell = true
+alert_title = true
+alert_desktop = false
+low_time_alert = 30
+resign_threshold = 600
+resign_moves = 4
+draw_threshold = 10
+draw_moves = 8
+tablebase_adjudication = true
+max_game_moves = 200
+
+[keys]
+flip = ["ctrl-r"]
+prev = ["pageup", "alt-left"]
+next = ["pagedown", "alt-right"]
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::chess_clock::TimeControl;
+
+const DEFAULT_ENGINE_DEPTH: u32 = 10;
+const DEFAULT_AUTOSAVE_INTERVAL: u32 = 30;
+const DEFAULT_BLUNDER_THRESHOLD: u32 = 200;
+const DEFAULT_LOW_TIME_ALERT: u32 = 30;
+const DEFAULT_RESIGN_THRESHOLD: u32 = 600;
+const DEFAULT_DRAW_THRESHOLD: u32 = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChessConfig {
+    theme: BoardTheme,
+    pieces: PieceStyle,
+    // How many columns the terminal draws chess symbols in, measured when auto.
+    glyph_width: GlyphWidth,
+    board_size: BoardSize,
+    // Draw pieces as small pictures on the large board.
+    piece_shapes: bool,
+    // The piece letters moves are typed with.
+    input_language: InputLanguage,
+    // Commands can be typed as any prefix naming only them, e.g. mo for move.
+    command_prefixes: bool,
+    language: Language,
+    // Tell the position and moves in words instead of drawing the board.
+    screen_reader: bool,
+    player_name: Option<String>,
+    autosave_path: Option<PathBuf>,
+    // Autosave after every command that changes the game, and at most this many seconds after
+    // a change otherwise (0 for never).
+    autosave_on_move: bool,
+    autosave_interval: u32,
+    // The PGN file imported games are added to.
+    database_path: Option<PathBuf>,
+    // The file the players' ratings are kept in.
+    ratings_path: Option<PathBuf>,
+    // Where every finished game is appended, to a PGN file for each month.
+    archive_dir: Option<PathBuf>,
+    time_control: Option<TimeControl>,
+    engine_path: Option<PathBuf>,
+    engine_depth: u32,
+    // Let the computer opponent think while it is the player's turn.
+    ponder: bool,
+    // Warn about moves that lose at least this many centipawns against the engine's best, in
+    // games without a clock.
+    blunder_alerts: bool,
+    blunder_threshold: u32,
+    // How the player is told the opponent moved, gave check, or their clock is low: with the
+    // terminal bell, in the window title, and with a desktop notification.
+    alert_bell: bool,
+    alert_title: bool,
+    alert_desktop: bool,
+    // The seconds left on the player's clock that count as low (0 for never).
+    low_time_alert: u32,
+    // Adjudication of engine games: a side behind by resign_threshold centipawns for resign_moves
+    // moves in a row loses, and the game is drawn when the evaluation stays within
+    // draw_threshold for draw_moves moves, 0 moves turning either off. Elementary endings can be
+    // settled, and games drawn after max_game_moves moves (0 for no limit).
+    resign_threshold: u32,
+    resign_moves: u32,
+    draw_threshold: u32,
+    draw_moves: u32,
+    tablebase_adjudication: bool,
+    max_game_moves: u32,
+    // The keys of the full screen interface that differ from the defaults.
+    #[serde(skip_serializing_if = "KeyMap::is_empty")]
+    keys: KeyMap,
+}
+
+// Square colors of the board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardTheme {
+    #[default]
+    Green,
+    Brown,
+    Blue,
+    Gray,
+}
+
+// How big the squares of the board are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardSize {
+    #[default]
+    Normal,
+    Large,
+}
+
+// How pieces are drawn: chess symbols, or letters for terminals and fonts without them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PieceStyle {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+// The language of the piece letters in typed moves, e.g. Sf3 for Nf3 in German. Moves are kept
+// and saved in English SAN whatever the language.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputLanguage {
+    #[default]
+    English,
+    German,
+    Spanish,
+    French,
+    Italian,
+    Dutch,
+}
+
+// The width of chess symbols on the terminal. Many terminals and fonts draw them two columns wide
+// although Unicode gives them one, which skews the board. Auto measures the terminal in use, the
+// others override that for terminals that can't be asked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlyphWidth {
+    #[default]
+    Auto,
+    Narrow,
+    Wide,
+}
+
+// The language of the interface's messages. Auto takes the one of the environment, e.g. LANG.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Auto,
+    English,
+    German,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Serialize(toml::ser::Error),
+    UnknownSetting(String),
+    InvalidValue { setting: String, value: String },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "{}: {}", path.display(), e.to_string().trim_end()),
+            ConfigError::Serialize(e) => write!(f, "{}", e),
+            ConfigError::UnknownSetting(setting) => {
+                write!(f, "unknown setting {}, expected one of {}", setting, ChessConfig::SETTINGS.join(", "))
+            }
+            ConfigError::InvalidValue { setting, value } => write!(f, "{} is not a valid value for {}", value, setting),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Default for ChessConfig {
+    fn default() -> ChessConfig {
+        ChessConfig {
+            theme: BoardTheme::default(),
+            pieces: PieceStyle::default(),
+            glyph_width: GlyphWidth::default(),
+            board_size: BoardSize::default(),
+            piece_shapes: false,
+            input_language: InputLanguage::default(),
+            command_prefixes: true,
+            language: Language::default(),
+            screen_reader: false,
+            player_name: None,
+            autosave_path: None,
+            autosave_on_move: true,
+            autosave_interval: DEFAULT_AUTOSAVE_INTERVAL,
+            database_path: None,
+            ratings_path: None,
+            archive_dir: None,
+            time_control: None,
+            engine_path: None,
+            engine_depth: DEFAULT_ENGINE_DEPTH,
+            ponder: false,
+            blunder_alerts: false,
+            blunder_threshold: DEFAULT_BLUNDER_THRESHOLD,
+            alert_bell: false,
+            alert_title: false,
+            alert_desktop: false,
+            low_time_alert: DEFAULT_LOW_TIME_ALERT,
+            resign_threshold: DEFAULT_RESIGN_THRESHOLD,
+            resign_moves: 0,
+            draw_threshold: DEFAULT_DRAW_THRESHOLD,
+            draw_moves: 0,
+            tablebase_adjudication: false,
+            max_game_moves: 0,
+            keys: KeyMap::default(),
+        }
+    }
+}
+
+impl Display for ChessConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<String> = ChessConfig::SETTINGS.iter()
+            .map(|s| format!("{} = {}", s, self.get(s).unwrap_or_default()))
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl ChessConfig {
+    // Names of the settings, as written in the file and taken by `config set`.
+    pub const SETTINGS: [&'static str; 32] = [
+        "theme",
+        "pieces",
+        "glyph_width",
+        "board_size",
+        "piece_shapes",
+        "input_language",
+        "command_prefixes",
+        "language",
+        "screen_reader",
+        "player_name",
+        "autosave_path",
+        "autosave_on_move",
+        "autosave_interval",
+        "database_path",
+        "ratings_path",
+        "archive_dir",
+        "time_control",
+        "engine_path",
+        "engine_depth",
+        "ponder",
+        "blunder_alerts",
+        "blunder_threshold",
+        "alert_bell",
+        "alert_title",
+        "alert_desktop",
+        "low_time_alert",
+        "resign_threshold",
+        "resign_moves",
+        "draw_threshold",
+        "draw_moves",
+        "tablebase_adjudication",
+        "max_game_moves",
+    ];
+
+    pub fn new() -> ChessConfig {
+        ChessConfig::default()
+    }
+
+    // The config file used when none is given: $XDG_CONFIG_HOME/rust-chess/config.toml, falling
+    // back to ~/.config on Unix and %APPDATA% on Windows.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(config_dir.join("rust-chess").join("config.toml"))
+    }
+
+    // The autosave file used when none is configured, autosave.pgn in the data directory.
+    pub fn default_autosave_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("autosave.pgn"))
+    }
+
+    // The game database used when none is configured, games.pgn next to the autosave file.
+    pub fn default_database_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("games.pgn"))
+    }
+
+    // The ratings file used when none is configured, ratings.toml in the data directory.
+    pub fn default_ratings_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("ratings.toml"))
+    }
+
+    // The opening training record, training.toml in the data directory.
+    pub fn default_training_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("training.toml"))
+    }
+
+    // The puzzles of puzzle rush, puzzles.csv in the data directory.
+    pub fn default_puzzles_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("puzzles.csv"))
+    }
+
+    // The commands typed at the line mode prompt, history.txt in the data directory.
+    pub fn default_history_path() -> Option<PathBuf> {
+        Some(data_dir()?.join("history.txt"))
+    }
+
+    // Where games are saved when the program crashes, the recovery directory in the data
+    // directory.
+    pub fn default_recovery_dir() -> Option<PathBuf> {
+        Some(data_dir()?.join("recovery"))
+    }
+
+    // The game archive used when none is configured, the archive directory in the data directory.
+    pub fn default_archive_dir() -> Option<PathBuf> {
+        Some(data_dir()?.join("archive"))
+    }
+
+    pub fn from(toml_str: &str) -> Result<ChessConfig, toml::de::Error> {
+        toml::from_str(toml_str)
+    }
+
+    pub fn load(path: &Path) -> Result<ChessConfig, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        ChessConfig::from(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    // Writes the settings to a file, creating any missing parent directories.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let text = toml::to_string(self).map_err(ConfigError::Serialize)?;
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| ConfigError::Io(parent.to_path_buf(), e))?;
+            }
+        }
+        fs::write(path, text).map_err(|e| ConfigError::Io(path.to_path_buf(), e))
+    }
+
+    // Gets a setting by name as text, None for an unknown setting and an empty string for an
+    // unset optional one.
+    pub fn get(&self, setting: &str) -> Option<String> {
+        let path_text = |p: &Option<PathBuf>| p.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+        let value = match setting {
+            "theme" => self.theme.to_string(),
+            "pieces" => self.pieces.to_string(),
+            "glyph_width" => self.glyph_width.to_string(),
+            "board_size" => self.board_size.to_string(),
+            "piece_shapes" => self.piece_shapes.to_string(),
+            "input_language" => self.input_language.to_string(),
+            "command_prefixes" => self.command_prefixes.to_string(),
+            "language" => self.language.to_string(),
+            "screen_reader" => self.screen_reader.to_string(),
+            "player_name" => self.player_name.clone().unwrap_or_default(),
+            "autosave_path" => path_text(&self.autosave_path),
+            "autosave_on_move" => self.autosave_on_move.to_string(),
+            "autosave_interval" => self.autosave_interval.to_string(),
+            "database_path" => path_text(&self.database_path),
+            "ratings_path" => path_text(&self.ratings_path),
+            "archive_dir" => path_text(&self.archive_dir),
+            "time_control" => self.time_control.as_ref().map(|t| t.to_string()).unwrap_or_default(),
+            "engine_path" => path_text(&self.engine_path),
+            "engine_depth" => self.engine_depth.to_string(),
+            "ponder" => self.ponder.to_string(),
+            "blunder_alerts" => self.blunder_alerts.to_string(),
+            "blunder_threshold" => self.blunder_threshold.to_string(),
+            "alert_bell" => self.alert_bell.to_string(),
+            "alert_title" => self.alert_title.to_string(),
+            "alert_desktop" => self.alert_desktop.to_string(),
+            "low_time_alert" => self.low_time_alert.to_string(),
+            "resign_threshold" => self.resign_threshold.to_string(),
+            "resign_moves" => self.resign_moves.to_string(),
+            "draw_threshold" => self.draw_threshold.to_string(),
+            "draw_moves" => self.draw_moves.to_string(),
+            "tablebase_adjudication" => self.tablebase_adjudication.to_string(),
+            "max_game_moves" => self.max_game_moves.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // Sets a setting by name from text. An empty value unsets an optional setting.
+    pub fn set(&mut self, setting: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue { setting: String::from(setting), value: String::from(value) };
+        let optional = |v: &str| if v.is_empty() { None } else { Some(String::from(v)) };
+        match setting {
+            "theme" => self.theme = BoardTheme::from(value).ok_or_else(invalid)?,
+            "pieces" => self.pieces = PieceStyle::from(value).ok_or_else(invalid)?,
+            "glyph_width" => self.glyph_width = GlyphWidth::from(value).ok_or_else(invalid)?,
+            "board_size" => self.board_size = BoardSize::from(value).ok_or_else(invalid)?,
+            "piece_shapes" => self.piece_shapes = parse_switch(value).ok_or_else(invalid)?,
+            "input_language" => self.input_language = InputLanguage::from(value).ok_or_else(invalid)?,
+            "command_prefixes" => self.command_prefixes = parse_switch(value).ok_or_else(invalid)?,
+            "language" => self.language = Language::from(value).ok_or_else(invalid)?,
+            "screen_reader" => self.screen_reader = parse_switch(value).ok_or_else(invalid)?,
+            "player_name" => self.player_name = optional(value),
+            "autosave_path" => self.autosave_path = optional(value).map(PathBuf::from),
+            "autosave_on_move" => self.autosave_on_move = parse_switch(value).ok_or_else(invalid)?,
+            "autosave_interval" => self.autosave_interval = value.parse().map_err(|_| invalid())?,
+            "database_path" => self.database_path = optional(value).map(PathBuf::from),
+            "ratings_path" => self.ratings_path = optional(value).map(PathBuf::from),
+            "archive_dir" => self.archive_dir = optional(value).map(PathBuf::from),
+            "time_control" => {
+                self.time_control = match value {
+                    "" => None,
+                    v => Some(TimeControl::from(v).ok_or_else(invalid)?),
+                }
+            }
+            "engine_path" => self.engine_path = optional(value).map(PathBuf::from),
+            "engine_depth" => self.engine_depth = value.parse().ok().filter(|d| *d > 0).ok_or_else(invalid)?,
+            "ponder" => self.ponder = parse_switch(value).ok_or_else(invalid)?,
+            "blunder_alerts" => self.blunder_alerts = parse_switch(value).ok_or_else(invalid)?,
+            "blunder_threshold" => self.blunder_threshold = value.parse().ok().filter(|t| *t > 0).ok_or_else(invalid)?,
+            "alert_bell" => self.alert_bell = parse_switch(value).ok_or_else(invalid)?,
+            "alert_title" => self.alert_title = parse_switch(value).ok_or_else(invalid)?,
+            "alert_desktop" => self.alert_desktop = parse_switch(value).ok_or_else(invalid)?,
+            "low_time_alert" => self.low_time_alert = value.parse().map_err(|_| invalid())?,
+            "resign_threshold" => self.resign_threshold = value.parse().ok().filter(|t| *t > 0).ok_or_else(invalid)?,
+            "resign_moves" => self.resign_moves = value.parse().map_err(|_| invalid())?,
+            "draw_threshold" => self.draw_threshold = value.parse().map_err(|_| invalid())?,
+            "draw_moves" => self.draw_moves = value.parse().map_err(|_| invalid())?,
+            "tablebase_adjudication" => self.tablebase_adjudication = parse_switch(value).ok_or_else(invalid)?,
+            "max_game_moves" => self.max_game_moves = value.parse().map_err(|_| invalid())?,
+            _ => return Err(ConfigError::UnknownSetting(String::from(setting))),
+        }
+        Ok(())
+    }
+
+    pub fn get_theme(&self) -> BoardTheme {
+        self.theme
+    }
+
+    pub fn get_pieces(&self) -> PieceStyle {
+        self.pieces
+    }
+
+    pub fn get_glyph_width(&self) -> GlyphWidth {
+        self.glyph_width
+    }
+
+    pub fn get_board_size(&self) -> BoardSize {
+        self.board_size
+    }
+
+    pub fn get_piece_shapes(&self) -> bool {
+        self.piece_shapes
+    }
+
+    pub fn get_input_language(&self) -> InputLanguage {
+        self.input_language
+    }
+
+    pub fn get_command_prefixes(&self) -> bool {
+        self.command_prefixes
+    }
+
+    pub fn get_language(&self) -> Language {
+        self.language
+    }
+
+    pub fn get_screen_reader(&self) -> bool {
+        self.screen_reader
+    }
+
+    pub fn get_player_name(&self) -> Option<&String> {
+        self.player_name.as_ref()
+    }
+
+    pub fn get_autosave_path(&self) -> Option<&PathBuf> {
+        self.autosave_path.as_ref()
+    }
+
+    pub fn get_autosave_on_move(&self) -> bool {
+        self.autosave_on_move
+    }
+
+    pub fn get_autosave_interval(&self) -> u32 {
+        self.autosave_interval
+    }
+
+    pub fn get_database_path(&self) -> Option<&PathBuf> {
+        self.database_path.as_ref()
+    }
+
+    pub fn get_ratings_path(&self) -> Option<&PathBuf> {
+        self.ratings_path.as_ref()
+    }
+
+    pub fn get_archive_dir(&self) -> Option<&PathBuf> {
+        self.archive_dir.as_ref()
+    }
+
+    pub fn get_time_control(&self) -> Option<TimeControl> {
+        self.time_control.clone()
+    }
+
+    pub fn get_engine_path(&self) -> Option<&PathBuf> {
+        self.engine_path.as_ref()
+    }
+
+    pub fn get_engine_depth(&self) -> u32 {
+        self.engine_depth
+    }
+
+    pub fn get_ponder(&self) -> bool {
+        self.ponder
+    }
+
+    pub fn get_blunder_alerts(&self) -> bool {
+        self.blunder_alerts
+    }
+
+    pub fn get_blunder_threshold(&self) -> u32 {
+        self.blunder_threshold
+    }
+
+    pub fn get_alert_bell(&self) -> bool {
+        self.alert_bell
+    }
+
+    pub fn get_alert_title(&self) -> bool {
+        self.alert_title
+    }
+
+    pub fn get_alert_desktop(&self) -> bool {
+        self.alert_desktop
+    }
+
+    pub fn get_low_time_alert(&self) -> u32 {
+        self.low_time_alert
+    }
+
+    pub fn get_resign_threshold(&self) -> u32 {
+        self.resign_threshold
+    }
+
+    pub fn get_resign_moves(&self) -> u32 {
+        self.resign_moves
+    }
+
+    pub fn get_draw_threshold(&self) -> u32 {
+        self.draw_threshold
+    }
+
+    pub fn get_draw_moves(&self) -> u32 {
+        self.draw_moves
+    }
+
+    pub fn get_tablebase_adjudication(&self) -> bool {
+        self.tablebase_adjudication
+    }
+
+    pub fn get_max_game_moves(&self) -> u32 {
+        self.max_game_moves
+    }
+
+    pub fn get_keys(&self) -> &KeyMap {
+        &self.keys
+    }
+}
+
+// Where the program keeps its data: $XDG_DATA_HOME/rust-chess, falling back to ~/.local/share on
+// Unix and %APPDATA% on Windows.
+fn data_dir() -> Option<PathBuf> {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("share")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(data_dir.join("rust-chess"))
+}
+
+// Reads an on/off setting, accepting the usual spellings.
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "yes" => Some(true),
+        "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+impl Display for BoardTheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            BoardTheme::Green => "green",
+            BoardTheme::Brown => "brown",
+            BoardTheme::Blue => "blue",
+            BoardTheme::Gray => "gray",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl BoardTheme {
+    pub fn from(theme_str: &str) -> Option<BoardTheme> {
+        match theme_str.to_lowercase().as_str() {
+            "green" => Some(BoardTheme::Green),
+            "brown" => Some(BoardTheme::Brown),
+            "blue" => Some(BoardTheme::Blue),
+            "gray" | "grey" => Some(BoardTheme::Gray),
+            _ => None,
+        }
+    }
+
+    // 256 color palette indices of the light and dark squares.
+    pub fn get_square_colors(&self) -> (u8, u8) {
+        match self {
+            BoardTheme::Green => (180, 64),
+            BoardTheme::Brown => (223, 137),
+            BoardTheme::Blue => (153, 67),
+            BoardTheme::Gray => (250, 243),
+        }
+    }
+}
+
+impl Display for BoardSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardSize::Normal => write!(f, "normal"),
+            BoardSize::Large => write!(f, "large"),
+        }
+    }
+}
+
+impl BoardSize {
+    pub fn from(size_str: &str) -> Option<BoardSize> {
+        match size_str.to_lowercase().as_str() {
+            "normal" => Some(BoardSize::Normal),
+            "large" => Some(BoardSize::Large),
+            _ => None,
+        }
+    }
+
+    // Terminal columns and rows taken by one square.
+    pub fn get_square_size(&self) -> (usize, usize) {
+        match self {
+            BoardSize::Normal => (3, 1),
+            BoardSize::Large => (7, 3),
+        }
+    }
+}
+
+impl Display for PieceStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PieceStyle::Unicode => write!(f, "unicode"),
+            PieceStyle::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+impl PieceStyle {
+    pub fn from(style_str: &str) -> Option<PieceStyle> {
+        match style_str.to_lowercase().as_str() {
+            "unicode" => Some(PieceStyle::Unicode),
+            "ascii" => Some(PieceStyle::Ascii),
+            _ => None,
+        }
+    }
+}
+
+impl Display for GlyphWidth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GlyphWidth::Auto => write!(f, "auto"),
+            GlyphWidth::Narrow => write!(f, "narrow"),
+            GlyphWidth::Wide => write!(f, "wide"),
+        }
+    }
+}
+
+impl GlyphWidth {
+    pub fn from(width_str: &str) -> Option<GlyphWidth> {
+        match width_str.to_lowercase().as_str() {
+            "auto" => Some(GlyphWidth::Auto),
+            "narrow" | "1" => Some(GlyphWidth::Narrow),
+            "wide" | "2" => Some(GlyphWidth::Wide),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Language::Auto => write!(f, "auto"),
+            Language::English => write!(f, "english"),
+            Language::German => write!(f, "german"),
+        }
+    }
+}
+
+impl Language {
+    // Takes the language's name or its two letter code, e.g. german or de.
+    pub fn from(language_str: &str) -> Option<Language> {
+        match language_str.to_lowercase().as_str() {
+            "auto" => Some(Language::Auto),
+            "english" | "en" => Some(Language::English),
+            "german" | "de" => Some(Language::German),
+            _ => None,
+        }
+    }
+}
+
+impl Display for InputLanguage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            InputLanguage::English => "english",
+            InputLanguage::German => "german",
+            InputLanguage::Spanish => "spanish",
+            InputLanguage::French => "french",
+            InputLanguage::Italian => "italian",
+            InputLanguage::Dutch => "dutch",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl InputLanguage {
+    // Takes the language's name or its two letter code, e.g. german or de.
+    pub fn from(language_str: &str) -> Option<InputLanguage> {
+        match language_str.to_lowercase().as_str() {
+            "english" | "en" => Some(InputLanguage::English),
+            "german" | "de" => Some(InputLanguage::German),
+            "spanish" | "es" => Some(InputLanguage::Spanish),
+            "french" | "fr" => Some(InputLanguage::French),
+            "italian" | "it" => Some(InputLanguage::Italian),
+            "dutch" | "nl" => Some(InputLanguage::Dutch),
+            _ => None,
+        }
+    }
+
+    // The letters of the king, queen, rook, bishop and knight.
+    fn get_piece_letters(&self) -> [char; 5] {
+        match self {
+            InputLanguage::English => ['K', 'Q', 'R', 'B', 'N'],
+            InputLanguage::German => ['K', 'D', 'T', 'L', 'S'],
+            InputLanguage::Spanish => ['R', 'D', 'T', 'A', 'C'],
+            InputLanguage::French => ['R', 'D', 'T', 'F', 'C'],
+            InputLanguage::Italian => ['R', 'D', 'T', 'A', 'C'],
+            InputLanguage::Dutch => ['K', 'D', 'T', 'L', 'P'],
+        }
+    }
+
+    // A move typed with this language's piece letters in English SAN, e.g. Sxe5 to Nxe5 and
+    // e8=D to e8=Q in German. Files are lowercase, so only uppercase letters are pieces; the
+    // other uppercase letters, like the O of castling, are left alone.
+    pub fn to_english_san(self, typed: &str) -> String {
+        let english = InputLanguage::English.get_piece_letters();
+        let letters = self.get_piece_letters();
+        typed.chars()
+            .map(|c| letters.iter().position(|l| *l == c).map_or(c, |i| english[i]))
+            .collect()
+    }
+}
+
+// What a key does in the full screen interface, named as in the [keys] section of the config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Prev,
+    Next,
+    Start,
+    End,
+    Flip,
+    Undo,
+    Redo,
+    Redraw,
+    BoardCursor,
+    CursorLeft,
+    CursorRight,
+    CursorUp,
+    CursorDown,
+    Pick,
+    LeaveBoard,
+}
+
+impl Display for KeyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            KeyAction::Prev => "prev",
+            KeyAction::Next => "next",
+            KeyAction::Start => "start",
+            KeyAction::End => "end",
+            KeyAction::Flip => "flip",
+            KeyAction::Undo => "undo",
+            KeyAction::Redo => "redo",
+            KeyAction::Redraw => "redraw",
+            KeyAction::BoardCursor => "board_cursor",
+            KeyAction::CursorLeft => "cursor_left",
+            KeyAction::CursorRight => "cursor_right",
+            KeyAction::CursorUp => "cursor_up",
+            KeyAction::CursorDown => "cursor_down",
+            KeyAction::Pick => "pick",
+            KeyAction::LeaveBoard => "leave_board",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 15] = [
+        KeyAction::Prev,
+        KeyAction::Next,
+        KeyAction::Start,
+        KeyAction::End,
+        KeyAction::Flip,
+        KeyAction::Undo,
+        KeyAction::Redo,
+        KeyAction::Redraw,
+        KeyAction::BoardCursor,
+        KeyAction::CursorLeft,
+        KeyAction::CursorRight,
+        KeyAction::CursorUp,
+        KeyAction::CursorDown,
+        KeyAction::Pick,
+        KeyAction::LeaveBoard,
+    ];
+
+    // The keys used when the config doesn't set any. Most keys type into the input line, so the
+    // game's keys are ones that don't, and letters only move the board cursor.
+    fn get_default_keys(&self) -> &'static [&'static str] {
+        match self {
+            KeyAction::Prev => &["pageup"],
+            KeyAction::Next => &["pagedown"],
+            KeyAction::Start => &["ctrl-home"],
+            KeyAction::End => &["ctrl-end"],
+            KeyAction::Flip => &["ctrl-f"],
+            KeyAction::Undo => &["ctrl-z"],
+            KeyAction::Redo => &["ctrl-y"],
+            KeyAction::Redraw => &["ctrl-l"],
+            KeyAction::BoardCursor => &["tab"],
+            KeyAction::CursorLeft => &["left", "h"],
+            KeyAction::CursorRight => &["right", "l"],
+            KeyAction::CursorUp => &["up", "k"],
+            KeyAction::CursorDown => &["down", "j"],
+            KeyAction::Pick => &["enter", "space"],
+            KeyAction::LeaveBoard => &["tab", "esc"],
+        }
+    }
+
+    // The command the key runs, for the actions that are commands.
+    pub fn get_command(&self) -> Option<&'static str> {
+        match self {
+            KeyAction::Prev => Some("prev"),
+            KeyAction::Next => Some("next"),
+            KeyAction::Start => Some("start"),
+            KeyAction::End => Some("end"),
+            KeyAction::Flip => Some("flip"),
+            KeyAction::Undo => Some("undo"),
+            KeyAction::Redo => Some("redo"),
+            _ => None,
+        }
+    }
+
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            KeyAction::Prev => "step back through the game",
+            KeyAction::Next => "step forward through the game",
+            KeyAction::Start => "show the starting position",
+            KeyAction::End => "return to the current position",
+            KeyAction::Flip => "turn the board around",
+            KeyAction::Undo => "take back the last move",
+            KeyAction::Redo => "replay the move taken back",
+            KeyAction::Redraw => "draw the screen again",
+            KeyAction::BoardCursor => "move from the input line to the board",
+            KeyAction::CursorLeft => "move the board cursor left",
+            KeyAction::CursorRight => "move the board cursor right",
+            KeyAction::CursorUp => "move the board cursor up",
+            KeyAction::CursorDown => "move the board cursor down",
+            KeyAction::Pick => "pick up or drop the piece at the board cursor",
+            KeyAction::LeaveBoard => "move from the board back to the input line",
+        }
+    }
+}
+
+// A key and the modifiers held with it, written like ctrl-f, alt-left, pagedown, f5 or h.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const KEY_NAMES: [(&str, KeyCode); 14] = [
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("esc", KeyCode::Esc),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+];
+
+const MODIFIER_NAMES: [(&str, KeyModifiers); 3] = [
+    ("ctrl-", KeyModifiers::CONTROL),
+    ("alt-", KeyModifiers::ALT),
+    ("shift-", KeyModifiers::SHIFT),
+];
+
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, modifier) in MODIFIER_NAMES {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            code => match KEY_NAMES.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => match code {
+                    KeyCode::Char(c) => write!(f, "{}", c),
+                    _ => write!(f, "?"),
+                },
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<KeyBinding, String> {
+        KeyBinding::from(&value).ok_or_else(|| format!("{} is not a key, e.g. ctrl-f, alt-left, pagedown, f5 or h", value))
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(key: KeyBinding) -> String {
+        key.to_string()
+    }
+}
+
+impl KeyBinding {
+    pub fn from(key_str: &str) -> Option<KeyBinding> {
+        let mut rest = key_str.trim();
+        let mut modifiers = KeyModifiers::NONE;
+        'prefixes: loop {
+            for (name, modifier) in MODIFIER_NAMES {
+                if rest.len() > name.len() && rest[..name.len()].eq_ignore_ascii_case(name) {
+                    modifiers |= modifier;
+                    rest = &rest[name.len()..];
+                    continue 'prefixes;
+                }
+            }
+            break;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            // A single character is the key typing it, so case counts, except with Ctrl or Alt
+            // where terminals differ in reporting Shift.
+            (Some(c), None) if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => KeyCode::Char(c.to_ascii_lowercase()),
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "escape" => KeyCode::Esc,
+                "return" => KeyCode::Enter,
+                "del" => KeyCode::Delete,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => KEY_NAMES.iter().find(|(n, _)| *n == name)?.1,
+                },
+            },
+        };
+        Some(KeyBinding { code, modifiers })
+    }
+
+    // Whether a key pressed is this one. Shift is part of the character typed, so it only counts
+    // for other keys.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let held = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Char(c) if held.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                self.code == KeyCode::Char(c.to_ascii_lowercase()) && held - KeyModifiers::SHIFT == self.modifiers - KeyModifiers::SHIFT
+            }
+            KeyCode::Char(_) | KeyCode::BackTab => {
+                self.code == key.code && held - KeyModifiers::SHIFT == self.modifiers - KeyModifiers::SHIFT
+            }
+            _ => self.code == key.code && held == self.modifiers,
+        }
+    }
+}
+
+// The keys set for actions in the config, the others keeping their default keys.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyMap(BTreeMap<KeyAction, Vec<KeyBinding>>);
+
+impl KeyMap {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get_keys(&self, action: KeyAction) -> Vec<KeyBinding> {
+        match self.0.get(&action) {
+            Some(keys) => keys.clone(),
+            None => action.get_default_keys().iter()
+                .map(|k| KeyBinding::from(k).expect("the default keys are valid"))
+                .collect(),
+        }
+    }
+
+    // The first of the actions the key pressed is a key of.
+    pub fn find(&self, key: &KeyEvent, actions: &[KeyAction]) -> Option<KeyAction> {
+        actions.iter().copied().find(|a| self.get_keys(*a).iter().any(|k| k.matches(key)))
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+
+    #[test]
+    pub fn missing_settings_keep_defaults() {
+        let config = ChessConfig::from("theme = \"blue\"\nengine_depth = 15\n").unwrap();
+        assert_eq!(config.get_theme(), BoardTheme::Blue);
+        assert_eq!(config.get_engine_depth(), 15);
+        assert_eq!(config.get_pieces(), PieceStyle::Unicode);
+        assert_eq!(config.get_player_name(), None);
+        assert_eq!(ChessConfig::from("").unwrap(), ChessConfig::new());
+    }
+
+    #[test]
+    pub fn invalid_files_fail() {
+        assert!(ChessConfig::from("theme = \"purple\"").is_err());
+        assert!(ChessConfig::from("colour = \"blue\"").is_err());
+        assert!(ChessConfig::from("engine_depth = \"deep\"").is_err());
+        assert!(ChessConfig::from("time_control = \"soon\"").is_err());
+        assert!(ChessConfig::from("[keys]\nflip = [\"ctrl-sometimes\"]").is_err());
+        assert!(ChessConfig::from("[keys]\nfly = [\"f\"]").is_err());
+    }
+
+    #[test]
+    pub fn keys_are_remapped() {
+        let config = ChessConfig::from("theme = \"blue\"\n[keys]\nflip = [\"Ctrl-R\", \"f5\"]\nprev = [\"alt-left\"]\n").unwrap();
+        let keys = config.get_keys();
+        assert_eq!(keys.get_keys(KeyAction::Flip).iter().map(|k| k.to_string()).collect::<Vec<_>>(), ["ctrl-r", "f5"]);
+        assert_eq!(keys.get_keys(KeyAction::Next).iter().map(|k| k.to_string()).collect::<Vec<_>>(), ["pagedown"]);
+
+        let press = |code, modifiers| KeyEvent::new(code, modifiers);
+        let all = &KeyAction::ALL;
+        assert_eq!(keys.find(&press(KeyCode::Char('r'), KeyModifiers::CONTROL), all), Some(KeyAction::Flip));
+        assert_eq!(keys.find(&press(KeyCode::Left, KeyModifiers::ALT), all), Some(KeyAction::Prev));
+        // The default ctrl-f is gone, Left alone still moves the board cursor.
+        assert_eq!(keys.find(&press(KeyCode::Char('f'), KeyModifiers::CONTROL), all), None);
+        assert_eq!(keys.find(&press(KeyCode::Left, KeyModifiers::NONE), all), Some(KeyAction::CursorLeft));
+        assert_eq!(keys.find(&press(KeyCode::Char('H'), KeyModifiers::SHIFT), all), None);
+
+        let path = std::env::temp_dir().join(format!("rust_chess_keys_{}.toml", std::process::id()));
+        config.save(&path).unwrap();
+        assert_eq!(ChessConfig::load(&path).unwrap(), config);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn set_and_get_by_name() {
+        let mut config = ChessConfig::new();
+        config.set("pieces", "ASCII").unwrap();
+        config.set("glyph_width", "2").unwrap();
+        assert_eq!(config.get_glyph_width(), GlyphWidth::Wide);
+        assert_eq!(config.get("glyph_width"), Some(String::from("wide")));
+        config.set("player_name", "Raul Rojas").unwrap();
+        config.set("engine_path", "/usr/bin/stockfish").unwrap();
+        assert_eq!(config.get("pieces"), Some(String::from("ascii")));
+        assert_eq!(config.get("player_name"), Some(String::from("Raul Rojas")));
+        assert_eq!(config.get_engine_path(), Some(&PathBuf::from("/usr/bin/stockfish")));
+
+        config.set("player_name", "").unwrap();
+        assert_eq!(config.get_player_name(), None);
+
+        config.set("autosave_on_move", "off").unwrap();
+        config.set("autosave_interval", "0").unwrap();
+        assert_eq!(config.get("autosave_on_move"), Some(String::from("false")));
+        assert!(!config.get_autosave_on_move());
+        assert_eq!(config.get_autosave_interval(), 0);
+        assert!(matches!(config.set("autosave_on_move", "maybe"), Err(ConfigError::InvalidValue { .. })));
+
+        assert!(matches!(config.set("engine_depth", "0"), Err(ConfigError::InvalidValue { .. })));
+        config.set("blunder_alerts", "on").unwrap();
+        config.set("blunder_threshold", "150").unwrap();
+        assert!(config.get_blunder_alerts());
+        assert_eq!(config.get_blunder_threshold(), 150);
+        assert!(matches!(config.set("blunder_threshold", "-5"), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(config.set("colour", "blue"), Err(ConfigError::UnknownSetting(_))));
+        assert_eq!(config.get("colour"), None);
+    }
+
+    #[test]
+    pub fn localized_moves_become_english() {
+        let mut config = ChessConfig::from("input_language = \"german\"\n").unwrap();
+        let german = config.get_input_language();
+        assert_eq!(german.to_english_san("Sf3"), "Nf3");
+        assert_eq!(german.to_english_san("Lxb5+"), "Bxb5+");
+        assert_eq!(german.to_english_san("e8=D"), "e8=Q");
+        assert_eq!(german.to_english_san("O-O-O"), "O-O-O");
+
+        config.set("input_language", "ES").unwrap();
+        assert_eq!(config.get("input_language"), Some(String::from("spanish")));
+        // The Spanish R is the king, T the rook.
+        assert_eq!(config.get_input_language().to_english_san("Rxe2"), "Kxe2");
+        assert_eq!(config.get_input_language().to_english_san("Tad1"), "Rad1");
+        assert_eq!(InputLanguage::English.to_english_san("Rad1"), "Rad1");
+        assert!(matches!(config.set("input_language", "klingon"), Err(ConfigError::InvalidValue { .. })));
+    }
+
+    #[test]
+    pub fn save_and_load_round_trip() {
+        let path = std::env::temp_dir().join(format!("rust_chess_config_{}", std::process::id())).join("config.toml");
+        let mut config = ChessConfig::new();
+        config.set("theme", "gray").unwrap();
+        config.set("time_control", "5+3").unwrap();
+        assert!(matches!(config.set("time_control", "5 minutes"), Err(ConfigError::InvalidValue { .. })));
+        config.set("time_control", "classical").unwrap();
+        assert_eq!(config.get("time_control").unwrap(), "40/90+30:30+30");
+        config.save(&path).unwrap();
+        assert_eq!(ChessConfig::load(&path).unwrap(), config);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
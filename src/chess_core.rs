@@ -1,21 +1,64 @@
 use std::fmt::{Display, Formatter};
 use crate::chess_common::*;
 
+pub mod diagram;
+pub mod diff;
+pub mod fen;
+pub mod movegen;
+pub mod pattern;
+pub mod san;
+pub mod speech;
+pub mod zobrist;
+
+pub use diagram::DiagramStyle;
+pub use diff::BoardDifference;
+pub use movegen::BoardMove;
+pub use san::MoveResolveError;
+
 pub type BoardSquares = [[Square; 8]; 8];
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Board {
     squares: BoardSquares,
+    turn: Team,
+    castling_rights: CastlingRights,
+    en_passant: Option<BoardSquare>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    // The Zobrist key of the position, kept up to date as pieces are placed and moves made.
+    key: u64,
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
 }
 
 impl Board {
     pub fn new() -> Board {
-        let mut b = Board {
-            squares: [[Square {piece: None}; 8]; 8]
-        };
+        let mut b = Board::empty();
         b.new_game();
         b
     }
 
+    // A board with no pieces on it and White to move.
+    pub fn empty() -> Board {
+        Board {
+            squares: [[Square {piece: None}; 8]; 8],
+            turn: Team::Light,
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            key: 0,
+        }
+    }
+
     pub fn new_game(&mut self) {
+        *self = Board::empty();
+        self.castling_rights = CastlingRights::all();
+
         // Add pawns
         for f in 0..8 {
             self.squares[ChessRank::R2.as_usize()][f] = Square::new(Some(Piece::new(Team::Light, ChessPiece::Pawn)));
@@ -48,14 +91,190 @@ impl Board {
         self.squares[ChessRank::R1.as_usize()][ChessFile::E.as_usize()] = Square::new(Some(Piece::new(Team::Light, ChessPiece::King)));
         self.squares[ChessRank::R8.as_usize()][ChessFile::E.as_usize()] = Square::new(Some(Piece::new(Team::Dark, ChessPiece::King)));
 
+        self.key = self.compute_zobrist_key();
     }
 
     pub fn get_squares(&self) -> &BoardSquares {
         &self.squares
     }
+
+    pub fn get_piece_at(&self, square: BoardSquare) -> Option<Piece> {
+        self.squares[square.get_rank().as_usize()][square.get_file().as_usize()].piece
+    }
+
+    pub fn set_piece_at(&mut self, square: BoardSquare, piece: Option<Piece>) {
+        self.key ^= zobrist::piece_key(self.get_piece_at(square), square) ^ zobrist::piece_key(piece, square);
+        self.squares[square.get_rank().as_usize()][square.get_file().as_usize()] = Square::new(piece);
+    }
+
+    pub fn get_turn(&self) -> Team {
+        self.turn
+    }
+
+    pub fn get_castling_rights(&self) -> &CastlingRights {
+        &self.castling_rights
+    }
+
+    pub fn get_en_passant(&self) -> Option<BoardSquare> {
+        self.en_passant
+    }
+
+    pub fn get_halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn get_fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    // The total material value of a side's pieces on the board.
+    pub fn get_material(&self, team: Team) -> u32 {
+        self.squares.iter()
+            .flatten()
+            .filter_map(|s| s.piece)
+            .filter(|p| p.team == team)
+            .map(|p| p.piece_type.get_value())
+            .sum()
+    }
+
+    // Whether a side has the material to checkmate by any series of legal moves, however
+    // unlikely. A lone king can't, a lone knight only with the other side's pieces to hem the
+    // king in, and bishops all on squares of one color only if the other side has a piece that
+    // can stand on the other color.
+    pub fn can_checkmate(&self, team: Team) -> bool {
+        let mut knights = 0;
+        // Whether the side has bishops on dark and on light squares.
+        let mut bishop_colors = [false; 2];
+        let mut others = Vec::new();
+        for (rank, row) in self.squares.iter().enumerate() {
+            for (file, piece) in row.iter().enumerate().filter_map(|(f, s)| s.piece.map(|p| (f, p))) {
+                let color = (rank + file) % 2;
+                match piece.piece_type {
+                    ChessPiece::King => (),
+                    _ if piece.team != team => others.push((piece.piece_type, color)),
+                    ChessPiece::Knight => knights += 1,
+                    ChessPiece::Bishop => bishop_colors[color] = true,
+                    _ => return true,
+                }
+            }
+        }
+        match (knights, bishop_colors) {
+            (0, [false, false]) => false,
+            (1, [false, false]) => !others.is_empty(),
+            (0, [true, false]) | (0, [false, true]) => others.iter()
+                .any(|(piece_type, color)| *piece_type != ChessPiece::Bishop || !bishop_colors[*color]),
+            _ => true,
+        }
+    }
+}
+
+// A single, fully specified square of the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoardSquare {
+    file: ChessFile,
+    rank: ChessRank,
+}
+
+impl Display for BoardSquare {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.file, self.rank)
+    }
+}
+
+impl BoardSquare {
+    pub fn new(file: ChessFile, rank: ChessRank) -> BoardSquare {
+        BoardSquare { file, rank }
+    }
+
+    pub fn from(square_str: &str) -> Option<BoardSquare> {
+        let mut chars = square_str.trim().chars();
+        let file = ChessFile::from(chars.next()?.to_ascii_lowercase())?;
+        let rank = ChessRank::from(chars.next()?)?;
+        if chars.next().is_some() {
+            return None;
+        }
+        Some(BoardSquare { file, rank })
+    }
+
+    pub fn from_indices(file: usize, rank: usize) -> Option<BoardSquare> {
+        Some(BoardSquare {
+            file: ChessFile::from_usize(file)?,
+            rank: ChessRank::from_usize(rank)?,
+        })
+    }
+
+    pub fn from_coordinate(coordinate: &ChessCoordinate) -> Option<BoardSquare> {
+        Some(BoardSquare {
+            file: (*coordinate.get_file())?,
+            rank: (*coordinate.get_rank())?,
+        })
+    }
+
+    pub fn to_coordinate(self) -> ChessCoordinate {
+        ChessCoordinate::new(self.file, self.rank)
+    }
+
+    // Returns the square offset by the given number of files and ranks, if it is on the board.
+    pub fn offset(self, file_delta: i32, rank_delta: i32) -> Option<BoardSquare> {
+        let file = self.file.as_usize() as i32 + file_delta;
+        let rank = self.rank.as_usize() as i32 + rank_delta;
+        if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+            return None;
+        }
+        BoardSquare::from_indices(file as usize, rank as usize)
+    }
+
+    pub fn get_file(&self) -> ChessFile {
+        self.file
+    }
+
+    pub fn get_rank(&self) -> ChessRank {
+        self.rank
+    }
+
+    // Iterates over all 64 squares from a1 to h8, rank by rank.
+    pub fn all() -> impl Iterator<Item = BoardSquare> {
+        (0..64).map(|i| BoardSquare::from_indices(i % 8, i / 8).unwrap())
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CastlingRights {
+    light_kingside: bool,
+    light_queenside: bool,
+    dark_kingside: bool,
+    dark_queenside: bool,
+}
+
+impl CastlingRights {
+    pub fn all() -> CastlingRights {
+        CastlingRights { light_kingside: true, light_queenside: true, dark_kingside: true, dark_queenside: true }
+    }
+
+    pub fn none() -> CastlingRights {
+        CastlingRights { light_kingside: false, light_queenside: false, dark_kingside: false, dark_queenside: false }
+    }
+
+    pub fn can_castle(&self, team: Team, side: ChessCastle) -> bool {
+        match (team, side) {
+            (Team::Light, ChessCastle::KingsideCastle) => self.light_kingside,
+            (Team::Light, ChessCastle::QueensideCastle) => self.light_queenside,
+            (Team::Dark, ChessCastle::KingsideCastle) => self.dark_kingside,
+            (Team::Dark, ChessCastle::QueensideCastle) => self.dark_queenside,
+        }
+    }
+
+    pub fn set(&mut self, team: Team, side: ChessCastle, allowed: bool) {
+        match (team, side) {
+            (Team::Light, ChessCastle::KingsideCastle) => self.light_kingside = allowed,
+            (Team::Light, ChessCastle::QueensideCastle) => self.light_queenside = allowed,
+            (Team::Dark, ChessCastle::KingsideCastle) => self.dark_kingside = allowed,
+            (Team::Dark, ChessCastle::QueensideCastle) => self.dark_queenside = allowed,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Square {
     piece: Option<Piece>
 }
@@ -67,7 +286,7 @@ impl Display for Square {
                 write!(f, "{}", p)
             }
             None => {
-                write!(f, "{}", ' ')
+                write!(f, " ")
             }
         }
     }
@@ -83,7 +302,7 @@ impl Square {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Piece {
     team: Team,
     piece_type: ChessPiece,
@@ -121,13 +340,62 @@ impl Piece {
         }
     }
 
+    // The piece as a FEN letter, uppercase for White and lowercase for Black.
+    pub fn get_ascii_symbol(self) -> char {
+        let c = match self.piece_type {
+            ChessPiece::Pawn => 'P',
+            p => p.to_string().chars().next().unwrap_or('?'),
+        };
+        match self.team {
+            Team::Light => c,
+            Team::Dark => c.to_ascii_lowercase(),
+        }
+    }
+
     pub fn get_team(&self) -> &Team {
         &self.team
     }
+
+    pub fn get_piece_type(&self) -> ChessPiece {
+        self.piece_type
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Team {
     Light,
     Dark,
 }
+
+impl Display for Team {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Team::Light => write!(f, "White"),
+            Team::Dark => write!(f, "Black"),
+        }
+    }
+}
+
+impl Team {
+    pub fn opposite(self) -> Team {
+        match self {
+            Team::Light => Team::Dark,
+            Team::Dark => Team::Light,
+        }
+    }
+
+    // The rank direction this team's pawns move in.
+    pub fn pawn_direction(self) -> i32 {
+        match self {
+            Team::Light => 1,
+            Team::Dark => -1,
+        }
+    }
+
+    pub fn back_rank(self) -> ChessRank {
+        match self {
+            Team::Light => ChessRank::R1,
+            Team::Dark => ChessRank::R8,
+        }
+    }
+}
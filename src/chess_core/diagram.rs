@@ -0,0 +1,105 @@
+/*
+diagram.rs
+Plain text board diagrams, for pasting into forums, emails and issue reports. They have no
+terminal escape codes, only a frame, the pieces, coordinates and the side to move:
+
+  +-----------------+
+8 | r n b q k b n r |
+7 | p p p p p p p p |
+6 | . . . . . . . . |
+5 | . . . . . . . . |
+4 | . . . . P . . . |
+3 | . . . . . . . . |
+2 | P P P P . P P P |
+1 | R N B Q K B N R |
+  +-----------------+
+    a b c d e f g h
+Black to move
+*/
+
+use super::{Board, BoardSquare};
+
+// The characters a diagram is drawn with. Ascii shows pieces as FEN letters and is safe
+// anywhere, Unicode uses chess symbols and box drawing characters, which need a font that has
+// them at the width of a letter to line up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DiagramStyle {
+    #[default]
+    Ascii,
+    Unicode,
+}
+
+impl DiagramStyle {
+    // The corners, the horizontal and vertical sides of the frame, and the empty square.
+    fn get_frame(self) -> ([char; 4], char, char, char) {
+        match self {
+            DiagramStyle::Ascii => (['+', '+', '+', '+'], '-', '|', '.'),
+            DiagramStyle::Unicode => (['┌', '┐', '└', '┘'], '─', '│', '·'),
+        }
+    }
+}
+
+impl Board {
+    // The board as a diagram, with White at the bottom or with Black at the bottom when flipped.
+    // Every line ends with a line break.
+    pub fn to_diagram(&self, style: DiagramStyle, flipped: bool) -> String {
+        let ([top_left, top_right, bottom_left, bottom_right], side, edge, empty) = style.get_frame();
+        let ranks: Vec<usize> = if flipped { (0..8).collect() } else { (0..8).rev().collect() };
+        let files: Vec<usize> = if flipped { (0..8).rev().collect() } else { (0..8).collect() };
+        let border: String = std::iter::repeat_n(side, files.len() * 2 + 1).collect();
+
+        let mut output = format!("  {top_left}{border}{top_right}\n");
+        for &r in ranks.iter() {
+            output += format!("{} {}", r + 1, edge).as_str();
+            for &f in files.iter() {
+                let piece = BoardSquare::from_indices(f, r).and_then(|s| self.get_piece_at(s));
+                let symbol = match (piece, style) {
+                    (Some(p), DiagramStyle::Ascii) => p.get_ascii_symbol(),
+                    (Some(p), DiagramStyle::Unicode) => p.get_unicode_symbol(),
+                    (None, _) => empty,
+                };
+                output.push(' ');
+                output.push(symbol);
+            }
+            output += format!(" {}\n", edge).as_str();
+        }
+        output += format!("  {bottom_left}{border}{bottom_right}\n").as_str();
+        let file_labels: Vec<String> = files.iter().map(|f| ((b'a' + *f as u8) as char).to_string()).collect();
+        output += format!("    {}\n", file_labels.join(" ")).as_str();
+        output += format!("{} to move\n", self.get_turn()).as_str();
+        output
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_diagram {
+    use super::*;
+
+    #[test]
+    pub fn diagrams_have_coordinates_and_no_escape_codes() {
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let diagram = board.to_diagram(DiagramStyle::Ascii, false);
+        assert_eq!(diagram, "  +-----------------+\n\
+            8 | r n b q k b n r |\n\
+            7 | p p p p p p p p |\n\
+            6 | . . . . . . . . |\n\
+            5 | . . . . . . . . |\n\
+            4 | . . . . P . . . |\n\
+            3 | . . . . . . . . |\n\
+            2 | P P P P . P P P |\n\
+            1 | R N B Q K B N R |\n  \
+            +-----------------+\n    \
+            a b c d e f g h\n\
+            Black to move\n");
+
+        let flipped = board.to_diagram(DiagramStyle::Unicode, true);
+        let lines: Vec<&str> = flipped.lines().collect();
+        assert_eq!(lines[0], "  ┌─────────────────┐");
+        assert_eq!(lines[1], "1 │ ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖ │");
+        assert_eq!(lines[5], "5 │ · · · · · · · · │");
+        assert_eq!(lines[10], "    h g f e d c b a");
+        assert!(!flipped.contains('\u{001b}'));
+    }
+}
@@ -0,0 +1,111 @@
+/*
+diff.rs
+How two positions differ, square by square and in the state FEN keeps beside the pieces. Useful
+for finding where a replayed game went wrong or checking a transcribed position:
+
+e4: White pawn vs empty
+castling: KQkq vs KQ
+*/
+
+use std::fmt::Display;
+use super::{Board, BoardSquare, CastlingRights, Piece, Team};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardDifference {
+    Square { square: BoardSquare, this: Option<Piece>, other: Option<Piece> },
+    Turn { this: Team, other: Team },
+    CastlingRights { this: CastlingRights, other: CastlingRights },
+    EnPassant { this: Option<BoardSquare>, other: Option<BoardSquare> },
+    HalfmoveClock { this: u32, other: u32 },
+    FullmoveNumber { this: u32, other: u32 },
+}
+
+impl Display for BoardDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardDifference::Square { square, this, other } => {
+                write!(f, "{}: {} vs {}", square, describe_piece(this), describe_piece(other))
+            }
+            BoardDifference::Turn { this, other } => write!(f, "to move: {} vs {}", this, other),
+            BoardDifference::CastlingRights { this, other } => {
+                write!(f, "castling: {} vs {}", this.to_fen(), other.to_fen())
+            }
+            BoardDifference::EnPassant { this, other } => {
+                write!(f, "en passant: {} vs {}", describe_square(this), describe_square(other))
+            }
+            BoardDifference::HalfmoveClock { this, other } => write!(f, "halfmove clock: {} vs {}", this, other),
+            BoardDifference::FullmoveNumber { this, other } => write!(f, "move number: {} vs {}", this, other),
+        }
+    }
+}
+
+fn describe_piece(piece: &Option<Piece>) -> String {
+    match piece {
+        Some(p) => format!("{} {}", p.get_team(), p.get_piece_type().get_name()),
+        None => String::from("empty"),
+    }
+}
+
+fn describe_square(square: &Option<BoardSquare>) -> String {
+    match square {
+        Some(s) => s.to_string(),
+        None => String::from("-"),
+    }
+}
+
+impl Board {
+    // What differs between this board and another, the squares from a1 to h8 first and then the
+    // side to move, castling rights, en passant square and move clocks. Empty for equal boards.
+    pub fn diff(&self, other: &Board) -> Vec<BoardDifference> {
+        let mut differences: Vec<BoardDifference> = BoardSquare::all()
+            .filter(|s| self.get_piece_at(*s) != other.get_piece_at(*s))
+            .map(|s| BoardDifference::Square { square: s, this: self.get_piece_at(s), other: other.get_piece_at(s) })
+            .collect();
+        if self.turn != other.turn {
+            differences.push(BoardDifference::Turn { this: self.turn, other: other.turn });
+        }
+        if self.castling_rights != other.castling_rights {
+            differences.push(BoardDifference::CastlingRights { this: self.castling_rights, other: other.castling_rights });
+        }
+        if self.en_passant != other.en_passant {
+            differences.push(BoardDifference::EnPassant { this: self.en_passant, other: other.en_passant });
+        }
+        if self.halfmove_clock != other.halfmove_clock {
+            differences.push(BoardDifference::HalfmoveClock { this: self.halfmove_clock, other: other.halfmove_clock });
+        }
+        if self.fullmove_number != other.fullmove_number {
+            differences.push(BoardDifference::FullmoveNumber { this: self.fullmove_number, other: other.fullmove_number });
+        }
+        differences
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_diff {
+    use super::*;
+    use crate::chess_core::fen::START_POSITION_FEN;
+
+    #[test]
+    pub fn differences_are_listed_in_order() {
+        let start = Board::new();
+        assert!(start.diff(&Board::from_fen(START_POSITION_FEN).unwrap()).is_empty());
+
+        let after_e4 = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 1").unwrap();
+        let differences: Vec<String> = start.diff(&after_e4).iter().map(|d| d.to_string()).collect();
+        assert_eq!(differences, vec![
+            "e2: White pawn vs empty",
+            "e4: empty vs White pawn",
+            "to move: White vs Black",
+            "castling: KQkq vs Kq",
+            "en passant: - vs e3",
+        ]);
+
+        let later = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 3").unwrap();
+        assert_eq!(start.diff(&later), vec![
+            BoardDifference::HalfmoveClock { this: 0, other: 4 },
+            BoardDifference::FullmoveNumber { this: 1, other: 3 },
+        ]);
+    }
+}
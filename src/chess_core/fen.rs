@@ -0,0 +1,209 @@
+/*
+fen.rs
+Forsyth-Edwards Notation (FEN) reading and writing for the Board.
+
+Example FEN (the starting position):
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+*/
+
+use std::fmt::Display;
+use crate::chess_common::*;
+use super::{Board, BoardSquare, CastlingRights, Piece, Team};
+
+pub const START_POSITION_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, PartialEq)]
+pub enum FenParseError {
+    WrongFieldCount,
+    InvalidPiecePlacement,
+    InvalidActiveColor,
+    InvalidCastlingRights,
+    InvalidEnPassant,
+    InvalidClock,
+}
+
+impl Display for FenParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            FenParseError::WrongFieldCount => "a FEN string needs 6 space separated fields",
+            FenParseError::InvalidPiecePlacement => "the piece placement field is invalid",
+            FenParseError::InvalidActiveColor => "the active color must be w or b",
+            FenParseError::InvalidCastlingRights => "the castling field is invalid",
+            FenParseError::InvalidEnPassant => "the en passant field is invalid",
+            FenParseError::InvalidClock => "the move clocks must be numbers",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl std::error::Error for FenParseError {}
+
+impl Board {
+    pub fn from_fen(fen: &str) -> Result<Board, FenParseError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        // The move clocks are often left off, default them like most tools do.
+        if fields.len() != 6 && fields.len() != 4 {
+            return Err(FenParseError::WrongFieldCount);
+        }
+
+        let mut board = Board::empty();
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenParseError::InvalidPiecePlacement);
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i;
+            let mut file = 0;
+            for c in rank_str.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    file += skip as usize;
+                    continue;
+                }
+                let team = if c.is_ascii_uppercase() { Team::Light } else { Team::Dark };
+                let piece_type = match c.to_ascii_uppercase() {
+                    'P' => ChessPiece::Pawn,
+                    upper => ChessPiece::from(upper).ok_or(FenParseError::InvalidPiecePlacement)?,
+                };
+                let square = BoardSquare::from_indices(file, rank).ok_or(FenParseError::InvalidPiecePlacement)?;
+                board.set_piece_at(square, Some(Piece::new(team, piece_type)));
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenParseError::InvalidPiecePlacement);
+            }
+        }
+
+        board.turn = match fields[1] {
+            "w" => Team::Light,
+            "b" => Team::Dark,
+            _ => return Err(FenParseError::InvalidActiveColor),
+        };
+
+        board.castling_rights = CastlingRights::none();
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let (team, side) = match c {
+                    'K' => (Team::Light, ChessCastle::KingsideCastle),
+                    'Q' => (Team::Light, ChessCastle::QueensideCastle),
+                    'k' => (Team::Dark, ChessCastle::KingsideCastle),
+                    'q' => (Team::Dark, ChessCastle::QueensideCastle),
+                    _ => return Err(FenParseError::InvalidCastlingRights),
+                };
+                board.castling_rights.set(team, side, true);
+            }
+        }
+
+        board.en_passant = match fields[3] {
+            "-" => None,
+            s => Some(BoardSquare::from(s).ok_or(FenParseError::InvalidEnPassant)?),
+        };
+
+        if fields.len() == 6 {
+            board.halfmove_clock = fields[4].parse().map_err(|_| FenParseError::InvalidClock)?;
+            board.fullmove_number = fields[5].parse().map_err(|_| FenParseError::InvalidClock)?;
+        }
+
+        board.key = board.compute_zobrist_key();
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut output = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get_squares()[rank][file].get_piece() {
+                    Some(p) => {
+                        if empty > 0 {
+                            output += empty.to_string().as_str();
+                            empty = 0;
+                        }
+                        output.push(p.get_ascii_symbol());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                output += empty.to_string().as_str();
+            }
+            if rank > 0 {
+                output.push('/');
+            }
+        }
+
+        output += match self.get_turn() {
+            Team::Light => " w ",
+            Team::Dark => " b ",
+        };
+
+        output += self.get_castling_rights().to_fen().as_str();
+
+        match self.get_en_passant() {
+            Some(sq) => output += format!(" {}", sq).as_str(),
+            None => output += " -",
+        }
+
+        output += format!(" {} {}", self.get_halfmove_clock(), self.get_fullmove_number()).as_str();
+        output
+    }
+}
+
+impl CastlingRights {
+    // The castling field of a FEN string, e.g. KQkq, or - when no side can castle.
+    pub fn to_fen(&self) -> String {
+        let mut castling = String::new();
+        for (team, side, c) in [
+            (Team::Light, ChessCastle::KingsideCastle, 'K'),
+            (Team::Light, ChessCastle::QueensideCastle, 'Q'),
+            (Team::Dark, ChessCastle::KingsideCastle, 'k'),
+            (Team::Dark, ChessCastle::QueensideCastle, 'q'),
+        ] {
+            if self.can_castle(team, side) {
+                castling.push(c);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        castling
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_fen {
+    use super::*;
+
+    #[test]
+    pub fn start_position_round_trip() {
+        let b = Board::from_fen(START_POSITION_FEN).unwrap();
+        assert_eq!(b, Board::new());
+        assert_eq!(Board::new().to_fen(), START_POSITION_FEN);
+    }
+
+    #[test]
+    pub fn position_round_trip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq e3 4 17";
+        assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+    }
+
+    #[test]
+    pub fn clocks_are_optional() {
+        let b = Board::from_fen("8/8/8/8/8/8/8/K6k w - -").unwrap();
+        assert_eq!(b.get_halfmove_clock(), 0);
+        assert_eq!(b.get_fullmove_number(), 1);
+    }
+
+    #[test]
+    pub fn invalid_fen_fails() {
+        assert_eq!(Board::from_fen("").unwrap_err(), FenParseError::WrongFieldCount);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8 w - - 0 1").unwrap_err(), FenParseError::InvalidPiecePlacement);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/9 w - - 0 1").unwrap_err(), FenParseError::InvalidPiecePlacement);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").unwrap_err(), FenParseError::InvalidActiveColor);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w X - 0 1").unwrap_err(), FenParseError::InvalidCastlingRights);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - z9 0 1").unwrap_err(), FenParseError::InvalidEnPassant);
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8 w - - a 1").unwrap_err(), FenParseError::InvalidClock);
+    }
+}
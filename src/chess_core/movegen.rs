@@ -0,0 +1,527 @@
+/*
+movegen.rs
+Legal move generation and move execution for the Board.
+
+Moves are generated pseudo-legally per piece and then filtered by playing each one on a
+copy of the board and rejecting those that leave the moving side's king in check.
+*/
+
+use std::fmt::{Display, Formatter};
+use crate::chess_common::*;
+use super::{Board, BoardSquare, Piece, Team};
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const PROMOTION_PIECES: [ChessPiece; 4] = [ChessPiece::Queen, ChessPiece::Rook, ChessPiece::Bishop, ChessPiece::Knight];
+
+// A move on a concrete board: where a piece moves from and to, and what a pawn promotes to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoardMove {
+    from: BoardSquare,
+    to: BoardSquare,
+    promotion: Option<ChessPiece>,
+}
+
+impl Display for BoardMove {
+    // Long algebraic (coordinate) notation, e.g. e2e4 or e7e8q.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(p) = self.promotion {
+            write!(f, "{}", p.to_string().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+impl BoardMove {
+    pub fn new(from: BoardSquare, to: BoardSquare, promotion: Option<ChessPiece>) -> BoardMove {
+        BoardMove { from, to, promotion }
+    }
+
+    // Parses a move in coordinate notation, e.g. e2e4 or e7e8q.
+    pub fn from(move_str: &str) -> Option<BoardMove> {
+        let move_str = move_str.trim();
+        if !move_str.is_ascii() || !(4..=5).contains(&move_str.len()) {
+            return None;
+        }
+        let from = BoardSquare::from(&move_str[0..2])?;
+        let to = BoardSquare::from(&move_str[2..4])?;
+        let promotion = match move_str.chars().nth(4) {
+            Some(c) => match ChessPiece::from(c.to_ascii_uppercase()) {
+                Some(p) if p != ChessPiece::King => Some(p),
+                _ => return None,
+            },
+            None => None,
+        };
+        Some(BoardMove { from, to, promotion })
+    }
+
+    pub fn get_from(&self) -> BoardSquare {
+        self.from
+    }
+
+    pub fn get_to(&self) -> BoardSquare {
+        self.to
+    }
+
+    pub fn get_promotion(&self) -> Option<ChessPiece> {
+        self.promotion
+    }
+}
+
+impl Board {
+    // All legal moves for the side to move.
+    pub fn legal_moves(&self) -> Vec<BoardMove> {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|m| self.is_move_safe(*m))
+            .collect()
+    }
+
+    // The legal moves that take a piece, en passant included, for searches that look at captures
+    // before generating the rest.
+    pub fn legal_captures(&self) -> Vec<BoardMove> {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|m| self.is_capture(*m) && self.is_move_safe(*m))
+            .collect()
+    }
+
+    // The legal moves that don't take anything.
+    pub fn legal_quiet_moves(&self) -> Vec<BoardMove> {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|m| !self.is_capture(*m) && self.is_move_safe(*m))
+            .collect()
+    }
+
+    // Whether a move takes a piece, en passant included.
+    pub fn is_capture(&self, mv: BoardMove) -> bool {
+        if self.get_piece_at(mv.to).is_some() {
+            return true;
+        }
+        let is_pawn = self.get_piece_at(mv.from).map(|p| p.get_piece_type()) == Some(ChessPiece::Pawn);
+        is_pawn && self.en_passant == Some(mv.to)
+    }
+
+    // All legal moves of the piece standing on the given square.
+    pub fn legal_moves_from(&self, square: BoardSquare) -> Vec<BoardMove> {
+        let mut moves = Vec::new();
+        if let Some(piece) = self.get_piece_at(square) {
+            if *piece.get_team() == self.get_turn() {
+                self.pseudo_legal_moves_from(square, piece, &mut moves);
+            }
+        }
+        moves.retain(|m| self.is_move_safe(*m));
+        moves
+    }
+
+    pub fn is_legal_move(&self, mv: BoardMove) -> bool {
+        self.legal_moves_from(mv.from).contains(&mv)
+    }
+
+    // Plays a move without checking that it is legal and returns the captured piece, if any.
+    pub fn make_move(&mut self, mv: BoardMove) -> Option<Piece> {
+        let piece = self.get_piece_at(mv.from)?;
+        let team = *piece.get_team();
+        let piece_type = piece.get_piece_type();
+        let mut captured = self.get_piece_at(mv.to);
+
+        // The pieces update the key as they are placed, the rest of the state is taken out of it
+        // here and put back once the move is made.
+        self.key ^= self.state_key();
+
+        // En passant removes the pawn beside the destination rather than on it.
+        if piece_type == ChessPiece::Pawn && captured.is_none() && Some(mv.to) == self.en_passant {
+            let captured_square = BoardSquare::new(mv.to.get_file(), mv.from.get_rank());
+            captured = self.get_piece_at(captured_square);
+            self.set_piece_at(captured_square, None);
+        }
+
+        // Castling is written as a two square king move, the rook jumps over the king.
+        let file_delta = mv.to.get_file().as_usize() as i32 - mv.from.get_file().as_usize() as i32;
+        if piece_type == ChessPiece::King && file_delta.abs() == 2 {
+            let rank = mv.from.get_rank();
+            let (rook_from, rook_to) = if file_delta > 0 {
+                (ChessFile::H, ChessFile::F)
+            }
+            else {
+                (ChessFile::A, ChessFile::D)
+            };
+            let rook = self.get_piece_at(BoardSquare::new(rook_from, rank));
+            self.set_piece_at(BoardSquare::new(rook_from, rank), None);
+            self.set_piece_at(BoardSquare::new(rook_to, rank), rook);
+        }
+
+        let placed = match mv.promotion {
+            Some(p) => Piece::new(team, p),
+            None => piece,
+        };
+        self.set_piece_at(mv.from, None);
+        self.set_piece_at(mv.to, Some(placed));
+
+        let rank_delta = mv.to.get_rank().as_usize() as i32 - mv.from.get_rank().as_usize() as i32;
+        self.en_passant = if piece_type == ChessPiece::Pawn && rank_delta.abs() == 2 {
+            mv.from.offset(0, rank_delta / 2)
+        }
+        else {
+            None
+        };
+
+        // A king move loses both castling rights, moving or capturing a rook loses that side.
+        if piece_type == ChessPiece::King {
+            self.castling_rights.set(team, ChessCastle::KingsideCastle, false);
+            self.castling_rights.set(team, ChessCastle::QueensideCastle, false);
+        }
+        for square in [mv.from, mv.to] {
+            for t in [Team::Light, Team::Dark] {
+                if square.get_rank() == t.back_rank() {
+                    match square.get_file() {
+                        ChessFile::A => self.castling_rights.set(t, ChessCastle::QueensideCastle, false),
+                        ChessFile::H => self.castling_rights.set(t, ChessCastle::KingsideCastle, false),
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        if piece_type == ChessPiece::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        }
+        else {
+            self.halfmove_clock += 1;
+        }
+        if team == Team::Dark {
+            self.fullmove_number += 1;
+        }
+        self.turn = team.opposite();
+        self.key ^= self.state_key();
+
+        captured
+    }
+
+    pub fn find_king(&self, team: Team) -> Option<BoardSquare> {
+        BoardSquare::all().find(|sq| self.get_piece_at(*sq) == Some(Piece::new(team, ChessPiece::King)))
+    }
+
+    pub fn is_in_check(&self, team: Team) -> bool {
+        match self.find_king(team) {
+            Some(king) => self.is_square_attacked(king, team.opposite()),
+            None => false,
+        }
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check(self.get_turn()) && self.legal_moves().is_empty()
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_in_check(self.get_turn()) && self.legal_moves().is_empty()
+    }
+
+    // Whether any piece of the attacking team attacks the given square.
+    pub fn is_square_attacked(&self, square: BoardSquare, by: Team) -> bool {
+        let has = |sq: Option<BoardSquare>, piece_types: &[ChessPiece]| -> bool {
+            match sq.and_then(|s| self.get_piece_at(s)) {
+                Some(p) => *p.get_team() == by && piece_types.contains(&p.get_piece_type()),
+                None => false,
+            }
+        };
+
+        // A pawn attacks diagonally forward, so look diagonally backward from the square.
+        for file_delta in [-1, 1] {
+            if has(square.offset(file_delta, -by.pawn_direction()), &[ChessPiece::Pawn]) {
+                return true;
+            }
+        }
+        if KNIGHT_OFFSETS.iter().any(|(f, r)| has(square.offset(*f, *r), &[ChessPiece::Knight])) {
+            return true;
+        }
+        if KING_OFFSETS.iter().any(|(f, r)| has(square.offset(*f, *r), &[ChessPiece::King])) {
+            return true;
+        }
+
+        let slider_attacks = |directions: &[(i32, i32)], piece_types: &[ChessPiece]| -> bool {
+            for (f, r) in directions {
+                let mut current = square.offset(*f, *r);
+                while let Some(sq) = current {
+                    if self.get_piece_at(sq).is_some() {
+                        if has(Some(sq), piece_types) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = sq.offset(*f, *r);
+                }
+            }
+            false
+        };
+        slider_attacks(&BISHOP_DIRECTIONS, &[ChessPiece::Bishop, ChessPiece::Queen])
+            || slider_attacks(&ROOK_DIRECTIONS, &[ChessPiece::Rook, ChessPiece::Queen])
+    }
+
+    // Counts the leaf nodes of the legal move tree to the given depth.
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.iter().map(|m| {
+            let mut next = self.clone();
+            next.make_move(*m);
+            next.perft(depth - 1)
+        }).sum()
+    }
+
+    // Whether playing the move keeps the moving side's king out of check.
+    fn is_move_safe(&self, mv: BoardMove) -> bool {
+        let mut next = self.clone();
+        next.make_move(mv);
+        !next.is_in_check(self.get_turn())
+    }
+
+    fn pseudo_legal_moves(&self) -> Vec<BoardMove> {
+        let mut moves = Vec::new();
+        for square in BoardSquare::all() {
+            if let Some(piece) = self.get_piece_at(square) {
+                if *piece.get_team() == self.get_turn() {
+                    self.pseudo_legal_moves_from(square, piece, &mut moves);
+                }
+            }
+        }
+        moves
+    }
+
+    fn pseudo_legal_moves_from(&self, from: BoardSquare, piece: Piece, moves: &mut Vec<BoardMove>) {
+        let team = *piece.get_team();
+        match piece.get_piece_type() {
+            ChessPiece::Pawn => self.pawn_moves(from, team, moves),
+            ChessPiece::Knight => self.step_moves(from, team, &KNIGHT_OFFSETS, moves),
+            ChessPiece::Bishop => self.slide_moves(from, team, &BISHOP_DIRECTIONS, moves),
+            ChessPiece::Rook => self.slide_moves(from, team, &ROOK_DIRECTIONS, moves),
+            ChessPiece::Queen => {
+                self.slide_moves(from, team, &BISHOP_DIRECTIONS, moves);
+                self.slide_moves(from, team, &ROOK_DIRECTIONS, moves);
+            }
+            ChessPiece::King => {
+                self.step_moves(from, team, &KING_OFFSETS, moves);
+                self.castle_moves(from, team, moves);
+            }
+        }
+    }
+
+    fn pawn_moves(&self, from: BoardSquare, team: Team, moves: &mut Vec<BoardMove>) {
+        let direction = team.pawn_direction();
+        let start_rank = if team == Team::Light { ChessRank::R2 } else { ChessRank::R7 };
+        let mut push = |to: BoardSquare| {
+            if to.get_rank() == team.opposite().back_rank() {
+                for p in PROMOTION_PIECES {
+                    moves.push(BoardMove::new(from, to, Some(p)));
+                }
+            }
+            else {
+                moves.push(BoardMove::new(from, to, None));
+            }
+        };
+
+        if let Some(one) = from.offset(0, direction) {
+            if self.get_piece_at(one).is_none() {
+                push(one);
+                if from.get_rank() == start_rank {
+                    if let Some(two) = one.offset(0, direction) {
+                        if self.get_piece_at(two).is_none() {
+                            push(two);
+                        }
+                    }
+                }
+            }
+        }
+
+        for file_delta in [-1, 1] {
+            if let Some(to) = from.offset(file_delta, direction) {
+                let captures_piece = matches!(self.get_piece_at(to), Some(p) if *p.get_team() != team);
+                if captures_piece || Some(to) == self.en_passant {
+                    push(to);
+                }
+            }
+        }
+    }
+
+    fn step_moves(&self, from: BoardSquare, team: Team, offsets: &[(i32, i32)], moves: &mut Vec<BoardMove>) {
+        for (f, r) in offsets {
+            if let Some(to) = from.offset(*f, *r) {
+                match self.get_piece_at(to) {
+                    Some(p) if *p.get_team() == team => (),
+                    _ => moves.push(BoardMove::new(from, to, None)),
+                }
+            }
+        }
+    }
+
+    fn slide_moves(&self, from: BoardSquare, team: Team, directions: &[(i32, i32)], moves: &mut Vec<BoardMove>) {
+        for (f, r) in directions {
+            let mut current = from.offset(*f, *r);
+            while let Some(to) = current {
+                match self.get_piece_at(to) {
+                    Some(p) => {
+                        if *p.get_team() != team {
+                            moves.push(BoardMove::new(from, to, None));
+                        }
+                        break;
+                    }
+                    None => moves.push(BoardMove::new(from, to, None)),
+                }
+                current = to.offset(*f, *r);
+            }
+        }
+    }
+
+    fn castle_moves(&self, from: BoardSquare, team: Team, moves: &mut Vec<BoardMove>) {
+        let rank = team.back_rank();
+        if from != BoardSquare::new(ChessFile::E, rank) || self.is_square_attacked(from, team.opposite()) {
+            return;
+        }
+
+        for side in [ChessCastle::KingsideCastle, ChessCastle::QueensideCastle] {
+            if !self.castling_rights.can_castle(team, side) {
+                continue;
+            }
+            let (rook_file, empty_files, king_path): (ChessFile, &[ChessFile], [ChessFile; 2]) = match side {
+                ChessCastle::KingsideCastle => (ChessFile::H, &[ChessFile::F, ChessFile::G], [ChessFile::F, ChessFile::G]),
+                ChessCastle::QueensideCastle => (ChessFile::A, &[ChessFile::B, ChessFile::C, ChessFile::D], [ChessFile::D, ChessFile::C]),
+            };
+            if self.get_piece_at(BoardSquare::new(rook_file, rank)) != Some(Piece::new(team, ChessPiece::Rook)) {
+                continue;
+            }
+            if empty_files.iter().any(|f| self.get_piece_at(BoardSquare::new(*f, rank)).is_some()) {
+                continue;
+            }
+            if king_path.iter().any(|f| self.is_square_attacked(BoardSquare::new(*f, rank), team.opposite())) {
+                continue;
+            }
+            moves.push(BoardMove::new(from, BoardSquare::new(king_path[1], rank), None));
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_move_generation {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    pub fn perft_start_position() {
+        let b = Board::new();
+        assert_eq!(b.perft(1), 20);
+        assert_eq!(b.perft(2), 400);
+        assert_eq!(b.perft(3), 8902);
+    }
+
+    #[test]
+    pub fn perft_kiwipete() {
+        // Castling, en passant and pins.
+        let b = board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        assert_eq!(b.perft(1), 48);
+        assert_eq!(b.perft(2), 2039);
+    }
+
+    #[test]
+    pub fn perft_en_passant_discovered_check() {
+        let b = board("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+        assert_eq!(b.perft(1), 14);
+        assert_eq!(b.perft(2), 191);
+        assert_eq!(b.perft(3), 2812);
+    }
+
+    #[test]
+    pub fn perft_promotions() {
+        let b = board("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1");
+        assert_eq!(b.perft(1), 6);
+        assert_eq!(b.perft(2), 264);
+        let b = board("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8");
+        assert_eq!(b.perft(1), 44);
+        assert_eq!(b.perft(2), 1486);
+    }
+
+    #[test]
+    pub fn make_move_updates_state() {
+        let mut b = Board::new();
+        b.make_move(BoardMove::from("e2e4").unwrap());
+        assert_eq!(b.get_turn(), Team::Dark);
+        assert_eq!(b.get_en_passant(), BoardSquare::from("e3"));
+        assert_eq!(b.get_halfmove_clock(), 0);
+        assert_eq!(b.get_fullmove_number(), 1);
+
+        b.make_move(BoardMove::from("g8f6").unwrap());
+        assert_eq!(b.get_turn(), Team::Light);
+        assert_eq!(b.get_en_passant(), None);
+        assert_eq!(b.get_halfmove_clock(), 1);
+        assert_eq!(b.get_fullmove_number(), 2);
+    }
+
+    #[test]
+    pub fn castling_moves_the_rook() {
+        let mut b = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(b.is_legal_move(BoardMove::from("e1g1").unwrap()));
+        b.make_move(BoardMove::from("e1g1").unwrap());
+        assert_eq!(b.get_piece_at(BoardSquare::from("f1").unwrap()), Some(Piece::new(Team::Light, ChessPiece::Rook)));
+        assert_eq!(b.get_piece_at(BoardSquare::from("h1").unwrap()), None);
+        assert!(!b.get_castling_rights().can_castle(Team::Light, ChessCastle::QueensideCastle));
+
+        b.make_move(BoardMove::from("e8c8").unwrap());
+        assert_eq!(b.get_piece_at(BoardSquare::from("d8").unwrap()), Some(Piece::new(Team::Dark, ChessPiece::Rook)));
+    }
+
+    #[test]
+    pub fn cannot_castle_through_check() {
+        // The rook on f2 covers f1 but none of the queenside squares.
+        let b = board("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1");
+        assert!(!b.is_legal_move(BoardMove::from("e1g1").unwrap()));
+        assert!(b.is_legal_move(BoardMove::from("e1c1").unwrap()));
+
+        // No castling out of check.
+        let b = board("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1");
+        assert!(!b.is_legal_move(BoardMove::from("e1g1").unwrap()));
+        assert!(!b.is_legal_move(BoardMove::from("e1c1").unwrap()));
+    }
+
+    #[test]
+    pub fn captures_and_quiet_moves_split_the_legal_moves() {
+        let b = board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        let (captures, quiet) = (b.legal_captures(), b.legal_quiet_moves());
+        assert_eq!(captures.len(), 8);
+        assert_eq!(captures.len() + quiet.len(), b.legal_moves().len());
+        assert!(captures.iter().all(|m| b.get_piece_at(m.get_to()).is_some()));
+        let en_passant = board("8/8/8/3pP3/8/8/8/4K2k w - d6 0 1");
+        assert_eq!(en_passant.legal_captures(), vec![BoardMove::from("e5d6").unwrap()]);
+    }
+
+    #[test]
+    pub fn en_passant_removes_captured_pawn() {
+        let mut b = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        let captured = b.make_move(BoardMove::from("e5d6").unwrap());
+        assert_eq!(captured, Some(Piece::new(Team::Dark, ChessPiece::Pawn)));
+        assert_eq!(b.get_piece_at(BoardSquare::from("d5").unwrap()), None);
+    }
+
+    #[test]
+    pub fn checkmate_and_stalemate() {
+        let mate = board("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert!(mate.is_checkmate());
+        assert!(!mate.is_stalemate());
+
+        let stalemate = board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert!(stalemate.is_stalemate());
+        assert!(!stalemate.is_checkmate());
+    }
+}
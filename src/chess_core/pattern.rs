@@ -0,0 +1,255 @@
+/*
+pattern.rs
+Queries for positions by their material and pawn structure, for searching collections of games.
+A query is one or more terms joined by "and", each of which must hold for some side:
+
+R+P vs R                the material of each side besides the king, either side having either
+Q vs 2R                 a count goes before the piece, letters can be run together: RBP vs RB
+iqp                     an isolated queen pawn, a d-pawn with no pawns of its side on c or e
+doubled f               two or more pawns of one side on a file, any file when none is given
+isolated a              a pawn with no pawns of its side on the files next to it
+passed                  a pawn with no enemy pawns ahead of it on its file or the files next to it
+R+P vs R and passed     both at once
+
+Pawn terms hold when either side has the pattern, and files may be written as f, f-pawn or
+f-pawns.
+*/
+
+use std::fmt::Display;
+
+use crate::chess_common::{ChessFile, ChessPiece};
+use super::{Board, BoardSquare, Piece, Team};
+
+// The pieces counted for material, the king being always on the board.
+const MATERIAL_PIECES: [ChessPiece; 5] = [
+    ChessPiece::Queen,
+    ChessPiece::Rook,
+    ChessPiece::Bishop,
+    ChessPiece::Knight,
+    ChessPiece::Pawn,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum PatternParseError {
+    Empty,
+    // A side of a material term that isn't piece letters and counts.
+    InvalidMaterial(String),
+    // A word that isn't a pawn pattern or a file.
+    UnknownTerm(String),
+}
+
+impl Display for PatternParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternParseError::Empty => write!(f, "the query has an empty term"),
+            PatternParseError::InvalidMaterial(side) => write!(f, "\"{side}\" isn't material, like R+P or Q+2B"),
+            PatternParseError::UnknownTerm(term) => write!(f, "\"{term}\" isn't material or a pawn pattern"),
+        }
+    }
+}
+
+impl std::error::Error for PatternParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PawnPattern {
+    IsolatedQueenPawn,
+    Doubled(Option<ChessFile>),
+    Isolated(Option<ChessFile>),
+    Passed(Option<ChessFile>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternTerm {
+    // The counts of queens, rooks, bishops, knights and pawns of each side.
+    Material([u32; 5], [u32; 5]),
+    Pawns(PawnPattern),
+}
+
+// A parsed query, holding when all of its terms do.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionPattern {
+    terms: Vec<PatternTerm>,
+}
+
+impl PositionPattern {
+    pub fn parse(query: &str) -> Result<PositionPattern, PatternParseError> {
+        let mut terms = Vec::new();
+        let mut words = Vec::new();
+        for word in query.split_whitespace().chain(std::iter::once("and")) {
+            if word.eq_ignore_ascii_case("and") {
+                terms.push(parse_term(&words)?);
+                words.clear();
+            }
+            else {
+                words.push(word);
+            }
+        }
+        Ok(PositionPattern { terms })
+    }
+
+    pub fn matches(&self, board: &Board) -> bool {
+        self.terms.iter().all(|term| match term {
+            PatternTerm::Material(one, other) => {
+                let (white, black) = (count_material(board, Team::Light), count_material(board, Team::Dark));
+                (white == *one && black == *other) || (white == *other && black == *one)
+            }
+            PatternTerm::Pawns(pattern) => [Team::Light, Team::Dark].into_iter().any(|team| has_pawn_pattern(board, team, *pattern)),
+        })
+    }
+}
+
+fn parse_term(words: &[&str]) -> Result<PatternTerm, PatternParseError> {
+    if words.is_empty() {
+        return Err(PatternParseError::Empty);
+    }
+    if let Some(vs) = words.iter().position(|w| w.eq_ignore_ascii_case("vs")) {
+        let one = parse_material(&words[..vs].concat())?;
+        let other = parse_material(&words[vs + 1..].concat())?;
+        return Ok(PatternTerm::Material(one, other));
+    }
+    let unknown = || PatternParseError::UnknownTerm(words.join(" "));
+    let file = match words.get(1) {
+        Some(word) => Some(parse_file(word).ok_or_else(unknown)?),
+        None => None,
+    };
+    if words.len() > 2 {
+        return Err(unknown());
+    }
+    let pattern = match words[0].to_lowercase().as_str() {
+        "iqp" if file.is_none() => PawnPattern::IsolatedQueenPawn,
+        "doubled" => PawnPattern::Doubled(file),
+        "isolated" => PawnPattern::Isolated(file),
+        "passed" => PawnPattern::Passed(file),
+        _ => return Err(unknown()),
+    };
+    Ok(PatternTerm::Pawns(pattern))
+}
+
+// One side's material, e.g. "R+2P" or "RPP". A king may be written and is not counted.
+fn parse_material(side: &str) -> Result<[u32; 5], PatternParseError> {
+    let invalid = || PatternParseError::InvalidMaterial(side.to_string());
+    let mut counts = [0; 5];
+    let mut count = None;
+    for c in side.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            count = Some(count.unwrap_or(0) * 10 + digit);
+            continue;
+        }
+        if c == '+' {
+            if count.is_some() {
+                return Err(invalid());
+            }
+            continue;
+        }
+        // ChessPiece::from reads SAN, where pawns have no letter.
+        let piece_type = match c.to_ascii_uppercase() {
+            'P' => ChessPiece::Pawn,
+            letter => ChessPiece::from(letter).ok_or_else(invalid)?,
+        };
+        let added = count.take().unwrap_or(1);
+        match MATERIAL_PIECES.iter().position(|p| *p == piece_type) {
+            Some(i) => counts[i] += added,
+            None if added == 1 => (),
+            None => return Err(invalid()),
+        }
+    }
+    if side.is_empty() || count.is_some() {
+        return Err(invalid());
+    }
+    Ok(counts)
+}
+
+// A file as "f", "f-pawn" or "f-pawns".
+fn parse_file(word: &str) -> Option<ChessFile> {
+    let lower = word.to_lowercase();
+    let letter = lower.strip_suffix("-pawns").or_else(|| lower.strip_suffix("-pawn")).unwrap_or(&lower);
+    let mut chars = letter.chars();
+    let file = ChessFile::from(chars.next()?)?;
+    chars.next().is_none().then_some(file)
+}
+
+fn count_material(board: &Board, team: Team) -> [u32; 5] {
+    let mut counts = [0; 5];
+    for (i, piece_type) in MATERIAL_PIECES.into_iter().enumerate() {
+        let piece = Some(Piece::new(team, piece_type));
+        counts[i] = BoardSquare::all().filter(|s| board.get_piece_at(*s) == piece).count() as u32;
+    }
+    counts
+}
+
+fn pawn_squares(board: &Board, team: Team) -> Vec<BoardSquare> {
+    let pawn = Some(Piece::new(team, ChessPiece::Pawn));
+    BoardSquare::all().filter(|s| board.get_piece_at(*s) == pawn).collect()
+}
+
+fn has_pawn_pattern(board: &Board, team: Team, pattern: PawnPattern) -> bool {
+    let pawns = pawn_squares(board, team);
+    let mut files = [0; 8];
+    for square in &pawns {
+        files[square.get_file().as_usize()] += 1;
+    }
+    let isolated = |file: usize| {
+        files[file] > 0 && (file == 0 || files[file - 1] == 0) && (file == 7 || files[file + 1] == 0)
+    };
+    let on_file = |file: Option<ChessFile>, index: usize| file.is_none_or(|f| f.as_usize() == index);
+    match pattern {
+        PawnPattern::IsolatedQueenPawn => isolated(ChessFile::D.as_usize()),
+        PawnPattern::Doubled(file) => (0..8).any(|i| on_file(file, i) && files[i] >= 2),
+        PawnPattern::Isolated(file) => (0..8).any(|i| on_file(file, i) && isolated(i)),
+        PawnPattern::Passed(file) => {
+            let enemy_pawns = pawn_squares(board, team.opposite());
+            pawns.iter().any(|pawn| {
+                let (pawn_file, pawn_rank) = (pawn.get_file().as_usize() as i32, pawn.get_rank().as_usize() as i32);
+                on_file(file, pawn_file as usize) && !enemy_pawns.iter().any(|enemy| {
+                    let ahead = (enemy.get_rank().as_usize() as i32 - pawn_rank) * team.pawn_direction() > 0;
+                    ahead && (enemy.get_file().as_usize() as i32 - pawn_file).abs() <= 1
+                })
+            })
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_pattern {
+    use super::*;
+
+    fn matches(query: &str, fen: &str) -> bool {
+        PositionPattern::parse(query).unwrap().matches(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    pub fn material_is_matched_for_either_side() {
+        let lucena = "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1";
+        assert!(matches("R+P vs R", lucena));
+        assert!(matches("r vs KRP", lucena));
+        assert!(!matches("R vs R", lucena));
+        assert!(!matches("R+2P vs R", lucena));
+        assert!(matches("Q vs 2R", "4k3/8/8/8/8/8/8/r2QK2r w - - 0 1"));
+        assert!(matches("RBPP vs R+B+2P", "8/5k2/2b2pp1/8/8/1B3PP1/r7/4R1K1 w - - 0 1"));
+        assert!(!matches("RBPP vs R+B+2P and passed", "8/5k2/2b2pp1/8/8/1B3PP1/r7/4R1K1 w - - 0 1"));
+    }
+
+    #[test]
+    pub fn pawn_structures_are_matched() {
+        // White has an isolated d-pawn and doubled f-pawns, Black a passed a-pawn.
+        let fen = "6k1/p4p1p/8/8/3P4/5P2/5PPP/6K1 w - - 0 1";
+        assert!(matches("iqp", fen));
+        assert!(matches("doubled f-pawns", fen));
+        assert!(matches("doubled", fen));
+        assert!(!matches("doubled g", fen));
+        assert!(matches("isolated a and passed a", fen));
+        assert!(matches("passed d", fen));
+        assert!(!matches("passed h", fen));
+        assert!(matches("iqp and 5P vs 3P", fen));
+    }
+
+    #[test]
+    pub fn bad_queries_are_told_apart() {
+        assert_eq!(PositionPattern::parse("R+X vs R"), Err(PatternParseError::InvalidMaterial(String::from("R+X"))));
+        assert_eq!(PositionPattern::parse("R vs"), Err(PatternParseError::InvalidMaterial(String::new())));
+        assert_eq!(PositionPattern::parse("tripled f"), Err(PatternParseError::UnknownTerm(String::from("tripled f"))));
+        assert_eq!(PositionPattern::parse("iqp and"), Err(PatternParseError::Empty));
+    }
+}
@@ -0,0 +1,242 @@
+/*
+san.rs
+Conversion between Standard Algebraic Notation (SAN) moves, as parsed into a ChessMove, and
+concrete BoardMoves on a given Board.
+*/
+
+use std::fmt::Display;
+use crate::chess_common::*;
+use crate::chess_pgn::ChessMove;
+use super::{Board, BoardMove, BoardSquare, Team};
+
+#[derive(Debug, PartialEq)]
+pub enum MoveResolveError {
+    IllegalMove,
+    AmbiguousMove(Vec<BoardMove>),
+    MissingPromotion,
+}
+
+impl Display for MoveResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveResolveError::IllegalMove => write!(f, "no legal move matches it in this position"),
+            MoveResolveError::AmbiguousMove(candidates) => {
+                let origins: Vec<String> = candidates.iter().map(|m| m.get_from().to_string()).collect();
+                write!(f, "it is ambiguous, the piece could come from {}", origins.join(" or "))
+            }
+            MoveResolveError::MissingPromotion => write!(f, "a pawn reaching the last rank must name its promotion piece"),
+        }
+    }
+}
+
+impl std::error::Error for MoveResolveError {}
+
+impl Board {
+    // Finds the legal move on this board that a SAN move refers to.
+    pub fn resolve_move(&self, chess_move: &ChessMove) -> Result<BoardMove, MoveResolveError> {
+        let legal_moves = self.legal_moves();
+
+        if let Some(castle) = chess_move.get_castle() {
+            let king_file = match castle {
+                ChessCastle::KingsideCastle => ChessFile::G,
+                ChessCastle::QueensideCastle => ChessFile::C,
+            };
+            return legal_moves.into_iter()
+                .find(|m| {
+                    self.piece_type_at(m.get_from()) == Some(ChessPiece::King)
+                        && m.get_from().get_file() == ChessFile::E
+                        && m.get_to().get_file() == king_file
+                })
+                .ok_or(MoveResolveError::IllegalMove);
+        }
+
+        let destination = match chess_move.get_destination().and_then(BoardSquare::from_coordinate) {
+            Some(d) => d,
+            None => return Err(MoveResolveError::IllegalMove),
+        };
+        let moving_piece = chess_move.get_moving_piece().copied().unwrap_or(ChessPiece::Pawn);
+
+        let mut candidates: Vec<BoardMove> = legal_moves.into_iter()
+            .filter(|m| m.get_to() == destination && self.piece_type_at(m.get_from()) == Some(moving_piece))
+            .filter(|m| match chess_move.get_origin() {
+                Some(origin) => {
+                    origin.get_file().is_none_or(|f| f == m.get_from().get_file())
+                        && origin.get_rank().is_none_or(|r| r == m.get_from().get_rank())
+                }
+                None => true,
+            })
+            .collect();
+
+        // Promotions generate one move per promotion piece, narrow them to the one asked for.
+        let is_promotion = candidates.iter().any(|m| m.get_promotion().is_some());
+        if is_promotion {
+            match chess_move.get_promotion() {
+                Some(p) => candidates.retain(|m| m.get_promotion() == Some(*p)),
+                None => return Err(MoveResolveError::MissingPromotion),
+            }
+        }
+        else if chess_move.get_promotion().is_some() {
+            return Err(MoveResolveError::IllegalMove);
+        }
+
+        match candidates.len() {
+            0 => Err(MoveResolveError::IllegalMove),
+            1 => Ok(candidates[0]),
+            _ => Err(MoveResolveError::AmbiguousMove(candidates)),
+        }
+    }
+
+    // Writes a legal move on this board as a SAN move with minimal disambiguation and
+    // check/checkmate markers.
+    pub fn to_chess_move(&self, mv: BoardMove) -> ChessMove {
+        let piece_type = self.piece_type_at(mv.get_from()).unwrap_or(ChessPiece::Pawn);
+        let is_capture = self.get_piece_at(mv.get_to()).is_some()
+            || (piece_type == ChessPiece::Pawn && mv.get_from().get_file() != mv.get_to().get_file());
+
+        let mut after = self.clone();
+        after.make_move(mv);
+        let gives_check = after.is_in_check(after.get_turn());
+        let gives_mate = gives_check && after.legal_moves().is_empty();
+
+        let mut builder = ChessMove::new()
+            .set_moving_piece(piece_type)
+            .set_is_check(gives_check && !gives_mate)
+            .set_is_checkmate(gives_mate);
+
+        let file_delta = mv.get_to().get_file().as_usize() as i32 - mv.get_from().get_file().as_usize() as i32;
+        if piece_type == ChessPiece::King && file_delta.abs() == 2 {
+            let castle = if file_delta > 0 { ChessCastle::KingsideCastle } else { ChessCastle::QueensideCastle };
+            builder = builder.set_castle(castle);
+        }
+        else {
+            builder = builder
+                .set_destination(mv.get_to().to_coordinate())
+                .set_is_capture(is_capture);
+            if let Some(p) = mv.get_promotion() {
+                builder = builder.set_promotion(p);
+            }
+
+            if piece_type == ChessPiece::Pawn {
+                if is_capture {
+                    builder = builder.set_origin(ChessCoordinate::from_file(mv.get_from().get_file()));
+                }
+            }
+            else {
+                let others: Vec<BoardSquare> = self.legal_moves().into_iter()
+                    .filter(|m| m.get_to() == mv.get_to() && m.get_from() != mv.get_from())
+                    .filter(|m| self.piece_type_at(m.get_from()) == Some(piece_type))
+                    .map(|m| m.get_from())
+                    .collect();
+                if !others.is_empty() {
+                    let from = mv.get_from();
+                    let origin = if others.iter().all(|o| o.get_file() != from.get_file()) {
+                        ChessCoordinate::from_file(from.get_file())
+                    }
+                    else if others.iter().all(|o| o.get_rank() != from.get_rank()) {
+                        ChessCoordinate::from_rank(from.get_rank())
+                    }
+                    else {
+                        from.to_coordinate()
+                    };
+                    builder = builder.set_origin(origin);
+                }
+            }
+        }
+
+        // The builder only rejects malformed notation, which a legal board move never produces.
+        builder.build().expect("a legal board move always forms a valid SAN move")
+    }
+
+    // A line of moves from this board in SAN with move numbers, e.g. "12... Nf6 13. e5", up to
+    // the first move that isn't legal where it is played.
+    pub fn to_san_line(&self, moves: &[BoardMove]) -> String {
+        let mut board = self.clone();
+        let mut text = Vec::new();
+        for (i, mv) in moves.iter().enumerate() {
+            if !board.is_legal_move(*mv) {
+                break;
+            }
+            let number = board.get_fullmove_number();
+            match board.get_turn() {
+                Team::Light => text.push(format!("{number}.")),
+                Team::Dark if i == 0 => text.push(format!("{number}...")),
+                Team::Dark => (),
+            }
+            text.push(board.to_chess_move(*mv).to_string());
+            board.make_move(*mv);
+        }
+        text.join(" ")
+    }
+
+    fn piece_type_at(&self, square: BoardSquare) -> Option<ChessPiece> {
+        self.get_piece_at(square).map(|p| p.get_piece_type())
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_san {
+    use super::*;
+
+    fn resolve(board: &Board, san: &str) -> Result<BoardMove, MoveResolveError> {
+        board.resolve_move(&ChessMove::from(san).unwrap())
+    }
+
+    #[test]
+    pub fn resolves_simple_moves() {
+        let b = Board::new();
+        assert_eq!(resolve(&b, "e4"), Ok(BoardMove::from("e2e4").unwrap()));
+        assert_eq!(resolve(&b, "Nf3"), Ok(BoardMove::from("g1f3").unwrap()));
+        assert_eq!(resolve(&b, "e5"), Err(MoveResolveError::IllegalMove));
+        assert_eq!(resolve(&b, "Ke2"), Err(MoveResolveError::IllegalMove));
+    }
+
+    #[test]
+    pub fn resolves_castling_and_promotion() {
+        let b = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(resolve(&b, "O-O"), Ok(BoardMove::from("e1g1").unwrap()));
+        assert_eq!(resolve(&b, "O-O-O"), Ok(BoardMove::from("e1c1").unwrap()));
+        assert_eq!(resolve(&b, "b8=N"), Ok(BoardMove::from("b7b8n").unwrap()));
+        assert_eq!(resolve(&b, "bxa8=Q"), Ok(BoardMove::from("b7a8q").unwrap()));
+        assert_eq!(resolve(&b, "b8"), Err(MoveResolveError::MissingPromotion));
+    }
+
+    #[test]
+    pub fn ambiguous_move_lists_candidates() {
+        let b = Board::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        match resolve(&b, "Nd2") {
+            Err(MoveResolveError::AmbiguousMove(candidates)) => assert_eq!(candidates.len(), 2),
+            other => panic!("expected an ambiguous move, got {:?}", other),
+        }
+        assert_eq!(resolve(&b, "Nbd2"), Ok(BoardMove::from("b1d2").unwrap()));
+        assert_eq!(resolve(&b, "N3d2"), Ok(BoardMove::from("f3d2").unwrap()));
+    }
+
+    #[test]
+    pub fn writes_san_with_disambiguation_and_checks() {
+        let b = Board::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(b.to_chess_move(BoardMove::from("b1d2").unwrap()).to_string(), "Nbd2");
+
+        let b = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(b.to_chess_move(BoardMove::from("e1g1").unwrap()).to_string(), "O-O");
+        assert_eq!(b.to_chess_move(BoardMove::from("a1a8").unwrap()).to_string(), "Ra8+");
+
+        let b = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2").unwrap();
+        assert_eq!(b.to_chess_move(BoardMove::from("d8h4").unwrap()).to_string(), "Qh4#");
+
+        let b = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert_eq!(b.to_chess_move(BoardMove::from("e5d6").unwrap()).to_string(), "exd6");
+    }
+
+    #[test]
+    pub fn lines_are_written_with_move_numbers() {
+        let moves: Vec<BoardMove> = ["e2e4", "e7e5", "g1f3"].iter().map(|m| BoardMove::from(m).unwrap()).collect();
+        assert_eq!(Board::new().to_san_line(&moves), "1. e4 e5 2. Nf3");
+        let mut b = Board::new();
+        b.make_move(moves[0]);
+        assert_eq!(b.to_san_line(&moves[1..]), "1... e5 2. Nf3");
+        // The line stops at a move that doesn't fit.
+        assert_eq!(Board::new().to_san_line(&[moves[0], moves[2], moves[1]]), "1. e4");
+    }
+}
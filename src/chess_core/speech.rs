@@ -0,0 +1,178 @@
+/*
+speech.rs
+The position and moves in plain words, for screen readers and anyone who can't see the board.
+Moves are told with the squares they go between, rather than in SAN:
+
+White knight from g1 to f3
+Black bishop from b4 takes knight on c3, check
+White pawn from e7 to e8, promotes to queen, checkmate
+*/
+
+use crate::chess_common::{ChessFile, ChessPiece, ChessRank};
+use super::{Board, BoardMove, BoardSquare, Piece, Team};
+
+// The order pieces are listed in when the position is read.
+const PIECE_ORDER: [ChessPiece; 6] = [
+    ChessPiece::King,
+    ChessPiece::Queen,
+    ChessPiece::Rook,
+    ChessPiece::Bishop,
+    ChessPiece::Knight,
+    ChessPiece::Pawn,
+];
+
+impl Board {
+    // A legal move on this board in words, with what it captures and whether it checks.
+    pub fn describe_move(&self, mv: BoardMove) -> String {
+        let piece = match self.get_piece_at(mv.get_from()) {
+            Some(p) => p,
+            None => return format!("from {} to {}", mv.get_from(), mv.get_to()),
+        };
+        let team = *piece.get_team();
+        let piece_type = piece.get_piece_type();
+        let file_delta = mv.get_to().get_file().as_usize() as i32 - mv.get_from().get_file().as_usize() as i32;
+        let en_passant = piece_type == ChessPiece::Pawn && file_delta != 0 && self.get_piece_at(mv.get_to()).is_none();
+
+        let mut text = if piece_type == ChessPiece::King && file_delta.abs() == 2 {
+            let side = if file_delta > 0 { "kingside" } else { "queenside" };
+            format!("{team} castles {side}")
+        }
+        else if en_passant {
+            format!("{team} pawn from {} takes pawn on {} en passant", mv.get_from(), mv.get_to())
+        }
+        else {
+            match self.get_piece_at(mv.get_to()) {
+                Some(captured) => format!("{team} {} from {} takes {} on {}",
+                    piece_type.get_name(), mv.get_from(), captured.get_piece_type().get_name(), mv.get_to()),
+                None => format!("{team} {} from {} to {}", piece_type.get_name(), mv.get_from(), mv.get_to()),
+            }
+        };
+        if let Some(promotion) = mv.get_promotion() {
+            text += format!(", promotes to {}", promotion.get_name()).as_str();
+        }
+
+        let mut after = self.clone();
+        after.make_move(mv);
+        if after.is_checkmate() {
+            text += ", checkmate";
+        }
+        else if after.is_in_check(after.get_turn()) {
+            text += ", check";
+        }
+        else if after.is_stalemate() {
+            text += ", stalemate";
+        }
+        text
+    }
+
+    // What stands on a square, e.g. "e4: White pawn" or "e5: empty".
+    pub fn describe_square(&self, square: BoardSquare) -> String {
+        match self.get_piece_at(square) {
+            Some(p) => format!("{square}: {}", describe_piece(p)),
+            None => format!("{square}: empty"),
+        }
+    }
+
+    // The pieces on a rank from the a file to the h file, e.g. "rank 1: a1 White rook, e1 White
+    // king". Empty squares are left out.
+    pub fn describe_rank(&self, rank: ChessRank) -> String {
+        let squares = (0..8).filter_map(|f| BoardSquare::from_indices(f, rank.as_usize()));
+        format!("rank {rank}: {}", self.describe_squares(squares))
+    }
+
+    // The pieces on a file from the first rank to the eighth, e.g. "file e: e1 White king".
+    pub fn describe_file(&self, file: ChessFile) -> String {
+        let squares = (0..8).filter_map(|r| BoardSquare::from_indices(file.as_usize(), r));
+        format!("file {file}: {}", self.describe_squares(squares))
+    }
+
+    // Every piece on the board, White's and then Black's from the king down to the pawns, and the
+    // side to move:
+    // White: king on g1, rook on f1, pawns on f2, g2 and h2. Black: king on g8. Black to move.
+    pub fn describe_position(&self) -> String {
+        let mut sides = Vec::new();
+        for team in [Team::Light, Team::Dark] {
+            let mut groups = Vec::new();
+            for piece_type in PIECE_ORDER {
+                let squares: Vec<String> = BoardSquare::all()
+                    .filter(|s| self.get_piece_at(*s) == Some(Piece::new(team, piece_type)))
+                    .map(|s| s.to_string())
+                    .collect();
+                match squares.len() {
+                    0 => (),
+                    1 => groups.push(format!("{} on {}", piece_type.get_name(), squares[0])),
+                    _ => groups.push(format!("{}s on {}", piece_type.get_name(), join_words(&squares))),
+                }
+            }
+            if groups.is_empty() {
+                groups.push(String::from("no pieces"));
+            }
+            sides.push(format!("{team}: {}.", groups.join(", ")));
+        }
+        format!("{} {} to move.", sides.join(" "), self.get_turn())
+    }
+
+    fn describe_squares(&self, squares: impl Iterator<Item = BoardSquare>) -> String {
+        let pieces: Vec<String> = squares
+            .filter_map(|s| self.get_piece_at(s).map(|p| format!("{s} {}", describe_piece(p))))
+            .collect();
+        if pieces.is_empty() {
+            String::from("empty")
+        }
+        else {
+            pieces.join(", ")
+        }
+    }
+}
+
+fn describe_piece(piece: Piece) -> String {
+    format!("{} {}", piece.get_team(), piece.get_piece_type().get_name())
+}
+
+// A list read out as words, e.g. "a2, b2 and c2".
+fn join_words(words: &[String]) -> String {
+    match words {
+        [] => String::new(),
+        [word] => word.clone(),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_speech {
+    use super::*;
+
+    fn board_move(mv: &str) -> BoardMove {
+        BoardMove::from(mv).unwrap()
+    }
+
+    #[test]
+    pub fn moves_are_told_in_words() {
+        let start = Board::new();
+        assert_eq!(start.describe_move(board_move("g1f3")), "White knight from g1 to f3");
+
+        let scholars = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4").unwrap();
+        assert_eq!(scholars.describe_move(board_move("h5f7")), "White queen from h5 takes pawn on f7, checkmate");
+
+        let castling = Board::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        assert_eq!(castling.describe_move(board_move("e1g1")), "White castles kingside");
+        assert_eq!(castling.describe_move(board_move("e5d6")), "White pawn from e5 takes pawn on d6 en passant");
+
+        let promotion = Board::from_fen("7k/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(promotion.describe_move(board_move("e7e8q")), "White pawn from e7 to e8, promotes to queen, check");
+    }
+
+    #[test]
+    pub fn the_board_is_read_by_square_rank_and_file() {
+        let board = Board::from_fen("6k1/8/8/8/8/8/5PPP/5RK1 b - - 0 1").unwrap();
+        assert_eq!(board.describe_square(BoardSquare::from("g1").unwrap()), "g1: White king");
+        assert_eq!(board.describe_square(BoardSquare::from("e4").unwrap()), "e4: empty");
+        assert_eq!(board.describe_rank(ChessRank::R1), "rank 1: f1 White rook, g1 White king");
+        assert_eq!(board.describe_rank(ChessRank::R5), "rank 5: empty");
+        assert_eq!(board.describe_file(ChessFile::G), "file g: g1 White king, g2 White pawn, g8 Black king");
+        assert_eq!(board.describe_position(),
+            "White: king on g1, rook on f1, pawns on f2, g2 and h2. Black: king on g8. Black to move.");
+    }
+}
@@ -0,0 +1,173 @@
+/*
+zobrist.rs
+Zobrist keys, 64 bit fingerprints of positions: the XOR of a random number for each piece on its
+square, for the side to move, for each castling right and for the file of the en passant square.
+Equal positions get equal keys, and different positions equal keys only by a rare accident, so
+positions can be compared and looked up by key. The random numbers are fixed, keys are the same
+from one run of the program to the next and can be kept in files. A move changes only a few of
+the numbers, so the board XORs them in and out as it is played instead of going over every square.
+*/
+
+use crate::chess_common::{ChessCastle, ChessPiece};
+use super::{Board, BoardSquare, Piece, Team};
+
+const PIECE_KEYS: usize = 12 * 64;
+const TURN_KEY: usize = PIECE_KEYS;
+const CASTLING_KEYS: usize = TURN_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const KEY_COUNT: usize = EN_PASSANT_KEYS + 8;
+
+static KEYS: [u64; KEY_COUNT] = generate_keys();
+
+// The random numbers, from SplitMix64 with a fixed seed.
+const fn generate_keys() -> [u64; KEY_COUNT] {
+    let mut keys = [0; KEY_COUNT];
+    let mut state: u64 = 0x5275_7374_4368_6573;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+fn piece_index(piece_type: ChessPiece) -> usize {
+    match piece_type {
+        ChessPiece::Pawn => 0,
+        ChessPiece::Knight => 1,
+        ChessPiece::Bishop => 2,
+        ChessPiece::Rook => 3,
+        ChessPiece::Queen => 4,
+        ChessPiece::King => 5,
+    }
+}
+
+fn team_index(team: Team) -> usize {
+    match team {
+        Team::Light => 0,
+        Team::Dark => 1,
+    }
+}
+
+// The key of a piece on a square, 0 for an empty square.
+pub(super) fn piece_key(piece: Option<Piece>, square: BoardSquare) -> u64 {
+    piece.map_or(0, |piece| {
+        let kind = team_index(*piece.get_team()) * 6 + piece_index(piece.get_piece_type());
+        KEYS[kind * 64 + square.get_rank().as_usize() * 8 + square.get_file().as_usize()]
+    })
+}
+
+impl Board {
+    // The Zobrist key of the position. The move clocks are left out, so positions reached again
+    // have the key they had before. The key is kept up to date by set_piece_at and make_move
+    // rather than worked out again for every position.
+    pub fn zobrist_key(&self) -> u64 {
+        self.key
+    }
+
+    // The key worked out from scratch, for positions set up all at once.
+    pub(super) fn compute_zobrist_key(&self) -> u64 {
+        BoardSquare::all().fold(self.state_key(), |key, square| key ^ piece_key(self.get_piece_at(square), square))
+    }
+
+    // The part of the key for the side to move, the castling rights and the en passant square.
+    pub(super) fn state_key(&self) -> u64 {
+        let mut key = 0;
+        if self.turn == Team::Dark {
+            key ^= KEYS[TURN_KEY];
+        }
+        let rights = [
+            (Team::Light, ChessCastle::KingsideCastle),
+            (Team::Light, ChessCastle::QueensideCastle),
+            (Team::Dark, ChessCastle::KingsideCastle),
+            (Team::Dark, ChessCastle::QueensideCastle),
+        ];
+        for (i, (team, side)) in rights.into_iter().enumerate() {
+            if self.castling_rights.can_castle(team, side) {
+                key ^= KEYS[CASTLING_KEYS + i];
+            }
+        }
+        // The en passant square only counts when a pawn could take there, so a double pawn push
+        // nothing can take gives the key the position has when reached by other moves.
+        if let Some(square) = self.en_passant.filter(|s| self.can_take_en_passant(*s)) {
+            key ^= KEYS[EN_PASSANT_KEYS + square.get_file().as_usize()];
+        }
+        key
+    }
+
+    fn can_take_en_passant(&self, square: BoardSquare) -> bool {
+        let pawn = Some(Piece::new(self.turn, ChessPiece::Pawn));
+        [-1, 1].into_iter().any(|file_delta| {
+            square.offset(file_delta, -self.turn.pawn_direction()).is_some_and(|s| self.get_piece_at(s) == pawn)
+        })
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_zobrist {
+    use super::*;
+    use crate::chess_core::BoardMove;
+
+    fn play(board: &Board, moves: &[&str]) -> Board {
+        let mut board = board.clone();
+        for mv in moves {
+            board.make_move(BoardMove::from(mv).unwrap());
+        }
+        board
+    }
+
+    #[test]
+    pub fn transpositions_share_a_key() {
+        let start = Board::new();
+        let one_way = play(&start, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let other_way = play(&start, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert_eq!(one_way.zobrist_key(), other_way.zobrist_key());
+        assert_ne!(one_way.zobrist_key(), start.zobrist_key());
+
+        // Knights out and back only changes the move clocks, which aren't part of the key.
+        let back = play(&start, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(back.zobrist_key(), start.zobrist_key());
+
+        // A double pawn push no pawn can take en passant transposes too.
+        let one_way = play(&start, &["e2e4", "g8f6", "g1f3"]);
+        let other_way = play(&start, &["g1f3", "g8f6", "e2e4"]);
+        assert_eq!(one_way.zobrist_key(), other_way.zobrist_key());
+
+        // The side to move, the castling rights and the en passant square count.
+        let en_passant = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/2Pp4/8/PP1PPPPP/RNBQKBNR b KQkq c3 0 1").unwrap();
+        assert_ne!(en_passant.zobrist_key(), Board::from_fen("rnbqkbnr/ppp1pppp/8/8/2Pp4/8/PP1PPPPP/RNBQKBNR b KQkq - 0 1").unwrap().zobrist_key());
+        assert_ne!(start.zobrist_key(), Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1").unwrap().zobrist_key());
+    }
+
+    // Walks every line to the depth, checking the key made move by move against the one worked
+    // out from the position.
+    fn check_keys(board: &Board, depth: u32) {
+        assert_eq!(board.zobrist_key(), board.compute_zobrist_key(), "{}", board.to_fen());
+        if depth > 0 {
+            for mv in board.legal_moves() {
+                let mut next = board.clone();
+                next.make_move(mv);
+                check_keys(&next, depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    pub fn keys_are_updated_move_by_move() {
+        // Castling, castling rights lost to captures, en passant and promotions.
+        check_keys(&Board::new(), 3);
+        check_keys(&Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(), 3);
+        check_keys(&Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(), 4);
+        check_keys(&Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap(), 3);
+
+        let mut board = Board::new();
+        board.set_piece_at(BoardSquare::from("e2").unwrap(), None);
+        assert_eq!(board.zobrist_key(), Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1").unwrap().zobrist_key());
+    }
+}
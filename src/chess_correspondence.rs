@@ -0,0 +1,314 @@
+/*
+chess_correspondence.rs
+Correspondence games, played over days or weeks through a game file both players can reach, e.g.
+in a shared folder. The file starts with the game's PGN tags, and every move appends a line with
+the half move's number, the move in SAN and a checksum:
+
+[White "Raul Rojas"]
+[Black "Magnus"]
+
+1 e4 9c3b1a0f8e2d4c6b
+2 c5 0a7d5e3c1b9f8e6d
+
+Each checksum covers the one before it, the move and the position after it, so a file that was
+edited by hand, cut short or written by two moves at once is caught when the game is read. Moves
+are only appended on the side's turn, and notify waits for the opponent's move.
+*/
+
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    chess_cmd::{ChessArgs, CorrespondenceAction},
+    chess_config::ChessConfig,
+    chess_core::Team,
+    chess_game::{GameState, GameStatus},
+    chess_pgn::{ChessMove, PgnDate},
+};
+
+// The checksum of the game before its first move.
+const INITIAL_CHECKSUM: u64 = 0xcbf29ce484222325;
+
+#[derive(Debug)]
+pub enum CorrespondenceError {
+    Io(PathBuf, io::Error),
+    FileExists(PathBuf),
+    // A line of the file that isn't what it should be, by its number.
+    InvalidLine(usize, String),
+    NotYourTurn(Team),
+    GameOver(String),
+    UnknownSide,
+    InvalidMove(String, String),
+}
+
+impl Display for CorrespondenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CorrespondenceError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            CorrespondenceError::FileExists(path) => write!(f, "{} already exists", path.display()),
+            CorrespondenceError::InvalidLine(line, reason) => write!(f, "line {}: {}", line, reason),
+            CorrespondenceError::NotYourTurn(team) => write!(f, "it is {}'s move", team),
+            CorrespondenceError::GameOver(status) => write!(f, "the game is over ({})", status),
+            CorrespondenceError::UnknownSide => write!(f, "say which side you play with --as, or set player_name to your name in the game"),
+            CorrespondenceError::InvalidMove(san, e) => write!(f, "{} can't be played: {}", san, e),
+        }
+    }
+}
+
+impl std::error::Error for CorrespondenceError {}
+
+// A correspondence game as read from its file.
+pub struct CorrespondenceGame {
+    path: PathBuf,
+    state: GameState,
+    // The checksum of the last move, the one the next move's checksum covers.
+    checksum: u64,
+}
+
+impl CorrespondenceGame {
+    // Starts a game file between two players. An existing file is never replaced.
+    pub fn create(path: &Path, white: &str, black: &str) -> Result<CorrespondenceGame, CorrespondenceError> {
+        let io_error = |e| CorrespondenceError::Io(path.to_path_buf(), e);
+        let mut state = GameState::new();
+        let pgn_game = state.get_pgn_game_mut();
+        pgn_game.set_event(String::from("Correspondence game"));
+        pgn_game.set_date(PgnDate::now());
+        pgn_game.set_white(String::from(white));
+        pgn_game.set_black(String::from(black));
+        let header: String = ["Event", "Date", "White", "Black"].iter()
+            .map(|tag| format!("[{tag} \"{}\"]\n", pgn_game.get_tag(tag).unwrap_or_default()))
+            .collect();
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => CorrespondenceError::FileExists(path.to_path_buf()),
+            _ => io_error(e),
+        })?;
+        file.write_all(format!("{header}\n").as_bytes()).map_err(io_error)?;
+        Ok(CorrespondenceGame { path: path.to_path_buf(), state, checksum: INITIAL_CHECKSUM })
+    }
+
+    // Reads a game file, checking every move and its checksum.
+    pub fn load(path: &Path) -> Result<CorrespondenceGame, CorrespondenceError> {
+        let text = fs::read_to_string(path).map_err(|e| CorrespondenceError::Io(path.to_path_buf(), e))?;
+        let mut game = CorrespondenceGame { path: path.to_path_buf(), state: GameState::new(), checksum: INITIAL_CHECKSUM };
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |reason: String| CorrespondenceError::InvalidLine(index + 1, reason);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(tag) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let (name, value) = tag.split_once(' ').ok_or_else(|| invalid(String::from("invalid tag pair")))?;
+                let value = value.trim().trim_matches('"');
+                game.state.get_pgn_game_mut().set_tag(name, String::from(value));
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (ply, san, checksum) = match fields.as_slice() {
+                [ply, san, checksum] => (*ply, *san, *checksum),
+                _ => return Err(invalid(String::from("expected a half move number, a move and a checksum"))),
+            };
+            let expected_ply = game.state.get_ply_count() + 1;
+            if ply.parse::<usize>().ok() != Some(expected_ply) {
+                return Err(invalid(format!("expected half move {expected_ply}, found {ply}")));
+            }
+            let chess_move = ChessMove::from(san).map_err(|e| invalid(format!("invalid move {san}: {e}")))?;
+            game.state.play_move(&chess_move).map_err(|e| invalid(format!("illegal move {san}: {e}")))?;
+            let next = game.next_checksum(san);
+            if u64::from_str_radix(checksum, 16).ok() != Some(next) {
+                return Err(invalid(format!("the checksum of {san} doesn't match, the file was changed")));
+            }
+            game.checksum = next;
+        }
+        Ok(game)
+    }
+
+    pub fn get_state(&self) -> &GameState {
+        &self.state
+    }
+
+    // The players' names by side.
+    pub fn get_player(&self, team: Team) -> &String {
+        match team {
+            Team::Light => self.state.get_pgn_game().get_white(),
+            Team::Dark => self.state.get_pgn_game().get_black(),
+        }
+    }
+
+    // The side a player has in this game, by name.
+    pub fn find_side(&self, name: &str) -> Option<Team> {
+        [Team::Light, Team::Dark].into_iter().find(|t| self.get_player(*t).eq_ignore_ascii_case(name))
+    }
+
+    // Plays a move for `side` and appends it to the file.
+    pub fn play(&mut self, side: Team, san: &str) -> Result<ChessMove, CorrespondenceError> {
+        let status = self.state.get_status();
+        if matches!(status, GameStatus::Checkmate { .. } | GameStatus::Stalemate | GameStatus::Ended(_)) {
+            return Err(CorrespondenceError::GameOver(status.to_string()));
+        }
+        let turn = self.state.get_board().get_turn();
+        if turn != side {
+            return Err(CorrespondenceError::NotYourTurn(turn));
+        }
+        let invalid = |e: String| CorrespondenceError::InvalidMove(String::from(san), e);
+        let chess_move = ChessMove::from(san).map_err(|e| invalid(e.to_string()))?;
+        let played = self.state.play_move(&chess_move).map_err(|e| invalid(e.to_string()))?;
+
+        let recorded = played.to_string();
+        let checksum = self.next_checksum(&recorded);
+        let line = format!("{} {recorded} {checksum:016x}\n", self.state.get_ply_count());
+        let io_error = |e| CorrespondenceError::Io(self.path.clone(), e);
+        OpenOptions::new().append(true).open(&self.path).and_then(|mut f| f.write_all(line.as_bytes())).map_err(io_error)?;
+        self.checksum = checksum;
+        Ok(played)
+    }
+
+    // The checksum of the move just played: FNV-1a over the last checksum, the half move number,
+    // the move and the position after it.
+    fn next_checksum(&self, san: &str) -> u64 {
+        let text = format!("{:016x} {} {san} {}", self.checksum, self.state.get_ply_count(), self.state.get_board().to_fen());
+        text.bytes().fold(INITIAL_CHECKSUM, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+    }
+
+    // e.g. "12. Nf3 was played, Black to move (Magnus)".
+    pub fn describe(&self) -> String {
+        let last = match self.state.get_pgn_game().get_plies().last() {
+            Some((chess_move, _)) => {
+                let ply = self.state.get_ply_count();
+                let dots = if ply % 2 == 1 { "." } else { "..." };
+                format!("{}{dots} {chess_move} was played, ", ply.div_ceil(2))
+            }
+            None => String::from("No moves yet, "),
+        };
+        match self.state.get_status() {
+            GameStatus::ToMove(team) | GameStatus::InCheck(team) => format!("{last}{team} to move ({})", self.get_player(team)),
+            status => format!("{last}{status}"),
+        }
+    }
+}
+
+pub fn correspondence_main(args: &ChessArgs, action: &CorrespondenceAction) {
+    if let Err(e) = run(args, action) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &ChessArgs, action: &CorrespondenceAction) -> Result<(), CorrespondenceError> {
+    match action {
+        CorrespondenceAction::New { file_path, white, black } => {
+            let game = CorrespondenceGame::create(file_path, white, black)?;
+            println!("Started {} against {} in {}. {}", white, black, file_path.display(), game.describe());
+        }
+        CorrespondenceAction::Move { file_path, pgn_move, side } => {
+            let mut game = CorrespondenceGame::load(file_path)?;
+            let side = player_side(args, &game, side.as_deref())?;
+            game.play(side, pgn_move)?;
+            println!("{}", game.describe());
+        }
+        CorrespondenceAction::Show { file_path } => {
+            let game = CorrespondenceGame::load(file_path)?;
+            println!("{}\n", game.get_state().get_pgn_game());
+            println!("{}", game.describe());
+        }
+        CorrespondenceAction::Notify { file_path, side, interval } => {
+            let side = player_side(args, &CorrespondenceGame::load(file_path)?, side.as_deref())?;
+            let interval = Duration::from_secs(*interval);
+            loop {
+                // The opponent may be writing the file right now, so it is only given up on when
+                // it can't be read at all.
+                let game = match CorrespondenceGame::load(file_path) {
+                    Ok(g) => g,
+                    Err(CorrespondenceError::Io(path, e)) => return Err(CorrespondenceError::Io(path, e)),
+                    Err(_) => {
+                        thread::sleep(interval);
+                        continue;
+                    }
+                };
+                let waiting = game.get_state().get_board().get_turn() != side
+                    && matches!(game.get_state().get_status(), GameStatus::ToMove(_) | GameStatus::InCheck(_));
+                if !waiting {
+                    // The terminal bell, for whoever isn't looking.
+                    println!("\u{7}{}", game.describe());
+                    break;
+                }
+                thread::sleep(interval);
+            }
+        }
+    }
+    Ok(())
+}
+
+// The side the player running the program has: the one given, or the one their configured
+// player_name plays.
+fn player_side(args: &ChessArgs, game: &CorrespondenceGame, side: Option<&str>) -> Result<Team, CorrespondenceError> {
+    match side {
+        Some("white") => return Ok(Team::Light),
+        Some("black") => return Ok(Team::Dark),
+        _ => (),
+    }
+    let config = args.config.clone()
+        .or_else(ChessConfig::default_path)
+        .and_then(|path| ChessConfig::load(&path).ok())
+        .unwrap_or_default();
+    config.get_player_name().and_then(|name| game.find_side(name)).ok_or(CorrespondenceError::UnknownSide)
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_correspondence {
+    use super::*;
+
+    fn game_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rust_chess_correspondence_{name}_{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    pub fn moves_are_appended_and_checked() {
+        let path = game_file("moves");
+        let mut game = CorrespondenceGame::create(&path, "Raul Rojas", "Magnus").unwrap();
+        assert!(matches!(CorrespondenceGame::create(&path, "a", "b"), Err(CorrespondenceError::FileExists(_))));
+        game.play(Team::Light, "e4").unwrap();
+        assert_eq!(game.play(Team::Light, "d4").unwrap_err().to_string(), "it is Black's move");
+        game.play(Team::Dark, "c5").unwrap();
+
+        let game = CorrespondenceGame::load(&path).unwrap();
+        assert_eq!(game.get_state().get_ply_count(), 2);
+        assert_eq!(game.find_side("magnus"), Some(Team::Dark));
+        assert_eq!(game.describe(), "1... c5 was played, White to move (Raul Rojas)");
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("[Event \"Correspondence game\"]\n"), "{text}");
+        assert!(text.contains("\n\n1 e4 "), "{text}");
+
+        // Changing a move is caught.
+        fs::write(&path, text.replace(" c5 ", " c6 ")).unwrap();
+        let error = CorrespondenceGame::load(&path).err().unwrap();
+        assert_eq!(error.to_string(), "line 7: the checksum of c6 doesn't match, the file was changed");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn finished_games_take_no_moves() {
+        let path = game_file("finished");
+        let mut game = CorrespondenceGame::create(&path, "White", "Black").unwrap();
+        for (side, san) in [(Team::Light, "f3"), (Team::Dark, "e5"), (Team::Light, "g4"), (Team::Dark, "Qh4#")] {
+            game.play(side, san).unwrap();
+        }
+        assert!(matches!(game.play(Team::Light, "a3"), Err(CorrespondenceError::GameOver(_))));
+        assert_eq!(CorrespondenceGame::load(&path).unwrap().describe(), "2... Qh4# was played, Checkmate \u{2014} Black wins");
+        let _ = fs::remove_file(&path);
+    }
+}
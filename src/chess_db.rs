@@ -0,0 +1,416 @@
+/*
+chess_db.rs
+The db mode of the program, for collections of games kept in PGN files. db dedup finds the games
+that are in the collection more than once, e.g. after importing the same tournament from two
+sites, by fingerprinting each game with the Zobrist key of its final position, its number of half
+moves and its players:
+
+rust_chess db dedup 2023.pgn 2024.pgn --remove
+
+Exact duplicates share the fingerprint. Near duplicates are the same moves under players named
+differently, e.g. "Carlsen, M" and "Carlsen, Magnus", and copies cut short, whose moves begin a
+longer game of the same players. Only exact duplicates are ever removed, near ones need a look.
+
+db search replays the games and finds those reaching a material balance or pawn structure, as
+queried in the language of pattern.rs:
+
+rust_chess db search "R+P vs R" games.pgn --out lucena.pgn
+*/
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chess_cmd::DbAction,
+    chess_core::{pattern::{PatternParseError, PositionPattern}, Board, Team},
+    chess_game::GameState,
+    chess_pgn::{parse_pgn_games_with, PgnGame, PgnParseError, PgnTextMode},
+};
+
+// Near duplicates need at least this many half moves in common, shorter games, like quick draws
+// in a well known line, are too often played twice for real.
+const NEAR_DUPLICATE_MIN_PLIES: usize = 20;
+// The width files are written again with, as pgn fmt writes them by default.
+const REWRITE_WIDTH: usize = 79;
+
+#[derive(Debug)]
+pub enum DbError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, PgnParseError),
+    Query(PatternParseError),
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            DbError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            DbError::Query(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+// Where a game is: its file, and its place in the file counted from 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameLocation {
+    pub path: PathBuf,
+    pub number: usize,
+}
+
+impl Display for GameLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} #{}", self.path.display(), self.number)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateKind {
+    Exact,
+    OtherPlayers,
+    // The copy stops after this many half moves of the original.
+    CutShort(usize),
+}
+
+// A game found again in the collection. The original is the copy met first, or for a game cut
+// short the longer game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicate {
+    pub kind: DuplicateKind,
+    pub original: GameLocation,
+    pub copy: GameLocation,
+}
+
+impl Display for Duplicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            DuplicateKind::Exact => write!(f, "{} is a copy of {}", self.copy, self.original),
+            DuplicateKind::OtherPlayers => write!(f, "{} has the moves of {} under other player names", self.copy, self.original),
+            DuplicateKind::CutShort(plies) => write!(f, "{} is {} cut short after {} half moves", self.copy, self.original, plies),
+        }
+    }
+}
+
+// A game as it is told apart from the others.
+struct Fingerprint {
+    location: GameLocation,
+    players: (String, String),
+    // The Zobrist key of the position after each half move, and of the starting position first.
+    keys: Vec<u64>,
+}
+
+impl Fingerprint {
+    fn get_plies(&self) -> usize {
+        self.keys.len() - 1
+    }
+
+    fn get_final_key(&self) -> u64 {
+        self.keys[self.keys.len() - 1]
+    }
+}
+
+// A player's name with case, spaces and punctuation left out, so differently written copies of
+// one game still match.
+fn normalize_player(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+// The fingerprint of a game, or why the game can't be replayed.
+fn fingerprint(game: &PgnGame, location: GameLocation) -> Result<Fingerprint, String> {
+    let players = (normalize_player(game.get_white()), normalize_player(game.get_black()));
+    let state = GameState::from_pgn_game(game.clone()).map_err(|e| format!("{location}: {e}"))?;
+    let mut board = Board::new();
+    let mut keys = vec![board.zobrist_key()];
+    for board_move in state.get_board_moves() {
+        board.make_move(board_move);
+        keys.push(board.zobrist_key());
+    }
+    Ok(Fingerprint { location, players, keys })
+}
+
+// The duplicates among the games of the files, in the order the copies are met, and the games
+// that can't be replayed and so were left out.
+pub fn find_duplicates(files: &[(PathBuf, Vec<PgnGame>)]) -> (Vec<Duplicate>, Vec<String>) {
+    let mut fingerprints = Vec::new();
+    let mut unplayable = Vec::new();
+    for (path, games) in files {
+        for (i, game) in games.iter().enumerate() {
+            match fingerprint(game, GameLocation { path: path.clone(), number: i + 1 }) {
+                Ok(f) => fingerprints.push(f),
+                Err(e) => unplayable.push(e),
+            }
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    // The first game with each fingerprint, and with each final position and length.
+    let mut exact: HashMap<(u64, usize, &(String, String)), usize> = HashMap::new();
+    let mut same_moves: HashMap<(u64, usize), usize> = HashMap::new();
+    let mut originals = Vec::new();
+    for (i, game) in fingerprints.iter().enumerate() {
+        let (key, plies) = (game.get_final_key(), game.get_plies());
+        if let Some(&first) = exact.get(&(key, plies, &game.players)) {
+            duplicates.push(Duplicate { kind: DuplicateKind::Exact, original: fingerprints[first].location.clone(), copy: game.location.clone() });
+            continue;
+        }
+        exact.insert((key, plies, &game.players), i);
+        originals.push(i);
+        match same_moves.get(&(key, plies)) {
+            Some(&first) if plies >= NEAR_DUPLICATE_MIN_PLIES => {
+                duplicates.push(Duplicate { kind: DuplicateKind::OtherPlayers, original: fingerprints[first].location.clone(), copy: game.location.clone() });
+            }
+            Some(_) => (),
+            None => {
+                same_moves.insert((key, plies), i);
+            }
+        }
+    }
+    // A game cut short ends where a longer game of the same players was after as many moves.
+    for &i in &originals {
+        let game = &fingerprints[i];
+        for plies in NEAR_DUPLICATE_MIN_PLIES..game.get_plies() {
+            if let Some(&short) = exact.get(&(game.keys[plies], plies, &game.players)) {
+                duplicates.push(Duplicate { kind: DuplicateKind::CutShort(plies), original: game.location.clone(), copy: fingerprints[short].location.clone() });
+            }
+        }
+    }
+    (duplicates, unplayable)
+}
+
+// A game reaching the position searched for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub location: GameLocation,
+    pub white: String,
+    pub black: String,
+    // The move and the side to move when the position was first reached.
+    pub move_number: u32,
+    pub turn: Team,
+}
+
+impl Display for SearchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} - {}, from move {} with {} to move", self.location, self.white, self.black, self.move_number, self.turn)
+    }
+}
+
+// The first position of a game matching the pattern, the final position included. Games set up
+// from a FEN tag are searched from that position. A game with a move that can't be played is
+// searched up to that move, one set up from an invalid FEN not at all.
+fn first_match(game: &PgnGame, pattern: &PositionPattern) -> Option<Board> {
+    let mut last = None;
+    for (board, chess_move) in game.positions() {
+        if pattern.matches(&board) {
+            return Some(board);
+        }
+        last = Some((board, chess_move));
+    }
+    let board = match last {
+        Some((mut board, chess_move)) => {
+            board.make_move(board.resolve_move(chess_move).ok()?);
+            board
+        }
+        None => game.get_starting_board()?,
+    };
+    pattern.matches(&board).then_some(board)
+}
+
+// The games of the files reaching a position matching the pattern, in the order of the files.
+pub fn search_games(files: &[(PathBuf, Vec<PgnGame>)], pattern: &PositionPattern) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    for (path, games) in files {
+        for (i, game) in games.iter().enumerate() {
+            if let Some(board) = first_match(game, pattern) {
+                hits.push(SearchHit {
+                    location: GameLocation { path: path.clone(), number: i + 1 },
+                    white: game.get_white().to_string(),
+                    black: game.get_black().to_string(),
+                    move_number: board.get_fullmove_number(),
+                    turn: board.get_turn(),
+                });
+            }
+        }
+    }
+    hits
+}
+
+pub fn db_main(action: &DbAction) {
+    if let Err(e) = run(action) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(action: &DbAction) -> Result<(), DbError> {
+    match action {
+        DbAction::Dedup { files, remove } => {
+            let collection = read_files(files)?;
+            let (duplicates, unplayable) = find_duplicates(&collection);
+            for line in &unplayable {
+                println!("Left out {line}");
+            }
+            for duplicate in &duplicates {
+                println!("{duplicate}");
+            }
+            let game_count: usize = collection.iter().map(|(_, games)| games.len()).sum();
+            let exact = duplicates.iter().filter(|d| d.kind == DuplicateKind::Exact).count();
+            println!("Found {exact} exact and {} near duplicate(s) among {game_count} game(s).", duplicates.len() - exact);
+            if *remove && exact > 0 {
+                let changed = remove_duplicates(collection, &duplicates)?;
+                println!("Removed {exact} game(s) from {changed} file(s).");
+            }
+        }
+        DbAction::Search { query, files, out } => {
+            let pattern = PositionPattern::parse(query).map_err(DbError::Query)?;
+            let collection = read_files(files)?;
+            let hits = search_games(&collection, &pattern);
+            for hit in &hits {
+                println!("{hit}");
+            }
+            let game_count: usize = collection.iter().map(|(_, games)| games.len()).sum();
+            println!("Found {} of {game_count} game(s).", hits.len());
+            if let Some(out) = out {
+                let found: Vec<String> = collection.iter()
+                    .flat_map(|(path, games)| games.iter().enumerate()
+                        .filter(|(i, _)| hits.iter().any(|h| h.location.number == i + 1 && h.location.path == *path)))
+                    .map(|(_, g)| g.to_formatted_string(PgnTextMode::Preserved, REWRITE_WIDTH))
+                    .collect();
+                write_games(out, &found)?;
+                println!("Wrote them to {}.", out.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_files(paths: &[PathBuf]) -> Result<Vec<(PathBuf, Vec<PgnGame>)>, DbError> {
+    paths.iter().map(|path| {
+        let text = fs::read_to_string(path).map_err(|e| DbError::Io(path.clone(), e))?;
+        // Comments are kept as written, for files that are written again.
+        let games = parse_pgn_games_with(&text, PgnTextMode::Preserved).map_err(|e| DbError::Parse(path.clone(), e))?;
+        Ok((path.clone(), games))
+    }).collect()
+}
+
+// Writes the files again without their exact duplicates. Returns how many files changed.
+fn remove_duplicates(collection: Vec<(PathBuf, Vec<PgnGame>)>, duplicates: &[Duplicate]) -> Result<usize, DbError> {
+    let mut changed = 0;
+    for (path, games) in collection {
+        let removed = |number: usize| duplicates.iter()
+            .any(|d| d.kind == DuplicateKind::Exact && d.copy.number == number && d.copy.path == path);
+        let kept: Vec<String> = games.iter().enumerate()
+            .filter(|(i, _)| !removed(i + 1))
+            .map(|(_, g)| g.to_formatted_string(PgnTextMode::Preserved, REWRITE_WIDTH))
+            .collect();
+        if kept.len() == games.len() {
+            continue;
+        }
+        write_games(&path, &kept)?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+fn write_games(path: &Path, games: &[String]) -> Result<(), DbError> {
+    fs::write(path, games.join("\n")).map_err(|e| DbError::Io(path.to_path_buf(), e))
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_db {
+    use super::*;
+    use crate::chess_pgn::parse_pgn_games;
+
+    // 24 half moves of a Ruy Lopez, long enough for near duplicates.
+    const RUY_LOPEZ: &str = "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O \
+        9. h3 Nb8 10. d4 Nbd7 11. c4 c6 12. cxb5 axb5";
+
+    fn game(white: &str, black: &str, moves: &str) -> String {
+        format!("[White \"{white}\"]\n[Black \"{black}\"]\n\n{moves} *\n\n")
+    }
+
+    #[test]
+    pub fn exact_and_near_duplicates_are_found() {
+        let first = game("Carlsen, Magnus", "Caruana, Fabiano", RUY_LOPEZ)
+            + game("Smith", "Jones", "1. d4 d5 2. c4 e6").as_str()
+            + game("Smith", "Jones", "1. d4 d5 2. c4 e6").as_str();
+        let second = game("carlsen magnus", "CARUANA, Fabiano", RUY_LOPEZ)
+            + game("Carlsen, M", "Caruana, F", RUY_LOPEZ).as_str()
+            + game("Carlsen, Magnus", "Caruana, Fabiano", "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7 11. c4").as_str()
+            + game("Fool", "Jones", "1. f3 e5 2. Ke3").as_str();
+        let files = vec![
+            (PathBuf::from("a.pgn"), parse_pgn_games(&first).unwrap()),
+            (PathBuf::from("b.pgn"), parse_pgn_games(&second).unwrap()),
+        ];
+        let (duplicates, unplayable) = find_duplicates(&files);
+        let lines: Vec<String> = duplicates.iter().map(|d| d.to_string()).collect();
+        assert_eq!(lines, vec![
+            "a.pgn #3 is a copy of a.pgn #2",
+            "b.pgn #1 is a copy of a.pgn #1",
+            "b.pgn #2 has the moves of a.pgn #1 under other player names",
+            "b.pgn #3 is a.pgn #1 cut short after 21 half moves",
+        ]);
+        assert_eq!(unplayable.len(), 1);
+        assert!(unplayable[0].starts_with("b.pgn #4: move 2. Ke3"), "{unplayable:?}");
+    }
+
+    #[test]
+    pub fn removing_keeps_the_first_copy() {
+        let path = std::env::temp_dir().join(format!("rust_chess_dedup_{}.pgn", std::process::id()));
+        let text = game("Smith", "Jones", "1. d4 d5 {The Queen's Gambit} 2. c4 e6")
+            + game("Brown", "Jones", "1. e4 c5").as_str()
+            + game("Smith", "Jones", "1. d4 d5 2. c4 e6").as_str();
+        fs::write(&path, text).unwrap();
+        let collection = read_files(std::slice::from_ref(&path)).unwrap();
+        let (duplicates, _) = find_duplicates(&collection);
+        assert_eq!(remove_duplicates(collection, &duplicates).unwrap(), 1);
+
+        let games = parse_pgn_games(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_plies()[1].1.get_comments(), &vec![String::from("The Queen's Gambit")]);
+        assert_eq!(games[1].get_white(), "Brown");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn search_finds_the_move_a_position_is_reached() {
+        let text = game("Smith", "Jones", "1. d4 d5 2. c4 e6 3. Nc3 c5 4. cxd5 exd5 5. Nf3 Nc6 6. g3 Nf6 7. Bg2 Be7 \
+            8. O-O O-O 9. Bg5 cxd4 10. Nxd4")
+            + game("Brown", "Jones", "1. d4 d5 2. c4 e6 3. Nc3 Nf6 4. cxd5").as_str()
+            + game("Brown", "Smith", "1. e4 d5 2. exd5 Qxd5 3. Nc3 Qxg2").as_str();
+        let files = vec![(PathBuf::from("a.pgn"), parse_pgn_games(&text).unwrap())];
+        let search = |query: &str| -> Vec<String> {
+            search_games(&files, &PositionPattern::parse(query).unwrap()).iter().map(|h| h.to_string()).collect()
+        };
+
+        assert_eq!(search("iqp"), vec!["a.pgn #1 Smith - Jones, from move 10 with White to move"]);
+        assert_eq!(search("doubled d"), vec![
+            "a.pgn #1 Smith - Jones, from move 4 with Black to move",
+            "a.pgn #2 Brown - Jones, from move 4 with Black to move",
+            "a.pgn #3 Brown - Smith, from move 2 with Black to move",
+        ]);
+        // The final position is searched too.
+        assert_eq!(search("Q+2R+2B+2N+6P vs Q+2R+2B+2N+7P"), vec![
+            "a.pgn #1 Smith - Jones, from move 10 with White to move",
+            "a.pgn #3 Brown - Smith, from move 4 with White to move",
+        ]);
+        assert!(search("R+P vs R").is_empty());
+
+        // Games set up from a position are searched from it, not from the usual start.
+        let text = "[White \"Study\"]\n[Black \"?\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/3RK3 b - - 0 60\"]\n\n60... Kd7 61. Rd2+ Kc6 *\n\n".to_string()
+            + "[White \"Empty\"]\n[Black \"?\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Ke3 *\n\n";
+        let files = vec![(PathBuf::from("b.pgn"), parse_pgn_games(&text).unwrap())];
+        let search = |query: &str| -> Vec<String> {
+            search_games(&files, &PositionPattern::parse(query).unwrap()).iter().map(|h| h.to_string()).collect()
+        };
+        assert_eq!(search("passed"), vec!["b.pgn #1 Study - ?, from move 60 with Black to move"]);
+        assert!(search("Q+2R+2B+2N+8P vs Q+2R+2B+2N+8P").is_empty());
+    }
+}
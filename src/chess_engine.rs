@@ -0,0 +1,770 @@
+/*
+chess_engine.rs
+The built-in engine, for playing against the computer without an external one. It searches with
+negamax and alpha-beta pruning over the core move generator, deepening one half move at a time
+so a search limited by time always has a move from the last depth it completed. Captures are
+followed past the search depth until the position is quiet, so it doesn't stop counting material
+in the middle of an exchange.
+
+Alpha-beta prunes the most when the best move is searched first, so moves are ordered: the move
+the last depth's principal variation played there, then captures of the most valuable piece by the
+least valuable one (MVV-LVA), then the quiet moves that refuted other moves at the same depth
+(killer moves), then promotions, then the rest by how often they refuted moves anywhere in the
+search (history heuristic). Moves are generated in those stages as the search asks for them, so a node
+that is cut off by its first moves never generates the quiet moves at all. The bench compares the
+search with and without the ordering.
+
+Analysis can ask for several lines (MultiPV). At every depth the root is then searched once per
+line, each time leaving out the moves of the lines already found, so the next search finds the
+next best move.
+
+Results use the same types as UCI engines, so either kind of engine can be used in its place.
+*/
+
+use std::{
+    sync::{atomic::{AtomicBool, Ordering}, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chess_common::ChessPiece,
+    chess_core::{Board, BoardMove, BoardSquare, Piece, Team},
+    chess_uci::{EngineScore, SearchInfo, SearchLimit, SearchResult},
+};
+
+pub const ENGINE_NAME: &str = "Rust Chess";
+
+// A checkmate, less the half moves it takes, so quicker mates score higher.
+const MATE_SCORE: i32 = 100_000;
+// Scores further from zero than this are mates.
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+const INFINITY: i32 = MATE_SCORE + 1;
+// The deepest a search limited by time goes.
+const MAX_DEPTH: u32 = 64;
+// How many positions are searched between checks of the time.
+const NODES_PER_TIME_CHECK: u64 = 1024;
+
+// Ordering scores. Captures come first, then promotions, then killer moves, then quiet moves by
+// their history score, which stays below the killers. The root orders its moves once by these,
+// deeper positions get theirs from a MovePicker.
+const CAPTURE_ORDER: u32 = 3_000_000;
+const PROMOTION_ORDER: u32 = 2_000_000;
+const KILLER_ORDER: u32 = 1_000_000;
+
+// Positions the bench searches, from the opening to the endgame.
+const BENCH_POSITIONS: [(&str, &str); 4] = [
+    ("start position", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    ("italian game", "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"),
+    ("queen's gambit", "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8"),
+    ("rook endgame", "8/8/1k6/8/2K5/8/1P3r2/4R3 w - - 0 1"),
+];
+
+// Whether the search orders moves, only turned off to measure what the ordering saves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveOrdering {
+    Unordered,
+    Ordered,
+}
+
+// How much work a search took.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchStats {
+    nodes: u64,
+    time: Duration,
+}
+
+// One bench position searched without and with move ordering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchResult {
+    name: &'static str,
+    unordered: SearchStats,
+    ordered: SearchStats,
+}
+
+struct Searcher<'a> {
+    deadline: Option<Instant>,
+    // Set from elsewhere to end the search early.
+    stop: Option<&'a AtomicBool>,
+    nodes: u64,
+    // Set once the time is up, the search then unwinds without using its scores.
+    stopped: bool,
+
+    ordering: MoveOrdering,
+    // Up to two quiet moves per ply from the root that caused a cutoff, the latest first.
+    killers: Vec<[Option<BoardMove>; 2]>,
+    // How much each quiet move, by its from and to squares, caused cutoffs.
+    history: Vec<[u32; 64]>,
+    // The best line found from each ply, filled in as the search returns.
+    pv: Vec<Vec<BoardMove>>,
+    // The principal variation of the last depth completed, tried first along the same line.
+    last_pv: Vec<BoardMove>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PickStage {
+    Hint,
+    Captures,
+    Killers,
+    Quiet,
+    Done,
+}
+
+// The moves of a position in the order the search tries them, generated a stage at a time: the
+// hinted move, the captures, the killer moves and then the other quiet moves. Each move comes
+// once, the hint and killers being left out of the stages after them.
+struct MovePicker {
+    stage: PickStage,
+    hint: Option<BoardMove>,
+    killers: [Option<BoardMove>; 2],
+    // The moves of the current stage, best last so they are popped off.
+    moves: Vec<BoardMove>,
+}
+
+impl MovePicker {
+    fn new(hint: Option<BoardMove>, killers: [Option<BoardMove>; 2]) -> MovePicker {
+        MovePicker { stage: PickStage::Hint, hint, killers, moves: Vec::new() }
+    }
+
+    fn next(&mut self, board: &Board, history: &[[u32; 64]]) -> Option<BoardMove> {
+        loop {
+            if let Some(board_move) = self.moves.pop() {
+                return Some(board_move);
+            }
+            match self.stage {
+                PickStage::Hint => {
+                    self.stage = PickStage::Captures;
+                    self.hint = self.hint.filter(|m| board.is_legal_move(*m));
+                    self.moves.extend(self.hint);
+                }
+                PickStage::Captures => {
+                    self.stage = PickStage::Killers;
+                    self.moves = board.legal_captures();
+                    self.moves.retain(|m| Some(*m) != self.hint);
+                    self.moves.sort_by_cached_key(|m| capture_order(board, *m));
+                }
+                PickStage::Killers => {
+                    self.stage = PickStage::Quiet;
+                    // Killers come from other positions at the same ply, so may not be legal here.
+                    let killers = self.killers.into_iter().flatten()
+                        .filter(|m| Some(*m) != self.hint && !board.is_capture(*m) && m.get_promotion().is_none() && board.is_legal_move(*m));
+                    self.moves = killers.collect();
+                    self.moves.reverse();
+                }
+                PickStage::Quiet => {
+                    self.stage = PickStage::Done;
+                    self.moves = board.legal_quiet_moves();
+                    self.moves.retain(|m| Some(*m) != self.hint && !self.killers.contains(&Some(*m)));
+                    // Promotions before the rest, by the piece promoted to.
+                    self.moves.sort_by_cached_key(|m| match m.get_promotion() {
+                        Some(promotion) => PROMOTION_ORDER + promotion.get_value(),
+                        None => history[square_index(m.get_from())][square_index(m.get_to())].min(KILLER_ORDER - 1),
+                    });
+                }
+                PickStage::Done => return None,
+            }
+        }
+    }
+}
+
+impl SearchStats {
+    // Positions searched, including those of the capture search.
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+}
+
+impl BenchResult {
+    pub fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn get_unordered(&self) -> &SearchStats {
+        &self.unordered
+    }
+
+    pub fn get_ordered(&self) -> &SearchStats {
+        &self.ordered
+    }
+}
+
+// Searches for the best move of the side to move. None when it has no legal moves.
+pub fn search(board: &Board, limit: SearchLimit) -> Option<SearchResult> {
+    search_with_stats(board, limit, MoveOrdering::Ordered).map(|(result, _)| result)
+}
+
+// Searches like search, reporting the work it took.
+pub fn search_with_stats(board: &Board, limit: SearchLimit, ordering: MoveOrdering) -> Option<(SearchResult, SearchStats)> {
+    let (results, stats) = run_search(board, limit, 1, ordering, None, &mut |_, _| ())?;
+    results.into_iter().next().map(|result| (result, stats))
+}
+
+// Searches like search until the limit is reached or `stop` is set, reporting the result of
+// every depth completed along the way.
+pub fn search_until_stopped(board: &Board, limit: SearchLimit, stop: &AtomicBool,
+    mut report: impl FnMut(&SearchResult, &SearchStats)) -> Option<SearchResult>
+{
+    let (results, _) = run_search(board, limit, 1, MoveOrdering::Ordered, Some(stop), &mut |results, stats| report(&results[0], stats))?;
+    results.into_iter().next()
+}
+
+// Searches like search_until_stopped for the best `lines` moves instead of only the best one,
+// each with its own score and principal variation, best first. Fewer lines are found when the
+// side to move has fewer moves.
+pub fn search_lines_until_stopped(board: &Board, limit: SearchLimit, lines: usize, stop: &AtomicBool,
+    mut report: impl FnMut(&[SearchResult], &SearchStats)) -> Vec<SearchResult>
+{
+    run_search(board, limit, lines, MoveOrdering::Ordered, Some(stop), &mut report).map(|(results, _)| results).unwrap_or_default()
+}
+
+fn run_search(board: &Board, limit: SearchLimit, lines: usize, ordering: MoveOrdering, stop: Option<&AtomicBool>,
+    report: &mut dyn FnMut(&[SearchResult], &SearchStats)) -> Option<(Vec<SearchResult>, SearchStats)>
+{
+    let started = Instant::now();
+    let mut moves = board.legal_moves();
+    let first_move = *moves.first()?;
+    let lines = lines.clamp(1, moves.len());
+    let (max_depth, deadline) = match limit {
+        SearchLimit::Depth(depth) => (depth.clamp(1, MAX_DEPTH), None),
+        SearchLimit::MoveTime(time) => (MAX_DEPTH, Some(started + time)),
+        SearchLimit::Infinite => (MAX_DEPTH, None),
+    };
+    let mut searcher = Searcher {
+        deadline,
+        stop,
+        nodes: 0,
+        stopped: false,
+        ordering,
+        killers: Vec::new(),
+        history: vec![[0; 64]; 64],
+        pv: Vec::new(),
+        last_pv: Vec::new(),
+    };
+    let mut results = vec![SearchResult::new(first_move, SearchInfo::default())];
+    searcher.order_moves(board, &mut moves, 0);
+
+    for depth in 1..=max_depth {
+        // Each line is searched without the moves of the lines found before it.
+        let mut found: Vec<(BoardMove, i32, Vec<BoardMove>)> = Vec::new();
+        while found.len() < lines && !searcher.stopped {
+            let excluded: Vec<BoardMove> = found.iter().map(|(m, _, _)| *m).collect();
+            if let Some(line) = searcher.search_root(board, &moves, depth, &excluded) {
+                found.push(line);
+            }
+        }
+        if searcher.stopped {
+            break;
+        }
+
+        let stats = SearchStats { nodes: searcher.nodes, time: started.elapsed() };
+        results = found.into_iter()
+            .map(|(best, score, pv)| SearchResult::new(best, SearchInfo::new(depth, engine_score(score), stats.nodes, stats.time, pv)))
+            .collect();
+        report(&results, &stats);
+        searcher.last_pv = results[0].get_info().get_pv().clone();
+        // The best moves are searched first at the next depth, where they make for more cutoffs.
+        for (i, result) in results.iter().enumerate() {
+            if let Some(at) = moves.iter().position(|m| *m == result.get_best_move()) {
+                moves[i..=at].rotate_right(1);
+            }
+        }
+        if results.iter().all(|r| matches!(r.get_score(), Some(EngineScore::Mate(_)))) {
+            break;
+        }
+    }
+    Some((results, SearchStats { nodes: searcher.nodes, time: started.elapsed() }))
+}
+
+// Searches the bench positions to a depth, without and with move ordering.
+pub fn bench(depth: u32) -> Vec<BenchResult> {
+    let limit = SearchLimit::Depth(depth);
+    let mut results = Vec::new();
+    for (name, fen) in BENCH_POSITIONS {
+        let board = Board::from_fen(fen).expect("bench positions are valid");
+        let stats = |ordering| search_with_stats(&board, limit, ordering).map(|(_, s)| s).expect("bench positions have moves");
+        results.push(BenchResult { name, unordered: stats(MoveOrdering::Unordered), ordered: stats(MoveOrdering::Ordered) });
+    }
+    results
+}
+
+impl Searcher<'_> {
+    // Searches the root moves left after `excluded` to a depth. Returns the best of them with its
+    // score and line, None when the time ran out.
+    fn search_root(&mut self, board: &Board, moves: &[BoardMove], depth: u32, excluded: &[BoardMove])
+        -> Option<(BoardMove, i32, Vec<BoardMove>)>
+    {
+        let mut alpha = -INFINITY;
+        let mut best = None;
+        for board_move in moves.iter().filter(|m| !excluded.contains(m)) {
+            let mut child = board.clone();
+            child.make_move(*board_move);
+            let score = -self.negamax(&child, depth - 1, 1, -INFINITY, -alpha, self.last_pv.first() == Some(board_move));
+            if self.stopped {
+                return None;
+            }
+            if score > alpha {
+                alpha = score;
+                let pv = std::iter::once(*board_move).chain(self.take_pv(1)).collect();
+                best = Some((*board_move, score, pv));
+            }
+        }
+        best
+    }
+
+    // Searches a position to a depth. Along the last depth's principal variation its move at each
+    // ply is tried first.
+    fn negamax(&mut self, board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32, on_pv: bool) -> i32 {
+        if self.is_out_of_time() {
+            return 0;
+        }
+        self.clear_pv(ply as usize);
+        if board.get_halfmove_clock() >= 100 {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(board, alpha, beta);
+        }
+
+        let hint = if on_pv { self.last_pv.get(ply as usize).copied() } else { None };
+        let mut picker = MovePicker::new(hint, self.killers.get(ply as usize).copied().unwrap_or_default());
+        let mut unordered = match self.ordering {
+            MoveOrdering::Unordered => Some(board.legal_moves().into_iter()),
+            MoveOrdering::Ordered => None,
+        };
+        let mut searched = 0;
+        loop {
+            let next = match &mut unordered {
+                Some(moves) => moves.next(),
+                None => picker.next(board, &self.history),
+            };
+            let board_move = match next {
+                Some(m) => m,
+                None => break,
+            };
+            searched += 1;
+            let mut child = board.clone();
+            child.make_move(board_move);
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, hint == Some(board_move));
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                if !board.is_capture(board_move) {
+                    self.remember_cutoff(board_move, depth, ply as usize);
+                }
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                self.extend_pv(ply as usize, board_move);
+            }
+        }
+        if searched == 0 {
+            return if board.is_in_check(board.get_turn()) { ply - MATE_SCORE } else { 0 };
+        }
+        alpha
+    }
+
+    fn clear_pv(&mut self, ply: usize) {
+        if self.pv.len() <= ply + 1 {
+            self.pv.resize(ply + 2, Vec::new());
+        }
+        self.pv[ply].clear();
+    }
+
+    // Makes the line from a ply the move played there followed by the best line after it.
+    fn extend_pv(&mut self, ply: usize, board_move: BoardMove) {
+        let (line, rest) = self.pv.split_at_mut(ply + 1);
+        let line = &mut line[ply];
+        line.clear();
+        line.push(board_move);
+        line.extend_from_slice(&rest[0]);
+    }
+
+    fn take_pv(&mut self, ply: usize) -> Vec<BoardMove> {
+        self.pv.get_mut(ply).map(std::mem::take).unwrap_or_default()
+    }
+
+    // Plays out the captures of a position, letting the side to move stop capturing whenever
+    // that is better for it.
+    fn quiescence(&mut self, board: &Board, mut alpha: i32, beta: i32) -> i32 {
+        if self.is_out_of_time() {
+            return 0;
+        }
+        let standing = evaluate(board);
+        if standing >= beta {
+            return beta;
+        }
+        alpha = alpha.max(standing);
+
+        let mut captures = board.legal_captures();
+        if self.ordering == MoveOrdering::Ordered {
+            captures.sort_by_cached_key(|m| std::cmp::Reverse(capture_order(board, *m)));
+        }
+        for board_move in captures {
+            let mut child = board.clone();
+            child.make_move(board_move);
+            let score = -self.quiescence(&child, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    // Sorts moves so the likeliest best moves come first.
+    fn order_moves(&self, board: &Board, moves: &mut [BoardMove], ply: usize) {
+        if self.ordering == MoveOrdering::Unordered {
+            return;
+        }
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|m| {
+            let order = if board.is_capture(*m) {
+                CAPTURE_ORDER + capture_order(board, *m)
+            }
+            else if let Some(promotion) = m.get_promotion() {
+                PROMOTION_ORDER + promotion.get_value()
+            }
+            else if killers[0] == Some(*m) {
+                KILLER_ORDER + 1
+            }
+            else if killers[1] == Some(*m) {
+                KILLER_ORDER
+            }
+            else {
+                self.history[square_index(m.get_from())][square_index(m.get_to())].min(KILLER_ORDER - 1)
+            };
+            std::cmp::Reverse(order)
+        });
+    }
+
+    // Remembers a quiet move that refuted the move before it, as a killer move for its ply and
+    // in the history, where deeper cutoffs count for more.
+    fn remember_cutoff(&mut self, board_move: BoardMove, depth: u32, ply: usize) {
+        if self.ordering == MoveOrdering::Unordered {
+            return;
+        }
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(board_move) {
+            killers[1] = killers[0];
+            killers[0] = Some(board_move);
+        }
+        let history = &mut self.history[square_index(board_move.get_from())][square_index(board_move.get_to())];
+        *history = history.saturating_add(depth * depth);
+    }
+
+    fn is_out_of_time(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_TIME_CHECK) {
+            let out_of_time = self.deadline.is_some_and(|d| Instant::now() >= d);
+            let told_to_stop = self.stop.is_some_and(|s| s.load(Ordering::Relaxed));
+            self.stopped = out_of_time || told_to_stop;
+        }
+        self.stopped
+    }
+}
+
+// Most valuable victim first, and among captures of the same piece the least valuable attacker.
+fn capture_order(board: &Board, board_move: BoardMove) -> u32 {
+    // En passant captures a pawn on another square.
+    let victim = board.get_piece_at(board_move.get_to()).map_or(ChessPiece::Pawn, |p| p.get_piece_type());
+    let attacker = board.get_piece_at(board_move.get_from()).map_or(ChessPiece::Pawn, |p| p.get_piece_type());
+    let attacker_value = if attacker == ChessPiece::King { 10 } else { attacker.get_value() };
+    victim.get_value() * 16 + 10 - attacker_value
+}
+
+fn square_index(square: BoardSquare) -> usize {
+    square.get_rank().as_usize() * 8 + square.get_file().as_usize()
+}
+
+// Scores a search result for display, mates as the number of moves to it.
+fn engine_score(score: i32) -> EngineScore {
+    if score > MATE_THRESHOLD {
+        EngineScore::Mate((MATE_SCORE - score + 1) / 2)
+    }
+    else if score < -MATE_THRESHOLD {
+        EngineScore::Mate(-(MATE_SCORE + score + 1) / 2)
+    }
+    else {
+        EngineScore::Centipawns(score)
+    }
+}
+
+// The position's value in centipawns for the side to move: the material, and where the pieces
+// stand. The value of every piece on every square is looked up in tables worked out once, and
+// summed eight squares at a time with AVX2 when built with the simd feature on a CPU that has it.
+pub fn evaluate(board: &Board) -> i32 {
+    let tables = piece_square_tables();
+    let mut indices = [EMPTY_TABLE as i32 * 64; 64];
+    let mut kings = [None; 2];
+    let mut material = [0; 2];
+    for square in BoardSquare::all() {
+        if let Some(piece) = board.get_piece_at(square) {
+            let team = team_index(*piece.get_team());
+            let piece_type = piece.get_piece_type();
+            material[team] += piece_type.get_value();
+            if piece_type == ChessPiece::King {
+                kings[team] = Some(square);
+            }
+            indices[square_index(square)] = (table_index(team, piece_type, false) * 64 + square_index(square)) as i32;
+        }
+    }
+    // Once the other side has little left to attack with, the king comes out to help.
+    for (team, king) in kings.into_iter().enumerate() {
+        if let Some(square) = king {
+            let endgame = material[1 - team] <= 13;
+            indices[square_index(square)] = (table_index(team, ChessPiece::King, endgame) * 64 + square_index(square)) as i32;
+        }
+    }
+    let score = sum_table_values(tables.as_flattened(), &indices);
+    if board.get_turn() == Team::Light { score } else { -score }
+}
+
+// Tables of the value of each piece on each square from White's side, Black's negative: six for
+// the pieces of a team and one for its king in the endgame, and one of zeros for empty squares.
+const TABLE_COUNT: usize = 15;
+const EMPTY_TABLE: usize = TABLE_COUNT - 1;
+
+fn table_index(team: usize, piece_type: ChessPiece, endgame: bool) -> usize {
+    let piece = match piece_type {
+        ChessPiece::Pawn => 0,
+        ChessPiece::Knight => 1,
+        ChessPiece::Bishop => 2,
+        ChessPiece::Rook => 3,
+        ChessPiece::Queen => 4,
+        ChessPiece::King if endgame => 6,
+        ChessPiece::King => 5,
+    };
+    team * 7 + piece
+}
+
+fn team_index(team: Team) -> usize {
+    match team {
+        Team::Light => 0,
+        Team::Dark => 1,
+    }
+}
+
+fn piece_square_tables() -> &'static [[i32; 64]; TABLE_COUNT] {
+    static TABLES: OnceLock<[[i32; 64]; TABLE_COUNT]> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut tables = [[0; 64]; TABLE_COUNT];
+        for team in [Team::Light, Team::Dark] {
+            let sign = if team == Team::Light { 1 } else { -1 };
+            for piece_type in [ChessPiece::Pawn, ChessPiece::Knight, ChessPiece::Bishop, ChessPiece::Rook, ChessPiece::Queen, ChessPiece::King] {
+                for endgame in [false, true] {
+                    let table = &mut tables[table_index(team_index(team), piece_type, endgame)];
+                    for square in BoardSquare::all() {
+                        let piece = Piece::new(team, piece_type);
+                        table[square_index(square)] = sign * (piece_type.get_value() as i32 * 100 + placement(piece, square, endgame));
+                    }
+                }
+            }
+        }
+        tables
+    })
+}
+
+// The sum of the table values at the indices, each below the length of the tables.
+fn sum_table_values(values: &[i32], indices: &[i32; 64]) -> i32 {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        if std::arch::is_x86_feature_detected!("avx2") {
+            // SAFETY: the CPU has AVX2, and every index is within the tables.
+            return unsafe { sum_table_values_avx2(values, indices) };
+        }
+    }
+    indices.iter().map(|i| values[*i as usize]).sum()
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn sum_table_values_avx2(values: &[i32], indices: &[i32; 64]) -> i32 {
+    use std::arch::x86_64::*;
+    let mut sum = _mm256_setzero_si256();
+    for chunk in indices.chunks_exact(8) {
+        let offsets = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+        sum = _mm256_add_epi32(sum, _mm256_i32gather_epi32::<4>(values.as_ptr(), offsets));
+    }
+    let mut lanes = [0; 8];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+    lanes.iter().sum()
+}
+
+// A bonus for a piece standing on a square: pawns for advancing, knights and bishops for being
+// central, and the king for staying home until the endgame.
+fn placement(piece: Piece, square: BoardSquare, endgame: bool) -> i32 {
+    let file = square.get_file().as_usize() as i32;
+    let rank = square.get_rank().as_usize() as i32;
+    let advance = if *piece.get_team() == Team::Light { rank } else { 7 - rank };
+    // 0 on the four center squares up to 3 on the edge.
+    let ring = (2 * file - 7).abs().max((2 * rank - 7).abs()) / 2;
+    match piece.get_piece_type() {
+        ChessPiece::Pawn => (advance - 1) * 8 + if (3..=4).contains(&file) { advance * 4 } else { 0 },
+        ChessPiece::Knight => (3 - ring) * 10 - 10,
+        ChessPiece::Bishop => (3 - ring) * 5,
+        ChessPiece::Rook | ChessPiece::Queen => 0,
+        ChessPiece::King if endgame => (3 - ring) * 10,
+        ChessPiece::King => -advance * 20,
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_chess_engine {
+    use super::*;
+
+    fn best_move(fen: &str, depth: u32) -> SearchResult {
+        search(&Board::from_fen(fen).unwrap(), SearchLimit::Depth(depth)).unwrap()
+    }
+
+    #[test]
+    pub fn finds_mate_in_one() {
+        let result = best_move("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 2);
+        assert_eq!(result.get_best_move(), BoardMove::from("d1d8").unwrap());
+        assert_eq!(result.get_score(), Some(EngineScore::Mate(1)));
+    }
+
+    #[test]
+    pub fn principal_variation_follows_the_best_move() {
+        // Black's best defence against the back rank threat is to make room for the king.
+        let result = best_move("6k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1", 3);
+        let pv = result.get_info().get_pv();
+        assert_eq!(pv.len(), 3);
+        assert_eq!(pv[0], result.get_best_move());
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1").unwrap();
+        for board_move in pv {
+            assert!(board.is_legal_move(*board_move));
+            board.make_move(*board_move);
+        }
+    }
+
+    #[test]
+    pub fn lines_are_the_best_moves_in_order() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let stop = AtomicBool::new(false);
+        let lines = search_lines_until_stopped(&board, SearchLimit::Depth(2), 3, &stop, |_, _| ());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].get_best_move(), BoardMove::from("d1d8").unwrap());
+        assert!(lines[1..].iter().all(|l| l.get_best_move() != lines[0].get_best_move()));
+        assert_ne!(lines[1].get_best_move(), lines[2].get_best_move());
+        let scores: Vec<i32> = lines.iter().map(|l| l.get_score().unwrap().to_centipawns()).collect();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]), "{scores:?}");
+
+        // No more lines than moves.
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K6R b - - 0 1").unwrap();
+        assert_eq!(search_lines_until_stopped(&board, SearchLimit::Depth(2), 3, &stop, |_, _| ()).len(), 2);
+    }
+
+    #[test]
+    pub fn takes_a_hanging_queen() {
+        let result = best_move("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1", 2);
+        assert_eq!(result.get_best_move(), BoardMove::from("f3h4").unwrap());
+    }
+
+    #[test]
+    pub fn time_limited_search_has_a_move() {
+        let result = search(&Board::new(), SearchLimit::MoveTime(std::time::Duration::from_millis(50))).unwrap();
+        assert!(Board::new().is_legal_move(result.get_best_move()));
+        assert!(result.get_depth().is_some());
+    }
+
+    #[test]
+    pub fn captures_are_ordered_by_victim_then_attacker() {
+        // The pawn and the queen can both take the rook, the rook can take the knight.
+        let board = Board::from_fen("4k3/8/8/3r4/4P3/2n5/3Q4/3RK3 w - - 0 1").unwrap();
+        let searcher = Searcher {
+            deadline: None,
+            stop: None,
+            nodes: 0,
+            stopped: false,
+            ordering: MoveOrdering::Ordered,
+            killers: Vec::new(),
+            history: vec![[0; 64]; 64],
+            pv: Vec::new(),
+            last_pv: Vec::new(),
+        };
+        let mut moves = board.legal_moves();
+        searcher.order_moves(&board, &mut moves, 0);
+        let first: Vec<String> = moves.iter().take(3).map(|m| m.to_string()).collect();
+        assert_eq!(first, vec!["e4d5", "d2d5", "d2c3"]);
+    }
+
+    #[test]
+    pub fn staged_moves_come_hint_captures_killers_quiet() {
+        let board = Board::from_fen("4k3/8/8/3r4/4P3/2n5/3Q4/3RK3 w - - 0 1").unwrap();
+        let hint = BoardMove::from("d2d3").unwrap();
+        let killer = BoardMove::from("e1f2").unwrap();
+        let mut picker = MovePicker::new(Some(hint), [Some(killer), Some(BoardMove::from("h7h8").unwrap())]);
+        let mut history = vec![[0; 64]; 64];
+        history[square_index(BoardSquare::from("e4").unwrap())][square_index(BoardSquare::from("e5").unwrap())] = 5;
+        let mut picked = Vec::new();
+        while let Some(board_move) = picker.next(&board, &history) {
+            picked.push(board_move.to_string());
+        }
+        // The killer from elsewhere that isn't legal here is left out.
+        assert_eq!(&picked[..6], ["d2d3", "e4d5", "d2d5", "d2c3", "e1f2", "e4e5"]);
+        let mut legal: Vec<String> = board.legal_moves().iter().map(|m| m.to_string()).collect();
+        legal.sort();
+        picked.sort();
+        assert_eq!(picked, legal);
+    }
+
+    #[test]
+    pub fn ordering_searches_fewer_nodes_for_the_same_score() {
+        let board = Board::from_fen(BENCH_POSITIONS[1].1).unwrap();
+        let (unordered, unordered_stats) = search_with_stats(&board, SearchLimit::Depth(2), MoveOrdering::Unordered).unwrap();
+        let (ordered, ordered_stats) = search_with_stats(&board, SearchLimit::Depth(2), MoveOrdering::Ordered).unwrap();
+        assert_eq!(ordered.get_score(), unordered.get_score());
+        assert!(ordered_stats.get_nodes() < unordered_stats.get_nodes());
+    }
+
+    #[test]
+    pub fn no_move_when_the_game_is_over() {
+        let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(search(&mated, SearchLimit::Depth(3)), None);
+    }
+
+    #[test]
+    pub fn tables_add_up_to_material_and_placement() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            BENCH_POSITIONS[2].1,
+            "8/8/1k6/8/2K5/8/1P3r2/4R3 b - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let mut expected = 0;
+            for square in BoardSquare::all() {
+                if let Some(piece) = board.get_piece_at(square) {
+                    let team = *piece.get_team();
+                    let endgame = board.get_material(team.opposite()) <= 13;
+                    let value = piece.get_piece_type().get_value() as i32 * 100 + placement(piece, square, endgame);
+                    expected += if team == Team::Light { value } else { -value };
+                }
+            }
+            let expected = if board.get_turn() == Team::Light { expected } else { -expected };
+            assert_eq!(evaluate(&board), expected, "{fen}");
+        }
+    }
+
+    #[test]
+    pub fn evaluation_is_from_the_side_to_move() {
+        assert_eq!(evaluate(&Board::new()), 0);
+        let up_a_queen = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(evaluate(&up_a_queen) > 800);
+        let black_to_move = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_eq!(evaluate(&black_to_move), -evaluate(&up_a_queen));
+    }
+}
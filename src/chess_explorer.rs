@@ -0,0 +1,187 @@
+/*
+chess_explorer.rs
+An opening explorer over a local game database. Every position of every finished game is indexed
+by its Zobrist key with the moves played from it and how the games went on to end, so the moves
+of a position are found at once however the position was reached:
+
+  #  Move    Games  White  Draws  Black
+  1  e4         58    41%    31%    28%
+  2  d4         37    46%    35%    19%
+
+Games without a result, and games from a set up position, are left out.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    io,
+    path::Path,
+};
+
+use crate::{
+    chess_core::{Board, BoardMove},
+    chess_pgn::{parse_pgn_games, PgnGame, PgnParseError, PgnResult},
+};
+
+#[derive(Debug)]
+pub enum ExplorerError {
+    Io(io::Error),
+    Parse(PgnParseError),
+}
+
+impl Display for ExplorerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExplorerError::Io(e) => write!(f, "{}", e),
+            ExplorerError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExplorerError {}
+
+// A move played from a position, and the results of the games it was played in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExplorerMove {
+    board_move: BoardMove,
+    white_wins: u32,
+    draws: u32,
+    black_wins: u32,
+}
+
+impl ExplorerMove {
+    fn new(board_move: BoardMove) -> ExplorerMove {
+        ExplorerMove { board_move, white_wins: 0, draws: 0, black_wins: 0 }
+    }
+
+    fn add_result(&mut self, result: PgnResult) {
+        match result {
+            PgnResult::WhiteWin => self.white_wins += 1,
+            PgnResult::Draw => self.draws += 1,
+            PgnResult::BlackWin => self.black_wins += 1,
+            PgnResult::Unknown => (),
+        }
+    }
+
+    pub fn get_board_move(&self) -> BoardMove {
+        self.board_move
+    }
+
+    pub fn get_games(&self) -> u32 {
+        self.white_wins + self.draws + self.black_wins
+    }
+
+    // The share of the games won by White, drawn and won by Black, in whole percents.
+    pub fn get_percentages(&self) -> (u32, u32, u32) {
+        let games = self.get_games().max(1);
+        let percent = |n: u32| (n * 100 + games / 2) / games;
+        (percent(self.white_wins), percent(self.draws), percent(self.black_wins))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OpeningExplorer {
+    positions: HashMap<u64, Vec<ExplorerMove>>,
+    games: usize,
+}
+
+impl OpeningExplorer {
+    pub fn from_games(games: &[PgnGame]) -> OpeningExplorer {
+        let mut explorer = OpeningExplorer::default();
+        for game in games {
+            let result = *game.get_result();
+            if result == PgnResult::Unknown || game.get_tag("FEN").is_some() {
+                continue;
+            }
+            explorer.games += 1;
+            for (board, chess_move) in game.positions() {
+                // positions() only gives moves that could be played.
+                let board_move = board.resolve_move(chess_move).unwrap();
+                let moves = explorer.positions.entry(board.zobrist_key()).or_default();
+                let index = match moves.iter().position(|m| m.board_move == board_move) {
+                    Some(i) => i,
+                    None => {
+                        moves.push(ExplorerMove::new(board_move));
+                        moves.len() - 1
+                    }
+                };
+                moves[index].add_result(result);
+            }
+        }
+        explorer
+    }
+
+    pub fn load(path: &Path) -> Result<OpeningExplorer, ExplorerError> {
+        let text = fs::read_to_string(path).map_err(ExplorerError::Io)?;
+        let games = parse_pgn_games(&text).map_err(ExplorerError::Parse)?;
+        Ok(OpeningExplorer::from_games(&games))
+    }
+
+    // The games indexed.
+    pub fn get_game_count(&self) -> usize {
+        self.games
+    }
+
+    // The moves played from the position, the most played first.
+    pub fn get_moves(&self, board: &Board) -> Vec<&ExplorerMove> {
+        let mut moves: Vec<&ExplorerMove> = self.positions.get(&board.zobrist_key()).map_or(Vec::new(), |m| m.iter().collect());
+        moves.sort_by_key(|m| std::cmp::Reverse(m.get_games()));
+        moves
+    }
+}
+
+// The moves of a position as a numbered table, in SAN.
+pub fn format_moves(board: &Board, moves: &[&ExplorerMove]) -> Vec<String> {
+    let mut lines = vec![format!("{:>3}  {:<7} {:>5}  {:>5}  {:>5}  {:>5}", "#", "Move", "Games", "White", "Draws", "Black")];
+    for (i, explorer_move) in moves.iter().enumerate() {
+        let san = board.to_chess_move(explorer_move.board_move).to_string();
+        let (white, draws, black) = explorer_move.get_percentages();
+        lines.push(format!("{:>3}  {:<7} {:>5}  {:>4}%  {:>4}%  {:>4}%", i + 1, san, explorer_move.get_games(), white, draws, black));
+    }
+    lines
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_explorer {
+    use super::*;
+    use crate::chess_pgn::ChessMove;
+
+    fn game(moves: &str, result: &str) -> String {
+        format!("[Result \"{result}\"]\n\n{moves} {result}\n\n")
+    }
+
+    #[test]
+    pub fn moves_are_counted_by_position() {
+        let text = game("1. e4 e5 2. Nf3 Nc6 3. Bc4", "1-0")
+            + game("1. Nf3 Nc6 2. e4 e5 3. Bb5", "1/2-1/2").as_str()
+            + game("1. e4 c5", "0-1").as_str()
+            + game("1. e4 e5", "*").as_str()
+            + format!("[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n{}", game("1. Ke2", "1-0")).as_str();
+        let explorer = OpeningExplorer::from_games(&parse_pgn_games(&text).unwrap());
+        assert_eq!(explorer.get_game_count(), 3);
+
+        let start = Board::new();
+        let lines = format_moves(&start, &explorer.get_moves(&start));
+        assert_eq!(lines, vec![
+            "  #  Move    Games  White  Draws  Black",
+            "  1  e4          2    50%     0%    50%",
+            "  2  Nf3         1     0%   100%     0%",
+        ]);
+
+        // Both move orders reach the position after 2... Nc6.
+        let mut board = Board::new();
+        for san in ["e4", "e5", "Nf3", "Nc6"] {
+            board.make_move(board.resolve_move(&ChessMove::from(san).unwrap()).unwrap());
+        }
+        let lines = format_moves(&board, &explorer.get_moves(&board));
+        assert_eq!(&lines[1..], [
+            "  1  Bc4         1   100%     0%     0%",
+            "  2  Bb5         1     0%   100%     0%",
+        ]);
+        board.make_move(BoardMove::from("f1c4").unwrap());
+        assert!(explorer.get_moves(&board).is_empty());
+    }
+}
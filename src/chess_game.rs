@@ -0,0 +1,1013 @@
+/*
+chess_game.rs
+Module that ties the board and the PGN record of a game together. Moves played through the
+GameState are checked against the rules on the board and recorded in the PgnGame.
+*/
+
+use std::{fmt::Display, time::Duration};
+use crate::{
+    chess_clock::TimeControl,
+    chess_core::{fen::FenParseError, Board, BoardMove, MoveResolveError, Piece, Team},
+    chess_pgn::{format_variation, ChessMove, PgnAnnotation, PgnGame, PgnParseError, PgnResult},
+};
+
+pub struct GameState {
+    board: Board,
+    pgn_game: PgnGame,
+
+    // Moves that can be undone, most recent last, and moves that were undone and can be
+    // replayed, most recently undone last.
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+
+    // The half move whose position is being looked at when stepping through the game, None
+    // for the current position. Looking back doesn't change the board or the record.
+    viewed_ply: Option<usize>,
+
+    // The time control the game is played at, None for an untimed game.
+    time_control: Option<TimeControl>,
+
+    // Told about what happens in the game, in the order they subscribed.
+    observers: Vec<(ObserverId, Box<dyn GameObserver>)>,
+    next_observer_id: usize,
+}
+
+// Something that reacts to the game, e.g. a logger, a sound or a network relay. Every method does
+// nothing unless implemented, so an observer only implements the events it cares about.
+pub trait GameObserver {
+    // A move was played, `board` is the position after it.
+    fn on_move(&mut self, _chess_move: &ChessMove, _board: &Board) {}
+    // The move played took a piece.
+    fn on_capture(&mut self, _captured: Piece, _board: &Board) {}
+    // The move played put `team` in check. Checkmate ends the game instead.
+    fn on_check(&mut self, _team: Team, _board: &Board) {}
+    // The game ended, by the position or with a result given to end_game.
+    fn on_game_end(&mut self, _status: &GameStatus) {}
+    // The time each side has left, whenever whoever runs the clocks updates them.
+    fn on_clock_tick(&mut self, _white: Duration, _black: Duration) {}
+}
+
+// Returned by subscribe, for unsubscribing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObserverId(usize);
+
+// A played move together with the board as it was before the move, so undoing restores
+// captured pieces, castling rights, the en passant square and the clocks exactly.
+struct HistoryEntry {
+    board_before: Board,
+    board_move: BoardMove,
+    recorded: ChessMove,
+    annotation: PgnAnnotation,
+    captured: Option<Piece>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GameMoveError {
+    GameOver,
+    ViewingHistory,
+    Unresolved(MoveResolveError),
+    Illegal(BoardMove),
+}
+
+impl Display for GameMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameMoveError::GameOver => write!(f, "the game is over"),
+            GameMoveError::ViewingHistory => write!(f, "an earlier position of the game is being viewed"),
+            GameMoveError::Unresolved(e) => write!(f, "{}", e),
+            GameMoveError::Illegal(board_move) => write!(f, "{} is not a legal move", board_move),
+        }
+    }
+}
+
+impl std::error::Error for GameMoveError {}
+
+// Where the game stands, as shown to the players.
+#[derive(Debug, PartialEq)]
+pub enum GameStatus {
+    ToMove(Team),
+    InCheck(Team),
+    Checkmate { winner: Team },
+    Stalemate,
+    // The game record has a result, e.g. from an adjudication.
+    Ended(PgnResult),
+}
+
+impl Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameStatus::ToMove(team) => write!(f, "{} to move", team),
+            GameStatus::InCheck(team) => write!(f, "{} is in check", team),
+            GameStatus::Checkmate { winner } => write!(f, "Checkmate \u{2014} {} wins", winner),
+            GameStatus::Stalemate => write!(f, "Stalemate \u{2014} draw"),
+            GameStatus::Ended(PgnResult::WhiteWin) => write!(f, "Game over \u{2014} White wins"),
+            GameStatus::Ended(PgnResult::BlackWin) => write!(f, "Game over \u{2014} Black wins"),
+            GameStatus::Ended(result) => write!(f, "Game over \u{2014} {}", result),
+        }
+    }
+}
+
+// A loaded game that can't be replayed: a FEN tag that isn't a valid position, or a move that
+// can't be played on the board, e.g. "12... Nxe4".
+#[derive(Debug, PartialEq)]
+pub enum GameLoadError {
+    InvalidFen(FenParseError),
+    Unplayable { ply: usize, chess_move: ChessMove, reason: MoveResolveError },
+}
+
+impl Display for GameLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameLoadError::InvalidFen(e) => write!(f, "the FEN tag isn't a valid position, {}", e),
+            GameLoadError::Unplayable { ply, chess_move, reason } => {
+                let dots = if ply.is_multiple_of(2) { "." } else { "..." };
+                write!(f, "move {}{} {}: {}", ply / 2 + 1, dots, chess_move, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GameLoadError {}
+
+impl GameLoadError {
+    // The zero based half move index of the failing move.
+    pub fn get_ply(&self) -> Option<usize> {
+        match self {
+            GameLoadError::Unplayable { ply, .. } => Some(*ply),
+            GameLoadError::InvalidFen(_) => None,
+        }
+    }
+
+    pub fn get_reason(&self) -> Option<&MoveResolveError> {
+        match self {
+            GameLoadError::Unplayable { reason, .. } => Some(reason),
+            GameLoadError::InvalidFen(_) => None,
+        }
+    }
+}
+
+// A variation of the game that can't be entered.
+#[derive(Debug, PartialEq)]
+pub enum VariationError {
+    NoVariation,
+    Unreadable(PgnParseError),
+    Unplayable(GameLoadError),
+}
+
+impl Display for VariationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariationError::NoVariation => write!(f, "there is no such variation"),
+            VariationError::Unreadable(e) => write!(f, "the variation can't be read, {}", e),
+            VariationError::Unplayable(e) => write!(f, "the variation can't be played, {}", e),
+        }
+    }
+}
+
+impl std::error::Error for VariationError {}
+
+// A draw the player to move can claim under the FIDE rules.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrawClaim {
+    // The same position, with the same player to move, for the third time.
+    ThreefoldRepetition,
+    // Fifty moves of each side without a capture or a pawn move.
+    FiftyMoveRule,
+}
+
+impl Display for DrawClaim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DrawClaim::ThreefoldRepetition => write!(f, "threefold repetition"),
+            DrawClaim::FiftyMoveRule => write!(f, "the fifty move rule"),
+        }
+    }
+}
+
+// The draw that can be claimed in a position on the board for the given time.
+fn draw_claim(board: &Board, repetitions: usize) -> Option<DrawClaim> {
+    if repetitions >= 3 {
+        Some(DrawClaim::ThreefoldRepetition)
+    }
+    else if board.get_halfmove_clock() >= 100 {
+        Some(DrawClaim::FiftyMoveRule)
+    }
+    else {
+        None
+    }
+}
+
+impl Default for GameState {
+    fn default() -> GameState {
+        GameState::new()
+    }
+}
+
+impl GameState {
+    pub fn new() -> GameState {
+        GameState {
+            board: Board::new(),
+            pgn_game: PgnGame::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            viewed_ply: None,
+            time_control: None,
+            observers: Vec::new(),
+            next_observer_id: 0,
+        }
+    }
+
+    // Replays a game record from its starting position, the one of its FEN tag when it was set
+    // up, checking every move. The tags and annotations of the record are kept, and the moves can
+    // be undone like played ones.
+    pub fn from_pgn_game(pgn_game: PgnGame) -> Result<GameState, GameLoadError> {
+        let plies: Vec<(ChessMove, PgnAnnotation)> = pgn_game.get_plies().into_iter()
+            .map(|(m, a)| (m.clone(), a.clone()))
+            .collect();
+
+        let mut state = GameState::new();
+        if let Some(fen) = pgn_game.get_tag("FEN") {
+            state.board = Board::from_fen(&fen).map_err(GameLoadError::InvalidFen)?;
+        }
+        state.time_control = pgn_game.get_tag("TimeControl").and_then(|t| TimeControl::from_pgn_tag(&t));
+        state.pgn_game = pgn_game;
+        state.pgn_game.clear_moves();
+        for (ply, (chess_move, annotation)) in plies.into_iter().enumerate() {
+            let board_move = match state.board.resolve_move(&chess_move) {
+                Ok(m) => m,
+                Err(reason) => return Err(GameLoadError::Unplayable { ply, chess_move, reason }),
+            };
+            let recorded = state.board.to_chess_move(board_move);
+            state.apply(board_move, recorded, annotation);
+        }
+        Ok(state)
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_pgn_game(&self) -> &PgnGame {
+        &self.pgn_game
+    }
+
+    pub fn get_pgn_game_mut(&mut self) -> &mut PgnGame {
+        &mut self.pgn_game
+    }
+
+    pub fn get_time_control(&self) -> Option<&TimeControl> {
+        self.time_control.as_ref()
+    }
+
+    // Sets the time control and records it in the TimeControl tag, "-" for an untimed game.
+    pub fn set_time_control(&mut self, time_control: Option<TimeControl>) {
+        let tag = match &time_control {
+            Some(t) => t.get_pgn_tag(),
+            None => String::from("-"),
+        };
+        self.pgn_game.set_tag("TimeControl", tag);
+        self.time_control = time_control;
+    }
+
+    // Plays a SAN move on the board and records it. Returns the move as written to the game
+    // record, which may differ from the input (e.g. added check markers or dropped
+    // unnecessary disambiguation).
+    pub fn play_move(&mut self, chess_move: &ChessMove) -> Result<ChessMove, GameMoveError> {
+        self.check_can_move()?;
+        let board_move = self.board.resolve_move(chess_move).map_err(GameMoveError::Unresolved)?;
+        Ok(self.record(board_move, PgnAnnotation::new()))
+    }
+
+    // Plays a move given as squares, e.g. from an engine, and records it with an annotation.
+    pub fn play_board_move(&mut self, board_move: BoardMove, annotation: PgnAnnotation) -> Result<ChessMove, GameMoveError> {
+        self.check_can_move()?;
+        if !self.board.is_legal_move(board_move) {
+            return Err(GameMoveError::Illegal(board_move));
+        }
+        Ok(self.record(board_move, annotation))
+    }
+
+    // The moves played so far as squares, oldest first.
+    pub fn get_board_moves(&self) -> Vec<BoardMove> {
+        self.undo_stack.iter().map(|e| e.board_move).collect()
+    }
+
+    fn check_can_move(&self) -> Result<(), GameMoveError> {
+        if self.viewed_ply.is_some() {
+            return Err(GameMoveError::ViewingHistory);
+        }
+        if self.pgn_game.is_finished() {
+            return Err(GameMoveError::GameOver);
+        }
+        Ok(())
+    }
+
+    // Plays a move and records it. Playing the move that was taken back last replays it, any
+    // other move keeps the moves taken back as a variation of it, along with their own.
+    fn record(&mut self, board_move: BoardMove, mut annotation: PgnAnnotation) -> ChessMove {
+        let recorded = self.board.to_chess_move(board_move);
+        match self.redo_stack.last() {
+            Some(entry) if entry.board_move == board_move => {
+                let entry = self.redo_stack.pop().expect("the move taken back is there");
+                annotation = entry.annotation;
+            }
+            Some(_) => {
+                let mut old_line: Vec<HistoryEntry> = self.redo_stack.drain(..).rev().collect();
+                let siblings = old_line[0].annotation.take_variations();
+                let plies: Vec<(ChessMove, PgnAnnotation)> = old_line.into_iter().map(|e| (e.recorded, e.annotation)).collect();
+                annotation.add_variation(format_variation(self.undo_stack.len() + 1, &plies));
+                for variation in siblings {
+                    annotation.add_variation(variation);
+                }
+            }
+            None => (),
+        }
+        self.apply(board_move, recorded.clone(), annotation);
+        self.notify_move(&recorded);
+        recorded
+    }
+
+    // The variations of the game, by the half move they are played instead of, counted from 1.
+    pub fn get_variations(&self) -> Vec<(usize, &String)> {
+        self.undo_stack.iter().enumerate()
+            .flat_map(|(i, e)| e.annotation.get_variations().iter().map(move |v| (i + 1, v)))
+            .collect()
+    }
+
+    // Plays the variation given by its place in get_variations instead of the moves from where
+    // it branches off. Those moves, along with any taken back, are kept as a variation in its
+    // place, so nothing is lost. Returns the half move the variation branches off at.
+    pub fn enter_variation(&mut self, index: usize) -> Result<usize, VariationError> {
+        let (ply, text) = match self.get_variations().get(index) {
+            Some((ply, text)) => (*ply, (*text).clone()),
+            None => return Err(VariationError::NoVariation),
+        };
+        let plies = PgnGame::from(&text).map_err(VariationError::Unreadable)?.get_plies().into_iter()
+            .map(|(m, a)| (m.clone(), a.clone()))
+            .collect::<Vec<(ChessMove, PgnAnnotation)>>();
+        // Every move is checked before the game is changed.
+        let mut board = self.undo_stack[ply - 1].board_before.clone();
+        let mut board_moves = Vec::new();
+        for (i, (chess_move, _)) in plies.iter().enumerate() {
+            let board_move = board.resolve_move(chess_move).map_err(|reason| {
+                VariationError::Unplayable(GameLoadError::Unplayable { ply: ply - 1 + i, chess_move: chess_move.clone(), reason })
+            })?;
+            board.make_move(board_move);
+            board_moves.push(board_move);
+        }
+        if board_moves.is_empty() {
+            return Err(VariationError::NoVariation);
+        }
+
+        // The moves taken back are played again first, so they stay in the line replaced.
+        self.redo(self.redo_stack.len());
+        self.undo(self.undo_stack.len() - ply + 1);
+        let replaced = self.redo_stack.last_mut().expect("the moves from the branch were taken back");
+        let position = replaced.annotation.get_variations().iter().position(|v| *v == text).expect("the variation is there");
+        replaced.annotation.remove_variation(position);
+        for (board_move, (_, annotation)) in board_moves.into_iter().zip(plies) {
+            self.record(board_move, annotation);
+        }
+        Ok(ply)
+    }
+
+    // How many times a position has been on the board in the game so far, the current position
+    // included. Positions are the same when the same pieces stand on the same squares with the
+    // same player to move, castling rights and en passant captures.
+    fn count_positions(&self, board: &Board) -> usize {
+        let key = board.zobrist_key();
+        self.undo_stack.iter().map(|e| &e.board_before).chain([&self.board])
+            .filter(|b| b.zobrist_key() == key)
+            .count()
+    }
+
+    // How many times the current position has been on the board.
+    pub fn get_repetition_count(&self) -> usize {
+        self.count_positions(&self.board)
+    }
+
+    // The draw the player to move can claim, if any.
+    pub fn get_draw_claim(&self) -> Option<DrawClaim> {
+        draw_claim(&self.board, self.get_repetition_count())
+    }
+
+    // Claims a draw for the player to move. As over the board, the claim can be made with the
+    // move that brings the repetition or the fiftieth move about: the move is played, and the
+    // game is drawn if the position after it makes the claim right. A wrong claim leaves the
+    // game going, with the move played. Returns the move played and the claim, when it was right.
+    pub fn claim_draw(&mut self, intended: Option<&ChessMove>) -> Result<(Option<ChessMove>, Option<DrawClaim>), GameMoveError> {
+        self.check_can_move()?;
+        let (played, claim) = match intended {
+            Some(chess_move) => {
+                let board_move = self.board.resolve_move(chess_move).map_err(GameMoveError::Unresolved)?;
+                let mut after = self.board.clone();
+                after.make_move(board_move);
+                let claim = draw_claim(&after, self.count_positions(&after) + 1);
+                (Some(self.record(board_move, PgnAnnotation::new())), claim)
+            }
+            None => (None, self.get_draw_claim()),
+        };
+        // A move that ends the game by itself, e.g. with checkmate, leaves nothing to claim.
+        if self.pgn_game.is_finished() {
+            return Ok((played, None));
+        }
+        if let Some(claim) = claim {
+            self.end_game(PgnResult::Draw);
+            self.pgn_game.set_result_comment(format!("Draw claimed by {claim}"));
+        }
+        Ok((played, claim))
+    }
+
+    // Adds an observer, told about every move played and game ending from now on.
+    pub fn subscribe(&mut self, observer: Box<dyn GameObserver>) -> ObserverId {
+        let id = ObserverId(self.next_observer_id);
+        self.next_observer_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+
+    // Removes an observer. Returns false if it wasn't subscribed.
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        let count = self.observers.len();
+        self.observers.retain(|(i, _)| *i != id);
+        self.observers.len() < count
+    }
+
+    // Whether an observer is still subscribed to this game.
+    pub fn is_subscribed(&self, id: ObserverId) -> bool {
+        self.observers.iter().any(|(i, _)| *i == id)
+    }
+
+    // Ends the game with a result decided away from the board, e.g. by time or adjudication.
+    pub fn end_game(&mut self, result: PgnResult) {
+        self.pgn_game.set_result(result);
+        let status = GameStatus::Ended(result);
+        for (_, observer) in &mut self.observers {
+            observer.on_game_end(&status);
+        }
+    }
+
+    // Tells the observers how much time each side has left.
+    pub fn tick_clock(&mut self, white: Duration, black: Duration) {
+        for (_, observer) in &mut self.observers {
+            observer.on_clock_tick(white, black);
+        }
+    }
+
+    // Tells the observers about the move just played and what followed from it.
+    fn notify_move(&mut self, recorded: &ChessMove) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let captured = self.undo_stack.last().and_then(|e| e.captured);
+        let status = self.get_status();
+        for (_, observer) in &mut self.observers {
+            observer.on_move(recorded, &self.board);
+            if let Some(piece) = captured {
+                observer.on_capture(piece, &self.board);
+            }
+            match &status {
+                GameStatus::InCheck(team) => observer.on_check(*team, &self.board),
+                GameStatus::Checkmate { .. } | GameStatus::Stalemate | GameStatus::Ended(_) => observer.on_game_end(&status),
+                _ => (),
+            }
+        }
+    }
+
+    // Takes back up to `count` moves and returns how many were taken back. Taking back a move
+    // of a finished game reopens it, as its result no longer follows from the position.
+    pub fn undo(&mut self, count: usize) -> usize {
+        self.viewed_ply = None;
+        let mut undone = 0;
+        while undone < count {
+            match self.undo_stack.pop() {
+                Some(entry) => {
+                    self.board = entry.board_before.clone();
+                    self.pgn_game.pop_move();
+                    self.redo_stack.push(entry);
+                    undone += 1;
+                }
+                None => break,
+            }
+        }
+        if undone > 0 && self.pgn_game.is_finished() {
+            self.pgn_game.set_result(PgnResult::Unknown);
+            self.pgn_game.clear_result_comment();
+        }
+        undone
+    }
+
+    // Replays up to `count` previously undone moves and returns how many were replayed.
+    pub fn redo(&mut self, count: usize) -> usize {
+        self.viewed_ply = None;
+        let mut redone = 0;
+        while redone < count {
+            match self.redo_stack.pop() {
+                Some(entry) => {
+                    self.apply(entry.board_move, entry.recorded, entry.annotation);
+                    redone += 1;
+                }
+                None => break,
+            }
+        }
+        redone
+    }
+
+    // The number of half moves played.
+    pub fn get_ply_count(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    // The number of half moves played up to the viewed position.
+    pub fn get_viewed_ply(&self) -> usize {
+        self.viewed_ply.unwrap_or(self.undo_stack.len())
+    }
+
+    pub fn is_viewing_history(&self) -> bool {
+        self.viewed_ply.is_some()
+    }
+
+    // Looks at the position after `ply` half moves, or the current position when `ply` is at
+    // or past the last move. Returns the half move reached.
+    pub fn view_ply(&mut self, ply: usize) -> usize {
+        if ply < self.undo_stack.len() {
+            self.viewed_ply = Some(ply);
+            ply
+        }
+        else {
+            self.viewed_ply = None;
+            self.undo_stack.len()
+        }
+    }
+
+    // The board at the viewed position, the current board unless an earlier one is viewed.
+    pub fn get_viewed_board(&self) -> &Board {
+        match self.viewed_ply {
+            Some(ply) => &self.undo_stack[ply].board_before,
+            None => &self.board,
+        }
+    }
+
+    // The move that led to the viewed position with its annotation from the game record, None
+    // at the start of the game.
+    pub fn get_viewed_move(&self) -> Option<(&ChessMove, &PgnAnnotation)> {
+        let ply = self.get_viewed_ply();
+        if ply == 0 {
+            return None;
+        }
+        self.pgn_game.get_plies().get(ply - 1).copied()
+    }
+
+    // The move that led to the viewed position as squares, with the board it was played on. None
+    // at the start of the game.
+    pub fn get_viewed_board_move(&self) -> Option<(&Board, BoardMove)> {
+        let entry = self.undo_stack.get(self.get_viewed_ply().checked_sub(1)?)?;
+        Some((&entry.board_before, entry.board_move))
+    }
+
+    // The status at the viewed position. The result of the game only shows at its end.
+    pub fn get_status(&self) -> GameStatus {
+        let board = self.get_viewed_board();
+        let turn = board.get_turn();
+        if board.is_checkmate() {
+            GameStatus::Checkmate { winner: turn.opposite() }
+        }
+        else if board.is_stalemate() {
+            GameStatus::Stalemate
+        }
+        else if self.pgn_game.is_finished() && self.viewed_ply.is_none() {
+            GameStatus::Ended(*self.pgn_game.get_result())
+        }
+        else if board.is_in_check(turn) {
+            GameStatus::InCheck(turn)
+        }
+        else {
+            GameStatus::ToMove(turn)
+        }
+    }
+
+    // The pieces a side has captured up to the viewed position, in the order they were taken.
+    pub fn get_captured_by(&self, team: Team) -> Vec<Piece> {
+        self.undo_stack[..self.get_viewed_ply()].iter()
+            .filter_map(|e| e.captured)
+            .filter(|p| *p.get_team() != team)
+            .collect()
+    }
+
+    // White's material minus Black's at the viewed position, counted from the pieces on the
+    // board so promotions are included.
+    pub fn get_material_difference(&self) -> i32 {
+        let board = self.get_viewed_board();
+        board.get_material(Team::Light) as i32 - board.get_material(Team::Dark) as i32
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    // The number of moves taken back that can be replayed.
+    pub fn get_redo_count(&self) -> usize {
+        self.redo_stack.len()
+    }
+
+    // The moves taken back with their annotations, in the order redo replays them.
+    pub fn get_undone_moves(&self) -> Vec<(&ChessMove, &PgnAnnotation)> {
+        self.redo_stack.iter().rev().map(|e| (&e.recorded, &e.annotation)).collect()
+    }
+
+    // Adds a NAG to the most recent move, kept when the move is undone and replayed. Returns
+    // false when no move has been played.
+    pub fn annotate_last_move(&mut self, nag: u8) -> bool {
+        self.edit_annotation(self.undo_stack.len(), |annotation| annotation.add_nag(nag))
+    }
+
+    // Changes the annotation of a half move, counted from 1, in the record and in what is kept
+    // for undo. Returns false when the move hasn't been played.
+    pub fn edit_annotation(&mut self, ply: usize, edit: impl Fn(&mut PgnAnnotation)) -> bool {
+        if ply == 0 {
+            return false;
+        }
+        match (self.undo_stack.get_mut(ply - 1), self.pgn_game.get_annotation_mut(ply - 1)) {
+            (Some(entry), Some(annotation)) => {
+                edit(&mut entry.annotation);
+                edit(annotation);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn apply(&mut self, board_move: BoardMove, recorded: ChessMove, annotation: PgnAnnotation) {
+        let board_before = self.board.clone();
+        let captured = self.board.make_move(board_move);
+        self.pgn_game.push_annotated_move(recorded.clone(), annotation.clone());
+        self.undo_stack.push(HistoryEntry { board_before, board_move, recorded, annotation, captured });
+        // A position ending the game gives the record its result, so it is never saved as
+        // unfinished.
+        if !self.pgn_game.is_finished() {
+            if self.board.is_checkmate() {
+                self.pgn_game.set_result(match self.board.get_turn() {
+                    Team::Light => PgnResult::BlackWin,
+                    Team::Dark => PgnResult::WhiteWin,
+                });
+            }
+            else if self.board.is_stalemate() {
+                self.pgn_game.set_result(PgnResult::Draw);
+            }
+            // Unlike threefold repetition and the fifty move rule, these draws need no claim.
+            else if self.get_repetition_count() >= 5 {
+                self.pgn_game.set_result(PgnResult::Draw);
+                self.pgn_game.set_result_comment(String::from("Draw by fivefold repetition"));
+            }
+            else if self.board.get_halfmove_clock() >= 150 {
+                self.pgn_game.set_result(PgnResult::Draw);
+                self.pgn_game.set_result_comment(String::from("Draw by the seventy-five move rule"));
+            }
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_game_state {
+    use std::{cell::RefCell, rc::Rc};
+    use super::*;
+    use crate::chess_common::ChessPiece;
+
+    fn play(game: &mut GameState, san: &str) -> Result<ChessMove, GameMoveError> {
+        game.play_move(&ChessMove::from(san).unwrap())
+    }
+
+    #[test]
+    pub fn moves_are_played_and_recorded() {
+        let mut game = GameState::new();
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+            assert!(play(&mut game, san).is_ok());
+        }
+        assert_eq!(game.get_board().to_fen(), "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3");
+        assert!(game.get_pgn_game().to_string().contains("1. e4 e5 2. Nf3 Nc6 3. Bb5"));
+    }
+
+    #[test]
+    pub fn recorded_move_is_normalized() {
+        let mut game = GameState::new();
+        for san in ["e4", "f6", "d4", "g5"] {
+            play(&mut game, san).unwrap();
+        }
+        assert_eq!(play(&mut game, "Qh5").unwrap().to_string(), "Qh5#");
+    }
+
+    #[test]
+    pub fn illegal_move_is_rejected() {
+        let mut game = GameState::new();
+        assert_eq!(play(&mut game, "e5"), Err(GameMoveError::Unresolved(MoveResolveError::IllegalMove)));
+        assert_eq!(game.get_board(), &Board::new());
+    }
+
+    #[test]
+    pub fn no_moves_after_the_game_ends() {
+        let mut game = GameState::new();
+        game.get_pgn_game_mut().set_result(PgnResult::Draw);
+        assert_eq!(play(&mut game, "e4"), Err(GameMoveError::GameOver));
+    }
+
+    #[test]
+    pub fn undo_restores_the_position() {
+        let mut game = GameState::new();
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3"] {
+            play(&mut game, san).unwrap();
+        }
+        let before_capture = Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2").unwrap();
+
+        assert_eq!(game.undo(3), 3);
+        assert_eq!(game.get_board(), &before_capture);
+        assert!(game.get_pgn_game().to_string().ends_with("1. e4 d5 *"));
+
+        assert_eq!(game.undo(5), 2);
+        assert_eq!(game.get_board(), &Board::new());
+        assert!(!game.can_undo());
+    }
+
+    #[test]
+    pub fn redo_replays_undone_moves() {
+        let mut game = GameState::new();
+        for san in ["e4", "e5", "Nf3"] {
+            play(&mut game, san).unwrap();
+        }
+        let fen = game.get_board().to_fen();
+        game.undo(2);
+        assert_eq!(game.redo(5), 2);
+        assert_eq!(game.get_board().to_fen(), fen);
+        assert!(game.get_pgn_game().to_string().ends_with("1. e4 e5 2. Nf3 *"));
+        assert!(!game.can_redo());
+    }
+
+    #[test]
+    pub fn annotations_are_kept_through_undo() {
+        let mut game = GameState::new();
+        assert!(!game.annotate_last_move(4));
+        for san in ["e4", "e5", "Qh5"] {
+            play(&mut game, san).unwrap();
+        }
+        assert!(game.annotate_last_move(4));
+        game.undo(1);
+        game.redo(1);
+        assert!(game.get_pgn_game().to_string().ends_with("1. e4 e5 2. Qh5 $4 *"));
+    }
+
+    #[test]
+    pub fn new_move_clears_redo() {
+        let mut game = GameState::new();
+        play(&mut game, "e4").unwrap();
+        game.undo(1);
+        assert!(game.can_redo());
+        play(&mut game, "d4").unwrap();
+        assert!(!game.can_redo());
+        assert_eq!(game.redo(1), 0);
+    }
+
+    #[test]
+    pub fn lines_taken_back_become_variations() {
+        let mut game = GameState::from_pgn_game(PgnGame::from("1. e4 e5 2. Nf3 Nc6 3. Bb5 {Spanish} (3. d4) a6 *").unwrap()).unwrap();
+        game.undo(4);
+        // The move taken back is replayed, the rest kept for redo.
+        play(&mut game, "Nf3").unwrap();
+        assert_eq!(game.get_redo_count(), 3);
+        play(&mut game, "Bc5").unwrap();
+        assert!(!game.can_redo());
+        assert_eq!(game.get_variations(), vec![(4, &String::from("2... Nc6 3. Bb5 {Spanish} (3. d4) 3... a6"))]);
+
+        play(&mut game, "c3").unwrap();
+        assert_eq!(game.enter_variation(0), Ok(4));
+        assert_eq!(game.get_ply_count(), 6);
+        assert_eq!(game.get_pgn_game().get_plies()[4].1.get_comments(), &vec![String::from("Spanish")]);
+        assert_eq!(game.get_variations(), vec![(4, &String::from("2... Bc5 3. c3")), (5, &String::from("3. d4"))]);
+        assert_eq!(game.enter_variation(5), Err(VariationError::NoVariation));
+        assert_eq!(game.get_pgn_game().to_string().lines().last(), Some("1. e4 e5 2. Nf3 Nc6 (2... Bc5 3. c3) 3. Bb5 {Spanish} (3. d4) 3... a6 *"));
+    }
+
+    #[test]
+    pub fn entering_a_variation_keeps_the_moves_taken_back() {
+        let mut game = GameState::from_pgn_game(PgnGame::from("1. e4 e5 2. Nf3 (2. d4 exd4) 2... Nc6 3. Bb5 *").unwrap()).unwrap();
+        game.undo(2);
+        assert_eq!(game.enter_variation(0), Ok(3));
+        assert_eq!(game.get_ply_count(), 4);
+        assert!(!game.can_redo());
+        assert_eq!(game.get_variations(), vec![(3, &String::from("2. Nf3 Nc6 3. Bb5"))]);
+    }
+
+    #[test]
+    pub fn loaded_game_is_replayed() {
+        let pgn = PgnGame::from("[White \"A\"]\n\n1. e4 e5 2. Nf3 {develops} Nc6 3. Bb5 a6 1-0").unwrap();
+        let mut game = GameState::from_pgn_game(pgn).unwrap();
+        assert_eq!(game.get_board().to_fen(), "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4");
+        assert_eq!(game.get_pgn_game().get_white(), "A");
+        assert!(game.get_pgn_game().to_string().contains("2. Nf3 {develops} 2... Nc6"));
+        assert!(game.get_pgn_game().is_finished());
+
+        // Taking back moves of a finished game reopens it, and annotations come back on redo.
+        assert_eq!(game.undo(3), 3);
+        assert!(!game.get_pgn_game().is_finished());
+        game.redo(3);
+        assert!(game.get_pgn_game().to_string().contains("2. Nf3 {develops} 2... Nc6"));
+    }
+
+    #[test]
+    pub fn loading_reports_the_failing_move() {
+        let pgn = PgnGame::from("1. e4 e5 2. Nf3 Nc6 3. Bb5 Nxe4 4. Bxc6 Ke7 *").unwrap();
+        let err = GameState::from_pgn_game(pgn).err().unwrap();
+        assert_eq!(err.get_ply(), Some(5));
+        assert_eq!(err.to_string(), "move 3... Nxe4: no legal move matches it in this position");
+    }
+
+    #[test]
+    pub fn loading_starts_from_the_fen_tag() {
+        let fen = "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1";
+        let pgn = PgnGame::from(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n\n1. e4 Kd6 2. Kd2 *")).unwrap();
+        let mut game = GameState::from_pgn_game(pgn).unwrap();
+        assert_eq!(game.get_ply_count(), 3);
+        assert_eq!(game.get_board().to_fen(), "8/8/3k4/8/4P3/8/3K4/8 b - - 2 2");
+        assert_eq!(game.undo(3), 3);
+        assert_eq!(game.get_board(), &Board::from_fen(fen).unwrap());
+
+        let invalid = PgnGame::from("[SetUp \"1\"]\n[FEN \"8/8/8 w - - 0 1\"]\n\n1. e4 *").unwrap();
+        let err = GameState::from_pgn_game(invalid).err().unwrap();
+        assert!(matches!(err, GameLoadError::InvalidFen(_)));
+        assert_eq!(err.get_ply(), None);
+    }
+
+    #[test]
+    pub fn captures_follow_the_history() {
+        let mut game = GameState::new();
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3", "Qxa2", "Rxa2"] {
+            play(&mut game, san).unwrap();
+        }
+        let captured = |game: &GameState, team| -> Vec<ChessPiece> {
+            game.get_captured_by(team).iter().map(|p| p.get_piece_type()).collect()
+        };
+        assert_eq!(captured(&game, Team::Light), vec![ChessPiece::Pawn, ChessPiece::Queen]);
+        assert_eq!(captured(&game, Team::Dark), vec![ChessPiece::Pawn, ChessPiece::Pawn]);
+        assert_eq!(game.get_material_difference(), 8);
+
+        game.undo(1);
+        assert_eq!(captured(&game, Team::Light), vec![ChessPiece::Pawn]);
+        assert_eq!(game.get_material_difference(), -1);
+    }
+
+    #[test]
+    pub fn status_follows_the_position() {
+        let mut game = GameState::new();
+        assert_eq!(game.get_status().to_string(), "White to move");
+        for san in ["f3", "e5", "g4"] {
+            play(&mut game, san).unwrap();
+        }
+        assert_eq!(game.get_status(), GameStatus::ToMove(Team::Dark));
+        play(&mut game, "Qh4").unwrap();
+        assert_eq!(game.get_status().to_string(), "Checkmate \u{2014} Black wins");
+        assert_eq!(game.get_pgn_game().get_result(), &PgnResult::BlackWin);
+        assert!(game.get_pgn_game().to_string().ends_with("2. g4 Qh4# 0-1"));
+        game.undo(1);
+        assert!(!game.get_pgn_game().is_finished());
+        game.redo(1);
+        assert_eq!(game.get_pgn_game().get_result(), &PgnResult::BlackWin);
+
+        game.undo(4);
+        for san in ["e4", "f5", "Qh5"] {
+            play(&mut game, san).unwrap();
+        }
+        assert_eq!(game.get_status().to_string(), "Black is in check");
+
+        game.get_pgn_game_mut().set_result(PgnResult::Draw);
+        assert_eq!(game.get_status().to_string(), "Game over \u{2014} 1/2-1/2");
+    }
+
+    #[test]
+    pub fn viewing_history_keeps_the_record() {
+        let pgn_game = PgnGame::from("1. e4 e5 2. Qh5 $2 {Too early} Nc6 3. Bc4 Nf6 4. Qxf7# 1-0").unwrap();
+        let mut game = GameState::from_pgn_game(pgn_game).unwrap();
+        assert_eq!(game.get_ply_count(), 7);
+
+        assert_eq!(game.view_ply(3), 3);
+        assert!(game.is_viewing_history());
+        assert_eq!(game.get_viewed_board().get_turn(), Team::Dark);
+        let (chess_move, annotation) = game.get_viewed_move().unwrap();
+        assert_eq!(chess_move.to_string(), "Qh5");
+        assert_eq!(annotation.get_nags(), &vec![2]);
+        assert_eq!(annotation.get_comments(), &vec![String::from("Too early")]);
+        assert_eq!(game.get_status(), GameStatus::ToMove(Team::Dark));
+        assert_eq!(play(&mut game, "Nc6"), Err(GameMoveError::ViewingHistory));
+
+        assert_eq!(game.view_ply(0), 0);
+        assert_eq!(game.get_viewed_move(), None);
+        assert_eq!(game.get_viewed_board(), &Board::new());
+
+        assert_eq!(game.view_ply(100), 7);
+        assert!(!game.is_viewing_history());
+        assert_eq!(game.get_pgn_game().get_ply_count(), 7);
+        assert_eq!(game.get_status(), GameStatus::Checkmate { winner: Team::Light });
+    }
+
+    // Writes down every event it is told about.
+    struct EventLog(Rc<RefCell<Vec<String>>>);
+
+    impl GameObserver for EventLog {
+        fn on_move(&mut self, chess_move: &ChessMove, _board: &Board) {
+            self.0.borrow_mut().push(format!("move {chess_move}"));
+        }
+
+        fn on_capture(&mut self, captured: Piece, _board: &Board) {
+            self.0.borrow_mut().push(format!("capture {}", captured.get_ascii_symbol()));
+        }
+
+        fn on_check(&mut self, team: Team, _board: &Board) {
+            self.0.borrow_mut().push(format!("check {team}"));
+        }
+
+        fn on_game_end(&mut self, status: &GameStatus) {
+            self.0.borrow_mut().push(format!("end {status}"));
+        }
+
+        fn on_clock_tick(&mut self, white: Duration, black: Duration) {
+            self.0.borrow_mut().push(format!("clock {} {}", white.as_secs(), black.as_secs()));
+        }
+    }
+
+    #[test]
+    pub fn observers_hear_about_the_game() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut game = GameState::new();
+        let id = game.subscribe(Box::new(EventLog(events.clone())));
+        for san in ["e4", "f5", "exf5", "g5", "Qh5"] {
+            play(&mut game, san).unwrap();
+        }
+        game.tick_clock(Duration::from_secs(60), Duration::from_secs(58));
+        assert_eq!(*events.borrow(), vec![
+            "move e4", "move f5", "move exf5", "capture p", "move g5", "move Qh5#", "end Checkmate \u{2014} White wins", "clock 60 58",
+        ]);
+
+        events.borrow_mut().clear();
+        game.undo(3);
+        play(&mut game, "Nc3").unwrap();
+        play(&mut game, "e6").unwrap();
+        play(&mut game, "Qh5").unwrap();
+        game.end_game(PgnResult::BlackWin);
+        assert_eq!(*events.borrow(), vec!["move Nc3", "move e6", "move Qh5+", "check Black", "end Game over \u{2014} Black wins"]);
+
+        assert!(game.is_subscribed(id));
+        assert!(game.unsubscribe(id));
+        assert!(!game.unsubscribe(id));
+        assert!(!game.is_subscribed(id));
+        game.undo(1);
+        play(&mut game, "a3").unwrap();
+        assert_eq!(events.borrow().len(), 5);
+    }
+
+    #[test]
+    pub fn draws_are_claimed_or_follow_from_the_rules() {
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        let mut game = GameState::new();
+        for san in shuffle.iter().chain(&shuffle[..3]) {
+            play(&mut game, san).unwrap();
+        }
+        assert_eq!(game.get_repetition_count(), 2);
+        assert_eq!(game.claim_draw(None), Ok((None, None)));
+        // The claim with the move repeating the position a third time plays the move and draws.
+        let (played, claim) = game.claim_draw(Some(&ChessMove::from("Ng8").unwrap())).unwrap();
+        assert_eq!((played.unwrap().to_string().as_str(), claim), ("Ng8", Some(DrawClaim::ThreefoldRepetition)));
+        assert_eq!(game.get_pgn_game().get_result(), &PgnResult::Draw);
+        assert_eq!(game.get_pgn_game().get_result_comment().unwrap(), "Draw claimed by threefold repetition");
+
+        // Unclaimed, the game goes on until the fifth time.
+        let mut game = GameState::new();
+        for san in shuffle.iter().cycle().take(12) {
+            play(&mut game, san).unwrap();
+        }
+        assert!(!game.get_pgn_game().is_finished());
+        for san in shuffle {
+            play(&mut game, san).unwrap();
+        }
+        assert_eq!(game.get_status(), GameStatus::Ended(PgnResult::Draw));
+        assert_eq!(game.get_pgn_game().get_result_comment().unwrap(), "Draw by fivefold repetition");
+
+        let quiet = Board::from_fen("8/8/4k3/8/8/3K4/8/7R w - - 100 90").unwrap();
+        assert_eq!(draw_claim(&quiet, 1), Some(DrawClaim::FiftyMoveRule));
+    }
+
+    #[test]
+    pub fn time_control_is_kept_in_the_record() {
+        let mut game = GameState::new();
+        game.set_time_control(TimeControl::from("classical"));
+        assert_eq!(game.get_pgn_game().get_tag("TimeControl"), Some(String::from("40/5400+30:1800+30")));
+        let loaded = GameState::from_pgn_game(game.get_pgn_game().clone()).unwrap();
+        assert_eq!(loaded.get_time_control(), TimeControl::from("40/90+30:30+30").as_ref());
+
+        game.set_time_control(None);
+        assert_eq!(game.get_pgn_game().get_tag("TimeControl"), Some(String::from("-")));
+        assert_eq!(GameState::from_pgn_game(game.get_pgn_game().clone()).unwrap().get_time_control(), None);
+    }
+}
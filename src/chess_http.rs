@@ -0,0 +1,253 @@
+/*
+chess_http.rs
+A small HTTP API over the game, for dashboards and custom interfaces. Replies are JSON, except the
+PGN export:
+
+  GET  /state              the board, moves, status and clocks, as WebSocket clients get them
+  GET  /fen                {"fen": ...}
+  GET  /moves              {"moves": [...]}, the legal moves in SAN
+  POST /move               plays {"move": "Nf3"} and replies with the state
+  GET  /pgn                the game as PGN
+  GET  /analysis?movetime  {"best_move": ..., "score": ..., "depth": ..., "pv": [...]}, movetime
+                           in milliseconds
+
+Requests are taken in by whoever runs the server, which knows the game, and answered by it.
+Browsers may call the API from any page.
+*/
+
+use std::{
+    io::{self, Read},
+    net::SocketAddr,
+    time::Duration,
+};
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    chess_core::{Board, Team},
+    chess_uci::{EngineScore, SearchResult},
+};
+
+// How long the engine thinks for /analysis when not told, and at most.
+const DEFAULT_ANALYSIS_TIME: Duration = Duration::from_millis(1000);
+const MAX_ANALYSIS_TIME: Duration = Duration::from_millis(10_000);
+
+// What a client asks for.
+#[derive(Debug, PartialEq)]
+pub enum ApiRequest {
+    State,
+    Fen,
+    LegalMoves,
+    Move(String),
+    Pgn,
+    Analysis(Duration),
+}
+
+// An answer to a request.
+#[derive(Debug, PartialEq)]
+pub struct ApiReply {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+pub struct HttpServer {
+    server: Server,
+    address: SocketAddr,
+}
+
+// A request taken in, answered with `answer`.
+pub struct PendingRequest {
+    request: Request,
+}
+
+#[derive(Deserialize)]
+struct MoveBody {
+    #[serde(rename = "move")]
+    san: String,
+}
+
+// The engine's view of a position, from White's side like the clocks.
+#[derive(Serialize)]
+struct AnalysisReply {
+    best_move: String,
+    // Centipawns, or moves to mate.
+    cp: Option<i32>,
+    mate: Option<i32>,
+    depth: Option<u32>,
+    pv: Vec<String>,
+}
+
+impl ApiReply {
+    pub fn json(value: &impl Serialize) -> ApiReply {
+        match serde_json::to_string(value) {
+            Ok(body) => ApiReply { status: 200, content_type: "application/json", body },
+            Err(e) => ApiReply::error(500, &e.to_string()),
+        }
+    }
+
+    pub fn text(body: String) -> ApiReply {
+        ApiReply { status: 200, content_type: "text/plain; charset=utf-8", body }
+    }
+
+    // e.g. {"error": "Illegal move Ke2: ..."}
+    pub fn error(status: u16, message: &str) -> ApiReply {
+        let body = serde_json::json!({ "error": message }).to_string();
+        ApiReply { status, content_type: "application/json", body }
+    }
+
+    pub fn get_status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn get_body(&self) -> &str {
+        &self.body
+    }
+}
+
+// Reads a request from its method, address and body. Requests that aren't part of the API get
+// the reply to send instead.
+pub fn route(method: &Method, url: &str, body: &str) -> Result<ApiRequest, ApiReply> {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let path = path.trim_end_matches('/');
+    match (method, path) {
+        (Method::Get, "/state") => Ok(ApiRequest::State),
+        (Method::Get, "/fen") => Ok(ApiRequest::Fen),
+        (Method::Get, "/moves") => Ok(ApiRequest::LegalMoves),
+        (Method::Get, "/pgn") => Ok(ApiRequest::Pgn),
+        (Method::Post, "/move") => match serde_json::from_str::<MoveBody>(body) {
+            Ok(m) => Ok(ApiRequest::Move(m.san)),
+            Err(e) => Err(ApiReply::error(400, &format!("Expected {{\"move\": \"<SAN>\"}}: {e}."))),
+        },
+        (Method::Get, "/analysis") => {
+            let movetime = query.split('&').find_map(|p| p.strip_prefix("movetime="));
+            match movetime.map(|t| t.parse::<u64>()) {
+                None => Ok(ApiRequest::Analysis(DEFAULT_ANALYSIS_TIME)),
+                Some(Ok(ms)) if ms > 0 => Ok(ApiRequest::Analysis(Duration::from_millis(ms).min(MAX_ANALYSIS_TIME))),
+                Some(_) => Err(ApiReply::error(400, "movetime is a number of milliseconds.")),
+            }
+        }
+        (_, "/state" | "/fen" | "/moves" | "/pgn" | "/move" | "/analysis") => Err(ApiReply::error(405, "Method not allowed.")),
+        _ => Err(ApiReply::error(404, &format!("No such endpoint {path}, try /state, /fen, /moves, /move, /pgn or /analysis."))),
+    }
+}
+
+// The legal moves of a position in SAN.
+pub fn legal_moves_reply(board: &Board) -> ApiReply {
+    let moves: Vec<String> = board.legal_moves().into_iter().map(|m| board.to_chess_move(m).to_string()).collect();
+    ApiReply::json(&serde_json::json!({ "moves": moves }))
+}
+
+// An engine's result for a position.
+pub fn analysis_reply(board: &Board, result: &SearchResult) -> ApiReply {
+    let score = result.get_score().map(|s| if board.get_turn() == Team::Dark { s.negate() } else { s });
+    let mut pv = Vec::new();
+    let mut line = board.clone();
+    for board_move in result.get_info().get_pv() {
+        if !line.is_legal_move(*board_move) {
+            break;
+        }
+        pv.push(line.to_chess_move(*board_move).to_string());
+        line.make_move(*board_move);
+    }
+    ApiReply::json(&AnalysisReply {
+        best_move: board.to_chess_move(result.get_best_move()).to_string(),
+        cp: match score {
+            Some(EngineScore::Centipawns(cp)) => Some(cp),
+            _ => None,
+        },
+        mate: match score {
+            Some(EngineScore::Mate(moves)) => Some(moves),
+            _ => None,
+        },
+        depth: result.get_depth(),
+        pv,
+    })
+}
+
+impl HttpServer {
+    // Listens on the address, e.g. 127.0.0.1:8081.
+    pub fn start(address: &str) -> io::Result<HttpServer> {
+        let server = Server::http(address).map_err(io::Error::other)?;
+        let address = server.server_addr().to_ip().ok_or_else(|| io::Error::other("not an IP address"))?;
+        Ok(HttpServer { server, address })
+    }
+
+    pub fn get_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // Takes in the requests that arrived, read as far as the API goes. CORS preflight requests
+    // are answered here.
+    pub fn poll(&self) -> Vec<(Result<ApiRequest, ApiReply>, PendingRequest)> {
+        let mut requests = Vec::new();
+        while let Ok(Some(mut request)) = self.server.try_recv() {
+            if *request.method() == Method::Options {
+                let response = Response::empty(204)
+                    .with_header(header("Access-Control-Allow-Methods", "GET, POST"))
+                    .with_header(header("Access-Control-Allow-Headers", "Content-Type"));
+                PendingRequest { request }.respond(response);
+                continue;
+            }
+            let mut body = String::new();
+            let routed = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => route(request.method(), request.url(), &body),
+                Err(e) => Err(ApiReply::error(400, &e.to_string())),
+            };
+            requests.push((routed, PendingRequest { request }));
+        }
+        requests
+    }
+}
+
+impl PendingRequest {
+    pub fn answer(self, reply: ApiReply) {
+        let response = Response::from_string(reply.body)
+            .with_status_code(reply.status)
+            .with_header(header("Content-Type", reply.content_type));
+        self.respond(response);
+    }
+
+    fn respond<R: Read>(self, response: Response<R>) {
+        // The client may be gone already, which is no concern of the game.
+        let _ = self.request.respond(response.with_header(header("Access-Control-Allow-Origin", "*")));
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_http {
+    use crate::chess_engine;
+    use crate::chess_uci::SearchLimit;
+    use super::*;
+
+    #[test]
+    pub fn requests_are_routed() {
+        assert_eq!(route(&Method::Get, "/state", ""), Ok(ApiRequest::State));
+        assert_eq!(route(&Method::Get, "/fen/", ""), Ok(ApiRequest::Fen));
+        assert_eq!(route(&Method::Post, "/move", r#"{"move": "e4"}"#), Ok(ApiRequest::Move(String::from("e4"))));
+        assert_eq!(route(&Method::Get, "/analysis?movetime=250", ""), Ok(ApiRequest::Analysis(Duration::from_millis(250))));
+        assert_eq!(route(&Method::Get, "/analysis?movetime=99999", ""), Ok(ApiRequest::Analysis(MAX_ANALYSIS_TIME)));
+        assert_eq!(route(&Method::Get, "/move", "").unwrap_err().get_status(), 405);
+        assert_eq!(route(&Method::Post, "/move", "e4").unwrap_err().get_status(), 400);
+        let missing = route(&Method::Get, "/board", "").unwrap_err();
+        assert_eq!(missing.get_status(), 404);
+        assert!(missing.get_body().starts_with(r#"{"error":"No such endpoint /board"#), "{}", missing.get_body());
+    }
+
+    #[test]
+    pub fn moves_and_analysis_are_in_san() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let moves = legal_moves_reply(&board);
+        assert!(moves.get_body().contains(r#""Ra8#""#), "{}", moves.get_body());
+
+        let result = chess_engine::search(&board, SearchLimit::Depth(2)).unwrap();
+        let analysis = analysis_reply(&board, &result);
+        assert!(analysis.get_body().starts_with(r#"{"best_move":"Ra8#","cp":null,"mate":1,"#), "{}", analysis.get_body());
+    }
+}
@@ -0,0 +1,220 @@
+/*
+chess_import.rs
+Importing a player's games from the chess.com public API (https://www.chess.com/news/view/published-data-api)
+into a local PGN database. The API lists a player's monthly archives, each of which is fetched as
+PGN and the games not yet in the database are added to the end of it. Games are told apart by
+their Link tag, which chess.com gives every game, so importing again only adds the new games.
+*/
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+use serde::Deserialize;
+
+use crate::chess_pgn::{PgnGame, PgnParseError, parse_pgn_games};
+
+const CHESS_COM_API: &str = "https://api.chess.com/pub";
+// chess.com asks API users to say who they are.
+const USER_AGENT: &str = concat!("rust_chess/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug)]
+pub enum ImportError {
+    // chess.com has no account by the name.
+    NoPlayer(String),
+    Http(String),
+    InvalidArchiveList(String),
+    Database(String, PgnParseError),
+    Io(io::Error),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::NoPlayer(username) => write!(f, "chess.com has no player {}", username),
+            ImportError::Http(e) => write!(f, "{}", e),
+            ImportError::InvalidArchiveList(e) => write!(f, "unexpected archive list: {}", e),
+            ImportError::Database(path, e) => write!(f, "could not read {}, {}", path, e),
+            ImportError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+// The reply of the archives endpoint.
+#[derive(Deserialize)]
+struct ArchiveList {
+    archives: Vec<String>,
+}
+
+// What an import did.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    months: usize,
+    added: usize,
+    // Games already in the database.
+    skipped: usize,
+    // Months that could not be read, with the reason.
+    failed: Vec<(String, String)>,
+}
+
+impl ImportSummary {
+    pub fn get_months(&self) -> usize {
+        self.months
+    }
+
+    pub fn get_added(&self) -> usize {
+        self.added
+    }
+
+    pub fn get_skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn get_failed(&self) -> &Vec<(String, String)> {
+        &self.failed
+    }
+}
+
+// Fetches a chess.com API address, None when there is nothing there.
+pub fn fetch_chess_com(url: &str) -> Result<Option<String>, ImportError> {
+    let response = ureq::get(url).set("User-Agent", USER_AGENT).call();
+    match response {
+        Ok(r) => r.into_string().map(Some).map_err(ImportError::Io),
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(ImportError::Http(e.to_string())),
+    }
+}
+
+// Imports the games of a chess.com player into the PGN file at `database`, which is created
+// when missing. Only months from `since` on are fetched when given, as YYYY/MM. `fetch` gets
+// each API address.
+pub fn import_archive(
+    username: &str,
+    database: &Path,
+    since: Option<&str>,
+    mut fetch: impl FnMut(&str) -> Result<Option<String>, ImportError>,
+) -> Result<ImportSummary, ImportError> {
+    let username = username.to_lowercase();
+    let list = fetch(&format!("{CHESS_COM_API}/player/{username}/games/archives"))?
+        .ok_or_else(|| ImportError::NoPlayer(username.clone()))?;
+    let list: ArchiveList = serde_json::from_str(&list).map_err(|e| ImportError::InvalidArchiveList(e.to_string()))?;
+
+    let existing = match fs::read_to_string(database) {
+        Ok(text) => parse_pgn_games(&text).map_err(|e| ImportError::Database(database.display().to_string(), e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(ImportError::Io(e)),
+    };
+    let mut known: HashSet<String> = existing.iter().filter_map(game_key).collect();
+
+    let mut summary = ImportSummary::default();
+    for archive in &list.archives {
+        let month = archive_month(archive);
+        if since.is_some_and(|s| month.as_str() < s) {
+            continue;
+        }
+        summary.months += 1;
+        let games = match fetch(&format!("{archive}/pgn")) {
+            Ok(text) => parse_pgn_games(&text.unwrap_or_default()).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let games = match games {
+            Ok(g) => g,
+            Err(e) => {
+                summary.failed.push((month, e));
+                continue;
+            }
+        };
+        let mut text = String::new();
+        for game in games {
+            if game_key(&game).is_some_and(|key| !known.insert(key)) {
+                summary.skipped += 1;
+            }
+            else {
+                text.push_str(format!("{game}\n\n").as_str());
+                summary.added += 1;
+            }
+        }
+        append_games(database, &text).map_err(ImportError::Io)?;
+    }
+    Ok(summary)
+}
+
+// The month of an archive address, e.g. 2024/01 for .../games/2024/01.
+fn archive_month(archive: &str) -> String {
+    let parts: Vec<&str> = archive.trim_end_matches('/').rsplitn(3, '/').collect();
+    match parts.as_slice() {
+        [month, year, _] => format!("{year}/{month}"),
+        _ => String::from(archive),
+    }
+}
+
+// What tells a game apart from the others in the database.
+fn game_key(game: &PgnGame) -> Option<String> {
+    game.get_tag("Link").filter(|l| !l.is_empty())
+}
+
+fn append_games(database: &Path, text: &str) -> io::Result<()> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = database.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    OpenOptions::new().create(true).append(true).open(database)?.write_all(text.as_bytes())
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_import {
+    use super::*;
+
+    const ARCHIVES: &str = r#"{"archives":["https://api.chess.com/pub/player/hikaru/games/2023/12","https://api.chess.com/pub/player/hikaru/games/2024/01"]}"#;
+
+    fn chess_com_game(id: u32, moves: &str) -> String {
+        format!("[Event \"Live Chess\"]\n[Site \"Chess.com\"]\n[Date \"2024.01.05\"]\n[Round \"-\"]\n\
+            [White \"Hikaru\"]\n[Black \"MagnusCarlsen\"]\n[Result \"*\"]\n[TimeControl \"180\"]\n\
+            [Link \"https://www.chess.com/game/live/{id}\"]\n\n{moves} *\n\n")
+    }
+
+    fn fake_api(url: &str) -> Result<Option<String>, ImportError> {
+        let text = match url {
+            "https://api.chess.com/pub/player/hikaru/games/archives" => String::from(ARCHIVES),
+            "https://api.chess.com/pub/player/hikaru/games/2023/12/pgn" => chess_com_game(1, "1. e4 {[%clk 0:02:59.9]} 1... e5 {[%clk 0:02:58.1]}"),
+            "https://api.chess.com/pub/player/hikaru/games/2024/01/pgn" => chess_com_game(1, "1. e4 e5") + chess_com_game(2, "1. d4 d5").as_str(),
+            _ => return Ok(None),
+        };
+        Ok(Some(text))
+    }
+
+    #[test]
+    pub fn new_games_are_added_once() {
+        let database = std::env::temp_dir().join(format!("rust_chess_import_{}.pgn", std::process::id()));
+        let _ = fs::remove_file(&database);
+        let summary = import_archive("Hikaru", &database, None, fake_api).unwrap();
+        assert_eq!((summary.get_months(), summary.get_added(), summary.get_skipped()), (2, 2, 1));
+        let games = parse_pgn_games(&fs::read_to_string(&database).unwrap()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_plies()[1].1.get_comments(), &vec![String::from("[%clk 0:02:58.1]")]);
+
+        // Importing again adds nothing, and older months can be left out.
+        let summary = import_archive("hikaru", &database, Some("2024/01"), fake_api).unwrap();
+        assert_eq!((summary.get_months(), summary.get_added(), summary.get_skipped()), (1, 0, 2));
+        let _ = fs::remove_file(&database);
+    }
+
+    #[test]
+    pub fn unknown_players_are_reported() {
+        let database = std::env::temp_dir().join("rust_chess_import_unused.pgn");
+        let error = import_archive("nobody", &database, None, fake_api).unwrap_err();
+        assert_eq!(error.to_string(), "chess.com has no player nobody");
+        assert_eq!(archive_month("https://api.chess.com/pub/player/hikaru/games/2024/01"), "2024/01");
+    }
+}
@@ -0,0 +1,235 @@
+/*
+chess_match.rs
+The match mode of the program, a series of games between two players changing colors every game.
+The player plays an engine at the terminal, or with --against two engines play each other:
+
+rust_chess match 3 --games 4                      the player against the built-in engine level 3
+rust_chess match engine --games 10                the player against the configured engine_path
+rust_chess match /usr/bin/stockfish --against 5   Stockfish against the built-in engine level 5
+
+The score is shown after every game and the result of the match at the end. Every game is added to
+the game archive, and to a PGN file too with --out.
+*/
+
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    chess_adjudication::AdjudicationRules,
+    chess_cmd::{ChessArgs, MatchArgs},
+    chess_config::ChessConfig,
+    chess_pgn::{PgnDate, PgnResult, PgnRound},
+    chess_tournament::{play_game, Player, Terminal, TournamentConsole, TournamentError},
+    chess_uci::UciEngine,
+    chess_ui::tui::archive_pgn_game,
+};
+
+const MAX_LEVEL: u32 = 6;
+
+#[derive(Debug)]
+pub enum MatchError {
+    // An opponent that is neither a level, engine nor an engine's path.
+    Opponent(String),
+    NoEngine,
+    Game(TournamentError),
+}
+
+impl Display for MatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchError::Opponent(spec) => write!(f, "{spec} isn't a level from 1 to {MAX_LEVEL}, engine or the path of an engine"),
+            MatchError::NoEngine => write!(f, "no engine to play, use config set engine_path"),
+            MatchError::Game(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+// A player of the match and the name their games are recorded under.
+pub struct MatchPlayer {
+    name: String,
+    player: Player,
+}
+
+impl MatchPlayer {
+    // A level of the built-in engine, "engine" for the configured engine, or an engine's path.
+    pub fn from_spec(spec: &str, config: &ChessConfig) -> Result<MatchPlayer, MatchError> {
+        if let Ok(level) = spec.parse::<u32>() {
+            if !(1..=MAX_LEVEL).contains(&level) {
+                return Err(MatchError::Opponent(spec.to_string()));
+            }
+            return Ok(MatchPlayer { name: format!("Rust Chess level {level}"), player: Player::BuiltIn(level) });
+        }
+        let path = match spec {
+            "engine" => config.get_engine_path().cloned().ok_or(MatchError::NoEngine)?,
+            _ if Path::new(spec).is_file() => PathBuf::from(spec),
+            _ => return Err(MatchError::Opponent(spec.to_string())),
+        };
+        let engine = UciEngine::start(&path).map_err(|e| MatchError::Game(TournamentError::Engine(spec.to_string(), e)))?;
+        let name = engine.get_name().clone();
+        Ok(MatchPlayer { name, player: Player::Uci(engine) })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_player_mut(&mut self) -> &mut Player {
+        &mut self.player
+    }
+
+    pub fn human(config: &ChessConfig) -> MatchPlayer {
+        let name = config.get_player_name().cloned().unwrap_or_else(|| String::from("Player"));
+        MatchPlayer { name, player: Player::Human }
+    }
+}
+
+// The points of each player, the first player of the match first.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchScore {
+    pub first: f64,
+    pub second: f64,
+}
+
+impl MatchScore {
+    fn add(&mut self, result: PgnResult, first_white: bool) {
+        let (white, black) = match result {
+            PgnResult::WhiteWin => (1.0, 0.0),
+            PgnResult::BlackWin => (0.0, 1.0),
+            PgnResult::Draw => (0.5, 0.5),
+            PgnResult::Unknown => (0.0, 0.0),
+        };
+        let (first, second) = if first_white { (white, black) } else { (black, white) };
+        self.first += first;
+        self.second += second;
+    }
+}
+
+// Plays the games of the match, the first player having White in the odd games.
+pub fn run_match(
+    players: [&mut MatchPlayer; 2],
+    games: u32,
+    movetime: Duration,
+    rules: AdjudicationRules,
+    console: &mut dyn TournamentConsole,
+    archive_dir: Option<&Path>,
+    out: Option<&Path>,
+) -> Result<MatchScore, MatchError> {
+    let [first, second] = players;
+    let names = [first.name.clone(), second.name.clone()];
+    let event = format!("{} - {} match", names[0], names[1]);
+    let mut score = MatchScore::default();
+    let mut records = Vec::new();
+    for number in 1..=games {
+        let first_white = number % 2 == 1;
+        let (white, black) = if first_white { (&mut *first, &mut *second) } else { (&mut *second, &mut *first) };
+        console.say(&format!("Game {number} of {games}: {} - {}", white.name, black.name));
+        let (mut game, reason) = play_game((&mut white.player, &white.name), (&mut black.player, &black.name), &[], movetime, rules, console)
+            .map_err(MatchError::Game)?;
+        let result = *game.get_result();
+        score.add(result, first_white);
+        console.say(&format!("{} {result} {}, by {reason}. Score: {} {} - {} {}",
+            white.name, black.name, names[0], score.first, score.second, names[1]));
+
+        game.set_event(event.clone());
+        game.set_date(PgnDate::now());
+        game.set_round(PgnRound::Known(vec![number]));
+        game.set_white(white.name.clone());
+        game.set_black(black.name.clone());
+        game.set_result_comment(String::from(reason));
+        if let Some(dir) = archive_dir {
+            if let Err(e) = archive_pgn_game(dir, &game) {
+                console.say(&format!("Could not archive the game to {}: {e}.", dir.display()));
+            }
+        }
+        records.push(game.to_string());
+        if let Some(path) = out {
+            fs::write(path, records.join("\n"))
+                .map_err(|e| MatchError::Game(TournamentError::Io(path.to_path_buf(), e)))?;
+        }
+    }
+    let summary = match score.first.partial_cmp(&score.second) {
+        Some(std::cmp::Ordering::Greater) => format!("{} wins the match {} - {}.", names[0], score.first, score.second),
+        Some(std::cmp::Ordering::Less) => format!("{} wins the match {} - {}.", names[1], score.second, score.first),
+        _ => format!("The match is drawn {} - {}.", score.first, score.second),
+    };
+    console.say(&summary);
+    Ok(score)
+}
+
+pub fn match_main(args: &ChessArgs, match_args: &MatchArgs) {
+    if let Err(e) = run(args, match_args) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &ChessArgs, match_args: &MatchArgs) -> Result<(), MatchError> {
+    let config = args.config.clone()
+        .or_else(ChessConfig::default_path)
+        .and_then(|p| ChessConfig::load(&p).ok())
+        .unwrap_or_default();
+    let mut first = match &match_args.against {
+        Some(spec) => MatchPlayer::from_spec(spec, &config)?,
+        None => MatchPlayer::human(&config),
+    };
+    let mut second = MatchPlayer::from_spec(&match_args.opponent, &config)?;
+    let archive_dir = config.get_archive_dir().cloned().or_else(ChessConfig::default_archive_dir);
+    let movetime = Duration::from_millis(match_args.movetime);
+    run_match([&mut first, &mut second], match_args.games, movetime, AdjudicationRules::from_config(&config), &mut Terminal, archive_dir.as_deref(), match_args.out.as_deref())?;
+    Ok(())
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_match {
+    use super::*;
+    use crate::chess_pgn::parse_pgn_games;
+
+    struct Script {
+        lines: Vec<String>,
+        said: Vec<String>,
+    }
+
+    impl TournamentConsole for Script {
+        fn say(&mut self, text: &str) {
+            self.said.push(String::from(text));
+        }
+
+        fn read_line(&mut self) -> Option<String> {
+            (!self.lines.is_empty()).then(|| self.lines.remove(0))
+        }
+    }
+
+    #[test]
+    pub fn matches_alternate_colors_and_keep_score() {
+        let dir = std::env::temp_dir().join(format!("rust_chess_match_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = ChessConfig::new();
+        let mut human = MatchPlayer::human(&config);
+        let mut engine = MatchPlayer::from_spec("1", &config).unwrap();
+        let mut console = Script { lines: vec![String::from("resign"), String::from("resign")], said: Vec::new() };
+        let score = run_match([&mut human, &mut engine], 2, Duration::from_millis(10), AdjudicationRules::default(), &mut console, Some(&dir), None).unwrap();
+        assert_eq!(score, MatchScore { first: 0.0, second: 2.0 });
+        assert_eq!(console.said[0], "Game 1 of 2: Player - Rust Chess level 1");
+        assert!(console.said.contains(&String::from("Player 0-1 Rust Chess level 1, by resignation. Score: Player 0 - 1 Rust Chess level 1")));
+        assert!(console.said.contains(&String::from("Game 2 of 2: Rust Chess level 1 - Player")));
+        assert_eq!(console.said.last().unwrap(), "Rust Chess level 1 wins the match 2 - 0.");
+
+        let archived = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        let games = parse_pgn_games(&fs::read_to_string(&archived).unwrap()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].get_white(), "Rust Chess level 1");
+        assert_eq!(games[1].get_result(), &PgnResult::WhiteWin);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(matches!(MatchPlayer::from_spec("7", &config), Err(MatchError::Opponent(_))));
+        assert!(matches!(MatchPlayer::from_spec("engine", &config), Err(MatchError::NoEngine)));
+    }
+}
@@ -24,10 +24,24 @@ f3 Bc8 34. Kf2 Bf5 35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5
 40. Rd6 Kc5 41. Ra6 Nf2 42. g4 Bd3 43. Re6 1/2-1/2
 */
 
-use std::{fmt::Display, num::ParseIntError};
+use std::{
+    fmt::Display,
+    fs,
+    io,
+    num::ParseIntError,
+    path::{Path, PathBuf},
+};
 use crate::chess_common::*;
+use crate::chess_core::Board;
 use time::OffsetDateTime;
 
+mod parser;
+pub use parser::*;
+mod canonical;
+pub use canonical::*;
+mod latex;
+
+#[derive(Clone)]
 pub struct PgnGame {
     // Required tag pairs
     event: PgnTagPair<String>,
@@ -38,8 +52,14 @@ pub struct PgnGame {
     black: PgnTagPair<String>,
     result: PgnTagPair<PgnResult>,
 
+    // Any tag pairs beyond the seven required ones, in the order they were added.
+    other_tags: Vec<PgnTagPair<String>>,
+
     // Move text
-    moves: MoveList
+    moves: MoveList,
+
+    // Comment placed before the game termination marker, e.g. the reason for an adjudication.
+    result_comment: Option<String>,
 }
 
 impl Display for PgnGame {
@@ -54,36 +74,52 @@ impl Display for PgnGame {
         output += format!("{}\n", self.white).as_str();
         output += format!("{}\n", self.black).as_str();
         output += format!("{}\n", self.result).as_str();
+        for tag in &self.other_tags {
+            output += format!("{}\n", tag).as_str();
+        }
 
         output += "\n";
 
         // Show move list
         output += self.moves.to_string().as_str();
 
-        // Add result at the end of move list
-        let lines = output.split("\n").last();
+        // Add result at the end of move list, preceded by the result comment if there is one.
+        let mut termination = String::new();
+        if let Some(comment) = &self.result_comment {
+            termination += format!("{{{}}} ", comment).as_str();
+        }
+        termination += self.result.get_value().to_string().as_str();
+        let lines = output.split('\n').next_back();
         if let Some(last_line) = lines {
-            if last_line.len() + self.result.to_string().len() >= 80 {
+            if last_line.len() + termination.len() >= 80 {
                 output += "\n";
             }
-            output += self.result.get_value().to_string().as_str();
+            output += termination.as_str();
         }
 
         write!(f, "{}", output)
     }
 }
 
+impl Default for PgnGame {
+    fn default() -> PgnGame {
+        PgnGame::new()
+    }
+}
+
 impl PgnGame {
     pub fn new() -> PgnGame {
         PgnGame {
-            event: PgnTagPair::new(String::from("Event"), String::new()),
-            site: PgnTagPair::new(String::from("Site"), String::new()),
+            event: PgnTagPair::new(String::from("Event"), String::from("?")),
+            site: PgnTagPair::new(String::from("Site"), String::from("?")),
             date: PgnTagPair::new(String::from("Date"), PgnDate::now()),
             round: PgnTagPair::new(String::from("Round"), PgnRound::Unknown),
-            white: PgnTagPair::new(String::from("White"), String::new()),
-            black: PgnTagPair::new(String::from("Black"), String::new()),
+            white: PgnTagPair::new(String::from("White"), String::from("?")),
+            black: PgnTagPair::new(String::from("Black"), String::from("?")),
             result: PgnTagPair::new(String::from("Result"), PgnResult::Unknown),
+            other_tags: Vec::new(),
             moves: MoveList::new(),
+            result_comment: None,
         }
     }
 
@@ -143,19 +179,190 @@ impl PgnGame {
         self.result.get_value()
     }
 
+    pub fn set_result_comment(&mut self, comment: String) {
+        self.result_comment = Some(comment);
+    }
+
+    pub fn clear_result_comment(&mut self) {
+        self.result_comment = None;
+    }
+
+    pub fn get_result_comment(&self) -> Option<&String> {
+        self.result_comment.as_ref()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        *self.get_result() != PgnResult::Unknown
+    }
+
+    // Sets any tag by name. Values of the typed required tags (Date, Round, Result) that
+    // can't be read are stored as unknown.
+    pub fn set_tag(&mut self, name: &str, value: String) {
+        match name {
+            "Event" => self.set_event(value),
+            "Site" => self.set_site(value),
+            "Date" => self.set_date(PgnDate::from(&value).unwrap_or(PgnDate::new(None, None, None))),
+            "Round" => self.set_round(PgnRound::from(&value).unwrap_or(PgnRound::Unknown)),
+            "White" => self.set_white(value),
+            "Black" => self.set_black(value),
+            "Result" => self.set_result(PgnResult::from(&value).unwrap_or(PgnResult::Unknown)),
+            _ => {
+                match self.other_tags.iter_mut().find(|t| t.get_name() == name) {
+                    Some(tag) => tag.set_value(value),
+                    None => self.other_tags.push(PgnTagPair::new(String::from(name), value)),
+                }
+            }
+        }
+    }
+
+    // Gets the value of any tag by name as it would be written in the PGN tag pair.
+    pub fn get_tag(&self, name: &str) -> Option<String> {
+        match name {
+            "Event" => Some(self.get_event().clone()),
+            "Site" => Some(self.get_site().clone()),
+            "Date" => Some(self.get_date().to_string()),
+            "Round" => Some(self.get_round().to_string()),
+            "White" => Some(self.get_white().clone()),
+            "Black" => Some(self.get_black().clone()),
+            "Result" => Some(self.get_result().to_string()),
+            _ => self.other_tags.iter().find(|t| t.get_name() == name).map(|t| t.get_value().clone()),
+        }
+    }
+
+    pub fn get_other_tags(&self) -> &Vec<PgnTagPair<String>> {
+        &self.other_tags
+    }
+
     pub fn push_move(&mut self, new_move: ChessMove) {
         self.moves.push_move(new_move);
     }
 
+    pub fn push_annotated_move(&mut self, new_move: ChessMove, annotation: PgnAnnotation) {
+        self.moves.push_annotated_move(new_move, annotation);
+    }
+
     pub fn pop_move(&mut self) -> Option<ChessMove> {
         self.moves.pop_move()
     }
 
+    // Removes every move, keeping any comments that come before the first move.
+    pub fn clear_moves(&mut self) {
+        self.moves.moves.clear();
+    }
+
+    pub fn add_initial_comment(&mut self, comment: String) {
+        self.moves.initial_comments.push(comment);
+    }
+
+    pub fn get_initial_comments(&self) -> &Vec<String> {
+        &self.moves.initial_comments
+    }
+
+    // Removes every comment of the game, the result comment included.
+    pub fn strip_comments(&mut self) {
+        self.moves.initial_comments.clear();
+        self.moves.for_each_annotation(|a| a.comments.clear());
+        self.result_comment = None;
+    }
+
+    pub fn strip_variations(&mut self) {
+        self.moves.for_each_annotation(|a| a.variations.clear());
+    }
+
+    // Removes the clock commands sites and engines put in comments, e.g. [%clk 0:02:59] and
+    // [%emt 0:00:01], along with the comments left empty.
+    pub fn strip_clock_annotations(&mut self) {
+        let strip = |comments: &mut Vec<String>| {
+            for comment in comments.iter_mut() {
+                *comment = strip_clock_commands(comment);
+            }
+            comments.retain(|c| !c.is_empty());
+        };
+        strip(&mut self.moves.initial_comments);
+        self.moves.for_each_annotation(|a| strip(&mut a.comments));
+    }
+
+    // Every half move of the game in order, with its annotation.
+    pub fn get_plies(&self) -> Vec<(&ChessMove, &PgnAnnotation)> {
+        self.moves.get_plies()
+    }
+
+    pub fn get_ply_count(&self) -> usize {
+        self.moves.get_plies().len()
+    }
+
+    // The position the game starts from: the one of its FEN tag, when it was set up, or else the
+    // usual one. None when the FEN tag isn't a valid position.
+    pub fn get_starting_board(&self) -> Option<Board> {
+        match self.get_tag("FEN") {
+            Some(fen) => Board::from_fen(&fen).ok(),
+            None => Some(Board::new()),
+        }
+    }
+
+    // Replays the game from its starting position, giving each position with the move played
+    // from it. The board is only worked out as the iterator is advanced, and it ends early at a
+    // move that can't be played. A game set up from an invalid FEN gives no positions.
+    pub fn positions(&self) -> impl Iterator<Item = (Board, &ChessMove)> {
+        let mut board = self.get_starting_board();
+        self.moves.moves.iter()
+            .flat_map(|m| m.white_move.iter().chain(m.black_move.iter()))
+            .map_while(move |chess_move| {
+                let board = board.as_mut()?;
+                let board_move = board.resolve_move(chess_move).ok()?;
+                let before = board.clone();
+                board.make_move(board_move);
+                Some((before, chess_move))
+            })
+    }
+
+    // The annotation of the most recent half move, if any move has been played.
+    pub fn last_annotation_mut(&mut self) -> Option<&mut PgnAnnotation> {
+        self.moves.last_annotation_mut()
+    }
+
+    // The annotation of a half move, counted from 0.
+    pub fn get_annotation_mut(&mut self, index: usize) -> Option<&mut PgnAnnotation> {
+        self.moves.get_annotation_mut(index)
+    }
+
     pub fn get_turn(&self) -> ChessTurn {
         self.moves.get_turn()
     }
+
+    // Writes the game to a PGN file, creating any missing parent directories. An existing
+    // file is only replaced when `overwrite` is set.
+    pub fn save_to_file(&self, path: &Path, overwrite: bool) -> Result<(), PgnSaveError> {
+        if path.exists() && !overwrite {
+            return Err(PgnSaveError::FileExists(path.to_path_buf()));
+        }
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(PgnSaveError::Io)?;
+            }
+        }
+        fs::write(path, format!("{}\n", self)).map_err(PgnSaveError::Io)
+    }
+}
+
+#[derive(Debug)]
+pub enum PgnSaveError {
+    FileExists(PathBuf),
+    Io(io::Error),
 }
 
+impl Display for PgnSaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnSaveError::FileExists(path) => write!(f, "{} already exists, use --force to overwrite it", path.display()),
+            PgnSaveError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PgnSaveError {}
+
+#[derive(Clone)]
 pub struct PgnTagPair<T: Display> {
     tag_name: String,
     tag_value: T
@@ -163,7 +370,9 @@ pub struct PgnTagPair<T: Display> {
 
 impl<T: Display> Display for PgnTagPair<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{} \"{}\"]", self.tag_name, self.tag_value)
+        // Quotes and backslashes in the value are escaped with a backslash.
+        let value = self.tag_value.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+        write!(f, "[{} \"{}\"]", self.tag_name, value)
     }
 }
 
@@ -185,6 +394,7 @@ impl<T: Display> PgnTagPair<T> {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct PgnDate {
     year: Option<i32>,
     month: Option<u8>,
@@ -223,6 +433,37 @@ impl PgnDate {
         PgnDate { year, month, day }
     }
 
+    // Reads a date in the YYYY.MM.DD form, where any part may be question marks.
+    pub fn from(date_str: &str) -> Option<PgnDate> {
+        let parts: Vec<&str> = date_str.trim().split('.').collect();
+        if parts.len() != 3 {
+            return None;
+        }
+        fn part<T: std::str::FromStr>(s: &str) -> Option<Option<T>> {
+            if !s.is_empty() && s.chars().all(|c| c == '?') {
+                return Some(None);
+            }
+            s.parse::<T>().ok().map(Some)
+        }
+        Some(PgnDate {
+            year: part::<i32>(parts[0])?,
+            month: part::<u8>(parts[1])?,
+            day: part::<u8>(parts[2])?,
+        })
+    }
+
+    pub fn get_year(&self) -> Option<i32> {
+        self.year
+    }
+
+    pub fn get_month(&self) -> Option<u8> {
+        self.month
+    }
+
+    pub fn get_day(&self) -> Option<u8> {
+        self.day
+    }
+
     pub fn now() -> PgnDate {
         // Create a PGN date from the current datetime.
         let local = OffsetDateTime::now_local();
@@ -241,6 +482,8 @@ impl PgnDate {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum PgnResult {
     WhiteWin,
     BlackWin,
@@ -248,6 +491,18 @@ pub enum PgnResult {
     Unknown
 }
 
+impl PgnResult {
+    pub fn from(result_str: &str) -> Option<PgnResult> {
+        match result_str.trim() {
+            "1-0" => Some(PgnResult::WhiteWin),
+            "0-1" => Some(PgnResult::BlackWin),
+            "1/2-1/2" => Some(PgnResult::Draw),
+            "*" => Some(PgnResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
 impl Display for PgnResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let output = match self {
@@ -260,6 +515,7 @@ impl Display for PgnResult {
     }
 }
 
+#[derive(Clone)]
 pub enum PgnRound {
     Known(Vec<u32>),
     Unknown,
@@ -288,6 +544,11 @@ impl Display for PgnRound {
 
 impl PgnRound {
     pub fn from(round_str: &str) -> Result<PgnRound, ParseIntError> {
+        match round_str.trim() {
+            "?" | "" => return Ok(PgnRound::Unknown),
+            "-" => return Ok(PgnRound::Inappropriate),
+            _ => (),
+        }
         let mut rounds: Vec<u32> = Vec::new();
         for round in round_str.trim().split(".") {
             let r = round.parse::<u32>()?;
@@ -297,54 +558,59 @@ impl PgnRound {
     }
 }
 
+#[derive(Clone)]
 struct MoveList {
-    moves: Vec<PgnMove>
+    moves: Vec<PgnMove>,
+
+    // Comments that come before the first move.
+    initial_comments: Vec<String>,
 }
 
 impl Display for MoveList {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::new();
-        let mut new_line = String::new();
-        for i in 0..self.moves.len() {
-            let mvs = format!("{}. {}", i + 1, self.moves[i].to_string());
-            if mvs.len() + new_line.len() >= 80 {
-                let mut carriage_returned = false;
-                for token in mvs.split_whitespace() {
-                    if carriage_returned {
-                        if new_line.len() != 0 {
-                            new_line += " ";
-                        }
-                        new_line += token;
-                    }
-                    else if token.len() + new_line.len() < 80 {
-                        new_line += token;
-                    }
-                    else {
-                        new_line += "\n";
-                        carriage_returned = true;
-                        output += new_line.as_str();
-                        new_line.clear();
-                    }
-                }
-            }
-            else {
-                new_line += mvs.as_str();
-                new_line += " ";
-            }
+        let mut tokens: Vec<String> = Vec::new();
+        self.push_tokens(&mut tokens, PgnTextMode::Collapsed);
+        let mut output = wrap_movetext(tokens, 79);
+        if !output.is_empty() && !output.ends_with('\n') {
+            output += " ";
         }
-        output += new_line.as_str();
         write!(f, "{}", output)
     }
 }
 
 impl MoveList {
-    pub fn new() -> MoveList {
+    fn push_tokens(&self, tokens: &mut Vec<String>, mode: PgnTextMode) {
+        let initial = PgnAnnotation { comments: self.initial_comments.clone(), ..PgnAnnotation::new() };
+        initial.push_tokens(tokens, mode);
+        for (i, m) in self.moves.iter().enumerate() {
+            tokens.push(format!("{}.", i + 1));
+            if let Some(wm) = &m.white_move {
+                tokens.push(wm.to_string());
+                m.white_annotation.push_tokens(tokens, mode);
+                if m.black_move.is_some() && m.white_annotation.has_text() {
+                    // Black's move number is repeated after commentary on White's move.
+                    tokens.push(format!("{}...", i + 1));
+                }
+            }
+            if let Some(bm) = &m.black_move {
+                tokens.push(bm.to_string());
+                m.black_annotation.push_tokens(tokens, mode);
+            }
+        }
+    }
+
+    fn new() -> MoveList {
         MoveList {
             moves: Vec::new(),
+            initial_comments: Vec::new(),
         }
     }
 
-    pub fn push_move(&mut self, new_move: ChessMove) {
+    fn push_move(&mut self, new_move: ChessMove) {
+        self.push_annotated_move(new_move, PgnAnnotation::new());
+    }
+
+    fn push_annotated_move(&mut self, new_move: ChessMove, annotation: PgnAnnotation) {
         if self.moves.is_empty() {
             self.moves.push(PgnMove::new())
         }
@@ -353,32 +619,71 @@ impl MoveList {
             match m.get_state() {
                 PgnMoveState::MoveComplete => {
                     let mut new_pgn_move = PgnMove::new();
-                    new_pgn_move.add_move(new_move);
+                    new_pgn_move.add_move(new_move, annotation);
                     self.moves.push(new_pgn_move);
                 }
                 _ => {
-                    m.add_move(new_move);
+                    m.add_move(new_move, annotation);
                 }
             }
         }
     }
 
-    pub fn pop_move(&mut self) -> Option<ChessMove> {
+    fn pop_move(&mut self) -> Option<ChessMove> {
         let mut ret_move = None;
 
-        while ret_move.is_none() && self.moves.len() > 0 {
+        while ret_move.is_none() && !self.moves.is_empty() {
             let index = self.moves.len() - 1;
             let pgn_move = &mut self.moves[index];
             ret_move = pgn_move.remove_move();
-            if ret_move.is_none() {
+            // Drop the move number once both of its moves are gone.
+            if let PgnMoveState::WhiteToMove = pgn_move.get_state() {
                 self.moves.pop();
             }
         }
 
-        return ret_move;
+        ret_move
     }
 
-    pub fn get_turn(&self) -> ChessTurn {
+    fn get_plies(&self) -> Vec<(&ChessMove, &PgnAnnotation)> {
+        let mut plies = Vec::new();
+        for m in &self.moves {
+            if let Some(wm) = &m.white_move {
+                plies.push((wm, &m.white_annotation));
+            }
+            if let Some(bm) = &m.black_move {
+                plies.push((bm, &m.black_annotation));
+            }
+        }
+        plies
+    }
+
+    fn for_each_annotation(&mut self, mut edit: impl FnMut(&mut PgnAnnotation)) {
+        for m in &mut self.moves {
+            edit(&mut m.white_annotation);
+            edit(&mut m.black_annotation);
+        }
+    }
+
+    fn get_annotation_mut(&mut self, index: usize) -> Option<&mut PgnAnnotation> {
+        let m = self.moves.get_mut(index / 2)?;
+        match (index % 2, &m.white_move, &m.black_move) {
+            (0, Some(_), _) => Some(&mut m.white_annotation),
+            (1, _, Some(_)) => Some(&mut m.black_annotation),
+            _ => None,
+        }
+    }
+
+    fn last_annotation_mut(&mut self) -> Option<&mut PgnAnnotation> {
+        let m = self.moves.last_mut()?;
+        match m.get_state() {
+            PgnMoveState::MoveComplete => Some(&mut m.black_annotation),
+            PgnMoveState::BlackToMove => Some(&mut m.white_annotation),
+            PgnMoveState::WhiteToMove => None,
+        }
+    }
+
+    fn get_turn(&self) -> ChessTurn {
         if let Some(m) = self.moves.last() {
             let state = m.get_state();
             match state {
@@ -393,19 +698,198 @@ impl MoveList {
                 }
             }
         }
-        return ChessTurn::WhiteToMove;
+        ChessTurn::WhiteToMove
     }
 }
 
-pub enum PgnMoveState {
+enum PgnMoveState {
     WhiteToMove,
     BlackToMove,
     MoveComplete,
 }
 
+// Commentary attached to a single half move: numeric annotation glyphs ($1 for "!", $2 for
+// "?", ...), comments, and recursive annotation variations kept as written.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PgnAnnotation {
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    nags: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    comments: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    variations: Vec<String>,
+}
+
+impl PgnAnnotation {
+    pub fn new() -> PgnAnnotation {
+        PgnAnnotation::default()
+    }
+
+    pub fn add_nag(&mut self, nag: u8) {
+        if !self.nags.contains(&nag) {
+            self.nags.push(nag);
+        }
+    }
+
+    pub fn add_comment(&mut self, comment: String) {
+        self.comments.push(comment);
+    }
+
+    pub fn add_variation(&mut self, variation: String) {
+        self.variations.push(variation);
+    }
+
+    pub fn remove_nag(&mut self, nag: u8) {
+        self.nags.retain(|n| *n != nag);
+    }
+
+    pub fn remove_variation(&mut self, index: usize) -> Option<String> {
+        (index < self.variations.len()).then(|| self.variations.remove(index))
+    }
+
+    pub fn take_variations(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.variations)
+    }
+
+    pub fn get_nags(&self) -> &Vec<u8> {
+        &self.nags
+    }
+
+    pub fn get_comments(&self) -> &Vec<String> {
+        &self.comments
+    }
+
+    pub fn get_variations(&self) -> &Vec<String> {
+        &self.variations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nags.is_empty() && !self.has_text()
+    }
+
+    // Whether the annotation has comments or variations, after which a move number is needed
+    // again to say whose move follows.
+    fn has_text(&self) -> bool {
+        !self.comments.is_empty() || !self.variations.is_empty()
+    }
+
+    // Adds the annotation's movetext tokens.
+    fn push_tokens(&self, tokens: &mut Vec<String>, mode: PgnTextMode) {
+        for nag in &self.nags {
+            tokens.push(format!("${}", nag));
+        }
+        for comment in &self.comments {
+            if comment.contains('}') {
+                // Braces can't hold it, a rest-of-line comment can.
+                tokens.push(format!("; {}", comment.split_whitespace().collect::<Vec<&str>>().join(" ")));
+            }
+            else {
+                push_text(tokens, '{', comment, '}', mode);
+            }
+        }
+        for variation in &self.variations {
+            push_text(tokens, '(', variation, ')', mode);
+        }
+    }
+}
+
+// A comment without its [%clk ...] and [%emt ...] commands, trimmed.
+fn strip_clock_commands(comment: &str) -> String {
+    let mut text = String::from(comment);
+    for command in ["[%clk", "[%emt"] {
+        while let Some(start) = text.find(command) {
+            let end = text[start..].find(']').map_or(text.len(), |i| start + i + 1);
+            text.replace_range(start..end, "");
+        }
+    }
+    String::from(text.trim())
+}
+
+// Writes moves with their annotations as the text of a variation, the first being the half
+// move `first_ply` counted from 1, e.g. "2... Nc6 3. Bb5 a6".
+pub fn format_variation(first_ply: usize, plies: &[(ChessMove, PgnAnnotation)]) -> String {
+    let mut tokens = Vec::new();
+    let mut after_text = true;
+    for (i, (chess_move, annotation)) in plies.iter().enumerate() {
+        let ply = first_ply + i;
+        if ply % 2 == 1 {
+            tokens.push(format!("{}.", ply.div_ceil(2)));
+        }
+        else if after_text {
+            tokens.push(format!("{}...", ply / 2));
+        }
+        tokens.push(chess_move.to_string());
+        annotation.push_tokens(&mut tokens, PgnTextMode::Collapsed);
+        after_text = annotation.has_text();
+    }
+    tokens.join(" ")
+}
+
+// How the text of comments and variations, which isn't parsed into moves, is read and written.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PgnTextMode {
+    // Runs of whitespace become single spaces and the text is wrapped with the rest of the
+    // movetext.
+    #[default]
+    Collapsed,
+    // The text is kept exactly as written, line breaks included.
+    Preserved,
+}
+
+// Adds a comment or variation in its brackets, split into words when its whitespace is
+// collapsed so it can be wrapped like the rest of the movetext.
+fn push_text(tokens: &mut Vec<String>, open: char, text: &str, close: char, mode: PgnTextMode) {
+    let mut words: Vec<String> = match mode {
+        PgnTextMode::Collapsed => text.split_whitespace().map(String::from).collect(),
+        PgnTextMode::Preserved => vec![String::from(text)],
+    };
+    if words.is_empty() {
+        words.push(String::new());
+    }
+    words[0].insert(0, open);
+    if let Some(last) = words.last_mut() {
+        last.push(close);
+    }
+    tokens.extend(words);
+}
+
+// Joins movetext tokens into lines of at most `width` characters, breaking between tokens, or
+// into one line for a width of 0. A rest-of-line comment (;) always ends its line, and a token
+// written over several lines is measured by its first and last lines.
+fn wrap_movetext(tokens: Vec<String>, width: usize) -> String {
+    let mut output = String::new();
+    let mut line_len = 0;
+    let mut line_ended = false;
+    for token in tokens {
+        let first_len = token.split('\n').next().map_or(0, |l| l.len());
+        if line_ended || (width > 0 && line_len > 0 && line_len + 1 + first_len > width) {
+            output += "\n";
+            line_len = 0;
+        }
+        else if line_len > 0 {
+            output += " ";
+            line_len += 1;
+        }
+        output += token.as_str();
+        line_len = match token.rfind('\n') {
+            Some(i) => token.len() - i - 1,
+            None => line_len + token.len(),
+        };
+        line_ended = token.starts_with(';');
+    }
+    if line_ended {
+        output += "\n";
+    }
+    output
+}
+
+#[derive(Clone)]
 struct PgnMove {
     white_move: Option<ChessMove>,
     black_move: Option<ChessMove>,
+    white_annotation: PgnAnnotation,
+    black_annotation: PgnAnnotation,
 }
 
 impl Display for PgnMove {
@@ -413,8 +897,8 @@ impl Display for PgnMove {
         let mut output = String::new();
         if let Some(wm) = &self.white_move {
             output += wm.to_string().as_str();
-            output += " ";
             if let Some(bm) = &self.black_move {
+                output += " ";
                 output += bm.to_string().as_str();
             }
         }
@@ -423,50 +907,59 @@ impl Display for PgnMove {
 }
 
 impl PgnMove {
-    pub fn new() -> PgnMove {
-        PgnMove { white_move: None, black_move: None }
+    fn new() -> PgnMove {
+        PgnMove {
+            white_move: None,
+            black_move: None,
+            white_annotation: PgnAnnotation::new(),
+            black_annotation: PgnAnnotation::new(),
+        }
     }
 
-    pub fn get_state(&self) -> PgnMoveState {
+    fn get_state(&self) -> PgnMoveState {
         if self.white_move.is_none() {
-            return PgnMoveState::WhiteToMove;
+            PgnMoveState::WhiteToMove
         }
         else if self.black_move.is_none() {
-            return PgnMoveState::BlackToMove;
+            PgnMoveState::BlackToMove
         }
         else {
-            return PgnMoveState::MoveComplete;
+            PgnMoveState::MoveComplete
         }
     }
 
-    pub fn add_move(&mut self, new_move: ChessMove) -> bool {
+    fn add_move(&mut self, new_move: ChessMove, annotation: PgnAnnotation) -> bool {
         if self.white_move.is_none() {
             self.white_move = Some(new_move);
+            self.white_annotation = annotation;
         }
         else if self.black_move.is_none() {
             self.black_move = Some(new_move);
+            self.black_annotation = annotation;
         }
         else {
             return false;
         }
-        return true;
+        true
     }
 
-    pub fn remove_move(&mut self) -> Option<ChessMove> {
+    fn remove_move(&mut self) -> Option<ChessMove> {
         let mut temp: Option<ChessMove> = None;
         if let Some(m) = &self.black_move {
             temp = Some(m.clone());
             self.black_move = None;
+            self.black_annotation = PgnAnnotation::new();
         }
         else if let Some(m) = &self.white_move {
             temp = Some(m.clone());
             self.white_move = None;
+            self.white_annotation = PgnAnnotation::new();
         }
-        return temp;
+        temp
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ChessMove {
     origin: Option<ChessCoordinate>,
     destination: Option<ChessCoordinate>,
@@ -475,7 +968,7 @@ pub struct ChessMove {
     promotion: Option<ChessPiece>,
     is_capture: bool,
     is_check: bool,
-    is_check_mate: bool,
+    is_checkmate: bool,
 }
 
 impl Display for ChessMove {
@@ -539,7 +1032,7 @@ impl Display for ChessMove {
         }
 
         // Show check & check mate markers.
-        if self.is_check_mate {
+        if self.is_checkmate {
             output += "#"
         }
         else if self.is_check {
@@ -551,12 +1044,13 @@ impl Display for ChessMove {
 }
 
 impl ChessMove {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> ChessMoveBuilder {
         ChessMoveBuilder::new()
     }
 
     pub fn from(pgn_move_string: &str) -> Result<ChessMove, ChessMoveBuildError> {
-        if pgn_move_string.len() == 0 {
+        if pgn_move_string.is_empty() {
             return Err(ChessMoveBuildError::MissingMoveData);
         }
         if !pgn_move_string.is_ascii() {
@@ -610,12 +1104,14 @@ impl ChessMove {
                                 .set_castle(ChessCastle::QueensideCastle)
                                 .set_moving_piece(ChessPiece::King);
                             phase = MoveBuildPhase::Checks;
+                            continue; // let a check marker continue into the next phase.
                         }
                         else if castle_count == 2 {
                             new_move = new_move
                                 .set_castle(ChessCastle::KingsideCastle)
                                 .set_moving_piece(ChessPiece::King);
                             phase = MoveBuildPhase::Checks;
+                            continue; // let a check marker continue into the next phase.
                         }
                         else if castle_count == 0 && current_char.is_some() {
                             phase = MoveBuildPhase::PieceType;
@@ -765,7 +1261,7 @@ impl ChessMove {
                             new_move = new_move.set_is_check(true);
                         }
                         else if c == '#' {
-                            new_move = new_move.set_is_check_mate(true);
+                            new_move = new_move.set_is_checkmate(true);
                         }
                         else {
                             return Err(ChessMoveBuildError::InvalidMove);
@@ -784,35 +1280,35 @@ impl ChessMove {
 
     pub fn get_origin(&self) -> Option<&ChessCoordinate> {
         if let Some(o) = &self.origin {
-            return Some(&o);
+            return Some(o);
         }
         None
     }
 
     pub fn get_destination(&self) -> Option<&ChessCoordinate> {
         if let Some(d) = &self.destination {
-            return Some(&d);
+            return Some(d);
         }
         None
     }
 
     pub fn get_moving_piece(&self) -> Option<&ChessPiece> {
         if let Some(mp) = &self.moving_piece {
-            return Some(&mp);
+            return Some(mp);
         }
         None
     }
 
     pub fn get_castle(&self) -> Option<&ChessCastle> {
         if let Some(c) = &self.castle {
-            return Some(&c);
+            return Some(c);
         }
         None
     }
 
     pub fn get_promotion(&self) -> Option<&ChessPiece> {
         if let Some(p) = &self.promotion {
-            return Some(&p);
+            return Some(p);
         }
         None
     }
@@ -825,8 +1321,8 @@ impl ChessMove {
         self.is_check
     }
 
-    pub fn is_check_mate(&self) -> bool {
-        self.is_check_mate
+    pub fn is_checkmate(&self) -> bool {
+        self.is_checkmate
     }
 }
 
@@ -838,7 +1334,7 @@ pub struct ChessMoveBuilder {
     promotion: Option<ChessPiece>,
     is_capture: bool,
     is_check: bool,
-    is_check_mate: bool,
+    is_checkmate: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -850,6 +1346,27 @@ pub enum ChessMoveBuildError {
     InvalidInputFormat,
 }
 
+impl Display for ChessMoveBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            ChessMoveBuildError::InvalidMove => "the move is not written in valid SAN",
+            ChessMoveBuildError::ImpossibleMove => "the move combines markers that cannot occur together",
+            ChessMoveBuildError::MissingDestination => "the destination square needs both a file and a rank",
+            ChessMoveBuildError::MissingMoveData => "the move is missing a destination square or capturing pawn file",
+            ChessMoveBuildError::InvalidInputFormat => "the move contains non-ASCII characters",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl std::error::Error for ChessMoveBuildError {}
+
+impl Default for ChessMoveBuilder {
+    fn default() -> ChessMoveBuilder {
+        ChessMoveBuilder::new()
+    }
+}
+
 impl ChessMoveBuilder {
     pub fn new() -> ChessMoveBuilder {
         ChessMoveBuilder {
@@ -860,7 +1377,7 @@ impl ChessMoveBuilder {
             promotion: None,
             is_capture: false,
             is_check: false,
-            is_check_mate: false,
+            is_checkmate: false,
         }
     }
 
@@ -899,8 +1416,8 @@ impl ChessMoveBuilder {
         self
     }
 
-    pub fn set_is_check_mate(mut self, is_mate: bool) -> ChessMoveBuilder {
-        self.is_check_mate = is_mate;
+    pub fn set_is_checkmate(mut self, is_mate: bool) -> ChessMoveBuilder {
+        self.is_checkmate = is_mate;
         self
     }
 
@@ -910,7 +1427,7 @@ impl ChessMoveBuilder {
         //       regarding PGN written notation rules.
 
         // Can't be check and check mate at the same time.
-        if self.is_check && self.is_check_mate {
+        if self.is_check && self.is_checkmate {
             return Err(ChessMoveBuildError::ImpossibleMove);
         }
 
@@ -937,22 +1454,15 @@ impl ChessMoveBuilder {
         }
 
         // Check piece specific rules
-        if let Some(piece) = &self.moving_piece {
-            match piece {
-                ChessPiece::Pawn => {
-                    if self.is_capture {
-                        if let Some(orig) = &self.origin {
-                            // If is a pawn capture, must contain the origin file.
-                            if orig.get_file().is_none() {
-                                return Err(ChessMoveBuildError::MissingMoveData);
-                            }
-                        }
-                        else {
-                            return Err(ChessMoveBuildError::MissingMoveData);
-                        }
-                    }
-                },
-                _ => ()
+        if self.moving_piece == Some(ChessPiece::Pawn) && self.is_capture {
+            if let Some(orig) = &self.origin {
+                // If is a pawn capture, must contain the origin file.
+                if orig.get_file().is_none() {
+                    return Err(ChessMoveBuildError::MissingMoveData);
+                }
+            }
+            else {
+                return Err(ChessMoveBuildError::MissingMoveData);
             }
         }
         Ok(ChessMove{
@@ -963,7 +1473,7 @@ impl ChessMoveBuilder {
             promotion: self.promotion,
             is_capture: self.is_capture,
             is_check: self.is_check,
-            is_check_mate: self.is_check_mate,
+            is_checkmate: self.is_checkmate,
         })
     }
 }
@@ -975,6 +1485,7 @@ mod test_move_parsing {
     use super::*;
 
     #[derive(Debug)]
+    #[allow(clippy::enum_variant_names)]
     enum ExpectedParameter {
         ExpectOrigin(Option<ChessCoordinate>),
         ExpectDestination(Option<ChessCoordinate>),
@@ -1073,7 +1584,7 @@ mod test_move_parsing {
                         },
                         ExpectedParameter::ExpectCheckMate(expected_value) => {
                             tested_check_mate = true;
-                            assert_eq!(mov.is_check_mate(), expected_value);
+                            assert_eq!(mov.is_checkmate(), expected_value);
                         },
                         ExpectedParameter::ExpectError(e) => {
                             panic!("Testing for error {:?} in string \"{:?}\" but error was not encountered.", e, test_str);
@@ -1097,13 +1608,13 @@ mod test_move_parsing {
                     assert!(mov.get_promotion().is_none())
                 };
                 if !tested_capture {
-                    assert_eq!(mov.is_capture(), false);
+                    assert!(!mov.is_capture());
                 }
                 if !tested_check {
-                    assert_eq!(mov.is_check(), false);
+                    assert!(!mov.is_check());
                 }
                 if !tested_check_mate {
-                    assert_eq!(mov.is_check_mate(), false);
+                    assert!(!mov.is_checkmate());
                 }
             }
             Err(resulting_error) => {
@@ -1158,6 +1669,22 @@ mod test_move_parsing {
         ]);
     }
 
+    #[test]
+    pub fn castle_with_check_passes() {
+        test_move_parser_helper("O-O+", vec![
+            ExpectedParameter::ExpectCastle(Some(ChessCastle::KingsideCastle)),
+            ExpectedParameter::ExpectMovingPiece(Some(ChessPiece::King)),
+            ExpectedParameter::ExpectCheck(true),
+            ExpectedParameter::ExpectCheckMate(false),
+        ]);
+        test_move_parser_helper("O-O-O#", vec![
+            ExpectedParameter::ExpectCastle(Some(ChessCastle::QueensideCastle)),
+            ExpectedParameter::ExpectMovingPiece(Some(ChessPiece::King)),
+            ExpectedParameter::ExpectCheck(false),
+            ExpectedParameter::ExpectCheckMate(true),
+        ]);
+    }
+
     #[test]
     pub fn invalid_castles_fails() {
 
@@ -1464,15 +1991,106 @@ mod test_move_printing {
         let mov = ChessMove::new()
             .set_destination(ChessCoordinate::new_opt(Some(ChessFile::E), Some(ChessRank::R8)))
             .set_promotion(ChessPiece::Queen)
-            .set_is_check_mate(true)
+            .set_is_checkmate(true)
             .build();
         assert_eq!(mov.unwrap().to_string(), "e8=Q#");
 
         let mov = ChessMove::new()
             .set_moving_piece(ChessPiece::Queen)
             .set_destination(ChessCoordinate::new_opt(Some(ChessFile::E), Some(ChessRank::R8)))
-            .set_is_check_mate(true)
+            .set_is_checkmate(true)
             .build();
         assert_eq!(mov.unwrap().to_string(), "Qe8#");
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test_pgn_game {
+    use super::*;
+
+    #[test]
+    pub fn result_from_string() {
+        assert_eq!(PgnResult::from("1-0"), Some(PgnResult::WhiteWin));
+        assert_eq!(PgnResult::from("0-1"), Some(PgnResult::BlackWin));
+        assert_eq!(PgnResult::from("1/2-1/2"), Some(PgnResult::Draw));
+        assert_eq!(PgnResult::from("*"), Some(PgnResult::Unknown));
+        assert_eq!(PgnResult::from("1-1"), None);
+    }
+
+    #[test]
+    pub fn adjudicated_game_ends_with_comment_and_result() {
+        let mut game = PgnGame::new();
+        game.push_move(ChessMove::from("e4").unwrap());
+        game.push_move(ChessMove::from("e5").unwrap());
+        assert!(!game.is_finished());
+
+        game.set_result(PgnResult::BlackWin);
+        game.set_result_comment(String::from("White abandoned the game"));
+        assert!(game.is_finished());
+
+        let output = game.to_string();
+        assert!(output.contains("[Result \"0-1\"]"));
+        assert!(output.ends_with("1. e4 e5 {White abandoned the game} 0-1"));
+    }
+
+    #[test]
+    pub fn save_refuses_to_overwrite_without_force() {
+        let dir = std::env::temp_dir().join(format!("rust_chess_save_test_{}", std::process::id()));
+        let path = dir.join("nested").join("game.pgn");
+        let mut game = PgnGame::new();
+        game.push_move(ChessMove::from("d4").unwrap());
+
+        assert!(game.save_to_file(&path, false).is_ok());
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.ends_with("1. d4 *\n"));
+
+        assert!(matches!(game.save_to_file(&path, false), Err(PgnSaveError::FileExists(_))));
+        assert!(game.save_to_file(&path, true).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn long_movetext_wraps_at_79_characters() {
+        let mut game = PgnGame::new();
+        for _ in 0..20 {
+            for m in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+                game.push_move(ChessMove::from(m).unwrap());
+            }
+        }
+        let output = game.to_string();
+        let movetext = output.split("\n\n").nth(1).unwrap();
+        for line in movetext.lines() {
+            assert!(line.len() <= 79, "line too long: {:?}", line);
+        }
+        let tokens: Vec<&str> = movetext.split_whitespace().collect();
+        assert_eq!(tokens.len(), 40 + 80 + 1);
+        assert_eq!(tokens[0], "1.");
+        assert_eq!(tokens[tokens.len() - 2], "Ng8");
+        assert_eq!(tokens[tokens.len() - 1], "*");
+    }
+
+    #[test]
+    pub fn positions_replay_the_game() {
+        let game = PgnGame::from("1. e4 e5 2. Nf3 Nc6 *").unwrap();
+        let positions: Vec<(Board, &ChessMove)> = game.positions().collect();
+        assert_eq!(positions.len(), 4);
+        assert_eq!(positions[0].0, Board::new());
+        assert_eq!(positions[0].1.to_string(), "e4");
+        assert_eq!(positions[3].0.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+        assert_eq!(positions[3].1.to_string(), "Nc6");
+
+        // Replaying stops at a move that can't be played.
+        let broken = PgnGame::from("1. e4 e5 2. Ke3 Nc6 *").unwrap();
+        assert_eq!(broken.positions().count(), 2);
+
+        // Games set up from a position are replayed from it.
+        let set_up = PgnGame::from("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 Kd7 2. Ke2 *").unwrap();
+        let positions: Vec<(Board, &ChessMove)> = set_up.positions().collect();
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[0].0.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(positions[2].0.to_fen(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+        let invalid = PgnGame::from("[SetUp \"1\"]\n[FEN \"8/8 w\"]\n\n1. e4 *").unwrap();
+        assert_eq!(invalid.positions().count(), 0);
+    }
+}
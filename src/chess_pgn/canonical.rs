@@ -0,0 +1,105 @@
+/*
+canonical.rs
+Canonical PGN output, for normalizing collections of games. Writing a game read back from
+canonical output gives the same bytes again: the Seven Tag Roster comes first and the other tags
+follow in ASCII order by name, tag values are escaped, the movetext is wrapped at 79 characters
+and every game ends with a line break.
+
+Comments and variations aren't parsed into moves. With PgnTextMode::Collapsed their whitespace
+is normalized like the rest of the movetext, with PgnTextMode::Preserved they are written exactly
+as they were read.
+*/
+
+use super::{parse_pgn_games_with, wrap_movetext, PgnAnnotation, PgnGame, PgnParseError, PgnTextMode};
+
+impl PgnGame {
+    // The game in canonical form. Games read with PgnTextMode::Preserved should be written with
+    // it too, to keep the text of their comments and variations.
+    pub fn to_canonical_string(&self, text_mode: PgnTextMode) -> String {
+        self.to_formatted_string(text_mode, 79)
+    }
+
+    // The game in canonical form with the movetext wrapped at another width, or kept on one line
+    // for a width of 0. Only output wrapped at the same width is stable.
+    pub fn to_formatted_string(&self, text_mode: PgnTextMode, width: usize) -> String {
+        let mut output = String::new();
+        output += format!("{}\n", self.event).as_str();
+        output += format!("{}\n", self.site).as_str();
+        output += format!("{}\n", self.date).as_str();
+        output += format!("{}\n", self.round).as_str();
+        output += format!("{}\n", self.white).as_str();
+        output += format!("{}\n", self.black).as_str();
+        output += format!("{}\n", self.result).as_str();
+        let mut other_tags: Vec<_> = self.other_tags.iter().collect();
+        other_tags.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+        for tag in other_tags {
+            output += format!("{}\n", tag).as_str();
+        }
+        output += "\n";
+
+        // The result comment is read back as a comment on the last move, so it is wrapped
+        // with the movetext rather than kept with the termination marker.
+        let mut tokens = Vec::new();
+        self.moves.push_tokens(&mut tokens, text_mode);
+        if let Some(comment) = &self.result_comment {
+            let annotation = PgnAnnotation { comments: vec![comment.clone()], ..PgnAnnotation::new() };
+            annotation.push_tokens(&mut tokens, text_mode);
+        }
+        tokens.push(self.result.get_value().to_string());
+        output += wrap_movetext(tokens, width).as_str();
+        if !output.ends_with('\n') {
+            output += "\n";
+        }
+        output
+    }
+}
+
+// Reads every game of the PGN text and writes them back in canonical form, separated by blank
+// lines. Nothing is written when a game can't be read, so a collection is never half normalized.
+pub fn normalize_pgn(pgn_str: &str, text_mode: PgnTextMode) -> Result<String, PgnParseError> {
+    let games = parse_pgn_games_with(pgn_str, text_mode)?;
+    Ok(games.iter().map(|g| g.to_canonical_string(text_mode)).collect::<Vec<String>>().join("\n"))
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_canonical {
+    use super::*;
+
+    const MESSY: &str = "[Round \"3\"]\n[White \"Tal, \\\"Misha\\\"\"]\n[Opening  \"Sicilian\"]\n[ECO \"B90\"]\n\
+        [Event \"Casual\"]\n\n\
+        {An   opening\n   comment} 1.e4 c5 2 Nf3 $1 (2.  Nc3 {the\n    closed   one}\n  Nc6) 2... d6 ; sharp } play\n\
+        3.d4 cxd4 4.Nxd4 Nf6 5.Nc3 a6 6.Be3 e5 7.Nb3 Be6 8.f3 Be7 9.Qd2 O-O 10.O-O-O Nbd7 11.g4 b5\n\
+        12.g5 b4 {lots to play for} 1/2-1/2\n\n\
+        1. d4 d5 *\n";
+
+    #[test]
+    pub fn canonical_output_is_stable() {
+        for mode in [PgnTextMode::Collapsed, PgnTextMode::Preserved] {
+            let once = normalize_pgn(MESSY, mode).unwrap();
+            assert_eq!(normalize_pgn(&once, mode).unwrap(), once, "{:?}", mode);
+            for line in once.lines() {
+                assert!(line.len() <= 79, "line too long: {:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    pub fn tags_are_ordered_and_escaped() {
+        let canonical = normalize_pgn(MESSY, PgnTextMode::Collapsed).unwrap();
+        assert!(canonical.starts_with("[Event \"Casual\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"3\"]\n\
+            [White \"Tal, \\\"Misha\\\"\"]\n[Black \"?\"]\n[Result \"1/2-1/2\"]\n[ECO \"B90\"]\n[Opening \"Sicilian\"]\n\n\
+            {An opening comment} 1. e4 c5 2. Nf3 $1 (2. Nc3 {the closed one} Nc6) 2... d6\n\
+            ; sharp } play\n3. d4 "));
+        assert!(canonical.contains(" b4 {lots to play for} 1/2-1/2\n\n[Event \"?\"]"));
+        assert!(canonical.ends_with("1. d4 d5 *\n"));
+    }
+
+    #[test]
+    pub fn preserved_text_is_written_as_read() {
+        let canonical = normalize_pgn(MESSY, PgnTextMode::Preserved).unwrap();
+        assert!(canonical.contains("{An   opening\n   comment} 1. e4 c5 2. Nf3 $1 (2.  Nc3 {the\n    closed   one}\n  Nc6) 2... d6"));
+        assert!(canonical.contains("; sharp } play\n"));
+    }
+}
@@ -0,0 +1,143 @@
+/*
+latex.rs
+LaTeX export for printed study material and club bulletins, using the xskak package, which loads
+skak. The game is typeset with \mainline, broken off for the comments, which are set as text,
+and for diagrams of chosen positions, drawn with \chessboard. Variations are left out.
+
+% Needs \usepackage{xskak} in the preamble.
+\newchessgame[white={Fischer, Robert J.},black={Spassky, Boris V.},result={1/2-1/2}]
+\noindent\textbf{Fischer, Robert J. -- Spassky, Boris V.}
+
+\mainline{1. e4 e5 2. Nf3 Nc6 3. Bb5}
+
+\chessboard
+
+\mainline{3... a6}
+*/
+
+use super::{nag_suffix_annotation, PgnGame, PgnResult};
+
+impl PgnGame {
+    // The game as a LaTeX snippet for xskak, with a diagram after each of the given half moves,
+    // counted from 1, or of the starting position for 0.
+    pub fn to_latex(&self, diagram_plies: &[usize]) -> String {
+        let mut keys = vec![
+            format!("white={{{}}}", escape_latex(self.get_white())),
+            format!("black={{{}}}", escape_latex(self.get_black())),
+            format!("result={{{}}}", self.get_result()),
+        ];
+        let mut details = Vec::new();
+        for (key, value) in [
+            ("event", self.get_event().clone()),
+            ("site", self.get_site().clone()),
+            ("date", self.get_date().to_string()),
+            ("round", self.get_round().to_string()),
+        ] {
+            // Unknown tags are left for xskak to default.
+            if value.is_empty() || value == "?" || value == "????.??.??" {
+                continue;
+            }
+            keys.push(format!("{key}={{{}}}", escape_latex(&value)));
+            if key != "round" {
+                details.push(escape_latex(&value));
+            }
+        }
+
+        let mut output = String::from("% Needs \\usepackage{xskak} in the preamble.\n");
+        output += format!("\\newchessgame[{}]\n", keys.join(",")).as_str();
+        output += format!("\\noindent\\textbf{{{} -- {}}}", escape_latex(self.get_white()), escape_latex(self.get_black())).as_str();
+        if !details.is_empty() {
+            output += format!("\\\\\n{}", details.join(", ")).as_str();
+        }
+        output += "\n";
+
+        // Moves are gathered until a comment or diagram breaks the line off.
+        let mut line: Vec<String> = Vec::new();
+        let mut paragraphs: Vec<String> = Vec::new();
+        for comment in self.get_initial_comments() {
+            paragraphs.push(escape_latex(comment));
+        }
+        if diagram_plies.contains(&0) {
+            paragraphs.push(String::from("\\chessboard"));
+        }
+        for (i, (chess_move, annotation)) in self.get_plies().into_iter().enumerate() {
+            let ply = i + 1;
+            if ply % 2 == 1 {
+                line.push(format!("{}.", ply.div_ceil(2)));
+            }
+            else if line.is_empty() {
+                line.push(format!("{}...", ply / 2));
+            }
+            let suffix = annotation.get_nags().iter().find_map(|n| nag_suffix_annotation(*n)).unwrap_or("");
+            line.push(format!("{chess_move}{suffix}"));
+
+            let comments = annotation.get_comments();
+            let diagram = diagram_plies.contains(&ply);
+            if comments.is_empty() && !diagram {
+                continue;
+            }
+            paragraphs.push(format!("\\mainline{{{}}}", line.join(" ")));
+            line.clear();
+            paragraphs.extend(comments.iter().map(|c| escape_latex(c)));
+            if diagram {
+                paragraphs.push(String::from("\\chessboard"));
+            }
+        }
+        if !line.is_empty() {
+            paragraphs.push(format!("\\mainline{{{}}}", line.join(" ")));
+        }
+        if let Some(comment) = self.get_result_comment() {
+            paragraphs.push(escape_latex(comment));
+        }
+        if *self.get_result() != PgnResult::Unknown {
+            paragraphs.push(format!("\\noindent\\textbf{{{}}}", self.get_result()));
+        }
+        for paragraph in paragraphs {
+            output += format!("\n{paragraph}\n").as_str();
+        }
+        output
+    }
+}
+
+// Text with the characters LaTeX treats specially written so they print as themselves.
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped += "\\textbackslash{}",
+            '~' => escaped += "\\textasciitilde{}",
+            '^' => escaped += "\\textasciicircum{}",
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_latex {
+    use crate::chess_pgn::parse_pgn_games;
+
+    const GAME: &str = "[Event \"Club Championship\"]\n[Site \"?\"]\n[Date \"2024.05.01\"]\n[Round \"3\"]\n\
+        [White \"Smith & Jones\"]\n[Black \"O'Neil\"]\n[Result \"1-0\"]\n\n\
+        1. e4 e5 2. Nf3 Nc6 3. Bb5 {The Spanish, 50% of my games.} 3... a6 4. Ba4 Nf6 5. O-O $1 (5. Qe2) 1-0\n";
+
+    #[test]
+    pub fn games_are_typeset_for_xskak() {
+        let game = &parse_pgn_games(GAME).unwrap()[0];
+        assert_eq!(game.to_latex(&[5, 9]), "% Needs \\usepackage{xskak} in the preamble.\n\
+            \\newchessgame[white={Smith \\& Jones},black={O'Neil},result={1-0},event={Club Championship},date={2024.05.01},round={3}]\n\
+            \\noindent\\textbf{Smith \\& Jones -- O'Neil}\\\\\nClub Championship, 2024.05.01\n\
+            \n\\mainline{1. e4 e5 2. Nf3 Nc6 3. Bb5}\n\
+            \nThe Spanish, 50\\% of my games.\n\
+            \n\\chessboard\n\
+            \n\\mainline{3... a6 4. Ba4 Nf6 5. O-O!}\n\
+            \n\\chessboard\n\
+            \n\\noindent\\textbf{1-0}\n");
+    }
+}
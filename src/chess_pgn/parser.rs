@@ -0,0 +1,505 @@
+/*
+parser.rs
+Reads PGN text (one or more games) into PgnGames.
+
+Handles the tag pair section, move numbers, SAN moves with suffix annotations (!, ?, !!, ??,
+!?, ?!), numeric annotation glyphs ($n), brace and rest-of-line comments, recursive annotation
+variations and game termination markers. Variations are kept as text and attached to the
+move they follow. The whitespace in comments and variations is collapsed, unless they are read
+with PgnTextMode::Preserved.
+*/
+
+use std::fmt::Display;
+use super::{ChessMove, ChessMoveBuildError, PgnAnnotation, PgnGame, PgnResult, PgnTextMode};
+
+#[derive(Debug, PartialEq)]
+pub struct PgnParseError {
+    line: usize,
+    kind: PgnParseErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PgnParseErrorKind {
+    NoGame,
+    InvalidTagPair,
+    UnterminatedComment,
+    UnterminatedVariation,
+    UnexpectedCharacter(char),
+    InvalidMove(String, ChessMoveBuildError),
+}
+
+impl Display for PgnParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            PgnParseErrorKind::NoGame => write!(f, "no game found"),
+            PgnParseErrorKind::InvalidTagPair => write!(f, "invalid tag pair"),
+            PgnParseErrorKind::UnterminatedComment => write!(f, "comment is never closed"),
+            PgnParseErrorKind::UnterminatedVariation => write!(f, "variation is never closed"),
+            PgnParseErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            PgnParseErrorKind::InvalidMove(san, e) => write!(f, "invalid move {}: {}", san, e),
+        }
+    }
+}
+
+impl std::error::Error for PgnParseError {}
+
+impl PgnParseError {
+    pub fn get_line(&self) -> usize {
+        self.line
+    }
+
+    pub fn get_kind(&self) -> &PgnParseErrorKind {
+        &self.kind
+    }
+}
+
+impl PgnGame {
+    // Reads the first game in the given PGN text.
+    pub fn from(pgn_str: &str) -> Result<PgnGame, PgnParseError> {
+        parse_pgn_games(pgn_str)?
+            .into_iter()
+            .next()
+            .ok_or(PgnParseError { line: 1, kind: PgnParseErrorKind::NoGame })
+    }
+}
+
+// Reads every game in the given PGN text.
+pub fn parse_pgn_games(pgn_str: &str) -> Result<Vec<PgnGame>, PgnParseError> {
+    parse_pgn_games_with(pgn_str, PgnTextMode::Collapsed)
+}
+
+// Reads every game in the given PGN text, keeping the text of comments and variations as the
+// mode says.
+pub fn parse_pgn_games_with(pgn_str: &str, text_mode: PgnTextMode) -> Result<Vec<PgnGame>, PgnParseError> {
+    let mut parser = PgnParser::new(pgn_str, text_mode);
+    let mut games = Vec::new();
+    while let Some(game) = parser.next_game()? {
+        games.push(game);
+    }
+    Ok(games)
+}
+
+struct PgnParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    at_line_start: bool,
+    text_mode: PgnTextMode,
+}
+
+// Whether a game being read has anything in it yet.
+#[derive(PartialEq)]
+enum GameProgress {
+    Empty,
+    Tags,
+    Moves,
+}
+
+impl<'a> PgnParser<'a> {
+    fn new(pgn_str: &'a str, text_mode: PgnTextMode) -> PgnParser<'a> {
+        PgnParser {
+            chars: pgn_str.chars().peekable(),
+            line: 1,
+            at_line_start: true,
+            text_mode,
+        }
+    }
+
+    // The text of a comment or variation as it is kept.
+    fn keep_text(&self, text: &str) -> String {
+        match self.text_mode {
+            PgnTextMode::Collapsed => collapse_whitespace(text),
+            PgnTextMode::Preserved => String::from(text),
+        }
+    }
+
+    fn error(&self, kind: PgnParseErrorKind) -> PgnParseError {
+        PgnParseError { line: self.line, kind }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.at_line_start = c == '\n';
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn next_game(&mut self) -> Result<Option<PgnGame>, PgnParseError> {
+        let mut game = PgnGame::new();
+        // A file's games carry their own tags, so don't invent a date for a game without one.
+        game.set_tag("Date", String::from("????.??.??"));
+        let mut progress = GameProgress::Empty;
+
+        loop {
+            // Lines starting with % are escaped from PGN processing.
+            if self.at_line_start && self.chars.peek() == Some(&'%') {
+                while let Some(c) = self.next_char() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            let c = match self.chars.peek() {
+                Some(c) => *c,
+                None => break,
+            };
+
+            match c {
+                _ if c.is_whitespace() => {
+                    self.next_char();
+                }
+                '[' => {
+                    if progress == GameProgress::Moves {
+                        // Tags of the next game after a game with no termination marker.
+                        break;
+                    }
+                    self.next_char();
+                    let (name, value) = self.read_tag_pair()?;
+                    game.set_tag(&name, value);
+                    progress = GameProgress::Tags;
+                }
+                '{' => {
+                    self.next_char();
+                    let comment = self.read_until('}', PgnParseErrorKind::UnterminatedComment)?;
+                    add_comment(&mut game, comment);
+                    progress = GameProgress::Moves;
+                }
+                ';' => {
+                    self.next_char();
+                    let mut comment = String::new();
+                    while let Some(c) = self.next_char() {
+                        if c == '\n' {
+                            break;
+                        }
+                        comment.push(c);
+                    }
+                    add_comment(&mut game, self.keep_text(&comment));
+                    progress = GameProgress::Moves;
+                }
+                '(' => {
+                    self.next_char();
+                    let variation = self.read_variation()?;
+                    if let Some(annotation) = game.last_annotation_mut() {
+                        annotation.add_variation(variation);
+                    }
+                    progress = GameProgress::Moves;
+                }
+                '$' => {
+                    self.next_char();
+                    let digits = self.read_symbol();
+                    match digits.parse::<u8>() {
+                        Ok(nag) => {
+                            if let Some(annotation) = game.last_annotation_mut() {
+                                annotation.add_nag(nag);
+                            }
+                        }
+                        Err(_) => return Err(self.error(PgnParseErrorKind::UnexpectedCharacter('$'))),
+                    }
+                    progress = GameProgress::Moves;
+                }
+                _ if is_symbol_char(c) => {
+                    let symbol = self.read_symbol();
+                    if let Some(result) = termination_marker(&symbol) {
+                        if *game.get_result() == PgnResult::Unknown {
+                            game.set_result(result);
+                        }
+                        return Ok(Some(game));
+                    }
+                    self.read_move_symbol(&mut game, &symbol)?;
+                    progress = GameProgress::Moves;
+                }
+                _ => return Err(self.error(PgnParseErrorKind::UnexpectedCharacter(c))),
+            }
+        }
+
+        match progress {
+            GameProgress::Empty => Ok(None),
+            _ => Ok(Some(game)),
+        }
+    }
+
+    // Reads the rest of a tag pair after the opening bracket.
+    fn read_tag_pair(&mut self) -> Result<(String, String), PgnParseError> {
+        let start_line = self.line;
+        let invalid = PgnParseError { line: start_line, kind: PgnParseErrorKind::InvalidTagPair };
+        self.skip_inline_whitespace();
+        let name = self.read_symbol();
+        if name.is_empty() {
+            return Err(invalid);
+        }
+        self.skip_inline_whitespace();
+        if self.next_char() != Some('"') {
+            return Err(invalid);
+        }
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some('\\') => match self.next_char() {
+                    Some(c) => value.push(c),
+                    None => return Err(invalid),
+                },
+                Some('"') => break,
+                Some('\n') | None => return Err(invalid),
+                Some(c) => value.push(c),
+            }
+        }
+        self.skip_inline_whitespace();
+        if self.next_char() != Some(']') {
+            return Err(invalid);
+        }
+        Ok((name, value))
+    }
+
+    fn read_until(&mut self, end: char, unterminated: PgnParseErrorKind) -> Result<String, PgnParseError> {
+        let start_line = self.line;
+        let mut text = String::new();
+        loop {
+            match self.next_char() {
+                Some(c) if c == end => break,
+                Some(c) => text.push(c),
+                None => return Err(PgnParseError { line: start_line, kind: unterminated }),
+            }
+        }
+        Ok(self.keep_text(&text))
+    }
+
+    // Reads a variation after its opening parenthesis, including any nested variations, and
+    // returns its text without the outer parentheses.
+    fn read_variation(&mut self) -> Result<String, PgnParseError> {
+        let start_line = self.line;
+        let mut text = String::new();
+        let mut depth = 1;
+        let mut in_comment = false;
+        loop {
+            let c = match self.next_char() {
+                Some(c) => c,
+                None => return Err(PgnParseError { line: start_line, kind: PgnParseErrorKind::UnterminatedVariation }),
+            };
+            match c {
+                '{' if !in_comment => in_comment = true,
+                '}' if in_comment => in_comment = false,
+                '(' if !in_comment => depth += 1,
+                ')' if !in_comment => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => (),
+            }
+            text.push(c);
+        }
+        Ok(self.keep_text(&text))
+    }
+
+    fn read_symbol(&mut self) -> String {
+        let mut symbol = String::new();
+        while let Some(c) = self.chars.peek() {
+            if !is_symbol_char(*c) {
+                break;
+            }
+            symbol.push(*c);
+            self.next_char();
+        }
+        symbol
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if *c == '\n' || !c.is_whitespace() {
+                break;
+            }
+            self.next_char();
+        }
+    }
+
+    // Reads a move symbol, which may carry a leading move number (1. or 1...) and trailing
+    // suffix annotations.
+    fn read_move_symbol(&mut self, game: &mut PgnGame, symbol: &str) -> Result<(), PgnParseError> {
+        let san = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+        let san = if san.len() != symbol.len() && san.starts_with('.') {
+            san.trim_start_matches('.')
+        }
+        else if san.len() != symbol.len() && san.is_empty() {
+            "" // a move number written without its period
+        }
+        else {
+            symbol.trim_start_matches('.')
+        };
+        if san.is_empty() {
+            return Ok(());
+        }
+
+        let suffix_start = san.find(['!', '?']).unwrap_or(san.len());
+        let (san, suffix) = san.split_at(suffix_start);
+        let san = match san {
+            "0-0" => "O-O",
+            "0-0-0" => "O-O-O",
+            _ => san,
+        };
+
+        let chess_move = ChessMove::from(san)
+            .map_err(|e| self.error(PgnParseErrorKind::InvalidMove(String::from(symbol), e)))?;
+        let mut annotation = PgnAnnotation::new();
+        if let Some(nag) = suffix_annotation_nag(suffix) {
+            annotation.add_nag(nag);
+        }
+        game.push_annotated_move(chess_move, annotation);
+        Ok(())
+    }
+}
+
+fn add_comment(game: &mut PgnGame, comment: String) {
+    match game.last_annotation_mut() {
+        Some(annotation) => annotation.add_comment(comment),
+        None => game.add_initial_comment(comment),
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_+#=:-/*.!?".contains(c)
+}
+
+fn termination_marker(symbol: &str) -> Option<PgnResult> {
+    match symbol {
+        "1-0" | "0-1" | "1/2-1/2" | "*" => PgnResult::from(symbol),
+        _ => None,
+    }
+}
+
+// The NAG equivalent of a move suffix annotation.
+pub fn suffix_annotation_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+// The move suffix annotation of a NAG, the reverse of suffix_annotation_nag.
+pub fn nag_suffix_annotation(nag: u8) -> Option<&'static str> {
+    match nag {
+        1 => Some("!"),
+        2 => Some("?"),
+        3 => Some("!!"),
+        4 => Some("??"),
+        5 => Some("!?"),
+        6 => Some("?!"),
+        _ => None,
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_pgn_parsing {
+    use super::*;
+    use crate::chess_pgn::PgnRound;
+
+    const FISCHER_SPASSKY: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.} 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3
+O-O 9. h3 Nb8 10. d4 Nbd7 11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15.
+Nb1 h6 16. Bh4 c5 17. dxe5 Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21.
+Nc4 Nxc4 22. Bxc4 Nb6 23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7
+27. Qe3 Qg5 28. Qxg5 hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33.
+f3 Bc8 34. Kf2 Bf5 35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5
+40. Rd6 Kc5 41. Ra6 Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+"#;
+
+    #[test]
+    pub fn parses_tags_and_moves() {
+        let game = PgnGame::from(FISCHER_SPASSKY).unwrap();
+        assert_eq!(game.get_event(), "F/S Return Match");
+        assert_eq!(game.get_white(), "Fischer, Robert J.");
+        assert_eq!(game.get_date().to_string(), "1992.11.04");
+        assert!(matches!(game.get_round(), PgnRound::Known(r) if r == &vec![29]));
+        assert_eq!(*game.get_result(), PgnResult::Draw);
+        assert_eq!(game.get_ply_count(), 85);
+
+        let plies = game.get_plies();
+        assert_eq!(plies[5].0.to_string(), "a6");
+        assert_eq!(plies[5].1.get_comments(), &vec![String::from("This opening is called the Ruy Lopez.")]);
+        assert_eq!(plies[84].0.to_string(), "Re6");
+    }
+
+    #[test]
+    pub fn parses_annotations() {
+        let game = PgnGame::from("1. e4! e5?! $14 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) Nc6 ; main line\n3. Bb5 *").unwrap();
+        let plies = game.get_plies();
+        assert_eq!(plies[0].1.get_nags(), &vec![1]);
+        assert_eq!(plies[1].1.get_nags(), &vec![6, 14]);
+        assert_eq!(plies[2].1.get_variations(), &vec![String::from("2. f4 exf4 (2... d5) 3. Nf3")]);
+        assert_eq!(plies[3].1.get_comments(), &vec![String::from("main line")]);
+        assert_eq!(plies[4].0.to_string(), "Bb5");
+        assert_eq!(*game.get_result(), PgnResult::Unknown);
+    }
+
+    #[test]
+    pub fn annotated_game_round_trips() {
+        let text = "1. e4 $1 {Best by test} 1... e5 2. Nf3 (2. f4) 2... Nc6 *";
+        let game = PgnGame::from(text).unwrap();
+        let output = game.to_string();
+        let movetext = output.split("\n\n").nth(1).unwrap();
+        assert_eq!(movetext, text);
+    }
+
+    #[test]
+    pub fn parses_multiple_games() {
+        let text = "[Event \"A\"]\n\n1. e4 e5 1-0\n\n[Event \"B\"]\n\n1. d4 d5 0-1\n[Event \"C\"]\n1. c4 *";
+        let games = parse_pgn_games(text).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[1].get_event(), "B");
+        assert_eq!(*games[0].get_result(), PgnResult::WhiteWin);
+        assert_eq!(*games[1].get_result(), PgnResult::BlackWin);
+        assert_eq!(games[2].get_ply_count(), 1);
+    }
+
+    #[test]
+    pub fn keeps_unknown_tags_and_escapes() {
+        let game = PgnGame::from("[Annotator \"Someone \\\"quoted\\\"\"]\n[ECO \"C65\"]\n\n1. e4 *").unwrap();
+        assert_eq!(game.get_tag("ECO"), Some(String::from("C65")));
+        assert_eq!(game.get_tag("Annotator"), Some(String::from("Someone \"quoted\"")));
+        assert!(game.to_string().contains("[ECO \"C65\"]"));
+    }
+
+    #[test]
+    pub fn reports_error_lines() {
+        let err = PgnGame::from("[Event \"x\"]\n\n1. e4 e5\n2. Nf9 *").err().unwrap();
+        assert_eq!(err.get_line(), 4);
+        assert!(matches!(err.get_kind(), PgnParseErrorKind::InvalidMove(m, _) if m == "Nf9"));
+
+        let err = PgnGame::from("[Event \"x\n\n1. e4").err().unwrap();
+        assert_eq!(err, PgnParseError { line: 1, kind: PgnParseErrorKind::InvalidTagPair });
+
+        let err = PgnGame::from("1. e4 {never closed\n2. d4").err().unwrap();
+        assert_eq!(err, PgnParseError { line: 1, kind: PgnParseErrorKind::UnterminatedComment });
+
+        assert_eq!(PgnGame::from("  \n").err().unwrap().get_kind(), &PgnParseErrorKind::NoGame);
+    }
+
+    #[test]
+    pub fn accepts_common_variations_in_notation() {
+        let game = PgnGame::from("1.e4 e5 2 Nf3 Nc6 3.Bc4 Bc5 4.0-0 *\n% escaped line 1. d4\n").unwrap();
+        assert_eq!(game.get_ply_count(), 7);
+        assert_eq!(game.get_plies()[6].0.to_string(), "O-O");
+    }
+}
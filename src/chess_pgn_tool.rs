@@ -0,0 +1,134 @@
+/*
+chess_pgn_tool.rs
+The pgn mode of the program, for working on PGN files from the command line. pgn fmt rewrites a
+file in canonical form, optionally leaving out comments, variations and clock annotations:
+
+rust_chess pgn fmt export.pgn clean.pgn --strip-clocks
+*/
+
+use std::{
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chess_cmd::PgnAction,
+    chess_pgn::{parse_pgn_games_with, PgnParseError, PgnTextMode},
+};
+
+#[derive(Debug)]
+pub enum PgnToolError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, PgnParseError),
+    FileExists(PathBuf),
+}
+
+impl Display for PgnToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnToolError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            PgnToolError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            PgnToolError::FileExists(path) => write!(f, "{} already exists, use --force to overwrite it", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for PgnToolError {}
+
+// What pgn fmt does to the games.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormatOptions {
+    pub width: usize,
+    pub text_mode: PgnTextMode,
+    pub strip_comments: bool,
+    pub strip_variations: bool,
+    pub strip_clocks: bool,
+}
+
+pub fn pgn_main(action: &PgnAction) {
+    if let Err(e) = run(action) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(action: &PgnAction) -> Result<(), PgnToolError> {
+    match action {
+        PgnAction::Fmt { input, output, width, preserve_text, strip_comments, strip_variations, strip_clocks, force } => {
+            let options = FormatOptions {
+                width: *width,
+                text_mode: if *preserve_text { PgnTextMode::Preserved } else { PgnTextMode::Collapsed },
+                strip_comments: *strip_comments,
+                strip_variations: *strip_variations,
+                strip_clocks: *strip_clocks,
+            };
+            let formatted = format_file(input, options)?;
+            match output {
+                Some(path) => write_file(path, &formatted, *force)?,
+                None => print!("{formatted}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+// The games of a PGN file, formatted. Nothing is given when any game can't be read.
+pub fn format_file(path: &Path, options: FormatOptions) -> Result<String, PgnToolError> {
+    let text = fs::read_to_string(path).map_err(|e| PgnToolError::Io(path.to_path_buf(), e))?;
+    format_pgn(&text, options).map_err(|e| PgnToolError::Parse(path.to_path_buf(), e))
+}
+
+pub fn format_pgn(text: &str, options: FormatOptions) -> Result<String, PgnParseError> {
+    let mut games = parse_pgn_games_with(text, options.text_mode)?;
+    for game in &mut games {
+        if options.strip_comments {
+            game.strip_comments();
+        }
+        if options.strip_variations {
+            game.strip_variations();
+        }
+        if options.strip_clocks {
+            game.strip_clock_annotations();
+        }
+    }
+    Ok(games.iter().map(|g| g.to_formatted_string(options.text_mode, options.width)).collect::<Vec<String>>().join("\n"))
+}
+
+fn write_file(path: &Path, text: &str, overwrite: bool) -> Result<(), PgnToolError> {
+    if path.exists() && !overwrite {
+        return Err(PgnToolError::FileExists(path.to_path_buf()));
+    }
+    fs::write(path, text).map_err(|e| PgnToolError::Io(path.to_path_buf(), e))
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_pgn_tool {
+    use super::*;
+
+    const EXPORT: &str = "[Event \"Rated blitz game\"]\n[Site \"https://lichess.org/abcd\"]\n[White \"a\"]\n[Black \"b\"]\n\
+        [Result \"1-0\"]\n[TimeControl \"180+0\"]\n\n\
+        1. e4 { [%clk 0:03:00] } 1... e5 { [%clk 0:03:00] Solid. } 2. Qh5 (2. Nf3 Nc6) 2... Nc6 3. Bc4 Nf6?? 4. Qxf7# 1-0\n";
+
+    fn options() -> FormatOptions {
+        FormatOptions { width: 79, text_mode: PgnTextMode::Collapsed, strip_comments: false, strip_variations: false, strip_clocks: false }
+    }
+
+    #[test]
+    pub fn exports_are_cleaned_up() {
+        let clean = format_pgn(EXPORT, FormatOptions { strip_clocks: true, ..options() }).unwrap();
+        assert!(clean.starts_with("[Event \"Rated blitz game\"]\n[Site \"https://lichess.org/abcd\"]\n[Date \"????.??.??\"]\n"));
+        assert!(clean.ends_with("[TimeControl \"180+0\"]\n\n1. e4 e5 {Solid.} 2. Qh5 (2. Nf3 Nc6) 2... Nc6 3. Bc4 Nf6 $4 4. Qxf7# 1-0\n"));
+
+        let moves_only = format_pgn(EXPORT, FormatOptions { strip_comments: true, strip_variations: true, ..options() }).unwrap();
+        assert!(moves_only.ends_with("\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 $4 4. Qxf7# 1-0\n"));
+
+        let narrow = format_pgn(EXPORT, FormatOptions { width: 20, ..options() }).unwrap();
+        let movetext = narrow.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 4);
+        assert!(movetext.lines().all(|l| l.len() <= 20), "{movetext}");
+    }
+}
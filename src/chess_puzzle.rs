@@ -0,0 +1,236 @@
+/*
+chess_puzzle.rs
+Tactics puzzles, read from a CSV file in the format of the Lichess puzzle database, by default
+puzzles.csv in the data directory. Without the file a small built-in set of checkmates is used.
+Each line holds an id, the position, the moves in UCI notation and the puzzle's rating; the
+columns after those are left alone. The position is the one before the opponent's move, the
+first of the moves, and the moves after it alternate between the solver and the opponent.
+
+Example puzzles.csv:
+PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,https://lichess.org/yyznGmXs/black#34,
+*/
+
+use std::{
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chess_core::{Board, BoardMove},
+    chess_pgn::ChessMove,
+};
+
+// The built-in puzzles, in the format of the file.
+const BUILT_IN_PUZZLES: [&str; 10] = [
+    "builtin01,6k1/p4ppp/8/8/8/8/5PPP/4Q1K1 b - - 0 1,a7a6 e1e8,600",
+    "builtin02,3r2k1/5ppp/8/8/8/8/P4PPP/6K1 w - - 0 1,a2a3 d8d1,650",
+    "builtin03,rnbqkbnr/pppp1ppp/8/4p3/6P1/8/PPPPPP1P/RNBQKBNR w KQkq - 0 2,f2f3 d8h4,700",
+    "builtin04,r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 3 3,g8f6 h5f7,750",
+    "builtin05,7k/P7/6K1/1p6/8/8/8/8 b - - 0 1,b5b4 a7a8q,800",
+    "builtin06,6k1/p4p1p/8/8/3Q4/8/1B3PPP/6K1 b - - 0 1,a7a6 d4g7,850",
+    "builtin07,6rk/p5pp/8/6N1/8/8/8/6K1 b - - 0 1,a7a6 g5f7,900",
+    "builtin08,2r3k1/p4ppp/8/8/8/8/4RPPP/4R1K1 b - - 0 1,a7a6 e2e8 c8e8 e1e8,1000",
+    "builtin09,4r1k1/4rppp/8/8/8/8/P4PPP/2R3K1 w - - 0 1,a2a3 e7e1 c1e1 e8e1,1100",
+    "builtin10,6k1/8/8/8/8/3n4/P5PP/6RK w - - 0 1,a2a3 d3f2,950",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    id: String,
+    // The position the solver moves in, after the opponent's move.
+    board: Board,
+    opponent_move: ChessMove,
+    // The solver's moves and the opponent's answers between them.
+    solution: Vec<BoardMove>,
+    rating: u32,
+}
+
+#[derive(Debug)]
+pub enum PuzzleError {
+    Io(PathBuf, io::Error),
+    // A line of the file that isn't a puzzle, by its number counted from 1.
+    InvalidLine(usize, String),
+}
+
+impl Display for PuzzleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PuzzleError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            PuzzleError::InvalidLine(number, reason) => write!(f, "line {}: {}", number, reason),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl Puzzle {
+    // Reads a line of the CSV file.
+    pub fn from(line: &str) -> Result<Puzzle, String> {
+        let columns: Vec<&str> = line.split(',').collect();
+        if columns.len() < 4 {
+            return Err(String::from("expected an id, a position, moves and a rating"));
+        }
+        let start = Board::from_fen(columns[1]).map_err(|e| format!("invalid position: {e}"))?;
+        let rating = columns[3].trim().parse().map_err(|_| format!("invalid rating {}", columns[3]))?;
+        let mut board = start.clone();
+        let mut moves = Vec::new();
+        for move_str in columns[2].split_whitespace() {
+            let board_move = BoardMove::from(move_str).filter(|m| board.is_legal_move(*m))
+                .ok_or_else(|| format!("{move_str} can't be played"))?;
+            board.make_move(board_move);
+            moves.push(board_move);
+        }
+        if moves.len() < 2 {
+            return Err(String::from("expected the opponent's move and at least one move to find"));
+        }
+        // The puzzle starts after the opponent's move.
+        let mut board = start;
+        let opponent_move = board.to_chess_move(moves[0]);
+        board.make_move(moves[0]);
+        Ok(Puzzle { id: String::from(columns[0]), board, opponent_move, solution: moves.split_off(1), rating })
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_opponent_move(&self) -> &ChessMove {
+        &self.opponent_move
+    }
+
+    pub fn get_solution(&self) -> &Vec<BoardMove> {
+        &self.solution
+    }
+
+    pub fn get_rating(&self) -> u32 {
+        self.rating
+    }
+
+    // The moves of the solution, e.g. "Re8+ Rxe8 Rxe8#".
+    pub fn format_solution(&self) -> String {
+        let mut board = self.board.clone();
+        let moves: Vec<String> = self.solution.iter()
+            .map(|m| {
+                let chess_move = board.to_chess_move(*m);
+                board.make_move(*m);
+                chess_move.to_string()
+            })
+            .collect();
+        moves.join(" ")
+    }
+
+    // Whether a move is right as the given move of the solution, counted from 0. Any move
+    // checkmating is right, mates can often be given in more ways than one.
+    pub fn is_solution_move(&self, board: &Board, index: usize, board_move: BoardMove) -> bool {
+        if self.solution.get(index) == Some(&board_move) {
+            return true;
+        }
+        let mut after = board.clone();
+        after.make_move(board_move);
+        after.is_checkmate()
+    }
+}
+
+// The puzzles to choose from, easiest first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PuzzleSet {
+    puzzles: Vec<Puzzle>,
+}
+
+impl PuzzleSet {
+    pub fn built_in() -> PuzzleSet {
+        PuzzleSet::from(&BUILT_IN_PUZZLES.join("\n")).expect("the built-in puzzles are valid")
+    }
+
+    // Reads the puzzles of a CSV file. The first line is skipped when it names the columns.
+    pub fn from(csv: &str) -> Result<PuzzleSet, PuzzleError> {
+        let mut puzzles = Vec::new();
+        for (number, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (number == 0 && line.starts_with("PuzzleId")) {
+                continue;
+            }
+            puzzles.push(Puzzle::from(line).map_err(|e| PuzzleError::InvalidLine(number + 1, e))?);
+        }
+        puzzles.sort_by_key(|p| p.rating);
+        Ok(PuzzleSet { puzzles })
+    }
+
+    // Reads the puzzles of a file, or gives the built-in ones when the file doesn't exist.
+    pub fn load(path: &Path) -> Result<PuzzleSet, PuzzleError> {
+        match fs::read_to_string(path) {
+            Ok(text) => PuzzleSet::from(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(PuzzleSet::built_in()),
+            Err(e) => Err(PuzzleError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    pub fn get_puzzles(&self) -> &Vec<Puzzle> {
+        &self.puzzles
+    }
+
+    // Up to `count` puzzles getting harder, spread over the whole set. The seed picks among
+    // puzzles of about the same rating, so a large set gives other puzzles every time.
+    pub fn pick(&self, count: usize, seed: u64) -> Vec<Puzzle> {
+        if self.puzzles.len() <= count {
+            return self.puzzles.clone();
+        }
+        let step = self.puzzles.len() / count;
+        (0..count).map(|i| self.puzzles[i * step + (seed as usize).wrapping_add(i * 7919) % step].clone()).collect()
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_puzzle {
+    use super::*;
+
+    #[test]
+    pub fn built_in_puzzles_end_in_checkmate() {
+        let set = PuzzleSet::built_in();
+        assert_eq!(set.get_puzzles().len(), BUILT_IN_PUZZLES.len());
+        for puzzle in set.get_puzzles() {
+            let mut board = puzzle.get_board().clone();
+            for board_move in puzzle.get_solution() {
+                assert!(board.is_legal_move(*board_move), "{}", puzzle.get_id());
+                board.make_move(*board_move);
+            }
+            assert!(board.is_checkmate(), "{}", puzzle.get_id());
+        }
+    }
+
+    #[test]
+    pub fn puzzles_are_read_from_lichess_csv() {
+        let csv = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags\n\
+            00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,https://lichess.org/yyznGmXs/black#34,\n\
+            builtin01,6k1/p4ppp/8/8/8/8/5PPP/4Q1K1 b - - 0 1,a7a6 e1e8,600\n";
+        let set = PuzzleSet::from(csv).unwrap();
+        let ids: Vec<&str> = set.get_puzzles().iter().map(|p| p.get_id()).collect();
+        assert_eq!(ids, ["builtin01", "00sHx"]);
+        let puzzle = &set.get_puzzles()[1];
+        assert_eq!(puzzle.get_opponent_move().to_string(), "Kd7");
+        assert_eq!(puzzle.format_solution(), "Be6+ Kd8 Qf8#");
+        assert_eq!(puzzle.get_board().get_turn(), crate::chess_core::Team::Light);
+
+        let e = PuzzleSet::from("a,8/8/8/8/8/8/8/K6k w - - 0 1,a1a3 h1h2,900").unwrap_err();
+        assert_eq!(e.to_string(), "line 1: a1a3 can't be played");
+    }
+
+    #[test]
+    pub fn picked_puzzles_get_harder() {
+        let csv: Vec<String> = (0..50).map(|i| format!("p{i},6k1/p4ppp/8/8/8/8/5PPP/4Q1K1 b - - 0 1,a7a6 e1e8,{}", 2000 - i * 10)).collect();
+        let set = PuzzleSet::from(&csv.join("\n")).unwrap();
+        let picked = set.pick(10, 12345);
+        assert_eq!(picked.len(), 10);
+        assert!(picked.windows(2).all(|p| p[0].get_rating() < p[1].get_rating()));
+        assert_eq!(PuzzleSet::built_in().pick(20, 1).len(), BUILT_IN_PUZZLES.len());
+    }
+}
@@ -0,0 +1,249 @@
+/*
+chess_python.rs
+Python bindings, built with the python feature into the rust_chess module:
+
+  import rust_chess
+  board = rust_chess.Board()
+  board.push("e4")
+  for game in rust_chess.read_pgn_file("games.pgn"):
+      print(game.tag("White"), game.moves, game.fens()[-1])
+
+Moves are given and returned in SAN, positions in FEN. Errors are raised as ValueError, or
+OSError for files that can't be read.
+*/
+
+use std::fs;
+use pyo3::{
+    exceptions::{PyOSError, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    chess_core::Board,
+    chess_game::GameState,
+    chess_pgn::{parse_pgn_games, ChessMove, PgnGame},
+};
+
+#[pyclass(name = "Board")]
+#[derive(Clone)]
+struct PyBoard {
+    board: Board,
+}
+
+#[pyclass(name = "ChessMove")]
+#[derive(Clone)]
+struct PyChessMove {
+    chess_move: ChessMove,
+}
+
+#[pyclass(name = "PgnGame")]
+#[derive(Clone)]
+struct PyPgnGame {
+    pgn_game: PgnGame,
+}
+
+fn value_error(message: String) -> PyErr {
+    PyValueError::new_err(message)
+}
+
+#[pymethods]
+impl PyBoard {
+    // The starting position, or the position of a FEN.
+    #[new]
+    #[pyo3(signature = (fen = None))]
+    fn new(fen: Option<&str>) -> PyResult<PyBoard> {
+        let board = match fen {
+            Some(fen) => Board::from_fen(fen).map_err(|e| value_error(format!("Invalid FEN {fen}: {e}.")))?,
+            None => Board::new(),
+        };
+        Ok(PyBoard { board })
+    }
+
+    #[getter]
+    fn fen(&self) -> String {
+        self.board.to_fen()
+    }
+
+    // "white" or "black".
+    #[getter]
+    fn turn(&self) -> String {
+        self.board.get_turn().to_string().to_lowercase()
+    }
+
+    // The legal moves in SAN.
+    fn legal_moves(&self) -> Vec<String> {
+        self.board.legal_moves().into_iter().map(|m| self.board.to_chess_move(m).to_string()).collect()
+    }
+
+    // Plays a move given in SAN and returns it as recorded, e.g. "Nf3+" for "Nf3".
+    fn push(&mut self, san: &str) -> PyResult<String> {
+        let chess_move = ChessMove::from(san).map_err(|e| value_error(format!("Invalid move {san}: {e}.")))?;
+        let board_move = self.board.resolve_move(&chess_move).map_err(|e| value_error(format!("Illegal move {san}: {e}.")))?;
+        let recorded = self.board.to_chess_move(board_move);
+        self.board.make_move(board_move);
+        Ok(recorded.to_string())
+    }
+
+    fn is_check(&self) -> bool {
+        self.board.is_in_check(self.board.get_turn())
+    }
+
+    fn is_checkmate(&self) -> bool {
+        self.board.is_checkmate()
+    }
+
+    fn is_stalemate(&self) -> bool {
+        self.board.is_stalemate()
+    }
+
+    // The number of move sequences of the given length, for testing move generation.
+    fn perft(&self, depth: u32) -> u64 {
+        self.board.perft(depth)
+    }
+
+    fn copy(&self) -> PyBoard {
+        self.clone()
+    }
+
+    fn __str__(&self) -> String {
+        self.board.to_fen()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Board('{}')", self.board.to_fen())
+    }
+}
+
+#[pymethods]
+impl PyChessMove {
+    #[new]
+    fn new(san: &str) -> PyResult<PyChessMove> {
+        let chess_move = ChessMove::from(san).map_err(|e| value_error(format!("Invalid move {san}: {e}.")))?;
+        Ok(PyChessMove { chess_move })
+    }
+
+    // The square moved to, e.g. "e4", None for castling.
+    #[getter]
+    fn destination(&self) -> Option<String> {
+        self.chess_move.get_destination().map(|c| c.to_string())
+    }
+
+    #[getter]
+    fn is_capture(&self) -> bool {
+        self.chess_move.is_capture()
+    }
+
+    #[getter]
+    fn is_check(&self) -> bool {
+        self.chess_move.is_check()
+    }
+
+    #[getter]
+    fn is_checkmate(&self) -> bool {
+        self.chess_move.is_checkmate()
+    }
+
+    fn __str__(&self) -> String {
+        self.chess_move.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ChessMove('{}')", self.chess_move)
+    }
+
+    fn __eq__(&self, other: &PyChessMove) -> bool {
+        self.chess_move == other.chess_move
+    }
+}
+
+#[pymethods]
+impl PyPgnGame {
+    // An empty game, or the game of a PGN text.
+    #[new]
+    #[pyo3(signature = (pgn = None))]
+    fn new(pgn: Option<&str>) -> PyResult<PyPgnGame> {
+        let pgn_game = match pgn {
+            Some(pgn) => PgnGame::from(pgn).map_err(|e| value_error(format!("Invalid PGN: {e}.")))?,
+            None => PgnGame::new(),
+        };
+        Ok(PyPgnGame { pgn_game })
+    }
+
+    // A tag's value, None if the game doesn't have it.
+    fn tag(&self, name: &str) -> Option<String> {
+        self.pgn_game.get_tag(name)
+    }
+
+    fn set_tag(&mut self, name: &str, value: String) {
+        self.pgn_game.set_tag(name, value);
+    }
+
+    // Every tag as (name, value) pairs, in the order they are written.
+    #[getter]
+    fn tags(&self) -> Vec<(String, String)> {
+        let required = ["Event", "Site", "Date", "Round", "White", "Black", "Result"].iter()
+            .map(|name| (String::from(*name), self.pgn_game.get_tag(name).unwrap_or_default()));
+        let others = self.pgn_game.get_other_tags().iter().map(|t| (t.get_name().clone(), t.get_value().clone()));
+        required.chain(others).collect()
+    }
+
+    // The moves in SAN.
+    #[getter]
+    fn moves(&self) -> Vec<String> {
+        self.pgn_game.get_plies().into_iter().map(|(m, _)| m.to_string()).collect()
+    }
+
+    // The positions of the game as FEN, from the starting position to the last one.
+    fn fens(&self) -> PyResult<Vec<String>> {
+        let state = GameState::from_pgn_game(self.pgn_game.clone()).map_err(|e| value_error(format!("The game can't be replayed: {e}.")))?;
+        let mut board = Board::new();
+        let mut fens = vec![board.to_fen()];
+        for board_move in state.get_board_moves() {
+            board.make_move(board_move);
+            fens.push(board.to_fen());
+        }
+        Ok(fens)
+    }
+
+    // Plays a move at the end of the game and returns it as recorded.
+    fn push(&mut self, san: &str) -> PyResult<String> {
+        let mut state = GameState::from_pgn_game(self.pgn_game.clone()).map_err(|e| value_error(format!("The game can't be replayed: {e}.")))?;
+        let chess_move = ChessMove::from(san).map_err(|e| value_error(format!("Invalid move {san}: {e}.")))?;
+        let played = state.play_move(&chess_move).map_err(|e| value_error(format!("Illegal move {san}: {e}.")))?;
+        self.pgn_game = state.get_pgn_game().clone();
+        Ok(played.to_string())
+    }
+
+    fn __str__(&self) -> String {
+        self.pgn_game.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        let pgn_game = &self.pgn_game;
+        format!("<PgnGame {} - {}, {} moves>", pgn_game.get_white(), pgn_game.get_black(), pgn_game.get_ply_count())
+    }
+}
+
+// The games of a PGN text.
+#[pyfunction]
+fn read_pgn(pgn: &str) -> PyResult<Vec<PyPgnGame>> {
+    let games = parse_pgn_games(pgn).map_err(|e| value_error(format!("Invalid PGN: {e}.")))?;
+    Ok(games.into_iter().map(|pgn_game| PyPgnGame { pgn_game }).collect())
+}
+
+// The games of a PGN file.
+#[pyfunction]
+fn read_pgn_file(path: &str) -> PyResult<Vec<PyPgnGame>> {
+    let pgn = fs::read_to_string(path).map_err(|e| PyOSError::new_err(format!("{path}: {e}")))?;
+    read_pgn(&pgn)
+}
+
+#[pymodule]
+fn rust_chess(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyBoard>()?;
+    module.add_class::<PyChessMove>()?;
+    module.add_class::<PyPgnGame>()?;
+    module.add_function(wrap_pyfunction!(read_pgn, module)?)?;
+    module.add_function(wrap_pyfunction!(read_pgn_file, module)?)?;
+    Ok(())
+}
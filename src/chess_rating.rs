@@ -0,0 +1,226 @@
+/*
+chess_rating.rs
+Elo ratings of the players of local games, kept in a TOML file, by default ratings.toml in the
+data directory. Players are known by the name their games are played under: the configured
+player_name, the name given for the other player, or for the computer its name and level, e.g.
+"rust-chess at 1000 ms". Each level is rated on its own, so the levels find their rating from
+the games played against them and the players' ratings are measured against those.
+
+Example ratings.toml:
+[players."Raul Rojas"]
+rating = 1532.5
+games = 12
+
+[players."rust-chess at 1000 ms"]
+rating = 1688.1
+games = 9
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::chess_pgn::PgnResult;
+
+// The rating of a player without games.
+pub const INITIAL_RATING: f64 = 1500.0;
+// Ratings move faster over a player's first games, until they settle near the player's strength.
+const PROVISIONAL_GAMES: u32 = 30;
+const PROVISIONAL_K_FACTOR: f64 = 40.0;
+const K_FACTOR: f64 = 20.0;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RatingList {
+    players: BTreeMap<String, PlayerRating>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerRating {
+    rating: f64,
+    games: u32,
+}
+
+// How a player's rating changed with a game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RatingChange {
+    before: f64,
+    after: f64,
+}
+
+#[derive(Debug)]
+pub enum RatingError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl Display for RatingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RatingError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            RatingError::Parse(path, e) => write!(f, "{}: {}", path.display(), e.to_string().trim_end()),
+            RatingError::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RatingError {}
+
+impl Default for PlayerRating {
+    fn default() -> PlayerRating {
+        PlayerRating { rating: INITIAL_RATING, games: 0 }
+    }
+}
+
+impl PlayerRating {
+    pub fn get_rating(&self) -> f64 {
+        self.rating
+    }
+
+    pub fn get_games(&self) -> u32 {
+        self.games
+    }
+
+    pub fn is_provisional(&self) -> bool {
+        self.games < PROVISIONAL_GAMES
+    }
+
+    fn get_k_factor(&self) -> f64 {
+        if self.is_provisional() { PROVISIONAL_K_FACTOR } else { K_FACTOR }
+    }
+}
+
+// Ratings are shown rounded, e.g. "1500 -> 1516 (+16)".
+impl Display for RatingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let difference = self.after.round() - self.before.round();
+        write!(f, "{:.0} -> {:.0} ({:+.0})", self.before, self.after, difference)
+    }
+}
+
+impl RatingChange {
+    pub fn get_before(&self) -> f64 {
+        self.before
+    }
+
+    pub fn get_after(&self) -> f64 {
+        self.after
+    }
+}
+
+impl RatingList {
+    pub fn new() -> RatingList {
+        RatingList::default()
+    }
+
+    pub fn from(toml_str: &str) -> Result<RatingList, toml::de::Error> {
+        toml::from_str(toml_str)
+    }
+
+    // Reads the ratings from a file. A file that doesn't exist yet holds no ratings.
+    pub fn load(path: &Path) -> Result<RatingList, RatingError> {
+        match fs::read_to_string(path) {
+            Ok(text) => RatingList::from(&text).map_err(|e| RatingError::Parse(path.to_path_buf(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(RatingList::new()),
+            Err(e) => Err(RatingError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    // Writes the ratings to a file, creating any missing parent directories.
+    pub fn save(&self, path: &Path) -> Result<(), RatingError> {
+        let text = toml::to_string(self).map_err(RatingError::Serialize)?;
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| RatingError::Io(parent.to_path_buf(), e))?;
+            }
+        }
+        fs::write(path, text).map_err(|e| RatingError::Io(path.to_path_buf(), e))
+    }
+
+    // A player's rating, the initial one for a player without games.
+    pub fn get(&self, name: &str) -> PlayerRating {
+        self.players.get(name).copied().unwrap_or_default()
+    }
+
+    // The rated players, highest rating first.
+    pub fn get_players(&self) -> Vec<(&String, &PlayerRating)> {
+        let mut players: Vec<_> = self.players.iter().collect();
+        players.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        players
+    }
+
+    // Rates a finished game and returns how White's and Black's ratings changed, None when the
+    // game has no result.
+    pub fn record_game(&mut self, white: &str, black: &str, result: PgnResult) -> Option<(RatingChange, RatingChange)> {
+        let white_score = match result {
+            PgnResult::WhiteWin => 1.0,
+            PgnResult::BlackWin => 0.0,
+            PgnResult::Draw => 0.5,
+            PgnResult::Unknown => return None,
+        };
+        let (white_rating, black_rating) = (self.get(white), self.get(black));
+        let white_change = update(white_rating, black_rating.rating, white_score);
+        let black_change = update(black_rating, white_rating.rating, 1.0 - white_score);
+        for (name, change) in [(white, white_change), (black, black_change)] {
+            let player = self.players.entry(String::from(name)).or_default();
+            player.rating = change.after;
+            player.games += 1;
+        }
+        Some((white_change, black_change))
+    }
+}
+
+// The score a player is expected to make against an opponent, from 0 to 1.
+pub fn expected_score(rating: f64, opponent_rating: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+}
+
+fn update(player: PlayerRating, opponent_rating: f64, score: f64) -> RatingChange {
+    let after = player.rating + player.get_k_factor() * (score - expected_score(player.rating, opponent_rating));
+    RatingChange { before: player.rating, after }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_rating {
+    use super::*;
+
+    #[test]
+    pub fn games_move_ratings_by_the_surprise_of_the_result() {
+        let mut ratings = RatingList::new();
+        let (white, black) = ratings.record_game("Raul Rojas", "rust-chess at 1000 ms", PgnResult::WhiteWin).unwrap();
+        assert_eq!(white.to_string(), "1500 -> 1520 (+20)");
+        assert_eq!(black.to_string(), "1500 -> 1480 (-20)");
+        assert_eq!(ratings.get("Raul Rojas").get_games(), 1);
+
+        // Losing to a lower rated player costs more than beating them gained.
+        let (white, _) = ratings.record_game("Raul Rojas", "rust-chess at 1000 ms", PgnResult::BlackWin).unwrap();
+        assert!(white.get_after() - white.get_before() < -20.0);
+        let (white, black) = ratings.record_game("Raul Rojas", "rust-chess at 1000 ms", PgnResult::Draw).unwrap();
+        assert!(white.get_after() > white.get_before() && black.get_after() < black.get_before());
+        assert_eq!(ratings.record_game("Raul Rojas", "rust-chess at 1000 ms", PgnResult::Unknown), None);
+        assert_eq!(ratings.get("Magnus"), PlayerRating::default());
+        assert!((expected_score(1900.0, 1500.0) - 1.0 / 1.1).abs() < 1e-9);
+    }
+
+    #[test]
+    pub fn save_and_load_round_trip() {
+        let path = std::env::temp_dir().join(format!("rust_chess_ratings_{}", std::process::id())).join("ratings.toml");
+        assert_eq!(RatingList::load(&path).unwrap(), RatingList::new());
+        let mut ratings = RatingList::new();
+        ratings.record_game("Raul Rojas", "Magnus", PgnResult::BlackWin);
+        ratings.save(&path).unwrap();
+        let loaded = RatingList::load(&path).unwrap();
+        assert_eq!(loaded, ratings);
+        let names: Vec<&String> = loaded.get_players().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Magnus", "Raul Rojas"]);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,267 @@
+/*
+chess_repertoire.rs
+Opening repertoires read from PGN files, and how well each of their lines is known. A repertoire
+is one or more games whose moves and variations are the openings a player prepared: every path
+from the starting position to the end of the main line or of a variation is one line to learn.
+How often each line was drilled, and how often it was played without a mistake, is kept in
+training.toml in the data directory.
+
+Example training.toml:
+[repertoires."/home/raul/openings/white.pgn"]
+"1. e4 c5 2. Nf3" = { attempts = 4, successes = 3 }
+"1. e4 e5 2. Nf3 Nc6 3. Bb5" = { attempts = 2, successes = 2 }
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chess_core::{Board, BoardMove, MoveResolveError},
+    chess_pgn::{ChessMove, PgnAnnotation, PgnGame, PgnParseError},
+};
+
+// The lines of a repertoire, as moves from the starting position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Repertoire {
+    lines: Vec<Vec<BoardMove>>,
+}
+
+#[derive(Debug)]
+pub enum RepertoireError {
+    Parse(PgnParseError),
+    // A move that can't be played where it stands, after the moves given.
+    IllegalMove(String, ChessMove, MoveResolveError),
+    Empty,
+}
+
+impl Display for RepertoireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepertoireError::Parse(e) => write!(f, "{}", e),
+            RepertoireError::IllegalMove(line, chess_move, e) if line.is_empty() => write!(f, "{} can't be played: {}", chess_move, e),
+            RepertoireError::IllegalMove(line, chess_move, e) => write!(f, "{} can't be played after {}: {}", chess_move, line, e),
+            RepertoireError::Empty => write!(f, "it has no moves"),
+        }
+    }
+}
+
+impl std::error::Error for RepertoireError {}
+
+impl Repertoire {
+    // Collects the lines of the main lines and variations of the given games.
+    pub fn from_games(games: &[PgnGame]) -> Result<Repertoire, RepertoireError> {
+        let mut lines = Vec::new();
+        for game in games {
+            collect_lines(&Board::new(), Vec::new(), &game.get_plies(), &mut lines)?;
+        }
+        // A line that only starts another one is learnt with it.
+        let mut repertoire = Repertoire { lines: Vec::new() };
+        for line in lines {
+            if !repertoire.lines.iter().any(|l| l.starts_with(&line)) {
+                repertoire.lines.retain(|l| !line.starts_with(l));
+                repertoire.lines.push(line);
+            }
+        }
+        if repertoire.lines.is_empty() {
+            return Err(RepertoireError::Empty);
+        }
+        Ok(repertoire)
+    }
+
+    pub fn get_lines(&self) -> &Vec<Vec<BoardMove>> {
+        &self.lines
+    }
+
+    // The moves the repertoire has after the given ones, in the order its lines come.
+    pub fn get_replies(&self, played: &[BoardMove]) -> Vec<BoardMove> {
+        let mut replies = Vec::new();
+        for line in &self.lines {
+            if line.len() > played.len() && line.starts_with(played) && !replies.contains(&line[played.len()]) {
+                replies.push(line[played.len()]);
+            }
+        }
+        replies
+    }
+}
+
+// Adds the line of the given moves, and of the variations along it, after the moves before them.
+fn collect_lines(board: &Board, mut line: Vec<BoardMove>, plies: &[(&ChessMove, &PgnAnnotation)],
+    lines: &mut Vec<Vec<BoardMove>>) -> Result<(), RepertoireError>
+{
+    let mut board = board.clone();
+    for (chess_move, annotation) in plies {
+        // A variation is played instead of the move it follows.
+        for variation in annotation.get_variations() {
+            let variation = PgnGame::from(variation).map_err(RepertoireError::Parse)?;
+            collect_lines(&board, line.clone(), &variation.get_plies(), lines)?;
+        }
+        let board_move = board.resolve_move(chess_move)
+            .map_err(|e| RepertoireError::IllegalMove(format_line(&line), (*chess_move).clone(), e))?;
+        board.make_move(board_move);
+        line.push(board_move);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    Ok(())
+}
+
+// A line written as it is in a game, e.g. "1. e4 c5 2. Nf3".
+pub fn format_line(line: &[BoardMove]) -> String {
+    let mut board = Board::new();
+    let mut text = Vec::new();
+    for (ply, board_move) in line.iter().enumerate() {
+        let chess_move = board.to_chess_move(*board_move);
+        if ply % 2 == 0 {
+            text.push(format!("{}. {chess_move}", ply / 2 + 1));
+        }
+        else {
+            text.push(chess_move.to_string());
+        }
+        board.make_move(*board_move);
+    }
+    text.join(" ")
+}
+
+// How well the lines of each repertoire file are known, by line.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingRecord {
+    repertoires: BTreeMap<String, BTreeMap<String, LineRecord>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineRecord {
+    attempts: u32,
+    // The attempts played without a mistake.
+    successes: u32,
+}
+
+#[derive(Debug)]
+pub enum TrainingRecordError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl Display for TrainingRecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrainingRecordError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            TrainingRecordError::Parse(path, e) => write!(f, "{}: {}", path.display(), e.to_string().trim_end()),
+            TrainingRecordError::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TrainingRecordError {}
+
+impl LineRecord {
+    pub fn get_attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn get_successes(&self) -> u32 {
+        self.successes
+    }
+
+    // The share of attempts without a mistake, from 0 to 1, 0 before the first attempt.
+    pub fn get_success_rate(&self) -> f64 {
+        if self.attempts == 0 { 0.0 } else { self.successes as f64 / self.attempts as f64 }
+    }
+}
+
+impl TrainingRecord {
+    pub fn new() -> TrainingRecord {
+        TrainingRecord::default()
+    }
+
+    pub fn from(toml_str: &str) -> Result<TrainingRecord, toml::de::Error> {
+        toml::from_str(toml_str)
+    }
+
+    // Reads the record from a file. A file that doesn't exist yet holds no attempts.
+    pub fn load(path: &Path) -> Result<TrainingRecord, TrainingRecordError> {
+        match fs::read_to_string(path) {
+            Ok(text) => TrainingRecord::from(&text).map_err(|e| TrainingRecordError::Parse(path.to_path_buf(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(TrainingRecord::new()),
+            Err(e) => Err(TrainingRecordError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    // Writes the record to a file, creating any missing parent directories.
+    pub fn save(&self, path: &Path) -> Result<(), TrainingRecordError> {
+        let text = toml::to_string(self).map_err(TrainingRecordError::Serialize)?;
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| TrainingRecordError::Io(parent.to_path_buf(), e))?;
+            }
+        }
+        fs::write(path, text).map_err(|e| TrainingRecordError::Io(path.to_path_buf(), e))
+    }
+
+    // The record of a line of a repertoire, empty before it is first drilled.
+    pub fn get(&self, repertoire: &str, line: &str) -> LineRecord {
+        self.repertoires.get(repertoire).and_then(|lines| lines.get(line)).copied().unwrap_or_default()
+    }
+
+    // Counts an attempt at a line and returns the line's record after it.
+    pub fn record_attempt(&mut self, repertoire: &str, line: &str, success: bool) -> LineRecord {
+        let record = self.repertoires.entry(String::from(repertoire)).or_default()
+            .entry(String::from(line)).or_default();
+        record.attempts += 1;
+        if success {
+            record.successes += 1;
+        }
+        *record
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_repertoire {
+    use crate::chess_pgn::parse_pgn_games;
+    use super::*;
+
+    #[test]
+    pub fn variations_are_lines_of_their_own() {
+        let games = parse_pgn_games("1. e4 e5 (1... c5 2. Nf3 (2. Nc3) d6) 2. Nf3 Nc6 3. Bb5 *").unwrap();
+        let repertoire = Repertoire::from_games(&games).unwrap();
+        let lines: Vec<String> = repertoire.get_lines().iter().map(|l| format_line(l)).collect();
+        assert_eq!(lines, ["1. e4 c5 2. Nc3", "1. e4 c5 2. Nf3 d6", "1. e4 e5 2. Nf3 Nc6 3. Bb5"]);
+
+        let e4 = repertoire.get_lines()[0][0];
+        let mut board = Board::new();
+        board.make_move(e4);
+        let replies: Vec<String> = repertoire.get_replies(&[e4]).iter().map(|m| board.to_chess_move(*m).to_string()).collect();
+        assert_eq!(replies, ["c5", "e5"]);
+        assert_eq!(repertoire.get_replies(&repertoire.get_lines()[0]), []);
+    }
+
+    #[test]
+    pub fn broken_repertoires_say_where() {
+        let games = parse_pgn_games("1. e4 e5 (1... Nf4) *").unwrap();
+        let e = Repertoire::from_games(&games).unwrap_err();
+        assert_eq!(e.to_string(), "Nf4 can't be played after 1. e4: no legal move matches it in this position");
+        assert!(matches!(Repertoire::from_games(&parse_pgn_games("[Event \"?\"]\n*").unwrap()), Err(RepertoireError::Empty)));
+    }
+
+    #[test]
+    pub fn attempts_are_recorded_by_line() {
+        let mut record = TrainingRecord::new();
+        record.record_attempt("white.pgn", "1. e4 c5 2. Nf3", true);
+        let line = record.record_attempt("white.pgn", "1. e4 c5 2. Nf3", false);
+        assert_eq!((line.get_attempts(), line.get_successes()), (2, 1));
+        assert_eq!(line.get_success_rate(), 0.5);
+        assert_eq!(record.get("black.pgn", "1. e4 c5 2. Nf3"), LineRecord::default());
+        let loaded = TrainingRecord::from(&toml::to_string(&record).unwrap()).unwrap();
+        assert_eq!(loaded, record);
+    }
+}
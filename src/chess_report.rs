@@ -0,0 +1,383 @@
+/*
+chess_report.rs
+The report mode of the program, which sums up a player's games from the game database: their
+results with each color, the openings they play most, the strength of their opponents, the
+losses that came in time trouble and how often they err, as text or JSON:
+
+rust_chess report Rojas --from club.pgn --json
+
+Time trouble is read from the [%clk] times sites put after each move, and errors from the
+[%eval] evaluations annotate and analyze-batch leave, so games without them only count for the
+results, openings and opponents.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+use serde::Serialize;
+
+use crate::{
+    chess_annotate::{summarize_evals, PlayerSummary},
+    chess_cmd::{ChessArgs, ReportArgs},
+    chess_config::ChessConfig,
+    chess_core::{Board, Team},
+    chess_pgn::{parse_pgn_games, PgnGame, PgnParseError, PgnResult},
+};
+
+// A loss is in time trouble when the player's clock went under this, or under a tenth of their
+// time for the game when that is less, as in bullet.
+const TIME_TROUBLE: Duration = Duration::from_secs(30);
+// Half moves naming an opening when the game has no Opening or ECO tag.
+const OPENING_PLIES: usize = 4;
+// Openings listed for each color.
+const OPENINGS_SHOWN: usize = 5;
+
+#[derive(Debug)]
+pub enum ReportError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, PgnParseError),
+    NoDatabase,
+    NoGames(String),
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ReportError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            ReportError::NoDatabase => write!(f, "no games to read, use --from or config set database_path"),
+            ReportError::NoGames(player) => write!(f, "no finished games of {} were found", player),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+// Games won, drawn and lost.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Score {
+    games: u32,
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl Display for Score {
+    // e.g. "12 games, 5 wins, 3 draws, 4 losses (54%)"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = |n: u32, one: &str, many: &str| format!("{n} {}", if n == 1 { one } else { many });
+        write!(f, "{}, {}, {}, {} ({:.0}%)", count(self.games, "game", "games"), count(self.wins, "win", "wins"),
+            count(self.draws, "draw", "draws"), count(self.losses, "loss", "losses"), self.get_percent())
+    }
+}
+
+impl Score {
+    fn add(&mut self, result: PgnResult, team: Team) {
+        self.games += 1;
+        match (result, team) {
+            (PgnResult::WhiteWin, Team::Light) | (PgnResult::BlackWin, Team::Dark) => self.wins += 1,
+            (PgnResult::WhiteWin, Team::Dark) | (PgnResult::BlackWin, Team::Light) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    // The points scored out of the games played, in percent.
+    pub fn get_percent(&self) -> f64 {
+        match self.games {
+            0 => 0.0,
+            games => (self.wins as f64 + self.draws as f64 / 2.0) * 100.0 / games as f64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OpeningScore {
+    name: String,
+    #[serde(flatten)]
+    score: Score,
+}
+
+// How often the player erred, by the evaluations of the games that have them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ErrorRates {
+    games: u32,
+    moves: u32,
+    accuracy: Option<f64>,
+    inaccuracies_per_game: f64,
+    mistakes_per_game: f64,
+    blunders_per_game: f64,
+}
+
+impl ErrorRates {
+    fn from(games: u32, summary: &PlayerSummary) -> ErrorRates {
+        let per_game = |n: u32| n as f64 / games.max(1) as f64;
+        ErrorRates {
+            games,
+            moves: summary.get_moves(),
+            accuracy: summary.get_accuracy(),
+            inaccuracies_per_game: per_game(summary.get_inaccuracies()),
+            mistakes_per_game: per_game(summary.get_mistakes()),
+            blunders_per_game: per_game(summary.get_blunders()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PlayerReport {
+    player: String,
+    total: Score,
+    as_white: Score,
+    as_black: Score,
+    openings_as_white: Vec<OpeningScore>,
+    openings_as_black: Vec<OpeningScore>,
+    // The average rating of the opponents whose rating the games give, and how many those were.
+    average_opponent_rating: Option<f64>,
+    rated_opponents: u32,
+    // The losses with clock times, and those of them lost in time trouble.
+    losses_with_clocks: u32,
+    time_trouble_losses: u32,
+    errors: Option<ErrorRates>,
+}
+
+impl Display for PlayerReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}: {}", self.player, self.total)?;
+        writeln!(f, "  As White: {}", self.as_white)?;
+        writeln!(f, "  As Black: {}", self.as_black)?;
+        match self.average_opponent_rating {
+            Some(rating) => writeln!(f, "Average opponent rating: {rating:.0}, over {} rated games", self.rated_opponents)?,
+            None => writeln!(f, "Average opponent rating: unknown, the games have no ratings")?,
+        }
+        for (color, openings) in [("White", &self.openings_as_white), ("Black", &self.openings_as_black)] {
+            if !openings.is_empty() {
+                writeln!(f, "Openings as {color}:")?;
+                for opening in openings {
+                    writeln!(f, "  {}: {}", opening.name, opening.score)?;
+                }
+            }
+        }
+        match self.losses_with_clocks {
+            0 => writeln!(f, "Time trouble: unknown, the losses have no clock times")?,
+            losses => writeln!(f, "Time trouble: {} of {losses} losses with clock times", self.time_trouble_losses)?,
+        }
+        match &self.errors {
+            Some(errors) => {
+                let accuracy = errors.accuracy.map_or(String::from("-"), |a| format!("{a:.0}%"));
+                writeln!(f, "Errors over {} evaluated games: accuracy {accuracy}, {:.1} inaccuracies, {:.1} mistakes and {:.1} blunders per game",
+                    errors.games, errors.inaccuracies_per_game, errors.mistakes_per_game, errors.blunders_per_game)
+            }
+            None => writeln!(f, "Errors: unknown, the games have no evaluations"),
+        }
+    }
+}
+
+// Which side the player had in a game, by any part of their name.
+fn player_side(game: &PgnGame, player: &str) -> Option<Team> {
+    let player = player.to_lowercase();
+    if game.get_white().to_lowercase().contains(&player) {
+        Some(Team::Light)
+    }
+    else if game.get_black().to_lowercase().contains(&player) {
+        Some(Team::Dark)
+    }
+    else {
+        None
+    }
+}
+
+// The opening by the Opening tag, the ECO tag, or else the first moves. A game set up from a FEN
+// tag has no opening unless a tag names one.
+fn opening_name(game: &PgnGame) -> Option<String> {
+    if let Some(name) = game.get_tag("Opening").or_else(|| game.get_tag("ECO")).filter(|n| !n.is_empty() && n != "?") {
+        return Some(name);
+    }
+    if game.get_tag("FEN").is_some() {
+        return None;
+    }
+    let moves: Vec<_> = game.positions().take(OPENING_PLIES)
+        .filter_map(|(board, chess_move)| board.resolve_move(chess_move).ok())
+        .collect();
+    Some(Board::new().to_san_line(&moves))
+}
+
+fn opponent_rating(game: &PgnGame, team: Team) -> Option<u32> {
+    let tag = match team {
+        Team::Light => "BlackElo",
+        Team::Dark => "WhiteElo",
+    };
+    game.get_tag(tag)?.parse().ok().filter(|r| *r > 0)
+}
+
+// The time on a %clk comment, e.g. [%clk 0:02:58.1].
+fn read_clock(comment: &str) -> Option<Duration> {
+    let start = comment.find("[%clk ")? + "[%clk ".len();
+    let text = comment[start..].split(']').next()?.trim();
+    let mut seconds = 0.0;
+    for part in text.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+// Whether the player's clock ran low in the game. None when their moves have no clock times.
+fn was_in_time_trouble(game: &PgnGame, team: Team) -> Option<bool> {
+    let first = if team == Team::Light { 0 } else { 1 };
+    let clocks: Vec<Duration> = game.get_plies().into_iter().skip(first).step_by(2)
+        .filter_map(|(_, annotation)| annotation.get_comments().iter().find_map(|c| read_clock(c)))
+        .collect();
+    let limit = TIME_TROUBLE.min(*clocks.first()? / 10);
+    Some(clocks.iter().any(|c| *c < limit))
+}
+
+// Sums up the finished games the player played among the games.
+pub fn compile_report(player: &str, games: &[PgnGame]) -> Result<PlayerReport, ReportError> {
+    let mut report = PlayerReport { player: String::from(player), ..Default::default() };
+    let mut openings: [BTreeMap<String, Score>; 2] = Default::default();
+    let mut rating_sum = 0;
+    let mut evaluated_games = 0;
+    let mut errors = PlayerSummary::default();
+    for game in games {
+        let result = *game.get_result();
+        let team = match player_side(game, player) {
+            Some(team) if result != PgnResult::Unknown => team,
+            _ => continue,
+        };
+        report.total.add(result, team);
+        let (color, index) = match team {
+            Team::Light => (&mut report.as_white, 0),
+            Team::Dark => (&mut report.as_black, 1),
+        };
+        color.add(result, team);
+        if let Some(name) = opening_name(game) {
+            openings[index].entry(name).or_default().add(result, team);
+        }
+
+        if let Some(rating) = opponent_rating(game, team) {
+            rating_sum += rating as u64;
+            report.rated_opponents += 1;
+        }
+        let lost = matches!((result, team), (PgnResult::WhiteWin, Team::Dark) | (PgnResult::BlackWin, Team::Light));
+        if let (true, Some(trouble)) = (lost, was_in_time_trouble(game, team)) {
+            report.losses_with_clocks += 1;
+            report.time_trouble_losses += trouble as u32;
+        }
+        let summary = summarize_evals(game, team);
+        if summary.get_moves() > 0 {
+            evaluated_games += 1;
+            errors.merge(&summary);
+        }
+    }
+    if report.total.games == 0 {
+        return Err(ReportError::NoGames(String::from(player)));
+    }
+
+    let [white, black] = openings.map(|scores| {
+        let mut list: Vec<OpeningScore> = scores.into_iter().map(|(name, score)| OpeningScore { name, score }).collect();
+        // Stable, so openings played as often stay in order of name.
+        list.sort_by_key(|o| std::cmp::Reverse(o.score.games));
+        list.truncate(OPENINGS_SHOWN);
+        list
+    });
+    report.openings_as_white = white;
+    report.openings_as_black = black;
+    if report.rated_opponents > 0 {
+        report.average_opponent_rating = Some(rating_sum as f64 / report.rated_opponents as f64);
+    }
+    if evaluated_games > 0 {
+        report.errors = Some(ErrorRates::from(evaluated_games, &errors));
+    }
+    Ok(report)
+}
+
+pub fn report_main(args: &ChessArgs, report_args: &ReportArgs) {
+    if let Err(e) = run(args, report_args) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &ChessArgs, report_args: &ReportArgs) -> Result<(), ReportError> {
+    let paths = match report_args.from.is_empty() {
+        false => report_args.from.clone(),
+        true => {
+            let config = args.config.clone()
+                .or_else(ChessConfig::default_path)
+                .and_then(|path| ChessConfig::load(&path).ok())
+                .unwrap_or_default();
+            let database = config.get_database_path().cloned().or_else(ChessConfig::default_database_path);
+            vec![database.ok_or(ReportError::NoDatabase)?]
+        }
+    };
+    let mut games = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(&path).map_err(|e| ReportError::Io(path.clone(), e))?;
+        games.extend(parse_pgn_games(&text).map_err(|e| ReportError::Parse(path.clone(), e))?);
+    }
+    let report = compile_report(&report_args.player, &games)?;
+    if report_args.json {
+        // The report is plain data, it always serializes.
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+    else {
+        print!("{report}");
+    }
+    Ok(())
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_report {
+    use super::*;
+
+    fn game(white: &str, black: &str, tags: &str, moves: &str, result: &str) -> String {
+        format!("[White \"{white}\"]\n[Black \"{black}\"]\n[Result \"{result}\"]\n{tags}\n{moves} {result}\n\n")
+    }
+
+    fn games() -> Vec<PgnGame> {
+        let text = game("Rojas, Raul", "Smith", "[BlackElo \"1800\"]\n[Opening \"Sicilian Defense\"]",
+                "1. e4 { [%eval 0.3] } 1... c5 { [%eval 0.3] } 2. Nf3 { [%eval 0.3] } 2... d6 { [%eval 0.3] } 3. Ba6?? { [%eval -3.0] }", "0-1")
+            + game("Rojas, Raul", "Jones", "[BlackElo \"2000\"]\n[Opening \"Sicilian Defense\"]", "1. e4 c5", "1-0").as_str()
+            + game("Jones", "Rojas, Raul", "", "1. d4 { [%clk 0:03:00] } 1... d5 { [%clk 0:03:00] } 2. c4 { [%clk 0:02:50] } 2... e6 { [%clk 0:00:12] }", "1-0").as_str()
+            + game("Jones", "Rojas, Raul", "", "1. d4 Nf6", "1/2-1/2").as_str()
+            + game("Jones", "Smith", "", "1. d4 Nf6", "1-0").as_str()
+            + game("Rojas, Raul", "Smith", "", "1. c4", "*").as_str();
+        parse_pgn_games(&text).unwrap()
+    }
+
+    #[test]
+    pub fn a_players_games_are_summed_up() {
+        let report = compile_report("rojas", &games()).unwrap();
+        assert_eq!(report.total, Score { games: 4, wins: 1, draws: 1, losses: 2 });
+        assert_eq!(report.as_white.to_string(), "2 games, 1 win, 0 draws, 1 loss (50%)");
+        assert_eq!(report.openings_as_white, vec![OpeningScore { name: String::from("Sicilian Defense"), score: report.as_white }]);
+        // Openings played as often are listed by name.
+        assert_eq!(report.openings_as_black[0].name, "1. d4 Nf6");
+        assert_eq!(report.openings_as_black[1].name, "1. d4 d5 2. c4 e6");
+        assert_eq!(report.average_opponent_rating, Some(1900.0));
+        assert_eq!((report.losses_with_clocks, report.time_trouble_losses), (1, 1));
+        let errors = report.errors.as_ref().unwrap();
+        assert_eq!((errors.games, errors.moves, errors.blunders_per_game), (1, 2, 1.0));
+
+        let text = report.to_string();
+        assert!(text.starts_with("rojas: 4 games, 1 win, 1 draw, 2 losses (38%)\n"), "{text}");
+        assert!(text.contains("Average opponent rating: 1900, over 2 rated games\n"));
+        assert!(text.contains("Time trouble: 1 of 1 losses with clock times\n"));
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["openings_as_white"][0]["wins"], 1);
+        assert_eq!(json["errors"]["games"], 1);
+
+        assert!(matches!(compile_report("Carlsen", &games()), Err(ReportError::NoGames(_))));
+
+        // Games set up from a position count, but not as an opening.
+        let set_up = game("Rojas, Raul", "Smith", "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]", "1. e4 Kd7", "1-0");
+        let report = compile_report("rojas", &parse_pgn_games(&set_up).unwrap()).unwrap();
+        assert_eq!(report.as_white.games, 1);
+        assert!(report.openings_as_white.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+/*
+chess_serde.rs
+Serialize and Deserialize for the core types, built with the serde feature. Each type is written
+the way chess players write it, so the JSON reads well and stays valid as the types change:
+
+  Board            its FEN, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+  ChessMove        its SAN, "Nxe5+"
+  ChessCoordinate  "e4", or the part a move gives for disambiguation, e.g. "e"
+  PgnGame          {"tags": [["Event", "?"], ...], "moves": [{"move": "e4", "nags": [1]}, ...]}
+  GameState        {"game": <PgnGame>, "fen": <Board>}
+
+Games are replayed when they are read, so illegal moves are refused like in a PGN file.
+*/
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    chess_common::{ChessCoordinate, ChessFile, ChessRank},
+    chess_core::Board,
+    chess_game::GameState,
+    chess_pgn::{ChessMove, PgnAnnotation, PgnGame},
+};
+
+// The seven tags every PGN game has, in the order they are written.
+const REQUIRED_TAGS: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Serialize, Deserialize)]
+struct PgnGameRecord {
+    tags: Vec<(String, String)>,
+    moves: Vec<PgnMoveRecord>,
+    // Comments before the first move.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    initial_comments: Vec<String>,
+    // Said before the result, e.g. why the game was adjudicated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result_comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PgnMoveRecord {
+    #[serde(rename = "move")]
+    chess_move: ChessMove,
+    #[serde(flatten)]
+    annotation: PgnAnnotation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GameStateRecord {
+    game: PgnGame,
+    // The position the moves lead to. It is checked when given.
+    #[serde(default)]
+    fen: Option<Board>,
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        Board::from_fen(&fen).map_err(|e| D::Error::custom(format!("invalid FEN {fen}: {e}")))
+    }
+}
+
+impl Serialize for ChessMove {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessMove {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ChessMove, D::Error> {
+        let san = String::deserialize(deserializer)?;
+        ChessMove::from(&san).map_err(|e| D::Error::custom(format!("invalid move {san}: {e}")))
+    }
+}
+
+impl Serialize for ChessCoordinate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChessCoordinate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ChessCoordinate, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let invalid = || D::Error::custom(format!("invalid coordinate {text}, expected e.g. e4"));
+        let mut chars = text.chars().peekable();
+        let file = chars.next_if(|c| c.is_ascii_lowercase()).map(|c| ChessFile::from(c).ok_or_else(invalid)).transpose()?;
+        let rank = chars.next().map(|c| ChessRank::from(c).ok_or_else(invalid)).transpose()?;
+        if chars.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(ChessCoordinate::new_opt(file, rank))
+    }
+}
+
+impl Serialize for PgnGame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let required = REQUIRED_TAGS.iter().map(|name| (String::from(*name), self.get_tag(name).unwrap_or_default()));
+        let others = self.get_other_tags().iter().map(|t| (t.get_name().clone(), t.get_value().clone()));
+        PgnGameRecord {
+            tags: required.chain(others).collect(),
+            moves: self.get_plies().into_iter()
+                .map(|(chess_move, annotation)| PgnMoveRecord { chess_move: chess_move.clone(), annotation: annotation.clone() })
+                .collect(),
+            initial_comments: self.get_initial_comments().clone(),
+            result_comment: self.get_result_comment().cloned(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PgnGame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PgnGame, D::Error> {
+        let record = PgnGameRecord::deserialize(deserializer)?;
+        let mut pgn_game = PgnGame::new();
+        for (name, value) in record.tags {
+            pgn_game.set_tag(&name, value);
+        }
+        for comment in record.initial_comments {
+            pgn_game.add_initial_comment(comment);
+        }
+        for ply in record.moves {
+            pgn_game.push_annotated_move(ply.chess_move, ply.annotation);
+        }
+        if let Some(comment) = record.result_comment {
+            pgn_game.set_result_comment(comment);
+        }
+        Ok(pgn_game)
+    }
+}
+
+impl Serialize for GameState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GameStateRecord { game: self.get_pgn_game().clone(), fen: Some(self.get_board().clone()) }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<GameState, D::Error> {
+        let record = GameStateRecord::deserialize(deserializer)?;
+        let state = GameState::from_pgn_game(record.game).map_err(D::Error::custom)?;
+        if record.fen.is_some_and(|fen| fen.to_fen() != state.get_board().to_fen()) {
+            return Err(D::Error::custom("the moves don't lead to the position given by fen"));
+        }
+        Ok(state)
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_serde {
+    use super::*;
+
+    #[test]
+    pub fn values_are_written_as_notation() {
+        let board = Board::from_fen("8/8/8/4k3/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert_eq!(serde_json::to_string(&board).unwrap(), r#""8/8/8/4k3/8/8/4P3/4K3 w - - 0 1""#);
+        assert_eq!(serde_json::from_str::<Board>(r#""8/8/8/4k3/8/8/4P3/4K3 w - - 0 1""#).unwrap(), board);
+        assert!(serde_json::from_str::<Board>(r#""8/8 w""#).is_err());
+
+        let chess_move = ChessMove::from("Nbxd7+").unwrap();
+        assert_eq!(serde_json::to_string(&chess_move).unwrap(), r#""Nbxd7+""#);
+        assert_eq!(serde_json::from_str::<ChessMove>(r#""Nbxd7+""#).unwrap(), chess_move);
+
+        let coordinate = ChessCoordinate::new(ChessFile::E, ChessRank::R4);
+        assert_eq!(serde_json::to_string(&coordinate).unwrap(), r#""e4""#);
+        assert_eq!(serde_json::from_str::<ChessCoordinate>(r#""e4""#).unwrap(), coordinate);
+        assert_eq!(serde_json::from_str::<ChessCoordinate>(r#""b""#).unwrap(), ChessCoordinate::from_file(ChessFile::B));
+        assert!(serde_json::from_str::<ChessCoordinate>(r#""e9""#).is_err());
+    }
+
+    #[test]
+    pub fn games_keep_tags_moves_and_annotations() {
+        let mut state = GameState::new();
+        state.get_pgn_game_mut().set_white(String::from("Raul Rojas"));
+        state.get_pgn_game_mut().set_tag("ECO", String::from("C20"));
+        for san in ["e4", "e5", "Qh5"] {
+            state.play_move(&ChessMove::from(san).unwrap()).unwrap();
+        }
+        state.annotate_last_move(2);
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains(r#"["White","Raul Rojas"]"#), "{json}");
+        assert!(json.contains(r#"["ECO","C20"]"#), "{json}");
+        assert!(json.contains(r#""moves":[{"move":"e4"},{"move":"e5"},{"move":"Qh5","nags":[2]}]"#), "{json}");
+        let read: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.get_pgn_game().to_string(), state.get_pgn_game().to_string());
+        assert_eq!(read.get_board(), state.get_board());
+
+        // Moves are replayed, so a record that can't be played is refused.
+        let illegal = json.replace(r#"{"move":"e5"}"#, r#"{"move":"e3"}"#);
+        assert!(serde_json::from_str::<GameState>(&illegal).is_err());
+    }
+
+    #[test]
+    pub fn games_set_up_from_a_position_round_trip() {
+        let pgn = PgnGame::from("[SetUp \"1\"]\n[FEN \"8/8/8/4k3/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 Kd6 *").unwrap();
+        let state = GameState::from_pgn_game(pgn).unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        let read: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.get_board(), state.get_board());
+        assert_eq!(read.get_pgn_game().to_string(), state.get_pgn_game().to_string());
+    }
+}
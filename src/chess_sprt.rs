@@ -0,0 +1,332 @@
+/*
+chess_sprt.rs
+The sprt mode of the program, for telling whether a change made an engine stronger. Two engines,
+e.g. a new build and the last one, play pairs of games from the same opening with the colors
+swapped, until a sequential probability ratio test decides between the hypotheses that the first
+engine is elo0 stronger (H0) or elo1 stronger (H1):
+
+rust_chess sprt ./engine-new ./engine-old --elo0 0 --elo1 5
+rust_chess sprt ./engine-new 4 --openings book.pgn --movetime 50 --out sprt.pgn
+
+The log likelihood ratio (LLR) is worked out from the wins, draws and losses with the logistic
+Elo model after every pair, and the test stops when it leaves the bounds alpha and beta give:
+ln(beta / (1 - alpha)) accepting H0, and ln((1 - beta) / alpha) accepting H1. Openings are the
+first moves of the games of a PGN file, or a few main lines of common openings when none is given.
+*/
+
+use std::{
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    chess_adjudication::AdjudicationRules,
+    chess_cmd::{ChessArgs, SprtArgs},
+    chess_config::ChessConfig,
+    chess_core::{Board, BoardMove},
+    chess_match::{MatchError, MatchPlayer},
+    chess_pgn::{parse_pgn_games, ChessMove, PgnParseError, PgnResult, PgnRound},
+    chess_tournament::{play_game, Terminal, TournamentConsole, TournamentError},
+};
+
+// Main lines played from when no openings are given, so deterministic engines don't play the
+// same game over and over.
+const OPENINGS: [&str; 12] = [
+    "e4 e5 Nf3 Nc6 Bb5 a6",
+    "e4 e5 Nf3 Nc6 Bc4 Bc5",
+    "e4 c5 Nf3 d6 d4 cxd4",
+    "e4 c5 Nc3 Nc6 g3 g6",
+    "e4 e6 d4 d5 Nc3 Nf6",
+    "e4 c6 d4 d5 e5 Bf5",
+    "d4 d5 c4 e6 Nc3 Nf6",
+    "d4 d5 c4 c6 Nf3 Nf6",
+    "d4 Nf6 c4 g6 Nc3 Bg7",
+    "d4 Nf6 c4 e6 Nc3 Bb4",
+    "c4 e5 Nc3 Nf6 g3 d5",
+    "Nf3 d5 g3 Nf6 Bg2 c6",
+];
+
+#[derive(Debug)]
+pub enum SprtError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, PgnParseError),
+    // Bounds that don't make a test.
+    InvalidTest(String),
+    NoOpenings(PathBuf),
+    Player(MatchError),
+}
+
+impl Display for SprtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SprtError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            SprtError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            SprtError::InvalidTest(e) => write!(f, "{}", e),
+            SprtError::NoOpenings(path) => write!(f, "{} has no games to take openings from", path.display()),
+            SprtError::Player(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SprtError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SprtDecision {
+    // The first engine is at most elo0 stronger.
+    AcceptH0,
+    // The first engine is at least elo1 stronger.
+    AcceptH1,
+}
+
+// The hypotheses and error rates of a test, and the games after which it gives up (0 for never).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+    max_games: u32,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64, max_games: u32) -> Result<Sprt, SprtError> {
+        if elo1 <= elo0 {
+            return Err(SprtError::InvalidTest(format!("elo1 ({elo1}) has to be above elo0 ({elo0})")));
+        }
+        if !(0.0..0.5).contains(&alpha) || !(0.0..0.5).contains(&beta) || alpha == 0.0 || beta == 0.0 {
+            return Err(SprtError::InvalidTest(String::from("alpha and beta have to be between 0 and 0.5")));
+        }
+        Ok(Sprt { elo0, elo1, alpha, beta, max_games })
+    }
+
+    // The LLR at which H0 and H1 are accepted.
+    pub fn get_bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    // The log likelihood ratio of H1 against H0 given the first engine's results, by the normal
+    // approximation of the game results, 0 while the results don't vary.
+    pub fn llr(&self, score: &SprtScore) -> f64 {
+        let games = score.get_games() as f64;
+        if games == 0.0 {
+            return 0.0;
+        }
+        let (wins, draws, losses) = (score.wins as f64 / games, score.draws as f64 / games, score.losses as f64 / games);
+        let mean = wins + draws / 2.0;
+        let variance = wins * (1.0 - mean).powi(2) + draws * (0.5 - mean).powi(2) + losses * mean.powi(2);
+        if variance <= 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        games * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn decide(&self, score: &SprtScore) -> Option<SprtDecision> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.get_bounds();
+        if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        }
+        else if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        }
+        else {
+            None
+        }
+    }
+}
+
+// The share of the points a player the given Elo stronger is expected to score.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// The first engine's results.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SprtScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl SprtScore {
+    pub fn get_games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // The Elo difference the score shows, None while it is all wins or all losses.
+    pub fn get_elo(&self) -> Option<f64> {
+        let games = self.get_games() as f64;
+        let score = (self.wins as f64 + self.draws as f64 / 2.0) / games;
+        (score > 0.0 && score < 1.0).then(|| -400.0 * (1.0 / score - 1.0).log10())
+    }
+
+    fn add(&mut self, result: PgnResult, first_white: bool) {
+        match (result, first_white) {
+            (PgnResult::WhiteWin, true) | (PgnResult::BlackWin, false) => self.wins += 1,
+            (PgnResult::WhiteWin, false) | (PgnResult::BlackWin, true) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+}
+
+// The openings of the test, the first plies of the games of a PGN file or the built-in lines.
+pub fn load_openings(path: Option<&Path>, plies: usize) -> Result<Vec<Vec<BoardMove>>, SprtError> {
+    let path = match path {
+        Some(p) => p,
+        None => {
+            return Ok(OPENINGS.iter().map(|line| {
+                let mut board = Board::new();
+                line.split_whitespace().map(|san| {
+                    // The built-in lines are legal.
+                    let board_move = board.resolve_move(&ChessMove::from(san).unwrap()).unwrap();
+                    board.make_move(board_move);
+                    board_move
+                }).collect()
+            }).collect());
+        }
+    };
+    let text = fs::read_to_string(path).map_err(|e| SprtError::Io(path.to_path_buf(), e))?;
+    let games = parse_pgn_games(&text).map_err(|e| SprtError::Parse(path.to_path_buf(), e))?;
+    let openings: Vec<Vec<BoardMove>> = games.iter()
+        .filter(|g| g.get_tag("FEN").is_none())
+        .map(|g| g.positions().take(plies).map(|(board, m)| board.resolve_move(m).unwrap()).collect())
+        .collect();
+    if openings.is_empty() {
+        return Err(SprtError::NoOpenings(path.to_path_buf()));
+    }
+    Ok(openings)
+}
+
+fn format_progress(score: &SprtScore, sprt: &Sprt) -> String {
+    let (lower, upper) = sprt.get_bounds();
+    let elo = score.get_elo().map_or(String::from("-"), |e| format!("{e:.1}"));
+    format!("Games {}: +{} ={} -{}, Elo {elo}, LLR {:.2} ({lower:.2}, {upper:.2})",
+        score.get_games(), score.wins, score.draws, score.losses, sprt.llr(score))
+}
+
+// Plays game pairs until the test decides or gives up.
+pub fn run_sprt(
+    players: [&mut MatchPlayer; 2],
+    sprt: &Sprt,
+    openings: &[Vec<BoardMove>],
+    movetime: Duration,
+    rules: AdjudicationRules,
+    console: &mut dyn TournamentConsole,
+    out: Option<&Path>,
+) -> Result<(SprtScore, Option<SprtDecision>), SprtError> {
+    let [first, second] = players;
+    let names = [first.get_name().clone(), second.get_name().clone()];
+    let event = format!("SPRT {} - {}", names[0], names[1]);
+    let mut score = SprtScore::default();
+    let mut records = Vec::new();
+    let mut decision = None;
+    let mut pair = 0;
+    while decision.is_none() && (sprt.max_games == 0 || score.get_games() < sprt.max_games) {
+        let opening = &openings[pair % openings.len()];
+        pair += 1;
+        for first_white in [true, false] {
+            let (white, black) = if first_white { (&mut *first, &mut *second) } else { (&mut *second, &mut *first) };
+            let (white_name, black_name) = (white.get_name().clone(), black.get_name().clone());
+            let (mut game, reason) = play_game((white.get_player_mut(), &white_name), (black.get_player_mut(), &black_name),
+                opening, movetime, rules, console).map_err(|e| SprtError::Player(MatchError::Game(e)))?;
+            score.add(*game.get_result(), first_white);
+            if let Some(path) = out {
+                game.set_event(event.clone());
+                game.set_round(PgnRound::Known(vec![score.get_games()]));
+                game.set_white(white_name);
+                game.set_black(black_name);
+                game.set_result_comment(String::from(reason));
+                records.push(game.to_string());
+                fs::write(path, records.join("\n"))
+                    .map_err(|e| SprtError::Player(MatchError::Game(TournamentError::Io(path.to_path_buf(), e))))?;
+            }
+        }
+        console.say(&format_progress(&score, sprt));
+        decision = sprt.decide(&score);
+    }
+    let summary = match decision {
+        Some(SprtDecision::AcceptH1) => format!("H1 accepted: {} is at least {} Elo stronger than {}.", names[0], sprt.elo1, names[1]),
+        Some(SprtDecision::AcceptH0) => format!("H0 accepted: {} is at most {} Elo stronger than {}.", names[0], sprt.elo0, names[1]),
+        None => format!("No decision after {} games.", score.get_games()),
+    };
+    console.say(&summary);
+    Ok((score, decision))
+}
+
+pub fn sprt_main(args: &ChessArgs, sprt_args: &SprtArgs) {
+    if let Err(e) = run(args, sprt_args) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &ChessArgs, sprt_args: &SprtArgs) -> Result<(), SprtError> {
+    let config = args.config.clone()
+        .or_else(ChessConfig::default_path)
+        .and_then(|p| ChessConfig::load(&p).ok())
+        .unwrap_or_default();
+    let sprt = Sprt::new(sprt_args.elo0, sprt_args.elo1, sprt_args.alpha, sprt_args.beta, sprt_args.max_games)?;
+    let openings = load_openings(sprt_args.openings.as_deref(), sprt_args.opening_plies)?;
+    let mut first = MatchPlayer::from_spec(&sprt_args.engine, &config).map_err(SprtError::Player)?;
+    let mut second = MatchPlayer::from_spec(&sprt_args.baseline, &config).map_err(SprtError::Player)?;
+    let movetime = Duration::from_millis(sprt_args.movetime);
+    let rules = AdjudicationRules::from_config(&config);
+    run_sprt([&mut first, &mut second], &sprt, &openings, movetime, rules, &mut Terminal, sprt_args.out.as_deref())?;
+    Ok(())
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_sprt {
+    use super::*;
+
+    struct Silent;
+
+    impl TournamentConsole for Silent {
+        fn say(&mut self, _text: &str) {}
+
+        fn read_line(&mut self) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    pub fn likelihood_ratios_decide_tests() {
+        let sprt = Sprt::new(0.0, 5.0, 0.05, 0.05, 0).unwrap();
+        let (lower, upper) = sprt.get_bounds();
+        assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+        let even = SprtScore { wins: 300, draws: 400, losses: 300 };
+        assert_eq!(even.get_elo(), Some(0.0));
+        assert!(sprt.llr(&even) < 0.0);
+        assert_eq!(sprt.decide(&SprtScore { wins: 100, draws: 100, losses: 100 }), None);
+        assert_eq!(sprt.decide(&SprtScore { wins: 9000, draws: 12000, losses: 9000 }), Some(SprtDecision::AcceptH0));
+        assert_eq!(sprt.decide(&SprtScore { wins: 3200, draws: 4000, losses: 2800 }), Some(SprtDecision::AcceptH1));
+        assert_eq!(format_progress(&SprtScore { wins: 2, draws: 0, losses: 0 }, &sprt), "Games 2: +2 =0 -0, Elo -, LLR 0.00 (-2.94, 2.94)");
+        assert!(Sprt::new(5.0, 0.0, 0.05, 0.05, 0).is_err());
+    }
+
+    #[test]
+    pub fn games_are_played_in_pairs_from_each_opening() {
+        let config = ChessConfig::new();
+        let mut first = MatchPlayer::from_spec("1", &config).unwrap();
+        let mut second = MatchPlayer::from_spec("1", &config).unwrap();
+        let openings = load_openings(None, 0).unwrap();
+        assert_eq!(openings.len(), OPENINGS.len());
+        assert!(openings.iter().all(|o| o.len() == 6));
+
+        let mut short = ChessConfig::new();
+        short.set("max_game_moves", "8").unwrap();
+        let sprt = Sprt::new(0.0, 5.0, 0.05, 0.05, 3).unwrap();
+        let (score, decision) = run_sprt([&mut first, &mut second], &sprt, &openings, Duration::from_millis(10),
+            AdjudicationRules::from_config(&short), &mut Silent, None).unwrap();
+        // Games are played a pair at a time.
+        assert_eq!(score.get_games(), 4);
+        assert_eq!(decision, None);
+    }
+}
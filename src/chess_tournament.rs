@@ -0,0 +1,700 @@
+/*
+chess_tournament.rs
+The tournament mode of the program, which runs a round robin or Swiss event among players
+playing at the terminal, levels of the built-in engine and UCI engines. The event is described in
+a TOML file, and its games are written as they finish to one PGN file:
+
+rust_chess tournament club.toml --out club.pgn
+
+Example club.toml:
+name = "Club blitz"
+format = "swiss"        # or "round-robin", the default
+rounds = 4              # Swiss only, enough rounds to find a winner by default
+double = false          # round robin only, whether everyone meets twice with both colors
+movetime = 500          # milliseconds UCI engines think per move
+
+[[players]]
+name = "Raul"           # without an engine or a level, plays at the terminal
+
+[[players]]
+name = "Rust Chess level 2"
+level = 2               # the built-in engine, searching this many moves deep
+
+[[players]]
+name = "Stockfish"
+engine = "/usr/bin/stockfish"
+
+Standings go by points, a win being worth one and a draw half. Ties are broken by the
+Sonneborn-Berger score and then wins in a round robin, and by the Buchholz score, the points of
+the opponents met, and then Sonneborn-Berger in a Swiss event. A bye is worth a point.
+*/
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use serde::Deserialize;
+
+use crate::{
+    chess_adjudication::{AdjudicationRules, Adjudicator},
+    chess_cmd::{ChessArgs, TournamentArgs},
+    chess_config::ChessConfig,
+    chess_core::{BoardMove, DiagramStyle, Team},
+    chess_engine,
+    chess_game::GameState,
+    chess_pgn::{ChessMove, PgnAnnotation, PgnGame, PgnResult, PgnRound},
+    chess_uci::{SearchLimit, UciEngine, UciError},
+    chess_ui::tui::game_ending,
+};
+
+// Games still going after this many half moves are drawn, so engines can't shuffle forever.
+const MAX_PLIES: usize = 600;
+const MAX_LEVEL: u32 = 6;
+
+#[derive(Debug)]
+pub enum TournamentError {
+    Io(PathBuf, io::Error),
+    Config(PathBuf, String),
+    FileExists(PathBuf),
+    Engine(String, UciError),
+    // The terminal closed while a player was to move.
+    InputEnded,
+}
+
+impl Display for TournamentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TournamentError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            TournamentError::Config(path, e) => write!(f, "{}: {}", path.display(), e),
+            TournamentError::FileExists(path) => write!(f, "{} already exists, use --force to overwrite it", path.display()),
+            TournamentError::Engine(name, e) => write!(f, "{} failed: {}", name, e),
+            TournamentError::InputEnded => write!(f, "the input ended during a game"),
+        }
+    }
+}
+
+impl std::error::Error for TournamentError {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TournamentFormat {
+    #[default]
+    RoundRobin,
+    Swiss,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParticipantConfig {
+    name: String,
+    // The depth the built-in engine plays at.
+    level: Option<u32>,
+    // A UCI engine.
+    engine: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TournamentConfig {
+    name: String,
+    #[serde(default)]
+    format: TournamentFormat,
+    rounds: Option<u32>,
+    #[serde(default)]
+    double: bool,
+    #[serde(default = "default_movetime")]
+    movetime: u64,
+    players: Vec<ParticipantConfig>,
+}
+
+fn default_movetime() -> u64 {
+    1000
+}
+
+impl TournamentConfig {
+    pub fn from(text: &str) -> Result<TournamentConfig, String> {
+        let config: TournamentConfig = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        if config.players.len() < 2 {
+            return Err(String::from("a tournament needs at least two players"));
+        }
+        for (i, player) in config.players.iter().enumerate() {
+            if config.players[..i].iter().any(|p| p.name == player.name) {
+                return Err(format!("{} is in the tournament twice", player.name));
+            }
+            match (player.level, &player.engine) {
+                (Some(_), Some(_)) => return Err(format!("{} has both a level and an engine", player.name)),
+                (Some(level), None) if !(1..=MAX_LEVEL).contains(&level) => {
+                    return Err(format!("{}'s level {level} isn't from 1 to {MAX_LEVEL}", player.name));
+                }
+                _ => (),
+            }
+        }
+        if config.rounds == Some(0) {
+            return Err(String::from("a tournament needs at least one round"));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<TournamentConfig, TournamentError> {
+        let text = fs::read_to_string(path).map_err(|e| TournamentError::Io(path.to_path_buf(), e))?;
+        TournamentConfig::from(&text).map_err(|e| TournamentError::Config(path.to_path_buf(), e))
+    }
+}
+
+// Who a player is, and how their moves are chosen.
+pub enum Player {
+    Human,
+    BuiltIn(u32),
+    Uci(UciEngine),
+}
+
+impl Player {
+    fn start(config: &ParticipantConfig) -> Result<Player, TournamentError> {
+        match (config.level, &config.engine) {
+            (_, Some(path)) => {
+                let engine = UciEngine::start(path).map_err(|e| TournamentError::Engine(config.name.clone(), e))?;
+                Ok(Player::Uci(engine))
+            }
+            (Some(level), None) => Ok(Player::BuiltIn(level)),
+            (None, None) => Ok(Player::Human),
+        }
+    }
+}
+
+// Where players at the terminal see the games and type their moves.
+pub trait TournamentConsole {
+    fn say(&mut self, text: &str);
+    // The next line typed, None when the input ended.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+pub struct Terminal;
+
+impl TournamentConsole for Terminal {
+    fn say(&mut self, text: &str) {
+        println!("{text}");
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+// Who meets whom in a round. A player left over gets a bye.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pairing {
+    Game { white: usize, black: usize },
+    Bye(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PlayedGame {
+    white: usize,
+    black: usize,
+    result: PgnResult,
+}
+
+// A player's place in the standings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Standing {
+    pub player: usize,
+    pub points: f64,
+    pub buchholz: f64,
+    pub sonneborn_berger: f64,
+    pub wins: u32,
+}
+
+// The players, pairings and results of an event.
+pub struct Tournament {
+    names: Vec<String>,
+    format: TournamentFormat,
+    double: bool,
+    rounds: u32,
+    games: Vec<PlayedGame>,
+    byes: Vec<usize>,
+}
+
+impl Tournament {
+    pub fn new(names: Vec<String>, format: TournamentFormat, rounds: Option<u32>, double: bool) -> Tournament {
+        let count = names.len() as u32;
+        let rounds = match format {
+            // An odd number of players sit out in turn, adding a round.
+            TournamentFormat::RoundRobin => (count + count % 2 - 1) * if double { 2 } else { 1 },
+            // Enough rounds for one player to beat everyone else's score, by default.
+            TournamentFormat::Swiss => rounds.unwrap_or_else(|| count.next_power_of_two().trailing_zeros().max(1)),
+        };
+        Tournament { names, format, double, rounds, games: Vec::new(), byes: Vec::new() }
+    }
+
+    pub fn get_rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    pub fn record_game(&mut self, white: usize, black: usize, result: PgnResult) {
+        self.games.push(PlayedGame { white, black, result });
+    }
+
+    pub fn record_bye(&mut self, player: usize) {
+        self.byes.push(player);
+    }
+
+    // The points a player scored against each opponent they met, a game at a time.
+    fn get_scores(&self, player: usize) -> Vec<(usize, f64)> {
+        self.games.iter().filter_map(|g| {
+            let (opponent, team) = match player {
+                p if p == g.white => (g.black, Team::Light),
+                p if p == g.black => (g.white, Team::Dark),
+                _ => return None,
+            };
+            let points = match (g.result, team) {
+                (PgnResult::WhiteWin, Team::Light) | (PgnResult::BlackWin, Team::Dark) => 1.0,
+                (PgnResult::Draw, _) => 0.5,
+                _ => 0.0,
+            };
+            Some((opponent, points))
+        }).collect()
+    }
+
+    fn get_points(&self, player: usize) -> f64 {
+        let byes = self.byes.iter().filter(|p| **p == player).count() as f64;
+        self.get_scores(player).iter().map(|(_, points)| points).sum::<f64>() + byes
+    }
+
+    fn have_met(&self, a: usize, b: usize) -> bool {
+        self.games.iter().any(|g| (g.white == a && g.black == b) || (g.white == b && g.black == a))
+    }
+
+    // How many more times a player had White than Black, and the color they had last.
+    fn get_colors(&self, player: usize) -> (i32, Option<Team>) {
+        let mut balance = 0;
+        let mut last = None;
+        for game in &self.games {
+            if game.white == player {
+                balance += 1;
+                last = Some(Team::Light);
+            }
+            else if game.black == player {
+                balance -= 1;
+                last = Some(Team::Dark);
+            }
+        }
+        (balance, last)
+    }
+
+    pub fn get_standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = (0..self.names.len()).map(|player| {
+            let scores = self.get_scores(player);
+            Standing {
+                player,
+                points: self.get_points(player),
+                buchholz: scores.iter().map(|(opponent, _)| self.get_points(*opponent)).sum(),
+                sonneborn_berger: scores.iter().map(|(opponent, points)| points * self.get_points(*opponent)).sum(),
+                wins: scores.iter().filter(|(_, points)| *points == 1.0).count() as u32,
+            }
+        }).collect();
+        let tiebreaks = |s: &Standing| match self.format {
+            TournamentFormat::RoundRobin => [s.points, s.sonneborn_berger, s.wins as f64],
+            TournamentFormat::Swiss => [s.points, s.buchholz, s.sonneborn_berger],
+        };
+        // Stable, so players tied on everything stay in the order they were entered.
+        standings.sort_by(|a, b| tiebreaks(b).partial_cmp(&tiebreaks(a)).unwrap_or(Ordering::Equal));
+        standings
+    }
+
+    pub fn format_standings(&self) -> Vec<String> {
+        let width = self.names.iter().map(|n| n.chars().count()).max().unwrap_or(0).max("Player".len());
+        let mut lines = vec![format!("{:>3}  {:width$}  {:>6}  {:>8}  {:>5}  {:>4}", "#", "Player", "Points", "Buchholz", "S-B", "Wins")];
+        for (place, s) in self.get_standings().iter().enumerate() {
+            lines.push(format!("{:>3}  {:width$}  {:>6}  {:>8}  {:>5}  {:>4}",
+                place + 1, self.names[s.player], s.points, s.buchholz, s.sonneborn_berger, s.wins));
+        }
+        lines
+    }
+
+    // The pairings of a round, counted from 1.
+    pub fn pair_round(&self, round: u32) -> Vec<Pairing> {
+        match self.format {
+            TournamentFormat::RoundRobin => self.pair_round_robin(round),
+            TournamentFormat::Swiss => self.pair_swiss(),
+        }
+    }
+
+    // The circle method: the first player stays put while the others turn around them, so
+    // everyone meets everyone once in a cycle. A double round robin plays the cycle again with
+    // the colors swapped.
+    fn pair_round_robin(&self, round: u32) -> Vec<Pairing> {
+        let mut circle: Vec<Option<usize>> = (0..self.names.len()).map(Some).collect();
+        if circle.len() % 2 == 1 {
+            circle.push(None);
+        }
+        let size = circle.len();
+        let cycle_rounds = size - 1;
+        let index = (round as usize - 1) % cycle_rounds;
+        let second_cycle = self.double && round as usize > cycle_rounds;
+        circle[1..].rotate_right(index);
+
+        let mut pairings = Vec::new();
+        for board in 0..size / 2 {
+            let (mut a, mut b) = (circle[board], circle[size - 1 - board]);
+            // The fixed player changes color every round, the others by board and round.
+            let swap = if board == 0 { index % 2 == 1 } else { board % 2 == 1 };
+            if swap != second_cycle {
+                std::mem::swap(&mut a, &mut b);
+            }
+            match (a, b) {
+                (Some(white), Some(black)) => pairings.push(Pairing::Game { white, black }),
+                (Some(player), None) | (None, Some(player)) => pairings.push(Pairing::Bye(player)),
+                (None, None) => (),
+            }
+        }
+        pairings
+    }
+
+    // Players are paired down the standings with the highest placed player they haven't met. The
+    // lowest placed player without a bye yet sits out when the number is odd.
+    fn pair_swiss(&self) -> Vec<Pairing> {
+        let mut order: Vec<usize> = self.get_standings().iter().map(|s| s.player).collect();
+        let mut pairings = Vec::new();
+        if order.len() % 2 == 1 {
+            let bye = order.iter().rposition(|p| !self.byes.contains(p)).unwrap_or(order.len() - 1);
+            pairings.push(Pairing::Bye(order.remove(bye)));
+        }
+        // Rematches only when there is no other way, late in a long event.
+        let pairs = self.pair_without_rematches(&order)
+            .unwrap_or_else(|| order.chunks(2).map(|pair| (pair[0], pair[1])).collect());
+        let mut games: Vec<Pairing> = pairs.into_iter().map(|(a, b)| {
+            let ((balance_a, last_a), (balance_b, _)) = (self.get_colors(a), self.get_colors(b));
+            let a_white = match balance_a.cmp(&balance_b) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => last_a != Some(Team::Light),
+            };
+            match a_white {
+                true => Pairing::Game { white: a, black: b },
+                false => Pairing::Game { white: b, black: a },
+            }
+        }).collect();
+        games.append(&mut pairings);
+        games
+    }
+
+    fn pair_without_rematches(&self, players: &[usize]) -> Option<Vec<(usize, usize)>> {
+        let (first, rest) = match players.split_first() {
+            Some(split) => split,
+            None => return Some(Vec::new()),
+        };
+        for (i, opponent) in rest.iter().enumerate() {
+            if self.have_met(*first, *opponent) {
+                continue;
+            }
+            let others: Vec<usize> = rest.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, p)| *p).collect();
+            if let Some(mut pairs) = self.pair_without_rematches(&others) {
+                pairs.insert(0, (*first, *opponent));
+                return Some(pairs);
+            }
+        }
+        None
+    }
+}
+
+// Two different players of the list, to play each other.
+fn two_players(players: &mut [Player], white: usize, black: usize) -> (&mut Player, &mut Player) {
+    if white < black {
+        let (left, right) = players.split_at_mut(black);
+        (&mut left[white], &mut right[0])
+    }
+    else {
+        let (left, right) = players.split_at_mut(white);
+        (&mut right[0], &mut left[black])
+    }
+}
+
+// The move of a player at the terminal, None when they resign.
+fn ask_move(state: &GameState, name: &str, console: &mut dyn TournamentConsole) -> Result<Option<BoardMove>, TournamentError> {
+    let board = state.get_board();
+    let turn = board.get_turn();
+    console.say(&board.to_diagram(DiagramStyle::Unicode, turn == Team::Dark));
+    loop {
+        console.say(&format!("{name} ({turn}) to move, enter a move or resign:"));
+        let line = console.read_line().ok_or(TournamentError::InputEnded)?;
+        let text = line.trim();
+        if text.eq_ignore_ascii_case("resign") {
+            return Ok(None);
+        }
+        let resolved = ChessMove::from(text).map_err(|e| e.to_string())
+            .and_then(|m| board.resolve_move(&m).map_err(|e| e.to_string()));
+        match resolved {
+            Ok(board_move) => return Ok(Some(board_move)),
+            Err(e) => console.say(&format!("{text}: {e}.")),
+        }
+    }
+}
+
+// Plays a game between two players from the position after the opening moves, and returns its
+// record and why it ended.
+pub fn play_game(
+    white: (&mut Player, &str),
+    black: (&mut Player, &str),
+    opening: &[BoardMove],
+    movetime: Duration,
+    rules: AdjudicationRules,
+    console: &mut dyn TournamentConsole,
+) -> Result<(PgnGame, &'static str), TournamentError> {
+    let mut players = [white, black];
+    for (player, name) in players.iter_mut() {
+        if let Player::Uci(engine) = player {
+            engine.new_game().map_err(|e| TournamentError::Engine(name.to_string(), e))?;
+        }
+    }
+    let mut state = GameState::new();
+    let mut seen: HashMap<u64, u32> = HashMap::from([(state.get_board().zobrist_key(), 1)]);
+    for board_move in opening {
+        if state.play_board_move(*board_move, PgnAnnotation::new()).is_err() {
+            break;
+        }
+        *seen.entry(state.get_board().zobrist_key()).or_default() += 1;
+    }
+    let mut adjudicator = Adjudicator::new(rules);
+    let mut adjudicated = false;
+    let (result, reason) = loop {
+        let board = state.get_board();
+        if let Some(ending) = game_ending(board) {
+            break ending;
+        }
+        if seen.get(&board.zobrist_key()).is_some_and(|n| *n >= 3) {
+            break (PgnResult::Draw, "threefold repetition");
+        }
+        if state.get_ply_count() >= MAX_PLIES {
+            break (PgnResult::Draw, "the move limit");
+        }
+        let turn = board.get_turn();
+        let loss = match turn {
+            Team::Light => PgnResult::BlackWin,
+            Team::Dark => PgnResult::WhiteWin,
+        };
+        let (player, name) = &mut players[if turn == Team::Light { 0 } else { 1 }];
+        // The move, and the evaluation of engines from White's side.
+        let chosen = match player {
+            Player::Human => ask_move(&state, name, console)?.map(|m| (m, None)),
+            Player::BuiltIn(level) => chess_engine::search(board, SearchLimit::Depth(*level)).map(|r| (r.get_best_move(), r.get_score())),
+            Player::Uci(engine) => {
+                let searched = engine.search(&state.get_board_moves(), SearchLimit::MoveTime(movetime));
+                let result = searched.map_err(|e| TournamentError::Engine(name.to_string(), e))?;
+                Some((result.get_best_move(), result.get_score()))
+            }
+        };
+        let (board_move, score) = match chosen {
+            Some((m, score)) => (m, score.map(|s| if turn == Team::Dark { s.negate() } else { s })),
+            None => break (loss, "resignation"),
+        };
+        if state.play_board_move(board_move, PgnAnnotation::new()).is_err() {
+            break (loss, "an illegal move");
+        }
+        *seen.entry(state.get_board().zobrist_key()).or_default() += 1;
+        if game_ending(state.get_board()).is_none() {
+            if let Some(ending) = adjudicator.adjudicate(state.get_board(), score) {
+                adjudicated = true;
+                break ending;
+            }
+        }
+    };
+    state.end_game(result);
+    if adjudicated {
+        state.get_pgn_game_mut().set_tag("Termination", String::from("adjudication"));
+    }
+    Ok((state.get_pgn_game().clone(), reason))
+}
+
+// Plays every round of the event, writing the games to the PGN file as they finish.
+pub fn run_tournament(
+    config: &TournamentConfig,
+    players: &mut [Player],
+    rules: AdjudicationRules,
+    console: &mut dyn TournamentConsole,
+    out: &Path,
+) -> Result<Tournament, TournamentError> {
+    let names: Vec<String> = config.players.iter().map(|p| p.name.clone()).collect();
+    let mut tournament = Tournament::new(names.clone(), config.format, config.rounds, config.double);
+    let movetime = Duration::from_millis(config.movetime);
+    let mut records = Vec::new();
+    for round in 1..=tournament.get_rounds() {
+        console.say(&format!("Round {round} of {}", tournament.get_rounds()));
+        for (board, pairing) in tournament.pair_round(round).into_iter().enumerate() {
+            let (white, black) = match pairing {
+                Pairing::Bye(player) => {
+                    console.say(&format!("  {} has a bye", names[player]));
+                    tournament.record_bye(player);
+                    continue;
+                }
+                Pairing::Game { white, black } => (white, black),
+            };
+            console.say(&format!("  Board {}: {} - {}", board + 1, names[white], names[black]));
+            let (white_player, black_player) = two_players(players, white, black);
+            let (mut game, reason) = play_game((white_player, &names[white]), (black_player, &names[black]), &[], movetime, rules, console)?;
+            let result = *game.get_result();
+            console.say(&format!("  {} {result} {}, by {reason}", names[white], names[black]));
+            tournament.record_game(white, black, result);
+
+            game.set_event(config.name.clone());
+            game.set_round(PgnRound::Known(vec![round, board as u32 + 1]));
+            game.set_white(names[white].clone());
+            game.set_black(names[black].clone());
+            game.set_result_comment(String::from(reason));
+            records.push(game.to_string());
+            fs::write(out, records.join("\n")).map_err(|e| TournamentError::Io(out.to_path_buf(), e))?;
+        }
+        console.say(&format!("Standings after round {round}:"));
+        for line in tournament.format_standings() {
+            console.say(&line);
+        }
+    }
+    Ok(tournament)
+}
+
+pub fn tournament_main(args: &ChessArgs, tournament_args: &TournamentArgs) {
+    if let Err(e) = run(args, tournament_args) {
+        eprintln!("error: {e}.");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &ChessArgs, tournament_args: &TournamentArgs) -> Result<(), TournamentError> {
+    let chess_config = args.config.clone()
+        .or_else(ChessConfig::default_path)
+        .and_then(|p| ChessConfig::load(&p).ok())
+        .unwrap_or_default();
+    let args = tournament_args;
+    let config = TournamentConfig::load(&args.file_path)?;
+    if args.out.exists() && !args.force {
+        return Err(TournamentError::FileExists(args.out.clone()));
+    }
+    let mut players = config.players.iter().map(Player::start).collect::<Result<Vec<Player>, TournamentError>>()?;
+    let tournament = run_tournament(&config, &mut players, AdjudicationRules::from_config(&chess_config), &mut Terminal, &args.out)?;
+    let winner = &tournament.get_standings()[0];
+    println!("{} wins {} with {} points. The games are in {}.", config.players[winner.player].name, config.name, winner.points, args.out.display());
+    Ok(())
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_tournament {
+    use super::*;
+    use crate::chess_pgn::parse_pgn_games;
+
+    struct Script {
+        lines: Vec<String>,
+        said: Vec<String>,
+    }
+
+    impl TournamentConsole for Script {
+        fn say(&mut self, text: &str) {
+            self.said.push(String::from(text));
+        }
+
+        fn read_line(&mut self) -> Option<String> {
+            (!self.lines.is_empty()).then(|| self.lines.remove(0))
+        }
+    }
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("P{i}")).collect()
+    }
+
+    #[test]
+    pub fn round_robins_pair_everyone_once() {
+        for count in [4, 5] {
+            let tournament = Tournament::new(names(count), TournamentFormat::RoundRobin, None, true);
+            assert_eq!(tournament.get_rounds(), if count == 4 { 6 } else { 10 });
+            let mut met = HashMap::new();
+            let mut byes = vec![0; count];
+            for round in 1..=tournament.get_rounds() {
+                for pairing in tournament.pair_round(round) {
+                    match pairing {
+                        Pairing::Game { white, black } => *met.entry((white, black)).or_insert(0) += 1,
+                        Pairing::Bye(player) => byes[player] += 1,
+                    }
+                }
+            }
+            // Twice over, once with each color.
+            for a in 0..count {
+                for b in 0..count {
+                    assert_eq!(met.get(&(a, b)).copied().unwrap_or(0), if a == b { 0 } else { 1 }, "{a} - {b}");
+                }
+            }
+            assert!(byes.iter().all(|b| *b == if count == 5 { 2 } else { 0 }));
+        }
+    }
+
+    #[test]
+    pub fn swiss_rounds_pair_by_score_without_rematches() {
+        let mut tournament = Tournament::new(names(5), TournamentFormat::Swiss, None, false);
+        assert_eq!(tournament.get_rounds(), 3);
+        let first = tournament.pair_round(1);
+        assert_eq!(first, vec![
+            Pairing::Game { white: 0, black: 1 },
+            Pairing::Game { white: 2, black: 3 },
+            Pairing::Bye(4),
+        ]);
+        tournament.record_game(0, 1, PgnResult::WhiteWin);
+        tournament.record_game(2, 3, PgnResult::Draw);
+        tournament.record_bye(4);
+
+        // P0 and P4 lead and P2 and P3 follow, but P2 and P3 have met, so the leaders take one
+        // each, P2 getting White having had it less lately. P1 is last and sits out.
+        assert_eq!(tournament.pair_round(2), vec![
+            Pairing::Game { white: 2, black: 0 },
+            Pairing::Game { white: 3, black: 4 },
+            Pairing::Bye(1),
+        ]);
+    }
+
+    #[test]
+    pub fn standings_break_ties() {
+        let mut tournament = Tournament::new(names(4), TournamentFormat::RoundRobin, None, false);
+        tournament.record_game(0, 1, PgnResult::WhiteWin);
+        tournament.record_game(2, 3, PgnResult::WhiteWin);
+        tournament.record_game(0, 2, PgnResult::BlackWin);
+        tournament.record_game(1, 3, PgnResult::Draw);
+        tournament.record_game(3, 0, PgnResult::Draw);
+        tournament.record_game(1, 2, PgnResult::Draw);
+        let standings = tournament.get_standings();
+        // P1 and P3 both have a point, but P1 drew the winner.
+        assert_eq!(standings.iter().map(|s| s.player).collect::<Vec<_>>(), vec![2, 0, 1, 3]);
+        assert_eq!(standings[2].sonneborn_berger, 0.5 + 1.25);
+        assert_eq!(standings[1].sonneborn_berger, 1.0 + 0.5);
+        assert_eq!(tournament.format_standings()[1], "  1  P2         2.5       3.5      3     2");
+    }
+
+    #[test]
+    pub fn events_are_played_and_recorded() {
+        let config = TournamentConfig::from("name = \"Test\"\n\
+            [[players]]\nname = \"Raul\"\n\
+            [[players]]\nname = \"Level 1\"\nlevel = 1\n").unwrap();
+        let out = std::env::temp_dir().join(format!("rust_chess_tournament_{}.pgn", std::process::id()));
+        let mut players = config.players.iter().map(|p| Player::start(p).unwrap()).collect::<Vec<_>>();
+        let mut console = Script { lines: vec![String::from("e9"), String::from("resign")], said: Vec::new() };
+        let tournament = run_tournament(&config, &mut players, AdjudicationRules::default(), &mut console, &out).unwrap();
+        assert_eq!(tournament.get_standings()[0].player, 1);
+        assert!(console.said.contains(&String::from("  Raul 0-1 Level 1, by resignation")), "{:?}", console.said);
+
+        let games = parse_pgn_games(&fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].get_tag("Round").unwrap(), "1.1");
+        assert_eq!(games[0].get_event(), "Test");
+        let _ = fs::remove_file(&out);
+
+        assert!(TournamentConfig::from("name = \"Solo\"\n[[players]]\nname = \"Raul\"\n").is_err());
+        assert_eq!(TournamentConfig::from("name = \"T\"\n[[players]]\nname = \"A\"\nlevel = 9\n[[players]]\nname = \"B\"\n"),
+            Err(String::from("A's level 9 isn't from 1 to 6")));
+    }
+}
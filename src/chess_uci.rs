@@ -0,0 +1,511 @@
+/*
+chess_uci.rs
+Client side of the Universal Chess Interface (UCI), for playing against and watching external
+engines such as Stockfish. The server side, for other programs to play the built-in engine, is
+in server.rs. The engine runs as a child process. Its output is read on a thread of
+its own, so a search can be started and then checked on without blocking the interface.
+
+Protocol: https://backscattering.de/chess/uci/
+*/
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::chess_core::BoardMove;
+
+pub mod server;
+
+// How long an engine gets to answer the handshake and readiness checks.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// A mate in centipawns, less the moves to it.
+const MATE_CENTIPAWNS: i32 = 100_000;
+
+pub struct UciEngine {
+    path: PathBuf,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: String,
+    // What the engine reported about each line of the running search, the best first, None
+    // when it isn't searching.
+    search: Option<Vec<SearchInfo>>,
+}
+
+// How long a search may run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchLimit {
+    Depth(u32),
+    MoveTime(Duration),
+    // Until it is stopped.
+    Infinite,
+}
+
+// An evaluation from the point of view of the side to move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EngineScore {
+    Centipawns(i32),
+    // Moves to checkmate, negative when the side to move gets mated.
+    Mate(i32),
+}
+
+// What an engine reported about a search. Engines leave out what they like, so every part is
+// optional.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchInfo {
+    depth: Option<u32>,
+    score: Option<EngineScore>,
+    nodes: Option<u64>,
+    // Nodes per second.
+    nps: Option<u64>,
+    // The principal variation, the moves the engine expects from the position.
+    pv: Vec<BoardMove>,
+    // Which line this is when the engine reports several, counted from 1.
+    multipv: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    best_move: BoardMove,
+    info: SearchInfo,
+    // The answer the engine expects, which it may think about on the opponent's time.
+    ponder_move: Option<BoardMove>,
+}
+
+#[derive(Debug)]
+pub enum UciError {
+    Spawn(PathBuf, io::Error),
+    Io(io::Error),
+    // The engine's process ended.
+    Exited,
+    // The engine didn't answer a command in time.
+    NoResponse(&'static str),
+    InvalidBestMove(String),
+}
+
+impl Display for UciError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UciError::Spawn(path, e) => write!(f, "could not start {}: {}", path.display(), e),
+            UciError::Io(e) => write!(f, "{}", e),
+            UciError::Exited => write!(f, "the engine stopped running"),
+            UciError::NoResponse(command) => write!(f, "the engine did not answer {}", command),
+            UciError::InvalidBestMove(line) => write!(f, "the engine sent an invalid move: {}", line),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+impl Display for EngineScore {
+    // Pawns with a sign, e.g. +0.35, or moves to mate, e.g. #3 and #-2.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineScore::Centipawns(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+            EngineScore::Mate(moves) => write!(f, "#{}", moves),
+        }
+    }
+}
+
+impl EngineScore {
+    // The same evaluation seen from the other side.
+    pub fn negate(self) -> EngineScore {
+        match self {
+            EngineScore::Centipawns(cp) => EngineScore::Centipawns(-cp),
+            EngineScore::Mate(moves) => EngineScore::Mate(-moves),
+        }
+    }
+
+    // The evaluation in centipawns, with mates counted beyond any material and quicker mates
+    // more.
+    pub fn to_centipawns(self) -> i32 {
+        match self {
+            EngineScore::Centipawns(cp) => cp,
+            EngineScore::Mate(moves) if moves > 0 => MATE_CENTIPAWNS - moves,
+            EngineScore::Mate(moves) => -MATE_CENTIPAWNS - moves,
+        }
+    }
+}
+
+impl SearchInfo {
+    pub fn new(depth: u32, score: EngineScore, nodes: u64, time: Duration, pv: Vec<BoardMove>) -> SearchInfo {
+        let nps = (nodes as f64 / time.as_secs_f64().max(0.001)) as u64;
+        SearchInfo { depth: Some(depth), score: Some(score), nodes: Some(nodes), nps: Some(nps), pv, multipv: None }
+    }
+
+    pub fn get_depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    pub fn get_score(&self) -> Option<EngineScore> {
+        self.score
+    }
+
+    pub fn get_nodes(&self) -> Option<u64> {
+        self.nodes
+    }
+
+    pub fn get_nps(&self) -> Option<u64> {
+        self.nps
+    }
+
+    pub fn get_pv(&self) -> &Vec<BoardMove> {
+        &self.pv
+    }
+
+    pub fn get_multipv(&self) -> Option<u32> {
+        self.multipv
+    }
+
+    // Takes in a later report, keeping what it leaves out.
+    pub fn update(&mut self, later: SearchInfo) {
+        self.depth = later.depth.or(self.depth);
+        self.score = later.score.or(self.score);
+        self.nodes = later.nodes.or(self.nodes);
+        self.nps = later.nps.or(self.nps);
+        self.multipv = later.multipv.or(self.multipv);
+        if !later.pv.is_empty() {
+            self.pv = later.pv;
+        }
+    }
+}
+
+impl SearchResult {
+    pub fn new(best_move: BoardMove, info: SearchInfo) -> SearchResult {
+        SearchResult { best_move, info, ponder_move: None }
+    }
+
+    pub fn get_best_move(&self) -> BoardMove {
+        self.best_move
+    }
+
+    pub fn get_score(&self) -> Option<EngineScore> {
+        self.info.score
+    }
+
+    pub fn get_depth(&self) -> Option<u32> {
+        self.info.depth
+    }
+
+    pub fn get_info(&self) -> &SearchInfo {
+        &self.info
+    }
+
+    // The answer the engine expects to its best move, as it said or else the second move of its
+    // principal variation.
+    pub fn get_ponder_move(&self) -> Option<BoardMove> {
+        let pv = &self.info.pv;
+        self.ponder_move.or_else(|| pv.get(1).copied().filter(|_| pv[0] == self.best_move))
+    }
+}
+
+impl UciEngine {
+    // Starts an engine and completes the UCI handshake.
+    pub fn start(path: &Path) -> Result<UciEngine, UciError> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| UciError::Spawn(path.to_path_buf(), e))?;
+        let stdin = child.stdin.take().ok_or(UciError::Exited)?;
+        let stdout = child.stdout.take().ok_or(UciError::Exited)?;
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            // Ends when the engine exits or the engine is dropped.
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let mut engine = UciEngine { path: path.to_path_buf(), child, stdin, lines, name, search: None };
+        engine.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.receive_until(deadline, "uci")?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = String::from(name.trim());
+            }
+            else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.wait_ready()?;
+        Ok(engine)
+    }
+
+    // The name the engine gave itself, or its file name if it gave none.
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    // What the engine reported so far about the running search, for its best line.
+    pub fn get_search_info(&self) -> Option<&SearchInfo> {
+        self.search.as_ref().and_then(|lines| lines.first())
+    }
+
+    // What the engine reported so far about every line of the running search, the best first.
+    pub fn get_search_lines(&self) -> &[SearchInfo] {
+        self.search.as_deref().unwrap_or_default()
+    }
+
+    // Sets one of the options the engine offers, e.g. MultiPV or Hash.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), UciError> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.wait_ready()
+    }
+
+    // Tells the engine the next position is from another game.
+    pub fn new_game(&mut self) -> Result<(), UciError> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    // Starts searching the position after `moves` from the starting position. The result is
+    // picked up with poll_search.
+    pub fn start_search(&mut self, moves: &[BoardMove], limit: SearchLimit) -> Result<(), UciError> {
+        self.go(moves, limit, false)
+    }
+
+    // Starts thinking on the opponent's time about the position after `moves`, whose last move
+    // is the one the engine expects from the opponent. The search goes on until ponder_hit, when
+    // the opponent played it and the limit starts to count, or until stop_search otherwise.
+    pub fn start_ponder(&mut self, moves: &[BoardMove], limit: SearchLimit) -> Result<(), UciError> {
+        self.go(moves, limit, true)
+    }
+
+    // Tells the pondering engine the opponent played the expected move.
+    pub fn ponder_hit(&mut self) -> Result<(), UciError> {
+        self.send("ponderhit")
+    }
+
+    fn go(&mut self, moves: &[BoardMove], limit: SearchLimit, ponder: bool) -> Result<(), UciError> {
+        let mut position = String::from("position startpos");
+        if !moves.is_empty() {
+            position.push_str(" moves");
+            for m in moves {
+                position.push_str(format!(" {}", m).as_str());
+            }
+        }
+        self.send(&position)?;
+        let go = if ponder { "go ponder" } else { "go" };
+        match limit {
+            SearchLimit::Depth(depth) => self.send(&format!("{} depth {}", go, depth))?,
+            SearchLimit::MoveTime(time) => self.send(&format!("{} movetime {}", go, time.as_millis()))?,
+            SearchLimit::Infinite => self.send(&format!("{} infinite", go))?,
+        }
+        self.search = Some(vec![SearchInfo::default()]);
+        Ok(())
+    }
+
+    // Reads what the engine sent without waiting. Returns the result once the search ended.
+    pub fn poll_search(&mut self) -> Result<Option<SearchResult>, UciError> {
+        loop {
+            let line = match self.lines.try_recv() {
+                Ok(l) => l,
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(UciError::Exited),
+            };
+            if let Some(result) = self.read_search_line(&line)? {
+                return Ok(Some(result));
+            }
+        }
+    }
+
+    // Searches a position and waits for the result.
+    pub fn search(&mut self, moves: &[BoardMove], limit: SearchLimit) -> Result<SearchResult, UciError> {
+        self.start_search(moves, limit)?;
+        self.wait_search().map(|r| r.expect("a search was started"))
+    }
+
+    // Waits for the running search to end, e.g. after stop_search. None when there was none.
+    pub fn wait_search(&mut self) -> Result<Option<SearchResult>, UciError> {
+        while self.search.is_some() {
+            let line = self.lines.recv().map_err(|_| UciError::Exited)?;
+            if let Some(result) = self.read_search_line(&line)? {
+                return Ok(Some(result));
+            }
+        }
+        Ok(None)
+    }
+
+    // Asks the engine to end the running search early, it still answers with a best move.
+    pub fn stop_search(&mut self) -> Result<(), UciError> {
+        self.send("stop")
+    }
+
+    fn read_search_line(&mut self, line: &str) -> Result<Option<SearchResult>, UciError> {
+        let lines = match &mut self.search {
+            Some(l) => l,
+            None => return Ok(None),
+        };
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("info") => {
+                let info = parse_info(line);
+                let index = info.get_multipv().unwrap_or(1).max(1) as usize - 1;
+                if lines.len() <= index {
+                    lines.resize(index + 1, SearchInfo::default());
+                }
+                lines[index].update(info);
+                Ok(None)
+            }
+            Some("bestmove") => {
+                let best_move = tokens.next()
+                    .and_then(BoardMove::from)
+                    .ok_or_else(|| UciError::InvalidBestMove(String::from(line)))?;
+                let ponder_move = match tokens.next() {
+                    Some("ponder") => tokens.next().and_then(BoardMove::from),
+                    _ => None,
+                };
+                let info = self.search.take().and_then(|l| l.into_iter().next()).unwrap_or_default();
+                Ok(Some(SearchResult { best_move, info, ponder_move }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn wait_ready(&mut self) -> Result<(), UciError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.receive_until(deadline, "isready")?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    fn receive_until(&mut self, deadline: Instant, command: &'static str) -> Result<String, UciError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(UciError::NoResponse(command)),
+            Err(RecvTimeoutError::Disconnected) => Err(UciError::Exited),
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UciError> {
+        writeln!(self.stdin, "{}", command).and_then(|()| self.stdin.flush()).map_err(UciError::Io)
+    }
+}
+
+impl Drop for UciEngine {
+    // Asks the engine to quit, and ends it if it doesn't.
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The parts of an info line, e.g.
+// "info depth 12 seldepth 18 score cp 35 nodes 15362 nps 1200000 pv e2e4 e7e5".
+pub fn parse_info(line: &str) -> SearchInfo {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let mut info = SearchInfo::default();
+    let mut i = 1;
+    while i < tokens.len() {
+        let number = tokens.get(i + 1).and_then(|n| n.parse::<u64>().ok());
+        match tokens[i] {
+            "depth" => info.depth = number.and_then(|d| u32::try_from(d).ok()),
+            "nodes" => info.nodes = number,
+            "nps" => info.nps = number,
+            "multipv" => info.multipv = number.and_then(|m| u32::try_from(m).ok()),
+            "score" => {
+                let value = tokens.get(i + 2).and_then(|v| v.parse().ok());
+                info.score = match (tokens.get(i + 1), value) {
+                    (Some(&"cp"), Some(v)) => Some(EngineScore::Centipawns(v)),
+                    (Some(&"mate"), Some(v)) => Some(EngineScore::Mate(v)),
+                    _ => info.score,
+                };
+            }
+            // The rest of the line is moves.
+            "pv" => {
+                info.pv = tokens[i + 1..].iter().map_while(|m| BoardMove::from(m)).collect();
+                break;
+            }
+            // The rest of the line is free text.
+            "string" => break,
+            _ => (),
+        }
+        i += 1;
+    }
+    info
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_uci {
+    use super::*;
+
+    #[test]
+    pub fn info_lines_give_the_search_progress() {
+        let info = parse_info("info depth 12 seldepth 18 multipv 1 score cp 35 nodes 15362 nps 120000 pv e2e4 e7e5");
+        assert_eq!(info.get_depth(), Some(12));
+        assert_eq!(info.get_score(), Some(EngineScore::Centipawns(35)));
+        assert_eq!(info.get_nodes(), Some(15362));
+        assert_eq!(info.get_nps(), Some(120000));
+        assert_eq!(info.get_multipv(), Some(1));
+        assert_eq!(info.get_pv(), &vec![BoardMove::from("e2e4").unwrap(), BoardMove::from("e7e5").unwrap()]);
+
+        let info = parse_info("info depth 20 score mate -3 pv h7h8");
+        assert_eq!((info.get_score(), info.get_depth()), (Some(EngineScore::Mate(-3)), Some(20)));
+        let info = parse_info("info depth 5 score cp 12 lowerbound");
+        assert_eq!((info.get_score(), info.get_depth()), (Some(EngineScore::Centipawns(12)), Some(5)));
+        assert_eq!(parse_info("info string depth 99 score cp 1"), SearchInfo::default());
+    }
+
+    #[test]
+    pub fn later_info_keeps_what_it_leaves_out() {
+        let mut info = parse_info("info depth 8 score cp 20 pv d2d4");
+        info.update(parse_info("info nodes 5000 nps 100000"));
+        assert_eq!(info.get_depth(), Some(8));
+        assert_eq!(info.get_nodes(), Some(5000));
+        assert_eq!(info.get_pv().len(), 1);
+    }
+
+    #[test]
+    pub fn scores_are_shown_in_pawns_or_moves_to_mate() {
+        assert_eq!(EngineScore::Centipawns(35).to_string(), "+0.35");
+        assert_eq!(EngineScore::Centipawns(-120).to_string(), "-1.20");
+        assert_eq!(EngineScore::Mate(3).to_string(), "#3");
+        assert_eq!(EngineScore::Mate(3).negate().to_string(), "#-3");
+    }
+
+    #[test]
+    pub fn mates_count_beyond_material() {
+        assert_eq!(EngineScore::Centipawns(-120).to_centipawns(), -120);
+        assert!(EngineScore::Mate(1).to_centipawns() > EngineScore::Mate(5).to_centipawns());
+        assert!(EngineScore::Mate(5).to_centipawns() > EngineScore::Centipawns(5000).to_centipawns());
+        assert!(EngineScore::Mate(-1).to_centipawns() < EngineScore::Mate(-5).to_centipawns());
+    }
+
+    #[test]
+    pub fn missing_engine_fails_to_start() {
+        let path = Path::new("/nonexistent/rust_chess_engine");
+        assert!(matches!(UciEngine::start(path), Err(UciError::Spawn(_, _))));
+    }
+}
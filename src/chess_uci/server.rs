@@ -0,0 +1,345 @@
+/*
+server.rs
+The engine side of UCI, so chess interfaces such as Arena, Cute Chess and lichess-bot can play
+the built-in engine. Commands are read from stdin and answered on stdout. Searches run on a
+thread of their own, so stop and isready are answered while the engine thinks.
+*/
+
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    chess_core::{Board, BoardMove, Team},
+    chess_engine::{self, ENGINE_NAME},
+};
+use super::{EngineScore, SearchLimit, SearchResult};
+
+// Time kept back from every move for the interface's own delays.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+// Moves the remaining time is shared among when the interface doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// The most lines the MultiPV option allows.
+const MAX_MULTIPV: usize = 16;
+
+pub struct UciServer {
+    board: Board,
+    // Where answers go, a line at a time.
+    output: Sender<String>,
+    search: Option<RunningSearch>,
+    // Lines searched and reported, set with the MultiPV option.
+    multipv: usize,
+}
+
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    // Set while pondering, the best move then waits for ponderhit or stop.
+    pondering: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+// What a go command asks for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoLimits {
+    limit: SearchLimit,
+    // The best move is held back until stop, even when the search ends on its own.
+    infinite: bool,
+    // Searching on the opponent's time, the best move is held back until ponderhit or stop.
+    ponder: bool,
+}
+
+// Answers UCI commands on stdin until quit or the end of the input.
+pub fn uci_main() {
+    let (output, lines) = mpsc::channel::<String>();
+    let printer = thread::spawn(move || {
+        let mut stdout = io::stdout();
+        for line in lines {
+            if writeln!(stdout, "{}", line).and_then(|()| stdout.flush()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut server = UciServer::new(output);
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        if !server.handle(&line) {
+            break;
+        }
+    }
+    server.stop_search();
+    drop(server);
+    let _ = printer.join();
+}
+
+impl UciServer {
+    pub fn new(output: Sender<String>) -> UciServer {
+        UciServer { board: Board::new(), output, search: None, multipv: 1 }
+    }
+
+    // Carries out a command. Returns false once told to quit.
+    pub fn handle(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("uci") => {
+                self.send(format!("id name {} {}", ENGINE_NAME, env!("CARGO_PKG_VERSION")));
+                self.send(String::from("id author Raul Rojas"));
+                self.send(String::from("option name Ponder type check default false"));
+                self.send(format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTIPV));
+                self.send(String::from("uciok"));
+            }
+            Some("isready") => self.send(String::from("readyok")),
+            Some("setoption") => self.set_option(&tokens.collect::<Vec<&str>>()),
+            Some("ucinewgame") => {
+                self.stop_search();
+                self.board = Board::new();
+            }
+            Some("position") => {
+                self.stop_search();
+                match parse_position(&tokens.collect::<Vec<&str>>()) {
+                    Ok(board) => self.board = board,
+                    Err(e) => self.send(format!("info string {}", e)),
+                }
+            }
+            Some("go") => {
+                self.stop_search();
+                let limits = parse_go(&tokens.collect::<Vec<&str>>(), self.board.get_turn());
+                self.start_search(limits);
+            }
+            Some("ponderhit") => {
+                if let Some(search) = &self.search {
+                    search.pondering.store(false, Ordering::Relaxed);
+                }
+            }
+            Some("stop") => self.stop_search(),
+            Some("quit") => {
+                self.stop_search();
+                return false;
+            }
+            // Unknown commands are ignored, as the protocol asks.
+            _ => (),
+        }
+        true
+    }
+
+    fn start_search(&mut self, limits: GoLimits) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let board = self.board.clone();
+        let output = self.output.clone();
+        let stopped = stop.clone();
+        let pondering = Arc::new(AtomicBool::new(limits.ponder));
+        let still_pondering = pondering.clone();
+        let lines = self.multipv;
+        let thread = thread::spawn(move || {
+            let results = chess_engine::search_lines_until_stopped(&board, limits.limit, lines, &stopped, |results, stats| {
+                for (i, result) in results.iter().enumerate() {
+                    let multipv = if lines > 1 { Some(i + 1) } else { None };
+                    let _ = output.send(info_line(result, multipv, stats.get_nodes(), stats.get_time()));
+                }
+            });
+            let result = results.first();
+            let held = || limits.infinite || still_pondering.load(Ordering::Relaxed);
+            while held() && !stopped.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            // A position without moves still gets an answer, the null move.
+            let answer = match result {
+                Some(r) => match r.get_ponder_move() {
+                    Some(ponder) => format!("bestmove {} ponder {}", r.get_best_move(), ponder),
+                    None => format!("bestmove {}", r.get_best_move()),
+                },
+                None => String::from("bestmove 0000"),
+            };
+            let _ = output.send(answer);
+        });
+        self.search = Some(RunningSearch { stop, pondering, thread });
+    }
+
+    // Takes "name <id> value <x>". MultiPV is the only option used, Ponder only tells whether
+    // pondering may happen, which go ponder says anyway.
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_at = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        let name = tokens.get(1..value_at).map(|n| n.join(" ")).unwrap_or_default();
+        let value = tokens.get(value_at + 1).and_then(|v| v.parse::<usize>().ok());
+        if name.eq_ignore_ascii_case("MultiPV") {
+            match value {
+                Some(lines) => self.multipv = lines.clamp(1, MAX_MULTIPV),
+                None => self.send(String::from("info string MultiPV needs a number")),
+            }
+        }
+    }
+
+    // Ends the running search, which then sends its best move.
+    pub fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            let _ = search.thread.join();
+        }
+    }
+
+    fn send(&self, line: String) {
+        let _ = self.output.send(line);
+    }
+}
+
+// The board of a position command: "startpos" or "fen" and a FEN, then optionally "moves" and
+// moves in coordinate notation.
+pub fn parse_position(tokens: &[&str]) -> Result<Board, String> {
+    let moves_at = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+    let mut board = match tokens.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => Board::from_fen(&tokens[1..moves_at].join(" ")).map_err(|e| format!("invalid position: {}", e))?,
+        _ => return Err(String::from("position needs startpos or fen")),
+    };
+    for move_str in tokens.iter().skip(moves_at + 1) {
+        match BoardMove::from(move_str).filter(|m| board.is_legal_move(*m)) {
+            Some(m) => {
+                board.make_move(m);
+            }
+            None => return Err(format!("illegal move {}", move_str)),
+        }
+    }
+    Ok(board)
+}
+
+// The limits of a go command for the side to move. A fixed depth or time is used as given,
+// otherwise a share of the side's remaining time. Without any, the search goes on until stop.
+pub fn parse_go(tokens: &[&str], turn: Team) -> GoLimits {
+    let value = |name: &str| -> Option<u64> {
+        let at = tokens.iter().position(|t| *t == name)?;
+        tokens.get(at + 1).and_then(|v| v.parse().ok())
+    };
+    let (time, increment) = match turn {
+        Team::Light => (value("wtime"), value("winc")),
+        Team::Dark => (value("btime"), value("binc")),
+    };
+    let infinite = tokens.contains(&"infinite");
+    let ponder = tokens.contains(&"ponder");
+
+    let limit = if let Some(depth) = value("depth") {
+        SearchLimit::Depth(u32::try_from(depth).unwrap_or(u32::MAX))
+    }
+    else if let Some(ms) = value("movetime") {
+        SearchLimit::MoveTime(Duration::from_millis(ms))
+    }
+    else if let (Some(ms), false) = (time, infinite) {
+        let time = Duration::from_millis(ms);
+        let moves_to_go = value("movestogo").map_or(DEFAULT_MOVES_TO_GO, |m| m.max(1) as u32);
+        let share = time / moves_to_go + Duration::from_millis(increment.unwrap_or(0)) / 2;
+        SearchLimit::MoveTime(share.min(time.saturating_sub(MOVE_OVERHEAD)).max(Duration::from_millis(10)))
+    }
+    else {
+        return GoLimits { limit: SearchLimit::Infinite, infinite: true, ponder };
+    };
+    GoLimits { limit, infinite, ponder }
+}
+
+// The info line of a completed depth, for one of several lines when `multipv` is given.
+fn info_line(result: &SearchResult, multipv: Option<usize>, nodes: u64, time: Duration) -> String {
+    let score = match result.get_score() {
+        Some(EngineScore::Centipawns(cp)) => format!(" score cp {}", cp),
+        Some(EngineScore::Mate(moves)) => format!(" score mate {}", moves),
+        None => String::new(),
+    };
+    let info = result.get_info();
+    let pv: Vec<String> = info.get_pv().iter().map(|m| m.to_string()).collect();
+    let multipv = multipv.map(|m| format!(" multipv {}", m)).unwrap_or_default();
+    format!("info depth {}{}{} nodes {} nps {} time {} pv {}", info.get_depth().unwrap_or(0), multipv, score, nodes,
+        info.get_nps().unwrap_or(0), time.as_millis(), pv.join(" "))
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_uci_server {
+    use super::*;
+
+    #[test]
+    pub fn positions_are_set_up() {
+        let board = parse_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        let fen = "8/8/4k3/8/8/3K4/8/7R w - - 0 60";
+        let board = parse_position(&fen_tokens(fen)).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(parse_position(&["startpos", "moves", "e2e5"]), Err(String::from("illegal move e2e5")));
+        assert!(parse_position(&["fen", "nonsense"]).is_err());
+    }
+
+    fn fen_tokens(fen: &str) -> Vec<&str> {
+        std::iter::once("fen").chain(fen.split_whitespace()).collect()
+    }
+
+    #[test]
+    pub fn go_limits_follow_the_clock() {
+        assert_eq!(parse_go(&["depth", "5"], Team::Light), GoLimits { limit: SearchLimit::Depth(5), infinite: false, ponder: false });
+        assert_eq!(parse_go(&["movetime", "200"], Team::Dark).limit, SearchLimit::MoveTime(Duration::from_millis(200)));
+        let clock = ["wtime", "60000", "btime", "30000", "winc", "1000", "binc", "1000"];
+        assert_eq!(parse_go(&clock, Team::Light).limit, SearchLimit::MoveTime(Duration::from_millis(2500)));
+        assert_eq!(parse_go(&clock, Team::Dark).limit, SearchLimit::MoveTime(Duration::from_millis(1500)));
+        assert_eq!(parse_go(&["btime", "30", "movestogo", "1"], Team::Dark).limit, SearchLimit::MoveTime(Duration::from_millis(10)));
+        assert!(parse_go(&["infinite"], Team::Light).infinite);
+        assert!(parse_go(&[], Team::Light).infinite);
+    }
+
+    #[test]
+    pub fn session_answers_with_a_best_move() {
+        let (output, lines) = mpsc::channel();
+        let mut server = UciServer::new(output);
+        for command in ["uci", "isready", "position fen 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "go depth 2"] {
+            assert!(server.handle(command));
+        }
+        server.stop_search();
+        assert!(!server.handle("quit"));
+        let lines: Vec<String> = lines.try_iter().collect();
+        assert!(lines[0].starts_with("id name Rust Chess"));
+        assert_eq!(lines[4..6], ["uciok", "readyok"]);
+        assert!(lines.iter().any(|l| l.starts_with("info depth 2 score mate 1 ")));
+        assert_eq!(lines.last().unwrap(), "bestmove d1d8");
+    }
+
+    #[test]
+    pub fn infinite_search_waits_for_stop() {
+        let (output, lines) = mpsc::channel();
+        let mut server = UciServer::new(output);
+        server.handle("position fen 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        server.handle("go infinite");
+        // The mate ends the search, the move is still held back until stop.
+        thread::sleep(Duration::from_millis(200));
+        assert!(lines.try_iter().all(|l| !l.starts_with("bestmove")));
+        server.handle("stop");
+        assert_eq!(lines.try_iter().last().unwrap(), "bestmove d1d8");
+    }
+
+    #[test]
+    pub fn pondering_waits_for_ponderhit() {
+        let (output, lines) = mpsc::channel();
+        let mut server = UciServer::new(output);
+        server.handle("position fen 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        server.handle("go ponder depth 2");
+        thread::sleep(Duration::from_millis(200));
+        assert!(lines.try_iter().all(|l| !l.starts_with("bestmove")));
+        server.handle("ponderhit");
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(lines.try_iter().last().unwrap(), "bestmove d1d8");
+    }
+
+    #[test]
+    pub fn multipv_reports_every_line() {
+        let (output, lines) = mpsc::channel();
+        let mut server = UciServer::new(output);
+        for command in ["setoption name MultiPV value 3", "position fen 6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "go depth 2"] {
+            server.handle(command);
+        }
+        server.stop_search();
+        let lines: Vec<String> = lines.try_iter().collect();
+        assert!(lines.iter().any(|l| l.starts_with("info depth 2 multipv 1 score mate 1 ")));
+        assert!(lines.iter().any(|l| l.starts_with("info depth 2 multipv 3 ")));
+        assert_eq!(lines.last().unwrap(), "bestmove d1d8");
+    }
+}
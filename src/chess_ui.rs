@@ -1,6 +1,14 @@
 pub mod tui;
+#[cfg(feature = "gui")]
 pub mod gui;
 
-pub fn ui_main() {
-    tui::tui_main();
-}
\ No newline at end of file
+use crate::chess_cmd::ChessArgs;
+
+pub fn ui_main(args: &ChessArgs) {
+    #[cfg(feature = "gui")]
+    if args.gui {
+        gui::gui_main(args);
+        return;
+    }
+    tui::tui_main(args);
+}
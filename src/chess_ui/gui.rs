@@ -1,4 +1,380 @@
+/*
+gui.rs
+The window rust_chess --gui opens, built with the gui feature. The board is played by dragging a
+piece to where it goes, or by clicking it and then its destination, pawns reaching the last rank
+becoming queens; any other promotion is typed, e.g. move b8=N. Beside the board are the clocks, the
+moves and the messages of the game, below it the status and a line taking the same commands as
+the terminal. The game itself is played by the terminal interface's game loop, see
+tui/window.rs, so everything set up in the config file works in the window too.
+*/
 
-pub fn gui_main() {
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, OnceLock,
+    },
+    thread,
+};
+use eframe::egui::{
+    self, Align2, Color32, FontId, Painter, Pos2, Rect, RichText, ScrollArea, Sense, Stroke, StrokeKind, Vec2,
+};
 
-}
\ No newline at end of file
+use crate::{
+    chess_cmd::ChessArgs,
+    chess_common::ChessPiece,
+    chess_core::{Board, BoardSquare, Piece, Team},
+};
+use super::tui::{window_game, WindowUpdate, WindowView};
+
+const LIGHT_SQUARE: Color32 = Color32::from_rgb(240, 217, 181);
+const DARK_SQUARE: Color32 = Color32::from_rgb(181, 136, 99);
+// Laid over the squares of the last move and the piece picked up.
+const LAST_MOVE_TINT: Color32 = Color32::from_rgba_premultiplied(155, 155, 0, 90);
+const SELECTED_TINT: Color32 = Color32::from_rgba_premultiplied(20, 85, 30, 110);
+const DESTINATION_DOT: Color32 = Color32::from_rgba_premultiplied(20, 85, 30, 130);
+// Messages kept in the log, the oldest go first.
+const MESSAGE_LOG_LENGTH: usize = 500;
+
+// Opens the window and plays the game in it until it is closed or the player quits.
+pub fn gui_main(args: &ChessArgs) {
+    let (commands, command_receiver) = mpsc::channel();
+    let (update_sender, updates) = mpsc::channel();
+    // The game wakes the window when it has something to show, once the window is open.
+    let context: Arc<OnceLock<egui::Context>> = Arc::default();
+    let waker = context.clone();
+    let game_args = ChessArgs {
+        no_color: args.no_color,
+        config: args.config.clone(),
+        broadcast: args.broadcast.clone(),
+        ..ChessArgs::default()
+    };
+    let game = thread::spawn(move || {
+        let wake = Box::new(move || {
+            if let Some(ctx) = waker.get() {
+                ctx.request_repaint();
+            }
+        });
+        window_game(&game_args, command_receiver, update_sender, wake);
+    });
+
+    // The game is set up before the window opens, so it opens on the game's first view.
+    let mut messages = Vec::new();
+    let view = loop {
+        match updates.recv() {
+            Ok(WindowUpdate::View(view)) => break *view,
+            Ok(WindowUpdate::Messages(mut m)) => messages.append(&mut m),
+            Ok(WindowUpdate::Closed) | Err(_) => {
+                let _ = game.join();
+                return;
+            }
+        }
+    };
+    let window = ChessWindow {
+        commands,
+        updates,
+        view,
+        messages,
+        input: String::new(),
+        selected: None,
+        dragging: None,
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([960.0, 680.0]).with_min_inner_size([480.0, 360.0]),
+        ..Default::default()
+    };
+    let opened = eframe::run_native("Rust Chess", options, Box::new(move |cc| {
+        let _ = context.set(cc.egui_ctx.clone());
+        Ok(Box::new(window))
+    }));
+    if let Err(e) = opened {
+        println!("Could not open the window: {e}.");
+    }
+    // The window is gone, which ends the game. It is waited for so the autosave is written.
+    let _ = game.join();
+}
+
+struct ChessWindow {
+    commands: Sender<String>,
+    updates: Receiver<WindowUpdate>,
+    view: WindowView,
+    messages: Vec<String>,
+    // The command line being typed.
+    input: String,
+    // The piece clicked, whose moves are shown, and the piece being dragged.
+    selected: Option<BoardSquare>,
+    dragging: Option<BoardSquare>,
+}
+
+impl eframe::App for ChessWindow {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.take_updates(ctx);
+
+        egui::TopBottomPanel::bottom("command").show(ctx, |ui| {
+            ui.add_space(4.0);
+            for line in &self.view.status {
+                ui.label(line);
+            }
+            let response = ui.add(egui::TextEdit::singleline(&mut self.input)
+                .hint_text("Type a command, e.g. move e4 or help")
+                .desired_width(f32::INFINITY));
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                let line = std::mem::take(&mut self.input);
+                if !line.trim().is_empty() {
+                    self.send(line);
+                }
+                response.request_focus();
+            }
+            ui.add_space(4.0);
+        });
+
+        egui::SidePanel::right("game").min_width(260.0).show(ctx, |ui| {
+            self.clocks_ui(ui);
+            ui.horizontal(|ui| {
+                for (label, command) in [("Flip", "flip"), ("Take back", "undo"), ("Resign", "resign"), ("New game", "new")] {
+                    if ui.button(label).clicked() {
+                        self.send(String::from(command));
+                    }
+                }
+            });
+            ui.separator();
+            ui.heading("Moves");
+            ScrollArea::vertical().id_salt("moves").max_height(ui.available_height() / 2.0).stick_to_bottom(true).show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                for entry in &self.view.moves {
+                    ui.monospace(entry);
+                }
+            });
+            ui.separator();
+            ScrollArea::vertical().id_salt("messages").stick_to_bottom(true).show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                for message in &self.messages {
+                    ui.label(message);
+                }
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| self.board_ui(ui));
+    }
+}
+
+impl ChessWindow {
+    // Shows what the game sent since the last frame.
+    fn take_updates(&mut self, ctx: &egui::Context) {
+        while let Ok(update) = self.updates.try_recv() {
+            match update {
+                WindowUpdate::View(view) => {
+                    self.view = *view;
+                    // A picked up piece stays picked up as the clocks tick, not once it can't move.
+                    if self.selected.is_some_and(|s| !can_pick_up(&self.view.board, s)) {
+                        self.selected = None;
+                    }
+                }
+                WindowUpdate::Messages(mut messages) => {
+                    self.messages.append(&mut messages);
+                    let excess = self.messages.len().saturating_sub(MESSAGE_LOG_LENGTH);
+                    self.messages.drain(..excess);
+                }
+                WindowUpdate::Closed => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            }
+        }
+    }
+
+    // Runs a command in the game, showing it in the log as the full screen interface does.
+    fn send(&mut self, command: String) {
+        self.messages.push(format!(">> {command}"));
+        self.selected = None;
+        let _ = self.commands.send(command);
+    }
+
+    // The clocks of both sides, the one shown at the top of the board first, marked while they run.
+    fn clocks_ui(&self, ui: &mut egui::Ui) {
+        let clocks = match &self.view.clocks {
+            Some(c) => c,
+            None => return,
+        };
+        let sides = if self.view.flipped { [Team::Light, Team::Dark] } else { [Team::Dark, Team::Light] };
+        for team in sides {
+            let time = &clocks[if team == Team::Light { 0 } else { 1 }];
+            let running = self.view.clock_running == Some(team);
+            let mut text = RichText::new(format!("{} {team}  {time}", if running { '▶' } else { ' ' })).monospace().size(22.0);
+            if running {
+                text = text.strong();
+            }
+            ui.label(text);
+        }
+        ui.separator();
+    }
+
+    fn board_ui(&mut self, ui: &mut egui::Ui) {
+        let side = ui.available_width().min(ui.available_height()).floor().max(8.0 * 24.0);
+        let (response, painter) = ui.allocate_painter(Vec2::splat(side), Sense::click_and_drag());
+        let board_rect = response.rect;
+        let flipped = self.view.flipped;
+        let pointer = response.interact_pointer_pos().or(response.hover_pos());
+        let pointer_square = pointer.and_then(|p| square_at(board_rect, p, flipped));
+
+        if response.drag_started() {
+            self.dragging = pointer_square.filter(|s| can_pick_up(&self.view.board, *s));
+            self.selected = self.dragging;
+        }
+        if response.drag_stopped() {
+            if let (Some(from), Some(to)) = (self.dragging.take(), pointer_square) {
+                if let Some(command) = move_command(&self.view.board, from, to) {
+                    self.send(command);
+                }
+            }
+        }
+        if response.clicked() {
+            if let Some(square) = pointer_square {
+                self.click(square);
+            }
+        }
+
+        self.draw_board(&painter, board_rect, pointer);
+    }
+
+    // A click moves the picked up piece to the square, or picks up the piece there.
+    fn click(&mut self, square: BoardSquare) {
+        if let Some(command) = self.selected.and_then(|from| move_command(&self.view.board, from, square)) {
+            self.send(command);
+        }
+        else if self.selected != Some(square) && can_pick_up(&self.view.board, square) {
+            self.selected = Some(square);
+        }
+        else {
+            self.selected = None;
+        }
+    }
+
+    fn draw_board(&self, painter: &Painter, board_rect: Rect, pointer: Option<Pos2>) {
+        let board = &self.view.board;
+        let flipped = self.view.flipped;
+        let size = board_rect.width() / 8.0;
+        let destinations: Vec<BoardSquare> = self.selected
+            .map(|s| board.legal_moves_from(s).iter().map(|m| m.get_to()).collect())
+            .unwrap_or_default();
+        let label_font = FontId::proportional(size * 0.18);
+        for square in BoardSquare::all() {
+            let rect = square_rect(board_rect, square, flipped);
+            let (file, rank) = (square.get_file().as_usize(), square.get_rank().as_usize());
+            let light = (file + rank) % 2 == 1;
+            let (color, label_color) = if light { (LIGHT_SQUARE, DARK_SQUARE) } else { (DARK_SQUARE, LIGHT_SQUARE) };
+            painter.rect_filled(rect, 0.0, color);
+            if self.view.last_move.is_some_and(|m| m.get_from() == square || m.get_to() == square) {
+                painter.rect_filled(rect, 0.0, LAST_MOVE_TINT);
+            }
+            if self.selected == Some(square) {
+                painter.rect_filled(rect, 0.0, SELECTED_TINT);
+            }
+            // The files along the bottom edge and the ranks along the left one.
+            let (column, row) = column_row(square, flipped);
+            if row == 7 {
+                painter.text(rect.right_bottom() - Vec2::splat(size * 0.06), Align2::RIGHT_BOTTOM, square.get_file(), label_font.clone(), label_color);
+            }
+            if column == 0 {
+                painter.text(rect.left_top() + Vec2::splat(size * 0.06), Align2::LEFT_TOP, square.get_rank(), label_font.clone(), label_color);
+            }
+            if let Some(piece) = board.get_piece_at(square).filter(|_| self.dragging != Some(square)) {
+                draw_piece(painter, piece, rect.center(), size);
+            }
+            if destinations.contains(&square) {
+                match board.get_piece_at(square) {
+                    Some(_) => { painter.rect_stroke(rect.shrink(2.0), 0.0, Stroke::new(size * 0.06, DESTINATION_DOT), StrokeKind::Inside); }
+                    None => { painter.circle_filled(rect.center(), size * 0.15, DESTINATION_DOT); }
+                }
+            }
+        }
+        // The dragged piece follows the pointer, above the board.
+        if let (Some(square), Some(pointer)) = (self.dragging, pointer) {
+            if let Some(piece) = board.get_piece_at(square) {
+                draw_piece(painter, piece, pointer, size);
+            }
+        }
+    }
+}
+
+// Draws a piece as the filled chess symbol in the color of its side, outlined by the hollow one.
+fn draw_piece(painter: &Painter, piece: Piece, center: Pos2, size: f32) {
+    let piece_type = piece.get_piece_type();
+    let font = FontId::proportional(size * 0.78);
+    let fill = if *piece.get_team() == Team::Light { Color32::WHITE } else { Color32::from_gray(25) };
+    painter.text(center, Align2::CENTER_CENTER, Piece::new(Team::Dark, piece_type).get_unicode_symbol(), font.clone(), fill);
+    painter.text(center, Align2::CENTER_CENTER, Piece::new(Team::Light, piece_type).get_unicode_symbol(), font, Color32::BLACK);
+}
+
+// Whether the piece on the square can be picked up, being of the side to move.
+fn can_pick_up(board: &Board, square: BoardSquare) -> bool {
+    board.get_piece_at(square).is_some_and(|p| *p.get_team() == board.get_turn())
+}
+
+// The command moving the piece on one square to another, promoting to a queen, or None when the
+// piece can't go there.
+fn move_command(board: &Board, from: BoardSquare, to: BoardSquare) -> Option<String> {
+    let board_move = board.legal_moves_from(from).into_iter()
+        .find(|m| m.get_to() == to && m.get_promotion().is_none_or(|p| p == ChessPiece::Queen))?;
+    Some(format!("move {}", board.to_chess_move(board_move)))
+}
+
+// The column and row a square is drawn in, counted from the top left, with White at the bottom
+// or with Black at the bottom when flipped.
+fn column_row(square: BoardSquare, flipped: bool) -> (usize, usize) {
+    let (file, rank) = (square.get_file().as_usize(), square.get_rank().as_usize());
+    if flipped { (7 - file, rank) } else { (file, 7 - rank) }
+}
+
+fn square_rect(board_rect: Rect, square: BoardSquare, flipped: bool) -> Rect {
+    let size = board_rect.width() / 8.0;
+    let (column, row) = column_row(square, flipped);
+    Rect::from_min_size(board_rect.min + Vec2::new(column as f32 * size, row as f32 * size), Vec2::splat(size))
+}
+
+// The square under a point, None when the point is off the board.
+fn square_at(board_rect: Rect, pos: Pos2, flipped: bool) -> Option<BoardSquare> {
+    if !board_rect.contains(pos) {
+        return None;
+    }
+    let size = board_rect.width() / 8.0;
+    let column = (((pos.x - board_rect.left()) / size) as usize).min(7);
+    let row = (((pos.y - board_rect.top()) / size) as usize).min(7);
+    if flipped {
+        BoardSquare::from_indices(7 - column, row)
+    }
+    else {
+        BoardSquare::from_indices(column, 7 - row)
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_gui {
+    use super::*;
+
+    fn square(s: &str) -> BoardSquare {
+        BoardSquare::from(s).unwrap()
+    }
+
+    #[test]
+    pub fn points_map_to_squares_either_way_up() {
+        let board_rect = Rect::from_min_size(Pos2::new(10.0, 20.0), Vec2::splat(400.0));
+        assert_eq!(square_at(board_rect, Pos2::new(15.0, 25.0), false), Some(square("a8")));
+        assert_eq!(square_at(board_rect, Pos2::new(15.0, 25.0), true), Some(square("h1")));
+        assert_eq!(square_at(board_rect, Pos2::new(409.0, 419.0), false), Some(square("h1")));
+        assert_eq!(square_at(board_rect, Pos2::new(5.0, 25.0), false), None);
+        for flipped in [false, true] {
+            for s in BoardSquare::all() {
+                assert_eq!(square_at(board_rect, square_rect(board_rect, s, flipped).center(), flipped), Some(s));
+            }
+        }
+    }
+
+    #[test]
+    pub fn dropped_pieces_become_move_commands() {
+        let board = Board::new();
+        assert_eq!(move_command(&board, square("g1"), square("f3")), Some(String::from("move Nf3")));
+        assert_eq!(move_command(&board, square("e2"), square("e5")), None);
+        assert!(can_pick_up(&board, square("e2")) && !can_pick_up(&board, square("e7")));
+        let board = Board::from_fen("8/1P6/8/7k/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(move_command(&board, square("b7"), square("b8")), Some(String::from("move b8=Q")));
+    }
+}
@@ -1,21 +1,92 @@
 use std::{
-    fmt::{
-        Display,
-        Formatter
-    },
-    io::{Write},
+    cmp::Ordering,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    style::Print,
+    terminal::{Clear, ClearType},
 };
-use clap::Parser;
 
 use crate::{
     chess_core::{
         Board,
+        BoardMove,
+        BoardSquare,
+        DiagramStyle,
+        MoveResolveError,
+        Piece,
         Team
     },
-    chess_cmd::{ChessTuiCmd, ChessCommands},
-    chess_pgn::{ChessMove},
+    chess_engine,
+    chess_clock::{ChessClock, format_clock_time},
+    chess_cmd::{ChessArgs, ChessTuiCmd, ChessCommands, ClaimAction, ConfigAction, ExportAction, GameAction, VariationAction, CommandDispatcher, CommandOutcome, split_command_line},
+    chess_common::{ChessFile, ChessPiece, ChessRank},
+    chess_config::{BoardSize, BoardTheme, ChessConfig, ConfigError, GlyphWidth, KeyAction, Language, PieceStyle},
+    chess_game::{GameMoveError, GameState, GameStatus},
+    chess_http::HttpServer,
+    chess_pgn::{ChessMove, PgnAnnotation, PgnResult, nag_suffix_annotation, parse_pgn_games, suffix_annotation_nag},
+    chess_rating::RatingList,
 };
 
+mod analysis;
+mod annotate;
+mod archive;
+mod autosave;
+mod blunder;
+mod broadcast;
+mod explore;
+mod frame;
+mod frontend;
+mod http;
+mod import;
+mod locale;
+mod notify;
+mod opening;
+mod opponent;
+mod prompt;
+mod rating;
+mod recovery;
+mod remote;
+mod rules;
+mod rush;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod screen;
+mod session;
+mod simul;
+mod tutorial;
+mod watch;
+#[cfg(feature = "gui")]
+mod window;
+
+use analysis::Analysis;
+use autosave::Autosave;
+use broadcast::Broadcast;
+use explore::ExplorerCache;
+use frontend::{FrontendInput, UiFrontend};
+use locale::Locale;
+use opening::OpeningTrainer;
+use opponent::Opponent;
+use prompt::{LinePrompt, PromptPrinter};
+use remote::RemoteSession;
+use rush::PuzzleRush;
+#[cfg(feature = "scripting")]
+use scripting::Scripts;
+use simul::Simul;
+use tutorial::Tutorial;
+use watch::{WatchSession, WATCH_TICK};
+pub use archive::archive_pgn_game;
+#[cfg(feature = "gui")]
+pub use window::{window_game, WindowUpdate, WindowView};
+pub use watch::game_ending;
+
 const TERMINAL_COLOR_RESET: &str        = "\u{001b}[0m";
 const TERMINAL_FG_COLOR_BLACK: &str     = "\u{001b}[30m";
 const TERMINAL_FG_COLOR_RED: &str       = "\u{001b}[31m";
@@ -35,141 +106,2330 @@ const TERMINAL_BG_COLOR_MAGENTA: &str   = "\u{001b}[45m";
 const TERMINAL_BG_COLOR_CYAN: &str      = "\u{001b}[46m";
 const TERMINAL_BG_COLOR_WHITE: &str     = "\u{001b}[47m";
 
-pub fn tui_main() {
-    let mut game: Board = Board::new();
-    let mut user_input;
-
-    loop {
-        println!("{game}");
-        print!(">> ");
-        std::io::stdout().flush().unwrap();
-        user_input = get_user_input();
-        user_input.insert_str(0, ">> ");
-        let parse_result = ChessTuiCmd::try_parse_from(user_input.split_whitespace());
-        match parse_result {
-            Ok(input_cmd) => {
-                match input_cmd.command {
-                    ChessCommands::Move { pgn_move } => {
-                        let parsed_move_result = ChessMove::from(&pgn_move);
-                        match parsed_move_result {
-                            Ok(parsed_move) => {
-                                println!("Entered move: {}", parsed_move);
-                            }
-                            Err(e) => {
-                                println!("Invalid move: {pgn_move}");
-                            }
-                        }
-                    }
-                    ChessCommands::Undo { undo_count } => {
-                        let num = match undo_count {
-                            Some(n) => n,
-                            None => 1,
-                        };
-                        println!("Undoing {} move(s)", num);
-                    },
-                    ChessCommands::Redo { redo_count } => {
-                        let num = match redo_count {
-                            Some(n) => n,
-                            None => 1,
-                        };
-                        println!("Redoing {} move(s)", num);
-                    },
-                    ChessCommands::Reset => {
-                        println!("Resetting board.");
-                        game.new_game();
-                    },
-                    ChessCommands::Save { file_path } => {
-                        println!("Saving game to file: {}", file_path);
-                    },
-                    ChessCommands::Load { file_path } => {
-                        println!("Loading game from file: {}", file_path);
-                    },
-                    ChessCommands::Quit => {
-                        println!("Quitting game.");
+pub fn tui_main(args: &ChessArgs) {
+    let mut game = start_game(args);
+    let screen_reader = game.config.get_screen_reader();
+
+    // The full screen interface needs a terminal on both ends, input piped in from a file or
+    // output redirected to one gets the line by line interface. So do screen readers, which
+    // read new lines as they are printed.
+    if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() && !screen_reader {
+        if let Err(e) = screen::screen_main(&mut game) {
+            println!("{}", game.locale.screen_failed(&e.to_string()));
+            line_main(&mut game);
+        }
+    }
+    else {
+        line_main(&mut game);
+    }
+    end_game(&mut game);
+}
+
+// Sets up the game from the config file and the command line, whichever way it is played.
+fn start_game(args: &ChessArgs) -> TuiGame {
+    let config_path = args.config.clone().or_else(ChessConfig::default_path);
+    let config = match &config_path {
+        Some(path) => match ChessConfig::load(path) {
+            Ok(c) => c,
+            // Not having a config file is fine unless one was asked for.
+            Err(ConfigError::Io(_, e)) if e.kind() == io::ErrorKind::NotFound && args.config.is_none() => ChessConfig::new(),
+            // The config's language isn't known, the environment's is used.
+            Err(e) => {
+                println!("{}", Locale::from_env().unwrap_or_default().config_not_read(&e.to_string()));
+                ChessConfig::new()
+            }
+        },
+        None => ChessConfig::new(),
+    };
+
+    let mut game = TuiGame::with_config(config, config_path);
+    game.view.plain = !use_color(args);
+    let screen_reader = game.config.get_screen_reader();
+    game.env_locale = Locale::from_env();
+    if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() && !screen_reader {
+        game.measured_glyph_width = measure_glyph_width();
+    }
+    game.apply_config();
+    start_autosave(&mut game);
+    rating::load_ratings(&mut game);
+    game.archive_dir = game.config.get_archive_dir().cloned().or_else(ChessConfig::default_archive_dir);
+    game.training_path = ChessConfig::default_training_path();
+    game.puzzles_path = ChessConfig::default_puzzles_path();
+    #[cfg(feature = "scripting")]
+    scripting::load_scripts(&mut game);
+    if let Some(target) = &args.broadcast {
+        match Broadcast::start(target) {
+            Ok(broadcast) => {
+                game.say(game.locale.broadcasting(&broadcast.get_description().to_string()));
+                game.broadcast = Some(broadcast);
+                game.advance_broadcast();
+            }
+            Err(e) => println!("{}", game.locale.broadcast_failed(target, &e.to_string())),
+        }
+    }
+
+    recovery::install(ChessConfig::default_recovery_dir().unwrap_or_else(std::env::temp_dir));
+    recovery::remember(&game.state, game.locale);
+    game
+}
+
+fn end_game(game: &mut TuiGame) {
+    // However the game was left, the autosave file is brought up to date.
+    game.write_autosave();
+    for message in game.take_messages() {
+        println!("{message}");
+    }
+}
+
+// Turns on autosaving, first offering to restore an unfinished game left by an earlier
+// session, with its clocks and the moves taken back. The offer needs someone at the terminal to
+// answer it.
+fn start_autosave(game: &mut TuiGame) {
+    let path = match game.config.get_autosave_path().cloned().or_else(ChessConfig::default_autosave_path) {
+        Some(p) => p,
+        None => return,
+    };
+    if std::io::stdin().is_terminal() {
+        if let Some(pgn_game) = Autosave::find_unfinished(&path) {
+            print!("{}", game.locale.offer_autosave(pgn_game.get_ply_count(), &path.display().to_string()));
+            std::io::stdout().flush().unwrap();
+            if game.locale.is_yes(&get_user_input().unwrap_or_default()) {
+                match game.restore_autosave(pgn_game, &Autosave::get_session_path(&path)) {
+                    Ok(()) => game.say(game.locale.restored_autosave(&path.display().to_string())),
+                    Err(e) => println!("{}", game.locale.autosave_not_restored(&e.to_string())),
+                }
+            }
+        }
+    }
+    game.autosave = Some(Autosave::new(path));
+}
+
+// Colors are used unless turned off with --no-color or the NO_COLOR environment variable
+// (https://no-color.org), or when the output goes somewhere other than a terminal.
+fn use_color(args: &ChessArgs) -> bool {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !args.no_color && !no_color_env && std::io::stdout().is_terminal()
+}
+
+// The columns the terminal draws a chess symbol in, found by drawing one at the start of the line
+// and asking the terminal where its cursor is. None when the terminal doesn't answer.
+fn measure_glyph_width() -> Option<usize> {
+    let mut out = io::stdout();
+    execute!(out, Print('\r'), Print('\u{265A}')).ok()?;
+    let position = crossterm::cursor::position();
+    let _ = execute!(out, Print('\r'), Clear(ClearType::CurrentLine));
+    position.ok().map(|(column, _)| column as usize)
+}
+
+// Prints the board and reads one command per line. On a terminal the board is redrawn in place
+// with the last command and its messages below it, output going elsewhere, or to a screen reader,
+// gets a new copy of the board after every command. Typed at a terminal, the line can be edited
+// and earlier commands recalled.
+fn line_main(game: &mut TuiGame) {
+    let terminal = std::io::stdout().is_terminal();
+    let editing = terminal && std::io::stdin().is_terminal();
+    let (prompt, printer) = match editing.then(|| LinePrompt::new(ChessConfig::default_history_path())).flatten() {
+        Some((prompt, printer)) => (Some(prompt), Some(printer)),
+        None => (None, None),
+    };
+    // Lines are read on a thread of their own, so a served game goes on while waiting for one.
+    // The edited prompt waits to be asked for each line, so it comes after the board is drawn.
+    let (sender, input) = mpsc::channel();
+    let (ask, asked) = mpsc::channel();
+    thread::spawn(move || {
+        let mut prompt = prompt;
+        loop {
+            let line = match &mut prompt {
+                Some(prompt) => asked.recv().ok().and_then(|()| prompt.read_line()),
+                None => get_user_input(),
+            };
+            match line {
+                Some(line) => {
+                    if sender.send(line).is_err() {
                         break;
-                    },
+                    }
                 }
-            },
-            Err(e) => println!("{e}"),
+                None => break,
+            }
+        }
+    });
+    let mut frontend = LineFrontend {
+        input,
+        ask,
+        printer,
+        messages: Vec::new(),
+        redraw: terminal && !game.config.get_screen_reader(),
+        prompting: false,
+    };
+    // Line mode writes to stdout, which doesn't fail in a way worth stopping for.
+    let _ = frontend::run_frontend(game, &mut frontend);
+}
+
+// Line mode: the board is printed, or redrawn in place at a terminal, with the messages since the
+// last time, and commands are read a line at a time.
+struct LineFrontend {
+    input: Receiver<String>,
+    // Asks the edited prompt for the next line.
+    ask: Sender<()>,
+    // Prints messages above the line being edited.
+    printer: Option<PromptPrinter>,
+    messages: Vec<String>,
+    redraw: bool,
+    // Whether the prompt is waiting for a line.
+    prompting: bool,
+}
+
+impl UiFrontend for LineFrontend {
+    fn render(&mut self, game: &TuiGame) -> io::Result<()> {
+        // Messages while a line is edited were printed above it, drawing again would lose it.
+        if self.prompting && self.printer.is_some() {
+            return Ok(());
+        }
+        draw_line_mode(game, &mut self.messages, self.redraw);
+        match self.printer {
+            Some(_) if !self.prompting => {
+                let _ = self.ask.send(());
+            }
+            Some(_) => (),
+            None => print_prompt(),
+        }
+        self.prompting = true;
+        Ok(())
+    }
+
+    // While a line is edited the messages are printed above it rather than drawing the board
+    // again, and when the board is redrawn in place they are shown below it again after the
+    // next command.
+    fn notify(&mut self, mut messages: Vec<String>) {
+        if messages.is_empty() {
+            return;
+        }
+        match &mut self.printer {
+            Some(printer) if self.prompting => {
+                let _ = printer.print(messages.join("\n"));
+                if self.redraw {
+                    self.messages.append(&mut messages);
+                }
+            }
+            _ => self.messages.append(&mut messages),
+        }
+    }
+
+    // A watched engine game plays out, and the remote player makes their move, before the next
+    // command is taken.
+    fn read_command(&mut self, game: &mut TuiGame, timeout: Duration) -> io::Result<FrontendInput> {
+        if game.watch.is_some() || game.is_remote_turn() {
+            thread::sleep(timeout);
+            return Ok(FrontendInput::Idle);
+        }
+        match self.input.recv_timeout(timeout) {
+            Ok(line) => {
+                self.prompting = false;
+                if self.redraw {
+                    self.messages.push(format!(">> {}", line.trim_end()));
+                }
+                Ok(FrontendInput::Command(line))
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(FrontendInput::Idle),
+            // stdin was closed.
+            Err(RecvTimeoutError::Disconnected) => Ok(FrontendInput::Closed),
         }
     }
+
+    fn close(&mut self) {
+        print_messages(&mut self.messages);
+    }
+}
+
+// Shows the board and the messages since the last time, redrawing in place or below the last
+// output.
+fn draw_line_mode(game: &TuiGame, messages: &mut Vec<String>, redraw: bool) {
+    let analysis: String = game.analysis.iter().flat_map(|a| a.render()).map(|line| format!("  {line}\n")).collect();
+    if redraw {
+        let _ = execute!(std::io::stdout(), MoveTo(0, 0), Clear(ClearType::FromCursorDown));
+        println!("{}{analysis}", game.render());
+        print_messages(messages);
+    }
+    else {
+        print_messages(messages);
+        println!("{}{analysis}", game.render());
+    }
 }
 
-fn get_user_input() -> String {
+fn print_prompt() {
+    print!(">> ");
+    std::io::stdout().flush().unwrap();
+}
+
+fn print_messages(messages: &mut Vec<String>) {
+    for message in messages.drain(..) {
+        println!("{message}");
+    }
+}
+
+fn get_user_input() -> Option<String> {
     let mut user_input = String::new();
-    std::io::stdin().read_line(&mut user_input).unwrap();
-    user_input
+    match std::io::stdin().read_line(&mut user_input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(user_input),
+    }
 }
 
-#[derive(Clone, Copy)]
-enum ChessTuiCommands {
-    Move,
-    Undo,
-    Redo,
-    Reset,
-    Save,
-    Load,
-    Quit,
-    Help,
+// The game being played at the terminal.
+struct TuiGame {
+    state: GameState,
+    view: BoardView,
+
+    // A view used for the next render only, set by `show`.
+    shown_view: Option<BoardView>,
+
+    // Output of the commands run since the messages were last taken.
+    messages: Vec<String>,
+    // Alerts raised since they were last sent, whether one is showing in the window title, and
+    // which sides were alerted about their clock running low.
+    alerts: Vec<String>,
+    alert_in_title: bool,
+    low_time_alerted: [bool; 2],
+
+    // The moves an ambiguous move could be, while the player is asked which piece moves.
+    ambiguous_move: Option<Vec<BoardMove>>,
+
+    config: ChessConfig,
+    // Where `config save` writes the settings.
+    config_path: Option<PathBuf>,
+
+    // None when the game isn't autosaved, as in tests.
+    autosave: Option<Autosave>,
+
+    // The clocks of the game, when a time control is configured.
+    clock: Option<ChessClock>,
+
+    // The other open games, in the order they are listed with the current game left out, and
+    // where the current game goes in that list.
+    other_games: Vec<OpenGame>,
+    current_game: usize,
+
+    // The computer, when playing against it.
+    opponent: Option<Opponent>,
+    // The open games played against the computer at once, while they go on.
+    simul: Option<Simul>,
+
+    // Engines playing the current game, while watching them.
+    watch: Option<WatchSession>,
+
+    // The engine analyzing the shown position.
+    analysis: Option<Analysis>,
+
+    // The WebSocket and HTTP servers the current game is played over, while serving it.
+    remote: Option<RemoteSession>,
+    http: Option<HttpServer>,
+
+    // Where the game is written as it changes, for spectators.
+    broadcast: Option<Broadcast>,
+
+    // The tutorial or opening training, while it is shown in place of the game.
+    drill: Option<Drill>,
+    // The file the opening training record is kept in. None when it isn't kept, as in tests.
+    training_path: Option<PathBuf>,
+
+    // The puzzles of puzzle rush. None when the built-in ones are played, as in tests.
+    puzzles_path: Option<PathBuf>,
+
+    // The players' ratings, and the file they are kept in. None when they aren't kept, as in
+    // tests.
+    ratings: RatingList,
+    ratings_path: Option<PathBuf>,
+    // Whether the current game counts for the ratings, games between engines don't.
+    rated: bool,
+    // Whether the end of the current game was recorded, in the archive and the ratings.
+    recorded: bool,
+    // Where finished games are archived. None when they aren't, as in tests.
+    archive_dir: Option<PathBuf>,
+    // The game database as last indexed by explore.
+    explorer: Option<ExplorerCache>,
+    // The columns the terminal was found to draw chess symbols in. None when it wasn't measured,
+    // as when the output isn't a terminal.
+    measured_glyph_width: Option<usize>,
+    // The language of the messages, from the config or else the environment's, which is None
+    // when it isn't read, as in tests, or isn't one of the languages shipped.
+    locale: Locale,
+    env_locale: Option<Locale>,
+    // The scripts reacting to the game, None when there are none.
+    #[cfg(feature = "scripting")]
+    scripts: Option<Scripts>,
 }
 
-fn terminal_fg_color_256(c: u8) -> String {
-    format!("\u{001b}[38;5;{c}m")
+// Exercises played on a board of their own, leaving the game alone until they are stopped.
+enum Drill {
+    Tutorial(Tutorial),
+    Opening(OpeningTrainer),
+    Rush(PuzzleRush),
 }
 
-fn terminal_bg_color_256(c: u8) -> String {
-    format!("\u{001b}[48;5;{c}m")
+impl Drill {
+    fn get_board(&self) -> &Board {
+        match self {
+            Drill::Tutorial(tutorial) => tutorial.get_board(),
+            Drill::Opening(trainer) => trainer.get_board(),
+            Drill::Rush(rush) => rush.get_board(),
+        }
+    }
+
+    fn get_title(&self) -> String {
+        match self {
+            Drill::Tutorial(tutorial) => tutorial.get_title(),
+            Drill::Opening(trainer) => trainer.get_title(),
+            Drill::Rush(rush) => rush.get_title(),
+        }
+    }
+
+    // Told when a command would change the game while the drill runs.
+    fn get_busy_message(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Drill::Tutorial(_), Locale::English) => "The tutorial is running, use tutorial stop to leave it first.",
+            (Drill::Opening(_), Locale::English) => "Opening training is running, use stop to leave it first.",
+            (Drill::Rush(_), Locale::English) => "Puzzle rush is running, use stop to end it first.",
+            (Drill::Tutorial(_), Locale::German) => "Das Tutorial läuft, verlasse es zuerst mit tutorial stop.",
+            (Drill::Opening(_), Locale::German) => "Das Eröffnungstraining läuft, verlasse es zuerst mit stop.",
+            (Drill::Rush(_), Locale::German) => "Puzzle Rush läuft, beende es zuerst mit stop.",
+        }
+    }
 }
 
-impl Display for Board {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut output = String::new();
-        for r in (0..self.get_squares().len()).rev() {
-            // reset terminal colorization before newline character to avoid coloring the rest of the line.
-            output.push_str(TERMINAL_COLOR_RESET);
-            output.push('\n');
+// A game that is open but not the one being played. Its clock keeps running, as it would on a
+// table nearby.
+struct OpenGame {
+    state: GameState,
+    clock: Option<ChessClock>,
+    opponent: Option<Opponent>,
+    rated: bool,
+    recorded: bool,
+}
 
-            // display the row number
-            output.push_str(format!("{} ", r + 1).as_str());
-
-            // Set colorization for the next characters.
-            let light_bg_color = terminal_bg_color_256(180);
-            let light_fg_color = terminal_fg_color_256(255);
-            let dark_bg_color = terminal_bg_color_256(64);
-            let dark_fg_color = terminal_fg_color_256(240);
-            for f in 0..self.get_squares()[r].len() {
-                if r % 2 == 0 {
-                    if f % 2 == 0 {
-                        output.push_str(dark_bg_color.as_str());
-                    }
-                    else {
-                        output.push_str(light_bg_color.as_str());
+// How the board is drawn: its orientation, any highlighted squares and the keyboard cursor of
+// the full screen interface.
+#[derive(Clone, Default)]
+struct BoardView {
+    flipped: bool,
+    selected: Option<BoardSquare>,
+    destinations: Vec<BoardSquare>,
+    cursor: Option<BoardSquare>,
+
+    theme: BoardTheme,
+    pieces: PieceStyle,
+    size: BoardSize,
+    shapes: bool,
+
+    // Draw without colors, for NO_COLOR, --no-color and output that isn't a terminal.
+    plain: bool,
+    // The terminal draws chess symbols two columns wide, so they take a column of the square's
+    // padding.
+    wide_glyphs: bool,
+}
+
+impl TuiGame {
+    fn new() -> TuiGame {
+        TuiGame::with_config(ChessConfig::new(), None)
+    }
+
+    fn with_config(config: ChessConfig, config_path: Option<PathBuf>) -> TuiGame {
+        let mut game = TuiGame {
+            state: GameState::new(),
+            view: BoardView::default(),
+            shown_view: None,
+            messages: Vec::new(),
+            alerts: Vec::new(),
+            alert_in_title: false,
+            low_time_alerted: [false; 2],
+            ambiguous_move: None,
+            config,
+            config_path,
+            autosave: None,
+            clock: None,
+            other_games: Vec::new(),
+            current_game: 0,
+            opponent: None,
+            simul: None,
+            watch: None,
+            analysis: None,
+            remote: None,
+            http: None,
+            broadcast: None,
+            drill: None,
+            training_path: None,
+            puzzles_path: None,
+            ratings: RatingList::new(),
+            ratings_path: None,
+            rated: true,
+            recorded: false,
+            archive_dir: None,
+            explorer: None,
+            measured_glyph_width: None,
+            locale: Locale::English,
+            env_locale: None,
+            #[cfg(feature = "scripting")]
+            scripts: None,
+        };
+        game.apply_config();
+        game.new_game();
+        game
+    }
+
+    // Starts a new game, with the configured player as White and nobody as the opponent.
+    fn new_game(&mut self) {
+        self.state = GameState::new();
+        self.opponent = None;
+        self.rated = true;
+        self.recorded = false;
+        if let Some(name) = self.config.get_player_name() {
+            self.state.get_pgn_game_mut().set_white(name.clone());
+        }
+        self.reset_clock();
+        if let Some(time_control) = self.config.get_time_control() {
+            self.state.set_time_control(Some(time_control));
+        }
+    }
+
+    // Sets the clocks to the configured time control, stopped until the next move.
+    fn reset_clock(&mut self) {
+        self.clock = self.config.get_time_control().map(ChessClock::new);
+    }
+
+    // Tells the game's observers the time left on running clocks, and ends the game on time when
+    // the side to move has run out of it. A side without the material to checkmate only draws
+    // when the other side's time runs out. A puzzle rush whose time is up ends too.
+    fn check_clock(&mut self) {
+        self.check_rush_time();
+        let now = Instant::now();
+        if let Some(clock) = self.clock.as_ref().filter(|c| c.get_running().is_some()) {
+            let (white, black) = (clock.get_remaining(Team::Light, now), clock.get_remaining(Team::Dark, now));
+            self.state.tick_clock(white, black);
+        }
+        self.check_low_time();
+        let flagged = match &mut self.clock {
+            Some(clock) => match clock.get_flagged(now) {
+                Some(team) => {
+                    clock.stop(now);
+                    team
+                }
+                None => return,
+            },
+            None => return,
+        };
+        if self.state.get_pgn_game().is_finished() {
+            return;
+        }
+        let opponent = flagged.opposite();
+        let (result, winner) = if !self.state.get_board().can_checkmate(opponent) {
+            (PgnResult::Draw, None)
+        }
+        else {
+            let result = match opponent {
+                Team::Light => PgnResult::WhiteWin,
+                Team::Dark => PgnResult::BlackWin,
+            };
+            (result, Some(opponent))
+        };
+        self.state.end_game(result);
+        let pgn_game = self.state.get_pgn_game_mut();
+        pgn_game.set_result_comment(format!("{flagged} lost on time"));
+        pgn_game.set_tag("Termination", String::from("time forfeit"));
+        self.say(self.locale.out_of_time(flagged, winner));
+        self.record_game_end();
+    }
+
+    // Ends the game with the player to move resigning, or against the computer with the player
+    // resigning.
+    fn resign(&mut self) {
+        if let Some(result) = self.get_finished_result() {
+            self.say(self.locale.already_ended(result));
+            return;
+        }
+        let team = match &self.opponent {
+            Some(opponent) => opponent.get_team().opposite(),
+            None => self.state.get_board().get_turn(),
+        };
+        let result = match team {
+            Team::Light => PgnResult::BlackWin,
+            Team::Dark => PgnResult::WhiteWin,
+        };
+        self.state.end_game(result);
+        self.state.get_pgn_game_mut().set_result_comment(format!("{team} resigns"));
+        self.say(self.locale.resigns(team));
+    }
+
+    // Ends the game in a draw both players agreed to. The computer plays on.
+    fn agree_draw(&mut self) {
+        if let Some(result) = self.get_finished_result() {
+            self.say(self.locale.already_ended(result));
+            return;
+        }
+        if let Some(opponent) = &self.opponent {
+            let name = String::from(opponent.get_name());
+            self.say(self.locale.declines_draw(&name));
+            return;
+        }
+        self.state.end_game(PgnResult::Draw);
+        self.state.get_pgn_game_mut().set_result_comment(String::from("Draw agreed"));
+        self.say(String::from(self.locale.drawn_by_agreement()));
+    }
+
+    // Claims a draw for the side to move, with the move given played first. The claim is checked
+    // against the rules, so it needs no answer from the opponent.
+    fn claim_draw(&mut self, pgn_move: Option<&str>) {
+        if let Some(result) = self.get_finished_result() {
+            self.say(self.locale.already_ended(result));
+            return;
+        }
+        if self.is_remote_turn() {
+            self.say(self.locale.remote_move(self.get_remote_side()));
+            return;
+        }
+        let intended = match pgn_move.map(|m| ChessMove::from(&self.config.get_input_language().to_english_san(m))) {
+            Some(Ok(chess_move)) => Some(chess_move),
+            Some(Err(e)) => {
+                self.say(self.locale.invalid_move(pgn_move.unwrap_or_default(), &e.to_string()));
+                return;
+            }
+            None => None,
+        };
+        let team = self.state.get_board().get_turn();
+        match self.state.claim_draw(intended.as_ref()) {
+            Ok((played, claim)) => {
+                if let Some(played) = played {
+                    self.say(self.locale.played(&played.to_string()));
+                    self.update_clock(true);
+                }
+                match claim {
+                    Some(claim) => self.say(self.locale.claims_draw(team, claim)),
+                    None if self.state.get_pgn_game().is_finished() => (),
+                    None => self.say(String::from(self.locale.no_draw_to_claim())),
+                }
+            }
+            Err(e) => self.say(self.locale.cannot_claim_draw(&e.to_string())),
+        }
+    }
+
+    // The result of the game once it is over.
+    fn get_finished_result(&self) -> Option<PgnResult> {
+        let pgn_game = self.state.get_pgn_game();
+        pgn_game.is_finished().then(|| *pgn_game.get_result())
+    }
+
+    // Stops the clocks after moves were taken back or replayed, until the next move is made.
+    fn stop_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            if clock.get_running().is_some() {
+                clock.stop(Instant::now());
+                self.say(String::from(self.locale.clocks_stopped()));
+            }
+        }
+    }
+
+    // Runs the clock of the side to move after a move, or stops both once the game is over.
+    fn update_clock(&mut self, moved: bool) {
+        let now = Instant::now();
+        let board = self.state.get_board();
+        let game_over = self.state.get_pgn_game().is_finished() || board.is_checkmate() || board.is_stalemate();
+        let turn = board.get_turn();
+        if let Some(clock) = &mut self.clock {
+            if game_over {
+                clock.stop(now);
+            }
+            else if moved {
+                match clock.get_running() {
+                    Some(_) => clock.press(now),
+                    // The first move, or the first one after the clocks were stopped.
+                    None => clock.start(turn, now),
+                }
+            }
+        }
+    }
+
+    // Archives and rates the current game once it is over. Runs after anything that can end it,
+    // and only records the game the first time.
+    fn record_game_end(&mut self) {
+        if self.recorded {
+            return;
+        }
+        let result = match self.state.get_status() {
+            GameStatus::Checkmate { winner: Team::Light } => PgnResult::WhiteWin,
+            GameStatus::Checkmate { winner: Team::Dark } => PgnResult::BlackWin,
+            GameStatus::Stalemate => PgnResult::Draw,
+            GameStatus::Ended(result) => result,
+            GameStatus::ToMove(_) | GameStatus::InCheck(_) => return,
+        };
+        self.recorded = true;
+        self.archive_game(result);
+        if self.rated {
+            self.rate_game(result);
+        }
+    }
+
+    fn apply_config(&mut self) {
+        self.view.theme = self.config.get_theme();
+        self.view.pieces = self.config.get_pieces();
+        self.view.size = self.config.get_board_size();
+        self.view.shapes = self.config.get_piece_shapes();
+        self.view.wide_glyphs = match self.config.get_glyph_width() {
+            GlyphWidth::Auto => self.measured_glyph_width == Some(2),
+            GlyphWidth::Narrow => false,
+            GlyphWidth::Wide => true,
+        };
+        self.locale = match self.config.get_language() {
+            Language::Auto => self.env_locale.unwrap_or_default(),
+            Language::English => Locale::English,
+            Language::German => Locale::German,
+        };
+    }
+
+    // Lists the keys of the full screen interface, as configured or by default.
+    fn list_keys(&mut self) {
+        let keys = self.config.get_keys();
+        let mut lines = vec![String::from(self.locale.keys_heading())];
+        for action in KeyAction::ALL {
+            let bound: Vec<String> = keys.get_keys(action).iter().map(|k| k.to_string()).collect();
+            let bound = if bound.is_empty() { String::from(self.locale.no_keys()) } else { bound.join(", ") };
+            lines.push(format!("  {:<13} {:<18} {}", action.to_string(), bound, self.locale.key_description(action)));
+        }
+        lines.push(String::from(self.locale.keys_always_quit()));
+        self.say(lines.join("\n"));
+    }
+
+    // Marks a move with a NAG. Move assessments (!, ?, ...) replace each other, other glyphs are
+    // added alongside.
+    fn annotate_move(&mut self, glyph: &str, ply: Option<usize>) {
+        let ply = ply.unwrap_or(self.state.get_viewed_ply());
+        if ply == 0 || ply > self.state.get_ply_count() {
+            self.say(String::from(self.locale.no_move_to_annotate()));
+            return;
+        }
+        let glyph = glyph.trim();
+        if glyph.eq_ignore_ascii_case("clear") {
+            self.state.edit_annotation(ply, |a| {
+                for nag in a.get_nags().clone() {
+                    a.remove_nag(nag);
+                }
+            });
+        }
+        else {
+            let nag = match suffix_annotation_nag(glyph).or_else(|| glyph.trim_start_matches('$').parse().ok()) {
+                Some(nag) => nag,
+                None => {
+                    self.say(self.locale.unknown_annotation(glyph));
+                    return;
+                }
+            };
+            self.state.edit_annotation(ply, |a| {
+                if (1..=6).contains(&nag) {
+                    // The assessment goes first, as it is written right after the move.
+                    let others: Vec<u8> = a.get_nags().iter().copied().filter(|n| !(1..=6).contains(n)).collect();
+                    for old in a.get_nags().clone() {
+                        a.remove_nag(old);
                     }
+                    a.add_nag(nag);
+                    others.into_iter().for_each(|n| a.add_nag(n));
                 }
                 else {
-                    if f % 2 == 0 {
-                        output.push_str(light_bg_color.as_str());
-                    }
-                    else {
-                        output.push_str(dark_bg_color.as_str());
+                    a.add_nag(nag);
+                }
+            });
+        }
+        let (chess_move, annotation) = self.state.get_pgn_game().get_plies()[ply - 1];
+        let dots = if ply % 2 == 1 { "." } else { "..." };
+        self.say(format!("{}{dots} {}", ply.div_ceil(2), format_annotated_move(chess_move, annotation)));
+    }
+
+    fn manage_variations(&mut self, action: VariationAction) {
+        let (number, promote) = match action {
+            VariationAction::List => {
+                let variations = self.state.get_variations();
+                if variations.is_empty() {
+                    self.say(String::from(self.locale.no_variations()));
+                    return;
+                }
+                let plies = self.state.get_pgn_game().get_plies();
+                let lines: Vec<String> = variations.iter().enumerate()
+                    .map(|(i, (ply, text))| {
+                        let dots = if ply % 2 == 1 { "." } else { "..." };
+                        format!("{}: {text} (instead of {}{dots} {})", i + 1, ply.div_ceil(2), plies[ply - 1].0)
+                    })
+                    .collect();
+                self.say(lines.join("\n"));
+                return;
+            }
+            VariationAction::Enter { number } => (number, false),
+            VariationAction::Promote { number } => (number, true),
+        };
+        match self.state.enter_variation(number.wrapping_sub(1)) {
+            Ok(ply) => {
+                self.stop_clock();
+                self.say(self.locale.playing_variation(number));
+                if promote {
+                    self.go_to_ply(ply);
+                }
+            }
+            Err(e) => self.say(self.locale.variation_failed(number, &e.to_string())),
+        }
+    }
+
+    fn configure(&mut self, action: ConfigAction) {
+        match action {
+            ConfigAction::Show => {
+                let path = self.config_path.as_ref().map(|p| p.display().to_string());
+                self.say(format!("{}\n{}", self.locale.config_file(path.as_deref()), self.config));
+            }
+            ConfigAction::Set { setting, value } => {
+                let setting = setting.to_lowercase();
+                match self.config.set(&setting, &value.join(" ")) {
+                    Ok(()) => {
+                        self.apply_config();
+                        let value = self.config.get(&setting).unwrap_or_default();
+                        self.say(format!("{setting} = {value}"));
                     }
+                    Err(e) => self.say(self.locale.setting_failed(&e.to_string())),
                 }
-                if let Some(p) = self.get_squares()[r][f].get_piece() {
-                    match p.get_team() {
-                        Team::Dark => {
-                            output.push_str(dark_fg_color.as_str());
-                        }
-                        Team::Light => {
-                            output.push_str(light_fg_color.as_str());
-                        }
+            }
+            ConfigAction::Save => {
+                let path = match &self.config_path {
+                    Some(p) => p.clone(),
+                    None => {
+                        self.say(String::from(self.locale.no_config_path()));
+                        return;
                     }
+                };
+                match self.config.save(&path) {
+                    Ok(()) => self.say(self.locale.saved_settings(&path.display().to_string())),
+                    Err(e) => self.say(self.locale.settings_not_saved(&e.to_string())),
+                }
+            }
+        }
+    }
+
+    // Autosaves the game if it changed. After a command that happens right away when autosaving
+    // on every move, otherwise once the autosave interval has passed since the last write. The copy
+    // saved if the program crashes is kept up to date every time.
+    fn autosave(&mut self, after_command: bool) {
+        recovery::remember(&self.state, self.locale);
+        let interval = self.config.get_autosave_interval();
+        let due = match &self.autosave {
+            Some(a) => {
+                (after_command && self.config.get_autosave_on_move())
+                    || (interval > 0 && a.is_due(Duration::from_secs(interval.into())))
+            }
+            None => false,
+        };
+        if due {
+            self.write_autosave();
+        }
+    }
+
+    fn write_autosave(&mut self) {
+        if self.autosave.is_none() {
+            return;
+        }
+        let session = self.get_session_text();
+        let autosave = match &mut self.autosave {
+            Some(a) => a,
+            None => return,
+        };
+        if let Err(e) = autosave.write(self.state.get_pgn_game(), &session) {
+            let path = autosave.get_path().display().to_string();
+            self.say(self.locale.autosave_failed(&path, &e.to_string()));
+        }
+    }
+
+    fn manage_games(&mut self, action: GameAction) {
+        match action {
+            GameAction::New => {
+                self.set_current_aside();
+                self.current_game = self.other_games.len();
+                self.new_game();
+                self.say(self.locale.started_game(self.current_game + 1));
+            }
+            GameAction::List => {
+                let mut lines = Vec::new();
+                for number in 0..=self.other_games.len() {
+                    let state = self.get_open_game_state(number);
+                    let pgn_game = state.get_pgn_game();
+                    lines.push(self.locale.open_game(number + 1, number == self.current_game, pgn_game.get_white(),
+                        pgn_game.get_black(), state.get_ply_count(), &state.get_status()));
                 }
+                self.say(lines.join("\n"));
+            }
+            GameAction::Switch { number } => {
+                if number == 0 || number > self.other_games.len() + 1 {
+                    self.say(self.locale.no_such_game(number));
+                }
+                else if number - 1 == self.current_game {
+                    self.say(self.locale.already_current_game(number));
+                }
+                else {
+                    self.switch_game(number - 1);
+                    self.say(self.locale.switched_game(number));
+                }
+            }
+        }
+    }
+
+    // Makes another open game, by its index in the list, the current game.
+    fn switch_game(&mut self, index: usize) {
+        self.set_current_aside();
+        self.resume_game(index);
+    }
+
+    // Takes an open game out of the list to play it, in place of the current game.
+    fn resume_game(&mut self, index: usize) {
+        let game = self.other_games.remove(index);
+        self.state = game.state;
+        self.clock = game.clock;
+        self.opponent = game.opponent;
+        self.rated = game.rated;
+        self.recorded = game.recorded;
+        self.current_game = index;
+    }
+
+    // The state of an open game, by its index in the list.
+    fn get_open_game_state(&self, index: usize) -> &GameState {
+        match index.cmp(&self.current_game) {
+            Ordering::Less => &self.other_games[index].state,
+            Ordering::Equal => &self.state,
+            Ordering::Greater => &self.other_games[index - 1].state,
+        }
+    }
+
+    // Moves the current game into the list of other open games, at its place in the list.
+    fn set_current_aside(&mut self) {
+        // The computer doesn't think about games set aside, it starts afresh when back.
+        if let Some(opponent) = &mut self.opponent {
+            let _ = opponent.stop_pondering();
+        }
+        let current = OpenGame {
+            state: std::mem::replace(&mut self.state, GameState::new()),
+            clock: self.clock.take(),
+            opponent: self.opponent.take(),
+            rated: self.rated,
+            recorded: self.recorded,
+        };
+        self.other_games.insert(self.current_game, current);
+    }
+
+    // Reads the first game of a PGN file, along with the number of games in the file. Reading
+    // errors are told and give None.
+    fn read_game_file(&mut self, file_path: &Path) -> Option<(GameState, usize)> {
+        let text = match fs::read_to_string(file_path) {
+            Ok(t) => t,
+            Err(e) => {
+                self.say(self.locale.unreadable_file(&file_path.display().to_string(), &e.to_string()));
+                return None;
+            }
+        };
+        let games = match parse_pgn_games(&text) {
+            Ok(g) => g,
+            Err(e) => {
+                self.say(self.locale.unreadable_file(&file_path.display().to_string(), &e.to_string()));
+                return None;
+            }
+        };
+        let game_count = games.len();
+        let pgn_game = match games.into_iter().next() {
+            Some(g) => g,
+            None => {
+                self.say(self.locale.no_game_in_file(&file_path.display().to_string()));
+                return None;
+            }
+        };
+        match GameState::from_pgn_game(pgn_game) {
+            Ok(state) => Some((state, game_count)),
+            Err(e) => {
+                self.say(self.locale.game_not_loaded(&e.to_string()));
+                None
+            }
+        }
+    }
+
+    // Compares the built-in engine's search without and with move ordering.
+    fn bench(&mut self, depth: u32) {
+        let mut lines = vec![self.locale.bench_heading(depth)];
+        let (mut unordered, mut ordered) = (0, 0);
+        for result in chess_engine::bench(depth) {
+            let (before, after) = (result.get_unordered(), result.get_ordered());
+            lines.push(self.locale.bench_position(result.get_name(), (before.get_nodes(), after.get_nodes()),
+                (before.get_time().as_millis(), after.get_time().as_millis())));
+            unordered += before.get_nodes();
+            ordered += after.get_nodes();
+        }
+        let saved = 100 - ordered * 100 / unordered.max(1);
+        lines.push(self.locale.bench_saved(saved));
+        self.say(lines.join("\n"));
+    }
+
+    fn say(&mut self, message: String) {
+        self.messages.push(message);
+    }
+
+    fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.messages)
+    }
 
-                output.push_str(format!(" {} ", self.get_squares()[r][f]).as_str());
+    // Parses a line of user input as a command and carries it out. Problems with the input are
+    // reported as messages.
+    fn run_command_line(&mut self, line: &str) -> CommandOutcome {
+        let mut args = match split_command_line(line) {
+            Ok(a) => a,
+            Err(e) => {
+                self.say(self.locale.error(&e.to_string()));
+                return CommandOutcome::Continue;
+            }
+        };
+        if args.is_empty() {
+            return CommandOutcome::Continue;
+        }
+        // The square of the piece meant by an ambiguous move, typed or clicked, plays it. Anything
+        // else drops the move.
+        if let Some(candidates) = self.ambiguous_move.take() {
+            let square = match args.as_slice() {
+                [square] => square.to_lowercase(),
+                [command, square] if command.eq_ignore_ascii_case("select") => square.to_lowercase(),
+                _ => String::new(),
+            };
+            if let Some(board_move) = candidates.iter().find(|m| m.get_from().to_string() == square) {
+                let san = self.state.get_board().to_chess_move(*board_move).to_string();
+                return self.dispatch(ChessCommands::Move { pgn_move: san });
+            }
+        }
+        args.insert(0, String::from(">>"));
+
+        // Command names are matched case-insensitively, arguments (moves, paths) are left as typed.
+        if let Some(command_name) = args.get_mut(1) {
+            *command_name = command_name.to_lowercase();
+        }
+        // The rules topics are help of their own, beside the help clap gives on the commands.
+        if args.len() > 2 && args[1] == "help" && args[2].eq_ignore_ascii_case("rules") {
+            self.show_rules(&args[3..]);
+            return CommandOutcome::Continue;
+        }
+        #[cfg(feature = "scripting")]
+        if self.run_script_command(&args[1..]) {
+            return CommandOutcome::Continue;
+        }
+        match ChessTuiCmd::try_parse_line(args, self.config.get_command_prefixes()) {
+            // Typed moves may use the piece letters of another language, the game keeps English SAN.
+            Ok(ChessTuiCmd { command: ChessCommands::Move { pgn_move } }) => {
+                let pgn_move = self.config.get_input_language().to_english_san(&pgn_move);
+                self.dispatch(ChessCommands::Move { pgn_move })
             }
+            Ok(input_cmd) => self.dispatch(input_cmd.command),
+            Err(e) => {
+                self.say(e.to_string().trim_end().to_string());
+                CommandOutcome::Continue
+            }
+        }
+    }
+
+    // The board with the pieces each side has captured next to that side's end of the board,
+    // followed by the status of the game.
+    fn render(&self) -> String {
+        if self.config.get_screen_reader() {
+            return self.render_spoken();
+        }
+        let flipped = self.shown_view.as_ref().unwrap_or(&self.view).flipped;
+        let (top, bottom) = if flipped { (Team::Light, Team::Dark) } else { (Team::Dark, Team::Light) };
+        let view = self.shown_view.as_ref().unwrap_or(&self.view);
+        if let Some(drill) = &self.drill {
+            return format!("\n{}\n  {}\n", render_board(drill.get_board(), view), drill.get_title());
+        }
+        let mut output = self.render_side(top, view.pieces);
+        output.push_str(render_board(self.state.get_viewed_board(), view).as_str());
+        output.push_str(self.render_side(bottom, view.pieces).as_str());
+        for line in self.get_status_lines() {
+            output.push_str(format!("  {line}\n").as_str());
+        }
+        output
+    }
+
+    // The shown position in words, in place of the board for screen readers: the move that led
+    // to it, the clocks and the status of the game. The pieces are told by `read`.
+    fn render_spoken(&self) -> String {
+        if let Some(drill) = &self.drill {
+            return format!("{}. {} to move.\n", drill.get_title(), drill.get_board().get_turn());
+        }
+        let mut lines = Vec::new();
+        if let Some((board, board_move)) = self.state.get_viewed_board_move() {
+            lines.push(format!("{}.", board.describe_move(board_move)));
         }
-        output.push_str(format!("{}\n  ", TERMINAL_COLOR_RESET).as_str());
-        output.push_str(" A  B  C  D  E  F  G  H\n");
-        write!(f, "{}", output)
+        if let Some(clock) = &self.clock {
+            let now = Instant::now();
+            lines.push(self.locale.clocks(
+                &format_clock_time(clock.get_remaining(Team::Light, now)), &format_clock_time(clock.get_remaining(Team::Dark, now))));
+        }
+        lines.extend(self.get_status_lines().into_iter().map(|l| format!("{l}.")));
+        lines.iter().map(|l| format!("{l}\n")).collect()
     }
-}
\ No newline at end of file
+
+    // The status of the game below the board, with where the game is being stepped through, the
+    // evaluation of watched engines and the board of a simul.
+    fn get_status_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.state.is_viewing_history() {
+            let (ply, count) = (self.state.get_viewed_ply(), self.state.get_ply_count());
+            lines.push(format!("{} {}", self.locale.status(&self.state.get_status()), self.locale.viewed_ply(ply, count)));
+        }
+        else if let Some(score) = self.watch.as_ref().and_then(|w| w.get_score()) {
+            lines.push(format!("{}, {}", self.locale.status(&self.state.get_status()), self.locale.evaluation(&score.to_string())));
+        }
+        else {
+            lines.push(self.locale.status(&self.state.get_status()));
+        }
+        if let Some(simul) = &self.simul {
+            if let Some(board) = simul.get_board(self.current_game) {
+                lines.push(self.locale.simul_board(board, simul.get_boards()));
+            }
+        }
+        lines
+    }
+
+    // The line next to a side's end of the board: its clock, when there is one, marked while it
+    // runs, and the pieces it captured.
+    fn render_side(&self, team: Team, pieces: PieceStyle) -> String {
+        let captures = render_captures(&self.state, team, pieces);
+        let line = match &self.clock {
+            Some(clock) => {
+                let marker = if clock.get_running() == Some(team) { '▶' } else { ' ' };
+                let time = format_clock_time(clock.get_remaining(team, Instant::now()));
+                format!("{marker} {time}  {captures}")
+            }
+            None => format!("  {captures}"),
+        };
+        format!("{line}\n")
+    }
+
+    // The position on the board: the drill's or the position of the game being viewed.
+    fn get_shown_board(&self) -> &Board {
+        match &self.drill {
+            Some(drill) => drill.get_board(),
+            None => self.state.get_viewed_board(),
+        }
+    }
+
+    // Steps the board to the position after `ply` half moves and says which move led there,
+    // with its annotations.
+    fn go_to_ply(&mut self, ply: usize) {
+        let ply = self.state.view_ply(ply);
+        let description = match self.state.get_viewed_move() {
+            Some((chess_move, annotation)) => {
+                let dots = if ply % 2 == 1 { "." } else { "..." };
+                let mut text = format!("{}{dots} {}", ply.div_ceil(2), format_annotated_move(chess_move, annotation));
+                for comment in annotation.get_comments() {
+                    text.push_str(format!(" {{{comment}}}").as_str());
+                }
+                for variation in annotation.get_variations() {
+                    text.push_str(format!(" ({variation})").as_str());
+                }
+                text
+            }
+            None => String::from(self.locale.start_of_game()),
+        };
+        self.say(description);
+    }
+
+    // Highlights the legal destinations of the piece on a square until the next command.
+    fn select(&mut self, square_str: &str) {
+        let board = self.get_shown_board();
+        let square = match BoardSquare::from(&square_str.to_lowercase()) {
+            Some(s) => s,
+            None => {
+                self.say(self.locale.invalid_square(square_str));
+                return;
+            }
+        };
+        let piece = match board.get_piece_at(square) {
+            Some(p) => p,
+            None => {
+                self.say(self.locale.no_piece_on(&square.to_string()));
+                return;
+            }
+        };
+        if *piece.get_team() != board.get_turn() {
+            self.say(self.locale.piece_cannot_move(&square.to_string(), board.get_turn()));
+            return;
+        }
+
+        let moves = board.legal_moves_from(square);
+        let mut destinations: Vec<BoardSquare> = moves.iter().map(|m| m.get_to()).collect();
+        // Promotions are one move per piece to the same square.
+        destinations.dedup();
+        if moves.is_empty() {
+            self.say(self.locale.no_legal_moves(&square.to_string()));
+        }
+        else {
+            let sans: Vec<String> = moves.iter()
+                .filter(|m| m.get_promotion().is_none_or(|p| p == ChessPiece::Queen))
+                .map(|m| board.to_chess_move(*m).to_string())
+                .collect();
+            self.say(self.locale.legal_moves_from(&square.to_string(), &sans));
+        }
+        self.view.selected = Some(square);
+        self.view.destinations = destinations;
+    }
+
+    // Writes exported text to a file, refusing to replace one unless forced, or shows it.
+    fn export(&mut self, what: &str, text: &str, file_path: Option<&Path>, force: bool) {
+        match file_path {
+            Some(path) if path.exists() && !force => {
+                self.say(self.locale.export_exists(what, &path.display().to_string()));
+            }
+            Some(path) => match fs::write(path, text) {
+                Ok(()) => self.say(self.locale.exported(what, &path.display().to_string())),
+                Err(e) => self.say(self.locale.export_failed(what, &e.to_string())),
+            },
+            None => self.say(text.trim_end().to_string()),
+        }
+    }
+
+    // Lists how the shown position differs from a FEN string, one difference per line.
+    // Tells what stands on a square, rank or file of the shown board, or the whole position.
+    fn read_board(&mut self, target: Option<&str>) {
+        let board = self.get_shown_board();
+        let text = match target.map(|t| t.trim().to_lowercase()) {
+            None => board.describe_position(),
+            Some(target) => {
+                let mut chars = target.chars();
+                let (first, rest) = (chars.next(), chars.next());
+                if let Some(square) = BoardSquare::from(&target) {
+                    board.describe_square(square)
+                }
+                else if let (Some(file), None) = (first.and_then(ChessFile::from), rest) {
+                    board.describe_file(file)
+                }
+                else if let (Some(rank), None) = (first.and_then(ChessRank::from), rest) {
+                    board.describe_rank(rank)
+                }
+                else {
+                    self.locale.unreadable_target(&target)
+                }
+            }
+        };
+        self.say(text);
+    }
+
+    fn diff_position(&mut self, fen: &str) {
+        let other = match Board::from_fen(fen) {
+            Ok(b) => b,
+            Err(e) => {
+                self.say(self.locale.invalid_fen(&e.to_string()));
+                return;
+            }
+        };
+        let differences = self.get_shown_board().diff(&other);
+        if differences.is_empty() {
+            self.say(String::from(self.locale.position_matches_fen()));
+            return;
+        }
+        self.say(String::from(self.locale.position_differs_from_fen()));
+        for difference in differences {
+            self.say(format!("  {difference}"));
+        }
+    }
+}
+
+// A move with its NAGs, the move assessments written as suffixes, e.g. "Nf3!? $14".
+fn format_annotated_move(chess_move: &ChessMove, annotation: &PgnAnnotation) -> String {
+    let mut text = chess_move.to_string();
+    for nag in annotation.get_nags() {
+        match nag_suffix_annotation(*nag) {
+            Some(suffix) => text.push_str(suffix),
+            None => text.push_str(format!(" ${nag}").as_str()),
+        }
+    }
+    text
+}
+
+// The pieces a side has captured, lowest value first, followed by the side's material advantage
+// when it is ahead, e.g. "♟♟♞ +2".
+fn render_captures(state: &GameState, team: Team, pieces: PieceStyle) -> String {
+    let mut captured = state.get_captured_by(team);
+    captured.sort_by_key(|p| p.get_piece_type().get_value());
+    let mut output: String = captured.iter().map(|p| piece_symbol(*p, pieces)).collect();
+    let advantage = match team {
+        Team::Light => state.get_material_difference(),
+        Team::Dark => -state.get_material_difference(),
+    };
+    if advantage > 0 {
+        if !output.is_empty() {
+            output.push(' ');
+        }
+        output.push_str(format!("+{advantage}").as_str());
+    }
+    output
+}
+
+impl CommandDispatcher for TuiGame {
+    fn dispatch(&mut self, command: ChessCommands) -> CommandOutcome {
+        // A selection, a shown view or an ambiguous move only lasts until the next command.
+        self.ambiguous_move = None;
+        self.view.selected = None;
+        self.view.destinations.clear();
+        self.shown_view = None;
+        #[cfg(feature = "scripting")]
+        self.watch_game_for_scripts();
+        self.check_clock();
+
+        // While engines play only commands that leave the game alone are taken.
+        let leaves_game_alone = matches!(command,
+            ChessCommands::Stop | ChessCommands::Show { .. } | ChessCommands::Flip | ChessCommands::Save { .. }
+            | ChessCommands::Diff { .. } | ChessCommands::Read { .. } | ChessCommands::Explore { choice: None, .. } | ChessCommands::Export { .. } | ChessCommands::Keys | ChessCommands::Config { .. } | ChessCommands::Quit);
+        if self.watch.is_some() && !leaves_game_alone {
+            self.say(String::from(self.locale.engines_playing()));
+            return CommandOutcome::Continue;
+        }
+        // A drill takes the moves and leaves the game alone meanwhile.
+        if let Some(drill) = &self.drill {
+            match command {
+                ChessCommands::Move { pgn_move } => {
+                    match drill {
+                        Drill::Tutorial(_) => self.play_tutorial_move(&pgn_move),
+                        Drill::Opening(_) => self.play_training_move(&pgn_move),
+                        Drill::Rush(_) => self.play_rush_move(&pgn_move),
+                    }
+                    return CommandOutcome::Continue;
+                }
+                ChessCommands::Select { .. } | ChessCommands::Tutorial { .. } | ChessCommands::TrainOpening { .. }
+                | ChessCommands::PuzzleRush { .. } | ChessCommands::Rating { .. } => (),
+                _ if leaves_game_alone => (),
+                _ => {
+                    let message = drill.get_busy_message(self.locale);
+                    self.say(String::from(message));
+                    return CommandOutcome::Continue;
+                }
+            }
+        }
+
+        match command {
+            ChessCommands::Move { pgn_move } => {
+                if self.state.is_viewing_history() {
+                    self.say(String::from(self.locale.viewing_history()));
+                    return CommandOutcome::Continue;
+                }
+                let pgn_game = self.state.get_pgn_game();
+                if pgn_game.is_finished() {
+                    let result = *pgn_game.get_result();
+                    self.say(self.locale.game_over(result));
+                    return CommandOutcome::Continue;
+                }
+                if self.is_remote_turn() {
+                    self.say(self.locale.remote_move(self.get_remote_side()));
+                    return CommandOutcome::Continue;
+                }
+                let parsed_move_result = ChessMove::from(&pgn_move);
+                match parsed_move_result {
+                    Ok(parsed_move) => {
+                        let before = self.state.get_board().clone();
+                        match self.state.play_move(&parsed_move) {
+                            Ok(played) => {
+                                self.say(self.locale.played(&played.to_string()));
+                                self.check_blunder(&before);
+                                self.update_clock(true);
+                            }
+                            Err(GameMoveError::Unresolved(MoveResolveError::AmbiguousMove(candidates))) => {
+                                let origins: Vec<String> = candidates.iter().map(|m| m.get_from().to_string()).collect();
+                                self.say(self.locale.ambiguous_move(&pgn_move, &origins));
+                                self.ambiguous_move = Some(candidates);
+                            }
+                            Err(e) => self.say(self.locale.illegal_move(&pgn_move, &e.to_string())),
+                        }
+                    }
+                    Err(e) => {
+                        self.say(self.locale.invalid_move(&pgn_move, &e.to_string()));
+                    }
+                }
+            }
+            ChessCommands::Undo { undo_count } => {
+                match self.state.undo(undo_count as usize) {
+                    0 => self.say(String::from(self.locale.nothing_to_undo())),
+                    n => {
+                        self.say(self.locale.undid(n));
+                        self.stop_clock();
+                    }
+                }
+            },
+            ChessCommands::AnnotateMove { glyph, ply } => {
+                self.annotate_move(&glyph, ply);
+            },
+            ChessCommands::Variation { action } => {
+                self.manage_variations(action);
+            },
+            ChessCommands::Redo { redo_count } => {
+                match self.state.redo(redo_count as usize) {
+                    0 => self.say(String::from(self.locale.nothing_to_redo())),
+                    n => {
+                        self.say(self.locale.redid(n));
+                        self.stop_clock();
+                    }
+                }
+            },
+            ChessCommands::Next { count } => {
+                if self.state.is_viewing_history() {
+                    self.go_to_ply(self.state.get_viewed_ply() + count as usize);
+                }
+                else {
+                    self.say(String::from(self.locale.last_move()));
+                }
+            },
+            ChessCommands::Prev { count } => {
+                match self.state.get_viewed_ply() {
+                    0 => self.say(String::from(self.locale.first_move())),
+                    ply => self.go_to_ply(ply.saturating_sub(count as usize)),
+                }
+            },
+            ChessCommands::Goto { ply } => {
+                let played = self.state.get_ply_count();
+                if ply > played + self.state.get_redo_count() {
+                    self.say(self.locale.only_plies(played + self.state.get_redo_count()));
+                }
+                else if ply > played {
+                    // Past the last move, the moves taken back are played again.
+                    self.state.redo(ply - played);
+                    self.stop_clock();
+                    self.go_to_ply(ply);
+                }
+                else {
+                    self.go_to_ply(ply);
+                }
+            },
+            ChessCommands::Start => {
+                self.go_to_ply(0);
+            },
+            ChessCommands::End => {
+                self.go_to_ply(self.state.get_ply_count());
+            },
+            ChessCommands::Reset => {
+                self.say(String::from(self.locale.resetting()));
+                self.new_game();
+            },
+            ChessCommands::New { vs_engine, black, movetime, opponent } => {
+                if vs_engine {
+                    self.start_single_player(black, movetime);
+                }
+                else {
+                    self.new_game();
+                    if let Some(name) = opponent {
+                        self.state.get_pgn_game_mut().set_black(name);
+                    }
+                    self.say(String::from(self.locale.new_game()));
+                }
+            },
+            ChessCommands::Show { flip } => {
+                self.shown_view = Some(BoardView { flipped: flip || self.view.flipped, ..self.view.clone() });
+            },
+            ChessCommands::Flip => {
+                self.view.flipped = !self.view.flipped;
+                let side = if self.view.flipped { Team::Dark } else { Team::Light };
+                self.say(self.locale.flipped(side));
+            },
+            ChessCommands::Select { square } => {
+                self.select(&square);
+            },
+            ChessCommands::Keys => {
+                self.list_keys();
+            },
+            ChessCommands::Diff { fen } => {
+                self.diff_position(&fen.join(" "));
+            },
+            ChessCommands::Read { target } => {
+                self.read_board(target.as_deref());
+            },
+            ChessCommands::Save { file_path, force, session: true } => {
+                self.save_session(&file_path, force);
+            },
+            ChessCommands::Save { file_path, force, session: false } => {
+                match self.state.get_pgn_game().save_to_file(&file_path, force) {
+                    Ok(()) => self.say(self.locale.saved_game(&file_path.display().to_string())),
+                    Err(e) => self.say(self.locale.game_not_saved(&e.to_string())),
+                }
+            },
+            ChessCommands::Export { action: ExportAction::Diagram { file_path, unicode, flip, force } } => {
+                let style = if unicode { DiagramStyle::Unicode } else { DiagramStyle::Ascii };
+                let diagram = self.get_shown_board().to_diagram(style, flip || self.view.flipped);
+                self.export(self.locale.diagram(), &diagram, file_path.as_deref(), force);
+            },
+            ChessCommands::Export { action: ExportAction::Session { file_path, force } } => {
+                self.export_session(&file_path, force);
+            },
+            ChessCommands::Import { file_path } => {
+                self.import_session(&file_path);
+            },
+            ChessCommands::Export { action: ExportAction::Latex { file_path, diagrams, force } } => {
+                let latex = self.state.get_pgn_game().to_latex(&diagrams);
+                self.export(self.locale.game(), &latex, file_path.as_deref(), force);
+            },
+            ChessCommands::Load { file_path, session: true } => {
+                self.load_session(&file_path);
+            },
+            ChessCommands::Load { file_path, session: false } => {
+                if let Some((state, game_count)) = self.read_game_file(&file_path) {
+                    self.state = state;
+                    self.reset_clock();
+                    // A game that was already over was recorded where it was played.
+                    self.recorded = self.state.get_pgn_game().is_finished();
+                    self.say(self.locale.loaded_game(&file_path.display().to_string()));
+                    if game_count > 1 {
+                        self.say(self.locale.only_first_game(game_count));
+                    }
+                }
+            },
+            ChessCommands::Adjudicate { result, reason } => {
+                if let Some(ended) = self.get_finished_result() {
+                    self.say(self.locale.already_ended(ended));
+                }
+                else {
+                    self.state.end_game(result);
+                    if !reason.is_empty() {
+                        self.state.get_pgn_game_mut().set_result_comment(reason.join(" "));
+                    }
+                    self.say(self.locale.adjudicated(result));
+                }
+            },
+            ChessCommands::Resign => {
+                self.resign();
+            },
+            ChessCommands::Draw => {
+                self.agree_draw();
+            },
+            ChessCommands::Claim { action: ClaimAction::Draw { pgn_move } } => {
+                self.claim_draw(pgn_move.as_deref());
+            },
+            ChessCommands::Config { action } => {
+                self.configure(action);
+            },
+            ChessCommands::Annotate { file_path, from, movetime, force } => {
+                self.annotate(&file_path, from.as_deref(), movetime, force);
+            },
+            ChessCommands::ImportArchive { username, to, since } => {
+                self.import_archive(&username, to, since.as_deref());
+            },
+            ChessCommands::Explore { choice, from } => {
+                self.explore(choice.as_deref(), from);
+            },
+            ChessCommands::Game { action } => {
+                self.manage_games(action);
+            },
+            ChessCommands::Simul { boards, movetime } => {
+                self.start_simul(boards, movetime);
+            },
+            ChessCommands::Board { number } => {
+                self.switch_board(number);
+            },
+            ChessCommands::Bench { depth } => {
+                self.bench(depth);
+            },
+            ChessCommands::Tutorial { action } => {
+                self.manage_tutorial(action);
+            },
+            ChessCommands::TrainOpening { file_path, black } => {
+                self.start_training(&file_path, black);
+            },
+            ChessCommands::PuzzleRush { minutes } => {
+                self.start_rush(minutes);
+            },
+            ChessCommands::Rating { player } => {
+                self.show_ratings(if player.is_empty() { None } else { Some(player.join(" ")) });
+            },
+            ChessCommands::Watch { white, black, delay, movetime } => {
+                self.start_watch(white, black, delay, movetime);
+            },
+            ChessCommands::Analyze { multipv } => {
+                self.start_analysis(multipv as usize);
+            },
+            ChessCommands::Serve { ws, http, host, white } => {
+                if let Some(port) = ws {
+                    self.start_serving(&host, port, white);
+                }
+                if let Some(port) = http {
+                    self.start_http(&host, port);
+                }
+            },
+            ChessCommands::Best => {
+                self.play_best_move();
+            },
+            ChessCommands::Stop => {
+                if let Some(Drill::Tutorial(_)) = self.drill {
+                    self.stop_tutorial();
+                }
+                else if let Some(Drill::Opening(_)) = self.drill {
+                    self.stop_training();
+                }
+                else if let Some(Drill::Rush(_)) = self.drill {
+                    self.stop_rush("stopped");
+                }
+                else if self.watch.is_some() {
+                    self.stop_watch();
+                }
+                else if self.analysis.is_some() {
+                    self.stop_analysis();
+                }
+                else if self.remote.is_some() || self.http.is_some() {
+                    self.stop_serving();
+                }
+                else {
+                    self.say(String::from(self.locale.nothing_to_stop()));
+                }
+            },
+            ChessCommands::Quit => {
+                self.say(String::from(self.locale.quitting()));
+                return CommandOutcome::Quit;
+            },
+        }
+        self.play_opponent_move();
+        self.update_clock(false);
+        self.record_game_end();
+        self.check_simul_end();
+        self.advance_analysis();
+        self.advance_remote();
+        #[cfg(feature = "scripting")]
+        self.run_script_hooks();
+        CommandOutcome::Continue
+    }
+}
+
+fn piece_symbol(piece: Piece, style: PieceStyle) -> char {
+    match style {
+        PieceStyle::Unicode => piece.get_unicode_symbol(),
+        PieceStyle::Ascii => piece.get_ascii_symbol(),
+    }
+}
+
+fn terminal_fg_color_256(c: u8) -> String {
+    format!("\u{001b}[38;5;{c}m")
+}
+
+fn terminal_bg_color_256(c: u8) -> String {
+    format!("\u{001b}[48;5;{c}m")
+}
+
+// Renders the board with White at the bottom, or with Black at the bottom when flipped. A
+// selected square and its destinations are drawn in highlight colors, with a dot on empty
+// destination squares, and the cursor square is bracketed. The plain board has no colors, so it
+// marks empty squares with dots, the selected piece with <> and captures with ().
+fn render_board(board: &Board, view: &BoardView) -> String {
+    let flipped = view.flipped;
+    let squares = board.get_squares();
+    let ranks: Vec<usize> = if flipped { (0..squares.len()).collect() } else { (0..squares.len()).rev().collect() };
+    let files: Vec<usize> = if flipped { (0..squares[0].len()).rev().collect() } else { (0..squares[0].len()).collect() };
+    let reset = if view.plain { "" } else { TERMINAL_COLOR_RESET };
+    // Pieces, markers and rank numbers go on the middle line of squares more than a line tall.
+    let (square_width, square_height) = view.size.get_square_size();
+    let middle = square_height / 2;
+
+    // Set colorization for the squares and pieces.
+    let (light_square, dark_square) = view.theme.get_square_colors();
+    let light_bg_color = terminal_bg_color_256(light_square);
+    let light_fg_color = terminal_fg_color_256(255);
+    let dark_bg_color = terminal_bg_color_256(dark_square);
+    let dark_fg_color = terminal_fg_color_256(240);
+
+    let mut output = String::new();
+    for &r in ranks.iter() {
+        for line in 0..square_height {
+            // reset terminal colorization before newline character to avoid coloring the rest of the line.
+            output.push_str(reset);
+            output.push('\n');
+
+            // display the row number
+            if line == middle {
+                output.push_str(format!("{} ", r + 1).as_str());
+            }
+            else {
+                output.push_str("  ");
+            }
+
+            for &f in files.iter() {
+                let square = BoardSquare::from_indices(f, r);
+                let is_selected = square.is_some() && square == view.selected;
+                let is_destination = square.is_some_and(|s| view.destinations.contains(&s));
+                let piece = squares[r][f].get_piece();
+
+                if !view.plain {
+                    if is_selected {
+                        output.push_str(terminal_bg_color_256(75).as_str());
+                    }
+                    else if is_destination {
+                        output.push_str(terminal_bg_color_256(if (r + f) % 2 == 0 { 143 } else { 229 }).as_str());
+                    }
+                    else if (r + f) % 2 == 0 {
+                        output.push_str(dark_bg_color.as_str());
+                    }
+                    else {
+                        output.push_str(light_bg_color.as_str());
+                    }
+                    match piece {
+                        Some(p) if *p.get_team() == Team::Dark => output.push_str(dark_fg_color.as_str()),
+                        Some(_) => output.push_str(light_fg_color.as_str()),
+                        None if is_destination => output.push_str(dark_fg_color.as_str()),
+                        None => (),
+                    }
+                }
+
+                // The cursor square is bracketed, e.g. "[♙]".
+                let (open, close) = if square.is_some() && square == view.cursor {
+                    ('[', ']')
+                }
+                else if view.plain && is_selected {
+                    ('<', '>')
+                }
+                else if view.plain && is_destination && piece.is_some() {
+                    ('(', ')')
+                }
+                else {
+                    (' ', ' ')
+                };
+                let symbol = match piece {
+                    Some(p) => piece_symbol(*p, view.pieces),
+                    None if is_destination => '·',
+                    None if view.plain => '.',
+                    None => ' ',
+                };
+
+                let mut cell: Vec<char> = match piece {
+                    Some(p) if view.shapes && square_height == PIECE_SHAPE_HEIGHT => piece_shape(*p)[line].chars().collect(),
+                    _ if line == middle => {
+                        let mut cell = vec![' '; square_width];
+                        cell[square_width / 2] = symbol;
+                        cell
+                    }
+                    _ => vec![' '; square_width],
+                };
+                if line == middle {
+                    for (column, marker) in [(square_width / 2 - 1, open), (square_width / 2 + 1, close)] {
+                        if marker != ' ' {
+                            cell[column] = marker;
+                        }
+                    }
+                    if cell[square_width / 2] == symbol && frame::char_width(symbol, view.wide_glyphs) == 2 {
+                        fit_wide_symbol(&mut cell, square_width / 2);
+                    }
+                }
+                output.extend(cell);
+            }
+        }
+    }
+    output.push_str(format!("{}\n  ", reset).as_str());
+    let file_labels: String = files.iter()
+        .map(|f| format!("{:^square_width$}", (b'A' + *f as u8) as char))
+        .collect();
+    output.push_str(file_labels.trim_end());
+    output.push('\n');
+    output
+}
+
+// Drops a column from a square holding a symbol drawn two columns wide, so the square keeps its
+// width: the last blank after the symbol, or the closing marker when the square has no blank.
+fn fit_wide_symbol(cell: &mut Vec<char>, symbol_column: usize) {
+    let column = (symbol_column + 1..cell.len()).rev().find(|c| cell[*c] == ' ').unwrap_or(cell.len() - 1);
+    cell.remove(column);
+}
+
+const PIECE_SHAPE_HEIGHT: usize = 3;
+
+// A piece drawn as a small picture filling a large square, one string per line. White pieces are
+// hollow and Black ones filled, so they can be told apart without colors.
+fn piece_shape(piece: Piece) -> [String; PIECE_SHAPE_HEIGHT] {
+    let shape = match piece.get_piece_type() {
+        ChessPiece::King => ["   +   ", "  (#)  ", "  /#\\  "],
+        ChessPiece::Queen => ["  \\|/  ", "  (#)  ", "  /#\\  "],
+        ChessPiece::Rook => ["  n_n  ", "  |#|  ", "  /#\\  "],
+        ChessPiece::Bishop => ["   o   ", "  (/)  ", "  /#\\  "],
+        ChessPiece::Knight => ["  ,^.  ", "  (#'  ", "  /#\\  "],
+        ChessPiece::Pawn => ["       ", "   o   ", "  /#\\  "],
+    };
+    let fill = if *piece.get_team() == Team::Dark { "#" } else { " " };
+    shape.map(|line| line.replace('#', fill))
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_board_rendering {
+    use super::*;
+    use crate::chess_pgn::PgnGame;
+    use std::sync::{Arc, Mutex};
+
+    // The rank labels of the rendered board from top to bottom, and the file footer.
+    fn labels(view: &BoardView) -> (String, String) {
+        let output = render_board(&Board::new(), view);
+        let lines: Vec<&str> = output.lines().filter(|l| !l.is_empty()).collect();
+        let ranks: String = lines.iter()
+            .filter_map(|l| l.trim_start_matches(TERMINAL_COLOR_RESET).chars().next())
+            .filter(|c| c.is_ascii_digit())
+            .collect();
+        (ranks, lines.last().unwrap().split_whitespace().collect())
+    }
+
+    #[test]
+    pub fn white_at_the_bottom_by_default() {
+        assert_eq!(labels(&BoardView::default()), (String::from("87654321"), String::from("ABCDEFGH")));
+    }
+
+    #[test]
+    pub fn wide_glyphs_keep_the_files_in_line() {
+        for size in [BoardSize::Normal, BoardSize::Large] {
+            let mut view = BoardView { size, plain: true, wide_glyphs: true, ..BoardView::default() };
+            view.selected = BoardSquare::from("e2");
+            view.cursor = BoardSquare::from("g1");
+            let output = render_board(&Board::new(), &view);
+            let lines: Vec<&str> = output.lines().filter(|l| !l.is_empty()).collect();
+            let width = |line: &str| line.chars().map(|c| frame::char_width(c, true)).sum::<usize>();
+            // Every square is as wide as on a terminal drawing the symbols narrow.
+            let square_width = size.get_square_size().0;
+            for line in &lines[..lines.len() - 1] {
+                assert_eq!(width(line), 2 + 8 * square_width, "{line}");
+            }
+            let footer = lines.last().unwrap();
+            assert_eq!(footer.find('H'), Some(2 + 7 * square_width + square_width / 2));
+        }
+        let view = BoardView { plain: true, wide_glyphs: true, ..BoardView::default() };
+        let output = render_board(&Board::new(), &view);
+        assert!(output.contains("\n1  ♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖\n"), "{output}");
+    }
+
+    #[test]
+    pub fn flipped_board_has_black_at_the_bottom() {
+        let view = BoardView { flipped: true, ..BoardView::default() };
+        assert_eq!(labels(&view), (String::from("12345678"), String::from("HGFEDCBA")));
+    }
+
+    #[test]
+    pub fn captures_are_shown_with_the_material_difference() {
+        let mut game = TuiGame::new();
+        for san in ["e4", "d5", "exd5", "Nf6", "c4", "c6", "dxc6", "Nxc6"] {
+            game.dispatch(ChessCommands::Move { pgn_move: String::from(san) });
+        }
+        assert_eq!(render_captures(&game.state, Team::Light, PieceStyle::Unicode), "♟♟ +1");
+        assert_eq!(render_captures(&game.state, Team::Dark, PieceStyle::Unicode), "♙");
+        assert_eq!(render_captures(&game.state, Team::Light, PieceStyle::Ascii), "pp +1");
+
+        let output = game.render();
+        assert!(output.starts_with("  ♙\n"));
+        assert!(output.ends_with("  ♟♟ +1\n  White to move\n"));
+    }
+
+    #[test]
+    pub fn ambiguous_moves_ask_which_piece() {
+        let mut game = TuiGame::new();
+        for san in ["d4", "d5", "Nf3", "Nf6"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        game.take_messages();
+        game.run_command_line("move Nd2");
+        assert_eq!(game.take_messages()[0], "Nd2 could be played from b1 or f3. Type the square of the piece to move.");
+        // Anything else drops the move.
+        game.run_command_line("flip");
+        game.run_command_line("b1");
+        assert_eq!(game.state.get_ply_count(), 4);
+
+        game.run_command_line("move Nd2");
+        game.run_command_line("select F3");
+        let messages = game.take_messages();
+        assert_eq!(messages[messages.len() - 1], "Played Nfd2");
+    }
+
+    #[test]
+    pub fn positions_are_compared_with_a_fen() {
+        let mut game = TuiGame::new();
+        game.run_command_line("move e4");
+        game.take_messages();
+        game.run_command_line("diff rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(game.take_messages(), vec![String::from("The position matches the FEN.")]);
+        game.run_command_line("diff rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1");
+        assert_eq!(game.take_messages(), vec![
+            String::from("The position differs from the FEN, the board's side given first:"),
+            String::from("  d2: White pawn vs empty"),
+            String::from("  e2: empty vs White pawn"),
+            String::from("  d4: empty vs White pawn"),
+            String::from("  e4: White pawn vs empty"),
+            String::from("  en passant: e3 vs -"),
+        ]);
+        game.run_command_line("diff 8/8 w - -");
+        assert_eq!(game.take_messages(), vec![String::from("Invalid FEN: the piece placement field is invalid.")]);
+    }
+
+    #[test]
+    pub fn diagrams_are_shown_and_exported() {
+        let mut game = TuiGame::new();
+        game.run_command_line("move e4");
+        game.take_messages();
+        game.run_command_line("export diagram");
+        let messages = game.take_messages();
+        assert!(messages[0].starts_with("  +-----------------+\n8 | r n b q k b n r |\n"));
+        assert!(messages[0].ends_with("\n    a b c d e f g h\nBlack to move"));
+
+        let path = std::env::temp_dir().join(format!("rust_chess_diagram_{}.txt", std::process::id()));
+        game.run_command_line(&format!("export diagram {} --unicode --flip", path.display()));
+        game.run_command_line(&format!("export diagram {}", path.display()));
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("  ┌─────────────────┐\n1 │ ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖ │\n"));
+        assert_eq!(game.take_messages(), vec![
+            format!("Exported diagram to {}", path.display()),
+            format!("Could not export diagram: {} already exists, use --force to overwrite it.", path.display()),
+        ]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn games_are_exported_as_latex() {
+        let mut game = TuiGame::new();
+        for san in ["e4", "e5", "Nf3"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        game.take_messages();
+        game.run_command_line("export latex --diagram 1w --diagram 0");
+        let messages = game.take_messages();
+        assert!(messages[0].contains("\n\\chessboard\n\n\\mainline{1. e4}\n\n\\chessboard\n\n\\mainline{1... e5 2. Nf3}"), "{}", messages[0]);
+    }
+
+    #[test]
+    pub fn flip_command_persists() {
+        let mut game = TuiGame::new();
+        game.dispatch(ChessCommands::Flip);
+        game.dispatch(ChessCommands::Move { pgn_move: String::from("e4") });
+        game.dispatch(ChessCommands::Reset);
+        assert!(game.view.flipped);
+        game.dispatch(ChessCommands::Flip);
+        assert!(!game.view.flipped);
+    }
+
+    #[test]
+    pub fn plain_board_has_no_escape_codes() {
+        let mut game = TuiGame::new();
+        game.view.plain = true;
+        game.run_command_line("move e4");
+        game.run_command_line("move d5");
+        game.run_command_line("select e4");
+        let output = game.render();
+        assert!(!output.contains('\u{001b}'));
+        assert!(output.contains("\n5  .  .  . (♟) ·  .  .  . \n"));
+        assert!(output.contains("\n4  .  .  .  . <♙> .  .  . \n"));
+    }
+
+    #[test]
+    pub fn config_changes_apply_to_the_board_and_new_games() {
+        let mut game = TuiGame::new();
+        game.view.plain = true;
+        game.run_command_line("config set pieces ascii");
+        game.run_command_line("config set player_name Raul Rojas");
+        game.run_command_line("config set theme purple");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "pieces = ascii");
+        assert!(messages[2].starts_with("Could not change the setting: purple is not a valid value for theme"));
+
+        assert!(game.render().contains("\n1  R  N  B  Q  K  B  N  R \n"));
+        game.run_command_line("reset");
+        assert_eq!(game.state.get_pgn_game().get_white(), "Raul Rojas");
+    }
+
+    #[test]
+    pub fn screen_readers_get_the_game_in_words() {
+        let mut game = TuiGame::new();
+        game.run_command_line("config set screen_reader on");
+        assert_eq!(game.render(), "White to move.\n");
+        game.run_command_line("move e4");
+        game.run_command_line("move f5");
+        game.run_command_line("move exf5");
+        assert_eq!(game.render(), "White pawn from e4 takes pawn on f5.\nBlack to move.\n");
+        game.run_command_line("move g5");
+        game.run_command_line("move Qh5");
+        assert_eq!(game.render(), "White queen from d1 to h5, checkmate.\nCheckmate \u{2014} White wins.\n");
+        game.take_messages();
+
+        game.run_command_line("read e8");
+        game.run_command_line("read 4");
+        game.run_command_line("read H");
+        game.run_command_line("read e9");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "e8: Black king");
+        assert_eq!(messages[1], "rank 4: empty");
+        assert_eq!(messages[2], "file h: h1 White rook, h2 White pawn, h5 White queen, h7 Black pawn, h8 Black rook");
+        assert!(messages[3].starts_with("Can't read e9"));
+        game.run_command_line("read");
+        assert!(game.take_messages()[0].starts_with("White: king on e1, queen on h5, rooks on a1 and h1,"));
+    }
+
+    #[test]
+    pub fn moves_are_typed_in_the_input_language() {
+        let mut game = TuiGame::new();
+        game.run_command_line("config set input_language german");
+        for san in ["e4", "e5", "Sf3", "Sc6", "Lb5"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        assert_eq!(game.state.get_pgn_game().get_ply_count(), 5);
+        assert!(game.state.get_pgn_game().to_string().contains("1. e4 e5 2. Nf3 Nc6 3. Bb5"));
+    }
+
+    #[test]
+    pub fn command_prefixes_follow_the_setting() {
+        let mut game = TuiGame::new();
+        game.take_messages();
+        game.run_command_line("mo e4");
+        assert_eq!(game.take_messages()[0], "Played e4");
+        game.run_command_line("config set command_prefixes off");
+        game.take_messages();
+        game.run_command_line("mo e5");
+        assert_eq!(game.state.get_pgn_game().get_ply_count(), 1);
+        game.take_messages();
+        game.run_command_line("move e5");
+        assert_eq!(game.take_messages()[0], "Played e5");
+    }
+
+    #[test]
+    pub fn messages_follow_the_language_setting() {
+        let mut game = TuiGame::new();
+        game.env_locale = Some(Locale::German);
+        game.run_command_line("config set language auto");
+        game.take_messages();
+        game.run_command_line("move e4");
+        game.run_command_line("move Ke2");
+        game.run_command_line("flip");
+        assert_eq!(game.take_messages(), [
+            "Gespielt: e4",
+            "Unzulässiger Zug Ke2: no legal move matches it in this position.",
+            "Das Brett wird aus Sicht von Schwarz gezeigt.",
+        ]);
+        assert!(game.render().ends_with("  Schwarz am Zug\n"));
+        // The record of the game stays in English.
+        game.run_command_line("resign");
+        assert_eq!(game.take_messages()[0], "Schwarz gibt auf, Weiß gewinnt.");
+        assert!(game.state.get_pgn_game().to_string().contains("Black resigns"));
+
+        game.run_command_line("config set language english");
+        game.run_command_line("reset");
+        assert_eq!(game.take_messages().last().unwrap(), "Resetting board.");
+    }
+
+    #[test]
+    pub fn status_and_error_messages_are_translated() {
+        let mut config = ChessConfig::new();
+        config.set("language", "german").unwrap();
+        config.set("time_control", "1+2").unwrap();
+        let mut game = TuiGame::with_config(config, None);
+        game.take_messages();
+        game.run_command_line("goto 3");
+        game.run_command_line("select e5");
+        game.run_command_line("adjudicate 1-0");
+        game.run_command_line("adjudicate 0-1");
+        assert_eq!(game.take_messages(), [
+            "Die Partie hat nur 0 Halbzüge.",
+            "Auf e5 steht keine Figur.",
+            "Partie entschieden: 1-0",
+            "Die Partie ist bereits beendet (1-0).",
+        ]);
+
+        game.run_command_line("new");
+        let minute_ago = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        game.clock.as_mut().unwrap().start(Team::Light, minute_ago);
+        game.take_messages();
+        game.run_command_line("move e4");
+        assert_eq!(game.take_messages()[0], "Weiß hat die Zeit überschritten. Schwarz gewinnt auf Zeit.");
+        // The record of the game stays in English.
+        assert!(game.state.get_pgn_game().to_string().contains("{White lost on time}"));
+
+        game.run_command_line("new --vs-engine --movetime 10");
+        game.take_messages();
+        game.run_command_line("draw");
+        assert_eq!(game.take_messages()[0], format!("{} lehnt das Remis ab und spielt weiter.", chess_engine::ENGINE_NAME));
+
+        game.run_command_line("game list");
+        game.run_command_line("keys");
+        game.run_command_line("bench 1");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], format!("* 1. ? gegen {}, 0 Halbzüge, Weiß am Zug", chess_engine::ENGINE_NAME));
+        assert!(messages[1].starts_with("Tasten der Vollbildoberfläche"));
+        assert!(messages[1].contains("den letzten Zug zurücknehmen"));
+        assert!(messages[1].ends_with("Strg-C und Strg-D beenden immer."));
+        assert!(messages[2].starts_with("Stellungen bis Tiefe 1 durchsucht"));
+        assert!(messages[2].contains(" Knoten, "));
+
+        let mut game = TuiGame::new();
+        game.run_command_line("config set language german");
+        game.run_command_line("config set blunder_alerts on");
+        for pgn_move in ["e4", "d5", "Qg4"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        assert!(game.take_messages().last().unwrap().starts_with("Qg4?? \u{2014} verliert die Dame, besser war "));
+        assert!(Locale::German.is_yes("ja") && Locale::German.is_yes("J") && !Locale::German.is_yes("n"));
+        assert!(!Locale::English.is_yes("ja"));
+    }
+
+    #[test]
+    pub fn stepping_through_a_game_shows_annotations() {
+        let mut game = TuiGame::new();
+        let pgn_game = PgnGame::from("1. e4 e5 2. Nf3 $1 {Developing} (2. f4 exf4) 2... Nc6 $13 1/2-1/2").unwrap();
+        game.state = GameState::from_pgn_game(pgn_game).unwrap();
+
+        game.run_command_line("start");
+        assert_eq!(game.take_messages(), vec![String::from("Start of the game")]);
+        assert_eq!(game.state.get_viewed_board(), &Board::new());
+        game.run_command_line("next 3");
+        assert_eq!(game.take_messages(), vec![String::from("2. Nf3! {Developing} (2. f4 exf4)")]);
+        assert!(game.render().ends_with("  Black to move (half move 3 of 4)\n"));
+        game.run_command_line("move Nc6");
+        assert!(game.take_messages()[0].starts_with("An earlier position is shown."));
+        game.run_command_line("prev");
+        assert_eq!(game.take_messages(), vec![String::from("1... e5")]);
+        game.run_command_line("goto 9");
+        assert_eq!(game.take_messages(), vec![String::from("The game has only 4 half moves.")]);
+        game.run_command_line("end");
+        assert_eq!(game.take_messages(), vec![String::from("2... Nc6 $13")]);
+        assert!(game.render().ends_with("  Game over \u{2014} 1/2-1/2\n"));
+        assert_eq!(game.state.get_pgn_game().get_ply_count(), 4);
+    }
+
+    #[test]
+    pub fn goto_takes_move_numbers_and_replays_undone_moves() {
+        let mut game = TuiGame::new();
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        game.run_command_line("undo 4");
+        game.take_messages();
+
+        game.run_command_line("goto 1b");
+        assert_eq!(game.take_messages(), vec![String::from("1... e5")]);
+        assert_eq!(game.state.get_ply_count(), 2);
+        game.run_command_line("goto 3W");
+        assert_eq!(game.take_messages(), vec![String::from("3. Bb5")]);
+        assert_eq!(game.state.get_ply_count(), 5);
+        assert!(game.state.can_redo());
+        game.run_command_line("goto 2w");
+        assert_eq!(game.take_messages(), vec![String::from("2. Nf3")]);
+        assert!(game.state.is_viewing_history());
+        game.run_command_line("goto 4w");
+        assert_eq!(game.take_messages(), vec![String::from("The game has only 6 half moves.")]);
+        game.run_command_line("goto 0w");
+        assert!(game.take_messages()[0].contains("expected a number of half moves or a move number followed by w or b"));
+    }
+
+    #[test]
+    pub fn moves_are_annotated_and_saved() {
+        let mut game = TuiGame::new();
+        game.run_command_line("annotate-move !");
+        assert_eq!(game.take_messages(), vec![String::from("There is no such move to annotate.")]);
+        for san in ["e4", "e5", "Nf3"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        game.take_messages();
+        game.run_command_line("annotate-move !?");
+        game.run_command_line("annotate-move $14");
+        game.run_command_line("annotate-move ! --ply 2w");
+        assert_eq!(game.take_messages(), vec![String::from("2. Nf3!?"), String::from("2. Nf3!? $14"), String::from("2. Nf3! $14")]);
+        game.run_command_line("annotate-move ?? --ply 1b");
+        game.run_command_line("annotate-move good --ply 1w");
+        assert_eq!(game.take_messages()[1], "Unknown annotation good, use !, ?, !!, ??, !?, ?! or a glyph number like $14.");
+        assert!(game.state.get_pgn_game().to_string().ends_with("1. e4 e5 $4 2. Nf3 $1 $14 *"));
+        game.run_command_line("annotate-move clear");
+        assert!(game.state.get_pgn_game().get_plies()[2].1.get_nags().is_empty());
+        game.run_command_line("undo");
+        game.run_command_line("redo");
+        assert_eq!(game.state.get_pgn_game().get_plies()[1].1.get_nags(), &vec![4]);
+    }
+
+    #[test]
+    pub fn variations_are_listed_and_entered() {
+        let mut game = TuiGame::new();
+        game.run_command_line("variation list");
+        assert!(game.take_messages()[0].starts_with("The game has no variations."));
+        for san in ["e4", "e5", "Nf3"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        game.run_command_line("undo");
+        game.run_command_line("move Bc4");
+        game.take_messages();
+        game.run_command_line("variation list");
+        assert_eq!(game.take_messages(), vec![String::from("1: 2. Nf3 (instead of 2. Bc4)")]);
+        game.run_command_line("variation promote 1");
+        assert_eq!(game.take_messages(), vec![
+            String::from("Playing variation 1, the line it replaces is kept as a variation."),
+            String::from("2. Nf3 (2. Bc4)"),
+        ]);
+        game.run_command_line("variation enter 3");
+        assert_eq!(game.take_messages(), vec![String::from("Could not enter variation 3: there is no such variation.")]);
+    }
+
+    #[test]
+    pub fn clocks_run_for_the_side_to_move_and_flag() {
+        let mut config = ChessConfig::new();
+        config.set("time_control", "1+2").unwrap();
+        let mut game = TuiGame::with_config(config, None);
+        assert_eq!(game.state.get_pgn_game().get_tag("TimeControl"), Some(String::from("60+2")));
+        assert!(game.render().starts_with("  1:00  \n"));
+
+        game.run_command_line("move e4");
+        assert_eq!(game.clock.as_ref().unwrap().get_running(), Some(Team::Dark));
+        assert!(game.render().starts_with("▶ 1:00  \n"));
+        game.take_messages();
+        game.run_command_line("undo");
+        assert_eq!(game.take_messages()[1], "The clocks are stopped until the next move.");
+        assert_eq!(game.clock.as_ref().unwrap().get_running(), None);
+
+        let minute_ago = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        game.clock.as_mut().unwrap().start(Team::Light, minute_ago);
+        game.run_command_line("move e4");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "White ran out of time. Black wins on time.");
+        assert!(messages[1].starts_with("The game is over (0-1)."));
+        assert_eq!(game.state.get_pgn_game().get_tag("Termination"), Some(String::from("time forfeit")));
+        assert_eq!(game.clock.as_ref().unwrap().get_running(), None);
+    }
+
+    #[test]
+    pub fn flags_fall_while_waiting_for_input() {
+        let mut config = ChessConfig::new();
+        config.set("time_control", "1+0").unwrap();
+        let mut game = TuiGame::with_config(config, None);
+        game.run_command_line("move e4");
+        let minute_ago = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        game.clock.as_mut().unwrap().start(Team::Dark, minute_ago);
+
+        let (_sender, input) = mpsc::channel();
+        let (ask, _asked) = mpsc::channel();
+        let mut frontend = LineFrontend { input, ask, printer: None, messages: Vec::new(), redraw: false, prompting: true };
+        assert!(matches!(frontend.read_command(&mut game, WATCH_TICK).unwrap(), FrontendInput::Idle));
+        game.advance_idle();
+        assert_eq!(game.state.get_pgn_game().get_result(), &PgnResult::WhiteWin);
+        assert_eq!(game.state.get_pgn_game().get_result_comment(), Some(&String::from("Black lost on time")));
+    }
+
+    // Keeps what an edited prompt would print above the line being typed.
+    struct Printed(Arc<Mutex<Vec<String>>>);
+
+    impl rustyline::ExternalPrinter for Printed {
+        fn print(&mut self, msg: String) -> rustyline::Result<()> {
+            self.0.lock().unwrap().push(msg);
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn messages_go_above_an_edited_prompt() {
+        let mut config = ChessConfig::new();
+        config.set("time_control", "1+0").unwrap();
+        let mut game = TuiGame::with_config(config, None);
+        game.run_command_line("move e4");
+        game.take_messages();
+        let minute_ago = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        game.clock.as_mut().unwrap().start(Team::Dark, minute_ago);
+
+        let (_sender, input) = mpsc::channel();
+        let (ask, _asked) = mpsc::channel();
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let printer: Option<PromptPrinter> = Some(Box::new(Printed(printed.clone())));
+        let mut frontend = LineFrontend { input, ask, printer, messages: Vec::new(), redraw: true, prompting: true };
+        assert!(matches!(frontend.read_command(&mut game, WATCH_TICK).unwrap(), FrontendInput::Idle));
+        game.advance_idle();
+        frontend.notify(game.take_messages());
+        assert_eq!(*printed.lock().unwrap(), vec![String::from("Black ran out of time. White wins on time.")]);
+        // Redrawing in place clears the screen, so they are shown again below the board.
+        assert_eq!(frontend.messages, vec![String::from("Black ran out of time. White wins on time.")]);
+    }
+
+    #[test]
+    pub fn games_can_be_opened_and_switched() {
+        let mut game = TuiGame::new();
+        game.run_command_line("move e4");
+        game.run_command_line("game new");
+        game.run_command_line("move d4");
+        game.run_command_line("move d5");
+        game.run_command_line("game new");
+        game.take_messages();
+
+        game.run_command_line("game list");
+        assert_eq!(game.take_messages(), vec![String::from(
+            "  1. ? vs ?, 1 half move(s), Black to move\n  2. ? vs ?, 2 half move(s), White to move\n* 3. ? vs ?, 0 half move(s), White to move"
+        )]);
+
+        game.run_command_line("game switch 1");
+        assert_eq!(game.state.get_ply_count(), 1);
+        game.run_command_line("move e5");
+        game.run_command_line("game switch 2");
+        assert_eq!(game.state.get_pgn_game().get_plies()[0].0.to_string(), "d4");
+        game.run_command_line("game switch 1");
+        assert_eq!(game.state.get_ply_count(), 2);
+        game.take_messages();
+
+        game.run_command_line("game switch 4");
+        assert_eq!(game.take_messages(), vec![String::from("There is no game 4, game list shows the open games.")]);
+        game.run_command_line("game list");
+        assert!(game.take_messages()[0].ends_with("  3. ? vs ?, 0 half move(s), White to move"));
+    }
+
+    #[test]
+    pub fn every_ending_gives_the_record_its_result() {
+        let mut game = TuiGame::new();
+        for pgn_move in ["f3", "e5", "g4", "Qh4"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        assert!(game.state.get_pgn_game().to_string().ends_with("Qh4# 0-1"));
+        game.take_messages();
+        game.run_command_line("resign");
+        assert_eq!(game.take_messages(), vec![String::from("The game has already ended (0-1).")]);
+
+        game.run_command_line("new");
+        game.run_command_line("move e4");
+        game.run_command_line("resign");
+        assert_eq!(game.take_messages()[2], "Black resigns, White wins.");
+        assert!(game.state.get_pgn_game().to_string().ends_with("1. e4 {Black resigns} 1-0"));
+
+        game.run_command_line("new");
+        game.run_command_line("draw");
+        assert_eq!(game.take_messages()[1], "The game is drawn by agreement.");
+        assert!(game.state.get_pgn_game().to_string().ends_with("{Draw agreed} 1/2-1/2"));
+
+        game.run_command_line("new --vs-engine --movetime 10");
+        game.run_command_line("draw");
+        assert_eq!(game.take_messages()[1], format!("{} declines the draw and plays on.", chess_engine::ENGINE_NAME));
+        game.run_command_line("resign");
+        assert_eq!(game.take_messages()[0], "White resigns, Black wins.");
+
+        game.run_command_line("new");
+        for pgn_move in ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        game.take_messages();
+        game.run_command_line("claim draw");
+        assert!(game.take_messages()[0].ends_with("the game goes on."));
+        game.run_command_line("claim draw Ng8");
+        assert_eq!(game.take_messages()[..2], ["Played Ng8", "Black claims a draw by threefold repetition, the game is drawn."]);
+        assert!(game.state.get_pgn_game().to_string().ends_with("{Draw claimed by threefold repetition} 1/2-1/2"));
+    }
+
+    #[test]
+    pub fn large_board_spans_several_lines_per_square() {
+        let view = BoardView { size: BoardSize::Large, plain: true, ..BoardView::default() };
+        let output = render_board(&Board::new(), &view);
+        let lines: Vec<&str> = output.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(lines.len(), 8 * 3 + 1);
+        assert_eq!(lines[0], format!("  {}", " ".repeat(56)));
+        assert!(lines[1].starts_with("8    ♜      ♞   "));
+        assert_eq!(lines[24], "     A      B      C      D      E      F      G      H");
+        assert_eq!(labels(&view).0, "87654321");
+
+        let shapes = BoardView { shapes: true, ..view };
+        let output = render_board(&Board::new(), &shapes);
+        let lines: Vec<&str> = output.lines().filter(|l| !l.is_empty()).collect();
+        assert!(lines[0].starts_with("    n_n    ,^.     o   "));
+        assert!(lines[2].starts_with("    /#\\    /#\\  "));
+        assert!(lines[23].starts_with("    / \\    / \\  "));
+    }
+    #[test]
+    pub fn bench_compares_the_move_ordering() {
+        let mut game = TuiGame::new();
+        game.run_command_line("bench 1");
+        let report = game.take_messages().remove(0);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Positions searched to depth 1, without and with move ordering:");
+        assert!(lines[1].starts_with("  start position "));
+        assert!(lines.last().unwrap().starts_with("  Move ordering searched "));
+        game.run_command_line("bench 9");
+        assert!(game.take_messages()[0].starts_with("error: invalid value '9'"));
+    }
+}
+
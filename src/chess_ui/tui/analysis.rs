@@ -0,0 +1,328 @@
+/*
+analysis.rs
+Analysis of the shown position by the configured UCI engine or, without one, the built-in engine.
+The engine searches until stopped and its latest depth, score, speed and principal variation
+are shown as they come in, for the best move or for as many of the best moves as asked for
+(MultiPV). When another position is shown, by moving or stepping through the game, the analysis
+starts over on it.
+*/
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    chess_core::{Board, Team},
+    chess_engine::{self, ENGINE_NAME},
+    chess_pgn::PgnAnnotation,
+    chess_uci::{EngineScore, SearchInfo, SearchLimit, UciEngine},
+};
+use super::TuiGame;
+
+// Moves of the principal variation shown.
+const PV_MOVES_SHOWN: usize = 10;
+
+pub struct Analysis {
+    engine: AnalysisEngine,
+    // The position analyzed.
+    board: Board,
+    // How many of the best moves are analyzed, and what the engine reported for each, the best
+    // first.
+    lines: usize,
+    infos: Vec<SearchInfo>,
+}
+
+enum AnalysisEngine {
+    BuiltIn(Option<BuiltInSearch>),
+    Uci(UciEngine),
+}
+
+// A search of the built-in engine on a thread of its own, ended when dropped.
+struct BuiltInSearch {
+    stop: Arc<AtomicBool>,
+    infos: Receiver<Vec<SearchInfo>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for BuiltInSearch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Analysis {
+    fn get_name(&self) -> &str {
+        match &self.engine {
+            AnalysisEngine::BuiltIn(_) => ENGINE_NAME,
+            AnalysisEngine::Uci(engine) => engine.get_name(),
+        }
+    }
+
+    // The analysis as a line per analyzed move, e.g.
+    // "Rust Chess: depth 12, +0.35, 1.2M nodes at 850k/s: 1. e4 e5 2. Nf3 Nc6" for the best and
+    // "  2) +0.20: 1. d4 d5 2. c4" for the next ones.
+    pub fn render(&self) -> Vec<String> {
+        let mut line = format!("{}: ", self.get_name());
+        let no_info = SearchInfo::default();
+        let best = self.infos.first().unwrap_or(&no_info);
+        let depth = match best.get_depth() {
+            Some(d) => d,
+            None if self.board.legal_moves().is_empty() => {
+                line.push_str("no moves in this position");
+                return vec![line];
+            }
+            None => {
+                line.push_str("thinking...");
+                return vec![line];
+            }
+        };
+        line.push_str(format!("depth {depth}").as_str());
+        if let Some(score) = best.get_score() {
+            line.push_str(format!(", {}", self.render_score(score)).as_str());
+        }
+        if let Some(nodes) = best.get_nodes() {
+            line.push_str(format!(", {} nodes", format_count(nodes)).as_str());
+        }
+        if let Some(nps) = best.get_nps() {
+            line.push_str(format!(" at {}/s", format_count(nps)).as_str());
+        }
+        let pv = self.render_pv(best);
+        if !pv.is_empty() {
+            line.push_str(format!(": {pv}").as_str());
+        }
+
+        let mut lines = vec![line];
+        for (i, info) in self.infos.iter().enumerate().skip(1).take(self.lines - 1) {
+            let score = info.get_score().map(|s| self.render_score(s)).unwrap_or_else(|| String::from("?"));
+            lines.push(format!("  {}) {score}: {}", i + 1, self.render_pv(info)));
+        }
+        lines
+    }
+
+    // Engines score from the side to move, shown from White's side like a clock.
+    fn render_score(&self, score: EngineScore) -> String {
+        let score = if self.board.get_turn() == Team::Dark { score.negate() } else { score };
+        score.to_string()
+    }
+
+    // A principal variation in SAN with move numbers, up to the first move that doesn't fit
+    // the position.
+    fn render_pv(&self, info: &SearchInfo) -> String {
+        let pv = info.get_pv();
+        self.board.to_san_line(&pv[..pv.len().min(PV_MOVES_SHOWN)])
+    }
+}
+
+impl TuiGame {
+    pub(super) fn start_analysis(&mut self, lines: usize) {
+        if self.analysis.is_some() {
+            self.say(String::from(self.locale.already_analyzing()));
+            return;
+        }
+        let engine = match self.config.get_engine_path() {
+            Some(path) => match UciEngine::start(path).and_then(|mut e| e.new_game().map(|()| e)).and_then(|mut e| {
+                // Engines analyze a single line unless told otherwise.
+                if lines > 1 {
+                    e.set_option("MultiPV", &lines.to_string())?;
+                }
+                Ok(e)
+            }) {
+                Ok(e) => AnalysisEngine::Uci(e),
+                Err(e) => {
+                    self.say(self.locale.engine_not_started(&e.to_string()));
+                    return;
+                }
+            },
+            None => AnalysisEngine::BuiltIn(None),
+        };
+        let mut analysis = Analysis { engine, board: Board::new(), lines, infos: Vec::new() };
+        self.say(self.locale.analyzing(analysis.get_name()));
+        self.search_shown_position(&mut analysis);
+        self.analysis = Some(analysis);
+    }
+
+    pub(super) fn stop_analysis(&mut self) {
+        self.analysis = None;
+        self.say(String::from(self.locale.stopped_analysis()));
+    }
+
+    // Takes in what the engine reported, and starts over when another position is shown.
+    // Returns whether the analysis shown changed.
+    pub(super) fn advance_analysis(&mut self) -> bool {
+        let mut analysis = match self.analysis.take() {
+            Some(a) => a,
+            None => return false,
+        };
+        let changed = if analysis.board != *self.state.get_viewed_board() {
+            self.search_shown_position(&mut analysis);
+            true
+        }
+        else {
+            let before = analysis.infos.clone();
+            match &mut analysis.engine {
+                AnalysisEngine::BuiltIn(Some(search)) => {
+                    if let Some(infos) = search.infos.try_iter().last() {
+                        analysis.infos = infos;
+                    }
+                }
+                AnalysisEngine::BuiltIn(None) => (),
+                AnalysisEngine::Uci(engine) => {
+                    if let Err(e) = engine.poll_search() {
+                        self.say(self.locale.analysis_failed(engine.get_name(), &e.to_string()));
+                        return true;
+                    }
+                    if !engine.get_search_lines().is_empty() {
+                        analysis.infos = engine.get_search_lines().to_vec();
+                    }
+                }
+            }
+            analysis.infos != before
+        };
+        self.analysis = Some(analysis);
+        changed
+    }
+
+    fn search_shown_position(&mut self, analysis: &mut Analysis) {
+        analysis.board = self.state.get_viewed_board().clone();
+        analysis.infos = Vec::new();
+        match &mut analysis.engine {
+            AnalysisEngine::BuiltIn(search) => {
+                // The running search is stopped before the next one starts.
+                *search = None;
+                let stop = Arc::new(AtomicBool::new(false));
+                let (sender, infos) = mpsc::channel();
+                let board = analysis.board.clone();
+                let stopped = stop.clone();
+                let lines = analysis.lines;
+                let thread = thread::spawn(move || {
+                    chess_engine::search_lines_until_stopped(&board, SearchLimit::Infinite, lines, &stopped, |results, _| {
+                        let _ = sender.send(results.iter().map(|r| r.get_info().clone()).collect());
+                    });
+                });
+                *search = Some(BuiltInSearch { stop, infos, thread: Some(thread) });
+            }
+            AnalysisEngine::Uci(engine) => {
+                let moves = self.state.get_board_moves();
+                let shown = &moves[..self.state.get_viewed_ply()];
+                let restarted = match engine.is_searching() {
+                    // The best move of the old search arrives before anything of the new one.
+                    true => engine.stop_search().and_then(|()| engine.wait_search().map(|_| ())),
+                    false => Ok(()),
+                }
+                .and_then(|()| engine.start_search(shown, SearchLimit::Infinite));
+                if let Err(e) = restarted {
+                    self.say(self.locale.engine_failed(engine.get_name(), &e.to_string()));
+                }
+            }
+        }
+    }
+
+    // Plays the first move of the principal variation.
+    pub(super) fn play_best_move(&mut self) {
+        let analysis = match &self.analysis {
+            Some(a) => a,
+            None => {
+                self.say(String::from(self.locale.nothing_analyzed()));
+                return;
+            }
+        };
+        let best = match analysis.infos.first().and_then(|i| i.get_pv().first()) {
+            Some(m) if analysis.board == *self.state.get_viewed_board() => *m,
+            _ => {
+                self.say(String::from(self.locale.no_engine_move()));
+                return;
+            }
+        };
+        match self.state.play_board_move(best, PgnAnnotation::new()) {
+            Ok(played) => {
+                self.say(self.locale.played(&played.to_string()));
+                self.update_clock(true);
+            }
+            Err(e) => self.say(self.locale.move_not_played(&best.to_string(), &e.to_string())),
+        }
+    }
+}
+
+// Counts shortened to thousands and millions, e.g. 850k and 1.2M.
+fn format_count(count: u64) -> String {
+    if count >= 1_000_000 {
+        format!("{:.1}M", count as f64 / 1_000_000.0)
+    }
+    else if count >= 1_000 {
+        format!("{}k", count / 1_000)
+    }
+    else {
+        count.to_string()
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_analysis {
+    use std::time::{Duration, Instant};
+    use super::*;
+
+    fn wait_for_depth(game: &mut TuiGame, depth: u32) {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while game.analysis.as_ref().unwrap().infos.iter().map(|i| i.get_depth().unwrap_or(0)).min().unwrap_or(0) < depth {
+            assert!(Instant::now() < deadline, "no analysis to depth {depth}");
+            thread::sleep(Duration::from_millis(10));
+            game.advance_analysis();
+        }
+    }
+
+    #[test]
+    pub fn analysis_follows_the_position_and_plays_the_best_move() {
+        let mut game = TuiGame::new();
+        game.run_command_line("best");
+        assert_eq!(game.take_messages()[0], "Nothing is being analyzed, analyze starts an analysis.");
+
+        game.run_command_line("analyze");
+        assert_eq!(game.take_messages()[0], format!("Analyzing with {ENGINE_NAME}, best plays its first move and stop ends the analysis."));
+        wait_for_depth(&mut game, 2);
+        let line = game.analysis.as_ref().unwrap().render().remove(0);
+        assert!(line.starts_with(&format!("{ENGINE_NAME}: depth ")), "{line}");
+        assert!(line.contains(": 1. "), "{line}");
+
+        game.run_command_line("move e4");
+        assert_eq!(game.analysis.as_ref().unwrap().board, *game.state.get_board());
+        wait_for_depth(&mut game, 1);
+        assert!(game.analysis.as_ref().unwrap().render()[0].contains(": 1... "));
+        game.take_messages();
+        game.run_command_line("best");
+        assert!(game.take_messages()[0].starts_with("Played "));
+        assert_eq!(game.state.get_ply_count(), 2);
+
+        game.run_command_line("stop");
+        assert_eq!(game.take_messages()[0], "Stopped the analysis.");
+        assert!(game.analysis.is_none());
+    }
+
+    #[test]
+    pub fn several_lines_are_shown() {
+        let mut game = TuiGame::new();
+        game.run_command_line("analyze --multipv 3");
+        wait_for_depth(&mut game, 2);
+        let lines = game.analysis.as_ref().unwrap().render();
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        assert!(lines[1].starts_with("  2) "), "{}", lines[1]);
+        assert!(lines[2].starts_with("  3) "), "{}", lines[2]);
+        assert!(lines[2].contains(": 1. "));
+    }
+
+    #[test]
+    pub fn counts_are_shortened() {
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(850_123), "850k");
+        assert_eq!(format_count(1_234_567), "1.2M");
+    }
+}
@@ -0,0 +1,114 @@
+/*
+annotate.rs
+The annotate command, which has the configured UCI engine or, without one, the built-in engine
+go over a whole game and saves the annotated game to a PGN file.
+*/
+
+use std::{path::Path, time::Duration};
+
+use crate::{
+    chess_annotate::annotate_game,
+    chess_core::Team,
+    chess_engine::{self, ENGINE_NAME},
+    chess_game::GameState,
+    chess_uci::{SearchLimit, UciEngine},
+};
+use super::TuiGame;
+
+// How long the built-in engine looks at each position unless told otherwise.
+const BUILT_IN_POSITION_TIME: Duration = Duration::from_millis(200);
+
+impl TuiGame {
+    // Annotates the current game, or the first game of `from`, and saves it to `file_path`.
+    pub(super) fn annotate(&mut self, file_path: &Path, from: Option<&Path>, movetime: Option<u64>, force: bool) {
+        let loaded = match from {
+            Some(path) => match self.read_game_file(path) {
+                Some((state, _)) => Some(state),
+                None => return,
+            },
+            None => None,
+        };
+        let state: &GameState = loaded.as_ref().unwrap_or(&self.state);
+        if state.get_ply_count() == 0 {
+            self.say(String::from(self.locale.no_moves_to_annotate()));
+            return;
+        }
+
+        let movetime = movetime.map(|ms| SearchLimit::MoveTime(Duration::from_millis(ms)));
+        let annotated = match self.config.get_engine_path() {
+            Some(path) => {
+                let mut engine = match UciEngine::start(path).and_then(|mut e| e.new_game().map(|()| e)) {
+                    Ok(e) => e,
+                    Err(e) => {
+                        self.say(self.locale.engine_not_started(&e.to_string()));
+                        return;
+                    }
+                };
+                let limit = movetime.unwrap_or(SearchLimit::Depth(self.config.get_engine_depth()));
+                let name = engine.get_name().clone();
+                annotate_game(state, &name, |_, moves| engine.search(moves, limit).map(Some))
+                    .map_err(|e| self.locale.engine_failed(&name, &e.to_string()))
+            }
+            None => {
+                let limit = movetime.unwrap_or(SearchLimit::MoveTime(BUILT_IN_POSITION_TIME));
+                annotate_game(state, ENGINE_NAME, |board, _| Ok(chess_engine::search(board, limit)))
+                    .map_err(|e| self.locale.engine_failed(ENGINE_NAME, &e.to_string()))
+            }
+        };
+        let annotated = match annotated {
+            Ok(a) => a,
+            Err(message) => {
+                self.say(message);
+                return;
+            }
+        };
+
+        match annotated.get_game().save_to_file(file_path, force) {
+            Ok(()) => {
+                self.say(self.locale.saved_annotated_game(&file_path.display().to_string()));
+                self.say(self.locale.player_summary(Team::Light, annotated.get_summary(Team::Light)));
+                self.say(self.locale.player_summary(Team::Dark, annotated.get_summary(Team::Dark)));
+            }
+            Err(e) => self.say(self.locale.game_not_saved(&e.to_string())),
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_annotate_command {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    pub fn annotated_game_is_saved() {
+        let dir = std::env::temp_dir().join(format!("rust_chess_annotate_{}", std::process::id()));
+        let from = dir.join("game.pgn");
+        let output = dir.join("annotated.pgn");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&from, "[White \"Fool\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n").unwrap();
+
+        let mut game = TuiGame::new();
+        game.run_command_line("annotate out.pgn");
+        assert_eq!(game.take_messages()[0], "The game has no moves to annotate.");
+
+        game.run_command_line(&format!("annotate {} --from {} --movetime 20", output.display(), from.display()));
+        let messages = game.take_messages();
+        assert_eq!(messages[0], format!("Saved the annotated game to {}", output.display()));
+        assert!(messages[1].starts_with("White: accuracy "), "{}", messages[1]);
+        assert!(messages[1].ends_with(" 1 blunder."), "{}", messages[1]);
+        let text = fs::read_to_string(&output).unwrap();
+        assert!(text.contains("[White \"Fool\"]"));
+        assert!(text.contains(&format!("[Annotator \"{ENGINE_NAME}\"]")));
+        assert!(text.contains("2. g4 $4"), "{text}");
+
+        game.run_command_line("config set language german");
+        game.run_command_line(&format!("annotate {} --from {} --movetime 20 --force", output.display(), from.display()));
+        let messages = game.take_messages();
+        assert!(messages.iter().any(|m| m.starts_with("Weiß: Genauigkeit ") && m.ends_with(" 1 grober Fehler.")), "{messages:?}");
+        // The current game is left as it was.
+        assert_eq!(game.state.get_ply_count(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,140 @@
+/*
+archive.rs
+Keeps every finished game, so nothing is lost when a game isn't saved. When a game ends it is
+appended to the archive file for the month it was played, e.g. archive/2024-01.pgn, after filling
+in the tags the game is missing: its date, the player's name and the time each side had left.
+*/
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chess_core::Team,
+    chess_pgn::{PgnDate, PgnGame, PgnResult},
+};
+use super::TuiGame;
+
+impl TuiGame {
+    // Fills in the tags of the game that just ended and appends it to the archive.
+    pub(super) fn archive_game(&mut self, result: PgnResult) {
+        self.fill_archive_tags();
+        let dir = match &self.archive_dir {
+            Some(d) => d,
+            None => return,
+        };
+        let mut pgn_game = self.state.get_pgn_game().clone();
+        // A checkmate or stalemate gives the result without it being recorded.
+        if *pgn_game.get_result() == PgnResult::Unknown {
+            pgn_game.set_result(result);
+        }
+        let path = archive_file(dir, pgn_game.get_date());
+        if let Err(e) = append_game(&path, &pgn_game) {
+            self.say(self.locale.archive_failed(&path.display().to_string(), &e.to_string()));
+        }
+    }
+
+    // Tags known to the session but not yet given to the game.
+    fn fill_archive_tags(&mut self) {
+        let now = Instant::now();
+        let clock_times = self.clock.as_ref()
+            .map(|c| [Team::Light, Team::Dark].map(|team| c.get_remaining(team, now)));
+        let player_name = self.config.get_player_name().cloned();
+        // The player plays whichever side the computer doesn't, White otherwise.
+        let player_team = self.opponent.as_ref().map_or(Team::Light, |o| o.get_team().opposite());
+        let pgn_game = self.state.get_pgn_game_mut();
+        if pgn_game.get_date().get_year().is_none() {
+            pgn_game.set_date(PgnDate::now());
+        }
+        if let Some(name) = player_name {
+            match player_team {
+                Team::Light if pgn_game.get_white() == "?" => pgn_game.set_white(name),
+                Team::Dark if pgn_game.get_black() == "?" => pgn_game.set_black(name),
+                _ => (),
+            }
+        }
+        if let Some([white, black]) = clock_times {
+            pgn_game.set_tag("WhiteClock", format_tag_time(white));
+            pgn_game.set_tag("BlackClock", format_tag_time(black));
+        }
+    }
+}
+
+// The archive file for the month a game was played, or undated.pgn for games without a date.
+pub fn archive_file(dir: &Path, date: &PgnDate) -> PathBuf {
+    match (date.get_year(), date.get_month()) {
+        (Some(year), Some(month)) => dir.join(format!("{year:04}-{month:02}.pgn")),
+        _ => dir.join("undated.pgn"),
+    }
+}
+
+// Appends a game played outside of the TUI to the archive, returning the file it went to.
+pub fn archive_pgn_game(dir: &Path, pgn_game: &PgnGame) -> io::Result<PathBuf> {
+    let path = archive_file(dir, pgn_game.get_date());
+    append_game(&path, pgn_game)?;
+    Ok(path)
+}
+
+fn append_game(path: &Path, pgn_game: &PgnGame) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(format!("{pgn_game}\n\n").as_bytes())
+}
+
+// Times as clock tags give them, e.g. 0:04:59.
+fn format_tag_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_archive {
+    use crate::{chess_config::ChessConfig, chess_pgn::parse_pgn_games};
+    use super::*;
+
+    #[test]
+    pub fn finished_games_are_appended_to_the_month() {
+        let dir = std::env::temp_dir().join(format!("rust_chess_archive_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut config = ChessConfig::new();
+        config.set("time_control", "5+0").unwrap();
+        let mut game = TuiGame::with_config(config, None);
+        game.archive_dir = Some(dir.clone());
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        game.run_command_line("new");
+        game.run_command_line("move e4");
+        game.run_command_line("adjudicate 1/2-1/2");
+        // Looking back at a finished game doesn't archive it again.
+        game.run_command_line("prev");
+        game.run_command_line("end");
+
+        let files: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files, [archive_file(&dir, &PgnDate::now())]);
+        let games = parse_pgn_games(&fs::read_to_string(&files[0]).unwrap()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_result(), &PgnResult::BlackWin);
+        assert_eq!(games[0].get_tag("TimeControl"), Some(String::from("300")));
+        assert!(games[0].get_tag("WhiteClock").is_some_and(|t| t.starts_with("0:0")));
+        assert_eq!(games[1].get_result(), &PgnResult::Draw);
+        assert_eq!(games[1].get_ply_count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    pub fn games_are_filed_by_month() {
+        let dir = Path::new("archive");
+        assert_eq!(archive_file(dir, &PgnDate::new(Some(2024), Some(1), Some(5))), dir.join("2024-01.pgn"));
+        assert_eq!(archive_file(dir, &PgnDate::new(None, None, None)), dir.join("undated.pgn"));
+        assert_eq!(format_tag_time(Duration::from_millis(299_900)), "0:04:59");
+    }
+}
@@ -0,0 +1,138 @@
+/*
+autosave.rs
+Keeps a copy of the game in progress on disk, so a crash or an accidental quit doesn't lose it.
+The file only holds unfinished games that have moves, it is removed once the game ends or is
+reset, and an unfinished game found there is offered for restoring on the next launch.
+
+The game's session, as save --session writes it, is kept beside the file, e.g. in
+autosave.session.json for autosave.pgn, so the clocks, the moves taken back, the computer opponent
+and the board's orientation are restored with the game.
+*/
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::chess_pgn::PgnGame;
+
+pub struct Autosave {
+    path: PathBuf,
+    session_path: PathBuf,
+    // The text last written to the file and to the session file, None when there are no files.
+    saved_text: Option<(String, String)>,
+    last_write: Instant,
+    // Whether the last write failed, so the failure is only reported once.
+    failed: bool,
+}
+
+impl Autosave {
+    pub fn new(path: PathBuf) -> Autosave {
+        Autosave {
+            session_path: Autosave::get_session_path(&path),
+            path,
+            saved_text: None,
+            last_write: Instant::now(),
+            failed: false,
+        }
+    }
+
+    // The unfinished game left in an autosave file by an earlier session, if there is one.
+    pub fn find_unfinished(path: &Path) -> Option<PgnGame> {
+        let text = fs::read_to_string(path).ok()?;
+        let pgn_game = PgnGame::from(&text).ok()?;
+        if pgn_game.get_ply_count() > 0 && !pgn_game.is_finished() {
+            Some(pgn_game)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    // Where the session of the game autosaved to a file is kept.
+    pub fn get_session_path(path: &Path) -> PathBuf {
+        path.with_extension("session.json")
+    }
+
+    // Whether more than `interval` passed since the file was last written.
+    pub fn is_due(&self, interval: Duration) -> bool {
+        self.last_write.elapsed() >= interval
+    }
+
+    // Brings the files up to date with the game and its session, writing them or removing them
+    // when there is nothing to restore. Returns an error only the first time in a row writing
+    // fails.
+    pub fn write(&mut self, pgn_game: &PgnGame, session: &str) -> Result<(), io::Error> {
+        let text = if pgn_game.get_ply_count() > 0 && !pgn_game.is_finished() {
+            Some((format!("{}\n", pgn_game), format!("{session}\n")))
+        }
+        else {
+            None
+        };
+        if text == self.saved_text {
+            return Ok(());
+        }
+
+        let remove = |path: &Path| match fs::remove_file(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        };
+        let result = match &text {
+            Some((pgn, session)) => self.path.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&self.path, pgn))
+                .and_then(|()| fs::write(&self.session_path, session)),
+            None => remove(&self.path).and_then(|()| remove(&self.session_path)),
+        };
+        self.last_write = Instant::now();
+        match result {
+            Ok(()) => {
+                self.saved_text = text;
+                self.failed = false;
+                Ok(())
+            }
+            Err(_) if self.failed => Ok(()),
+            Err(e) => {
+                self.failed = true;
+                Err(e)
+            }
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_autosave {
+    use super::*;
+    use crate::chess_pgn::{ChessMove, PgnResult};
+
+    #[test]
+    pub fn only_unfinished_games_are_kept() {
+        let path = std::env::temp_dir().join(format!("rust_chess_autosave_{}", std::process::id())).join("autosave.pgn");
+        let mut autosave = Autosave::new(path.clone());
+        let mut pgn_game = PgnGame::new();
+        autosave.write(&pgn_game, "{}").unwrap();
+        assert!(!path.exists());
+
+        pgn_game.push_move(ChessMove::from("e4").unwrap());
+        autosave.write(&pgn_game, "{}").unwrap();
+        let restored = Autosave::find_unfinished(&path).unwrap();
+        assert_eq!(restored.get_ply_count(), 1);
+        assert_eq!(fs::read_to_string(path.with_file_name("autosave.session.json")).unwrap(), "{}\n");
+
+        pgn_game.set_result(PgnResult::WhiteWin);
+        autosave.write(&pgn_game, "{}").unwrap();
+        assert!(!path.exists());
+        assert!(!path.with_file_name("autosave.session.json").exists());
+        assert!(Autosave::find_unfinished(&path).is_none());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,167 @@
+/*
+blunder.rs
+Blunder alerts for casual games, those without a clock. After each move typed in, the built-in
+engine searches the position before and after it, and when the move lost at least the configured
+number of centipawns against the engine's best the move is marked ?? in the record, with a
+warning saying what it loses.
+*/
+
+use crate::{
+    chess_common::ChessPiece,
+    chess_core::{Board, BoardMove, BoardSquare},
+    chess_engine,
+    chess_pgn::suffix_annotation_nag,
+    chess_uci::{EngineScore, SearchLimit},
+};
+use super::{locale::Locale, TuiGame};
+
+// How deep the position before the move is searched, the one after is searched a half move
+// less so both look equally far ahead. Deep enough for most hanging pieces and short mates
+// while keeping the answer immediate.
+const BLUNDER_CHECK_DEPTH: u32 = 4;
+// Evaluations are capped here, so moves that keep a won game won, or a lost one lost, are not
+// called blunders.
+const DECISIVE_CENTIPAWNS: i32 = 1000;
+// Half moves of the engine's answer followed to find out what a blunder loses.
+const REFUTATION_PLIES: usize = 4;
+
+// Pieces a blunder can lose, most valuable first.
+const LOST_PIECES: [ChessPiece; 5] = [
+    ChessPiece::Queen,
+    ChessPiece::Rook,
+    ChessPiece::Bishop,
+    ChessPiece::Knight,
+    ChessPiece::Pawn,
+];
+
+impl TuiGame {
+    // Checks the move just played from `before`, warning about it and marking it ?? when it
+    // is a blunder.
+    pub(super) fn check_blunder(&mut self, before: &Board) {
+        if !self.config.get_blunder_alerts() || self.clock.is_some() || self.watch.is_some() {
+            return;
+        }
+        let played = match self.state.get_board_moves().last() {
+            Some(m) => *m,
+            None => return,
+        };
+        let after = self.state.get_board().clone();
+        let best = match chess_engine::search(before, SearchLimit::Depth(BLUNDER_CHECK_DEPTH)) {
+            Some(best) if best.get_best_move() != played => best,
+            _ => return,
+        };
+        let best_score = match best.get_score() {
+            Some(score) => score,
+            None => return,
+        };
+
+        // The engine scores the position after the move for the opponent.
+        let (played_score, refutation) = match chess_engine::search(&after, SearchLimit::Depth(BLUNDER_CHECK_DEPTH - 1)) {
+            Some(answer) => match answer.get_score() {
+                Some(score) => (score.negate(), answer.get_info().get_pv().clone()),
+                None => return,
+            },
+            None if after.is_checkmate() => return,
+            None => (EngineScore::Centipawns(0), Vec::new()),
+        };
+        let loss = capped(best_score) - capped(played_score);
+        if loss < self.config.get_blunder_threshold() as i32 {
+            return;
+        }
+
+        let nag = suffix_annotation_nag("??").unwrap_or(4);
+        self.state.annotate_last_move(nag);
+        let played_san = before.to_chess_move(played);
+        let best_san = before.to_chess_move(best.get_best_move());
+        let reason = describe_blunder(self.locale, before, played, played_score, &refutation);
+        self.say(self.locale.blunder(&played_san.to_string(), &reason, &best_san.to_string()));
+    }
+}
+
+fn capped(score: EngineScore) -> i32 {
+    score.to_centipawns().clamp(-DECISIVE_CENTIPAWNS, DECISIVE_CENTIPAWNS)
+}
+
+// What a blunder gives away: a mate, stalemate, or the most valuable piece lost once the
+// engine's answer is played out.
+fn describe_blunder(locale: Locale, before: &Board, played: BoardMove, played_score: EngineScore, refutation: &[BoardMove]) -> String {
+    if let EngineScore::Mate(moves) = played_score {
+        if moves < 0 {
+            return locale.allowing_mate(-moves);
+        }
+    }
+    let team = before.get_turn();
+    let mut board = before.clone();
+    board.make_move(played);
+    if board.is_stalemate() {
+        return String::from(locale.giving_stalemate());
+    }
+    for board_move in refutation.iter().take(REFUTATION_PLIES) {
+        if !board.is_legal_move(*board_move) {
+            break;
+        }
+        board.make_move(*board_move);
+    }
+    let count = |board: &Board, piece_type: ChessPiece| {
+        BoardSquare::all()
+            .filter_map(|s| board.get_piece_at(s))
+            .filter(|p| *p.get_team() == team && p.get_piece_type() == piece_type)
+            .count()
+    };
+    let balance = |board: &Board| board.get_material(team) as i32 - board.get_material(team.opposite()) as i32;
+    let material_lost = balance(before) - balance(&board);
+    let lost_piece = LOST_PIECES.iter().find(|p| count(&board, **p) < count(before, **p));
+    match lost_piece {
+        Some(piece_type) if material_lost > 0 => locale.losing_piece(*piece_type),
+        _ => locale.dropping_evaluation(played_score),
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_blunder {
+    use super::*;
+
+    fn alerting_game() -> TuiGame {
+        let mut game = TuiGame::new();
+        game.config.set("blunder_alerts", "on").unwrap();
+        game
+    }
+
+    #[test]
+    pub fn blunders_are_explained_and_marked() {
+        let mut game = alerting_game();
+        for pgn_move in ["e4", "d5"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        game.take_messages();
+        game.run_command_line("move Qg4");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "Played Qg4");
+        assert!(messages[1].starts_with("Qg4?? \u{2014} losing the queen, best was "), "{}", messages[1]);
+        assert!(game.state.get_pgn_game().to_string().ends_with("2. Qg4 $4 *"));
+    }
+
+    #[test]
+    pub fn allowed_mates_are_named() {
+        let mut game = alerting_game();
+        for pgn_move in ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        let messages = game.take_messages();
+        let alerts: Vec<&String> = messages.iter().filter(|m| m.contains("??")).collect();
+        assert_eq!(alerts.len(), 1, "{messages:?}");
+        assert!(alerts[0].starts_with("Nf6?? \u{2014} allowing mate in 1, best was "), "{}", alerts[0]);
+    }
+
+    #[test]
+    pub fn alerts_are_off_by_default() {
+        let mut game = TuiGame::new();
+        for pgn_move in ["e4", "d5", "Qg4"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        assert!(game.take_messages().iter().all(|m| !m.contains("??")));
+        assert!(game.state.get_pgn_game().to_string().ends_with("2. Qg4 *"));
+    }
+}
@@ -0,0 +1,165 @@
+/*
+broadcast.rs
+A live feed of the game for stream overlays and other spectators. Whenever the game changes, its
+state is written as a line of JSON, the same state message WebSocket clients get, either appended
+to a file or sent to every program connected to a TCP port. Programs connecting to the port get
+the current state straight away.
+*/
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::chess_ws::{GameSnapshot, ServerMessage};
+use super::TuiGame;
+
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct Broadcast {
+    sink: BroadcastSink,
+    // The state last broadcast, as written.
+    last: Option<(GameSnapshot, String)>,
+}
+
+enum BroadcastSink {
+    File(PathBuf),
+    Tcp { listener: TcpListener, clients: Vec<TcpStream> },
+}
+
+impl Broadcast {
+    // Broadcasts to a port, given as a number or an address such as 0.0.0.0:9000, or to a file
+    // given by its path.
+    pub fn start(target: &str) -> io::Result<Broadcast> {
+        let address = match target.parse::<u16>() {
+            Ok(port) => Some(SocketAddr::from(([127, 0, 0, 1], port))),
+            Err(_) => target.parse::<SocketAddr>().ok(),
+        };
+        let sink = match address {
+            Some(address) => {
+                let listener = TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+                BroadcastSink::Tcp { listener, clients: Vec::new() }
+            }
+            None => {
+                let path = PathBuf::from(target);
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent)?;
+                }
+                // Opened once here so a path that can't be written is told about right away.
+                OpenOptions::new().create(true).append(true).open(&path)?;
+                BroadcastSink::File(path)
+            }
+        };
+        Ok(Broadcast { sink, last: None })
+    }
+
+    // Where the broadcast goes, e.g. tcp://127.0.0.1:9000 or the file's path.
+    pub fn get_description(&self) -> String {
+        match &self.sink {
+            BroadcastSink::File(path) => path.display().to_string(),
+            BroadcastSink::Tcp { listener, .. } => match listener.local_addr() {
+                Ok(address) => format!("tcp://{address}"),
+                Err(_) => String::from("tcp"),
+            },
+        }
+    }
+
+    // Takes in newly connected programs and broadcasts the game when it changed.
+    pub fn update(&mut self, snapshot: GameSnapshot) -> io::Result<()> {
+        if let BroadcastSink::Tcp { listener, clients } = &mut self.sink {
+            loop {
+                match listener.accept() {
+                    Ok((mut client, _)) => {
+                        // A program that stops reading is let go rather than holding up the game.
+                        let greeted = client.set_nonblocking(false)
+                            .and_then(|()| client.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)))
+                            .and_then(|()| match &self.last {
+                                Some((_, line)) => client.write_all(line.as_bytes()),
+                                None => Ok(()),
+                            });
+                        if greeted.is_ok() {
+                            clients.push(client);
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if self.last.as_ref().is_some_and(|(last, _)| last.same_game(&snapshot)) {
+            return Ok(());
+        }
+
+        let line = serde_json::to_string(&ServerMessage::State(snapshot.clone())).map_err(io::Error::other)? + "\n";
+        match &mut self.sink {
+            BroadcastSink::File(path) => OpenOptions::new().append(true).create(true).open(path)?.write_all(line.as_bytes())?,
+            // Programs that went away are let go.
+            BroadcastSink::Tcp { clients, .. } => clients.retain_mut(|c| c.write_all(line.as_bytes()).is_ok()),
+        }
+        self.last = Some((snapshot, line));
+        Ok(())
+    }
+}
+
+impl TuiGame {
+    // Broadcasts the game if it changed. Broadcasting ends if it fails.
+    pub(super) fn advance_broadcast(&mut self) {
+        let snapshot = match &self.broadcast {
+            Some(_) => GameSnapshot::new(&self.state, self.clock.as_ref()),
+            None => return,
+        };
+        if let Some(broadcast) = &mut self.broadcast {
+            if let Err(e) = broadcast.update(snapshot) {
+                let target = broadcast.get_description();
+                self.broadcast = None;
+                self.say(self.locale.broadcast_stopped(&target.to_string(), &e.to_string()));
+            }
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_broadcast {
+    use std::io::{BufRead, BufReader};
+    use super::*;
+
+    #[test]
+    pub fn changes_are_appended_to_the_file() {
+        let path = std::env::temp_dir().join(format!("rust_chess_broadcast_{}.ndjson", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut game = TuiGame::new();
+        game.broadcast = Some(Broadcast::start(path.to_str().unwrap()).unwrap());
+        game.advance_broadcast();
+        for command in ["move e4", "show", "move e5"] {
+            game.run_command_line(command);
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3, "{text}");
+        assert!(lines[0].starts_with(r#"{"type":"state","fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1","moves":[]"#), "{}", lines[0]);
+        assert!(lines[2].contains(r#""moves":["e4","e5"]"#), "{}", lines[2]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn programs_on_the_port_get_the_game() {
+        let mut game = TuiGame::new();
+        game.broadcast = Some(Broadcast::start("0").unwrap());
+        game.run_command_line("move d4");
+        let address = game.broadcast.as_ref().unwrap().get_description().replace("tcp://", "");
+        let mut reader = BufReader::new(TcpStream::connect(address).unwrap());
+        game.run_command_line("move d5");
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.contains(r#""moves":["d4"]"#), "{line}");
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.contains(r#""moves":["d4","d5"]"#), "{line}");
+    }
+}
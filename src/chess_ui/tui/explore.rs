@@ -0,0 +1,125 @@
+/*
+explore.rs
+The explore command, the opening explorer over the game database. It lists the moves played from
+the shown position, and with a move of the list plays it and lists the moves played after it, so
+the tree of the database is walked down a move at a time and back up with prev or undo. The
+database is indexed the first time it is explored, and again only when the file changes.
+*/
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    chess_cmd::{ChessCommands, CommandDispatcher},
+    chess_config::ChessConfig,
+    chess_explorer::{format_moves, OpeningExplorer},
+};
+use super::TuiGame;
+
+// A database as it was indexed, and when the file was last changed then.
+pub struct ExplorerCache {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    explorer: OpeningExplorer,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl TuiGame {
+    pub(super) fn explore(&mut self, choice: Option<&str>, from: Option<PathBuf>) {
+        let path = match from.or_else(|| self.config.get_database_path().cloned()).or_else(ChessConfig::default_database_path) {
+            Some(p) => p,
+            None => {
+                self.say(String::from(self.locale.no_database_to_explore()));
+                return;
+            }
+        };
+        let modified = modified_time(&path);
+        let indexed = self.explorer.as_ref().is_some_and(|c| c.path == path && c.modified.is_some() && c.modified == modified);
+        if !indexed {
+            match OpeningExplorer::load(&path) {
+                Ok(explorer) => self.explorer = Some(ExplorerCache { path: path.clone(), modified, explorer }),
+                Err(e) => {
+                    self.say(self.locale.unreadable_file(&path.display().to_string(), &e.to_string()));
+                    return;
+                }
+            }
+        }
+
+        if let Some(choice) = choice {
+            let board = self.state.get_viewed_board().clone();
+            let cache = self.explorer.as_ref().unwrap();
+            // A number picks a move of the list, anything else is played as typed.
+            let pgn_move = match choice.parse::<usize>() {
+                Ok(number) => match cache.explorer.get_moves(&board).get(number.wrapping_sub(1)) {
+                    Some(m) => board.to_chess_move(m.get_board_move()).to_string(),
+                    None => {
+                        self.say(self.locale.no_listed_move(number));
+                        return;
+                    }
+                },
+                Err(_) => choice.to_string(),
+            };
+            let before = self.state.get_viewed_board().clone();
+            self.dispatch(ChessCommands::Move { pgn_move });
+            if *self.state.get_viewed_board() == before {
+                return;
+            }
+        }
+        self.show_explorer();
+    }
+
+    fn show_explorer(&mut self) {
+        let cache = self.explorer.as_ref().unwrap();
+        let board = self.state.get_viewed_board();
+        let moves = cache.explorer.get_moves(board);
+        let file_name = cache.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if moves.is_empty() {
+            let message = format!("No moves were played from this position in the {} games of {file_name}.", cache.explorer.get_game_count());
+            self.say(message);
+            return;
+        }
+        let games: u32 = moves.iter().map(|m| m.get_games()).sum();
+        let mut lines = vec![format!("Moves played in {games} of the {} games of {file_name}:", cache.explorer.get_game_count())];
+        lines.extend(format_moves(board, &moves));
+        for line in lines {
+            self.say(line);
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_explore {
+    use super::*;
+
+    #[test]
+    pub fn explorer_walks_down_the_database() {
+        let path = std::env::temp_dir().join(format!("rust_chess_explore_{}.pgn", std::process::id()));
+        fs::write(&path, "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n[Result \"0-1\"]\n\n1. e4 c5 0-1\n\n[Result \"1-0\"]\n\n1. d4 1-0\n").unwrap();
+        let mut game = TuiGame::new();
+        game.run_command_line(&format!("explore --from {}", path.display()));
+        let messages = game.take_messages();
+        assert!(messages[0].starts_with("Moves played in 3 of the 3 games of rust_chess_explore_"), "{messages:?}");
+        assert_eq!(&messages[2..], ["  1  e4          2    50%     0%    50%", "  2  d4          1   100%     0%     0%"]);
+
+        game.run_command_line(&format!("explore 1 --from {}", path.display()));
+        let messages = game.take_messages();
+        assert_eq!(game.state.get_pgn_game().get_ply_count(), 1);
+        assert_eq!(messages[0], "Played e4");
+        assert!(messages[1].starts_with("Moves played in 2 of the 3 games"), "{messages:?}");
+        assert_eq!(messages.len(), 5);
+
+        game.run_command_line(&format!("explore c5 --from {}", path.display()));
+        assert!(game.take_messages()[1].starts_with("No moves were played from this position"));
+        game.run_command_line(&format!("explore 3 --from {}", path.display()));
+        assert_eq!(game.take_messages(), vec![String::from("There is no move 3 in the list.")]);
+        let _ = fs::remove_file(&path);
+    }
+}
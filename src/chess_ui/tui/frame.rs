@@ -0,0 +1,256 @@
+/*
+frame.rs
+What the full screen interface shows on the terminal, cell by cell. Each draw fills a new frame
+and only the cells that differ from the last frame drawn are written, so moving a piece rewrites
+a few squares and their highlights instead of the whole screen. That keeps flicker and output
+down on slow terminals and over SSH.
+
+Characters two columns wide, like CJK text and, on many terminals, the chess symbols, take their
+cell and the one after it, so what follows them stays in its column.
+*/
+
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthChar;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::Print,
+    terminal::{Clear, ClearType},
+};
+
+use super::TERMINAL_COLOR_RESET;
+
+// The colors of a cell, as the parameters of their escape codes, e.g. "38;5;240".
+#[derive(Clone, Debug, Default, PartialEq)]
+struct CellStyle {
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+impl CellStyle {
+    // Applies the parameters of a color escape code, e.g. "48;5;75" or "0".
+    fn apply(&mut self, parameters: &str) {
+        let parameters: Vec<&str> = parameters.split(';').collect();
+        let mut i = 0;
+        while i < parameters.len() {
+            // 256 color and RGB codes take the parameters after them.
+            let length = match (parameters[i], parameters.get(i + 1)) {
+                ("38" | "48", Some(&"5")) => 3,
+                ("38" | "48", Some(&"2")) => 5,
+                _ => 1,
+            };
+            let end = (i + length).min(parameters.len());
+            let code = parameters[i..end].join(";");
+            match parameters[i].parse::<u8>().unwrap_or(0) {
+                0 => *self = CellStyle::default(),
+                30..=38 | 90..=97 => self.fg = Some(code),
+                39 => self.fg = None,
+                40..=48 | 100..=107 => self.bg = Some(code),
+                49 => self.bg = None,
+                _ => (),
+            }
+            i = end;
+        }
+    }
+
+    // The escape codes that set these colors from none.
+    fn to_codes(&self) -> String {
+        let mut codes = String::from(TERMINAL_COLOR_RESET);
+        for code in [&self.fg, &self.bg].into_iter().flatten() {
+            codes.push_str(&format!("\u{001b}[{code}m"));
+        }
+        codes
+    }
+}
+
+// The second column of a wide character, which is drawn with it.
+const WIDE_CONTINUATION: char = '\0';
+
+#[derive(Clone, Debug, PartialEq)]
+struct Cell {
+    symbol: char,
+    style: CellStyle,
+}
+
+// The columns the terminal draws a character in. Unicode gives the chess symbols one column, the
+// terminal may draw them in two.
+pub fn char_width(c: char, wide_glyphs: bool) -> usize {
+    match c {
+        '\u{2654}'..='\u{265F}' if wide_glyphs => 2,
+        _ => c.width().unwrap_or(0),
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell { symbol: ' ', style: CellStyle::default() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    width: u16,
+    height: u16,
+    // Row by row.
+    cells: Vec<Cell>,
+    // Whether the terminal draws chess symbols two columns wide.
+    wide_glyphs: bool,
+}
+
+impl Frame {
+    pub fn new(width: u16, height: u16, wide_glyphs: bool) -> Frame {
+        Frame { width, height, cells: vec![Cell::default(); width as usize * height as usize], wide_glyphs }
+    }
+
+    // Puts text on the frame from a position, in the colors set by the escape codes in it.
+    // Text past the right edge is cut off.
+    pub fn print(&mut self, column: u16, row: u16, text: &str) {
+        if row >= self.height {
+            return;
+        }
+        let mut style = CellStyle::default();
+        let mut column = column;
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\u{001b}' {
+                // Only color codes are kept, other escape sequences are skipped.
+                let mut sequence = String::new();
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        if c == 'm' {
+                            style.apply(sequence.trim_start_matches('['));
+                        }
+                        break;
+                    }
+                    sequence.push(c);
+                }
+                continue;
+            }
+            // Zero width characters would be drawn over the one before them.
+            let width = char_width(c, self.wide_glyphs) as u16;
+            if width == 0 {
+                continue;
+            }
+            if column + width > self.width {
+                break;
+            }
+            let index = row as usize * self.width as usize + column as usize;
+            self.clear_wide_character(index);
+            if width == 2 {
+                self.clear_wide_character(index + 1);
+            }
+            self.cells[index] = Cell { symbol: c, style: style.clone() };
+            if width == 2 {
+                self.cells[index + 1] = Cell { symbol: WIDE_CONTINUATION, style: style.clone() };
+            }
+            column += width;
+        }
+    }
+
+    // Blanks the wide character a cell is part of, before the cell is written over. Half of it
+    // can't be drawn.
+    fn clear_wide_character(&mut self, index: usize) {
+        let first = if self.cells[index].symbol == WIDE_CONTINUATION { index - 1 } else { index };
+        if self.cells.get(first + 1).is_some_and(|c| c.symbol == WIDE_CONTINUATION) {
+            self.cells[first].symbol = ' ';
+            self.cells[first + 1].symbol = ' ';
+        }
+    }
+
+    // Writes what changed since the previous frame was drawn, in runs of changed cells. The
+    // whole frame is drawn when there is no previous one or the terminal was resized.
+    pub fn draw(&self, previous: Option<&Frame>, out: &mut impl Write) -> io::Result<()> {
+        let blank;
+        let previous = match previous {
+            Some(p) if p.width == self.width && p.height == self.height => p,
+            _ => {
+                queue!(out, Print(TERMINAL_COLOR_RESET), Clear(ClearType::All))?;
+                blank = Frame::new(self.width, self.height, self.wide_glyphs);
+                &blank
+            }
+        };
+
+        let mut style: Option<&CellStyle> = None;
+        for row in 0..self.height {
+            let start = row as usize * self.width as usize;
+            let mut in_run = false;
+            for column in 0..self.width {
+                let index = start + column as usize;
+                let cell = &self.cells[index];
+                // A wide character is drawn again when either of its columns changed, and its
+                // second column is covered by drawing it.
+                let wide = self.cells.get(index + 1).is_some_and(|c| c.symbol == WIDE_CONTINUATION);
+                let changed = *cell != previous.cells[index] || (wide && self.cells[index + 1] != previous.cells[index + 1]);
+                if cell.symbol == WIDE_CONTINUATION || !changed {
+                    in_run = in_run && cell.symbol == WIDE_CONTINUATION;
+                    continue;
+                }
+                if !in_run {
+                    queue!(out, MoveTo(column, row))?;
+                    in_run = true;
+                }
+                if style != Some(&cell.style) {
+                    queue!(out, Print(cell.style.to_codes()))?;
+                    style = Some(&cell.style);
+                }
+                queue!(out, Print(cell.symbol))?;
+            }
+        }
+        if style.is_some() {
+            queue!(out, Print(TERMINAL_COLOR_RESET))?;
+        }
+        Ok(())
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_frame {
+    use super::*;
+
+    fn drawn(frame: &Frame, previous: Option<&Frame>) -> String {
+        let mut output = Vec::new();
+        frame.draw(previous, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    pub fn only_changed_cells_are_drawn() {
+        let mut first = Frame::new(10, 2, false);
+        first.print(0, 0, "abc\u{001b}[48;5;75mdef\u{001b}[0m");
+        first.print(0, 1, "long enough to be cut off");
+        assert!(drawn(&first, None).starts_with("\u{001b}[0m\u{001b}[2J"));
+        assert_eq!(drawn(&first, Some(&first)), "");
+
+        let mut second = first.clone();
+        second.print(1, 0, "X");
+        second.print(4, 0, "\u{001b}[38;5;240m\u{001b}[48;5;75mE");
+        // Moving to a cell and setting its colors, one run per change.
+        assert_eq!(drawn(&second, Some(&first)),
+            "\u{001b}[1;2H\u{001b}[0mX\u{001b}[1;5H\u{001b}[0m\u{001b}[38;5;240m\u{001b}[48;5;75mE\u{001b}[0m");
+
+        // The same text in other colors is drawn again.
+        let mut third = first.clone();
+        third.print(0, 0, "abc\u{001b}[48;5;76md");
+        assert_eq!(drawn(&third, Some(&first)), "\u{001b}[1;4H\u{001b}[0m\u{001b}[48;5;76md\u{001b}[0m");
+
+        // A wide character takes two cells, what follows keeps its column.
+        let mut wide = Frame::new(10, 1, true);
+        wide.print(0, 0, "a\u{265A}b");
+        assert_eq!(drawn(&wide, None), "\u{001b}[0m\u{001b}[2J\u{001b}[1;1H\u{001b}[0ma\u{265A}b\u{001b}[0m");
+        let mut moved = wide.clone();
+        moved.print(1, 0, "xy");
+        assert_eq!(drawn(&moved, Some(&wide)), "\u{001b}[1;2H\u{001b}[0mxy\u{001b}[0m");
+        // Writing over half of a wide character blanks the other half.
+        let mut cut = wide.clone();
+        cut.print(2, 0, "z");
+        assert_eq!(drawn(&cut, Some(&wide)), "\u{001b}[1;2H\u{001b}[0m z\u{001b}[0m");
+        let mut narrow = Frame::new(10, 1, false);
+        narrow.print(0, 0, "\u{4E2D}\u{265A}b");
+        assert_eq!(narrow.cells[3].symbol, 'b');
+
+        // A resized terminal is drawn whole.
+        assert!(drawn(&Frame::new(11, 2, false), Some(&first)).contains("\u{001b}[2J"));
+    }
+}
@@ -0,0 +1,139 @@
+/*
+frontend.rs
+The game loop shared by the ways of playing. A frontend shows the game and reads commands, the
+loop runs the commands and keeps the game going while it waits for them: the clocks, the
+autosave, watched engine games, the analysis and the served game. The full screen interface and
+line mode are frontends, and another one, a window, a web page or a bot taking commands from a
+chat, only has to implement UiFrontend to get the same game.
+*/
+
+use std::{io, time::Duration};
+
+use crate::chess_cmd::CommandOutcome;
+use super::{notify, TuiGame, WATCH_TICK};
+
+// How often the game is checked for work to do while waiting for a command, when no engines or
+// clients need answering sooner.
+const IDLE_TICK: Duration = Duration::from_secs(1);
+
+pub enum FrontendInput {
+    // A command line, run as if typed.
+    Command(String),
+    // Nothing came in time.
+    Idle,
+    // The frontend changed what it shows, e.g. the line being typed.
+    Redraw,
+    // The player left, or the input ended.
+    Closed,
+}
+
+pub trait UiFrontend {
+    // Shows the game.
+    fn render(&mut self, game: &TuiGame) -> io::Result<()>;
+    // Tells the messages of a command, or of something that happened while waiting for one.
+    fn notify(&mut self, messages: Vec<String>);
+    // Waits up to the timeout for the next command.
+    fn read_command(&mut self, game: &mut TuiGame, timeout: Duration) -> io::Result<FrontendInput>;
+    // Whether the game is shown again as a running clock counts down and the analysis changes,
+    // not only when there are messages.
+    fn shows_live_updates(&self) -> bool {
+        false
+    }
+    // Called once the game loop ends, to show what is left.
+    fn close(&mut self) {}
+}
+
+// Plays the game through a frontend until the player quits or the input ends.
+pub fn run_frontend(game: &mut TuiGame, frontend: &mut impl UiFrontend) -> io::Result<()> {
+    frontend.notify(game.take_messages());
+    let mut changed = true;
+    loop {
+        if changed {
+            frontend.render(game)?;
+        }
+        let engines_running = game.watch.is_some() || game.analysis.is_some() || game.remote.is_some() || game.http.is_some();
+        let timeout = if engines_running { WATCH_TICK } else { IDLE_TICK };
+        changed = match frontend.read_command(game, timeout)? {
+            FrontendInput::Command(line) => {
+                notify::restore_title(game);
+                let outcome = game.run_command_line(&line);
+                game.autosave(true);
+                notify::send_alerts(game);
+                frontend.notify(game.take_messages());
+                if let CommandOutcome::Quit = outcome {
+                    break;
+                }
+                true
+            }
+            FrontendInput::Idle => {
+                let live_changes = game.advance_idle();
+                let messages = game.take_messages();
+                let changed = !messages.is_empty() || (frontend.shows_live_updates() && live_changes);
+                frontend.notify(messages);
+                changed
+            }
+            FrontendInput::Redraw => true,
+            FrontendInput::Closed => break,
+        };
+    }
+    frontend.close();
+    Ok(())
+}
+
+impl TuiGame {
+    // Keeps the game going between commands. Returns whether a clock is running or the analysis
+    // changed, which the board shows.
+    pub(super) fn advance_idle(&mut self) -> bool {
+        self.check_clock();
+        self.autosave(false);
+        self.advance_watch();
+        let analysis_changed = self.advance_analysis();
+        self.advance_remote();
+        notify::send_alerts(self);
+        let clock_running = self.clock.as_ref().is_some_and(|c| c.get_running().is_some());
+        clock_running || analysis_changed
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_frontend {
+    use super::*;
+
+    // Plays the commands it is given and keeps what it is told, as a bot would.
+    struct ScriptedFrontend {
+        commands: Vec<&'static str>,
+        told: Vec<String>,
+        renders: usize,
+    }
+
+    impl UiFrontend for ScriptedFrontend {
+        fn render(&mut self, _game: &TuiGame) -> io::Result<()> {
+            self.renders += 1;
+            Ok(())
+        }
+
+        fn notify(&mut self, messages: Vec<String>) {
+            self.told.extend(messages);
+        }
+
+        fn read_command(&mut self, _game: &mut TuiGame, _timeout: Duration) -> io::Result<FrontendInput> {
+            if self.commands.is_empty() {
+                return Ok(FrontendInput::Closed);
+            }
+            Ok(FrontendInput::Command(self.commands.remove(0).to_string()))
+        }
+    }
+
+    #[test]
+    pub fn frontends_play_through_the_shared_loop() {
+        let mut game = TuiGame::new();
+        game.take_messages();
+        let mut frontend = ScriptedFrontend { commands: vec!["move e4", "move e5", "quit", "move Nf3"], told: Vec::new(), renders: 0 };
+        run_frontend(&mut game, &mut frontend).unwrap();
+        assert_eq!(frontend.told, ["Played e4", "Played e5", "Quitting game."]);
+        assert_eq!(frontend.renders, 3);
+        assert_eq!(game.state.get_ply_count(), 2);
+    }
+}
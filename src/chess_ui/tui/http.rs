@@ -0,0 +1,146 @@
+/*
+http.rs
+Serving the current game over HTTP, answering the requests of the HTTP API from the game at hand.
+Moves made through the API are played for the side to move, unless it is the WebSocket player's,
+and the computer answers them in a game against it.
+*/
+
+use std::time::Duration;
+
+use crate::{
+    chess_engine,
+    chess_http::{analysis_reply, legal_moves_reply, ApiReply, ApiRequest, HttpServer},
+    chess_pgn::ChessMove,
+    chess_uci::{SearchLimit, UciEngine},
+    chess_ws::GameSnapshot,
+};
+use super::TuiGame;
+
+impl TuiGame {
+    pub(super) fn start_http(&mut self, host: &str, port: u16) {
+        if let Some(http) = &self.http {
+            let address = http.get_address();
+            self.say(self.locale.already_served(&format!("http://{address}")));
+            return;
+        }
+        match HttpServer::start(&format!("{host}:{port}")) {
+            Ok(http) => {
+                self.say(self.locale.serving_http(&format!("http://{}", http.get_address())));
+                self.http = Some(http);
+            }
+            Err(e) => self.say(self.locale.cannot_serve(&format!("{host}:{port}"), &e.to_string())),
+        }
+    }
+
+    pub(super) fn answer_http_requests(&mut self) {
+        let requests = match &self.http {
+            Some(http) => http.poll(),
+            None => return,
+        };
+        for (request, pending) in requests {
+            let reply = match request {
+                Ok(request) => self.answer_api_request(request),
+                Err(reply) => reply,
+            };
+            pending.answer(reply);
+        }
+    }
+
+    fn answer_api_request(&mut self, request: ApiRequest) -> ApiReply {
+        match request {
+            ApiRequest::State => ApiReply::json(&GameSnapshot::new(&self.state, self.clock.as_ref())),
+            ApiRequest::Fen => ApiReply::json(&serde_json::json!({ "fen": self.state.get_board().to_fen() })),
+            ApiRequest::LegalMoves => legal_moves_reply(self.state.get_board()),
+            ApiRequest::Pgn => ApiReply::text(format!("{}\n", self.state.get_pgn_game())),
+            ApiRequest::Move(san) => self.play_api_move(&san),
+            ApiRequest::Analysis(movetime) => self.analyze_for_api(movetime),
+        }
+    }
+
+    fn play_api_move(&mut self, san: &str) -> ApiReply {
+        if self.is_remote_turn() {
+            return ApiReply::error(409, &format!("It is {}'s move, played over WebSocket.", self.get_remote_side()));
+        }
+        self.check_clock();
+        let played = ChessMove::from(san)
+            .map_err(|e| format!("Invalid move {san}: {e}."))
+            .and_then(|m| self.state.play_move(&m).map_err(|e| format!("Illegal move {san}: {e}.")));
+        match played {
+            Ok(played) => {
+                self.say(self.locale.played_over_http(&played.to_string()));
+                self.update_clock(true);
+                self.play_opponent_move();
+                self.update_clock(false);
+                ApiReply::json(&GameSnapshot::new(&self.state, self.clock.as_ref()))
+            }
+            Err(message) => ApiReply::error(400, &message),
+        }
+    }
+
+    // Searches the current position with the configured engine, or the built-in one.
+    fn analyze_for_api(&mut self, movetime: Duration) -> ApiReply {
+        let board = self.state.get_board().clone();
+        if board.legal_moves().is_empty() {
+            return ApiReply::error(409, "The position has no moves to analyze.");
+        }
+        let limit = SearchLimit::MoveTime(movetime);
+        let result = match self.config.get_engine_path() {
+            Some(path) => UciEngine::start(path)
+                .and_then(|mut e| e.new_game().and_then(|()| e.search(&self.state.get_board_moves(), limit)))
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            None => Ok(chess_engine::search(&board, limit)),
+        };
+        match result {
+            Ok(Some(result)) => analysis_reply(&board, &result),
+            Ok(None) => ApiReply::error(409, "The position has no moves to analyze."),
+            Err(e) => ApiReply::error(502, &format!("The engine failed: {e}.")),
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_http_serving {
+    use super::*;
+
+    #[test]
+    pub fn api_drives_the_game() {
+        let mut game = TuiGame::new();
+        game.run_command_line("new --vs-engine --movetime 20");
+        let reply = game.answer_api_request(ApiRequest::Move(String::from("e4")));
+        assert_eq!(reply.get_status(), 200, "{}", reply.get_body());
+        assert!(reply.get_body().contains(r#""turn":"white""#), "{}", reply.get_body());
+        assert_eq!(game.state.get_ply_count(), 2);
+
+        let illegal = game.answer_api_request(ApiRequest::Move(String::from("Ke3")));
+        assert_eq!(illegal.get_status(), 400);
+        let pgn = game.answer_api_request(ApiRequest::Pgn);
+        assert!(pgn.get_body().contains("1. e4 "), "{}", pgn.get_body());
+        let analysis = game.answer_api_request(ApiRequest::Analysis(Duration::from_millis(50)));
+        assert!(analysis.get_body().starts_with(r#"{"best_move":"#), "{}", analysis.get_body());
+    }
+
+    #[test]
+    pub fn requests_are_answered_over_http() {
+        let mut game = TuiGame::new();
+        game.run_command_line("serve --http 0");
+        let message = game.take_messages().remove(0);
+        assert!(message.starts_with("Serving the game on http://127.0.0.1:"), "{message}");
+        let address = game.http.as_ref().unwrap().get_address();
+        let client = std::thread::spawn(move || {
+            let body = ureq::post(&format!("http://{address}/move")).send_string(r#"{"move": "d4"}"#).unwrap().into_string().unwrap();
+            let fen = ureq::get(&format!("http://{address}/fen")).call().unwrap().into_string().unwrap();
+            (body, fen)
+        });
+        while !client.is_finished() {
+            game.advance_remote();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let (body, fen) = client.join().unwrap();
+        assert!(body.contains(r#""moves":["d4"]"#), "{body}");
+        assert_eq!(fen, r#"{"fen":"rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 1"}"#);
+        assert_eq!(game.take_messages(), vec!["Played d4 over HTTP"]);
+    }
+}
@@ -0,0 +1,70 @@
+/*
+import.rs
+The import-archive command, which downloads a chess.com player's games into the game database.
+*/
+
+use std::path::PathBuf;
+
+use crate::{
+    chess_config::ChessConfig,
+    chess_import::{fetch_chess_com, import_archive},
+};
+use super::TuiGame;
+
+impl TuiGame {
+    pub(super) fn import_archive(&mut self, username: &str, to: Option<PathBuf>, since: Option<&str>) {
+        if since.is_some_and(|s| !is_month(s)) {
+            self.say(self.locale.invalid_month(since.unwrap_or_default()));
+            return;
+        }
+        let database = match to.or_else(|| self.config.get_database_path().cloned()).or_else(ChessConfig::default_database_path) {
+            Some(p) => p,
+            None => {
+                self.say(String::from(self.locale.no_import_file()));
+                return;
+            }
+        };
+
+        let summary = match import_archive(username, &database, since, fetch_chess_com) {
+            Ok(s) => s,
+            Err(e) => {
+                self.say(self.locale.import_failed(username, &e.to_string()));
+                return;
+            }
+        };
+        self.say(self.locale.imported_games(summary.get_added(), username, summary.get_months(),
+            &database.display().to_string(), summary.get_skipped()));
+        for (month, e) in summary.get_failed() {
+            self.say(self.locale.month_not_imported(&month.to_string(), &e.to_string()));
+        }
+    }
+}
+
+// Months as the archives are named, e.g. 2024/01.
+fn is_month(text: &str) -> bool {
+    match text.split_once('/') {
+        Some((year, month)) => {
+            year.len() == 4 && year.parse::<u32>().is_ok() && month.len() == 2 && month.parse::<u32>().is_ok_and(|m| (1..=12).contains(&m))
+        }
+        None => false,
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_import_command {
+    use super::*;
+
+    #[test]
+    pub fn months_are_checked() {
+        assert!(is_month("2024/01"));
+        assert!(!is_month("2024/1"));
+        assert!(!is_month("2024-01"));
+        assert!(!is_month("2024/13"));
+
+        let mut game = TuiGame::new();
+        game.run_command_line("import-archive hikaru --since 2024");
+        assert_eq!(game.take_messages(), vec!["Invalid month 2024, expected YYYY/MM, e.g. 2024/01."]);
+    }
+}
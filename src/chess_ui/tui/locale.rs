@@ -0,0 +1,1506 @@
+/*
+locale.rs
+The language the interface talks in: the status of the game, the replies to moves and commands,
+and the errors they meet. English and German are shipped.
+The language is the config's language setting, or with language = "auto" the one of the
+environment (LC_ALL, LC_MESSAGES or LANG, e.g. de_DE.UTF-8), falling back to English.
+
+Commands and moves are typed the same in every language, and what is written to PGN files and
+sent to other programs stays English.
+*/
+
+use crate::{
+    chess_annotate::PlayerSummary,
+    chess_common::ChessPiece,
+    chess_config::KeyAction,
+    chess_core::Team,
+    chess_game::{DrawClaim, GameStatus},
+    chess_pgn::PgnResult,
+    chess_uci::EngineScore,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    // The language of a locale name such as de_DE.UTF-8, de or C.
+    pub fn from_locale_name(name: &str) -> Option<Locale> {
+        let language = name.split(['_', '.', '@', '-']).next()?.to_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            _ => None,
+        }
+    }
+
+    // The language of the environment, from the first locale variable that is set as POSIX
+    // looks them up. None when it isn't one of the languages shipped.
+    pub fn from_env() -> Option<Locale> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|v| std::env::var(v).ok())
+            .find(|v| !v.is_empty())
+            .and_then(|v| Locale::from_locale_name(&v))
+    }
+
+    pub fn team(&self, team: Team) -> &'static str {
+        match (self, team) {
+            (Locale::English, Team::Light) => "White",
+            (Locale::English, Team::Dark) => "Black",
+            (Locale::German, Team::Light) => "Weiß",
+            (Locale::German, Team::Dark) => "Schwarz",
+        }
+    }
+
+    // e.g. "White to move" or "Checkmate — Black wins".
+    pub fn status(&self, status: &GameStatus) -> String {
+        let result = |result: &PgnResult| match result {
+            PgnResult::WhiteWin => Some(Team::Light),
+            PgnResult::BlackWin => Some(Team::Dark),
+            PgnResult::Draw | PgnResult::Unknown => None,
+        };
+        match self {
+            Locale::English => status.to_string(),
+            Locale::German => match status {
+                GameStatus::ToMove(team) => format!("{} am Zug", self.team(*team)),
+                GameStatus::InCheck(team) => format!("{} steht im Schach", self.team(*team)),
+                GameStatus::Checkmate { winner } => format!("Schachmatt \u{2014} {} gewinnt", self.team(*winner)),
+                GameStatus::Stalemate => String::from("Patt \u{2014} remis"),
+                GameStatus::Ended(ended) => match result(ended) {
+                    Some(winner) => format!("Partie beendet \u{2014} {} gewinnt", self.team(winner)),
+                    None => format!("Partie beendet \u{2014} {ended}"),
+                },
+            },
+        }
+    }
+
+    // Follows the status while an earlier position is shown.
+    pub fn viewed_ply(&self, ply: usize, count: usize) -> String {
+        match self {
+            Locale::English => format!("(half move {ply} of {count})"),
+            Locale::German => format!("(Halbzug {ply} von {count})"),
+        }
+    }
+
+    pub fn evaluation(&self, score: &str) -> String {
+        match self {
+            Locale::English => format!("evaluation {score}"),
+            Locale::German => format!("Bewertung {score}"),
+        }
+    }
+
+    pub fn simul_board(&self, board: usize, boards: usize) -> String {
+        match self {
+            Locale::English => format!("Simul board {board} of {boards}"),
+            Locale::German => format!("Simultanbrett {board} von {boards}"),
+        }
+    }
+
+    pub fn clocks(&self, white: &str, black: &str) -> String {
+        match self {
+            Locale::English => format!("White has {white}, Black has {black}."),
+            Locale::German => format!("Weiß hat {white}, Schwarz hat {black}."),
+        }
+    }
+
+    pub fn welcome(&self) -> &'static str {
+        match self {
+            Locale::English => "Type a command, e.g. move e4, or help for the list of commands.",
+            Locale::German => "Gib einen Befehl ein, z. B. move e4, oder help für die Liste der Befehle.",
+        }
+    }
+
+    pub fn error(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("error: {error}"),
+            Locale::German => format!("Fehler: {error}"),
+        }
+    }
+
+    pub fn played(&self, played: &str) -> String {
+        match self {
+            Locale::English => format!("Played {played}"),
+            Locale::German => format!("Gespielt: {played}"),
+        }
+    }
+
+    pub fn ambiguous_move(&self, pgn_move: &str, origins: &[String]) -> String {
+        match self {
+            Locale::English => format!("{pgn_move} could be played from {}. Type the square of the piece to move.", origins.join(" or ")),
+            Locale::German => format!("{pgn_move} kann von {} gezogen werden. Gib das Feld der Figur ein, die ziehen soll.", origins.join(" oder ")),
+        }
+    }
+
+    pub fn illegal_move(&self, pgn_move: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Illegal move {pgn_move}: {error}."),
+            Locale::German => format!("Unzulässiger Zug {pgn_move}: {error}."),
+        }
+    }
+
+    pub fn invalid_move(&self, pgn_move: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Invalid move {pgn_move}: {error}."),
+            Locale::German => format!("Ungültiger Zug {pgn_move}: {error}."),
+        }
+    }
+
+    pub fn invalid_square(&self, square: &str) -> String {
+        match self {
+            Locale::English => format!("Invalid square: {square}. Expected a file and rank such as e2."),
+            Locale::German => format!("Ungültiges Feld: {square}. Erwartet werden Linie und Reihe wie e2."),
+        }
+    }
+
+    pub fn viewing_history(&self) -> &'static str {
+        match self {
+            Locale::English => "An earlier position is shown. Use end to return to the game before moving.",
+            Locale::German => "Eine frühere Stellung wird gezeigt. Mit end geht es vor dem nächsten Zug zurück zur Partie.",
+        }
+    }
+
+    pub fn game_over(&self, result: PgnResult) -> String {
+        match self {
+            Locale::English => format!("The game is over ({result}). Reset the board to start a new game."),
+            Locale::German => format!("Die Partie ist beendet ({result}). Mit reset beginnt eine neue Partie."),
+        }
+    }
+
+    pub fn already_ended(&self, result: PgnResult) -> String {
+        match self {
+            Locale::English => format!("The game has already ended ({result})."),
+            Locale::German => format!("Die Partie ist bereits beendet ({result})."),
+        }
+    }
+
+    pub fn nothing_to_undo(&self) -> &'static str {
+        match self {
+            Locale::English => "There are no moves to undo.",
+            Locale::German => "Es gibt keine Züge zum Zurücknehmen.",
+        }
+    }
+
+    pub fn undid(&self, count: usize) -> String {
+        match self {
+            Locale::English => format!("Undid {count} move(s)"),
+            Locale::German => format!("{count} Zug/Züge zurückgenommen"),
+        }
+    }
+
+    pub fn nothing_to_redo(&self) -> &'static str {
+        match self {
+            Locale::English => "There are no moves to redo.",
+            Locale::German => "Es gibt keine Züge zum Wiederherstellen.",
+        }
+    }
+
+    pub fn redid(&self, count: usize) -> String {
+        match self {
+            Locale::English => format!("Redid {count} move(s)"),
+            Locale::German => format!("{count} Zug/Züge wiederhergestellt"),
+        }
+    }
+
+    pub fn last_move(&self) -> &'static str {
+        match self {
+            Locale::English => "This is the last move of the game.",
+            Locale::German => "Das ist der letzte Zug der Partie.",
+        }
+    }
+
+    pub fn first_move(&self) -> &'static str {
+        match self {
+            Locale::English => "This is the start of the game.",
+            Locale::German => "Das ist der Beginn der Partie.",
+        }
+    }
+
+    pub fn start_of_game(&self) -> &'static str {
+        match self {
+            Locale::English => "Start of the game",
+            Locale::German => "Beginn der Partie",
+        }
+    }
+
+    pub fn resetting(&self) -> &'static str {
+        match self {
+            Locale::English => "Resetting board.",
+            Locale::German => "Das Brett wird zurückgesetzt.",
+        }
+    }
+
+    pub fn new_game(&self) -> &'static str {
+        match self {
+            Locale::English => "Started a new game.",
+            Locale::German => "Eine neue Partie hat begonnen.",
+        }
+    }
+
+    // The side the board is shown from after it is flipped.
+    pub fn flipped(&self, team: Team) -> String {
+        match self {
+            Locale::English => format!("Showing the board from {}'s side.", self.team(team)),
+            Locale::German => format!("Das Brett wird aus Sicht von {} gezeigt.", self.team(team)),
+        }
+    }
+
+    pub fn resigns(&self, team: Team) -> String {
+        match self {
+            Locale::English => format!("{} resigns, {} wins.", self.team(team), self.team(team.opposite())),
+            Locale::German => format!("{} gibt auf, {} gewinnt.", self.team(team), self.team(team.opposite())),
+        }
+    }
+
+    pub fn drawn_by_agreement(&self) -> &'static str {
+        match self {
+            Locale::English => "The game is drawn by agreement.",
+            Locale::German => "Die Partie endet remis durch Einigung.",
+        }
+    }
+
+    pub fn quitting(&self) -> &'static str {
+        match self {
+            Locale::English => "Quitting game.",
+            Locale::German => "Die Partie wird verlassen.",
+        }
+    }
+    pub fn draw_claim(&self, claim: DrawClaim) -> String {
+        match (self, claim) {
+            (Locale::English, _) => claim.to_string(),
+            (Locale::German, DrawClaim::ThreefoldRepetition) => String::from("dreifache Stellungswiederholung"),
+            (Locale::German, DrawClaim::FiftyMoveRule) => String::from("die 50-Züge-Regel"),
+        }
+    }
+
+    pub fn claims_draw(&self, team: Team, claim: DrawClaim) -> String {
+        match self {
+            Locale::English => format!("{} claims a draw by {}, the game is drawn.", self.team(team), self.draw_claim(claim)),
+            Locale::German => format!("{} beansprucht remis durch {}, die Partie endet remis.", self.team(team), self.draw_claim(claim)),
+        }
+    }
+
+    pub fn no_draw_to_claim(&self) -> &'static str {
+        match self {
+            Locale::English => "The position hasn't been on the board three times and fifty moves haven't passed without a capture or pawn move, the game goes on.",
+            Locale::German => "Die Stellung stand nicht dreimal auf dem Brett und es sind keine fünfzig Züge ohne Schlagen oder Bauernzug vergangen, die Partie geht weiter.",
+        }
+    }
+
+    pub fn cannot_claim_draw(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Can't claim a draw: {error}."),
+            Locale::German => format!("Remis kann nicht beansprucht werden: {error}."),
+        }
+    }
+
+    pub fn declines_draw(&self, name: &str) -> String {
+        match self {
+            Locale::English => format!("{name} declines the draw and plays on."),
+            Locale::German => format!("{name} lehnt das Remis ab und spielt weiter."),
+        }
+    }
+
+    // The side that ran out of time, and the side that wins on time unless it can't checkmate.
+    pub fn out_of_time(&self, flagged: Team, winner: Option<Team>) -> String {
+        match (self, winner) {
+            (Locale::English, Some(winner)) => format!("{} ran out of time. {} wins on time.", self.team(flagged), self.team(winner)),
+            (Locale::English, None) => format!("{} ran out of time. Draw, as it can't checkmate.", self.team(flagged)),
+            (Locale::German, Some(winner)) => format!("{} hat die Zeit überschritten. {} gewinnt auf Zeit.", self.team(flagged), self.team(winner)),
+            (Locale::German, None) => format!("{} hat die Zeit überschritten. Remis, da {} nicht mattsetzen kann.", self.team(flagged), self.team(flagged.opposite())),
+        }
+    }
+
+    pub fn clocks_stopped(&self) -> &'static str {
+        match self {
+            Locale::English => "The clocks are stopped until the next move.",
+            Locale::German => "Die Uhren stehen bis zum nächsten Zug.",
+        }
+    }
+
+    pub fn remote_move(&self, team: Team) -> String {
+        match self {
+            Locale::English => format!("It is {}'s move, played over the network.", self.team(team)),
+            Locale::German => format!("{} ist am Zug und spielt über das Netzwerk.", self.team(team)),
+        }
+    }
+
+    pub fn adjudicated(&self, result: PgnResult) -> String {
+        match self {
+            Locale::English => format!("Game adjudicated: {result}"),
+            Locale::German => format!("Partie entschieden: {result}"),
+        }
+    }
+
+    pub fn only_plies(&self, count: usize) -> String {
+        match self {
+            Locale::English => format!("The game has only {count} half moves."),
+            Locale::German => format!("Die Partie hat nur {count} Halbzüge."),
+        }
+    }
+
+    pub fn no_move_to_annotate(&self) -> &'static str {
+        match self {
+            Locale::English => "There is no such move to annotate.",
+            Locale::German => "Diesen Zug gibt es nicht zum Kommentieren.",
+        }
+    }
+
+    pub fn unknown_annotation(&self, glyph: &str) -> String {
+        match self {
+            Locale::English => format!("Unknown annotation {glyph}, use !, ?, !!, ??, !?, ?! or a glyph number like $14."),
+            Locale::German => format!("Unbekannte Bewertung {glyph}, möglich sind !, ?, !!, ??, !?, ?! oder eine Nummer wie $14."),
+        }
+    }
+
+    pub fn no_variations(&self) -> &'static str {
+        match self {
+            Locale::English => "The game has no variations. Taking back moves and playing others keeps them as one.",
+            Locale::German => "Die Partie hat keine Varianten. Werden Züge zurückgenommen und andere gespielt, bleiben sie als Variante erhalten.",
+        }
+    }
+
+    pub fn playing_variation(&self, number: usize) -> String {
+        match self {
+            Locale::English => format!("Playing variation {number}, the line it replaces is kept as a variation."),
+            Locale::German => format!("Variante {number} wird gespielt, die ersetzte Fortsetzung bleibt als Variante erhalten."),
+        }
+    }
+
+    pub fn variation_failed(&self, number: usize, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not enter variation {number}: {error}."),
+            Locale::German => format!("Variante {number} konnte nicht gewählt werden: {error}."),
+        }
+    }
+
+    pub fn config_file(&self, path: Option<&str>) -> String {
+        match self {
+            Locale::English => format!("Config file: {}", path.unwrap_or("none")),
+            Locale::German => format!("Konfigurationsdatei: {}", path.unwrap_or("keine")),
+        }
+    }
+
+    pub fn setting_failed(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not change the setting: {error}."),
+            Locale::German => format!("Die Einstellung konnte nicht geändert werden: {error}."),
+        }
+    }
+
+    pub fn no_config_path(&self) -> &'static str {
+        match self {
+            Locale::English => "There is no config file location, start the game with --config <FILE>.",
+            Locale::German => "Es gibt keinen Ort für die Konfigurationsdatei, starte das Spiel mit --config <DATEI>.",
+        }
+    }
+
+    pub fn saved_settings(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Saved settings to {path}"),
+            Locale::German => format!("Einstellungen gespeichert in {path}"),
+        }
+    }
+
+    pub fn settings_not_saved(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not save settings: {error}."),
+            Locale::German => format!("Die Einstellungen konnten nicht gespeichert werden: {error}."),
+        }
+    }
+
+    pub fn restored_autosave(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Restored the game autosaved to {path}"),
+            Locale::German => format!("Die in {path} automatisch gespeicherte Partie wurde wiederhergestellt."),
+        }
+    }
+
+    pub fn autosave_failed(&self, path: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not autosave the game to {path}: {error}."),
+            Locale::German => format!("Die Partie konnte nicht automatisch in {path} gespeichert werden: {error}."),
+        }
+    }
+
+    pub fn started_game(&self, number: usize) -> String {
+        match self {
+            Locale::English => format!("Started game {number}"),
+            Locale::German => format!("Partie {number} begonnen"),
+        }
+    }
+
+    pub fn no_such_game(&self, number: usize) -> String {
+        match self {
+            Locale::English => format!("There is no game {number}, game list shows the open games."),
+            Locale::German => format!("Es gibt keine Partie {number}, game list zeigt die offenen Partien."),
+        }
+    }
+
+    pub fn already_current_game(&self, number: usize) -> String {
+        match self {
+            Locale::English => format!("Game {number} is already the current game."),
+            Locale::German => format!("Partie {number} ist bereits die aktuelle Partie."),
+        }
+    }
+
+    pub fn switched_game(&self, number: usize) -> String {
+        match self {
+            Locale::English => format!("Switched to game {number}"),
+            Locale::German => format!("Zu Partie {number} gewechselt"),
+        }
+    }
+
+    pub fn unreadable_file(&self, path: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not read {path}: {error}."),
+            Locale::German => format!("{path} konnte nicht gelesen werden: {error}."),
+        }
+    }
+
+    pub fn no_game_in_file(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("{path} does not contain a game."),
+            Locale::German => format!("{path} enthält keine Partie."),
+        }
+    }
+
+    // A game read from a file that couldn't be replayed.
+    pub fn game_not_loaded(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not load game, {error}."),
+            Locale::German => format!("Die Partie konnte nicht geladen werden, {error}."),
+        }
+    }
+
+    pub fn loaded_game(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Loaded game from {path}"),
+            Locale::German => format!("Partie aus {path} geladen"),
+        }
+    }
+
+    pub fn only_first_game(&self, count: usize) -> String {
+        match self {
+            Locale::English => format!("The file holds {count} games, only the first was loaded."),
+            Locale::German => format!("Die Datei enthält {count} Partien, nur die erste wurde geladen."),
+        }
+    }
+
+    pub fn saved_game(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Saved game to {path}"),
+            Locale::German => format!("Partie gespeichert in {path}"),
+        }
+    }
+
+    pub fn game_not_saved(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not save game: {error}."),
+            Locale::German => format!("Die Partie konnte nicht gespeichert werden: {error}."),
+        }
+    }
+
+    pub fn no_piece_on(&self, square: &str) -> String {
+        match self {
+            Locale::English => format!("There is no piece on {square}."),
+            Locale::German => format!("Auf {square} steht keine Figur."),
+        }
+    }
+
+    pub fn piece_cannot_move(&self, square: &str, turn: Team) -> String {
+        match self {
+            Locale::English => format!("The piece on {square} can't move, it is {} to move.", self.team(turn)),
+            Locale::German => format!("Die Figur auf {square} kann nicht ziehen, {} ist am Zug.", self.team(turn)),
+        }
+    }
+
+    pub fn no_legal_moves(&self, square: &str) -> String {
+        match self {
+            Locale::English => format!("The piece on {square} has no legal moves."),
+            Locale::German => format!("Die Figur auf {square} hat keine zulässigen Züge."),
+        }
+    }
+
+    pub fn legal_moves_from(&self, square: &str, moves: &[String]) -> String {
+        match self {
+            Locale::English => format!("Legal moves from {square}: {}", moves.join(", ")),
+            Locale::German => format!("Zulässige Züge von {square}: {}", moves.join(", ")),
+        }
+    }
+
+    pub fn unreadable_target(&self, target: &str) -> String {
+        match self {
+            Locale::English => format!("Can't read {target}, give a square like e4, a rank like 1 or a file like e."),
+            Locale::German => format!("{target} kann nicht vorgelesen werden, gib ein Feld wie e4, eine Reihe wie 1 oder eine Linie wie e an."),
+        }
+    }
+
+    // What is exported, named as the start of a sentence.
+    pub fn diagram(&self) -> &'static str {
+        match self {
+            Locale::English => "diagram",
+            Locale::German => "Das Diagramm",
+        }
+    }
+
+    pub fn game(&self) -> &'static str {
+        match self {
+            Locale::English => "game",
+            Locale::German => "Die Partie",
+        }
+    }
+
+    pub fn export_exists(&self, what: &str, path: &str) -> String {
+        match self {
+            Locale::English => format!("Could not export {what}: {path} already exists, use --force to overwrite it."),
+            Locale::German => format!("{what} konnte nicht exportiert werden: {path} existiert bereits, mit --force wird die Datei überschrieben."),
+        }
+    }
+
+    pub fn exported(&self, what: &str, path: &str) -> String {
+        match self {
+            Locale::English => format!("Exported {what} to {path}"),
+            Locale::German => format!("{what} wurde nach {path} exportiert."),
+        }
+    }
+
+    pub fn export_failed(&self, what: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not export {what}: {error}."),
+            Locale::German => format!("{what} konnte nicht exportiert werden: {error}."),
+        }
+    }
+
+    pub fn invalid_fen(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Invalid FEN: {error}."),
+            Locale::German => format!("Ungültiger FEN: {error}."),
+        }
+    }
+
+    pub fn position_matches_fen(&self) -> &'static str {
+        match self {
+            Locale::English => "The position matches the FEN.",
+            Locale::German => "Die Stellung stimmt mit dem FEN überein.",
+        }
+    }
+
+    pub fn position_differs_from_fen(&self) -> &'static str {
+        match self {
+            Locale::English => "The position differs from the FEN, the board's side given first:",
+            Locale::German => "Die Stellung weicht vom FEN ab, zuerst steht jeweils die des Bretts:",
+        }
+    }
+
+    pub fn engines_playing(&self) -> &'static str {
+        match self {
+            Locale::English => "The engines are playing, use stop to stop watching first.",
+            Locale::German => "Die Engines spielen, beende das Zuschauen zuerst mit stop.",
+        }
+    }
+
+    pub fn nothing_to_stop(&self) -> &'static str {
+        match self {
+            Locale::English => "There is nothing to stop, no engines are playing or analyzing and the game isn't served.",
+            Locale::German => "Es gibt nichts zu beenden, keine Engine spielt oder analysiert und die Partie wird nicht bereitgestellt.",
+        }
+    }
+
+    pub fn broadcasting(&self, target: &str) -> String {
+        match self {
+            Locale::English => format!("Broadcasting the game to {target}"),
+            Locale::German => format!("Die Partie wird übertragen an {target}"),
+        }
+    }
+    pub fn engine_not_started(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not start the engine: {error}."),
+            Locale::German => format!("Die Engine konnte nicht gestartet werden: {error}."),
+        }
+    }
+
+    pub fn engine_failed(&self, name: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("{name} failed: {error}."),
+            Locale::German => format!("{name} ist ausgefallen: {error}."),
+        }
+    }
+
+    pub fn already_analyzing(&self) -> &'static str {
+        match self {
+            Locale::English => "The position is already being analyzed, stop ends the analysis.",
+            Locale::German => "Die Stellung wird bereits analysiert, stop beendet die Analyse.",
+        }
+    }
+
+    pub fn analyzing(&self, name: &str) -> String {
+        match self {
+            Locale::English => format!("Analyzing with {name}, best plays its first move and stop ends the analysis."),
+            Locale::German => format!("Analyse mit {name}, best spielt ihren ersten Zug und stop beendet die Analyse."),
+        }
+    }
+
+    pub fn analysis_failed(&self, name: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("{name} failed: {error}. Stopped the analysis."),
+            Locale::German => format!("{name} ist ausgefallen: {error}. Die Analyse wurde beendet."),
+        }
+    }
+
+    pub fn stopped_analysis(&self) -> &'static str {
+        match self {
+            Locale::English => "Stopped the analysis.",
+            Locale::German => "Die Analyse wurde beendet.",
+        }
+    }
+
+    pub fn nothing_analyzed(&self) -> &'static str {
+        match self {
+            Locale::English => "Nothing is being analyzed, analyze starts an analysis.",
+            Locale::German => "Es läuft keine Analyse, analyze startet eine.",
+        }
+    }
+
+    pub fn no_engine_move(&self) -> &'static str {
+        match self {
+            Locale::English => "The engine has no move for this position yet.",
+            Locale::German => "Die Engine hat für diese Stellung noch keinen Zug.",
+        }
+    }
+
+    pub fn move_not_played(&self, best: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not play {best}: {error}."),
+            Locale::German => format!("{best} konnte nicht gespielt werden: {error}."),
+        }
+    }
+
+    pub fn no_moves_to_annotate(&self) -> &'static str {
+        match self {
+            Locale::English => "The game has no moves to annotate.",
+            Locale::German => "Die Partie hat keine Züge zum Kommentieren.",
+        }
+    }
+
+    pub fn saved_annotated_game(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Saved the annotated game to {path}"),
+            Locale::German => format!("Kommentierte Partie gespeichert in {path}"),
+        }
+    }
+
+    pub fn archive_failed(&self, path: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not archive the game to {path}: {error}."),
+            Locale::German => format!("Die Partie konnte nicht in {path} archiviert werden: {error}."),
+        }
+    }
+
+    pub fn broadcast_stopped(&self, target: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Stopped broadcasting to {target}: {error}."),
+            Locale::German => format!("Die Übertragung an {target} wurde beendet: {error}."),
+        }
+    }
+
+    pub fn no_database_to_explore(&self) -> &'static str {
+        match self {
+            Locale::English => "No game database to explore, use --from or config set database_path.",
+            Locale::German => "Keine Partiedatenbank zum Erkunden, nutze --from oder config set database_path.",
+        }
+    }
+
+    pub fn no_listed_move(&self, number: usize) -> String {
+        match self {
+            Locale::English => format!("There is no move {number} in the list."),
+            Locale::German => format!("Die Liste hat keinen Zug {number}."),
+        }
+    }
+
+    // The address the game is served on, e.g. http://127.0.0.1:8080.
+    pub fn already_served(&self, url: &str) -> String {
+        match self {
+            Locale::English => format!("The game is already served on {url}, stop ends serving it."),
+            Locale::German => format!("Die Partie wird bereits unter {url} bereitgestellt, stop beendet das."),
+        }
+    }
+
+    pub fn serving_http(&self, url: &str) -> String {
+        match self {
+            Locale::English => format!("Serving the game on {url}, e.g. GET /state. Stop ends serving it."),
+            Locale::German => format!("Die Partie wird unter {url} bereitgestellt, z. B. GET /state. Stop beendet das."),
+        }
+    }
+
+    pub fn serving_remote(&self, url: &str, side: Team) -> String {
+        match self {
+            Locale::English => format!("Serving the game on {url}, the first to connect plays {}. Stop ends serving it.", self.team(side)),
+            Locale::German => format!("Die Partie wird unter {url} bereitgestellt, wer sich zuerst verbindet, spielt {}. Stop beendet das.", self.team(side)),
+        }
+    }
+
+    pub fn cannot_serve(&self, address: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not serve the game on {address}: {error}."),
+            Locale::German => format!("Die Partie konnte nicht unter {address} bereitgestellt werden: {error}."),
+        }
+    }
+
+    pub fn stopped_serving(&self) -> &'static str {
+        match self {
+            Locale::English => "Stopped serving the game.",
+            Locale::German => "Die Partie wird nicht mehr bereitgestellt.",
+        }
+    }
+
+    pub fn played_over_http(&self, played: &str) -> String {
+        match self {
+            Locale::English => format!("Played {played} over HTTP"),
+            Locale::German => format!("Über HTTP gespielt: {played}"),
+        }
+    }
+
+    pub fn remote_joined(&self, side: Team) -> String {
+        match self {
+            Locale::English => format!("A remote player joined and plays {}.", self.team(side)),
+            Locale::German => format!("Ein Spieler hat sich über das Netzwerk verbunden und spielt {}.", self.team(side)),
+        }
+    }
+
+    pub fn remote_left(&self) -> &'static str {
+        match self {
+            Locale::English => "The remote player left, the next to connect takes their place.",
+            Locale::German => "Der Spieler im Netzwerk ist gegangen, wer sich als Nächstes verbindet, nimmt seinen Platz ein.",
+        }
+    }
+
+    pub fn remote_played(&self, played: &str) -> String {
+        match self {
+            Locale::English => format!("The remote player played {played}"),
+            Locale::German => format!("Der Spieler im Netzwerk spielte {played}"),
+        }
+    }
+
+    pub fn invalid_month(&self, month: &str) -> String {
+        match self {
+            Locale::English => format!("Invalid month {month}, expected YYYY/MM, e.g. 2024/01."),
+            Locale::German => format!("Ungültiger Monat {month}, erwartet wird JJJJ/MM, z. B. 2024/01."),
+        }
+    }
+
+    pub fn no_import_file(&self) -> &'static str {
+        match self {
+            Locale::English => "No file to import into, use --to or config set database_path.",
+            Locale::German => "Keine Datei für den Import, nutze --to oder config set database_path.",
+        }
+    }
+
+    pub fn import_failed(&self, username: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not import the games of {username}: {error}."),
+            Locale::German => format!("Die Partien von {username} konnten nicht importiert werden: {error}."),
+        }
+    }
+
+    pub fn imported_games(&self, added: usize, username: &str, months: usize, path: &str, skipped: usize) -> String {
+        match self {
+            Locale::English => format!("Imported {added} new games of {username} from {months} months into {path}, {skipped} were already there."),
+            Locale::German => format!("{added} neue Partien von {username} aus {months} Monaten in {path} importiert, {skipped} waren schon vorhanden."),
+        }
+    }
+
+    pub fn month_not_imported(&self, month: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not import {month}: {error}."),
+            Locale::German => format!("{month} konnte nicht importiert werden: {error}."),
+        }
+    }
+
+    pub fn unreadable_repertoire(&self, path: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not read the repertoire {path}: {error}."),
+            Locale::German => format!("Das Repertoire {path} konnte nicht gelesen werden: {error}."),
+        }
+    }
+
+    pub fn unreadable_training_record(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not read the training record, it won't be kept this session: {error}."),
+            Locale::German => format!("Die Trainingsaufzeichnung konnte nicht gelesen werden und wird in dieser Sitzung nicht geführt: {error}."),
+        }
+    }
+
+    pub fn training_record_not_saved(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not save the training record: {error}."),
+            Locale::German => format!("Die Trainingsaufzeichnung konnte nicht gespeichert werden: {error}."),
+        }
+    }
+
+    pub fn training_started(&self, lines: usize, path: &str, team: Team) -> String {
+        match self {
+            Locale::English => format!("Training the {lines} lines of {path} as {}. Play the repertoire's moves with move, stop ends the training.", self.team(team)),
+            Locale::German => format!("Training der {lines} Varianten von {path} mit {}. Spiele die Züge des Repertoires mit move, stop beendet das Training.", self.team(team)),
+        }
+    }
+
+    pub fn opponent_plays(&self, answer: &str) -> String {
+        match self {
+            Locale::English => format!("The opponent plays {answer}."),
+            Locale::German => format!("Der Gegner spielt {answer}."),
+        }
+    }
+
+    pub fn not_in_repertoire(&self, wrong: &str, answers: Option<&[String]>) -> String {
+        match (self, answers) {
+            (Locale::English, None) => format!("{wrong} isn't in the repertoire here. Try again."),
+            (Locale::English, Some(answers)) => format!("{wrong} isn't in the repertoire here, it plays {}. Try again.", answers.join(" or ")),
+            (Locale::German, None) => format!("{wrong} steht hier nicht im Repertoire. Versuch es noch einmal."),
+            (Locale::German, Some(answers)) => format!("{wrong} steht hier nicht im Repertoire, es spielt {}. Versuch es noch einmal.", answers.join(" oder ")),
+        }
+    }
+
+    pub fn correct(&self, played: &str) -> String {
+        match self {
+            Locale::English => format!("Correct, {played}!"),
+            Locale::German => format!("Richtig, {played}!"),
+        }
+    }
+
+    pub fn end_of_line(&self, line: &str, successes: u32, attempts: u32) -> String {
+        match self {
+            Locale::English => format!("End of the line {line}, played without a mistake {successes} of {attempts} times.\nNext line."),
+            Locale::German => format!("Ende der Variante {line}, {successes} von {attempts} Mal fehlerfrei gespielt.\nNächste Variante."),
+        }
+    }
+
+    pub fn stopped_training(&self, known: u32, finished: u32, lines: &str) -> String {
+        match self {
+            Locale::English => format!("Stopped the training, {known} of {finished} lines played without a mistake. How well each line is known:\n{lines}"),
+            Locale::German => format!("Das Training wurde beendet, {known} von {finished} Varianten fehlerfrei gespielt. Wie gut jede Variante sitzt:\n{lines}"),
+        }
+    }
+
+    pub fn no_training(&self) -> &'static str {
+        match self {
+            Locale::English => "No opening is being trained.",
+            Locale::German => "Es wird keine Eröffnung trainiert.",
+        }
+    }
+
+    pub fn playing_against(&self, player: Team, name: &str) -> String {
+        match self {
+            Locale::English => format!("Playing {} against {name}, undo 2 takes back your last move.", self.team(player)),
+            Locale::German => format!("Du spielst {} gegen {name}, undo 2 nimmt deinen letzten Zug zurück.", self.team(player)),
+        }
+    }
+
+    pub fn opponent_not_resumed(&self, path: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not start the engine {path}: {error}. The game goes on without it."),
+            Locale::German => format!("Die Engine {path} konnte nicht gestartet werden: {error}. Die Partie geht ohne sie weiter."),
+        }
+    }
+
+    pub fn opponent_failed(&self, name: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("{name} failed: {error}. The game goes on without it."),
+            Locale::German => format!("{name} ist ausgefallen: {error}. Die Partie geht ohne sie weiter."),
+        }
+    }
+
+    pub fn opponent_played(&self, name: &str, played: &str) -> String {
+        match self {
+            Locale::English => format!("{name} played {played}"),
+            Locale::German => format!("{name} spielte {played}"),
+        }
+    }
+
+    pub fn opponent_move_failed(&self, name: &str, best: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("{name} played {best}: {error}. The game goes on without it."),
+            Locale::German => format!("{name} spielte {best}: {error}. Die Partie geht ohne sie weiter."),
+        }
+    }
+
+    pub fn unreadable_ratings(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not read the ratings, games won't be rated this session: {error}."),
+            Locale::German => format!("Die Wertungen konnten nicht gelesen werden, in dieser Sitzung werden keine Partien gewertet: {error}."),
+        }
+    }
+
+    pub fn rating_changes(&self, white: &str, white_change: &str, black: &str, black_change: &str) -> String {
+        match self {
+            Locale::English => format!("Rating changes: {white} {white_change}, {black} {black_change}."),
+            Locale::German => format!("Wertungsänderungen: {white} {white_change}, {black} {black_change}."),
+        }
+    }
+
+    pub fn ratings_not_saved(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not save the ratings: {error}."),
+            Locale::German => format!("Die Wertungen konnten nicht gespeichert werden: {error}."),
+        }
+    }
+
+    pub fn unreadable_puzzles(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not read the puzzles: {error}."),
+            Locale::German => format!("Die Aufgaben konnten nicht gelesen werden: {error}."),
+        }
+    }
+
+    pub fn no_puzzles(&self) -> &'static str {
+        match self {
+            Locale::English => "There are no puzzles to play.",
+            Locale::German => "Es gibt keine Aufgaben zum Spielen.",
+        }
+    }
+
+    pub fn rush_started(&self, minutes: u64, strikes: usize) -> String {
+        match self {
+            Locale::English => format!("Solve as many puzzles as you can in {minutes} minutes, {strikes} wrong moves end the rush. Make the moves with move, stop ends the rush."),
+            Locale::German => format!("Löse in {minutes} Minuten so viele Aufgaben wie möglich, {strikes} falsche Züge beenden den Rush. Ziehe mit move, stop beendet den Rush."),
+        }
+    }
+
+    pub fn script_not_loaded(&self, name: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not load the script {name}: {error}."),
+            Locale::German => format!("Das Skript {name} konnte nicht geladen werden: {error}."),
+        }
+    }
+
+    pub fn script_command_taken(&self, script: &str, command: &str) -> String {
+        match self {
+            Locale::English => format!("The script {script} can't replace the command {command}, it is left out."),
+            Locale::German => format!("Das Skript {script} kann den Befehl {command} nicht ersetzen und wird ausgelassen."),
+        }
+    }
+
+    pub fn saved_session(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Saved the session to {path}"),
+            Locale::German => format!("Sitzung gespeichert in {path}"),
+        }
+    }
+
+    pub fn session_not_saved(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not save the session: {error}."),
+            Locale::German => format!("Die Sitzung konnte nicht gespeichert werden: {error}."),
+        }
+    }
+
+    pub fn loaded_session(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Loaded the session from {path}"),
+            Locale::German => format!("Sitzung aus {path} geladen"),
+        }
+    }
+
+    pub fn session_not_loaded(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not load the session: {error}."),
+            Locale::German => format!("Die Sitzung konnte nicht geladen werden: {error}."),
+        }
+    }
+
+    pub fn exported_session(&self, games: usize, path: &str) -> String {
+        match self {
+            Locale::English => format!("Exported {games} open game(s), the settings and the ratings to {path}"),
+            Locale::German => format!("{games} offene Partie(n), die Einstellungen und die Wertungen nach {path} exportiert"),
+        }
+    }
+
+    pub fn session_not_exported(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not export the session: {error}."),
+            Locale::German => format!("Die Sitzung konnte nicht exportiert werden: {error}."),
+        }
+    }
+
+    pub fn open_games_in_use(&self) -> &'static str {
+        match self {
+            Locale::English => "The open games are in use by a simul or a served game, end it first.",
+            Locale::German => "Die offenen Partien gehören zu einem Simultan oder einer bereitgestellten Partie, beende sie zuerst.",
+        }
+    }
+
+    pub fn session_not_imported(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not import the session: {error}."),
+            Locale::German => format!("Die Sitzung konnte nicht importiert werden: {error}."),
+        }
+    }
+
+    pub fn session_without_games(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("Could not import the session: {path} holds no games."),
+            Locale::German => format!("Die Sitzung konnte nicht importiert werden: {path} enthält keine Partien."),
+        }
+    }
+
+    pub fn imported_session(&self, games: usize, path: &str) -> String {
+        match self {
+            Locale::English => format!("Imported {games} open game(s) from {path}"),
+            Locale::German => format!("{games} offene Partie(n) aus {path} importiert"),
+        }
+    }
+
+    pub fn imported_settings(&self) -> &'static str {
+        match self {
+            Locale::English => "The settings were imported, config save keeps them.",
+            Locale::German => "Die Einstellungen wurden importiert, config save behält sie.",
+        }
+    }
+
+    pub fn settings_not_imported(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not import the settings, the current ones are kept: {error}."),
+            Locale::German => format!("Die Einstellungen konnten nicht importiert werden, die bisherigen bleiben: {error}."),
+        }
+    }
+
+    pub fn newer_version(&self) -> &'static str {
+        match self {
+            Locale::English => "The file was saved by a newer version of the program, what it added is left out.",
+            Locale::German => "Die Datei stammt von einer neueren Version des Programms, was diese hinzugefügt hat, wird ausgelassen.",
+        }
+    }
+
+    pub fn simul_running(&self) -> &'static str {
+        match self {
+            Locale::English => "A simul is already being played, board switches between its boards.",
+            Locale::German => "Es läuft bereits ein Simultan, board wechselt zwischen seinen Brettern.",
+        }
+    }
+
+    pub fn simul_started(&self, name: &str, boards: usize) -> String {
+        match self {
+            Locale::English => format!("Playing {name} on {boards} boards, you have White on each. Move on the board shown, board <number> goes to another board and board alone to the next one waiting for your move."),
+            Locale::German => format!("Du spielst gegen {name} an {boards} Brettern, überall mit Weiß. Ziehe auf dem gezeigten Brett, board <Nummer> wechselt zu einem anderen Brett und board allein zum nächsten, das auf deinen Zug wartet."),
+        }
+    }
+
+    pub fn simul_board_status(&self, board: usize, boards: usize, status: &GameStatus) -> String {
+        match self {
+            Locale::English => format!("Board {board} of {boards}, {}.", self.status(status)),
+            Locale::German => format!("Brett {board} von {boards}, {}.", self.status(status)),
+        }
+    }
+
+    pub fn no_simul(&self) -> &'static str {
+        match self {
+            Locale::English => "No simul is being played, start one with simul <boards>.",
+            Locale::German => "Es läuft kein Simultan, starte eines mit simul <Bretter>.",
+        }
+    }
+
+    pub fn no_board(&self, board: usize, boards: usize) -> String {
+        match self {
+            Locale::English => format!("There is no board {board}, the simul is played on {boards} boards."),
+            Locale::German => format!("Es gibt kein Brett {board}, das Simultan wird an {boards} Brettern gespielt."),
+        }
+    }
+
+    pub fn no_board_waiting(&self) -> &'static str {
+        match self {
+            Locale::English => "No other board is waiting for your move.",
+            Locale::German => "Kein anderes Brett wartet auf deinen Zug.",
+        }
+    }
+
+    pub fn board_shown(&self, board: usize) -> String {
+        match self {
+            Locale::English => format!("Board {board} is already shown."),
+            Locale::German => format!("Brett {board} wird bereits gezeigt."),
+        }
+    }
+
+    pub fn simul_over(&self, score: f64, boards: usize, won: usize, drawn: usize) -> String {
+        let lost = boards - won - drawn;
+        match self {
+            Locale::English => format!("The simul is over, you scored {score} of {boards} with {won} won, {drawn} drawn and {lost} lost."),
+            Locale::German => format!("Das Simultan ist beendet, du hast {score} von {boards} Punkten erzielt, mit {won} Siegen, {drawn} Remis und {lost} Niederlagen."),
+        }
+    }
+
+    pub fn no_lesson(&self, lesson: usize) -> String {
+        match self {
+            Locale::English => format!("There is no lesson {lesson}, tutorial list shows the lessons."),
+            Locale::German => format!("Es gibt keine Lektion {lesson}, tutorial list zeigt die Lektionen."),
+        }
+    }
+
+    pub fn tutorial_started(&self) -> &'static str {
+        match self {
+            Locale::English => "Make the moves with move, tutorial hint helps and tutorial stop returns to the game.",
+            Locale::German => "Ziehe mit move, tutorial hint hilft und tutorial stop führt zurück zur Partie.",
+        }
+    }
+
+    pub fn start_lesson(&self) -> &'static str {
+        match self {
+            Locale::English => "Start one with tutorial start <number>.",
+            Locale::German => "Starte eine mit tutorial start <Nummer>.",
+        }
+    }
+
+    pub fn tutorial_not_running(&self) -> &'static str {
+        match self {
+            Locale::English => "The tutorial isn't running.",
+            Locale::German => "Das Tutorial läuft nicht.",
+        }
+    }
+
+    pub fn start_tutorial(&self) -> &'static str {
+        match self {
+            Locale::English => "The tutorial isn't running, start it with tutorial start.",
+            Locale::German => "Das Tutorial läuft nicht, starte es mit tutorial start.",
+        }
+    }
+
+    pub fn tutorial_answer(&self, solution: &str) -> String {
+        match self {
+            Locale::English => format!("The answer was {solution}."),
+            Locale::German => format!("Die Lösung war {solution}."),
+        }
+    }
+
+    pub fn left_tutorial(&self) -> &'static str {
+        match self {
+            Locale::English => "Left the tutorial, back to the game.",
+            Locale::German => "Tutorial verlassen, zurück zur Partie.",
+        }
+    }
+
+    pub fn try_again(&self, message: &str) -> String {
+        match self {
+            Locale::English => format!("{message} Try again."),
+            Locale::German => format!("{message} Versuch es noch einmal."),
+        }
+    }
+
+    pub fn tutorial_finished(&self) -> &'static str {
+        match self {
+            Locale::English => "You finished the tutorial! Start a game with new, or play the computer with new --vs-engine.",
+            Locale::German => "Du hast das Tutorial abgeschlossen! Beginne eine Partie mit new oder spiele gegen den Computer mit new --vs-engine.",
+        }
+    }
+
+    pub fn no_engine_to_watch(&self) -> &'static str {
+        match self {
+            Locale::English => "No engine to watch, use --white and --black or config set engine_path.",
+            Locale::German => "Keine Engine zum Zuschauen, nutze --white und --black oder config set engine_path.",
+        }
+    }
+
+    pub fn engines_not_started(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not start the engines: {error}."),
+            Locale::German => format!("Die Engines konnten nicht gestartet werden: {error}."),
+        }
+    }
+
+    pub fn watching(&self, white: &str, black: &str) -> String {
+        match self {
+            Locale::English => format!("Watching {white} against {black}, stop ends the game."),
+            Locale::German => format!("{white} gegen {black}, stop beendet die Partie."),
+        }
+    }
+
+    pub fn stopped_watching(&self) -> &'static str {
+        match self {
+            Locale::English => "Stopped watching, the game so far can be saved with save.",
+            Locale::German => "Zuschauen beendet, die bisherige Partie kann mit save gespeichert werden.",
+        }
+    }
+
+    pub fn watched_engine_failed(&self, team: Team, error: &str) -> String {
+        match self {
+            Locale::English => format!("{}'s engine failed, stopped watching: {error}.", self.team(team)),
+            Locale::German => format!("Die Engine von {} ist ausgefallen, das Zuschauen wurde beendet: {error}.", self.team(team)),
+        }
+    }
+
+    pub fn watched_move_failed(&self, team: Team, best: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("{}'s engine played {best}: {error}. Stopped watching.", self.team(team)),
+            Locale::German => format!("Die Engine von {} spielte {best}: {error}. Das Zuschauen wurde beendet.", self.team(team)),
+        }
+    }
+
+    // The reason is the one written to the game record, which stays English.
+    pub fn watched_game_over(&self, result: PgnResult, reason: &str) -> String {
+        match self {
+            Locale::English => format!("Game over: {result} by {reason}."),
+            Locale::German => format!("Partie beendet: {result} ({reason})."),
+        }
+    }
+
+    pub fn window_welcome(&self) -> &'static str {
+        match self {
+            Locale::English => "Drag a piece to move it, or type a command, e.g. help for the list of commands.",
+            Locale::German => "Ziehe eine Figur mit der Maus oder gib einen Befehl ein, z. B. help für die Liste der Befehle.",
+        }
+    }
+
+    pub fn rules_topics(&self, names: &[&str]) -> String {
+        match self {
+            Locale::English => format!("Rules topics: {}. Type e.g. help rules castling.", names.join(", ")),
+            Locale::German => format!("Regelthemen: {}. Gib z. B. help rules rochade ein.", names.join(", ")),
+        }
+    }
+
+    pub fn no_rules_topic(&self, topic: &str, names: &[&str]) -> String {
+        match self {
+            Locale::English => format!("There is no rules topic {topic}. The topics are: {}.", names.join(", ")),
+            Locale::German => format!("Es gibt kein Regelthema {topic}. Die Themen sind: {}.", names.join(", ")),
+        }
+    }
+
+    // A rules topic, titled by its name, with the diagram of its example.
+    pub fn rule(&self, name: &str, diagram: &str, text: &str) -> String {
+        let mut title = name.to_string();
+        if let Some(first) = title.get(..1) {
+            title.replace_range(..1, &first.to_uppercase());
+        }
+        format!("{title}\n{diagram}\n{text}")
+    }
+
+    pub fn screen_failed(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not run the full screen interface ({error}), using line mode."),
+            Locale::German => format!("Die Vollbildoberfläche konnte nicht gestartet werden ({error}), es geht zeilenweise weiter."),
+        }
+    }
+
+    pub fn config_not_read(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not read the config file, using the default settings. {error}."),
+            Locale::German => format!("Die Konfigurationsdatei konnte nicht gelesen werden, es gelten die Standardeinstellungen. {error}."),
+        }
+    }
+
+    pub fn broadcast_failed(&self, target: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not broadcast to {target}: {error}."),
+            Locale::German => format!("Die Übertragung nach {target} ist fehlgeschlagen: {error}."),
+        }
+    }
+
+    pub fn offer_autosave(&self, ply: usize, path: &str) -> String {
+        match self {
+            Locale::English => format!("An unfinished game with {ply} half moves was autosaved to {path}. Restore it? [y/N] "),
+            Locale::German => format!("Eine nicht beendete Partie mit {ply} Halbzügen wurde in {path} gesichert. Wiederherstellen? [j/N] "),
+        }
+    }
+
+    // Whether an answer to a yes/no question is yes. The English answers are taken in every
+    // language.
+    pub fn is_yes(&self, answer: &str) -> bool {
+        let answer = answer.trim().to_lowercase();
+        match self {
+            Locale::English => answer == "y" || answer == "yes",
+            Locale::German => ["j", "ja", "y", "yes"].contains(&answer.as_str()),
+        }
+    }
+
+    pub fn autosave_not_restored(&self, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not restore the game, {error}."),
+            Locale::German => format!("Die Partie konnte nicht wiederhergestellt werden, {error}."),
+        }
+    }
+
+    pub fn keys_heading(&self) -> &'static str {
+        match self {
+            Locale::English => "Keys of the full screen interface, set in the [keys] section of the config:",
+            Locale::German => "Tasten der Vollbildoberfläche, festgelegt im Abschnitt [keys] der Konfiguration:",
+        }
+    }
+
+    pub fn no_keys(&self) -> &'static str {
+        match self {
+            Locale::English => "(none)",
+            Locale::German => "(keine)",
+        }
+    }
+
+    pub fn key_description(&self, action: KeyAction) -> &'static str {
+        match self {
+            Locale::English => action.get_description(),
+            Locale::German => match action {
+                KeyAction::Prev => "in der Partie zurückgehen",
+                KeyAction::Next => "in der Partie vorgehen",
+                KeyAction::Start => "die Ausgangsstellung zeigen",
+                KeyAction::End => "zur aktuellen Stellung zurückkehren",
+                KeyAction::Flip => "das Brett drehen",
+                KeyAction::Undo => "den letzten Zug zurücknehmen",
+                KeyAction::Redo => "den zurückgenommenen Zug wiederholen",
+                KeyAction::Redraw => "den Bildschirm neu zeichnen",
+                KeyAction::BoardCursor => "von der Eingabezeile auf das Brett wechseln",
+                KeyAction::CursorLeft => "den Brettcursor nach links bewegen",
+                KeyAction::CursorRight => "den Brettcursor nach rechts bewegen",
+                KeyAction::CursorUp => "den Brettcursor nach oben bewegen",
+                KeyAction::CursorDown => "den Brettcursor nach unten bewegen",
+                KeyAction::Pick => "die Figur am Brettcursor aufnehmen oder absetzen",
+                KeyAction::LeaveBoard => "vom Brett zurück in die Eingabezeile wechseln",
+            },
+        }
+    }
+
+    pub fn keys_always_quit(&self) -> &'static str {
+        match self {
+            Locale::English => "Ctrl-C and Ctrl-D always quit.",
+            Locale::German => "Strg-C und Strg-D beenden immer.",
+        }
+    }
+
+    // A line of the list of open games, marked * when it is the current one.
+    pub fn open_game(&self, number: usize, current: bool, white: &str, black: &str, ply: usize, status: &GameStatus) -> String {
+        let marker = if current { '*' } else { ' ' };
+        match self {
+            Locale::English => format!("{marker} {number}. {white} vs {black}, {ply} half move(s), {}", self.status(status)),
+            Locale::German => format!("{marker} {number}. {white} gegen {black}, {ply} Halbzüge, {}", self.status(status)),
+        }
+    }
+
+    pub fn bench_heading(&self, depth: u32) -> String {
+        match self {
+            Locale::English => format!("Positions searched to depth {depth}, without and with move ordering:"),
+            Locale::German => format!("Stellungen bis Tiefe {depth} durchsucht, ohne und mit Zugsortierung:"),
+        }
+    }
+
+    pub fn bench_position(&self, name: &str, nodes: (u64, u64), millis: (u128, u128)) -> String {
+        let unit = match self {
+            Locale::English => "nodes",
+            Locale::German => "Knoten",
+        };
+        format!("  {name:<16} {:>9} -> {:>7} {unit}, {:>6} ms -> {:>5} ms", nodes.0, nodes.1, millis.0, millis.1)
+    }
+
+    pub fn bench_saved(&self, saved: u64) -> String {
+        match self {
+            Locale::English => format!("  Move ordering searched {saved}% fewer positions."),
+            Locale::German => format!("  Mit Zugsortierung wurden {saved}% weniger Stellungen durchsucht."),
+        }
+    }
+
+    // e.g. "Qg4?? \u{2014} losing the queen, best was Nf3".
+    pub fn blunder(&self, played: &str, reason: &str, best: &str) -> String {
+        match self {
+            Locale::English => format!("{played}?? \u{2014} {reason}, best was {best}"),
+            Locale::German => format!("{played}?? \u{2014} {reason}, besser war {best}"),
+        }
+    }
+
+    pub fn allowing_mate(&self, moves: i32) -> String {
+        match self {
+            Locale::English => format!("allowing mate in {moves}"),
+            Locale::German => format!("lässt Matt in {moves} zu"),
+        }
+    }
+
+    pub fn giving_stalemate(&self) -> &'static str {
+        match self {
+            Locale::English => "giving stalemate",
+            Locale::German => "setzt patt",
+        }
+    }
+
+    pub fn losing_piece(&self, piece: ChessPiece) -> String {
+        match (self, piece) {
+            (Locale::English, ChessPiece::Queen) => String::from("losing the queen"),
+            (Locale::English, piece) => format!("losing a {}", piece.get_name()),
+            (Locale::German, ChessPiece::Queen) => String::from("verliert die Dame"),
+            (Locale::German, ChessPiece::Rook) => String::from("verliert einen Turm"),
+            (Locale::German, ChessPiece::Bishop) => String::from("verliert einen Läufer"),
+            (Locale::German, ChessPiece::Knight) => String::from("verliert einen Springer"),
+            (Locale::German, ChessPiece::Pawn) => String::from("verliert einen Bauern"),
+            (Locale::German, ChessPiece::King) => String::from("verliert den König"),
+        }
+    }
+
+    pub fn dropping_evaluation(&self, score: EngineScore) -> String {
+        match self {
+            Locale::English => format!("dropping the evaluation to {score}"),
+            Locale::German => format!("senkt die Bewertung auf {score}"),
+        }
+    }
+
+    // e.g. "White: accuracy 87%, 2 inaccuracies, 1 mistake, no blunders."
+    pub fn player_summary(&self, team: Team, summary: &PlayerSummary) -> String {
+        match self {
+            Locale::English => format!("{}: {summary}.", self.team(team)),
+            Locale::German => {
+                let count = |n: u32, one: &str, many: &str, none: &str| match n {
+                    0 => String::from(none),
+                    1 => format!("1 {one}"),
+                    n => format!("{n} {many}"),
+                };
+                let accuracy = match summary.get_accuracy() {
+                    Some(accuracy) => format!("Genauigkeit {accuracy:.0}%"),
+                    None => String::from("keine Züge"),
+                };
+                format!("{}: {accuracy}, {}, {}, {}.", self.team(team),
+                    count(summary.get_inaccuracies(), "Ungenauigkeit", "Ungenauigkeiten", "keine Ungenauigkeiten"),
+                    count(summary.get_mistakes(), "Fehler", "Fehler", "keine Fehler"),
+                    count(summary.get_blunders(), "grober Fehler", "grobe Fehler", "keine groben Fehler"))
+            }
+        }
+    }
+
+    pub fn crash_saved(&self, path: &str) -> String {
+        match self {
+            Locale::English => format!("The game in progress was saved to {path}, open it again with load {path}."),
+            Locale::German => format!("Die laufende Partie wurde in {path} gesichert, öffne sie wieder mit load {path}."),
+        }
+    }
+
+    pub fn crash_not_saved(&self, dir: &str, error: &str) -> String {
+        match self {
+            Locale::English => format!("Could not save the game in progress to {dir}: {error}."),
+            Locale::German => format!("Die laufende Partie konnte nicht in {dir} gesichert werden: {error}."),
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_locale {
+    use super::*;
+
+    #[test]
+    pub fn locale_names_give_the_language() {
+        assert_eq!(Locale::from_locale_name("de_DE.UTF-8"), Some(Locale::German));
+        assert_eq!(Locale::from_locale_name("de"), Some(Locale::German));
+        assert_eq!(Locale::from_locale_name("en_GB.UTF-8"), Some(Locale::English));
+        assert_eq!(Locale::from_locale_name("C"), None);
+        assert_eq!(Locale::from_locale_name("fr_FR"), None);
+    }
+
+    #[test]
+    pub fn english_matches_the_game_status() {
+        let statuses = [
+            GameStatus::ToMove(Team::Light),
+            GameStatus::InCheck(Team::Dark),
+            GameStatus::Checkmate { winner: Team::Dark },
+            GameStatus::Stalemate,
+            GameStatus::Ended(PgnResult::WhiteWin),
+            GameStatus::Ended(PgnResult::Draw),
+        ];
+        for status in &statuses {
+            assert_eq!(Locale::English.status(status), status.to_string());
+        }
+        assert_eq!(Locale::German.status(&statuses[2]), "Schachmatt \u{2014} Schwarz gewinnt");
+        assert_eq!(Locale::German.status(&statuses[5]), "Partie beendet \u{2014} 1/2-1/2");
+    }
+}
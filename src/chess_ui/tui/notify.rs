@@ -0,0 +1,168 @@
+/*
+notify.rs
+Alerts for when the player isn't looking at the board: the computer or the remote player moved,
+the player is in check, or the player's clock is running low. Each can ring the terminal bell,
+show in the window title and pop up a desktop notification, as configured; all are off by
+default. Alerts are queued by the game and sent by the interface, so tests see them without a
+terminal. The title is put back at the next command.
+
+Desktop notifications use notify-send on Linux and the BSDs and osascript on macOS, and are
+skipped where neither is available.
+*/
+
+use std::{
+    io,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+use crossterm::{execute, style::Print, terminal::SetTitle};
+
+use crate::{
+    chess_clock::format_clock_time,
+    chess_core::Team,
+    chess_game::GameStatus,
+    chess_pgn::ChessMove,
+};
+use super::TuiGame;
+
+const WINDOW_TITLE: &str = "rust-chess";
+
+impl TuiGame {
+    fn alerts_enabled(&self) -> bool {
+        self.config.get_alert_bell() || self.config.get_alert_title() || self.config.get_alert_desktop()
+    }
+
+    // Queues an alert, when any kind of alert is turned on.
+    pub(super) fn alert(&mut self, text: String) {
+        if self.alerts_enabled() {
+            self.alerts.push(text);
+        }
+    }
+
+    // Alerts that the other side, e.g. "Stockfish", moved, and whether that gave check.
+    pub(super) fn alert_move(&mut self, mover: &str, played: &ChessMove) {
+        let text = match self.state.get_status() {
+            GameStatus::InCheck(team) => format!("{mover} played {played}, {team} is in check"),
+            GameStatus::ToMove(_) => format!("{mover} played {played}"),
+            status => format!("{mover} played {played}, {status}"),
+        };
+        self.alert(text);
+    }
+
+    // Alerts once when the running clock of a side played at this terminal gets low, again
+    // after increments took it back above the limit.
+    pub(super) fn check_low_time(&mut self) {
+        let limit = Duration::from_secs(self.config.get_low_time_alert() as u64);
+        let team = match self.clock.as_ref().and_then(|c| c.get_running()) {
+            Some(team) if !limit.is_zero() && self.is_played_here(team) => team,
+            _ => return,
+        };
+        let remaining = self.clock.as_ref().map_or(Duration::ZERO, |c| c.get_remaining(team, Instant::now()));
+        let index = match team {
+            Team::Light => 0,
+            Team::Dark => 1,
+        };
+        if remaining > limit {
+            self.low_time_alerted[index] = false;
+        }
+        else if !self.low_time_alerted[index] && !remaining.is_zero() {
+            self.low_time_alerted[index] = true;
+            self.alert(format!("{team} has {} left", format_clock_time(remaining)));
+        }
+    }
+
+    // Whether a side's moves are made at this terminal, rather than by the computer, the remote
+    // player or watched engines.
+    fn is_played_here(&self, team: Team) -> bool {
+        self.watch.is_none()
+            && self.opponent.as_ref().is_none_or(|o| o.get_team() != team)
+            && (self.remote.is_none() || self.get_remote_side() != team)
+    }
+
+    pub(super) fn take_alerts(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.alerts)
+    }
+}
+
+// Sends the queued alerts the configured ways. Only the latest is shown in the title.
+pub(super) fn send_alerts(game: &mut TuiGame) {
+    let alerts = game.take_alerts();
+    let latest = match alerts.last() {
+        Some(text) => text,
+        None => return,
+    };
+    let mut out = io::stdout();
+    if game.config.get_alert_bell() {
+        let _ = execute!(out, Print('\u{0007}'));
+    }
+    if game.config.get_alert_title() {
+        let _ = execute!(out, SetTitle(format!("{latest} \u{2014} {WINDOW_TITLE}")));
+        game.alert_in_title = true;
+    }
+    if game.config.get_alert_desktop() {
+        for text in &alerts {
+            notify_desktop(text);
+        }
+    }
+}
+
+// Puts the window title back after an alert was shown in it.
+pub(super) fn restore_title(game: &mut TuiGame) {
+    if game.alert_in_title {
+        let _ = execute!(io::stdout(), SetTitle(WINDOW_TITLE));
+        game.alert_in_title = false;
+    }
+}
+
+fn notify_desktop(text: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!("display notification \"{}\" with title \"{WINDOW_TITLE}\"", text.replace('"', "'"));
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(script);
+        command
+    }
+    else if cfg!(unix) {
+        let mut command = Command::new("notify-send");
+        command.arg(WINDOW_TITLE).arg(text);
+        command
+    }
+    else {
+        return;
+    };
+    // The notifier is waited for on a thread of its own, so the game doesn't stall on it.
+    if let Ok(mut child) = command.spawn() {
+        thread::spawn(move || child.wait());
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_notify {
+    use super::*;
+
+    #[test]
+    pub fn opponent_moves_and_low_clocks_alert() {
+        let mut game = TuiGame::new();
+        game.run_command_line("new --vs-engine --movetime 20");
+        game.run_command_line("move e4");
+        assert!(game.take_alerts().is_empty());
+
+        game.run_command_line("config set alert_bell on");
+        game.run_command_line("move d4");
+        let alerts = game.take_alerts();
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].starts_with(&format!("{} played ", game.opponent.as_ref().unwrap().get_name())));
+
+        game.run_command_line("config set low_time_alert 600");
+        game.run_command_line("config set time_control 5+0");
+        game.run_command_line("new --vs-engine --movetime 20");
+        game.run_command_line("move e4");
+        game.take_alerts();
+        game.check_clock();
+        assert_eq!(game.take_alerts(), vec![String::from("White has 5:00 left")]);
+        game.check_clock();
+        assert!(game.take_alerts().is_empty());
+    }
+}
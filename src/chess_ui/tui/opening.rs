@@ -0,0 +1,316 @@
+/*
+opening.rs
+Training an opening repertoire. The lines of a repertoire PGN are played on a board of their
+own: the computer plays the other side's moves from the repertoire, choosing the line known least,
+and the player has to answer with the repertoire's move. A wrong move is taken back and the line
+counts as missed. Each finished line is recorded in the training record, so the lines missed
+most come back first in later sessions.
+*/
+
+use std::{fs, path::Path};
+
+use crate::{
+    chess_core::{Board, BoardMove, Team},
+    chess_pgn::{ChessMove, parse_pgn_games},
+    chess_repertoire::{LineRecord, Repertoire, TrainingRecord, format_line},
+};
+use super::{Drill, TuiGame};
+
+pub struct OpeningTrainer {
+    // The repertoire file, which the lines are recorded under.
+    name: String,
+    repertoire: Repertoire,
+    record: TrainingRecord,
+    team: Team,
+    board: Board,
+    played: Vec<BoardMove>,
+    // Wrong moves played in the current position, and whether the line had any.
+    wrong_moves: u32,
+    missed: bool,
+    // Lines finished this session, and how many of them without a mistake.
+    finished: u32,
+    known: u32,
+}
+
+impl OpeningTrainer {
+    fn new(name: String, repertoire: Repertoire, record: TrainingRecord, team: Team) -> OpeningTrainer {
+        let mut trainer = OpeningTrainer {
+            name,
+            repertoire,
+            record,
+            team,
+            board: Board::new(),
+            played: Vec::new(),
+            wrong_moves: 0,
+            missed: false,
+            finished: 0,
+            known: 0,
+        };
+        trainer.start_line();
+        trainer
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    // e.g. "Training white.pgn as White: 2 of 3 lines without a mistake".
+    pub fn get_title(&self) -> String {
+        let file_name = Path::new(&self.name).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        format!("Training {} as {}: {} of {} lines without a mistake", file_name, self.team, self.known, self.finished)
+    }
+
+    // Starts again from the starting position, playing the opponent's first move when the
+    // player has Black.
+    fn start_line(&mut self) -> Option<ChessMove> {
+        self.board = Board::new();
+        self.played.clear();
+        self.wrong_moves = 0;
+        self.missed = false;
+        self.play_opponent_move()
+    }
+
+    // The line to drill among those following the moves played: the one played without a
+    // mistake least often, then the one drilled least.
+    fn get_target_line(&self) -> Option<&Vec<BoardMove>> {
+        self.repertoire.get_lines().iter()
+            .filter(|line| line.len() > self.played.len() && line.starts_with(&self.played))
+            .min_by(|a, b| {
+                let (a, b) = (self.get_line_record(a), self.get_line_record(b));
+                a.get_success_rate().total_cmp(&b.get_success_rate()).then(a.get_attempts().cmp(&b.get_attempts()))
+            })
+    }
+
+    fn get_line_record(&self, line: &[BoardMove]) -> LineRecord {
+        self.record.get(&self.name, &format_line(line))
+    }
+
+    // Plays the opponent's move of the line being drilled, when it is the opponent's turn.
+    fn play_opponent_move(&mut self) -> Option<ChessMove> {
+        if self.board.get_turn() == self.team {
+            return None;
+        }
+        let board_move = *self.get_target_line()?.get(self.played.len())?;
+        Some(self.play(board_move))
+    }
+
+    fn play(&mut self, board_move: BoardMove) -> ChessMove {
+        let chess_move = self.board.to_chess_move(board_move);
+        self.board.make_move(board_move);
+        self.played.push(board_move);
+        self.wrong_moves = 0;
+        chess_move
+    }
+
+    // Whether the moves played reach the end of a line.
+    fn is_line_finished(&self) -> bool {
+        self.repertoire.get_replies(&self.played).is_empty()
+    }
+
+    // The success rate of every line, e.g. "  75% (3 of 4) 1. e4 c5 2. Nf3".
+    fn describe_lines(&self) -> String {
+        let lines: Vec<String> = self.repertoire.get_lines().iter()
+            .map(|line| {
+                let record = self.get_line_record(line);
+                format!("  {:>3}% ({} of {}) {}", (record.get_success_rate() * 100.0).round(), record.get_successes(),
+                    record.get_attempts(), format_line(line))
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+impl TuiGame {
+    // Reads a repertoire and starts drilling its lines, with the player on the side given.
+    pub(super) fn start_training(&mut self, file_path: &Path, black: bool) {
+        let repertoire = fs::read_to_string(file_path).map_err(|e| e.to_string())
+            .and_then(|text| parse_pgn_games(&text).map_err(|e| e.to_string()))
+            .and_then(|games| Repertoire::from_games(&games).map_err(|e| e.to_string()));
+        let repertoire = match repertoire {
+            Ok(r) => r,
+            Err(e) => {
+                self.say(self.locale.unreadable_repertoire(&file_path.display().to_string(), &e));
+                return;
+            }
+        };
+        // Lines are recorded under the full path of the file, however it was typed.
+        let name = fs::canonicalize(file_path).unwrap_or(file_path.to_path_buf()).display().to_string();
+        let record = match self.training_path.as_deref().map(TrainingRecord::load) {
+            Some(Ok(record)) => record,
+            Some(Err(e)) => {
+                self.say(self.locale.unreadable_training_record(&e.to_string()));
+                self.training_path = None;
+                TrainingRecord::new()
+            }
+            None => TrainingRecord::new(),
+        };
+
+        self.stop_clock();
+        let team = if black { Team::Dark } else { Team::Light };
+        let trainer = OpeningTrainer::new(name, repertoire, record, team);
+        let mut message = self.locale.training_started(trainer.repertoire.get_lines().len(), &file_path.display().to_string(), team);
+        if let Some(opening_move) = trainer.played.first().map(|m| Board::new().to_chess_move(*m)) {
+            message.push_str(&format!("\n{}", self.locale.opponent_plays(&opening_move.to_string())));
+        }
+        self.say(message);
+        self.drill = Some(Drill::Opening(trainer));
+    }
+
+    pub(super) fn stop_training(&mut self) {
+        match self.drill.take() {
+            Some(Drill::Opening(trainer)) => {
+                self.say(self.locale.stopped_training(trainer.known, trainer.finished, &trainer.describe_lines()));
+            }
+            drill => {
+                self.drill = drill;
+                self.say(String::from(self.locale.no_training()));
+            }
+        }
+    }
+
+    fn get_trainer(&mut self) -> Option<&mut OpeningTrainer> {
+        match &mut self.drill {
+            Some(Drill::Opening(trainer)) => Some(trainer),
+            _ => None,
+        }
+    }
+
+    // Plays the player's move if the repertoire has it, followed by the opponent's answer.
+    pub(super) fn play_training_move(&mut self, typed: &str) {
+        let locale = self.locale;
+        let trainer = match self.get_trainer() {
+            Some(t) => t,
+            None => return,
+        };
+        let typed = typed.trim();
+        let board_move = match ChessMove::from(typed)
+            .map_err(|e| locale.invalid_move(typed, &e.to_string()))
+            .and_then(|m| trainer.board.resolve_move(&m).map_err(|e| locale.illegal_move(typed, &e.to_string())))
+        {
+            Ok(m) => m,
+            Err(message) => {
+                self.say(message);
+                return;
+            }
+        };
+
+        let replies = trainer.repertoire.get_replies(&trainer.played);
+        if !replies.contains(&board_move) {
+            trainer.missed = true;
+            trainer.wrong_moves += 1;
+            let wrong = trainer.board.to_chess_move(board_move);
+            // The answer is given after the second try.
+            let message = if trainer.wrong_moves < 2 {
+                locale.not_in_repertoire(&wrong.to_string(), None)
+            }
+            else {
+                let answers: Vec<String> = replies.iter().map(|m| trainer.board.to_chess_move(*m).to_string()).collect();
+                locale.not_in_repertoire(&wrong.to_string(), Some(&answers))
+            };
+            self.say(message);
+            return;
+        }
+
+        let played = trainer.play(board_move);
+        let mut message = locale.correct(&played.to_string());
+        if let Some(answer) = trainer.play_opponent_move() {
+            message.push_str(&format!(" {}", locale.opponent_plays(&answer.to_string())));
+        }
+        if trainer.is_line_finished() {
+            let line = format_line(&trainer.played);
+            let record = trainer.record.record_attempt(&trainer.name, &line, !trainer.missed);
+            trainer.finished += 1;
+            if !trainer.missed {
+                trainer.known += 1;
+            }
+            message.push_str(&format!("\n{}", locale.end_of_line(&line, record.get_successes(), record.get_attempts())));
+            if let Some(opening_move) = trainer.start_line() {
+                message.push_str(&format!(" {}", locale.opponent_plays(&opening_move.to_string())));
+            }
+        }
+        self.say(message);
+        self.save_training_record();
+    }
+
+    fn save_training_record(&mut self) {
+        let (Some(path), Some(trainer)) = (self.training_path.clone(), self.get_trainer()) else {
+            return;
+        };
+        if let Err(e) = trainer.record.save(&path) {
+            self.say(self.locale.training_record_not_saved(&e.to_string()));
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_opening {
+    use std::io::Write;
+    use super::*;
+
+    fn write_repertoire(name: &str, pgn: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("rust_chess_{}_{name}.pgn", std::process::id()));
+        fs::File::create(&path).unwrap().write_all(pgn.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    pub fn the_repertoire_move_is_required() {
+        let path = write_repertoire("white", "1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 3. Bb5 *");
+        let mut game = TuiGame::new();
+        game.run_command_line(&format!("train-opening {}", path.display()));
+        assert!(game.take_messages()[0].starts_with("Training the 2 lines of "));
+
+        game.run_command_line("move d4");
+        assert_eq!(game.take_messages()[0], "d4 isn't in the repertoire here. Try again.");
+        game.run_command_line("move c4");
+        assert_eq!(game.take_messages()[0], "c4 isn't in the repertoire here, it plays e4. Try again.");
+        game.run_command_line("undo");
+        assert_eq!(game.take_messages()[0], "Opening training is running, use stop to leave it first.");
+
+        // The line drilled first is the first one never drilled, the Sicilian.
+        game.run_command_line("move e4");
+        assert_eq!(game.take_messages()[0], "Correct, e4! The opponent plays c5.");
+        game.run_command_line("move Nf3");
+        assert_eq!(game.take_messages()[0],
+            "Correct, Nf3!\nEnd of the line 1. e4 c5 2. Nf3, played without a mistake 0 of 1 times.\nNext line.");
+        assert!(game.render().contains("as White: 0 of 1 lines without a mistake"));
+
+        // Then the line not yet drilled.
+        game.run_command_line("move e4");
+        assert_eq!(game.take_messages()[0], "Correct, e4! The opponent plays e5.");
+        game.run_command_line("move Nf3");
+        game.run_command_line("move Bb5");
+        assert!(game.take_messages()[1].starts_with("Correct, Bb5!\nEnd of the line 1. e4 e5 2. Nf3 Nc6 3. Bb5, played without a mistake 1 of 1 times."));
+
+        game.run_command_line("stop");
+        assert_eq!(game.take_messages()[0], "Stopped the training, 1 of 2 lines played without a mistake. How well each line is known:\n\
+            \x20   0% (0 of 1) 1. e4 c5 2. Nf3\n\
+            \x20 100% (1 of 1) 1. e4 e5 2. Nf3 Nc6 3. Bb5");
+        assert!(game.drill.is_none());
+        assert_eq!(game.state.get_ply_count(), 0);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    pub fn the_opponent_opens_when_training_black() {
+        let path = write_repertoire("black", "1. d4 (1. e4 c5) 1... Nf6 (1... d5) 2. c4 *");
+        let mut game = TuiGame::new();
+        game.run_command_line(&format!("train-opening {} --black", path.display()));
+        assert!(game.take_messages()[0].ends_with("\nThe opponent plays e4."));
+        game.run_command_line("move c5");
+        assert_eq!(game.take_messages()[0],
+            "Correct, c5!\nEnd of the line 1. e4 c5, played without a mistake 1 of 1 times.\nNext line. The opponent plays d4.");
+        game.run_command_line("move d5");
+        assert_eq!(game.take_messages()[0],
+            "Correct, d5!\nEnd of the line 1. d4 d5, played without a mistake 1 of 1 times.\nNext line. The opponent plays d4.");
+        game.run_command_line("move Nf6");
+        assert!(game.take_messages()[0].starts_with("Correct, Nf6! The opponent plays c4.\nEnd of the line 1. d4 Nf6 2. c4,"));
+        fs::remove_file(path).unwrap();
+
+        game.run_command_line("stop");
+        game.run_command_line("train-opening missing.pgn");
+        assert!(game.take_messages()[1].starts_with("Could not read the repertoire missing.pgn: "));
+    }
+}
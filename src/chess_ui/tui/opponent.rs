@@ -0,0 +1,404 @@
+/*
+opponent.rs
+Playing against the computer. The computer answers each move as soon as it is made, with the
+configured UCI engine or, when there is none, the built-in engine.
+
+With pondering on, the computer keeps thinking while it is the player's turn, about the position
+after the answer it expects. When the player makes that move (a ponder hit) the search goes on
+from where it is, so the computer answers sooner or with a deeper search than otherwise. Any other
+move, or taking moves back, cancels the pondering and the computer searches afresh.
+*/
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chess_core::{Board, BoardMove, Team},
+    chess_engine::{self, ENGINE_NAME},
+    chess_game::GameState,
+    chess_pgn::PgnAnnotation,
+    chess_uci::{SearchLimit, SearchResult, UciEngine, UciError},
+};
+use super::TuiGame;
+
+// How long the built-in engine thinks per move unless told otherwise. The configured depth is
+// meant for external engines, which search far deeper in the same time.
+const BUILT_IN_MOVE_TIME: Duration = Duration::from_millis(1000);
+// How often the built-in engine's pondering is checked after a ponder hit.
+const PONDER_POLL: Duration = Duration::from_millis(5);
+
+pub struct Opponent {
+    // The side the computer plays.
+    team: Team,
+    engine: OpponentEngine,
+    limit: SearchLimit,
+    pondering: Option<Ponder>,
+}
+
+enum OpponentEngine {
+    BuiltIn,
+    Uci(UciEngine),
+}
+
+// Thinking on the player's time about the position after the move the computer expects.
+struct Ponder {
+    // The position the player moves from, and the one after the move expected.
+    before: Board,
+    after: Board,
+    search: PonderSearch,
+}
+
+enum PonderSearch {
+    // The search runs on a thread of its own, reporting every depth it completes.
+    BuiltIn {
+        stop: Arc<AtomicBool>,
+        results: Receiver<SearchResult>,
+        thread: Option<JoinHandle<()>>,
+        started: Instant,
+    },
+    // The UCI engine ponders by itself.
+    Uci,
+}
+
+impl Drop for PonderSearch {
+    fn drop(&mut self) {
+        if let PonderSearch::BuiltIn { stop, thread, .. } = self {
+            stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Opponent {
+    pub fn get_name(&self) -> &str {
+        match &self.engine {
+            OpponentEngine::BuiltIn => ENGINE_NAME,
+            OpponentEngine::Uci(engine) => engine.get_name(),
+        }
+    }
+
+    // The computer's name with how long it searches, e.g. "rust-chess at 1000 ms", under which
+    // it is rated. Each level has a rating of its own.
+    pub fn get_level_name(&self) -> String {
+        match self.limit {
+            SearchLimit::Depth(depth) => format!("{} at depth {depth}", self.get_name()),
+            SearchLimit::MoveTime(time) => format!("{} at {} ms", self.get_name(), time.as_millis()),
+            SearchLimit::Infinite => String::from(self.get_name()),
+        }
+    }
+
+    // The side the computer plays.
+    pub fn get_team(&self) -> Team {
+        self.team
+    }
+
+    // The UCI engine playing, None for the built-in engine.
+    pub fn get_engine_path(&self) -> Option<&PathBuf> {
+        match &self.engine {
+            OpponentEngine::BuiltIn => None,
+            OpponentEngine::Uci(engine) => Some(engine.get_path()),
+        }
+    }
+
+    pub fn get_limit(&self) -> SearchLimit {
+        self.limit
+    }
+
+    fn search(&mut self, state: &GameState) -> Result<Option<SearchResult>, UciError> {
+        if let Some(ponder) = self.pondering.take() {
+            if ponder.after != *state.get_board() {
+                self.cancel_ponder(ponder)?;
+            }
+            else if let Some(result) = self.ponder_hit(ponder)? {
+                return Ok(Some(result));
+            }
+        }
+        match &mut self.engine {
+            OpponentEngine::BuiltIn => Ok(chess_engine::search(state.get_board(), self.limit)),
+            OpponentEngine::Uci(engine) => engine.search(&state.get_board_moves(), self.limit).map(Some),
+        }
+    }
+
+    // Starts thinking about the position after the expected answer to the computer's move.
+    fn start_ponder(&mut self, state: &GameState, expected: BoardMove) -> Result<(), UciError> {
+        let before = state.get_board().clone();
+        if !before.is_legal_move(expected) {
+            return Ok(());
+        }
+        let mut after = before.clone();
+        after.make_move(expected);
+        if after.legal_moves().is_empty() {
+            return Ok(());
+        }
+        let search = match &mut self.engine {
+            OpponentEngine::BuiltIn => {
+                let stop = Arc::new(AtomicBool::new(false));
+                let (sender, results) = mpsc::channel();
+                let board = after.clone();
+                let stopped = stop.clone();
+                let thread = thread::spawn(move || {
+                    chess_engine::search_until_stopped(&board, SearchLimit::Infinite, &stopped, |result, _| {
+                        let _ = sender.send(result.clone());
+                    });
+                });
+                PonderSearch::BuiltIn { stop, results, thread: Some(thread), started: Instant::now() }
+            }
+            OpponentEngine::Uci(engine) => {
+                let mut moves = state.get_board_moves();
+                moves.push(expected);
+                engine.start_ponder(&moves, self.limit)?;
+                PonderSearch::Uci
+            }
+        };
+        self.pondering = Some(Ponder { before, after, search });
+        Ok(())
+    }
+
+    // The player made the expected move. The search goes on until its limit, for the built-in
+    // engine counted from when the pondering started, and its result is the computer's move.
+    // None when the pondering came to nothing and the position has to be searched afresh.
+    fn ponder_hit(&mut self, mut ponder: Ponder) -> Result<Option<SearchResult>, UciError> {
+        match (&mut ponder.search, &mut self.engine) {
+            (PonderSearch::BuiltIn { results, thread, started, .. }, _) => {
+                let mut latest: Option<SearchResult> = None;
+                loop {
+                    latest = results.try_iter().last().or(latest);
+                    let done = match self.limit {
+                        SearchLimit::Depth(depth) => latest.as_ref().and_then(|r| r.get_depth()).is_some_and(|d| d >= depth),
+                        SearchLimit::MoveTime(time) => started.elapsed() >= time,
+                        SearchLimit::Infinite => false,
+                    };
+                    // The search also ends by itself once it finds a mate.
+                    if done || thread.as_ref().is_some_and(|t| t.is_finished()) {
+                        latest = results.try_iter().last().or(latest);
+                        break;
+                    }
+                    thread::sleep(PONDER_POLL);
+                }
+                Ok(latest)
+            }
+            (PonderSearch::Uci, OpponentEngine::Uci(engine)) => {
+                engine.ponder_hit()?;
+                engine.wait_search()
+            }
+            (PonderSearch::Uci, OpponentEngine::BuiltIn) => Ok(None),
+        }
+    }
+
+    // Ends the pondering, the search's result is of no use.
+    fn cancel_ponder(&mut self, ponder: Ponder) -> Result<(), UciError> {
+        if let (PonderSearch::Uci, OpponentEngine::Uci(engine)) = (&ponder.search, &mut self.engine) {
+            engine.stop_search()?;
+            engine.wait_search()?;
+        }
+        Ok(())
+    }
+
+    // Stops pondering, e.g. when the game is set aside.
+    pub fn stop_pondering(&mut self) -> Result<(), UciError> {
+        match self.pondering.take() {
+            Some(ponder) => self.cancel_ponder(ponder),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TuiGame {
+    // Starts a UCI engine to play against, pondering if the config says so.
+    fn start_opponent_engine(&self, path: &Path) -> Result<UciEngine, UciError> {
+        let ponder = self.config.get_ponder();
+        UciEngine::start(path).and_then(|mut e| {
+            // Engines only ponder when told they may.
+            if ponder {
+                e.set_option("Ponder", "true")?;
+            }
+            e.new_game().map(|()| e)
+        })
+    }
+
+    pub(super) fn start_single_player(&mut self, play_black: bool, movetime: Option<u64>) {
+        let movetime = movetime.map(|ms| SearchLimit::MoveTime(Duration::from_millis(ms)));
+        let (engine, limit) = match self.config.get_engine_path() {
+            Some(path) => {
+                match self.start_opponent_engine(path) {
+                    Ok(e) => (OpponentEngine::Uci(e), movetime.unwrap_or(SearchLimit::Depth(self.config.get_engine_depth()))),
+                    Err(e) => {
+                        self.say(self.locale.engine_not_started(&e.to_string()));
+                        return;
+                    }
+                }
+            }
+            None => (OpponentEngine::BuiltIn, movetime.unwrap_or(SearchLimit::MoveTime(BUILT_IN_MOVE_TIME))),
+        };
+
+        self.new_game();
+        let player = if play_black { Team::Dark } else { Team::Light };
+        let opponent = Opponent { team: player.opposite(), engine, limit, pondering: None };
+        let name = String::from(opponent.get_name());
+        let pgn_game = self.state.get_pgn_game_mut();
+        if play_black {
+            if let Some(player_name) = self.config.get_player_name() {
+                pgn_game.set_black(player_name.clone());
+            }
+            pgn_game.set_white(name.clone());
+        }
+        else {
+            pgn_game.set_black(name.clone());
+        }
+        self.say(self.locale.playing_against(player, &name));
+        self.opponent = Some(opponent);
+    }
+
+    // Brings back the computer of a saved session, playing the same side at the same strength.
+    // The game goes on without it when its engine can't be started.
+    pub(super) fn resume_opponent(&mut self, team: Team, engine_path: Option<&Path>, limit: SearchLimit) {
+        let engine = match engine_path {
+            Some(path) => match self.start_opponent_engine(path) {
+                Ok(e) => OpponentEngine::Uci(e),
+                Err(e) => {
+                    self.say(self.locale.opponent_not_resumed(&path.display().to_string(), &e.to_string()));
+                    return;
+                }
+            },
+            None => OpponentEngine::BuiltIn,
+        };
+        self.opponent = Some(Opponent { team, engine, limit, pondering: None });
+    }
+
+    // Starts pondering on the move the computer expects in answer, when pondering is on.
+    fn start_pondering(&mut self, expected: Option<BoardMove>) {
+        let (opponent, expected) = match (&mut self.opponent, expected) {
+            (Some(o), Some(m)) if self.config.get_ponder() && !self.state.get_pgn_game().is_finished() => (o, m),
+            _ => return,
+        };
+        if let Err(e) = opponent.start_ponder(&self.state, expected) {
+            let name = String::from(opponent.get_name());
+            self.opponent = None;
+            self.say(self.locale.opponent_failed(&name, &e.to_string()));
+        }
+    }
+
+    // Lets the computer move when it is its turn in the game.
+    pub(super) fn play_opponent_move(&mut self) {
+        let opponent = match &mut self.opponent {
+            Some(o) => o,
+            None => return,
+        };
+        let board = self.state.get_board();
+        // Pondering is of no use once the game left the position, e.g. by taking moves back.
+        let left = opponent.pondering.as_ref().is_some_and(|p| p.before != *board && p.after != *board);
+        if left {
+            if let Err(e) = opponent.stop_pondering() {
+                let name = String::from(opponent.get_name());
+                self.opponent = None;
+                self.say(self.locale.opponent_failed(&name, &e.to_string()));
+                return;
+            }
+        }
+        if board.get_turn() != opponent.team || self.state.is_viewing_history() || self.state.get_pgn_game().is_finished() {
+            return;
+        }
+
+        let name = String::from(opponent.get_name());
+        match opponent.search(&self.state) {
+            Ok(Some(result)) => match self.state.play_board_move(result.get_best_move(), PgnAnnotation::new()) {
+                Ok(played) => {
+                    self.say(self.locale.opponent_played(&name, &played.to_string()));
+                    self.alert_move(&name, &played);
+                    self.update_clock(true);
+                    self.start_pondering(result.get_ponder_move());
+                }
+                Err(e) => {
+                    self.opponent = None;
+                    self.say(self.locale.opponent_move_failed(&name, &result.get_best_move().to_string(), &e.to_string()));
+                }
+            },
+            // Checkmate or stalemate, which the status shows.
+            Ok(None) => (),
+            Err(e) => {
+                self.opponent = None;
+                self.say(self.locale.opponent_failed(&name, &e.to_string()));
+            }
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_opponent {
+    use super::*;
+
+    #[test]
+    pub fn computer_answers_moves() {
+        let mut game = TuiGame::new();
+        game.run_command_line("new --vs-engine --movetime 20");
+        assert_eq!(game.take_messages(), vec![format!("Playing White against {ENGINE_NAME}, undo 2 takes back your last move.")]);
+        assert_eq!(game.state.get_pgn_game().get_black(), ENGINE_NAME);
+
+        game.run_command_line("move e4");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "Played e4");
+        assert!(messages[1].starts_with(&format!("{ENGINE_NAME} played ")));
+        assert_eq!(game.state.get_ply_count(), 2);
+    }
+
+    #[test]
+    pub fn computer_moves_first_as_white() {
+        let mut game = TuiGame::new();
+        game.run_command_line("new --vs-engine --black --movetime 20");
+        assert_eq!(game.state.get_ply_count(), 1);
+        assert_eq!(game.state.get_pgn_game().get_white(), ENGINE_NAME);
+
+        // A plain new game is played without the computer.
+        game.run_command_line("new");
+        game.run_command_line("move e4");
+        assert_eq!(game.state.get_ply_count(), 1);
+        assert!(game.opponent.is_none());
+    }
+
+    #[test]
+    pub fn computer_ponders_on_the_expected_move() {
+        let mut game = TuiGame::new();
+        game.config.set("ponder", "on").unwrap();
+        game.run_command_line("new --vs-engine --black --movetime 50");
+        let expected = {
+            let ponder = game.opponent.as_ref().unwrap().pondering.as_ref().expect("no pondering");
+            assert_eq!(ponder.before, *game.state.get_board());
+            ponder.before.legal_moves().into_iter().find(|m| {
+                let mut board = ponder.before.clone();
+                board.make_move(*m);
+                board == ponder.after
+            }).unwrap()
+        };
+
+        // The expected move is a ponder hit, the computer answers and ponders again.
+        let expected = game.state.get_board().to_chess_move(expected);
+        game.run_command_line(&format!("move {expected}"));
+        assert_eq!(game.state.get_ply_count(), 3);
+        assert!(game.opponent.as_ref().unwrap().pondering.is_some());
+
+        // Taking the moves back cancels it.
+        game.run_command_line("undo 2");
+        assert!(game.opponent.as_ref().unwrap().pondering.is_none());
+        game.run_command_line(&format!("move {expected}"));
+        assert_eq!(game.state.get_ply_count(), 3);
+    }
+
+    #[test]
+    pub fn pondering_is_off_by_default() {
+        let mut game = TuiGame::new();
+        game.run_command_line("new --vs-engine --black --movetime 20");
+        assert!(game.opponent.as_ref().unwrap().pondering.is_none());
+    }
+}
@@ -0,0 +1,54 @@
+/*
+prompt.rs
+The >> prompt of line mode at a terminal, read with rustyline. The line can be edited with the
+arrow keys, Home, End and the usual Emacs keys, Up and Down go through the commands typed before
+and Ctrl-R searches them. The commands are kept in history.txt in the data directory, so they are
+there again in the next session. Ctrl-C and Ctrl-D end the input, as the end of a file does.
+*/
+
+use std::{fs, path::PathBuf};
+
+use rustyline::{Config, DefaultEditor, ExternalPrinter};
+
+const PROMPT: &str = ">> ";
+const HISTORY_SIZE: usize = 1000;
+
+// Prints messages above the prompt while a line is being typed, keeping what was typed so far.
+pub type PromptPrinter = Box<dyn ExternalPrinter + Send>;
+
+pub struct LinePrompt {
+    editor: DefaultEditor,
+    history_path: Option<PathBuf>,
+}
+
+impl LinePrompt {
+    // The prompt with the history of earlier sessions, and the printer for messages shown while it
+    // waits. None when the terminal can't be set up for editing.
+    pub fn new(history_path: Option<PathBuf>) -> Option<(LinePrompt, PromptPrinter)> {
+        let config = Config::builder()
+            .max_history_size(HISTORY_SIZE).ok()?
+            .history_ignore_dups(true).ok()?
+            .auto_add_history(true)
+            .build();
+        let mut editor = DefaultEditor::with_config(config).ok()?;
+        if let Some(path) = &history_path {
+            // There is no history yet the first time.
+            let _ = editor.load_history(path);
+        }
+        let printer = editor.create_external_printer().ok()?;
+        Some((LinePrompt { editor, history_path }, Box::new(printer)))
+    }
+
+    // Reads a line, adding it to the history file straight away so it is kept however the
+    // program ends. None when the input ends.
+    pub fn read_line(&mut self) -> Option<String> {
+        let line = self.editor.readline(PROMPT).ok()?;
+        if let Some(path) = &self.history_path {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let _ = self.editor.append_history(path);
+        }
+        Some(line)
+    }
+}
@@ -0,0 +1,132 @@
+/*
+rating.rs
+Rating the games played at the terminal. A game is rated when it ends, if both players are
+known: the configured player_name, the other player named with new --opponent, or the computer
+at its level. The rating changes are shown and the ratings written to the ratings file.
+*/
+
+use crate::{
+    chess_config::ChessConfig,
+    chess_core::Team,
+    chess_pgn::PgnResult,
+    chess_rating::{RatingList, INITIAL_RATING},
+};
+use super::TuiGame;
+
+// Reads the ratings kept in the configured ratings file, or the default one. When the file can't
+// be read the ratings aren't kept this session, so the file isn't overwritten.
+pub fn load_ratings(game: &mut TuiGame) {
+    let path = match game.config.get_ratings_path().cloned().or_else(ChessConfig::default_ratings_path) {
+        Some(p) => p,
+        None => return,
+    };
+    match RatingList::load(&path) {
+        Ok(ratings) => {
+            game.ratings = ratings;
+            game.ratings_path = Some(path);
+        }
+        Err(e) => game.say(game.locale.unreadable_ratings(&e.to_string())),
+    }
+}
+
+impl TuiGame {
+    // Rates the game that just ended and tells how the ratings changed.
+    pub(super) fn rate_game(&mut self, result: PgnResult) {
+        let (white, black) = match (self.get_rated_name(Team::Light), self.get_rated_name(Team::Dark)) {
+            (Some(w), Some(b)) => (w, b),
+            _ => return,
+        };
+        let (white_change, black_change) = match self.ratings.record_game(&white, &black, result) {
+            Some(changes) => changes,
+            None => return,
+        };
+        self.say(self.locale.rating_changes(&white, &white_change.to_string(), &black, &black_change.to_string()));
+        if let Some(path) = &self.ratings_path {
+            if let Err(e) = self.ratings.save(path) {
+                self.say(self.locale.ratings_not_saved(&e.to_string()));
+            }
+        }
+    }
+
+    // The name a side is rated under, None when the player isn't known.
+    fn get_rated_name(&self, team: Team) -> Option<String> {
+        if let Some(opponent) = self.opponent.as_ref().filter(|o| o.get_team() == team) {
+            return Some(opponent.get_level_name());
+        }
+        let pgn_game = self.state.get_pgn_game();
+        let name = match team {
+            Team::Light => pgn_game.get_white(),
+            Team::Dark => pgn_game.get_black(),
+        };
+        Some(name.clone()).filter(|n| !n.is_empty() && n != "?")
+    }
+
+    // Lists the rated players, or shows one player's rating.
+    pub(super) fn show_ratings(&mut self, player: Option<String>) {
+        let mut lines = Vec::new();
+        match player {
+            Some(name) => {
+                let rating = self.ratings.get(&name);
+                if rating.get_games() == 0 {
+                    lines.push(format!("{name} has no rated games, and starts at {INITIAL_RATING:.0}."));
+                }
+                else {
+                    lines.push(format!("{name}: {}", describe_rating(rating.get_rating(), rating.get_games(), rating.is_provisional())));
+                }
+            }
+            None => {
+                let players = self.ratings.get_players();
+                if players.is_empty() {
+                    lines.push(String::from("No games have been rated yet."));
+                }
+                for (place, (name, rating)) in players.into_iter().enumerate() {
+                    lines.push(format!("{:>3}. {name}: {}", place + 1,
+                        describe_rating(rating.get_rating(), rating.get_games(), rating.is_provisional())));
+                }
+            }
+        }
+        if let Some(path) = &self.ratings_path {
+            lines.push(format!("Ratings are kept in {}", path.display()));
+        }
+        self.say(lines.join("\n"));
+    }
+}
+
+// e.g. "1532 after 12 games, provisional".
+fn describe_rating(rating: f64, games: u32, provisional: bool) -> String {
+    let games = if games == 1 { String::from("1 game") } else { format!("{games} games") };
+    let provisional = if provisional { ", provisional" } else { "" };
+    format!("{rating:.0} after {games}{provisional}")
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_rating_games {
+    use super::*;
+
+    #[test]
+    pub fn finished_games_between_known_players_are_rated_once() {
+        let mut game = TuiGame::new();
+        game.config.set("player_name", "Raul Rojas").unwrap();
+        game.run_command_line("new --opponent Magnus");
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            game.run_command_line(&format!("move {san}"));
+        }
+        let messages = game.take_messages();
+        assert_eq!(messages.last().unwrap(), "Rating changes: Raul Rojas 1500 -> 1480 (-20), Magnus 1500 -> 1520 (+20).");
+        game.run_command_line("undo");
+        game.run_command_line("move Qh4#");
+        assert!(!game.take_messages().iter().any(|m| m.starts_with("Rating changes")));
+
+        game.run_command_line("rating");
+        assert_eq!(game.take_messages()[0], "  1. Magnus: 1520 after 1 game, provisional\n  2. Raul Rojas: 1480 after 1 game, provisional");
+        game.run_command_line("rating Nobody");
+        assert_eq!(game.take_messages()[0], "Nobody has no rated games, and starts at 1500.");
+
+        // Without a name for Black there is nobody to rate against.
+        game.run_command_line("new");
+        game.run_command_line("adjudicate 1-0");
+        assert!(!game.take_messages().iter().any(|m| m.starts_with("Rating changes")));
+    }
+}
@@ -0,0 +1,148 @@
+/*
+recovery.rs
+Saves the game in progress when the interface panics, so a bug doesn't cost the game. A panic hook
+writes the game to crash-<time>.pgn in the recovery directory of the data directory and prints
+where it went. The file is PGN that load reads back, with the position as FEN and the moves taken
+back, which redo would have replayed, on the % lines before the game:
+
+% Rust Chess stopped on an unexpected error: index out of bounds (src/chess_ui/tui.rs:120:5)
+% Position: rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+% Taken back, replayed by redo: Nf3 Nc6
+[Event "?"]
+...
+
+The hook can't reach the game while the stack unwinds, so the interface keeps a copy of the text
+up to date after every command instead. Panics on other threads, e.g. an engine's, are left to
+the default hook.
+*/
+
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use crossterm::{
+    cursor::Show,
+    event::DisableMouseCapture,
+    execute,
+    terminal::{self, LeaveAlternateScreen},
+};
+
+use crate::chess_game::GameState;
+use super::locale::Locale;
+
+// The game as the recovery file has it, None when there is nothing to lose.
+static SNAPSHOT: Mutex<Option<String>> = Mutex::new(None);
+// The language the hook tells where the game went in.
+static LOCALE: Mutex<Locale> = Mutex::new(Locale::English);
+
+// Keeps the game and the interface's language for the panic hook.
+pub fn remember(state: &GameState, locale: Locale) {
+    let text = game_dump(state);
+    if let Ok(mut snapshot) = SNAPSHOT.lock() {
+        *snapshot = text;
+    }
+    if let Ok(mut current) = LOCALE.lock() {
+        *current = locale;
+    }
+}
+
+// The game with its position and the moves taken back, None for a game without moves.
+fn game_dump(state: &GameState) -> Option<String> {
+    let undone = state.get_undone_moves();
+    if state.get_ply_count() == 0 && undone.is_empty() {
+        return None;
+    }
+    let mut text = format!("% Position: {}\n", state.get_board().to_fen());
+    if !undone.is_empty() {
+        let moves: Vec<String> = undone.iter().map(|(m, _)| m.to_string()).collect();
+        text.push_str(&format!("% Taken back, replayed by redo: {}\n", moves.join(" ")));
+    }
+    text.push_str(&format!("{}\n", state.get_pgn_game()));
+    Some(text)
+}
+
+// Writes the game with what went wrong to a new file in the directory.
+fn write_dump(dir: &Path, dump: &str, error: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let path = dir.join(format!("crash-{seconds}.pgn"));
+    fs::write(&path, format!("% Rust Chess stopped on an unexpected error: {error}\n{dump}"))?;
+    Ok(path)
+}
+
+// The panic's message and where it happened, on one line.
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("panic"));
+    let message = message.lines().collect::<Vec<_>>().join(" ");
+    match info.location() {
+        Some(location) => format!("{message} ({location})"),
+        None => message,
+    }
+}
+
+// Installs the hook for panics on the calling thread, the one running the interface. The
+// recovery file goes to the directory given.
+pub fn install(dir: PathBuf) {
+    let interface_thread = thread::current().id();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().id() != interface_thread {
+            default_hook(info);
+            return;
+        }
+        // The full screen interface is left first, or the messages would go with its screen.
+        if io::stdout().is_terminal() && terminal::is_raw_mode_enabled().unwrap_or(false) {
+            let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen, Show);
+            let _ = terminal::disable_raw_mode();
+        }
+        default_hook(info);
+        // A panic while the copy is being made leaves it locked, there is nothing to save then.
+        let dump = match SNAPSHOT.try_lock() {
+            Ok(snapshot) => snapshot.clone(),
+            Err(_) => None,
+        };
+        if let Some(dump) = dump {
+            let locale = LOCALE.try_lock().map(|l| *l).unwrap_or_default();
+            match write_dump(&dir, &dump, &panic_message(info)) {
+                Ok(path) => eprintln!("{}", locale.crash_saved(&path.display().to_string())),
+                Err(e) => eprintln!("{}", locale.crash_not_saved(&dir.display().to_string(), &e.to_string())),
+            }
+        }
+    }));
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_recovery {
+    use super::*;
+    use crate::chess_pgn::{ChessMove, PgnGame};
+
+    #[test]
+    pub fn dumps_load_back_with_position_and_undone_moves() {
+        let mut state = GameState::new();
+        assert_eq!(game_dump(&state), None);
+        for san in ["e4", "e5", "Nf3", "Nc6"] {
+            state.play_move(&ChessMove::from(san).unwrap()).unwrap();
+        }
+        state.undo(2);
+        let dump = game_dump(&state).unwrap();
+        assert!(dump.starts_with("% Position: rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2\n\
+            % Taken back, replayed by redo: Nf3 Nc6\n["));
+
+        let dir = std::env::temp_dir().join(format!("rust_chess_recovery_{}", std::process::id()));
+        let path = write_dump(&dir, &dump, "something broke (src/main.rs:1:1)").unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("% Rust Chess stopped on an unexpected error: something broke (src/main.rs:1:1)\n% Position: "));
+        assert_eq!(PgnGame::from(&text).unwrap().get_ply_count(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
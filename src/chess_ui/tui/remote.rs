@@ -0,0 +1,252 @@
+/*
+remote.rs
+Serving the current game over WebSocket. The first client to connect plays the remote side,
+Black against the player at the terminal unless asked otherwise, or the player's side when the
+game is against the computer. Clients connecting while the seat is taken watch the game. Every
+client gets the game again whenever it changes.
+*/
+
+use crate::{
+    chess_core::Team,
+    chess_pgn::ChessMove,
+    chess_ws::{side_name, ClientId, ClientMessage, GameSnapshot, ServerMessage, WsEvent, WsServer},
+};
+use super::TuiGame;
+
+pub struct RemoteSession {
+    server: WsServer,
+    // The side the remote player takes when the computer isn't playing.
+    side: Team,
+    // The client playing the remote side, None while the seat is free.
+    player: Option<ClientId>,
+    // The game as last sent, it is sent again once it changes.
+    sent: Option<GameSnapshot>,
+}
+
+impl TuiGame {
+    pub(super) fn start_serving(&mut self, host: &str, port: u16, remote_white: bool) {
+        if let Some(remote) = &self.remote {
+            let address = remote.server.get_address();
+            self.say(self.locale.already_served(&format!("ws://{address}")));
+            return;
+        }
+        let server = match WsServer::start(&format!("{host}:{port}")) {
+            Ok(s) => s,
+            Err(e) => {
+                self.say(self.locale.cannot_serve(&format!("{host}:{port}"), &e.to_string()));
+                return;
+            }
+        };
+        let side = if remote_white { Team::Light } else { Team::Dark };
+        let address = server.get_address();
+        self.remote = Some(RemoteSession { server, side, player: None, sent: None });
+        let side = self.get_remote_side();
+        self.say(self.locale.serving_remote(&format!("ws://{address}"), side));
+    }
+
+    pub(super) fn stop_serving(&mut self) {
+        self.remote = None;
+        self.http = None;
+        self.say(String::from(self.locale.stopped_serving()));
+    }
+
+    // The side played over the network: the player's side against the computer, otherwise the
+    // side chosen when serving started.
+    pub(super) fn get_remote_side(&self) -> Team {
+        match (&self.opponent, &self.remote) {
+            (Some(opponent), _) => opponent.get_team().opposite(),
+            (None, Some(remote)) => remote.side,
+            (None, None) => Team::Dark,
+        }
+    }
+
+    // Whether it is the remote player's move, which the player at the terminal can't make.
+    pub(super) fn is_remote_turn(&self) -> bool {
+        self.remote.as_ref().is_some_and(|r| r.player.is_some())
+            && self.state.get_board().get_turn() == self.get_remote_side()
+            && !self.state.get_pgn_game().is_finished()
+    }
+
+    // Answers HTTP requests, takes in what WebSocket clients sent and sends them and the broadcast
+    // the game when it changed.
+    pub(super) fn advance_remote(&mut self) {
+        self.answer_http_requests();
+        self.advance_broadcast();
+        let events = match &mut self.remote {
+            Some(remote) => remote.server.poll(),
+            None => return,
+        };
+        for event in events {
+            self.handle_remote_event(event);
+        }
+        let snapshot = GameSnapshot::new(&self.state, self.clock.as_ref());
+        if let Some(remote) = &mut self.remote {
+            if !remote.sent.as_ref().is_some_and(|s| s.same_game(&snapshot)) {
+                remote.server.broadcast(&ServerMessage::State(snapshot.clone()));
+                remote.sent = Some(snapshot);
+            }
+        }
+    }
+
+    fn handle_remote_event(&mut self, event: WsEvent) {
+        let side = self.get_remote_side();
+        let remote = match &mut self.remote {
+            Some(r) => r,
+            None => return,
+        };
+        match event {
+            WsEvent::Connected(id) => {
+                let seated = remote.player.is_none();
+                if seated {
+                    remote.player = Some(id);
+                }
+                let side_taken = if seated { Some(side_name(side)) } else { None };
+                remote.server.send(id, &ServerMessage::Welcome { side: side_taken });
+                remote.server.send(id, &ServerMessage::State(GameSnapshot::new(&self.state, self.clock.as_ref())));
+                if seated {
+                    self.say(self.locale.remote_joined(side));
+                }
+            }
+            WsEvent::Disconnected(id) => {
+                if remote.player == Some(id) {
+                    remote.player = None;
+                    self.say(String::from(self.locale.remote_left()));
+                }
+            }
+            WsEvent::Message(id, ClientMessage::State) => {
+                remote.server.send(id, &ServerMessage::State(GameSnapshot::new(&self.state, self.clock.as_ref())));
+            }
+            WsEvent::Message(id, ClientMessage::Move { san }) => {
+                if remote.player != Some(id) {
+                    let message = String::from("Only the remote player can move, you are watching.");
+                    remote.server.send(id, &ServerMessage::Error { message });
+                }
+                else if self.state.get_board().get_turn() != side {
+                    let message = format!("It is {}'s move.", side.opposite());
+                    remote.server.send(id, &ServerMessage::Error { message });
+                }
+                else {
+                    self.play_remote_move(id, &san);
+                }
+            }
+        }
+    }
+
+    fn play_remote_move(&mut self, id: ClientId, san: &str) {
+        self.check_clock();
+        let played = ChessMove::from(san)
+            .map_err(|e| format!("Invalid move {san}: {e}."))
+            .and_then(|m| self.state.play_move(&m).map_err(|e| format!("Illegal move {san}: {e}.")));
+        match played {
+            Ok(played) => {
+                self.say(self.locale.remote_played(&played.to_string()));
+                self.alert_move("The remote player", &played);
+                self.update_clock(true);
+                // Against the computer it answers straight away.
+                self.play_opponent_move();
+                self.update_clock(false);
+                self.record_game_end();
+            }
+            Err(message) => {
+                if let Some(remote) = &self.remote {
+                    remote.server.send(id, &ServerMessage::Error { message });
+                }
+            }
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_remote {
+    use std::{
+        net::TcpStream,
+        thread,
+        time::{Duration, Instant},
+    };
+    use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+    use super::*;
+
+    type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+    fn connect(game: &mut TuiGame) -> Client {
+        let address = game.remote.as_ref().unwrap().server.get_address();
+        let (client, _) = tungstenite::connect(format!("ws://{address}")).unwrap();
+        client
+    }
+
+    // Advances the session until the client has a message, and returns it.
+    fn receive(game: &mut TuiGame, client: &mut Client) -> serde_json::Value {
+        if let MaybeTlsStream::Plain(stream) = client.get_mut() {
+            stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            assert!(Instant::now() < deadline, "no message from the server");
+            game.advance_remote();
+            match client.read() {
+                Ok(Message::Text(text)) => return serde_json::from_str(&text).unwrap(),
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(_)) => thread::sleep(Duration::from_millis(5)),
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    fn send(client: &mut Client, text: &str) {
+        client.send(Message::Text(String::from(text))).unwrap();
+    }
+
+    #[test]
+    pub fn remote_player_plays_the_other_side() {
+        let mut game = TuiGame::new();
+        game.run_command_line("serve --ws 0");
+        assert!(game.take_messages()[0].starts_with("Serving the game on ws://127.0.0.1:"));
+        let mut player = connect(&mut game);
+        assert_eq!(receive(&mut game, &mut player)["side"], "black");
+        assert_eq!(receive(&mut game, &mut player)["turn"], "white");
+        assert_eq!(game.take_messages(), vec!["A remote player joined and plays Black."]);
+
+        let mut spectator = connect(&mut game);
+        assert_eq!(receive(&mut game, &mut spectator)["side"], serde_json::Value::Null);
+        receive(&mut game, &mut spectator);
+
+        // The remote player waits for their turn.
+        send(&mut player, r#"{"type":"move","move":"e5"}"#);
+        assert_eq!(receive(&mut game, &mut player)["message"], "It is White's move.");
+        game.run_command_line("move e4");
+        let state = receive(&mut game, &mut player);
+        assert_eq!(state["moves"], serde_json::json!(["e4"]));
+        assert_eq!(receive(&mut game, &mut spectator)["turn"], "black");
+
+        // The player at the terminal waits for theirs.
+        game.run_command_line("move d4");
+        assert_eq!(game.take_messages(), vec!["Played e4", "It is Black's move, played over the network."]);
+        send(&mut spectator, r#"{"type":"move","move":"e5"}"#);
+        assert_eq!(receive(&mut game, &mut spectator)["message"], "Only the remote player can move, you are watching.");
+        send(&mut player, r#"{"type":"move","move":"Ke7"}"#);
+        assert!(receive(&mut game, &mut player)["message"].as_str().unwrap().starts_with("Illegal move Ke7: "));
+        send(&mut player, r#"{"type":"move","move":"e5"}"#);
+        assert_eq!(receive(&mut game, &mut player)["moves"], serde_json::json!(["e4", "e5"]));
+        assert_eq!(game.take_messages(), vec!["The remote player played e5"]);
+
+        game.run_command_line("stop");
+        assert_eq!(game.take_messages(), vec!["Stopped serving the game."]);
+        assert!(game.remote.is_none());
+    }
+
+    #[test]
+    pub fn remote_player_plays_the_computer() {
+        let mut game = TuiGame::new();
+        game.run_command_line("new --vs-engine --movetime 20");
+        game.run_command_line("serve --ws 0");
+        let mut player = connect(&mut game);
+        assert_eq!(receive(&mut game, &mut player)["side"], "white");
+        receive(&mut game, &mut player);
+        send(&mut player, r#"{"type":"move","move":"d4"}"#);
+        let state = receive(&mut game, &mut player);
+        assert_eq!(state["moves"].as_array().unwrap().len(), 2, "{state}");
+        assert_eq!(state["turn"], "white");
+    }
+}
@@ -0,0 +1,174 @@
+/*
+rules.rs
+The rules that surprise beginners, told by help rules <topic> with a small diagram of an example:
+castling, en passant, promotion, threefold repetition and the fifty move rule. The diagram is drawn
+by the board renderer in the game's piece style, without colors so it reads the same in the full
+screen message log, with the move of the example highlighted as a selection is.
+*/
+
+use crate::{chess_config::BoardSize, chess_core::{Board, BoardSquare}};
+use super::{locale::Locale, render_board, BoardView, TuiGame};
+
+struct RuleTopic {
+    name: &'static str,
+    // Other names the topic is found by.
+    aliases: &'static [&'static str],
+    fen: &'static str,
+    // The move of the example, highlighted on the diagram.
+    from: &'static str,
+    to: &'static [&'static str],
+    text: &'static str,
+    // The name and text told in German.
+    german_name: &'static str,
+    german_text: &'static str,
+}
+
+const TOPICS: [RuleTopic; 5] = [
+    RuleTopic {
+        name: "castling",
+        aliases: &["castle", "o o", "o o o"],
+        fen: "4k3/8/8/8/8/8/PPP2PPP/R3K2R w KQ - 0 1",
+        from: "e1",
+        to: &["c1", "g1"],
+        text: "Castling moves the king two squares toward a rook and that rook over to the square the king crossed, all in one move: O-O on the king's side, O-O-O on the queen's side. Neither the king nor the rook may have moved before, the squares between them must be empty, and the king may not be in check, cross a square the other side attacks or land in check. Here White can castle either way, with move O-O or move O-O-O.",
+        german_name: "rochade",
+        german_text: "Bei der Rochade zieht der König zwei Felder auf einen Turm zu und dieser Turm auf das Feld, das der König überquert hat, alles in einem Zug: O-O am Königsflügel, O-O-O am Damenflügel. Weder König noch Turm dürfen vorher gezogen haben, die Felder zwischen ihnen müssen leer sein, und der König darf nicht im Schach stehen, kein Feld überqueren, das die andere Seite angreift, und nicht ins Schach ziehen. Hier kann Weiß nach beiden Seiten rochieren, mit move O-O oder move O-O-O.",
+    },
+    RuleTopic {
+        name: "en passant",
+        aliases: &["ep", "passant"],
+        fen: "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        from: "e5",
+        to: &["d6"],
+        text: "A pawn that moves two squares from its starting square can be taken by an enemy pawn next to the square it lands on, as if it had moved only one: the capturing pawn goes to the square the pawn passed. The capture has to be made on the very next move or the chance is gone. Black has just played d7-d5, so White's pawn on e5 can take it en passant with move exd6.",
+        german_name: "en passant",
+        german_text: "Ein Bauer, der von seinem Ausgangsfeld zwei Felder vorrückt, kann von einem gegnerischen Bauern neben seinem Zielfeld geschlagen werden, als wäre er nur ein Feld gezogen: Der schlagende Bauer geht auf das Feld, das der Bauer überquert hat. Das Schlagen muss gleich im nächsten Zug geschehen, sonst ist die Gelegenheit vorbei. Schwarz hat gerade d7-d5 gespielt, also kann der weiße Bauer auf e5 en passant schlagen, mit move exd6.",
+    },
+    RuleTopic {
+        name: "promotion",
+        aliases: &["promote", "promoting"],
+        fen: "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1",
+        from: "b7",
+        to: &["b8"],
+        text: "A pawn that reaches the last rank becomes a queen, rook, bishop or knight of its color, whichever its player chooses, most often a queen. The piece is written after an equals sign: move b8=Q turns the pawn on b7 into a queen, move b8=N into a knight.",
+        german_name: "umwandlung",
+        german_text: "Ein Bauer, der die letzte Reihe erreicht, wird zu einer Dame, einem Turm, einem Läufer oder einem Springer seiner Farbe, ganz wie sein Spieler wählt, meist zu einer Dame. Die Figur wird nach einem Gleichheitszeichen geschrieben: move b8=Q wandelt den Bauern auf b7 in eine Dame um, move b8=N in einen Springer.",
+    },
+    RuleTopic {
+        name: "threefold repetition",
+        aliases: &["threefold", "repetition"],
+        fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 8 5",
+        from: "g1",
+        to: &["f3"],
+        text: "The game is drawn when the same position comes up for the third time with the same side to move, the same castling rights and the same en passant captures possible. The player to move claims the draw with claim draw, or with claim draw and the move that repeats the position; at the fifth time the game is drawn without a claim. Here the knights went out and back twice, 1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8, so the starting position is on the board for the third time and White can claim a draw.",
+        german_name: "dreifache stellungswiederholung",
+        german_text: "Die Partie ist remis, wenn dieselbe Stellung zum dritten Mal mit derselben Seite am Zug, denselben Rochaderechten und denselben möglichen En-passant-Schlägen entsteht. Der Spieler am Zug beansprucht das Remis mit claim draw, oder mit claim draw und dem Zug, der die Stellung wiederholt; beim fünften Mal ist die Partie ohne Antrag remis. Hier sind die Springer zweimal hinaus und zurück gezogen, 1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8, also steht die Ausgangsstellung zum dritten Mal auf dem Brett und Weiß kann Remis beanspruchen.",
+    },
+    RuleTopic {
+        name: "fifty move rule",
+        aliases: &["fifty move", "fifty", "50 move rule", "50 move", "50"],
+        fen: "8/8/4k3/8/8/3RK3/8/8 w - - 99 120",
+        from: "d3",
+        to: &["d8"],
+        text: "The game can be claimed drawn once fifty moves of each side have been played without a capture or a pawn move, with claim draw, or with claim draw and the move that completes them; after seventy-five moves the game is drawn without a claim. A side that can checkmate has to do it in time. Here nothing has been taken and no pawn moved for 99 half moves, so White can play claim draw Rd8, or checkmate with the rook and king before the count runs out.",
+        german_name: "50-züge-regel",
+        german_text: "Remis kann beansprucht werden, sobald jede Seite fünfzig Züge ohne Schlagen und ohne Bauernzug gespielt hat, mit claim draw, oder mit claim draw und dem Zug, der sie vollendet; nach fünfundsiebzig Zügen ist die Partie ohne Antrag remis. Eine Seite, die mattsetzen kann, muss es rechtzeitig tun. Hier wurde 99 Halbzüge lang nichts geschlagen und kein Bauer gezogen, also kann Weiß claim draw Rd8 spielen oder vorher mit Turm und König mattsetzen.",
+    },
+];
+
+impl TuiGame {
+    // Tells the rules of a topic, or the list of topics when none or an unknown one is given.
+    pub(super) fn show_rules(&mut self, topic: &[String]) {
+        let names: Vec<&str> = TOPICS.iter().map(|t| self.get_rule_name(t)).collect();
+        if topic.is_empty() {
+            self.say(self.locale.rules_topics(&names));
+            return;
+        }
+        // Topics are found by their name in either language.
+        let wanted = topic.join(" ").to_lowercase().replace(['-', '_'], " ");
+        let rule = match TOPICS.iter().find(|t| {
+            t.name == wanted || t.german_name.replace('-', " ") == wanted || t.aliases.contains(&wanted.as_str())
+        }) {
+            Some(r) => r,
+            None => {
+                self.say(self.locale.no_rules_topic(&topic.join(" "), &names));
+                return;
+            }
+        };
+        let board = Board::from_fen(rule.fen).expect("the rules examples are valid FEN");
+        let diagram = if self.config.get_screen_reader() {
+            board.describe_position()
+        } else {
+            let square = |s: &str| BoardSquare::from(s).expect("the rules examples are on the board");
+            let view = BoardView {
+                selected: Some(square(rule.from)),
+                destinations: rule.to.iter().map(|s| square(s)).collect(),
+                size: BoardSize::Normal,
+                shapes: false,
+                plain: true,
+                pieces: self.view.pieces,
+                wide_glyphs: self.view.wide_glyphs,
+                ..BoardView::default()
+            };
+            render_board(&board, &view).trim_start_matches('\n').to_string()
+        };
+        let text = match self.locale {
+            Locale::English => rule.text,
+            Locale::German => rule.german_text,
+        };
+        self.say(self.locale.rule(self.get_rule_name(rule), &diagram, text));
+    }
+
+    fn get_rule_name(&self, rule: &RuleTopic) -> &'static str {
+        match self.locale {
+            Locale::English => rule.name,
+            Locale::German => rule.german_name,
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_rules {
+    use super::*;
+
+    #[test]
+    pub fn topics_show_a_diagram_of_their_example() {
+        let mut game = TuiGame::new();
+        game.take_messages();
+        game.run_command_line("help rules en-passant");
+        let message = game.take_messages().remove(0);
+        assert!(message.starts_with("En passant\n"));
+        // The pawn taking and the square it goes to are marked.
+        assert!(message.contains("6  .  .  .  ·  .  .  .  . "));
+        assert!(message.contains("5  .  .  .  ♟ <♙> .  .  . "));
+        assert!(message.ends_with("with move exd6."));
+
+        game.run_command_line("help rules");
+        game.run_command_line("help rules stalemate");
+        assert_eq!(game.take_messages(), [
+            "Rules topics: castling, en passant, promotion, threefold repetition, fifty move rule. Type e.g. help rules castling.",
+            "There is no rules topic stalemate. The topics are: castling, en passant, promotion, threefold repetition, fifty move rule.",
+        ]);
+
+        game.run_command_line("config set language german");
+        game.take_messages();
+        game.run_command_line("help rules");
+        game.run_command_line("help rules rochade");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "Regelthemen: rochade, en passant, umwandlung, dreifache stellungswiederholung, 50-züge-regel. Gib z. B. help rules rochade ein.");
+        assert!(messages[1].starts_with("Rochade\n"));
+        assert!(messages[1].ends_with("mit move O-O oder move O-O-O."));
+    }
+
+    #[test]
+    pub fn examples_are_legal_positions() {
+        for topic in &TOPICS {
+            let board = Board::from_fen(topic.fen).unwrap();
+            let from = BoardSquare::from(topic.from).unwrap();
+            let destinations: Vec<BoardSquare> = board.legal_moves_from(from).iter().map(|m| m.get_to()).collect();
+            assert!(topic.to.iter().all(|s| destinations.contains(&BoardSquare::from(s).unwrap())), "{}", topic.name);
+        }
+    }
+}
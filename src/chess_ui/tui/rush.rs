@@ -0,0 +1,254 @@
+/*
+rush.rs
+Puzzle rush: as many puzzles as can be solved before the time is up, getting harder as they go.
+A wrong move ends the puzzle with a strike and the third strike ends the rush. The puzzles come
+from the local puzzle set, and the ones missed are shown with their solutions at the end.
+*/
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    chess_clock::format_clock_time,
+    chess_core::{Board, BoardMove},
+    chess_pgn::ChessMove,
+    chess_puzzle::{Puzzle, PuzzleSet},
+};
+use super::{Drill, TuiGame};
+
+const STRIKES: usize = 3;
+// Puzzles picked for a rush, more than can be solved in the time.
+const RUSH_PUZZLES: usize = 100;
+
+pub struct PuzzleRush {
+    puzzles: Vec<Puzzle>,
+    // The puzzle being solved, its position and the move of its solution to find next.
+    current: usize,
+    board: Board,
+    step: usize,
+    ends_at: Instant,
+    solved: u32,
+    // The puzzles got wrong, by their place in the rush.
+    missed: Vec<usize>,
+}
+
+impl PuzzleRush {
+    fn new(puzzles: Vec<Puzzle>, time: Duration, now: Instant) -> PuzzleRush {
+        let board = puzzles[0].get_board().clone();
+        PuzzleRush { puzzles, current: 0, board, step: 0, ends_at: now + time, solved: 0, missed: Vec::new() }
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    // e.g. "Puzzle rush: 4 solved, 1 of 3 strikes, 2:31 left".
+    pub fn get_title(&self) -> String {
+        let left = self.ends_at.saturating_duration_since(Instant::now());
+        format!("Puzzle rush: {} solved, {} of {STRIKES} strikes, {} left", self.solved, self.missed.len(),
+            format_clock_time(left))
+    }
+
+    fn get_puzzle(&self) -> &Puzzle {
+        &self.puzzles[self.current]
+    }
+
+    // e.g. "Puzzle 3, rated 750: Black played Nf6, find the best move for White."
+    fn describe(&self) -> String {
+        let puzzle = self.get_puzzle();
+        let turn = self.board.get_turn();
+        format!("Puzzle {}, rated {}: {} played {}, find the best move for {turn}.", self.current + 1,
+            puzzle.get_rating(), turn.opposite(), puzzle.get_opponent_move())
+    }
+
+    // Goes on to the next puzzle, returning false when there are none left.
+    fn advance(&mut self) -> bool {
+        if self.current + 1 >= self.puzzles.len() {
+            return false;
+        }
+        self.current += 1;
+        self.step = 0;
+        self.board = self.get_puzzle().get_board().clone();
+        true
+    }
+
+    // Plays a move of the puzzle and says how it went, along with why the rush ends when it does.
+    fn play(&mut self, board_move: BoardMove) -> (String, Option<&'static str>) {
+        let puzzle = &self.puzzles[self.current];
+        let mut message;
+        if puzzle.is_solution_move(&self.board, self.step, board_move) {
+            let played = self.board.to_chess_move(board_move);
+            self.board.make_move(board_move);
+            self.step += 1;
+            // The opponent's answer, unless the move ended the puzzle.
+            if let Some(&answer) = puzzle.get_solution().get(self.step).filter(|_| !self.board.is_checkmate()) {
+                let answer_move = self.board.to_chess_move(answer);
+                self.board.make_move(answer);
+                self.step += 1;
+                return (format!("Correct, {played}! The opponent plays {answer_move}."), None);
+            }
+            self.solved += 1;
+            message = format!("Solved, {played}! That makes {}.", self.solved);
+        }
+        else {
+            self.missed.push(self.current);
+            message = format!("Wrong, the solution was {}. Strike {} of {STRIKES}.", puzzle.format_solution(), self.missed.len());
+            if self.missed.len() >= STRIKES {
+                return (message, Some("three strikes"));
+            }
+        }
+        if !self.advance() {
+            return (message, Some("there are no puzzles left"));
+        }
+        message.push_str(&format!("\n{}", self.describe()));
+        (message, None)
+    }
+
+    // The end of the rush: the puzzles solved and the solutions of the ones missed.
+    fn summarize(&self, reason: &str) -> String {
+        let mut summary = format!("Puzzle rush over, {reason}. Your score is {}.", self.solved);
+        if !self.missed.is_empty() {
+            summary.push_str("\nMissed:");
+            for &missed in &self.missed {
+                let puzzle = &self.puzzles[missed];
+                summary.push_str(&format!("\n  Puzzle {}, rated {}: {}", missed + 1, puzzle.get_rating(), puzzle.format_solution()));
+            }
+        }
+        summary
+    }
+}
+
+impl TuiGame {
+    pub(super) fn start_rush(&mut self, minutes: u64) {
+        let puzzle_set = match self.puzzles_path.as_deref().map(PuzzleSet::load) {
+            Some(Ok(set)) => set,
+            Some(Err(e)) => {
+                self.say(self.locale.unreadable_puzzles(&e.to_string()));
+                return;
+            }
+            None => PuzzleSet::built_in(),
+        };
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos() as u64).unwrap_or_default();
+        let puzzles = puzzle_set.pick(RUSH_PUZZLES, seed);
+        if puzzles.is_empty() {
+            self.say(String::from(self.locale.no_puzzles()));
+            return;
+        }
+
+        self.stop_clock();
+        let rush = PuzzleRush::new(puzzles, Duration::from_secs(minutes * 60), Instant::now());
+        self.say(format!("{}\n{}", self.locale.rush_started(minutes, STRIKES), rush.describe()));
+        self.drill = Some(Drill::Rush(rush));
+    }
+
+    fn get_rush(&mut self) -> Option<&mut PuzzleRush> {
+        match &mut self.drill {
+            Some(Drill::Rush(rush)) => Some(rush),
+            _ => None,
+        }
+    }
+
+    // Ends the rush, with its summary.
+    pub(super) fn stop_rush(&mut self, reason: &str) {
+        if let Some(rush) = self.get_rush() {
+            let summary = rush.summarize(reason);
+            self.drill = None;
+            self.say(summary);
+        }
+    }
+
+    // Ends the rush once its time is up.
+    pub(super) fn check_rush_time(&mut self) {
+        if self.get_rush().is_some_and(|rush| Instant::now() >= rush.ends_at) {
+            self.stop_rush("the time is up");
+        }
+    }
+
+    // Plays a move of the puzzle. A right move is answered by the opponent until the puzzle is
+    // solved, a wrong one is a strike and moves on to the next puzzle.
+    pub(super) fn play_rush_move(&mut self, typed: &str) {
+        let locale = self.locale;
+        let rush = match self.get_rush() {
+            Some(r) => r,
+            None => return,
+        };
+        let typed = typed.trim();
+        let board_move = match ChessMove::from(typed)
+            .map_err(|e| locale.invalid_move(typed, &e.to_string()))
+            .and_then(|m| rush.board.resolve_move(&m).map_err(|e| locale.illegal_move(typed, &e.to_string())))
+        {
+            Ok(m) => m,
+            Err(message) => {
+                self.say(message);
+                return;
+            }
+        };
+
+        let (message, end) = rush.play(board_move);
+        self.say(message);
+        if let Some(reason) = end {
+            self.stop_rush(reason);
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_rush {
+    use super::*;
+
+    #[test]
+    pub fn puzzles_are_solved_until_three_strikes() {
+        let mut game = TuiGame::new();
+        game.run_command_line("puzzle-rush");
+        let messages = game.take_messages();
+        assert!(messages[0].starts_with("Solve as many puzzles as you can in 3 minutes"));
+        assert!(messages[0].ends_with("\nPuzzle 1, rated 600: Black played a6, find the best move for White."));
+        assert!(game.render().contains("Puzzle rush: 0 solved, 0 of 3 strikes, 3:00 left"));
+
+        game.run_command_line("move Qe8#");
+        assert_eq!(game.take_messages()[0],
+            "Solved, Qe8#! That makes 1.\nPuzzle 2, rated 650: White played a3, find the best move for Black.");
+        game.run_command_line("move Rd2");
+        assert!(game.take_messages()[0].starts_with("Wrong, the solution was Rd1#. Strike 1 of 3.\nPuzzle 3,"));
+        game.run_command_line("undo");
+        assert_eq!(game.take_messages()[0], "Puzzle rush is running, use stop to end it first.");
+        game.run_command_line("move Qh4#");
+        game.run_command_line("move Qxf7#");
+        game.run_command_line("move Kf7");
+        game.run_command_line("move Kh7");
+        game.take_messages();
+        game.run_command_line("move Qd5");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "Wrong, the solution was Qg7#. Strike 3 of 3.");
+        assert_eq!(messages[1], "Puzzle rush over, three strikes. Your score is 3.\nMissed:\n\
+            \x20 Puzzle 2, rated 650: Rd1#\n\
+            \x20 Puzzle 5, rated 800: a8=Q#\n\
+            \x20 Puzzle 6, rated 850: Qg7#");
+        assert!(game.drill.is_none());
+    }
+
+    #[test]
+    pub fn longer_puzzles_are_answered_and_time_runs_out() {
+        let mut game = TuiGame::new();
+        game.run_command_line("puzzle-rush --minutes 5");
+        game.take_messages();
+        if let Some(Drill::Rush(rush)) = &mut game.drill {
+            // Straight to the first mate in two.
+            while rush.get_puzzle().get_solution().len() < 3 {
+                rush.advance();
+            }
+        }
+        game.run_command_line("move Re8+");
+        assert_eq!(game.take_messages()[0], "Correct, Re8+! The opponent plays Rxe8.");
+        game.run_command_line("move Rxe8#");
+        assert!(game.take_messages()[0].starts_with("Solved, Rxe8#! That makes 1.\n"));
+
+        if let Some(Drill::Rush(rush)) = &mut game.drill {
+            rush.ends_at = Instant::now();
+        }
+        game.run_command_line("move e4");
+        assert_eq!(game.take_messages()[0], "Puzzle rush over, the time is up. Your score is 1.");
+        assert!(game.drill.is_none());
+    }
+}
@@ -0,0 +1,686 @@
+/*
+screen.rs
+Full screen terminal interface. Draws the board, the move list, a message log and an input line
+on the terminal's alternate screen and redraws what changed after every command or resize.
+Commands are the same ones the line by line interface reads. Where the terminal supports it,
+moves can also be made by clicking a piece and then one of its highlighted destinations, or
+without a mouse by moving a cursor over the board with the arrow keys (Tab switches between the
+board cursor and the input line). PageUp and PageDown step through the game, Ctrl-F flips the
+board and Ctrl-Z and Ctrl-Y undo and redo; these keys and the board cursor's can be changed in the
+[keys] section of the config, and the keys command lists them.
+
+Layout:
++------------------+-------------------+
+| board            | moves             |
++------------------+-------------------+
+| messages                             |
++--------------------------------------+
+| >> input                             |
+*/
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+use crossterm::{
+    cursor::{MoveTo, Show},
+    event::{
+        self,
+        DisableMouseCapture,
+        EnableMouseCapture,
+        Event,
+        KeyCode,
+        KeyEvent,
+        KeyEventKind,
+        KeyModifiers,
+        MouseButton,
+        MouseEventKind,
+    },
+    execute,
+    queue,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::{chess_common::ChessPiece, chess_config::{KeyAction, KeyMap}, chess_core::BoardSquare, chess_game::GameState};
+use super::{frame::Frame, frontend::{run_frontend, FrontendInput, UiFrontend}, format_annotated_move, BoardView, TuiGame, TERMINAL_COLOR_RESET};
+
+// Space needed next to the board for the move list, and above and below it for the captures,
+// the file labels, the status, the message log and the input line.
+const MOVES_PANE_MIN_WIDTH: u16 = 30;
+const MIN_ROWS_BESIDES_SQUARES: u16 = 12;
+const PROMPT: &str = ">> ";
+
+// Where the squares are in the board pane: below the captured pieces line and after the rank
+// label.
+const BOARD_TOP_ROW: u16 = 1;
+const BOARD_LEFT_COLUMN: u16 = 2;
+
+// Messages kept for scrolling back, older ones are dropped.
+const MESSAGE_LOG_LIMIT: usize = 500;
+// The keys that work the same on the input line and the board cursor.
+const GAME_ACTIONS: [KeyAction; 8] = [
+    KeyAction::Prev,
+    KeyAction::Next,
+    KeyAction::Start,
+    KeyAction::End,
+    KeyAction::Flip,
+    KeyAction::Undo,
+    KeyAction::Redo,
+    KeyAction::Redraw,
+];
+
+pub fn screen_main(game: &mut TuiGame) -> io::Result<()> {
+    let terminal = TerminalGuard::enter()?;
+    let mut screen = Screen::new();
+    screen.log(String::from(game.locale.welcome()));
+    if !terminal.mouse_enabled {
+        screen.log(String::from("Mouse input is not available in this terminal, type moves instead."));
+    }
+    run_frontend(game, &mut screen)
+}
+
+impl UiFrontend for Screen {
+    fn render(&mut self, game: &TuiGame) -> io::Result<()> {
+        self.draw(game)
+    }
+
+    fn notify(&mut self, messages: Vec<String>) {
+        for message in messages {
+            self.log(message);
+        }
+    }
+
+    fn read_command(&mut self, game: &mut TuiGame, timeout: Duration) -> io::Result<FrontendInput> {
+        if !event::poll(timeout)? {
+            return Ok(FrontendInput::Idle);
+        }
+        let input = match event::read()? {
+            // Windows also reports key releases, only act on presses and repeats.
+            Event::Key(key) if key.kind != KeyEventKind::Release => {
+                let keys = game.config.get_keys().clone();
+                let action = match keys.find(&key, &GAME_ACTIONS) {
+                    Some(KeyAction::Redraw) => InputAction::Redraw,
+                    Some(game_action) => game_action.get_command().map_or(InputAction::None, |c| InputAction::Submit(String::from(c))),
+                    None => match game.view.cursor {
+                        Some(_) => match cursor_key(&mut game.view, key, &keys) {
+                            CursorKey::Moved => InputAction::None,
+                            CursorKey::Pick(square) => click_command(game, square).map_or(InputAction::None, InputAction::Submit),
+                            CursorKey::Leave => {
+                                game.view.cursor = None;
+                                InputAction::None
+                            }
+                            // Anything else goes to the input line, so typing a command just works.
+                            CursorKey::Unhandled => {
+                                game.view.cursor = None;
+                                self.handle_key(key, &keys)
+                            }
+                        },
+                        None => self.handle_key(key, &keys),
+                    },
+                };
+                match action {
+                    InputAction::Submit(line) => {
+                        self.log(format!("{PROMPT}{line}"));
+                        FrontendInput::Command(line)
+                    }
+                    InputAction::BoardCursor => {
+                        let board = game.get_shown_board();
+                        game.view.cursor = game.view.selected.or(board.find_king(board.get_turn()));
+                        FrontendInput::Redraw
+                    }
+                    InputAction::Redraw => {
+                        self.invalidate();
+                        FrontendInput::Redraw
+                    }
+                    InputAction::Quit => FrontendInput::Closed,
+                    InputAction::None => FrontendInput::Redraw,
+                }
+            }
+            Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                let view = game.shown_view.as_ref().unwrap_or(&game.view);
+                let command = board_square_at(view, mouse.column, mouse.row)
+                    .and_then(|square| click_command(game, square));
+                match command {
+                    Some(command) => {
+                        self.log(format!("{PROMPT}{command}"));
+                        FrontendInput::Command(command)
+                    }
+                    None => FrontendInput::Redraw,
+                }
+            }
+            // Everything is laid out from the terminal size on every draw.
+            _ => FrontendInput::Redraw,
+        };
+        Ok(input)
+    }
+
+    // The clocks count down on the board and the analysis changes beside it.
+    fn shows_live_updates(&self) -> bool {
+        true
+    }
+}
+
+// Puts the terminal into raw mode on the alternate screen with mouse reporting, and restores
+// it when dropped so the shell is usable again even if the game panics.
+struct TerminalGuard {
+    mouse_enabled: bool,
+}
+
+impl TerminalGuard {
+    fn enter() -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(io::stdout(), EnterAlternateScreen) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        let mouse_enabled = execute!(io::stdout(), EnableMouseCapture).is_ok();
+        Ok(TerminalGuard { mouse_enabled })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.mouse_enabled {
+            let _ = execute!(io::stdout(), DisableMouseCapture);
+        }
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Terminal columns and rows of one square of the view's board.
+fn square_size(view: &BoardView) -> (u16, u16) {
+    let (width, height) = view.size.get_square_size();
+    (width as u16, height as u16)
+}
+
+// The width of the board pane, the move list starts right of it.
+fn board_pane_width(view: &BoardView) -> u16 {
+    BOARD_LEFT_COLUMN + 8 * square_size(view).0 + 4
+}
+
+// The smallest terminal the screen fits in, as columns and rows.
+fn min_terminal_size(view: &BoardView) -> (u16, u16) {
+    (board_pane_width(view) + MOVES_PANE_MIN_WIDTH, 8 * square_size(view).1 + MIN_ROWS_BESIDES_SQUARES)
+}
+
+// The board square drawn at a terminal position, if any.
+fn board_square_at(view: &BoardView, column: u16, row: u16) -> Option<BoardSquare> {
+    if column < BOARD_LEFT_COLUMN || row < BOARD_TOP_ROW {
+        return None;
+    }
+    let (square_width, square_height) = square_size(view);
+    let file = ((column - BOARD_LEFT_COLUMN) / square_width) as usize;
+    let rank = ((row - BOARD_TOP_ROW) / square_height) as usize;
+    if file > 7 || rank > 7 {
+        return None;
+    }
+    if view.flipped {
+        BoardSquare::from_indices(7 - file, rank)
+    }
+    else {
+        BoardSquare::from_indices(file, 7 - rank)
+    }
+}
+
+// The command a click on a board square stands for. Clicking one of the selected piece's
+// destinations moves it there (promoting to a queen), clicking a piece of the side to move
+// selects it, and clicking anywhere else clears the selection.
+fn click_command(game: &TuiGame, square: BoardSquare) -> Option<String> {
+    let board = game.get_shown_board();
+    if let Some(from) = game.view.selected {
+        if game.view.destinations.contains(&square) {
+            let board_move = board.legal_moves_from(from).into_iter()
+                .find(|m| m.get_to() == square && m.get_promotion().is_none_or(|p| p == ChessPiece::Queen))?;
+            return Some(format!("move {}", board.to_chess_move(board_move)));
+        }
+    }
+    match board.get_piece_at(square) {
+        Some(piece) if *piece.get_team() == board.get_turn() && game.view.selected != Some(square) => {
+            Some(format!("select {square}"))
+        }
+        // Any command clears the selection, show keeps the board as it is otherwise.
+        _ if game.view.selected.is_some() => Some(String::from("show")),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum InputAction {
+    None,
+    Submit(String),
+    // Switch from the input line to the board cursor.
+    BoardCursor,
+    // Draw the whole screen again, in case something else wrote over it.
+    Redraw,
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+enum CursorKey {
+    Moved,
+    Pick(BoardSquare),
+    Leave,
+    Unhandled,
+}
+
+// Moves the board cursor with the arrow keys or hjkl, in screen directions whichever way the
+// board is turned. Enter or space picks up or drops a piece at the cursor, Tab or Esc goes
+// back to the input line. Those are the default keys, the config can set others.
+fn cursor_key(view: &mut BoardView, key: KeyEvent, keys: &KeyMap) -> CursorKey {
+    let cursor = match view.cursor {
+        Some(c) => c,
+        None => return CursorKey::Unhandled,
+    };
+    let actions = [
+        KeyAction::CursorLeft,
+        KeyAction::CursorDown,
+        KeyAction::CursorUp,
+        KeyAction::CursorRight,
+        KeyAction::Pick,
+        KeyAction::LeaveBoard,
+    ];
+    let (right, up) = match keys.find(&key, &actions) {
+        Some(KeyAction::CursorLeft) => (-1, 0),
+        Some(KeyAction::CursorDown) => (0, -1),
+        Some(KeyAction::CursorUp) => (0, 1),
+        Some(KeyAction::CursorRight) => (1, 0),
+        Some(KeyAction::Pick) => return CursorKey::Pick(cursor),
+        Some(KeyAction::LeaveBoard) => return CursorKey::Leave,
+        _ => return CursorKey::Unhandled,
+    };
+    let (file_delta, rank_delta) = if view.flipped { (-right, -up) } else { (right, up) };
+    if let Some(moved) = cursor.offset(file_delta, rank_delta) {
+        view.cursor = Some(moved);
+    }
+    CursorKey::Moved
+}
+
+struct Screen {
+    input: Vec<char>,
+    cursor: usize,
+
+    // Submitted lines, oldest first, and the one being shown while browsing them.
+    history: Vec<String>,
+    history_index: Option<usize>,
+
+    message_log: Vec<String>,
+
+    // What the terminal shows, to draw only what changes.
+    last_frame: Option<Frame>,
+}
+
+impl Screen {
+    fn new() -> Screen {
+        Screen {
+            input: Vec::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+            message_log: Vec::new(),
+            last_frame: None,
+        }
+    }
+
+    fn log(&mut self, message: String) {
+        self.message_log.extend(message.lines().map(String::from));
+        if self.message_log.len() > MESSAGE_LOG_LIMIT {
+            let excess = self.message_log.len() - MESSAGE_LOG_LIMIT;
+            self.message_log.drain(..excess);
+        }
+    }
+
+    // Edits the input line. Enter submits it, the board cursor key (Tab unless configured) moves
+    // to the board, Ctrl-C and Ctrl-D leave the game whatever the config says.
+    fn handle_key(&mut self, key: KeyEvent, keys: &KeyMap) -> InputAction {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('d')) {
+            return InputAction::Quit;
+        }
+        if keys.find(&key, &[KeyAction::BoardCursor]).is_some() {
+            return InputAction::BoardCursor;
+        }
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return InputAction::None;
+        }
+
+        match key.code {
+            KeyCode::Enter => {
+                let line: String = self.input.iter().collect();
+                self.input.clear();
+                self.cursor = 0;
+                self.history_index = None;
+                if line.trim().is_empty() {
+                    return InputAction::None;
+                }
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                }
+                return InputAction::Submit(line);
+            }
+            KeyCode::Char(c) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
+            KeyCode::Esc => {
+                self.input.clear();
+                self.cursor = 0;
+                self.history_index = None;
+            }
+            KeyCode::Up => {
+                let index = match self.history_index {
+                    Some(i) => i.saturating_sub(1),
+                    None if !self.history.is_empty() => self.history.len() - 1,
+                    None => return InputAction::None,
+                };
+                self.show_history(Some(index));
+            }
+            KeyCode::Down => {
+                match self.history_index {
+                    Some(i) if i + 1 < self.history.len() => self.show_history(Some(i + 1)),
+                    Some(_) => self.show_history(None),
+                    None => (),
+                }
+            }
+            _ => (),
+        }
+        InputAction::None
+    }
+
+    fn show_history(&mut self, index: Option<usize>) {
+        self.history_index = index;
+        self.input = match index {
+            Some(i) => self.history[i].chars().collect(),
+            None => Vec::new(),
+        };
+        self.cursor = self.input.len();
+    }
+
+    // Lays out the screen on a new frame and draws what changed since the last one.
+    fn draw(&mut self, game: &TuiGame) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let mut frame = Frame::new(width, height, game.view.wide_glyphs);
+        let mut out = io::stdout();
+        let (min_width, min_height) = min_terminal_size(&game.view);
+        if width < min_width || height < min_height {
+            frame.print(0, 0, &format!("The terminal is too small, the game needs at least {min_width}x{min_height}."));
+            frame.draw(self.last_frame.as_ref(), &mut out)?;
+            self.last_frame = Some(frame);
+            return out.flush();
+        }
+
+        // Board pane, drawn line by line as the renderer's lines end in newlines.
+        let rendered = game.render();
+        let board_lines: Vec<&str> = rendered.lines()
+            .filter(|l| !l.trim_start_matches(TERMINAL_COLOR_RESET).is_empty())
+            .collect();
+        for (row, line) in board_lines.iter().enumerate() {
+            frame.print(0, row as u16, line);
+        }
+        let board_height = board_lines.len() as u16;
+
+        // Move list pane beside the board.
+        let pane_width = board_pane_width(&game.view);
+        let moves_width = (width - pane_width) as usize;
+        frame.print(pane_width, 0, "Moves");
+        let move_rows = board_height.saturating_sub(1) as usize;
+        for (column, row, entry) in layout_move_list(&move_list_entries(&game.state), moves_width, move_rows) {
+            frame.print(pane_width + column as u16, 1 + row as u16, &entry);
+        }
+
+        // Message log, showing the latest messages that fit. The analysis, if any, is shown in
+        // the line above it, and the lines of further moves analyzed below that.
+        let separator = "\u{2500}".repeat(width as usize);
+        let analysis = game.analysis.as_ref().map(|a| a.render()).unwrap_or_default();
+        match analysis.first() {
+            Some(best) => frame.print(0, board_height, &format!("\u{2500} {best} {separator}")),
+            None => frame.print(0, board_height, &separator),
+        }
+        for (i, line) in analysis.iter().skip(1).enumerate() {
+            frame.print(0, board_height + 1 + i as u16, line);
+        }
+        let log_top = board_height + analysis.len().max(1) as u16;
+        let log_rows = (height - 2).saturating_sub(log_top) as usize;
+        let first = self.message_log.len().saturating_sub(log_rows);
+        for (i, message) in self.message_log[first..].iter().enumerate() {
+            frame.print(0, log_top + i as u16, message);
+        }
+        frame.print(0, height - 2, &separator);
+
+        // The input line, scrolled so the cursor stays visible, or the board cursor's help.
+        let input_width = width as usize - PROMPT.len() - 1;
+        let offset = self.cursor.saturating_sub(input_width);
+        if game.view.cursor.is_some() {
+            frame.print(0, height - 1, "Board: arrows or hjkl move, Enter picks up and drops, Tab to type");
+        }
+        else {
+            let visible: String = self.input.iter().skip(offset).take(input_width).collect();
+            frame.print(0, height - 1, &format!("{PROMPT}{visible}"));
+        }
+
+        frame.draw(self.last_frame.as_ref(), &mut out)?;
+        self.last_frame = Some(frame);
+        if game.view.cursor.is_none() {
+            queue!(out, MoveTo((PROMPT.len() + self.cursor - offset) as u16, height - 1), Show)?;
+        }
+        out.flush()
+    }
+
+    // Forgets what was drawn, so the next draw redraws the whole screen.
+    fn invalidate(&mut self) {
+        self.last_frame = None;
+    }
+}
+
+// One entry per move number, e.g. "12. Nxe4 Qd5".
+pub(super) fn move_list_entries(state: &GameState) -> Vec<String> {
+    state.get_pgn_game().get_plies()
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            let mut entry = format!("{}. {}", i + 1, format_annotated_move(pair[0].0, pair[0].1));
+            if let Some((black_move, annotation)) = pair.get(1) {
+                entry.push_str(format!(" {}", format_annotated_move(black_move, annotation)).as_str());
+            }
+            entry
+        })
+        .collect()
+}
+
+// Places move list entries in columns, top to bottom and then left to right, keeping the latest
+// entries when they don't all fit. Returns the column offset, row and text of each entry shown.
+fn layout_move_list(entries: &[String], width: usize, rows: usize) -> Vec<(usize, usize, String)> {
+    if rows == 0 {
+        return Vec::new();
+    }
+    let column_width = entries.iter().map(|e| e.chars().count()).max().unwrap_or(0).max(14) + 2;
+    let columns = (width / column_width).max(1);
+    let capacity = columns * rows;
+    // Drop whole columns so the remaining entries stay aligned as the game grows.
+    let skipped = entries.len().saturating_sub(capacity).div_ceil(rows) * rows;
+
+    entries.iter()
+        .skip(skipped)
+        .enumerate()
+        .map(|(i, entry)| {
+            let text: String = entry.chars().take(width.min(column_width)).collect();
+            ((i / rows) * column_width, i % rows, text)
+        })
+        .collect()
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_screen {
+    use super::*;
+    use crate::chess_cmd::CommandDispatcher;
+    use crate::chess_cmd::ChessCommands;
+    use crate::chess_config::BoardSize;
+
+    fn square(s: &str) -> BoardSquare {
+        BoardSquare::from(s).unwrap()
+    }
+
+    fn press(screen: &mut Screen, code: KeyCode) -> InputAction {
+        screen.handle_key(KeyEvent::new(code, KeyModifiers::NONE), &KeyMap::default())
+    }
+
+    fn type_text(screen: &mut Screen, text: &str) {
+        for c in text.chars() {
+            press(screen, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    pub fn input_line_editing() {
+        let mut screen = Screen::new();
+        type_text(&mut screen, "move e5");
+        press(&mut screen, KeyCode::Backspace);
+        type_text(&mut screen, "4");
+        press(&mut screen, KeyCode::Home);
+        press(&mut screen, KeyCode::Delete);
+        type_text(&mut screen, "M");
+        assert_eq!(press(&mut screen, KeyCode::Enter), InputAction::Submit(String::from("Move e4")));
+        assert!(screen.input.is_empty());
+        assert_eq!(press(&mut screen, KeyCode::Enter), InputAction::None);
+    }
+
+    #[test]
+    pub fn history_recalls_previous_lines() {
+        let mut screen = Screen::new();
+        type_text(&mut screen, "move e4");
+        press(&mut screen, KeyCode::Enter);
+        type_text(&mut screen, "show");
+        press(&mut screen, KeyCode::Enter);
+
+        press(&mut screen, KeyCode::Up);
+        press(&mut screen, KeyCode::Up);
+        assert_eq!(screen.input.iter().collect::<String>(), "move e4");
+        press(&mut screen, KeyCode::Down);
+        assert_eq!(screen.input.iter().collect::<String>(), "show");
+        press(&mut screen, KeyCode::Down);
+        assert!(screen.input.is_empty());
+    }
+
+    #[test]
+    pub fn control_c_quits() {
+        let mut screen = Screen::new();
+        let key = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(screen.handle_key(key, &KeyMap::default()), InputAction::Quit);
+    }
+
+    #[test]
+    pub fn remapped_keys_replace_the_defaults() {
+        let config = crate::chess_config::ChessConfig::from("[keys]\nboard_cursor = [\"ctrl-b\"]\ncursor_left = [\"a\"]").unwrap();
+        let keys = config.get_keys();
+        let mut screen = Screen::new();
+        assert_eq!(screen.handle_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE), keys), InputAction::None);
+        assert_eq!(screen.handle_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL), keys), InputAction::BoardCursor);
+
+        let mut view = BoardView { cursor: Some(square("e1")), ..BoardView::default() };
+        assert_eq!(cursor_key(&mut view, KeyEvent::new(KeyCode::Left, KeyModifiers::NONE), keys), CursorKey::Unhandled);
+        assert_eq!(cursor_key(&mut view, KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE), keys), CursorKey::Moved);
+        assert_eq!(view.cursor, Some(square("d1")));
+    }
+
+    #[test]
+    pub fn move_list_pairs_moves_by_number() {
+        let mut game = TuiGame::new();
+        for san in ["e4", "e5", "Nf3"] {
+            game.dispatch(ChessCommands::Move { pgn_move: String::from(san) });
+        }
+        assert_eq!(move_list_entries(&game.state), vec!["1. e4 e5", "2. Nf3"]);
+    }
+
+    #[test]
+    pub fn move_list_keeps_the_latest_columns() {
+        let entries: Vec<String> = (1..=7).map(|i| format!("{i}. e4 e5")).collect();
+        let layout = layout_move_list(&entries, 40, 3);
+        // Two 16 wide columns of 3 rows fit, so the first column is dropped.
+        let shown: Vec<&str> = layout.iter().map(|(_, _, e)| e.as_str()).collect();
+        assert_eq!(shown, vec!["4. e4 e5", "5. e4 e5", "6. e4 e5", "7. e4 e5"]);
+        assert_eq!(layout[3].0, 16);
+        assert_eq!(layout[3].1, 0);
+    }
+
+    #[test]
+    pub fn clicks_map_to_board_squares() {
+        let view = BoardView::default();
+        assert_eq!(board_square_at(&view, 2, 1), Some(square("a8")));
+        assert_eq!(board_square_at(&view, 16, 5), Some(square("e4")));
+        assert_eq!(board_square_at(&view, 25, 8), Some(square("h1")));
+        assert_eq!(board_square_at(&view, 26, 8), None);
+        assert_eq!(board_square_at(&view, 1, 4), None);
+        assert_eq!(board_square_at(&view, 2, 9), None);
+
+        let flipped = BoardView { flipped: true, ..BoardView::default() };
+        assert_eq!(board_square_at(&flipped, 2, 1), Some(square("h1")));
+
+        let large = BoardView { size: BoardSize::Large, ..BoardView::default() };
+        assert_eq!(board_square_at(&large, 8, 3), Some(square("a8")));
+        assert_eq!(board_square_at(&large, 30, 13), Some(square("e4")));
+        assert_eq!(board_square_at(&large, 57, 24), Some(square("h1")));
+        assert_eq!(board_square_at(&large, 58, 24), None);
+        assert_eq!(board_square_at(&large, 57, 25), None);
+        assert_eq!(min_terminal_size(&large), (92, 36));
+        assert_eq!(min_terminal_size(&view), (60, 20));
+    }
+
+    #[test]
+    pub fn click_selects_then_moves() {
+        let mut game = TuiGame::new();
+        assert_eq!(click_command(&game, square("e4")), None);
+        assert_eq!(click_command(&game, square("e7")), None);
+        assert_eq!(click_command(&game, square("g1")), Some(String::from("select g1")));
+
+        game.run_command_line("select g1");
+        assert_eq!(click_command(&game, square("f3")), Some(String::from("move Nf3")));
+        assert_eq!(click_command(&game, square("e2")), Some(String::from("select e2")));
+        assert_eq!(click_command(&game, square("e5")), Some(String::from("show")));
+    }
+
+    #[test]
+    pub fn click_promotes_to_a_queen() {
+        let mut game = TuiGame::new();
+        for san in ["a4", "b5", "axb5", "a6", "bxa6", "Bb7", "axb7", "Nc6"] {
+            game.dispatch(ChessCommands::Move { pgn_move: String::from(san) });
+        }
+        game.run_command_line("select b7");
+        assert_eq!(click_command(&game, square("a8")), Some(String::from("move bxa8=Q")));
+        assert_eq!(click_command(&game, square("b8")), Some(String::from("move b8=Q")));
+    }
+
+    #[test]
+    pub fn cursor_moves_in_screen_directions() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let keys = KeyMap::default();
+        let mut view = BoardView { cursor: Some(square("e1")), ..BoardView::default() };
+        assert_eq!(cursor_key(&mut view, key(KeyCode::Up), &keys), CursorKey::Moved);
+        assert_eq!(cursor_key(&mut view, key(KeyCode::Char('l')), &keys), CursorKey::Moved);
+        assert_eq!(view.cursor, Some(square("f2")));
+        // The edge of the board stops the cursor.
+        cursor_key(&mut view, key(KeyCode::Down), &keys);
+        cursor_key(&mut view, key(KeyCode::Char('j')), &keys);
+        assert_eq!(view.cursor, Some(square("f1")));
+
+        view.flipped = true;
+        cursor_key(&mut view, key(KeyCode::Up), &keys);
+        cursor_key(&mut view, key(KeyCode::Right), &keys);
+        assert_eq!(view.cursor, Some(square("e1")));
+
+        assert_eq!(cursor_key(&mut view, key(KeyCode::Enter), &keys), CursorKey::Pick(square("e1")));
+        assert_eq!(cursor_key(&mut view, key(KeyCode::Tab), &keys), CursorKey::Leave);
+        assert_eq!(cursor_key(&mut view, key(KeyCode::Char('m')), &keys), CursorKey::Unhandled);
+    }
+}
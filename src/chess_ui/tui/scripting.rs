@@ -0,0 +1,339 @@
+/*
+scripting.rs
+Scripts that react to the game and add commands, built with the scripting feature. Every .rhai
+file in the scripts directory beside the config file, e.g. ~/.config/rust-chess/scripts, is loaded
+at start in the order of the file names. A script reacts to the game by defining functions for
+its events:
+
+  on_start()            once the scripts are loaded
+  on_move(san, fen)     after every move, with the move and the position after it
+  on_game_end(result)   when the game ends, with 1-0, 0-1 or 1/2-1/2
+
+A function named command_<name> with one parameter adds the command <name>, given the words typed
+after it as an array. Built-in commands can't be replaced. Scripts act through say(text), which
+shows a message, and run(command), which runs a command as if it were typed once the script is
+done; fen() gives the position shown. Commands run by scripts don't call the scripts again, so a
+script can't set itself off.
+
+Example scripts/club.rhai:
+fn on_move(san, fen) {
+    run("save --force /home/me/club/current.pgn");
+    // The club draws games that reach move 80.
+    if fen.ends_with(" 80") {
+        run("adjudicate 1/2-1/2 Drawn at move 80 by the rules of the club");
+    }
+}
+
+fn command_greet(words) {
+    say(`Good luck, ${words[0]}!`);
+}
+*/
+
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use clap::CommandFactory;
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+
+use crate::{
+    chess_cmd::ChessTuiCmd,
+    chess_core::{Board, Team},
+    chess_game::{GameObserver, GameStatus, ObserverId},
+    chess_pgn::{ChessMove, PgnResult},
+};
+use super::TuiGame;
+
+const COMMAND_PREFIX: &str = "command_";
+
+// What the scripts asked for while they ran.
+enum ScriptAction {
+    Say(String),
+    Run(String),
+}
+
+// A game event waiting to be told to the scripts.
+enum ScriptEvent {
+    Move(String, String),
+    GameEnd(String),
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+    actions: Rc<RefCell<Vec<ScriptAction>>>,
+    events: Rc<RefCell<Vec<ScriptEvent>>>,
+    // The position fen() gives.
+    fen: Rc<RefCell<String>>,
+    // The observer queuing the events of the current game. A game that replaced it, loaded or
+    // new, gets one of its own the next time the scripts look.
+    observer: Option<ObserverId>,
+    // Set while the commands the scripts asked for run.
+    running: bool,
+}
+
+// Queues the game's events for the scripts, which run once the command that caused them is done.
+struct ScriptObserver(Rc<RefCell<Vec<ScriptEvent>>>);
+
+impl GameObserver for ScriptObserver {
+    fn on_move(&mut self, chess_move: &ChessMove, board: &Board) {
+        self.0.borrow_mut().push(ScriptEvent::Move(chess_move.to_string(), board.to_fen()));
+    }
+
+    fn on_game_end(&mut self, status: &GameStatus) {
+        let result = match status {
+            GameStatus::Checkmate { winner: Team::Light } => PgnResult::WhiteWin,
+            GameStatus::Checkmate { winner: Team::Dark } => PgnResult::BlackWin,
+            GameStatus::Stalemate => PgnResult::Draw,
+            GameStatus::Ended(result) => *result,
+            GameStatus::ToMove(_) | GameStatus::InCheck(_) => return,
+        };
+        self.0.borrow_mut().push(ScriptEvent::GameEnd(result.to_string()));
+    }
+}
+
+impl Scripts {
+    fn new() -> Scripts {
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        let fen = Rc::new(RefCell::new(String::new()));
+        let mut engine = Engine::new();
+        let said = actions.clone();
+        engine.register_fn("say", move |text: &str| said.borrow_mut().push(ScriptAction::Say(text.to_string())));
+        let printed = actions.clone();
+        engine.on_print(move |text| printed.borrow_mut().push(ScriptAction::Say(text.to_string())));
+        let ran = actions.clone();
+        engine.register_fn("run", move |command: &str| ran.borrow_mut().push(ScriptAction::Run(command.to_string())));
+        let shown = fen.clone();
+        engine.register_fn("fen", move || shown.borrow().clone());
+        Scripts {
+            engine,
+            scripts: Vec::new(),
+            actions,
+            events: Rc::new(RefCell::new(Vec::new())),
+            fen,
+            observer: None,
+            running: false,
+        }
+    }
+
+    // Compiles a script and runs its top level, which sets up what its functions share.
+    fn add(&mut self, name: String, text: &str) -> Result<(), String> {
+        let ast = self.engine.compile(text).map_err(|e| e.to_string())?;
+        let mut scope = Scope::new();
+        self.engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| e.to_string())?;
+        self.scripts.push(Script { name, ast, scope });
+        Ok(())
+    }
+
+    // The commands the scripts add, by name, with the script adding each.
+    fn get_commands(&self) -> Vec<(String, &str)> {
+        self.scripts.iter()
+            .flat_map(|s| s.ast.iter_functions()
+                .filter(|f| f.params.len() == 1)
+                .filter_map(|f| f.name.strip_prefix(COMMAND_PREFIX).map(|c| (c.to_string(), s.name.as_str())))
+                .collect::<Vec<_>>())
+            .collect()
+    }
+
+    // Calls a function in every script defining it. Returns the errors, one message each.
+    fn call(&mut self, function: &str, args: Vec<Dynamic>) -> Vec<String> {
+        let mut errors = Vec::new();
+        for script in &mut self.scripts {
+            if !script.ast.iter_functions().any(|f| f.name == function && f.params.len() == args.len()) {
+                continue;
+            }
+            if let Err(e) = self.engine.call_fn::<Dynamic>(&mut script.scope, &script.ast, function, args.clone()) {
+                errors.push(format!("The script {} failed in {function}: {e}.", script.name));
+            }
+        }
+        errors
+    }
+
+    fn take_actions(&mut self) -> Vec<ScriptAction> {
+        std::mem::take(&mut *self.actions.borrow_mut())
+    }
+}
+
+// Loads the scripts beside the config file, telling what was loaded and any script that
+// couldn't be.
+pub fn load_scripts(game: &mut TuiGame) {
+    let dir = match game.config_path.as_ref().and_then(|p| p.parent()) {
+        Some(d) => d.join("scripts"),
+        None => return,
+    };
+    let mut paths: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "rhai"))
+            .collect(),
+        // There are no scripts until the directory is made.
+        Err(_) => return,
+    };
+    paths.sort();
+    game.load_script_files(&dir, &paths);
+}
+
+impl TuiGame {
+    fn load_script_files(&mut self, dir: &Path, paths: &[PathBuf]) {
+        let mut scripts = Scripts::new();
+        for path in paths {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let loaded = fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| scripts.add(name.clone(), &text));
+            if let Err(e) = loaded {
+                self.say(self.locale.script_not_loaded(&name, &e));
+            }
+        }
+        if scripts.scripts.is_empty() {
+            return;
+        }
+        let mut commands = Vec::new();
+        for (command, script) in scripts.get_commands() {
+            if ChessTuiCmd::command().find_subcommand(&command).is_some() {
+                self.say(self.locale.script_command_taken(script, &command));
+            }
+            else {
+                commands.push(command);
+            }
+        }
+        let mut message = format!("Loaded {} script(s) from {}", scripts.scripts.len(), dir.display());
+        if !commands.is_empty() {
+            message.push_str(&format!(", adding the command(s) {}", commands.join(", ")));
+        }
+        self.say(message);
+        self.scripts = Some(scripts);
+        self.run_scripts("on_start", Vec::new());
+    }
+
+    // Calls a function of the scripts and carries out what they asked for.
+    fn run_scripts(&mut self, function: &str, args: Vec<Dynamic>) {
+        let fen = self.get_shown_board().to_fen();
+        let scripts = match &mut self.scripts {
+            Some(s) if !s.running => s,
+            _ => return,
+        };
+        *scripts.fen.borrow_mut() = fen;
+        let errors = scripts.call(function, args);
+        let actions = scripts.take_actions();
+        for error in errors {
+            self.say(error);
+        }
+        let mut commands = Vec::new();
+        for action in actions {
+            match action {
+                ScriptAction::Say(text) => self.say(text),
+                ScriptAction::Run(command) => commands.push(command),
+            }
+        }
+        if commands.is_empty() {
+            return;
+        }
+        self.set_scripts_running(true);
+        for command in commands {
+            self.run_command_line(&command);
+        }
+        // What the commands did isn't told to the scripts.
+        self.watch_game_for_scripts();
+        if let Some(scripts) = &self.scripts {
+            scripts.events.borrow_mut().clear();
+        }
+        self.set_scripts_running(false);
+    }
+
+    fn set_scripts_running(&mut self, running: bool) {
+        if let Some(scripts) = &mut self.scripts {
+            scripts.running = running;
+        }
+    }
+
+    // Makes sure the current game tells the scripts about its events.
+    pub(super) fn watch_game_for_scripts(&mut self) {
+        let scripts = match &mut self.scripts {
+            Some(s) => s,
+            None => return,
+        };
+        if scripts.observer.is_some_and(|id| self.state.is_subscribed(id)) {
+            return;
+        }
+        scripts.observer = Some(self.state.subscribe(Box::new(ScriptObserver(scripts.events.clone()))));
+    }
+
+    // Tells the scripts what happened in the game during the command.
+    pub(super) fn run_script_hooks(&mut self) {
+        let events = match &self.scripts {
+            Some(s) if !s.running => std::mem::take(&mut *s.events.borrow_mut()),
+            _ => return,
+        };
+        for event in events {
+            match event {
+                ScriptEvent::Move(san, fen) => self.run_scripts("on_move", vec![san.into(), fen.into()]),
+                ScriptEvent::GameEnd(result) => self.run_scripts("on_game_end", vec![result.into()]),
+            }
+        }
+        self.watch_game_for_scripts();
+    }
+
+    // Runs a command a script adds. Returns false when no script adds it.
+    pub(super) fn run_script_command(&mut self, args: &[String]) -> bool {
+        let (command, words) = match args.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        let function = format!("{COMMAND_PREFIX}{command}");
+        let added = self.scripts.as_ref()
+            .is_some_and(|s| s.get_commands().iter().any(|(c, _)| c == command));
+        if !added || ChessTuiCmd::command().find_subcommand(command).is_some() {
+            return false;
+        }
+        let words: Array = words.iter().map(|w| Dynamic::from(w.clone())).collect();
+        self.run_scripts(&function, vec![words.into()]);
+        true
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_scripting {
+    use super::*;
+
+    #[test]
+    pub fn scripts_react_to_moves_and_add_commands() {
+        let dir = std::env::temp_dir().join(format!("rust_chess_scripts_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("club.rhai");
+        fs::write(&path, r#"
+            fn on_move(san, fen) {
+                say(`Seen ${san}`);
+                if san == "e5" {
+                    run("adjudicate 1/2-1/2 Drawn by the rules of the club");
+                }
+            }
+            fn on_game_end(result) { say(`Game over, ${result}`); }
+            fn command_greet(words) { say(`Good luck, ${words[0]}!`); }
+            fn command_undo(words) { say("never called"); }
+        "#).unwrap();
+        let mut game = TuiGame::new();
+        game.load_script_files(&dir, &[path]);
+        game.run_command_line("move e4");
+        game.run_command_line("greet Ann");
+        game.run_command_line("move e5");
+        let messages = game.take_messages();
+        assert_eq!(messages[..2], [
+            "The script club.rhai can't replace the command undo, it is left out.".to_string(),
+            format!("Loaded 1 script(s) from {}, adding the command(s) greet", dir.display()),
+        ]);
+        assert!(messages.contains(&String::from("Seen e4")));
+        assert!(messages.contains(&String::from("Good luck, Ann!")));
+        // The game ended by a script's command isn't told to the scripts.
+        assert!(!messages.iter().any(|m| m.starts_with("Game over")));
+        assert_eq!(*game.state.get_pgn_game().get_result(), PgnResult::Draw);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,538 @@
+/*
+session.rs
+The native save format, keeping what a PGN file can't. save --session and load --session write
+and read the current game's session as JSON:
+
+  game        the game as the serde feature writes it, with the moves taken back still at its end
+  undone      how many of its last moves were taken back, for redo to replay
+  viewed_ply  the half move being looked at when stepping through the game
+  clock       the time control, and each side's time, stage and moves made in the stage
+  opponent    the side the computer plays, its engine (none for the built-in one) and its limit
+  flipped     the board's orientation
+  rated       whether the game counts for the ratings
+  recorded    whether the finished game was already archived and rated
+
+export session and import take everything along instead, to back it up or move it to another
+machine: every open game as above under games, with current_game the index of the one being
+played, the settings under settings and the players' ratings under ratings.
+
+Every file has a version. Files of older versions are migrated up when read; version 0, a file
+without one, is a game as the serde feature writes it, {"game": ..., "fen": ...}. What a newer
+version added is left out when read, so its files still load as far as this version knows them.
+*/
+
+use std::{
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    chess_clock::{ChessClock, TimeControl},
+    chess_config::ChessConfig,
+    chess_core::Team,
+    chess_game::{GameLoadError, GameState},
+    chess_pgn::PgnGame,
+    chess_rating::RatingList,
+    chess_uci::SearchLimit,
+};
+use super::{opponent::Opponent, OpenGame, TuiGame};
+
+pub const SESSION_VERSION: u64 = 1;
+
+#[derive(Debug)]
+pub enum SessionError {
+    FileExists(PathBuf),
+    Io(PathBuf, io::Error),
+    Json(serde_json::Error),
+    Game(GameLoadError),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::FileExists(path) => write!(f, "{} already exists, use --force to overwrite it", path.display()),
+            SessionError::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            SessionError::Json(e) => write!(f, "not a session file, {e}"),
+            SessionError::Game(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Side {
+    White,
+    Black,
+}
+
+impl From<Team> for Side {
+    fn from(team: Team) -> Side {
+        match team {
+            Team::Light => Side::White,
+            Team::Dark => Side::Black,
+        }
+    }
+}
+
+impl From<Side> for Team {
+    fn from(side: Side) -> Team {
+        match side {
+            Side::White => Team::Light,
+            Side::Black => Team::Dark,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionRecord {
+    version: u64,
+    #[serde(flatten)]
+    open: GameRecord,
+    #[serde(default)]
+    flipped: bool,
+}
+
+// An open game with its clock and opponent.
+#[derive(Serialize, Deserialize)]
+struct GameRecord {
+    game: PgnGame,
+    #[serde(default)]
+    undone: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    viewed_ply: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clock: Option<ClockRecord>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    opponent: Option<OpponentRecord>,
+    #[serde(default = "rated_by_default")]
+    rated: bool,
+    #[serde(default)]
+    recorded: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportRecord {
+    version: u64,
+    games: Vec<GameRecord>,
+    #[serde(default)]
+    current_game: usize,
+    #[serde(default)]
+    flipped: bool,
+    // Kept as read until it is applied, so settings a newer version added don't stop the games
+    // from loading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    settings: Option<Value>,
+    #[serde(default)]
+    ratings: RatingList,
+}
+
+fn rated_by_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClockRecord {
+    time_control: TimeControl,
+    // White's and then Black's.
+    remaining_ms: [u64; 2],
+    stage: [usize; 2],
+    stage_moves: [u32; 2],
+    // The side whose clock was running, it runs again once the session is loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    running: Option<Side>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpponentRecord {
+    side: Side,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    engine: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    depth: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    movetime_ms: Option<u64>,
+}
+
+// Brings a session of an older version up to the current one.
+fn migrate(mut value: Value) -> Value {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version == 0 {
+        let game = value.get_mut("game").map(Value::take).unwrap_or(Value::Null);
+        value = serde_json::json!({ "version": 1, "game": game });
+    }
+    value
+}
+
+impl GameRecord {
+    fn new(state: &GameState, clock: Option<&ChessClock>, opponent: Option<&Opponent>, rated: bool, recorded: bool) -> GameRecord {
+        let mut game = state.get_pgn_game().clone();
+        let undone = state.get_undone_moves();
+        for (chess_move, annotation) in &undone {
+            game.push_annotated_move((*chess_move).clone(), (*annotation).clone());
+        }
+        let now = Instant::now();
+        let clock = clock.map(|clock| {
+            let teams = [Team::Light, Team::Dark];
+            ClockRecord {
+                time_control: clock.get_time_control().clone(),
+                remaining_ms: teams.map(|t| clock.get_remaining(t, now).as_millis() as u64),
+                stage: teams.map(|t| clock.get_stage_progress(t).0),
+                stage_moves: teams.map(|t| clock.get_stage_progress(t).1),
+                running: clock.get_running().map(Side::from),
+            }
+        });
+        let opponent = opponent.map(|opponent| {
+            let (depth, movetime_ms) = match opponent.get_limit() {
+                SearchLimit::Depth(depth) => (Some(depth), None),
+                SearchLimit::MoveTime(time) => (None, Some(time.as_millis() as u64)),
+                SearchLimit::Infinite => (None, None),
+            };
+            OpponentRecord { side: opponent.get_team().into(), engine: opponent.get_engine_path().cloned(), depth, movetime_ms }
+        });
+        GameRecord {
+            game,
+            undone: undone.len(),
+            viewed_ply: state.is_viewing_history().then(|| state.get_viewed_ply()),
+            clock,
+            opponent,
+            rated,
+            recorded,
+        }
+    }
+
+    // The game as it was left, with the moves taken back ready for redo.
+    fn get_state(&self) -> Result<GameState, SessionError> {
+        let result = *self.game.get_result();
+        let mut state = GameState::from_pgn_game(self.game.clone()).map_err(SessionError::Game)?;
+        let undone = state.undo(self.undone);
+        // Taking the moves back reopens the game, it was ended after they were.
+        if undone > 0 && *state.get_pgn_game().get_result() != result {
+            state.get_pgn_game_mut().set_result(result);
+            if let Some(comment) = self.game.get_result_comment() {
+                state.get_pgn_game_mut().set_result_comment(comment.clone());
+            }
+        }
+        if let Some(ply) = self.viewed_ply {
+            state.view_ply(ply);
+        }
+        Ok(state)
+    }
+
+    // The clock, running again for the side it ran for.
+    fn get_clock(&self) -> Option<ChessClock> {
+        self.clock.as_ref().map(|c| {
+            let mut clock = ChessClock::resume(c.time_control.clone(), c.remaining_ms.map(Duration::from_millis), c.stage, c.stage_moves);
+            if let Some(side) = c.running {
+                clock.start(side.into(), Instant::now());
+            }
+            clock
+        })
+    }
+}
+
+// Reads a file of the native format as the current version has it, along with the version it
+// was written by.
+fn read_versioned(path: &Path) -> Result<(Value, u64), SessionError> {
+    let text = fs::read_to_string(path).map_err(|e| SessionError::Io(path.to_path_buf(), e))?;
+    let value: Value = serde_json::from_str(&text).map_err(SessionError::Json)?;
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    Ok((migrate(value), version))
+}
+
+fn write_json<T: Serialize>(path: &Path, record: &T, force: bool) -> Result<(), SessionError> {
+    if path.exists() && !force {
+        return Err(SessionError::FileExists(path.to_path_buf()));
+    }
+    let text = serde_json::to_string_pretty(record).map_err(SessionError::Json)?;
+    path.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, format!("{text}\n")))
+        .map_err(|e| SessionError::Io(path.to_path_buf(), e))
+}
+
+impl TuiGame {
+    fn get_current_record(&self) -> GameRecord {
+        GameRecord::new(&self.state, self.clock.as_ref(), self.opponent.as_ref(), self.rated, self.recorded)
+    }
+
+    pub(super) fn save_session(&mut self, path: &Path, force: bool) {
+        let record = SessionRecord { version: SESSION_VERSION, open: self.get_current_record(), flipped: self.view.flipped };
+        match write_json(path, &record, force) {
+            Ok(()) => self.say(self.locale.saved_session(&path.display().to_string())),
+            Err(e) => self.say(self.locale.session_not_saved(&e.to_string())),
+        }
+    }
+
+    // The current game's session as save --session writes it.
+    pub(super) fn get_session_text(&self) -> String {
+        let record = SessionRecord { version: SESSION_VERSION, open: self.get_current_record(), flipped: self.view.flipped };
+        serde_json::to_string_pretty(&record).unwrap_or_default()
+    }
+
+    // Restores a game left in the autosave file. Its session, from the file beside it, is
+    // restored along with it when it holds the same game, as it does unless it was left by a
+    // crash between writing the two.
+    pub(super) fn restore_autosave(&mut self, pgn_game: PgnGame, session_path: &Path) -> Result<(), GameLoadError> {
+        match TuiGame::read_session(session_path) {
+            Ok((record, _, state)) if state.get_pgn_game().to_string() == pgn_game.to_string() => {
+                self.view.flipped = record.flipped;
+                self.restore_game(record.open, state);
+            }
+            _ => self.state = GameState::from_pgn_game(pgn_game)?,
+        }
+        Ok(())
+    }
+
+    // Reads a session, the version it was written by and the game it holds.
+    fn read_session(path: &Path) -> Result<(SessionRecord, u64, GameState), SessionError> {
+        let (value, version) = read_versioned(path)?;
+        let record: SessionRecord = serde_json::from_value(value).map_err(SessionError::Json)?;
+        let state = record.open.get_state()?;
+        Ok((record, version, state))
+    }
+
+    // Makes a game that was read the current one, starting its opponent again.
+    fn restore_game(&mut self, record: GameRecord, state: GameState) {
+        self.state = state;
+        self.clock = record.get_clock();
+        self.rated = record.rated;
+        self.recorded = record.recorded;
+        self.opponent = None;
+        if let Some(o) = record.opponent {
+            let limit = match (o.depth, o.movetime_ms) {
+                (Some(depth), _) => SearchLimit::Depth(depth),
+                (None, Some(ms)) => SearchLimit::MoveTime(Duration::from_millis(ms)),
+                (None, None) => SearchLimit::Infinite,
+            };
+            self.resume_opponent(o.side.into(), o.engine.as_deref(), limit);
+        }
+    }
+
+    pub(super) fn load_session(&mut self, path: &Path) {
+        let (record, version, state) = match TuiGame::read_session(path) {
+            Ok(session) => session,
+            Err(e) => {
+                self.say(self.locale.session_not_loaded(&e.to_string()));
+                return;
+            }
+        };
+        self.say(self.locale.loaded_session(&path.display().to_string()));
+        self.view.flipped = record.flipped;
+        self.restore_game(record.open, state);
+        if version > SESSION_VERSION {
+            self.say(String::from(self.locale.newer_version()));
+        }
+    }
+
+    // Writes every open game, the settings and the ratings to a file.
+    pub(super) fn export_session(&mut self, path: &Path, force: bool) {
+        let mut games: Vec<GameRecord> = self.other_games.iter()
+            .map(|g| GameRecord::new(&g.state, g.clock.as_ref(), g.opponent.as_ref(), g.rated, g.recorded))
+            .collect();
+        games.insert(self.current_game, self.get_current_record());
+        let record = ExportRecord {
+            version: SESSION_VERSION,
+            games,
+            current_game: self.current_game,
+            flipped: self.view.flipped,
+            settings: serde_json::to_value(&self.config).ok(),
+            ratings: self.ratings.clone(),
+        };
+        let game_count = record.games.len();
+        match write_json(path, &record, force) {
+            Ok(()) => self.say(self.locale.exported_session(game_count, &path.display().to_string())),
+            Err(e) => self.say(self.locale.session_not_exported(&e.to_string())),
+        }
+    }
+
+    // Replaces the open games, the settings and the ratings with those of an exported session.
+    pub(super) fn import_session(&mut self, path: &Path) {
+        if self.simul.is_some() || self.remote.is_some() || self.http.is_some() {
+            self.say(String::from(self.locale.open_games_in_use()));
+            return;
+        }
+        let read = read_versioned(path).and_then(|(value, version)| {
+            let record: ExportRecord = serde_json::from_value(value).map_err(SessionError::Json)?;
+            let states = record.games.iter().map(GameRecord::get_state).collect::<Result<Vec<_>, _>>()?;
+            Ok((record, version, states))
+        });
+        let (record, version, states) = match read {
+            Ok(r) => r,
+            Err(e) => {
+                self.say(self.locale.session_not_imported(&e.to_string()));
+                return;
+            }
+        };
+        if states.is_empty() {
+            self.say(self.locale.session_without_games(&path.display().to_string()));
+            return;
+        }
+        let game_count = states.len();
+        self.say(self.locale.imported_session(game_count, &path.display().to_string()));
+
+        // Each game is made current to start its opponent, then set aside.
+        if let Some(opponent) = &mut self.opponent {
+            let _ = opponent.stop_pondering();
+        }
+        self.other_games.clear();
+        for (game, state) in record.games.into_iter().zip(states) {
+            self.restore_game(game, state);
+            self.other_games.push(OpenGame {
+                state: std::mem::replace(&mut self.state, GameState::new()),
+                clock: self.clock.take(),
+                opponent: self.opponent.take(),
+                rated: self.rated,
+                recorded: self.recorded,
+            });
+        }
+        self.resume_game(record.current_game.min(game_count - 1));
+        self.view.flipped = record.flipped;
+
+        match record.settings.map(serde_json::from_value::<ChessConfig>) {
+            Some(Ok(config)) => {
+                self.config = config;
+                self.apply_config();
+                self.say(String::from(self.locale.imported_settings()));
+            }
+            Some(Err(e)) => self.say(self.locale.settings_not_imported(&e.to_string())),
+            None => (),
+        }
+        self.ratings = record.ratings;
+        if let Some(ratings_path) = &self.ratings_path {
+            if let Err(e) = self.ratings.save(ratings_path) {
+                self.say(self.locale.ratings_not_saved(&e.to_string()));
+            }
+        }
+        if version > SESSION_VERSION {
+            self.say(String::from(self.locale.newer_version()));
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_session {
+    use super::*;
+    use crate::chess_ui::tui::{locale::Locale, Autosave};
+
+    #[test]
+    pub fn sessions_keep_what_pgn_cannot() {
+        let path = std::env::temp_dir().join(format!("rust_chess_session_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut game = TuiGame::new();
+        game.config.set("time_control", "5+3").unwrap();
+        game.run_command_line("new --vs-engine --black --movetime 20");
+        game.run_command_line("move e5");
+        // The computer answers with dxe5, which is marked.
+        game.run_command_line("annotate-move !");
+        game.run_command_line("undo 2");
+        game.run_command_line("flip");
+        game.take_messages();
+        let played = game.state.get_pgn_game().to_string();
+        game.run_command_line(&format!("save --session {}", path.display()));
+        game.run_command_line(&format!("save --session {}", path.display()));
+        assert_eq!(game.take_messages(), [
+            format!("Saved the session to {}", path.display()),
+            format!("Could not save the session: {} already exists, use --force to overwrite it.", path.display()),
+        ]);
+
+        let mut loaded = TuiGame::new();
+        loaded.run_command_line(&format!("load --session {}", path.display()));
+        assert_eq!(loaded.take_messages(), [format!("Loaded the session from {}", path.display())]);
+        assert_eq!(loaded.state.get_pgn_game().to_string(), played);
+        assert_eq!(loaded.state.get_redo_count(), 2);
+        assert!(loaded.view.flipped);
+        let opponent = loaded.opponent.as_ref().unwrap();
+        assert_eq!((opponent.get_team(), opponent.get_limit()), (Team::Light, SearchLimit::MoveTime(Duration::from_millis(20))));
+        let clock = loaded.clock.as_ref().unwrap();
+        assert_eq!(clock.get_time_control(), &TimeControl::from("5+3").unwrap());
+        // The moves taken back come back with their annotations.
+        loaded.run_command_line("redo 2");
+        assert_eq!(loaded.state.get_pgn_game().get_plies()[2].1.get_nags(), &[1]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn older_and_newer_versions_are_read() {
+        let path = std::env::temp_dir().join(format!("rust_chess_session_versions_{}.json", std::process::id()));
+        let mut game = TuiGame::new();
+        game.run_command_line("move e4");
+        let version_0 = serde_json::to_string(&game.state).unwrap();
+        fs::write(&path, version_0).unwrap();
+        let (record, version, state) = TuiGame::read_session(&path).unwrap();
+        assert_eq!((version, record.version, state.get_ply_count()), (0, SESSION_VERSION, 1));
+
+        let newer = r#"{"version": 7, "game": {"tags": [], "moves": [{"move": "d4"}]}, "undone": 1, "hologram": true}"#;
+        fs::write(&path, newer).unwrap();
+        game.run_command_line(&format!("load --session {}", path.display()));
+        assert_eq!(game.take_messages().last().unwrap(), Locale::English.newer_version());
+        assert_eq!((game.state.get_ply_count(), game.state.get_redo_count()), (0, 1));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn exports_take_every_open_game_and_the_settings() {
+        let path = std::env::temp_dir().join(format!("rust_chess_export_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut game = TuiGame::new();
+        game.run_command_line("move e4");
+        game.run_command_line("game new");
+        game.run_command_line("move d4");
+        game.run_command_line("move d5");
+        game.run_command_line("config set theme blue");
+        game.ratings.record_game("Ann", "Bob", crate::chess_pgn::PgnResult::WhiteWin);
+        game.take_messages();
+        game.run_command_line(&format!("export session {}", path.display()));
+        assert_eq!(game.take_messages(), [format!("Exported 2 open game(s), the settings and the ratings to {}", path.display())]);
+
+        let mut imported = TuiGame::new();
+        imported.run_command_line("move c4");
+        imported.run_command_line(&format!("import {}", path.display()));
+        assert_eq!(imported.take_messages()[1..], [
+            format!("Imported 2 open game(s) from {}", path.display()),
+            String::from("The settings were imported, config save keeps them."),
+        ]);
+        assert_eq!((imported.current_game, imported.state.get_ply_count()), (1, 2));
+        assert_eq!(imported.get_open_game_state(0).get_pgn_game().to_string(), game.get_open_game_state(0).get_pgn_game().to_string());
+        assert_eq!(imported.config, game.config);
+        assert_eq!(imported.ratings, game.ratings);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    pub fn autosaved_games_are_restored_with_their_session() {
+        let path = std::env::temp_dir().join(format!("rust_chess_resume_{}", std::process::id())).join("autosave.pgn");
+        let mut game = TuiGame::new();
+        game.config.set("time_control", "10+5").unwrap();
+        game.new_game();
+        game.autosave = Some(Autosave::new(path.clone()));
+        for pgn_move in ["e4", "e5", "Nf3"] {
+            game.run_command_line(&format!("move {pgn_move}"));
+        }
+        game.run_command_line("undo");
+        game.run_command_line("flip");
+        game.write_autosave();
+
+        let mut restored = TuiGame::new();
+        let pgn_game = Autosave::find_unfinished(&path).unwrap();
+        restored.restore_autosave(pgn_game.clone(), &Autosave::get_session_path(&path)).unwrap();
+        assert_eq!((restored.state.get_ply_count(), restored.state.get_redo_count()), (2, 1));
+        assert!(restored.view.flipped);
+        assert_eq!(restored.clock.as_ref().unwrap().get_time_control(), &TimeControl::from("10+5").unwrap());
+
+        // Without its session the game still comes back, from the moves.
+        let mut restored = TuiGame::new();
+        restored.restore_autosave(pgn_game, &path.with_file_name("missing.json")).unwrap();
+        assert_eq!((restored.state.get_ply_count(), restored.state.get_redo_count()), (2, 0));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,183 @@
+/*
+simul.rs
+A simultaneous exhibition against the computer. Every board is an open game against the
+computer with the player as White, and the computer answers on a board as soon as the player
+moves there. The player goes from board to board with the board command, and once every board
+is finished the player's score over all of them is told.
+*/
+
+use crate::{
+    chess_core::Team,
+    chess_game::GameState,
+    chess_pgn::PgnResult,
+};
+use super::TuiGame;
+
+pub struct Simul {
+    // The boards are the open games from this one on, by their index in the list of open games.
+    first_game: usize,
+    boards: usize,
+}
+
+impl Simul {
+    // The board a game is played on, counted from 1, when it is one of the simul's.
+    pub fn get_board(&self, game: usize) -> Option<usize> {
+        (self.first_game..self.first_game + self.boards).contains(&game).then(|| game - self.first_game + 1)
+    }
+
+    pub fn get_boards(&self) -> usize {
+        self.boards
+    }
+}
+
+impl TuiGame {
+    // Opens a game against the computer for every board, keeping the current game open, and
+    // starts on the first board.
+    pub(super) fn start_simul(&mut self, boards: usize, movetime: Option<u64>) {
+        if self.simul.is_some() {
+            self.say(String::from(self.locale.simul_running()));
+            return;
+        }
+        let previous_game = self.current_game;
+        let first_game = self.other_games.len() + 1;
+        let mut name = String::new();
+        for _ in 0..boards {
+            self.set_current_aside();
+            self.current_game = self.other_games.len();
+            // Each board says who plays it, the simul says it once.
+            let said = self.messages.len();
+            self.start_single_player(false, movetime);
+            match &self.opponent {
+                Some(opponent) => {
+                    name = String::from(opponent.get_name());
+                    self.messages.truncate(said);
+                }
+                None => {
+                    // The computer couldn't be started, its error was told. Back to the game
+                    // from before.
+                    self.resume_game(previous_game);
+                    return;
+                }
+            }
+        }
+        self.simul = Some(Simul { first_game, boards });
+        self.switch_game(first_game);
+        self.say(self.locale.simul_started(&name, boards));
+        self.say(self.describe_board());
+    }
+
+    // Goes to a board of the simul, or to the next one where it is the player's move.
+    pub(super) fn switch_board(&mut self, number: Option<usize>) {
+        let simul = match &self.simul {
+            Some(s) => s,
+            None => {
+                self.say(String::from(self.locale.no_simul()));
+                return;
+            }
+        };
+        let (first_game, boards) = (simul.first_game, simul.boards);
+        let current = simul.get_board(self.current_game);
+        let number = match number {
+            Some(n) if n == 0 || n > boards => {
+                self.say(self.locale.no_board(n, boards));
+                return;
+            }
+            Some(n) => n,
+            None => {
+                // The boards after the current one come first, then the ones before it.
+                let start = current.unwrap_or(0);
+                let waiting = (1..=boards).map(|i| (start + i - 1) % boards + 1)
+                    .find(|n| Some(*n) != current && is_waiting(self.get_open_game_state(first_game + n - 1)));
+                match waiting {
+                    Some(n) => n,
+                    None => {
+                        self.say(String::from(self.locale.no_board_waiting()));
+                        return;
+                    }
+                }
+            }
+        };
+        if current == Some(number) {
+            self.say(self.locale.board_shown(number));
+            return;
+        }
+        self.switch_game(first_game + number - 1);
+        self.say(self.describe_board());
+    }
+
+    // e.g. "Board 2 of 5, White to move."
+    fn describe_board(&self) -> String {
+        match self.simul.as_ref().and_then(|s| s.get_board(self.current_game).map(|b| (b, s.boards))) {
+            Some((board, boards)) => self.locale.simul_board_status(board, boards, &self.state.get_status()),
+            None => String::new(),
+        }
+    }
+
+    // Once every board is finished, tells the player's score and ends the simul.
+    pub(super) fn check_simul_end(&mut self) {
+        let (first_game, boards) = match &self.simul {
+            Some(s) => (s.first_game, s.boards),
+            None => return,
+        };
+        let results: Vec<PgnResult> = (first_game..first_game + boards)
+            .map(|game| *self.get_open_game_state(game).get_pgn_game().get_result())
+            .collect();
+        if results.contains(&PgnResult::Unknown) {
+            return;
+        }
+        let won = results.iter().filter(|r| **r == PgnResult::WhiteWin).count();
+        let drawn = results.iter().filter(|r| **r == PgnResult::Draw).count();
+        let score = won as f64 + drawn as f64 / 2.0;
+        self.simul = None;
+        self.say(self.locale.simul_over(score, boards, won, drawn));
+    }
+}
+
+// Whether it is the player's move on a board.
+fn is_waiting(state: &GameState) -> bool {
+    !state.get_pgn_game().is_finished() && state.get_board().get_turn() == Team::Light
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_simul {
+    use crate::chess_engine::ENGINE_NAME;
+    use super::*;
+
+    #[test]
+    pub fn the_computer_answers_on_every_board() {
+        let mut game = TuiGame::new();
+        game.run_command_line("move d4");
+        game.run_command_line("simul 3 --movetime 10");
+        let messages = game.take_messages();
+        assert_eq!(messages[1], format!("Playing {ENGINE_NAME} on 3 boards, you have White on each. Move on the board shown, board <number> goes to another board and board alone to the next one waiting for your move."));
+        assert_eq!(messages[2], "Board 1 of 3, White to move.");
+        assert!(game.render().contains("Simul board 1 of 3"));
+
+        game.run_command_line("move e4");
+        let messages = game.take_messages();
+        assert!(messages[1].starts_with(&format!("{ENGINE_NAME} played ")));
+        game.run_command_line("board");
+        assert_eq!(game.take_messages()[0], "Board 2 of 3, White to move.");
+        game.run_command_line("move c4");
+        game.run_command_line("board 2");
+        assert_eq!(game.take_messages()[2], "Board 2 is already shown.");
+        game.run_command_line("board");
+        assert_eq!(game.take_messages()[0], "Board 3 of 3, White to move.");
+        game.run_command_line("board 4");
+        assert_eq!(game.take_messages()[0], "There is no board 4, the simul is played on 3 boards.");
+        // The boards are open games, the game from before is kept.
+        assert_eq!(game.get_open_game_state(0).get_ply_count(), 1);
+        assert_eq!(game.get_open_game_state(1).get_ply_count(), 2);
+
+        for number in 1..=3 {
+            game.run_command_line(&format!("board {number}"));
+            game.run_command_line("adjudicate 1/2-1/2");
+        }
+        let messages = game.take_messages();
+        assert_eq!(messages.last().unwrap(), "The simul is over, you scored 1.5 of 3 with 0 won, 3 drawn and 0 lost.");
+        game.run_command_line("board");
+        assert_eq!(game.take_messages()[0], "No simul is being played, start one with simul <boards>.");
+    }
+}
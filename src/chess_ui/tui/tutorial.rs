@@ -0,0 +1,427 @@
+/*
+tutorial.rs
+A guided tutorial for beginners. Each lesson is a series of exercises: a position is set up on the
+board, the player is told what to do and makes the move with the move command, as in a game. A
+move that doesn't do what was asked is taken back with a hint, the right one leads to the next
+exercise. The game being played is left as it was and shown again when the tutorial ends.
+*/
+
+use crate::{
+    chess_cmd::TutorialAction,
+    chess_core::{Board, BoardMove},
+    chess_pgn::ChessMove,
+};
+use super::{Drill, TuiGame};
+
+pub struct Tutorial {
+    lesson: usize,
+    step: usize,
+    board: Board,
+}
+
+struct Lesson {
+    title: &'static str,
+    steps: &'static [Step],
+}
+
+struct Step {
+    text: &'static str,
+    fen: &'static str,
+    goal: Goal,
+    // A move reaching the goal, shown when the exercise is skipped.
+    solution: &'static str,
+    hint: &'static str,
+}
+
+// What the move of an exercise has to do.
+enum Goal {
+    // Play the solution, however it is written.
+    Play,
+    // Write the solution exactly.
+    Write,
+    Check,
+    Checkmate,
+}
+
+const LESSONS: [Lesson; 6] = [
+    Lesson {
+        title: "How the pieces move",
+        steps: &[
+            Step {
+                text: "The rook moves any number of squares along a rank or a file. Move the rook from a1 to a8 by typing move Ra8: R for rook, then the square it goes to.",
+                fen: "8/8/7k/8/8/8/8/R3K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "Ra8",
+                hint: "The rook stays on the a-file and goes all the way up to a8.",
+            },
+            Step {
+                text: "The bishop moves any number of squares diagonally, so it stays on squares of one color. Move the bishop from c1 to h6.",
+                fen: "7k/8/8/8/8/8/8/2B1K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "Bh6",
+                hint: "Follow the diagonal c1, d2, e3, f4, g5 up to h6: move Bh6.",
+            },
+            Step {
+                text: "The queen moves like a rook and a bishop together. Move the queen from d1 to h5.",
+                fen: "k7/8/8/8/8/8/8/3QK3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "Qh5",
+                hint: "The queen goes up the diagonal e2, f3, g4 to h5: move Qh5.",
+            },
+            Step {
+                text: "The knight moves in an L, two squares one way and one to the side, and is the only piece that jumps over others. Jump the knight from g1 to f3, over the pawns.",
+                fen: "7k/8/8/8/8/8/5PPP/4K1N1 w - - 0 1",
+                goal: Goal::Play,
+                solution: "Nf3",
+                hint: "N stands for knight, as K is taken by the king: move Nf3.",
+            },
+            Step {
+                text: "The king moves one square in any direction. Move the king from e1 to d2.",
+                fen: "7k/8/8/8/8/8/8/4K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "Kd2",
+                hint: "d2 is diagonally up and to the left of the king: move Kd2.",
+            },
+            Step {
+                text: "Pawns move straight ahead one square, or two from their starting square. Moves of pawns are written with just the square. Push the e-pawn two squares.",
+                fen: "7k/8/8/8/8/8/4P3/4K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "e4",
+                hint: "The pawn goes from e2 to e4: move e4.",
+            },
+            Step {
+                text: "Pawns capture one square diagonally forward. Capture the pawn on d5; a pawn capture is written with the file the pawn comes from, x and the square, exd5.",
+                fen: "7k/8/8/3p4/4P3/8/8/4K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "exd5",
+                hint: "The pawn on e4 takes on d5: move exd5.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Check and checkmate",
+        steps: &[
+            Step {
+                text: "A king attacked by an enemy piece is in check. Give check to the black king with the rook.",
+                fen: "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+                goal: Goal::Check,
+                solution: "Ra8+",
+                hint: "The rook attacks the whole rank it stands on. Put it on the king's rank, the 8th.",
+            },
+            Step {
+                text: "A king in check must get out of it, by moving, blocking the attack or capturing the attacker. The rook on e8 checks your king: block the check with the bishop.",
+                fen: "4r2k/8/8/8/8/8/3B4/4K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "Be3",
+                hint: "The bishop can step onto the e-file between the rook and your king: move Be3.",
+            },
+            Step {
+                text: "A check the king can't escape is checkmate, and wins the game. The black king is shut in by its own pawns: checkmate it with the rook.",
+                fen: "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+                goal: Goal::Checkmate,
+                solution: "Ra8#",
+                hint: "The king can't leave the back rank. Check it along that rank.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Castling",
+        steps: &[
+            Step {
+                text: "Castling moves the king two squares toward a rook, and the rook to the square the king crossed, in one move. Neither may have moved before and the squares between them must be empty. Castle kingside, written O-O.",
+                fen: "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+                goal: Goal::Play,
+                solution: "O-O",
+                hint: "Type move O-O, with capital letter O's.",
+            },
+            Step {
+                text: "Castling queenside, with the rook on the a-file, is written O-O-O. Castle queenside.",
+                fen: "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1",
+                goal: Goal::Play,
+                solution: "O-O-O",
+                hint: "Type move O-O-O.",
+            },
+            Step {
+                text: "The king can't castle out of check, through an attacked square or into check. One way to castle is ruled out here: castle the other way.",
+                fen: "4k3/8/8/8/2b5/8/8/R3K2R w KQ - 0 1",
+                goal: Goal::Play,
+                solution: "O-O-O",
+                hint: "The bishop on c4 attacks f1, which the king would cross castling kingside. Castle queenside with O-O-O.",
+            },
+        ],
+    },
+    Lesson {
+        title: "En passant",
+        steps: &[
+            Step {
+                text: "A pawn moving two squares can be captured by an enemy pawn beside it as if it had moved one, on the very next move only. This is en passant. Black just played d7-d5: capture the pawn en passant.",
+                fen: "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2",
+                goal: Goal::Play,
+                solution: "exd6",
+                hint: "Your pawn on e5 captures onto d6, the square the black pawn crossed: move exd6.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Promotion",
+        steps: &[
+            Step {
+                text: "A pawn reaching the last rank becomes a queen, rook, bishop or knight of your choice, written with = and the piece's letter. Promote the pawn to a queen.",
+                fen: "8/4P2k/8/8/8/8/8/4K3 w - - 0 1",
+                goal: Goal::Play,
+                solution: "e8=Q",
+                hint: "Push the pawn to e8 and name the queen: move e8=Q.",
+            },
+        ],
+    },
+    Lesson {
+        title: "Notation",
+        steps: &[
+            Step {
+                text: "A move is written with the piece's letter, an x when it captures, and the square it goes to. Take the knight with the bishop, writing the capture.",
+                fen: "4k3/8/5n2/6B1/8/8/8/4K3 w - - 0 1",
+                goal: Goal::Write,
+                solution: "Bxf6",
+                hint: "B for bishop, x for the capture and the square: move Bxf6.",
+            },
+            Step {
+                text: "When two pieces of a kind can go to the same square, the file or rank the moving one comes from is added. Move the knight on b1 to d2.",
+                fen: "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1",
+                goal: Goal::Write,
+                solution: "Nbd2",
+                hint: "Both knights reach d2, so say it is the one on the b-file: move Nbd2.",
+            },
+            Step {
+                text: "A check is marked with + and a checkmate with #. Checkmate with the rook and mark it.",
+                fen: "6k1/5ppp/8/8/8/8/8/3R2K1 w - - 0 1",
+                goal: Goal::Write,
+                solution: "Rd8#",
+                hint: "The rook mates on the back rank: move Rd8#.",
+            },
+        ],
+    },
+];
+
+impl Tutorial {
+    // Starts a lesson, counted from 0, at its first exercise.
+    fn start(lesson: usize) -> Tutorial {
+        let board = Board::from_fen(LESSONS[lesson].steps[0].fen).unwrap_or_default();
+        Tutorial { lesson, step: 0, board }
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    fn get_step(&self) -> &'static Step {
+        &LESSONS[self.lesson].steps[self.step]
+    }
+
+    // e.g. "Lesson 2 of 6, Check and checkmate: exercise 1 of 3".
+    pub fn get_title(&self) -> String {
+        let lesson = &LESSONS[self.lesson];
+        format!("Lesson {} of {}, {}: exercise {} of {}", self.lesson + 1, LESSONS.len(), lesson.title,
+            self.step + 1, lesson.steps.len())
+    }
+
+    fn describe(&self) -> String {
+        format!("{}\n{}", self.get_title(), self.get_step().text)
+    }
+
+    // Goes on to the next exercise, returning false after the last one.
+    fn advance(&mut self) -> bool {
+        if self.step + 1 < LESSONS[self.lesson].steps.len() {
+            self.step += 1;
+        }
+        else if self.lesson + 1 < LESSONS.len() {
+            self.lesson += 1;
+            self.step = 0;
+        }
+        else {
+            return false;
+        }
+        self.board = Board::from_fen(self.get_step().fen).unwrap_or_default();
+        true
+    }
+
+    // Checks a move against the exercise. A move that doesn't reach the goal gives the reason.
+    fn check(&self, typed: &str, board_move: BoardMove) -> Result<(), String> {
+        let step = self.get_step();
+        let mut after = self.board.clone();
+        after.make_move(board_move);
+        let reached = match step.goal {
+            Goal::Play => solution_move(&self.board, step) == Some(board_move),
+            Goal::Write => typed == step.solution,
+            Goal::Check => after.is_in_check(after.get_turn()),
+            Goal::Checkmate => after.is_checkmate(),
+        };
+        if reached {
+            Ok(())
+        }
+        else if matches!(step.goal, Goal::Write) && solution_move(&self.board, step) == Some(board_move) {
+            Err(format!("That is the right move, but write it as asked. {}", step.hint))
+        }
+        else {
+            Err(step.hint.to_string())
+        }
+    }
+}
+
+fn solution_move(board: &Board, step: &Step) -> Option<BoardMove> {
+    ChessMove::from(step.solution).ok().and_then(|m| board.resolve_move(&m).ok())
+}
+
+impl TuiGame {
+    pub(super) fn manage_tutorial(&mut self, action: TutorialAction) {
+        match action {
+            TutorialAction::Start { lesson } => {
+                let lesson = lesson.unwrap_or(1);
+                if lesson == 0 || lesson > LESSONS.len() {
+                    self.say(self.locale.no_lesson(lesson));
+                    return;
+                }
+                self.stop_clock();
+                let tutorial = Tutorial::start(lesson - 1);
+                self.say(format!("{}\n{}", tutorial.describe(), self.locale.tutorial_started()));
+                self.drill = Some(Drill::Tutorial(tutorial));
+            }
+            TutorialAction::List => {
+                let lines: Vec<String> = LESSONS.iter().enumerate()
+                    .map(|(i, lesson)| format!("{}. {} ({} exercises)", i + 1, lesson.title, lesson.steps.len()))
+                    .collect();
+                self.say(format!("{}\n{}", lines.join("\n"), self.locale.start_lesson()));
+            }
+            TutorialAction::Hint => match self.get_tutorial().map(|t| t.get_step()) {
+                Some(step) => self.say(step.hint.to_string()),
+                None => self.say(String::from(self.locale.start_tutorial())),
+            },
+            TutorialAction::Skip => match self.get_tutorial().map(|t| t.get_step()) {
+                Some(step) => {
+                    self.say(self.locale.tutorial_answer(step.solution));
+                    self.advance_tutorial();
+                }
+                None => self.say(String::from(self.locale.start_tutorial())),
+            },
+            TutorialAction::Stop => self.stop_tutorial(),
+        }
+    }
+
+    pub(super) fn stop_tutorial(&mut self) {
+        match self.get_tutorial() {
+            Some(_) => {
+                self.drill = None;
+                self.say(String::from(self.locale.left_tutorial()));
+            }
+            None => self.say(String::from(self.locale.tutorial_not_running())),
+        }
+    }
+
+    fn get_tutorial(&mut self) -> Option<&mut Tutorial> {
+        match &mut self.drill {
+            Some(Drill::Tutorial(tutorial)) => Some(tutorial),
+            _ => None,
+        }
+    }
+
+    // Plays a move in the tutorial's exercise. Only a move doing what was asked stays on the board.
+    pub(super) fn play_tutorial_move(&mut self, typed: &str) {
+        let locale = self.locale;
+        let tutorial = match self.get_tutorial() {
+            Some(t) => t,
+            None => return,
+        };
+        let typed = typed.trim();
+        let checked = ChessMove::from(typed)
+            .map_err(|e| locale.invalid_move(typed, &e.to_string()))
+            .and_then(|m| tutorial.board.resolve_move(&m).map_err(|e| locale.illegal_move(typed, &e.to_string())))
+            .and_then(|board_move| tutorial.check(typed, board_move).map(|()| board_move));
+        match checked {
+            Ok(board_move) => {
+                let played = tutorial.board.to_chess_move(board_move);
+                self.say(locale.correct(&played.to_string()));
+                self.advance_tutorial();
+            }
+            Err(message) => self.say(locale.try_again(&message)),
+        }
+    }
+
+    fn advance_tutorial(&mut self) {
+        let advanced = self.get_tutorial().is_some_and(|t| t.advance());
+        match self.get_tutorial() {
+            Some(tutorial) if advanced => {
+                let description = tutorial.describe();
+                self.say(description);
+            }
+            _ => {
+                self.drill = None;
+                self.say(String::from(self.locale.tutorial_finished()));
+            }
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_tutorial {
+    use super::*;
+
+    #[test]
+    pub fn every_exercise_is_solved_by_its_solution() {
+        for (lesson, steps) in LESSONS.iter().enumerate() {
+            for (step, exercise) in steps.steps.iter().enumerate() {
+                let board = Board::from_fen(exercise.fen).unwrap_or_else(|e| panic!("{}: {e}", exercise.fen));
+                let tutorial = Tutorial { lesson, step, board };
+                let board_move = solution_move(&tutorial.board, exercise).unwrap_or_else(|| panic!("{}", exercise.solution));
+                assert_eq!(tutorial.check(exercise.solution, board_move), Ok(()), "{}", exercise.solution);
+                // Solutions are written the way the game records them.
+                assert_eq!(tutorial.board.to_chess_move(board_move).to_string(), exercise.solution);
+            }
+        }
+    }
+
+    #[test]
+    pub fn moves_are_checked_against_the_exercise() {
+        let mut game = TuiGame::new();
+        game.run_command_line("move e4");
+        game.take_messages();
+        game.run_command_line("tutorial start 3");
+        assert!(game.take_messages()[0].starts_with("Lesson 3 of 6, Castling: exercise 1 of 3\n"));
+        assert!(game.render().contains("Castling: exercise 1 of 3"));
+
+        game.run_command_line("move Kf1");
+        assert_eq!(game.take_messages()[0], "Type move O-O, with capital letter O's. Try again.");
+        game.run_command_line("move O-O");
+        let messages = game.take_messages();
+        assert_eq!(messages[0], "Correct, O-O!");
+        assert!(messages[1].starts_with("Lesson 3 of 6, Castling: exercise 2 of 3\n"));
+
+        game.run_command_line("tutorial skip");
+        game.run_command_line("move O-O");
+        assert_eq!(game.take_messages()[2], "Illegal move O-O: no legal move matches it in this position. Try again.");
+        game.run_command_line("undo");
+        assert_eq!(game.take_messages()[0], "The tutorial is running, use tutorial stop to leave it first.");
+
+        // The game is left as it was.
+        game.run_command_line("tutorial stop");
+        assert_eq!(game.take_messages()[0], "Left the tutorial, back to the game.");
+        assert_eq!(game.state.get_ply_count(), 1);
+    }
+
+    #[test]
+    pub fn notation_exercises_want_the_move_written_as_asked() {
+        let mut game = TuiGame::new();
+        game.run_command_line("tutorial start 6");
+        game.take_messages();
+        game.run_command_line("move Bf6");
+        assert!(game.take_messages()[0].starts_with("That is the right move, but write it as asked."));
+        game.run_command_line("move Bxf6");
+        game.run_command_line("move Nd2");
+        assert!(game.take_messages()[2].starts_with("Illegal move Nd2: it is ambiguous"));
+        game.run_command_line("move Nbd2");
+        game.run_command_line("move Rd8#");
+        let messages = game.take_messages();
+        assert_eq!(messages.last().unwrap(), "You finished the tutorial! Start a game with new, or play the computer with new --vs-engine.");
+        assert!(game.drill.is_none());
+    }
+}
@@ -0,0 +1,248 @@
+/*
+watch.rs
+Engine against engine games. Each engine searches in its own process while the interface keeps
+running, the interface advances the game whenever it checks in. Moves are recorded with the
+engine's evaluation as a comment, and the finished game is printed as PGN. Games the configured
+adjudication rules decide end early.
+*/
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chess_adjudication::{AdjudicationRules, Adjudicator},
+    chess_core::{Board, Team},
+    chess_pgn::{PgnAnnotation, PgnResult},
+    chess_uci::{EngineScore, SearchLimit, SearchResult, UciEngine, UciError},
+};
+use super::TuiGame;
+
+// How often a watched game is checked on.
+pub const WATCH_TICK: Duration = Duration::from_millis(50);
+
+pub struct WatchSession {
+    // The engines playing White and Black.
+    engines: [UciEngine; 2],
+    limit: SearchLimit,
+    delay: Duration,
+    // When the next search may start, the delay after the last move.
+    next_search: Instant,
+    // The latest evaluation, from White's side.
+    score: Option<EngineScore>,
+    adjudicator: Adjudicator,
+}
+
+impl WatchSession {
+    // The latest evaluation, from White's side.
+    pub fn get_score(&self) -> Option<EngineScore> {
+        self.score
+    }
+}
+
+impl TuiGame {
+    pub(super) fn start_watch(&mut self, white: Option<PathBuf>, black: Option<PathBuf>, delay: u64, movetime: Option<u64>) {
+        let configured = self.config.get_engine_path().cloned();
+        let (white, black) = match (white.or(configured.clone()), black.or(configured)) {
+            (Some(w), Some(b)) => (w, b),
+            _ => {
+                self.say(String::from(self.locale.no_engine_to_watch()));
+                return;
+            }
+        };
+        let engines = match start_engines(&white, &black) {
+            Ok(e) => e,
+            Err(e) => {
+                self.say(self.locale.engines_not_started(&e.to_string()));
+                return;
+            }
+        };
+
+        self.new_game();
+        // The engines keep their own time, and games between them aren't rated.
+        self.clock = None;
+        self.rated = false;
+        let pgn_game = self.state.get_pgn_game_mut();
+        pgn_game.set_event(String::from("Engine game"));
+        pgn_game.set_white(engines[0].get_name().clone());
+        pgn_game.set_black(engines[1].get_name().clone());
+        self.say(self.locale.watching(engines[0].get_name(), engines[1].get_name()));
+
+        let limit = match movetime {
+            Some(ms) => SearchLimit::MoveTime(Duration::from_millis(ms)),
+            None => SearchLimit::Depth(self.config.get_engine_depth()),
+        };
+        self.watch = Some(WatchSession {
+            engines,
+            limit,
+            delay: Duration::from_millis(delay),
+            next_search: Instant::now(),
+            score: None,
+            adjudicator: Adjudicator::new(AdjudicationRules::from_config(&self.config)),
+        });
+    }
+
+    pub(super) fn stop_watch(&mut self) {
+        self.watch = None;
+        self.say(String::from(self.locale.stopped_watching()));
+    }
+
+    // Starts the next search once the delay after the last move has passed, and plays the move
+    // of a search that ended.
+    pub(super) fn advance_watch(&mut self) {
+        let watch = match &mut self.watch {
+            Some(w) => w,
+            None => return,
+        };
+        let turn = self.state.get_board().get_turn();
+        let engine = &mut watch.engines[team_index(turn)];
+        let polled = if engine.is_searching() {
+            engine.poll_search()
+        }
+        else if Instant::now() >= watch.next_search {
+            engine.start_search(&self.state.get_board_moves(), watch.limit).map(|()| None)
+        }
+        else {
+            Ok(None)
+        };
+        match polled {
+            Ok(Some(result)) => self.play_engine_move(turn, result),
+            Ok(None) => (),
+            Err(e) => {
+                self.watch = None;
+                self.say(self.locale.watched_engine_failed(turn, &e.to_string()));
+            }
+        }
+    }
+
+    fn play_engine_move(&mut self, turn: Team, result: SearchResult) {
+        let board = self.state.get_board();
+        let number = format!("{}{}", board.get_fullmove_number(), if turn == Team::Light { "." } else { "..." });
+        let mut annotation = PgnAnnotation::new();
+        let evaluation = result.get_score().map(|s| match result.get_depth() {
+            Some(depth) => format!("{s}/{depth}"),
+            None => s.to_string(),
+        });
+        if let Some(e) = &evaluation {
+            annotation.add_comment(e.clone());
+        }
+
+        match self.state.play_board_move(result.get_best_move(), annotation) {
+            Ok(played) => {
+                match &evaluation {
+                    Some(e) => self.say(format!("{number} {played} ({e})")),
+                    None => self.say(format!("{number} {played}")),
+                }
+                let score = result.get_score().map(|s| if turn == Team::Dark { s.negate() } else { s });
+                if let Some(watch) = &mut self.watch {
+                    watch.score = score.or(watch.score);
+                    watch.next_search = Instant::now() + watch.delay;
+                }
+                self.end_watched_game(score);
+            }
+            Err(e) => {
+                self.watch = None;
+                self.say(self.locale.watched_move_failed(turn, &result.get_best_move().to_string(), &e.to_string()));
+            }
+        }
+    }
+
+    // Ends the game once the position or the adjudication rules decide it, and shows the game
+    // record. The score is the evaluation of the move just played, from White's side.
+    fn end_watched_game(&mut self, score: Option<EngineScore>) {
+        let board = self.state.get_board();
+        let ending = match game_ending(board) {
+            Some(ending) => Some((ending, false)),
+            None => self.watch.as_mut().and_then(|w| w.adjudicator.adjudicate(board, score)).map(|ending| (ending, true)),
+        };
+        let ((result, reason), adjudicated) = match ending {
+            Some(ending) => ending,
+            None => return,
+        };
+        self.watch = None;
+        self.state.end_game(result);
+        let pgn_game = self.state.get_pgn_game_mut();
+        pgn_game.set_result_comment(String::from(reason));
+        if adjudicated {
+            pgn_game.set_tag("Termination", String::from("adjudication"));
+        }
+        let record = pgn_game.to_string();
+        self.say(self.locale.watched_game_over(result, reason));
+        self.say(record);
+    }
+}
+
+fn start_engines(white: &Path, black: &Path) -> Result<[UciEngine; 2], UciError> {
+    let mut white = UciEngine::start(white)?;
+    let mut black = UciEngine::start(black)?;
+    white.new_game()?;
+    black.new_game()?;
+    Ok([white, black])
+}
+
+fn team_index(team: Team) -> usize {
+    match team {
+        Team::Light => 0,
+        Team::Dark => 1,
+    }
+}
+
+// The result of a position that ends the game and why, for the rules that end it without a
+// claim: checkmate, stalemate, bare kings and the fifty move rule.
+pub fn game_ending(board: &Board) -> Option<(PgnResult, &'static str)> {
+    if board.is_checkmate() {
+        let result = match board.get_turn() {
+            Team::Light => PgnResult::BlackWin,
+            Team::Dark => PgnResult::WhiteWin,
+        };
+        Some((result, "checkmate"))
+    }
+    else if board.is_stalemate() {
+        Some((PgnResult::Draw, "stalemate"))
+    }
+    else if !board.can_checkmate(Team::Light) && !board.can_checkmate(Team::Dark) {
+        Some((PgnResult::Draw, "insufficient material"))
+    }
+    else if board.get_halfmove_clock() >= 100 {
+        Some((PgnResult::Draw, "the fifty move rule"))
+    }
+    else {
+        None
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_watch {
+    use super::*;
+
+    #[test]
+    pub fn decided_positions_end_the_game() {
+        assert_eq!(game_ending(&Board::new()), None);
+        let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(game_ending(&mated), Some((PgnResult::BlackWin, "checkmate")));
+        let stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game_ending(&stalemate), Some((PgnResult::Draw, "stalemate")));
+        let bare_kings = Board::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 60").unwrap();
+        assert_eq!(game_ending(&bare_kings), Some((PgnResult::Draw, "insufficient material")));
+        let same_color_bishops = Board::from_fen("8/8/4k3/2b5/8/3K4/8/6B1 w - - 0 60").unwrap();
+        assert_eq!(game_ending(&same_color_bishops), Some((PgnResult::Draw, "insufficient material")));
+        // Either knight could mate with the other side's help.
+        let knights = Board::from_fen("8/8/4k3/2n5/8/3K4/8/7N w - - 0 60").unwrap();
+        assert_eq!(game_ending(&knights), None);
+        let fifty_moves = Board::from_fen("8/8/4k3/8/8/3K4/8/7R w - - 100 90").unwrap();
+        assert_eq!(game_ending(&fifty_moves), Some((PgnResult::Draw, "the fifty move rule")));
+    }
+
+    #[test]
+    pub fn watching_needs_an_engine() {
+        let mut game = TuiGame::new();
+        game.run_command_line("watch");
+        assert_eq!(game.take_messages()[0], "No engine to watch, use --white and --black or config set engine_path.");
+        game.run_command_line("watch --white /nonexistent/engine --black /nonexistent/engine");
+        assert!(game.take_messages()[0].starts_with("Could not start the engines: could not start /nonexistent/engine"));
+        assert!(game.watch.is_none());
+    }
+}
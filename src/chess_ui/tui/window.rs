@@ -0,0 +1,161 @@
+/*
+window.rs
+The game played in a window, built with the gui feature. The window, in chess_ui/gui.rs, draws the
+views it is sent and sends back commands, the moves dragged on its board and the lines typed below
+it. The game runs on a thread of its own through the same loop as the terminal interfaces, so the
+clocks, the computer's replies, the autosave and every command work as they do there.
+*/
+
+use std::{
+    io,
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chess_clock::format_clock_time,
+    chess_cmd::ChessArgs,
+    chess_core::{Board, BoardMove, Team},
+};
+use super::{
+    end_game,
+    frontend::{run_frontend, FrontendInput, UiFrontend},
+    screen::move_list_entries,
+    start_game,
+    TuiGame,
+};
+
+// What the window shows of the game.
+pub struct WindowView {
+    pub board: Board,
+    pub flipped: bool,
+    // The move that led to the position shown, highlighted on the board.
+    pub last_move: Option<BoardMove>,
+    // One entry per move number, e.g. "12. Nxe4 Qd5".
+    pub moves: Vec<String>,
+    // The time left of White and Black, when the game has clocks, and whose clock runs.
+    pub clocks: Option<[String; 2]>,
+    pub clock_running: Option<Team>,
+    pub status: Vec<String>,
+}
+
+// What the game sends the window.
+pub enum WindowUpdate {
+    View(Box<WindowView>),
+    Messages(Vec<String>),
+    // The player quit, the window closes.
+    Closed,
+}
+
+struct WindowFrontend {
+    commands: Receiver<String>,
+    updates: Sender<WindowUpdate>,
+    // Asks the window to draw again, it only does on its own when the player does something.
+    wake: Box<dyn Fn() + Send>,
+}
+
+impl WindowFrontend {
+    fn send(&self, update: WindowUpdate) {
+        if self.updates.send(update).is_ok() {
+            (self.wake)();
+        }
+    }
+}
+
+impl UiFrontend for WindowFrontend {
+    fn render(&mut self, game: &TuiGame) -> io::Result<()> {
+        self.send(WindowUpdate::View(Box::new(game.get_window_view())));
+        Ok(())
+    }
+
+    fn notify(&mut self, messages: Vec<String>) {
+        if !messages.is_empty() {
+            self.send(WindowUpdate::Messages(messages));
+        }
+    }
+
+    fn read_command(&mut self, _game: &mut TuiGame, timeout: Duration) -> io::Result<FrontendInput> {
+        Ok(match self.commands.recv_timeout(timeout) {
+            Ok(line) => FrontendInput::Command(line),
+            Err(RecvTimeoutError::Timeout) => FrontendInput::Idle,
+            // The window was closed.
+            Err(RecvTimeoutError::Disconnected) => FrontendInput::Closed,
+        })
+    }
+
+    // The clocks count down in the window.
+    fn shows_live_updates(&self) -> bool {
+        true
+    }
+
+    fn close(&mut self) {
+        self.send(WindowUpdate::Closed);
+    }
+}
+
+// Plays the game for a window until the player quits or closes it. Anything asked before the
+// game starts, such as restoring an autosaved game, is asked at the terminal.
+pub fn window_game(args: &ChessArgs, commands: Receiver<String>, updates: Sender<WindowUpdate>, wake: Box<dyn Fn() + Send>) {
+    let mut game = start_game(args);
+    game.say(String::from(game.locale.window_welcome()));
+    let mut frontend = WindowFrontend { commands, updates, wake };
+    // Sending to the window doesn't fail, it only stops being read once the window is closed.
+    let _ = run_frontend(&mut game, &mut frontend);
+    end_game(&mut game);
+}
+
+impl TuiGame {
+    fn get_window_view(&self) -> WindowView {
+        let now = Instant::now();
+        let status = match &self.drill {
+            Some(drill) => vec![drill.get_title()],
+            None => self.get_status_lines(),
+        };
+        WindowView {
+            board: self.get_shown_board().clone(),
+            flipped: self.shown_view.as_ref().unwrap_or(&self.view).flipped,
+            last_move: self.drill.is_none().then(|| self.state.get_viewed_board_move().map(|(_, m)| m)).flatten(),
+            moves: move_list_entries(&self.state),
+            clocks: self.clock.as_ref().map(|c| [Team::Light, Team::Dark].map(|team| format_clock_time(c.get_remaining(team, now)))),
+            clock_running: self.clock.as_ref().and_then(|c| c.get_running()),
+            status,
+        }
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_window {
+    use super::*;
+    use std::sync::mpsc;
+    use crate::chess_core::BoardSquare;
+
+    #[test]
+    pub fn window_gets_the_view_and_messages_of_its_commands() {
+        let (command_sender, commands) = mpsc::channel();
+        let (updates, update_receiver) = mpsc::channel();
+        for command in ["move e4", "flip", "quit"] {
+            command_sender.send(String::from(command)).unwrap();
+        }
+        let mut game = TuiGame::new();
+        game.take_messages();
+        let mut frontend = WindowFrontend { commands, updates, wake: Box::new(|| ()) };
+        run_frontend(&mut game, &mut frontend).unwrap();
+
+        let updates: Vec<WindowUpdate> = update_receiver.try_iter().collect();
+        assert!(matches!(updates.last(), Some(WindowUpdate::Closed)));
+        let messages: Vec<String> = updates.iter()
+            .filter_map(|u| match u { WindowUpdate::Messages(m) => Some(m.clone()), _ => None })
+            .flatten()
+            .collect();
+        assert_eq!(messages[0], "Played e4");
+        let view = updates.iter().rev().find_map(|u| match u { WindowUpdate::View(v) => Some(v), _ => None }).unwrap();
+        assert_eq!(view.moves, ["1. e4"]);
+        assert_eq!(view.board.get_turn(), Team::Dark);
+        assert!(view.flipped);
+        let last_move = view.last_move.unwrap();
+        assert_eq!((last_move.get_from(), last_move.get_to()), (BoardSquare::from("e2").unwrap(), BoardSquare::from("e4").unwrap()));
+        assert!(view.clocks.is_none());
+    }
+}
@@ -0,0 +1,221 @@
+/*
+chess_verify.rs
+The verify mode of the program, which counts the positions the move generator reaches from a set
+of well known perft positions and compares them with the published counts:
+
+rust_chess verify              every count, about ten seconds for a release build
+rust_chess verify --depth 3    only the counts up to depth 3
+
+Besides the start position, Kiwipete and the other positions of the Chess Programming Wiki, the
+table has the edge cases of Martin Sedlak's test suite: en passant captures that would expose the
+king, castling that gives check, promotions out of and into check, and stalemates. A count that
+differs is a bug in the move generator, or a build that miscompiled it.
+*/
+
+use std::time::Instant;
+
+use crate::{chess_cmd::VerifyArgs, chess_core::Board};
+
+// A position and its number of leaf nodes at each depth.
+pub struct PerftPosition {
+    pub name: &'static str,
+    pub fen: &'static str,
+    pub counts: &'static [(u32, u64)],
+}
+
+pub const POSITIONS: &[PerftPosition] = &[
+    PerftPosition {
+        name: "start position",
+        fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        counts: &[(1, 20), (2, 400), (3, 8902), (4, 197281), (5, 4865609)],
+    },
+    PerftPosition {
+        name: "Kiwipete",
+        fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        counts: &[(1, 48), (2, 2039), (3, 97862), (4, 4085603)],
+    },
+    PerftPosition {
+        name: "rook endgame with en passant",
+        fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        counts: &[(1, 14), (2, 191), (3, 2812), (4, 43238), (5, 674624)],
+    },
+    PerftPosition {
+        name: "promotions and castling",
+        fen: "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        counts: &[(1, 6), (2, 264), (3, 9467), (4, 422333)],
+    },
+    PerftPosition {
+        name: "promotions and castling, mirrored",
+        fen: "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        counts: &[(1, 6), (2, 264), (3, 9467), (4, 422333)],
+    },
+    PerftPosition {
+        name: "promotion with capture",
+        fen: "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        counts: &[(1, 44), (2, 1486), (3, 62379), (4, 2103487)],
+    },
+    PerftPosition {
+        name: "middlegame",
+        fen: "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        counts: &[(1, 46), (2, 2079), (3, 89890), (4, 3894594)],
+    },
+    PerftPosition {
+        name: "illegal en passant, White's king",
+        fen: "3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1",
+        counts: &[(6, 1134888)],
+    },
+    PerftPosition {
+        name: "illegal en passant, Black's king",
+        fen: "8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1",
+        counts: &[(6, 1015133)],
+    },
+    PerftPosition {
+        name: "en passant capture gives check",
+        fen: "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        counts: &[(6, 1440467)],
+    },
+    PerftPosition {
+        name: "short castling gives check",
+        fen: "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+        counts: &[(6, 661072)],
+    },
+    PerftPosition {
+        name: "long castling gives check",
+        fen: "3k4/8/8/8/8/8/8/R3K3 w Q - 0 1",
+        counts: &[(6, 803711)],
+    },
+    PerftPosition {
+        name: "castling rights",
+        fen: "r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1",
+        counts: &[(4, 1274206)],
+    },
+    PerftPosition {
+        name: "castling prevented",
+        fen: "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1",
+        counts: &[(4, 1720476)],
+    },
+    PerftPosition {
+        name: "promotion out of check",
+        fen: "2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1",
+        counts: &[(6, 3821001)],
+    },
+    PerftPosition {
+        name: "discovered check",
+        fen: "8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1",
+        counts: &[(5, 1004658)],
+    },
+    PerftPosition {
+        name: "promotion gives check",
+        fen: "4k3/1P6/8/8/8/8/K7/8 w - - 0 1",
+        counts: &[(6, 217342)],
+    },
+    PerftPosition {
+        name: "underpromotion gives check",
+        fen: "8/P1k5/K7/8/8/8/8/8 w - - 0 1",
+        counts: &[(6, 92683)],
+    },
+    PerftPosition {
+        name: "self stalemate",
+        fen: "K1k5/8/P7/8/8/8/8/8 w - - 0 1",
+        counts: &[(6, 2217)],
+    },
+    PerftPosition {
+        name: "stalemate and checkmate",
+        fen: "8/k1P5/8/1K6/8/8/8/8 w - - 0 1",
+        counts: &[(7, 567584)],
+    },
+    PerftPosition {
+        name: "double check",
+        fen: "8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1",
+        counts: &[(4, 23527)],
+    },
+];
+
+// The outcome of counting one position to one depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerftCheck {
+    pub name: &'static str,
+    pub depth: u32,
+    pub expected: u64,
+    pub nodes: u64,
+}
+
+impl PerftCheck {
+    pub fn passed(&self) -> bool {
+        self.nodes == self.expected
+    }
+}
+
+impl std::fmt::Display for PerftCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.passed() {
+            write!(f, "ok    {}, depth {}: {} nodes", self.name, self.depth, self.nodes)
+        } else {
+            write!(f, "FAIL  {}, depth {}: {} nodes, expected {}", self.name, self.depth, self.nodes, self.expected)
+        }
+    }
+}
+
+// Counts every position to each of its depths up to the given one, reporting each count as it
+// is made, and gives back the counts that differ.
+pub fn verify(positions: &[PerftPosition], max_depth: Option<u32>, mut report: impl FnMut(&PerftCheck)) -> Vec<PerftCheck> {
+    let mut failed = Vec::new();
+    for position in positions {
+        let board = Board::from_fen(position.fen).expect("the perft positions are valid FEN");
+        for &(depth, expected) in position.counts {
+            if max_depth.is_some_and(|max| depth > max) {
+                continue;
+            }
+            let check = PerftCheck { name: position.name, depth, expected, nodes: board.perft(depth) };
+            report(&check);
+            if !check.passed() {
+                failed.push(check);
+            }
+        }
+    }
+    failed
+}
+
+pub fn verify_main(verify_args: &VerifyArgs) {
+    let start = Instant::now();
+    let mut checked = 0;
+    let failed = verify(POSITIONS, verify_args.depth, |check| {
+        checked += 1;
+        println!("{check}");
+    });
+    let seconds = start.elapsed().as_secs_f64();
+    if failed.is_empty() {
+        println!("All {checked} counts match, in {seconds:.1}s.");
+    } else {
+        println!("{} of {checked} counts differ, in {seconds:.1}s.", failed.len());
+        std::process::exit(1);
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_verify {
+    use super::*;
+
+    #[test]
+    pub fn counts_are_checked_up_to_the_depth() {
+        const TABLE: &[PerftPosition] = &[
+            PerftPosition { name: "start position", fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", counts: &[(1, 20), (2, 401), (3, 8902)] },
+            PerftPosition { name: "self stalemate", fen: "K1k5/8/P7/8/8/8/8/8 w - - 0 1", counts: &[(6, 2217)] },
+        ];
+        let mut reported = Vec::new();
+        let failed = verify(TABLE, Some(2), |check| reported.push(check.to_string()));
+        assert_eq!(reported, vec![
+            "ok    start position, depth 1: 20 nodes",
+            "FAIL  start position, depth 2: 400 nodes, expected 401",
+        ]);
+        assert_eq!(failed, vec![PerftCheck { name: "start position", depth: 2, expected: 401, nodes: 400 }]);
+    }
+
+    #[test]
+    pub fn bundled_positions_pass_at_low_depths() {
+        assert!(verify(POSITIONS, Some(2), |_| ()).is_empty());
+        assert!(POSITIONS.iter().all(|p| Board::from_fen(p.fen).is_ok()));
+    }
+}
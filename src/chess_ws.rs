@@ -0,0 +1,332 @@
+/*
+chess_ws.rs
+A WebSocket server for remote players, such as a browser frontend or a bot. Clients send and
+receive JSON messages, one per WebSocket text message, each naming its kind in a "type" field:
+
+  client: {"type": "move", "move": "Nf3"}   plays a move in SAN
+          {"type": "state"}                 asks for the game as it stands
+  server: {"type": "welcome", "side": "black"}   the side the client plays, null for spectators
+          {"type": "state", "fen": ..., "moves": [...], "clock": {...}, ...}
+          {"type": "error", "message": ...}
+
+Every client is served on a thread of its own. What clients send arrives as events for whoever
+runs the server to act on, the server itself knows nothing about the game.
+*/
+
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use serde::{Deserialize, Serialize};
+use tungstenite::{HandshakeError, Message, WebSocket};
+
+use crate::{
+    chess_clock::ChessClock,
+    chess_core::Team,
+    chess_game::GameState,
+};
+
+// How often the server checks for new connections, and clients for messages to send, while
+// nothing comes in.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub type ClientId = usize;
+
+// Messages from clients.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Move {
+        #[serde(rename = "move")]
+        san: String,
+    },
+    State,
+}
+
+// Messages to clients.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome { side: Option<String> },
+    State(GameSnapshot),
+    Error { message: String },
+}
+
+// The game as clients see it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GameSnapshot {
+    fen: String,
+    // The moves so far in SAN.
+    moves: Vec<String>,
+    turn: String,
+    // e.g. "White to move" or "Checkmate, Black wins".
+    status: String,
+    result: String,
+    white: String,
+    black: String,
+    clock: Option<ClockSnapshot>,
+}
+
+// The time left on each clock when the snapshot was taken, and whose clock is running.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClockSnapshot {
+    white_ms: u64,
+    black_ms: u64,
+    running: Option<String>,
+}
+
+// What happened on the server since it was last polled.
+#[derive(Debug, PartialEq)]
+pub enum WsEvent {
+    Connected(ClientId),
+    Message(ClientId, ClientMessage),
+    Disconnected(ClientId),
+}
+
+pub struct WsServer {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    events: Receiver<ClientEvent>,
+    // Where messages to each connected client go.
+    clients: HashMap<ClientId, Sender<String>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+// What client threads tell the server, a new client along with where its messages go.
+enum ClientEvent {
+    Connected(ClientId, Sender<String>),
+    Message(ClientId, ClientMessage),
+    Disconnected(ClientId),
+}
+
+impl GameSnapshot {
+    pub fn new(state: &GameState, clock: Option<&ChessClock>) -> GameSnapshot {
+        let board = state.get_board();
+        let pgn_game = state.get_pgn_game();
+        let now = Instant::now();
+        GameSnapshot {
+            fen: board.to_fen(),
+            moves: pgn_game.get_plies().iter().map(|(m, _)| m.to_string()).collect(),
+            turn: side_name(board.get_turn()),
+            status: state.get_status().to_string(),
+            result: pgn_game.get_result().to_string(),
+            white: pgn_game.get_white().clone(),
+            black: pgn_game.get_black().clone(),
+            clock: clock.map(|c| ClockSnapshot {
+                white_ms: c.get_remaining(Team::Light, now).as_millis() as u64,
+                black_ms: c.get_remaining(Team::Dark, now).as_millis() as u64,
+                running: c.get_running().map(side_name),
+            }),
+        }
+    }
+
+    // Whether the snapshots show the same game and position, the clocks aside.
+    pub fn same_game(&self, other: &GameSnapshot) -> bool {
+        GameSnapshot { clock: None, ..self.clone() } == GameSnapshot { clock: None, ..other.clone() }
+    }
+}
+
+// Sides as clients name them, "white" and "black".
+pub fn side_name(team: Team) -> String {
+    team.to_string().to_lowercase()
+}
+
+impl WsServer {
+    // Listens for clients on the address, e.g. 127.0.0.1:8080.
+    pub fn start(address: &str) -> io::Result<WsServer> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, events) = mpsc::channel();
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || accept_clients(listener, sender, stopped));
+        Ok(WsServer { address, stop, events, clients: HashMap::new(), thread: Some(thread) })
+    }
+
+    pub fn get_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn get_client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    // Takes what happened since the last poll.
+    pub fn poll(&mut self) -> Vec<WsEvent> {
+        let mut events = Vec::new();
+        for event in self.events.try_iter() {
+            events.push(match event {
+                ClientEvent::Connected(id, sender) => {
+                    self.clients.insert(id, sender);
+                    WsEvent::Connected(id)
+                }
+                ClientEvent::Message(id, message) => WsEvent::Message(id, message),
+                ClientEvent::Disconnected(id) => {
+                    self.clients.remove(&id);
+                    WsEvent::Disconnected(id)
+                }
+            });
+        }
+        events
+    }
+
+    pub fn send(&self, id: ClientId, message: &ServerMessage) {
+        if let Some(client) = self.clients.get(&id) {
+            let _ = client.send(to_json(message));
+        }
+    }
+
+    pub fn broadcast(&self, message: &ServerMessage) {
+        let text = to_json(message);
+        for client in self.clients.values() {
+            let _ = client.send(text.clone());
+        }
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn to_json(message: &ServerMessage) -> String {
+    serde_json::to_string(message).unwrap_or_default()
+}
+
+// Serves every client that connects until stopped, then waits for their threads to end.
+fn accept_clients(listener: TcpListener, events: Sender<ClientEvent>, stop: Arc<AtomicBool>) {
+    let mut clients = Vec::new();
+    let mut next_id: ClientId = 1;
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let (id, events, stop) = (next_id, events.clone(), stop.clone());
+                next_id += 1;
+                clients.push(thread::spawn(move || serve_client(stream, id, events, stop)));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(_) => thread::sleep(POLL_INTERVAL),
+        }
+        clients.retain(|c: &JoinHandle<()>| !c.is_finished());
+    }
+    for client in clients {
+        let _ = client.join();
+    }
+}
+
+// Passes a client's messages on as events and sends it what the server has for it, until it
+// leaves or the server stops.
+fn serve_client(stream: TcpStream, id: ClientId, events: Sender<ClientEvent>, stop: Arc<AtomicBool>) {
+    // Reads time out so the thread gets to send messages and notice the server stopping.
+    if stream.set_nonblocking(false).and_then(|()| stream.set_read_timeout(Some(POLL_INTERVAL))).is_err() {
+        return;
+    }
+    let mut handshake = tungstenite::accept(stream);
+    let mut socket = loop {
+        match handshake {
+            Ok(socket) => break socket,
+            Err(HandshakeError::Interrupted(_)) if stop.load(Ordering::Relaxed) => return,
+            Err(HandshakeError::Interrupted(mid)) => handshake = mid.handshake(),
+            Err(HandshakeError::Failure(_)) => return,
+        }
+    };
+    let (sender, outgoing) = mpsc::channel();
+    if events.send(ClientEvent::Connected(id, sender)).is_err() {
+        return;
+    }
+    while !stop.load(Ordering::Relaxed) && exchange_messages(&mut socket, id, &events, &outgoing) {}
+    let _ = socket.close(None);
+    let _ = socket.flush();
+    let _ = events.send(ClientEvent::Disconnected(id));
+}
+
+// Sends what is waiting for the client and takes in what it sent. Returns false once the
+// connection is closed.
+fn exchange_messages(socket: &mut WebSocket<TcpStream>, id: ClientId, events: &Sender<ClientEvent>, outgoing: &Receiver<String>) -> bool {
+    for text in outgoing.try_iter() {
+        if socket.send(Message::Text(text)).is_err() {
+            return false;
+        }
+    }
+    match socket.read() {
+        Ok(Message::Text(text)) => match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(message) => events.send(ClientEvent::Message(id, message)).is_ok(),
+            Err(e) => {
+                let error = ServerMessage::Error { message: format!("Invalid message: {e}.") };
+                socket.send(Message::Text(to_json(&error))).is_ok()
+            }
+        },
+        // Pings are answered by the WebSocket itself, anything else isn't part of the protocol.
+        Ok(_) => true,
+        Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => true,
+        Err(_) => false,
+    }
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_ws {
+    use crate::chess_pgn::ChessMove;
+    use super::*;
+
+    // Polls the server until it has as many events as expected, or a few seconds have passed.
+    pub fn wait_for_events(server: &mut WsServer, count: usize) -> Vec<WsEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.len() < count && Instant::now() < deadline {
+            events.append(&mut server.poll());
+            thread::sleep(Duration::from_millis(5));
+        }
+        events
+    }
+
+    #[test]
+    pub fn messages_are_read_and_written_as_json() {
+        let message: ClientMessage = serde_json::from_str(r#"{"type": "move", "move": "Nf3"}"#).unwrap();
+        assert_eq!(message, ClientMessage::Move { san: String::from("Nf3") });
+        assert_eq!(serde_json::from_str::<ClientMessage>(r#"{"type": "state"}"#).unwrap(), ClientMessage::State);
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type": "dance"}"#).is_err());
+
+        let mut state = GameState::new();
+        state.play_move(&ChessMove::from("e4").unwrap()).unwrap();
+        let json = to_json(&ServerMessage::State(GameSnapshot::new(&state, None)));
+        assert!(json.starts_with(r#"{"type":"state","fen":"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1","moves":["e4"],"turn":"black","#), "{json}");
+        assert!(json.ends_with(r#""clock":null}"#), "{json}");
+        assert_eq!(to_json(&ServerMessage::Welcome { side: None }), r#"{"type":"welcome","side":null}"#);
+    }
+
+    #[test]
+    pub fn clients_are_served() {
+        let mut server = WsServer::start("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", server.get_address());
+        let (mut client, _) = tungstenite::connect(url).unwrap();
+        assert_eq!(wait_for_events(&mut server, 1), vec![WsEvent::Connected(1)]);
+
+        client.send(Message::Text(String::from(r#"{"type":"move","move":"e4"}"#))).unwrap();
+        client.send(Message::Text(String::from("hello"))).unwrap();
+        assert_eq!(wait_for_events(&mut server, 1), vec![WsEvent::Message(1, ClientMessage::Move { san: String::from("e4") })]);
+        let reply = client.read().unwrap().into_text().unwrap();
+        assert!(reply.starts_with(r#"{"type":"error","message":"Invalid message: "#), "{reply}");
+
+        server.broadcast(&ServerMessage::Welcome { side: Some(side_name(Team::Dark)) });
+        assert_eq!(client.read().unwrap().into_text().unwrap(), r#"{"type":"welcome","side":"black"}"#);
+
+        client.close(None).unwrap();
+        assert_eq!(wait_for_events(&mut server, 1), vec![WsEvent::Disconnected(1)]);
+        assert_eq!(server.get_client_count(), 0);
+    }
+}
@@ -0,0 +1,57 @@
+/*
+lib.rs
+The rules, notation and game record of chess, for the program and for anyone using the crate as a
+library. With the python feature, also a Python module of the same name.
+
+Most programs only need the prelude:
+
+  use rust_chess::prelude::*;
+
+  let mut game = GameState::new();
+  game.play_move(&ChessMove::from("e4")?)?;
+  println!("{}", game.get_board().to_fen());
+*/
+
+#![allow(dead_code)]
+pub mod chess_common;
+pub mod chess_core;
+pub mod chess_pgn;
+pub mod chess_game;
+pub mod chess_clock;
+#[cfg(feature = "serde")]
+mod chess_serde;
+#[cfg(feature = "python")]
+mod chess_python;
+#[cfg(feature = "arbitrary")]
+mod chess_arbitrary;
+
+// The types a program using the crate works with, and the errors it gets back.
+pub mod prelude {
+    pub use crate::chess_common::{ChessCastle, ChessCoordinate, ChessFile, ChessPiece, ChessRank};
+    pub use crate::chess_core::{fen::FenParseError, Board, BoardDifference, BoardMove, DiagramStyle, BoardSquare, MoveResolveError, Piece, Team};
+    pub use crate::chess_game::{GameLoadError, GameMoveError, GameObserver, GameState, GameStatus, ObserverId, VariationError};
+    pub use crate::chess_pgn::{
+        format_variation, normalize_pgn, parse_pgn_games, parse_pgn_games_with, ChessMove, ChessMoveBuildError,
+        PgnAnnotation, PgnDate, PgnGame, PgnParseError, PgnResult, PgnRound, PgnSaveError, PgnTextMode,
+    };
+}
+
+// === UNIT TESTS ===
+
+#[cfg(test)]
+mod test_prelude {
+    use super::prelude::*;
+
+    #[test]
+    pub fn prelude_plays_and_records_a_game() {
+        let mut game = GameState::new();
+        for san in ["f3", "e5", "g4", "Qh4"] {
+            game.play_move(&ChessMove::from(san).unwrap()).unwrap();
+        }
+        assert_eq!(game.get_status(), GameStatus::Checkmate { winner: Team::Dark });
+
+        let games = parse_pgn_games(&game.get_pgn_game().to_string()).unwrap();
+        assert_eq!(GameState::from_pgn_game(games[0].clone()).unwrap().get_board(), game.get_board());
+        assert!(matches!(Board::from_fen("8/8 w"), Err(FenParseError::WrongFieldCount)));
+    }
+}
@@ -1,12 +1,47 @@
 #![allow(dead_code)]
-mod chess_core;
+use rust_chess::{chess_clock, chess_common, chess_core, chess_game, chess_pgn};
+
 mod chess_ui;
-mod chess_pgn;
 mod chess_cmd;
-mod chess_common;
+mod chess_config;
+mod chess_uci;
+mod chess_engine;
+mod chess_annotate;
+mod chess_ws;
+mod chess_http;
+mod chess_import;
+mod chess_correspondence;
+mod chess_rating;
+mod chess_repertoire;
+mod chess_puzzle;
+mod chess_pgn_tool;
+mod chess_batch;
+mod chess_db;
+mod chess_explorer;
+mod chess_report;
+mod chess_tournament;
+mod chess_match;
+mod chess_adjudication;
+mod chess_sprt;
+mod chess_verify;
 
+use clap::Parser;
+use chess_cmd::{ChessArgs, ChessMode};
 use chess_ui::*;
 
 fn main() {
-    ui_main();
+    let args = ChessArgs::parse();
+    match &args.mode {
+        Some(ChessMode::Uci) => chess_uci::server::uci_main(),
+        Some(ChessMode::Correspondence { action }) => chess_correspondence::correspondence_main(&args, action),
+        Some(ChessMode::Pgn { action }) => chess_pgn_tool::pgn_main(action),
+        Some(ChessMode::Db { action }) => chess_db::db_main(action),
+        Some(ChessMode::AnalyzeBatch(batch_args)) => chess_batch::batch_main(&args, batch_args),
+        Some(ChessMode::Report(report_args)) => chess_report::report_main(&args, report_args),
+        Some(ChessMode::Tournament(tournament_args)) => chess_tournament::tournament_main(&args, tournament_args),
+        Some(ChessMode::Match(match_args)) => chess_match::match_main(&args, match_args),
+        Some(ChessMode::Sprt(sprt_args)) => chess_sprt::sprt_main(&args, sprt_args),
+        Some(ChessMode::Verify(verify_args)) => chess_verify::verify_main(verify_args),
+        None => ui_main(&args),
+    }
 }
\ No newline at end of file